@@ -0,0 +1,97 @@
+/*
+ * C API for Fidget
+ *
+ * Objects are opaque pointers, which must be released with the matching
+ * `*_free` function.  Math nodes are integer handles, which remain valid for
+ * the lifetime of their context.
+ *
+ * Every fallible function returns a status code and writes its result through
+ * an output pointer.  On failure, `fidget_last_error()` returns a description
+ * of the error, which is valid until the next failing call on the same thread.
+ *
+ * See `capi/src/lib.rs` for detailed documentation of each function.
+ */
+#ifndef FIDGET_H
+#define FIDGET_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef int32_t fidget_status;
+#define FIDGET_OK 0
+#define FIDGET_NULL_POINTER 1
+#define FIDGET_BAD_HANDLE 2
+#define FIDGET_ERROR 3
+#define FIDGET_PANIC 4
+
+typedef uint32_t fidget_node;
+
+/* Opcodes for fidget_unary */
+#define FIDGET_OP_NEG 0
+#define FIDGET_OP_ABS 1
+#define FIDGET_OP_RECIP 2
+#define FIDGET_OP_SQRT 3
+#define FIDGET_OP_SQUARE 4
+#define FIDGET_OP_SIN 5
+#define FIDGET_OP_COS 6
+
+/* Opcodes for fidget_binary */
+#define FIDGET_OP_ADD 0
+#define FIDGET_OP_SUB 1
+#define FIDGET_OP_MUL 2
+#define FIDGET_OP_DIV 3
+#define FIDGET_OP_MIN 4
+#define FIDGET_OP_MAX 5
+
+typedef struct fidget_context fidget_context;
+typedef struct fidget_tape fidget_tape;
+typedef struct fidget_mesh fidget_mesh;
+
+const char* fidget_last_error(void);
+
+/* Contexts and expressions */
+fidget_context* fidget_context_new(void);
+void fidget_context_free(fidget_context* ctx);
+
+fidget_status fidget_x(fidget_context* ctx, fidget_node* out);
+fidget_status fidget_y(fidget_context* ctx, fidget_node* out);
+fidget_status fidget_z(fidget_context* ctx, fidget_node* out);
+fidget_status fidget_constant(fidget_context* ctx, double value,
+                              fidget_node* out);
+fidget_status fidget_var(fidget_context* ctx, const char* name,
+                         fidget_node* out);
+fidget_status fidget_unary(fidget_context* ctx, uint32_t op, fidget_node a,
+                           fidget_node* out);
+fidget_status fidget_binary(fidget_context* ctx, uint32_t op, fidget_node a,
+                            fidget_node b, fidget_node* out);
+fidget_status fidget_eval_xyz(const fidget_context* ctx, fidget_node node,
+                              double x, double y, double z, double* out);
+
+/* Tapes and evaluation */
+fidget_status fidget_tape_new(const fidget_context* ctx, fidget_node root,
+                              fidget_tape** out);
+void fidget_tape_free(fidget_tape* tape);
+
+fidget_status fidget_eval_points(const fidget_tape* tape, const float* x,
+                                 const float* y, const float* z, size_t n,
+                                 float* out);
+fidget_status fidget_eval_grid(const fidget_tape* tape, const float lower[3],
+                               const float upper[3], const size_t dims[3],
+                               float* out);
+
+/* Meshing */
+fidget_status fidget_mesh_new(const fidget_tape* tape, uint8_t depth,
+                              uint8_t threads, fidget_mesh** out);
+void fidget_mesh_free(fidget_mesh* mesh);
+const float* fidget_mesh_vertices(const fidget_mesh* mesh, size_t* count);
+const uint32_t* fidget_mesh_triangles(const fidget_mesh* mesh, size_t* count);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FIDGET_H */
@@ -0,0 +1,788 @@
+//! C API for embedding Fidget in non-Rust applications
+//!
+//! Objects are exposed as opaque pointers, which must be released with the
+//! matching `*_free` function.  Math nodes are represented as integer handles
+//! ([`fidget_node`]), which remain valid for the lifetime of their context.
+//!
+//! Every fallible function returns a [`fidget_status`] code and writes its
+//! result through an output pointer.  On failure, a human-readable description
+//! is available from [`fidget_last_error`].  Panics are caught at the API
+//! boundary and reported as [`FIDGET_PANIC`].
+//!
+//! The matching C header is in `include/fidget.h`.
+#![allow(non_camel_case_types)]
+
+use fidget::{
+    context::{Context, Node},
+    eval::Tape,
+    mesh::{Octree, Settings},
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, CStr, CString},
+    panic::AssertUnwindSafe,
+};
+
+#[cfg(feature = "jit")]
+type Family = fidget::jit::Eval;
+#[cfg(not(feature = "jit"))]
+type Family = fidget::vm::Eval;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Status code returned by fallible functions
+pub type fidget_status = i32;
+
+/// The function succeeded
+pub const FIDGET_OK: fidget_status = 0;
+/// A required pointer argument was null
+pub const FIDGET_NULL_POINTER: fidget_status = 1;
+/// A node handle or opcode was not valid
+pub const FIDGET_BAD_HANDLE: fidget_status = 2;
+/// An error was returned by Fidget; see [`fidget_last_error`] for details
+pub const FIDGET_ERROR: fidget_status = 3;
+/// A panic occurred (and was caught) inside the library
+pub const FIDGET_PANIC: fidget_status = 4;
+
+/// Handle to a math node in a [`fidget_context`]
+pub type fidget_node = u32;
+
+/// Unary negation, for [`fidget_unary`]
+pub const FIDGET_OP_NEG: u32 = 0;
+/// Absolute value, for [`fidget_unary`]
+pub const FIDGET_OP_ABS: u32 = 1;
+/// Reciprocal (`1 / a`), for [`fidget_unary`]
+pub const FIDGET_OP_RECIP: u32 = 2;
+/// Square root, for [`fidget_unary`]
+pub const FIDGET_OP_SQRT: u32 = 3;
+/// Square (`a * a`), for [`fidget_unary`]
+pub const FIDGET_OP_SQUARE: u32 = 4;
+/// Sine, for [`fidget_unary`]
+pub const FIDGET_OP_SIN: u32 = 5;
+/// Cosine, for [`fidget_unary`]
+pub const FIDGET_OP_COS: u32 = 6;
+
+/// Addition, for [`fidget_binary`]
+pub const FIDGET_OP_ADD: u32 = 0;
+/// Subtraction (`a - b`), for [`fidget_binary`]
+pub const FIDGET_OP_SUB: u32 = 1;
+/// Multiplication, for [`fidget_binary`]
+pub const FIDGET_OP_MUL: u32 = 2;
+/// Division (`a / b`), for [`fidget_binary`]
+pub const FIDGET_OP_DIV: u32 = 3;
+/// Minimum, for [`fidget_binary`]
+pub const FIDGET_OP_MIN: u32 = 4;
+/// Maximum, for [`fidget_binary`]
+pub const FIDGET_OP_MAX: u32 = 5;
+
+/// A math context, which owns every node built within it
+pub struct fidget_context {
+    ctx: Context,
+    nodes: Vec<Node>,
+    handles: HashMap<Node, fidget_node>,
+}
+
+impl fidget_context {
+    /// Returns a stable handle for the given node
+    fn handle(&mut self, n: Node) -> fidget_node {
+        *self.handles.entry(n).or_insert_with(|| {
+            self.nodes.push(n);
+            (self.nodes.len() - 1) as fidget_node
+        })
+    }
+
+    /// Looks up the node for a handle
+    fn node(&self, h: fidget_node) -> Result<Node, Failure> {
+        self.nodes
+            .get(h as usize)
+            .cloned()
+            .ok_or(Failure::BadHandle)
+    }
+}
+
+/// A compiled tape, ready for evaluation or meshing
+pub struct fidget_tape(Tape<Family>);
+
+/// A triangle mesh, with flat vertex and index buffers
+pub struct fidget_mesh {
+    vertices: Vec<f32>,
+    triangles: Vec<u32>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Internal error type, which is converted into a status code
+enum Failure {
+    NullPointer,
+    BadHandle,
+    Fidget(fidget::Error),
+}
+
+impl From<fidget::Error> for Failure {
+    fn from(e: fidget::Error) -> Self {
+        Failure::Fidget(e)
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+}
+
+/// Runs the given function, catching panics and converting errors
+fn run<F: FnOnce() -> Result<(), Failure>>(f: F) -> fidget_status {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => FIDGET_OK,
+        Ok(Err(Failure::NullPointer)) => {
+            set_last_error("null pointer argument".to_owned());
+            FIDGET_NULL_POINTER
+        }
+        Ok(Err(Failure::BadHandle)) => {
+            set_last_error("invalid node handle or opcode".to_owned());
+            FIDGET_BAD_HANDLE
+        }
+        Ok(Err(Failure::Fidget(e))) => {
+            set_last_error(e.to_string());
+            FIDGET_ERROR
+        }
+        Err(e) => {
+            let msg = e
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+            set_last_error(format!("panic: {msg}"));
+            FIDGET_PANIC
+        }
+    }
+}
+
+/// Converts a raw pointer into a reference, checking for null
+unsafe fn get<'a, T>(p: *const T) -> Result<&'a T, Failure> {
+    p.as_ref().ok_or(Failure::NullPointer)
+}
+
+/// Converts a raw pointer into a mutable reference, checking for null
+unsafe fn get_mut<'a, T>(p: *mut T) -> Result<&'a mut T, Failure> {
+    p.as_mut().ok_or(Failure::NullPointer)
+}
+
+/// Converts a raw pointer and length into a slice, checking for null
+unsafe fn slice<'a, T>(p: *const T, n: usize) -> Result<&'a [T], Failure> {
+    if n == 0 {
+        Ok(&[])
+    } else if p.is_null() {
+        Err(Failure::NullPointer)
+    } else {
+        Ok(std::slice::from_raw_parts(p, n))
+    }
+}
+
+/// Converts a raw pointer and length into a mutable slice, checking for null
+unsafe fn slice_mut<'a, T>(
+    p: *mut T,
+    n: usize,
+) -> Result<&'a mut [T], Failure> {
+    if n == 0 {
+        Ok(&mut [])
+    } else if p.is_null() {
+        Err(Failure::NullPointer)
+    } else {
+        Ok(std::slice::from_raw_parts_mut(p, n))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns a description of the most recent error on this thread
+///
+/// The string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn fidget_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// Builds a new, empty context
+#[no_mangle]
+pub extern "C" fn fidget_context_new() -> *mut fidget_context {
+    Box::into_raw(Box::new(fidget_context {
+        ctx: Context::new(),
+        nodes: vec![],
+        handles: HashMap::new(),
+    }))
+}
+
+/// Releases a context built with [`fidget_context_new`]
+///
+/// # Safety
+/// `ctx` must be null or a pointer returned by [`fidget_context_new`] which
+/// has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fidget_context_free(ctx: *mut fidget_context) {
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+/// Shared implementation for the `fidget_x/y/z` functions
+unsafe fn axis(
+    ctx: *mut fidget_context,
+    out: *mut fidget_node,
+    f: fn(&mut Context) -> Node,
+) -> fidget_status {
+    run(|| {
+        let ctx = get_mut(ctx)?;
+        let out = get_mut(out)?;
+        let n = f(&mut ctx.ctx);
+        *out = ctx.handle(n);
+        Ok(())
+    })
+}
+
+/// Writes a handle to the X coordinate into `out`
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_x(
+    ctx: *mut fidget_context,
+    out: *mut fidget_node,
+) -> fidget_status {
+    axis(ctx, out, Context::x)
+}
+
+/// Writes a handle to the Y coordinate into `out`
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_y(
+    ctx: *mut fidget_context,
+    out: *mut fidget_node,
+) -> fidget_status {
+    axis(ctx, out, Context::y)
+}
+
+/// Writes a handle to the Z coordinate into `out`
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_z(
+    ctx: *mut fidget_context,
+    out: *mut fidget_node,
+) -> fidget_status {
+    axis(ctx, out, Context::z)
+}
+
+/// Writes a handle to a constant value into `out`
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_constant(
+    ctx: *mut fidget_context,
+    value: f64,
+    out: *mut fidget_node,
+) -> fidget_status {
+    run(|| {
+        let ctx = get_mut(ctx)?;
+        let out = get_mut(out)?;
+        let n = ctx.ctx.constant(value);
+        *out = ctx.handle(n);
+        Ok(())
+    })
+}
+
+/// Writes a handle to a named variable into `out`
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers, and `name` must be a valid
+/// nul-terminated string (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_var(
+    ctx: *mut fidget_context,
+    name: *const c_char,
+    out: *mut fidget_node,
+) -> fidget_status {
+    run(|| {
+        let ctx = get_mut(ctx)?;
+        let out = get_mut(out)?;
+        if name.is_null() {
+            return Err(Failure::NullPointer);
+        }
+        let name = CStr::from_ptr(name).to_string_lossy();
+        let n = ctx.ctx.var(&name)?;
+        *out = ctx.handle(n);
+        Ok(())
+    })
+}
+
+/// Builds a unary operation (one of the `FIDGET_OP_*` unary opcodes)
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_unary(
+    ctx: *mut fidget_context,
+    op: u32,
+    a: fidget_node,
+    out: *mut fidget_node,
+) -> fidget_status {
+    run(|| {
+        let ctx = get_mut(ctx)?;
+        let out = get_mut(out)?;
+        let a = ctx.node(a)?;
+        let c = &mut ctx.ctx;
+        let n = match op {
+            FIDGET_OP_NEG => c.neg(a),
+            FIDGET_OP_ABS => c.abs(a),
+            FIDGET_OP_RECIP => c.recip(a),
+            FIDGET_OP_SQRT => c.sqrt(a),
+            FIDGET_OP_SQUARE => c.square(a),
+            FIDGET_OP_SIN => c.sin(a),
+            FIDGET_OP_COS => c.cos(a),
+            _ => return Err(Failure::BadHandle),
+        }?;
+        *out = ctx.handle(n);
+        Ok(())
+    })
+}
+
+/// Builds a binary operation (one of the `FIDGET_OP_*` binary opcodes)
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_binary(
+    ctx: *mut fidget_context,
+    op: u32,
+    a: fidget_node,
+    b: fidget_node,
+    out: *mut fidget_node,
+) -> fidget_status {
+    run(|| {
+        let ctx = get_mut(ctx)?;
+        let out = get_mut(out)?;
+        let a = ctx.node(a)?;
+        let b = ctx.node(b)?;
+        let c = &mut ctx.ctx;
+        let n = match op {
+            FIDGET_OP_ADD => c.add(a, b),
+            FIDGET_OP_SUB => c.sub(a, b),
+            FIDGET_OP_MUL => c.mul(a, b),
+            FIDGET_OP_DIV => c.div(a, b),
+            FIDGET_OP_MIN => c.min(a, b),
+            FIDGET_OP_MAX => c.max(a, b),
+            _ => return Err(Failure::BadHandle),
+        }?;
+        *out = ctx.handle(n);
+        Ok(())
+    })
+}
+
+/// Evaluates a node at a single point, writing the result into `out`
+///
+/// This is slow, and intended for debugging; use a tape for bulk evaluation.
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_eval_xyz(
+    ctx: *const fidget_context,
+    node: fidget_node,
+    x: f64,
+    y: f64,
+    z: f64,
+    out: *mut f64,
+) -> fidget_status {
+    run(|| {
+        let ctx = get(ctx)?;
+        let out = get_mut(out)?;
+        *out = ctx.ctx.eval_xyz(ctx.node(node)?, x, y, z)?;
+        Ok(())
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Compiles the given node into a tape, writing a pointer into `out`
+///
+/// The tape is independent of the context, which may be freed afterwards.
+///
+/// # Safety
+/// `ctx` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_tape_new(
+    ctx: *const fidget_context,
+    root: fidget_node,
+    out: *mut *mut fidget_tape,
+) -> fidget_status {
+    run(|| {
+        let ctx = get(ctx)?;
+        let out = get_mut(out)?;
+        let tape = ctx.ctx.get_tape(ctx.node(root)?)?;
+        *out = Box::into_raw(Box::new(fidget_tape(tape)));
+        Ok(())
+    })
+}
+
+/// Releases a tape built with [`fidget_tape_new`]
+///
+/// # Safety
+/// `tape` must be null or a pointer returned by [`fidget_tape_new`] which has
+/// not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fidget_tape_free(tape: *mut fidget_tape) {
+    if !tape.is_null() {
+        drop(Box::from_raw(tape));
+    }
+}
+
+/// Evaluates a tape at `n` points, writing `n` values into `out`
+///
+/// The tape must not use any variables.
+///
+/// # Safety
+/// `x`, `y`, `z`, and `out` must each point to at least `n` values, and
+/// `tape` must be a valid tape.
+#[no_mangle]
+pub unsafe extern "C" fn fidget_eval_points(
+    tape: *const fidget_tape,
+    x: *const f32,
+    y: *const f32,
+    z: *const f32,
+    n: usize,
+    out: *mut f32,
+) -> fidget_status {
+    run(|| {
+        let tape = get(tape)?;
+        let (x, y, z) = (slice(x, n)?, slice(y, n)?, slice(z, n)?);
+        let out = slice_mut(out, n)?;
+        let eval = tape.0.new_float_slice_evaluator();
+        eval.eval_into(x, y, z, &[], out, &mut Default::default())?;
+        Ok(())
+    })
+}
+
+/// Evaluates a tape on a regular 3D grid, writing values into `out`
+///
+/// The grid has `dims[0] × dims[1] × dims[2]` samples, spanning from `lower`
+/// to `upper` (inclusive) on each axis; `out` is filled with X varying
+/// fastest, then Y, then Z.  An axis with a single sample is evaluated at
+/// its `lower` coordinate.
+///
+/// # Safety
+/// `lower`, `upper`, and `dims` must each point to 3 values, `out` must have
+/// room for every sample, and `tape` must be a valid tape.
+#[no_mangle]
+pub unsafe extern "C" fn fidget_eval_grid(
+    tape: *const fidget_tape,
+    lower: *const f32,
+    upper: *const f32,
+    dims: *const usize,
+    out: *mut f32,
+) -> fidget_status {
+    run(|| {
+        let tape = get(tape)?;
+        let lower = slice(lower, 3)?;
+        let upper = slice(upper, 3)?;
+        let dims = slice(dims, 3)?;
+        let n = dims.iter().product();
+        let out = slice_mut(out, n)?;
+        if n == 0 {
+            return Ok(());
+        }
+
+        let pos = |axis: usize, i: usize| {
+            let d = dims[axis];
+            if d <= 1 {
+                lower[axis]
+            } else {
+                let t = i as f32 / (d - 1) as f32;
+                lower[axis] * (1.0 - t) + upper[axis] * t
+            }
+        };
+        let x: Vec<f32> = (0..dims[0]).map(|i| pos(0, i)).collect();
+        let mut y = vec![0.0; dims[0]];
+        let mut z = vec![0.0; dims[0]];
+
+        let eval = tape.0.new_float_slice_evaluator();
+        let mut data = Default::default();
+        for (row, out) in out.chunks_mut(dims[0]).enumerate() {
+            y.fill(pos(1, row % dims[1]));
+            z.fill(pos(2, row / dims[1]));
+            eval.eval_into(&x, &y, &z, &[], out, &mut data)?;
+        }
+        Ok(())
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Builds a mesh of the tape's surface within the `[-1, 1]` cube
+///
+/// `depth` is the octree depth (e.g. 6 for a `64³` grid).  If `threads` is
+/// zero, meshing is single-threaded.
+///
+/// # Safety
+/// `tape` and `out` must be valid pointers (or null, which returns an error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_mesh_new(
+    tape: *const fidget_tape,
+    depth: u8,
+    threads: u8,
+    out: *mut *mut fidget_mesh,
+) -> fidget_status {
+    run(|| {
+        let tape = get(tape)?;
+        let out = get_mut(out)?;
+        let settings = Settings {
+            threads,
+            min_depth: depth,
+            max_depth: depth,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let mesh = Octree::build(&tape.0, settings).walk_dual(settings);
+        let mesh = fidget_mesh {
+            vertices: mesh
+                .vertices
+                .iter()
+                .flat_map(|v| [v.x, v.y, v.z])
+                .collect(),
+            triangles: mesh
+                .triangles
+                .iter()
+                .flat_map(|t| [t.x as u32, t.y as u32, t.z as u32])
+                .collect(),
+        };
+        *out = Box::into_raw(Box::new(mesh));
+        Ok(())
+    })
+}
+
+/// Releases a mesh built with [`fidget_mesh_new`]
+///
+/// # Safety
+/// `mesh` must be null or a pointer returned by [`fidget_mesh_new`] which has
+/// not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fidget_mesh_free(mesh: *mut fidget_mesh) {
+    if !mesh.is_null() {
+        drop(Box::from_raw(mesh));
+    }
+}
+
+/// Returns a pointer to the mesh's vertices, as packed `[x, y, z]` triples
+///
+/// The number of vertices is written into `count`; the pointer is valid
+/// until the mesh is freed.  If either argument is null, this returns a null
+/// pointer (writing 0 into `count` if it's valid).
+///
+/// # Safety
+/// `mesh` and `count` must be valid pointers (or null, which returns an
+/// error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_mesh_vertices(
+    mesh: *const fidget_mesh,
+    count: *mut usize,
+) -> *const f32 {
+    mesh_data(mesh, count, |m| &m.vertices)
+}
+
+/// Returns a pointer to the mesh's triangles, as packed vertex index triples
+///
+/// The number of triangles is written into `count`; the pointer is valid
+/// until the mesh is freed.  If either argument is null, this returns a null
+/// pointer (writing 0 into `count` if it's valid).
+///
+/// # Safety
+/// `mesh` and `count` must be valid pointers (or null, which returns an
+/// error).
+#[no_mangle]
+pub unsafe extern "C" fn fidget_mesh_triangles(
+    mesh: *const fidget_mesh,
+    count: *mut usize,
+) -> *const u32 {
+    mesh_data(mesh, count, |m| &m.triangles)
+}
+
+/// Returns one of the mesh's packed arrays, writing its number of triples
+/// into `count`
+///
+/// Null arguments are reported through [`fidget_last_error`], since the
+/// result is a pointer rather than a status code.
+unsafe fn mesh_data<T>(
+    mesh: *const fidget_mesh,
+    count: *mut usize,
+    f: fn(&fidget_mesh) -> &[T],
+) -> *const T {
+    match (get(mesh), get_mut(count)) {
+        (Ok(mesh), Ok(count)) => {
+            let data = f(mesh);
+            *count = data.len() / 3;
+            data.as_ptr()
+        }
+        (_, count) => {
+            if let Ok(count) = count {
+                *count = 0;
+            }
+            set_last_error("null pointer argument".to_owned());
+            std::ptr::null()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds a sphere of radius 0.5, returning its handle
+    unsafe fn sphere(ctx: *mut fidget_context) -> fidget_node {
+        let (mut x, mut y, mut z) = (0, 0, 0);
+        assert_eq!(fidget_x(ctx, &mut x), FIDGET_OK);
+        assert_eq!(fidget_y(ctx, &mut y), FIDGET_OK);
+        assert_eq!(fidget_z(ctx, &mut z), FIDGET_OK);
+        let mut out = 0;
+        let mut sum = 0;
+        for a in [x, y, z] {
+            assert_eq!(fidget_unary(ctx, FIDGET_OP_SQUARE, a, &mut out), 0);
+            if a == x {
+                sum = out;
+            } else {
+                assert_eq!(
+                    fidget_binary(ctx, FIDGET_OP_ADD, sum, out, &mut sum),
+                    0
+                );
+            }
+        }
+        assert_eq!(fidget_unary(ctx, FIDGET_OP_SQRT, sum, &mut out), 0);
+        let mut r = 0;
+        assert_eq!(fidget_constant(ctx, 0.5, &mut r), FIDGET_OK);
+        let mut s = 0;
+        assert_eq!(fidget_binary(ctx, FIDGET_OP_SUB, out, r, &mut s), 0);
+        s
+    }
+
+    #[test]
+    fn test_build_and_eval() {
+        unsafe {
+            let ctx = fidget_context_new();
+            let s = sphere(ctx);
+            let mut v = 0.0;
+            assert_eq!(fidget_eval_xyz(ctx, s, 1.0, 0.0, 0.0, &mut v), 0);
+            assert_eq!(v, 0.5);
+
+            // Handles are stable and deduplicated
+            let mut x1 = 0;
+            let mut x2 = 0;
+            fidget_x(ctx, &mut x1);
+            fidget_x(ctx, &mut x2);
+            assert_eq!(x1, x2);
+
+            let mut tape = std::ptr::null_mut();
+            assert_eq!(fidget_tape_new(ctx, s, &mut tape), FIDGET_OK);
+            fidget_context_free(ctx);
+
+            let xs = [0.0, 1.0, 0.25];
+            let zeros = [0.0; 3];
+            let mut out = [0.0f32; 3];
+            let r = fidget_eval_points(
+                tape,
+                xs.as_ptr(),
+                zeros.as_ptr(),
+                zeros.as_ptr(),
+                3,
+                out.as_mut_ptr(),
+            );
+            assert_eq!(r, FIDGET_OK);
+            assert_eq!(out, [-0.5, 0.5, -0.25]);
+
+            let mut grid = [0.0f32; 3 * 3 * 3];
+            let r = fidget_eval_grid(
+                tape,
+                [-1.0; 3].as_ptr(),
+                [1.0; 3].as_ptr(),
+                [3usize; 3].as_ptr(),
+                grid.as_mut_ptr(),
+            );
+            assert_eq!(r, FIDGET_OK);
+            assert_eq!(grid[13], -0.5); // center
+            assert_eq!(grid[14], 0.5); // +X
+            assert_eq!(grid[16], 0.5); // +Y
+            assert_eq!(grid[22], 0.5); // +Z
+            fidget_tape_free(tape);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let ctx = fidget_context_new();
+            let mut out = 0;
+            assert_eq!(
+                fidget_unary(ctx, FIDGET_OP_NEG, 100, &mut out),
+                FIDGET_BAD_HANDLE
+            );
+            let mut x = 0;
+            fidget_x(ctx, &mut x);
+            assert_eq!(fidget_unary(ctx, 100, x, &mut out), FIDGET_BAD_HANDLE);
+            assert_eq!(
+                fidget_x(ctx, std::ptr::null_mut()),
+                FIDGET_NULL_POINTER
+            );
+
+            let mut count = 1;
+            let p = fidget_mesh_vertices(std::ptr::null(), &mut count);
+            assert!(p.is_null());
+            assert_eq!(count, 0);
+            let p = fidget_mesh_triangles(std::ptr::null(), &mut count);
+            assert!(p.is_null());
+            assert_eq!(count, 0);
+            let msg = CStr::from_ptr(fidget_last_error()).to_str().unwrap();
+            assert_eq!(msg, "null pointer argument");
+            assert_eq!(fidget_var(ctx, c"X".as_ptr(), &mut out), FIDGET_ERROR);
+            let msg = CStr::from_ptr(fidget_last_error()).to_str().unwrap();
+            assert_eq!(msg, "this name is reserved for 3D coordinates");
+            fidget_context_free(ctx);
+        }
+    }
+
+    #[test]
+    fn test_mesh() {
+        unsafe {
+            let ctx = fidget_context_new();
+            let s = sphere(ctx);
+            let mut tape = std::ptr::null_mut();
+            assert_eq!(fidget_tape_new(ctx, s, &mut tape), FIDGET_OK);
+            let mut mesh = std::ptr::null_mut();
+            assert_eq!(fidget_mesh_new(tape, 4, 0, &mut mesh), FIDGET_OK);
+
+            let mut nv = 0;
+            let mut nt = 0;
+            let verts = fidget_mesh_vertices(mesh, &mut nv);
+            let tris = fidget_mesh_triangles(mesh, &mut nt);
+            assert!(nv > 0 && nt > 0);
+            assert!(fidget_mesh_vertices(mesh, std::ptr::null_mut()).is_null());
+            let verts = std::slice::from_raw_parts(verts, nv * 3);
+            let tris = std::slice::from_raw_parts(tris, nt * 3);
+            assert!(tris.iter().all(|&i| (i as usize) < nv));
+            for v in verts.chunks_exact(3) {
+                let r = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+                assert!((r - 0.5).abs() < 0.05, "bad radius {r}");
+            }
+
+            fidget_mesh_free(mesh);
+            fidget_tape_free(tape);
+            fidget_context_free(ctx);
+        }
+    }
+}
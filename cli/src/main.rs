@@ -0,0 +1,406 @@
+//! Command-line tool for batch operations with Fidget
+//!
+//! Each subcommand loads an expression file (either a `.frep` file, or the
+//! format read by [`Context::from_text`]) and passes it to one of Fidget's
+//! public APIs, so that the kernel can be scripted without writing Rust.
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use env_logger::Env;
+use log::info;
+
+use fidget::{
+    context::{frep::Frep, BoundingBox, Context, Node},
+    render::{
+        render2d, BitRenderMode, DebugRenderMode, RenderConfig, SdfRenderMode,
+    },
+};
+
+/// Batch operations on Fidget expressions
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(subcommand)]
+    cmd: Command,
+
+    /// Input expression file
+    #[clap(short, long)]
+    input: PathBuf,
+
+    /// Name of the root to use from a `.frep` input file
+    ///
+    /// By default, the file's first root is used.
+    #[clap(long)]
+    root: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Renders the expression at Z = 0 to a `.png` file
+    Render {
+        /// Name of a `.png` file to write
+        #[clap(short, long)]
+        out: PathBuf,
+
+        /// Evaluator flavor
+        #[clap(short, long, value_enum, default_value_t = EvalMode::Vm)]
+        eval: EvalMode,
+
+        /// Number of threads to use
+        #[clap(short, long, default_value_t = 8)]
+        threads: usize,
+
+        /// Image size
+        #[clap(short, long, default_value_t = 512)]
+        size: u32,
+
+        /// Render mode
+        #[clap(short, long, value_enum, default_value_t = ImageMode::Bit)]
+        mode: ImageMode,
+    },
+
+    /// Builds a mesh of the expression and writes it to a `.stl` file
+    Mesh {
+        /// Name of a `.stl` file to write
+        #[clap(short, long)]
+        out: PathBuf,
+
+        /// Evaluator flavor
+        #[clap(short, long, value_enum, default_value_t = EvalMode::Vm)]
+        eval: EvalMode,
+
+        /// Number of threads to use
+        #[clap(short, long, default_value_t = 8)]
+        threads: u8,
+
+        /// Minimum octree depth
+        #[clap(short, long, default_value_t = 6)]
+        depth: u8,
+
+        /// Maximum octree depth
+        #[clap(long)]
+        max_depth: Option<u8>,
+
+        /// Use Marching Cubes instead of Manifold Dual Contouring
+        #[clap(long)]
+        marching_cubes: bool,
+
+        /// Refine edge intersections with Newton's method
+        #[clap(long)]
+        refine_edges: bool,
+    },
+
+    /// Prints statistics about the expression and its tape
+    Stats {
+        /// Evaluator flavor, which determines the tape's register limit
+        #[clap(short, long, value_enum, default_value_t = EvalMode::Vm)]
+        eval: EvalMode,
+    },
+
+    /// Checks whether the expression is a distance field
+    ///
+    /// Exits with an error if any sample is out of tolerance.
+    Validate {
+        /// Number of samples along each axis
+        #[clap(short, long, default_value_t = 32)]
+        resolution: usize,
+
+        /// Expected gradient magnitude
+        #[clap(long, default_value_t = 1.0)]
+        scale: f32,
+
+        /// Allowed relative deviation from the expected magnitude
+        #[clap(short, long, default_value_t = 0.05)]
+        tolerance: f32,
+
+        /// Only check samples within this distance of the surface
+        #[clap(short, long)]
+        band: Option<f32>,
+
+        /// Half-width of the (origin-centered) region to check
+        #[clap(long, default_value_t = 1.0)]
+        size: f64,
+
+        /// Only check the Z = 0 plane
+        #[clap(long)]
+        flat: bool,
+    },
+
+    /// Writes the expression to a `.frep` file
+    Export {
+        /// Name of a `.frep` file to write
+        #[clap(short, long)]
+        out: PathBuf,
+
+        /// Name of the root in the output file
+        #[clap(short, long, default_value = "shape")]
+        name: String,
+    },
+}
+
+#[derive(ValueEnum, Clone)]
+enum EvalMode {
+    Vm,
+    Affine,
+
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+#[derive(ValueEnum, Clone)]
+enum ImageMode {
+    /// Filled regions are white, empty regions are black
+    Bit,
+    /// Color-gradient SDF
+    Sdf,
+    /// Colors pixels by how they were evaluated
+    Debug,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn run_render<I: fidget::eval::Family>(
+    ctx: &Context,
+    node: Node,
+    threads: usize,
+    size: u32,
+    mode: &ImageMode,
+) -> Result<Vec<u8>> {
+    let tape = ctx.get_tape::<I>(node)?;
+    let cfg = RenderConfig {
+        image_size: size as usize,
+        tile_sizes: I::tile_sizes_2d().to_vec(),
+        threads,
+        ..Default::default()
+    };
+    let out = match mode {
+        ImageMode::Bit => render2d(tape, &cfg, &BitRenderMode)
+            .into_iter()
+            .flat_map(|b| if b { [u8::MAX; 4] } else { [0, 0, 0, 255] })
+            .collect(),
+        ImageMode::Sdf => render2d(tape, &cfg, &SdfRenderMode)
+            .into_iter()
+            .flat_map(|a| [a[0], a[1], a[2], 255])
+            .collect(),
+        ImageMode::Debug => render2d(tape, &cfg, &DebugRenderMode)
+            .into_iter()
+            .flat_map(|p| p.as_debug_color())
+            .collect(),
+    };
+    Ok(out)
+}
+
+fn run_mesh<I: fidget::eval::Family>(
+    ctx: &Context,
+    node: Node,
+    settings: fidget::mesh::Settings,
+    marching_cubes: bool,
+) -> Result<fidget::mesh::Mesh> {
+    let tape = ctx.get_tape::<I>(node)?;
+    let mesh = if marching_cubes {
+        fidget::mesh::marching_cubes(&tape, settings)
+    } else {
+        fidget::mesh::Octree::build(&tape, settings).walk_dual(settings)
+    };
+    Ok(mesh)
+}
+
+fn run_stats<I: fidget::eval::Family>(ctx: &Context, node: Node) -> Result<()> {
+    let tape = ctx.get_tape::<I>(node)?;
+    let vars: Vec<_> = tape.vars().keys().cloned().collect();
+    println!("nodes:     {}", ctx.len());
+    println!("tape ops:  {}", tape.len());
+    println!(
+        "slots:     {} ({} registers)",
+        tape.slot_count(),
+        tape.reg_limit()
+    );
+    println!("choices:   {}", tape.choice_count());
+    if vars.is_empty() {
+        println!("variables: none");
+    } else {
+        println!("variables: {}", vars.join(", "));
+    }
+    let bounds = tape.bounds();
+    if bounds.is_finite() {
+        println!("bounds:    {:?} to {:?}", bounds.lower, bounds.upper);
+    } else {
+        println!("bounds:    unbounded");
+    }
+    Ok(())
+}
+
+/// Loads an expression from a `.frep` or text file
+fn load(args: &Args) -> Result<(Context, Node)> {
+    let mut file = std::io::BufReader::new(std::fs::File::open(&args.input)?);
+    if args.input.extension().is_some_and(|e| e == "frep") {
+        let (ctx, frep) = Context::read_frep(&mut file)?;
+        let root = match &args.root {
+            Some(name) => frep.root(name),
+            None => frep.roots.first().map(|(_, n)| *n),
+        };
+        let Some(root) = root else {
+            bail!("root not found in {:?}", args.input);
+        };
+        Ok((ctx, root))
+    } else {
+        if args.root.is_some() {
+            bail!("--root is only valid for .frep files");
+        }
+        Ok(Context::from_text(&mut file)?)
+    }
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info"))
+        .init();
+
+    let now = Instant::now();
+    let args = Args::parse();
+    let (ctx, root) = load(&args)?;
+    info!("Loaded file in {:?}", now.elapsed());
+
+    match args.cmd {
+        Command::Render {
+            out,
+            eval,
+            threads,
+            size,
+            mode,
+        } => {
+            let start = Instant::now();
+            let buffer = match eval {
+                #[cfg(feature = "jit")]
+                EvalMode::Jit => run_render::<fidget::jit::Eval>(
+                    &ctx, root, threads, size, &mode,
+                )?,
+                EvalMode::Vm => run_render::<fidget::vm::Eval>(
+                    &ctx, root, threads, size, &mode,
+                )?,
+                EvalMode::Affine => run_render::<fidget::vm::Affine>(
+                    &ctx, root, threads, size, &mode,
+                )?,
+            };
+            info!("Rendered in {:?}", start.elapsed());
+            info!("Writing image to {out:?}");
+            image::save_buffer(
+                out,
+                &buffer,
+                size,
+                size,
+                image::ColorType::Rgba8,
+            )?;
+        }
+        Command::Mesh {
+            out,
+            eval,
+            threads,
+            depth,
+            max_depth,
+            marching_cubes,
+            refine_edges,
+        } => {
+            let settings = fidget::mesh::Settings {
+                threads,
+                min_depth: depth,
+                max_depth: max_depth.unwrap_or(depth),
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: refine_edges.then(Default::default),
+            };
+            let start = Instant::now();
+            let mesh = match eval {
+                #[cfg(feature = "jit")]
+                EvalMode::Jit => run_mesh::<fidget::jit::Eval>(
+                    &ctx,
+                    root,
+                    settings,
+                    marching_cubes,
+                )?,
+                EvalMode::Vm => run_mesh::<fidget::vm::Eval>(
+                    &ctx,
+                    root,
+                    settings,
+                    marching_cubes,
+                )?,
+                EvalMode::Affine => run_mesh::<fidget::vm::Affine>(
+                    &ctx,
+                    root,
+                    settings,
+                    marching_cubes,
+                )?,
+            };
+            info!(
+                "Meshed {} triangles in {:?}",
+                mesh.triangles.len(),
+                start.elapsed()
+            );
+            info!("Writing STL to {out:?}");
+            mesh.write_stl(&mut std::fs::File::create(out)?)?;
+        }
+        Command::Stats { eval } => match eval {
+            #[cfg(feature = "jit")]
+            EvalMode::Jit => run_stats::<fidget::jit::Eval>(&ctx, root)?,
+            EvalMode::Vm => run_stats::<fidget::vm::Eval>(&ctx, root)?,
+            EvalMode::Affine => run_stats::<fidget::vm::Affine>(&ctx, root)?,
+        },
+        Command::Validate {
+            resolution,
+            scale,
+            tolerance,
+            band,
+            size,
+            flat,
+        } => {
+            let z = if flat { 0.0 } else { size };
+            let region = BoundingBox::new([-size, -size, -z], [size, size, z]);
+            let settings = fidget::validate::Settings {
+                resolution,
+                scale,
+                tolerance,
+                band,
+                ..Default::default()
+            };
+            let report = fidget::validate::check_distance_field::<
+                fidget::vm::Eval,
+            >(&ctx, root, region, &settings)?;
+            println!("samples:     {}", report.samples);
+            println!(
+                "gradient:    {} to {} (mean {})",
+                report.min_norm, report.max_norm, report.mean_norm
+            );
+            println!("too steep:   {}", report.too_steep);
+            println!("too shallow: {}", report.too_shallow);
+            println!("non-finite:  {}", report.non_finite);
+            for o in &report.worst {
+                println!(
+                    "  |∇f| = {} at {:?}{}",
+                    o.norm,
+                    o.pos,
+                    o.name
+                        .as_ref()
+                        .map(|n| format!(" in {n:?}"))
+                        .unwrap_or_default()
+                );
+            }
+            if !report.is_valid() {
+                bail!("not a distance field");
+            }
+        }
+        Command::Export { out, name } => {
+            let bounds = ctx.bounds(root)?;
+            let frep = Frep {
+                roots: vec![(name, root)],
+                bounds: bounds.is_finite().then_some(bounds),
+            };
+            info!("Writing .frep to {out:?}");
+            let mut w = std::io::BufWriter::new(std::fs::File::create(out)?);
+            ctx.write_frep(&frep, &mut w)?;
+        }
+    }
+
+    Ok(())
+}
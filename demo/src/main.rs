@@ -56,6 +56,7 @@ enum Command {
 #[derive(ValueEnum, Clone)]
 enum EvalMode {
     Vm,
+    Affine,
 
     #[cfg(feature = "jit")]
     Jit,
@@ -106,6 +107,10 @@ struct MeshSettings {
     #[clap(short, long, default_value_t = 8)]
     threads: u8,
 
+    /// Use Marching Cubes instead of Manifold Dual Contouring
+    #[clap(long)]
+    marching_cubes: bool,
+
     /// Number of times to render (for benchmarking)
     #[clap(short = 'N', default_value_t = 1)]
     n: usize,
@@ -133,6 +138,7 @@ fn run3d<I: fidget::eval::Family>(
         threads: settings.threads,
 
         mat,
+        exact_boundaries: false,
     };
 
     let start = Instant::now();
@@ -219,6 +225,7 @@ fn run2d<I: fidget::eval::Family>(
             threads: settings.threads,
 
             mat: nalgebra::Transform2::identity(),
+            exact_boundaries: false,
         };
         let start = Instant::now();
         let out = if sdf {
@@ -266,14 +273,23 @@ fn run_mesh<I: fidget::eval::Family>(
     let start = Instant::now();
     let mut mesh = fidget::mesh::Mesh::new();
 
+    let marching_cubes = settings.marching_cubes;
     for _ in 0..settings.n {
         let settings = fidget::mesh::Settings {
             threads: settings.threads,
             min_depth: settings.depth,
             max_depth: settings.max_depth.unwrap_or(settings.depth),
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        mesh = if marching_cubes {
+            fidget::mesh::marching_cubes(&tape, settings)
+        } else {
+            let octree = fidget::mesh::Octree::build(&tape, settings);
+            octree.walk_dual(settings)
         };
-        let octree = fidget::mesh::Octree::build(&tape, settings);
-        mesh = octree.walk_dual(settings);
     }
     (mesh, start)
 }
@@ -302,6 +318,9 @@ fn main() -> Result<()> {
                 EvalMode::Vm => {
                     run2d::<fidget::vm::Eval>(&ctx, root, &settings, brute, sdf)
                 }
+                EvalMode::Affine => run2d::<fidget::vm::Affine>(
+                    &ctx, root, &settings, brute, sdf,
+                ),
             };
 
             info!(
@@ -334,6 +353,9 @@ fn main() -> Result<()> {
                 EvalMode::Vm => run3d::<fidget::vm::Eval>(
                     &ctx, root, &settings, isometric, color,
                 ),
+                EvalMode::Affine => run3d::<fidget::vm::Affine>(
+                    &ctx, root, &settings, isometric, color,
+                ),
             };
             info!(
                 "Rendered {}x at {:?} ms/frame",
@@ -363,6 +385,9 @@ fn main() -> Result<()> {
                 EvalMode::Vm => {
                     run_mesh::<fidget::vm::Eval>(&ctx, root, &settings)
                 }
+                EvalMode::Affine => {
+                    run_mesh::<fidget::vm::Affine>(&ctx, root, &settings)
+                }
             };
             info!(
                 "Rendered {}x at {:?} ms/iter",
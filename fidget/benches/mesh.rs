@@ -16,6 +16,10 @@ pub fn colonnade_octree_thread_sweep(c: &mut Criterion) {
         let cfg = &fidget::mesh::Settings {
             min_depth: 6,
             max_depth: 6,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
             threads,
         };
         #[cfg(feature = "jit")]
@@ -41,6 +45,10 @@ pub fn colonnade_mesh(c: &mut Criterion) {
         min_depth: 8,
         max_depth: 8,
         threads: 8,
+        clip: None,
+        iso_band: None,
+        exact_boundaries: false,
+        edge_refinement: None,
     };
     let octree = &fidget::mesh::Octree::build(tape_vm, cfg);
 
@@ -9,6 +9,7 @@ use fidget::eval::Family;
 pub fn prospero_size_sweep(c: &mut Criterion) {
     let (ctx, root) = fidget::Context::from_text(PROSPERO.as_bytes()).unwrap();
     let tape_vm = &ctx.get_tape::<fidget::vm::Eval>(root).unwrap();
+    let tape_threaded = &ctx.get_tape::<fidget::vm::Threaded>(root).unwrap();
 
     #[cfg(feature = "jit")]
     let tape_jit = &ctx.get_tape::<fidget::jit::Eval>(root).unwrap();
@@ -21,6 +22,7 @@ pub fn prospero_size_sweep(c: &mut Criterion) {
             tile_sizes: fidget::vm::Eval::tile_sizes_2d().to_vec(),
             threads: 8,
             mat: nalgebra::Transform2::identity(),
+            exact_boundaries: false,
         };
         group.bench_function(BenchmarkId::new("vm", size), move |b| {
             b.iter(|| {
@@ -32,6 +34,16 @@ pub fn prospero_size_sweep(c: &mut Criterion) {
                 ))
             })
         });
+        group.bench_function(BenchmarkId::new("threaded", size), move |b| {
+            b.iter(|| {
+                let tape = tape_threaded.clone();
+                black_box(fidget::render::render2d(
+                    tape,
+                    cfg,
+                    &fidget::render::BitRenderMode,
+                ))
+            })
+        });
 
         #[cfg(feature = "jit")]
         {
@@ -40,6 +52,7 @@ pub fn prospero_size_sweep(c: &mut Criterion) {
                 tile_sizes: fidget::jit::Eval::tile_sizes_2d().to_vec(),
                 threads: 8,
                 mat: nalgebra::Transform2::identity(),
+                exact_boundaries: false,
             };
             group.bench_function(BenchmarkId::new("jit", size), move |b| {
                 b.iter(|| {
@@ -70,6 +83,7 @@ pub fn prospero_thread_sweep(c: &mut Criterion) {
             tile_sizes: fidget::vm::Eval::tile_sizes_2d().to_vec(),
             threads,
             mat: nalgebra::Transform2::identity(),
+            exact_boundaries: false,
         };
         group.bench_function(BenchmarkId::new("vm", threads), move |b| {
             b.iter(|| {
@@ -88,6 +102,7 @@ pub fn prospero_thread_sweep(c: &mut Criterion) {
                 tile_sizes: fidget::jit::Eval::tile_sizes_2d().to_vec(),
                 threads,
                 mat: nalgebra::Transform2::identity(),
+                exact_boundaries: false,
             };
             group.bench_function(BenchmarkId::new("jit", threads), move |b| {
                 b.iter(|| {
@@ -103,5 +118,101 @@ pub fn prospero_thread_sweep(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, prospero_size_sweep, prospero_thread_sweep);
+pub fn prospero_scheduling(c: &mut Criterion) {
+    #[cfg(feature = "jit")]
+    {
+        let (ctx, root) =
+            fidget::Context::from_text(PROSPERO.as_bytes()).unwrap();
+        let tape = ctx.get_tape::<fidget::jit::Eval>(root).unwrap();
+
+        let mut group = c.benchmark_group(
+            "speed vs scheduling (prospero, 2d) (jit) (1024 x 1024)",
+        );
+        let cfg = &fidget::render::RenderConfig {
+            image_size: 1024,
+            tile_sizes: fidget::jit::Eval::tile_sizes_2d().to_vec(),
+            threads: 8,
+            mat: nalgebra::Transform2::identity(),
+            exact_boundaries: false,
+        };
+        for enable in [false, true] {
+            let tape = &tape.clone().with_scheduling(enable);
+            let name = if enable { "scheduled" } else { "unscheduled" };
+            group.bench_function(name, move |b| {
+                b.iter(|| {
+                    let tape = tape.clone();
+                    black_box(fidget::render::render2d(
+                        tape,
+                        cfg,
+                        &fidget::render::BitRenderMode,
+                    ))
+                })
+            });
+        }
+    }
+}
+
+pub fn random_exprs(c: &mut Criterion) {
+    let settings = fidget::gen::Settings {
+        depth: 12,
+        ..Default::default()
+    };
+    let mut group =
+        c.benchmark_group("speed vs expression (random, 2d) (512 x 512)");
+    for seed in 0..4 {
+        let mut ctx = fidget::Context::new();
+        let mut rng = fidget::gen::Rng::new(seed);
+        let root =
+            fidget::gen::random_expr(&mut ctx, &mut rng, &settings).unwrap();
+
+        let tape_vm = &ctx.get_tape::<fidget::vm::Eval>(root).unwrap();
+        let cfg = &fidget::render::RenderConfig {
+            image_size: 512,
+            tile_sizes: fidget::vm::Eval::tile_sizes_2d().to_vec(),
+            threads: 8,
+            mat: nalgebra::Transform2::identity(),
+            exact_boundaries: false,
+        };
+        group.bench_function(BenchmarkId::new("vm", seed), move |b| {
+            b.iter(|| {
+                let tape = tape_vm.clone();
+                black_box(fidget::render::render2d(
+                    tape,
+                    cfg,
+                    &fidget::render::BitRenderMode,
+                ))
+            })
+        });
+
+        #[cfg(feature = "jit")]
+        {
+            let tape_jit = &ctx.get_tape::<fidget::jit::Eval>(root).unwrap();
+            let cfg = &fidget::render::RenderConfig {
+                image_size: 512,
+                tile_sizes: fidget::jit::Eval::tile_sizes_2d().to_vec(),
+                threads: 8,
+                mat: nalgebra::Transform2::identity(),
+                exact_boundaries: false,
+            };
+            group.bench_function(BenchmarkId::new("jit", seed), move |b| {
+                b.iter(|| {
+                    let tape = tape_jit.clone();
+                    black_box(fidget::render::render2d(
+                        tape,
+                        cfg,
+                        &fidget::render::BitRenderMode,
+                    ))
+                })
+            });
+        }
+    }
+}
+
+criterion_group!(
+    benches,
+    prospero_size_sweep,
+    prospero_thread_sweep,
+    prospero_scheduling,
+    random_exprs
+);
 criterion_main!(benches);
@@ -48,6 +48,7 @@ fn main() {
 
     if std::env::var("CARGO_FEATURE_MESH").is_ok() {
         build_mdc_table().unwrap();
+        build_mc_table().unwrap();
     }
 }
 
@@ -258,3 +259,114 @@ pub const CELL_TO_EDGE_TO_VERT: [[Intersection; 12]; 256] = ["
 
     Ok(())
 }
+
+/// Builds a triangle table for Marching Cubes.
+///
+/// Rather than transcribing the classic table, triangles are found by walking
+/// the surface's boundary on each face of the cell.  On ambiguous faces (with
+/// filled corners on a diagonal), filled corners are always kept separate;
+/// since this rule only depends on the face itself, neighboring cells agree
+/// about the contour on their shared face, and the resulting mesh is
+/// watertight and manifold.
+fn build_mc_table() -> Result<(), std::io::Error> {
+    // Packs an edge between two corners, matching `DirectedEdge::to_undirected`
+    let edge_index = |a: usize, b: usize| {
+        let t = a ^ b;
+        let u = next(t);
+        let v = next(u);
+        let a = a & !t;
+        t.trailing_zeros() as usize * 4
+            + ((a & u) != 0) as usize
+            + ((a & v) != 0) as usize * 2
+    };
+
+    let mut tri_table: Vec<Vec<[usize; 3]>> = vec![];
+    for i in 0..256 {
+        let inside = |c: usize| (i & (1 << c)) != 0;
+
+        // Map from an edge to the next edge on the contour
+        let mut next_edge = [None; 12];
+        for t in [X, Y, Z] {
+            let u = next(t);
+            let v = next(u);
+            for side in [0, t] {
+                // Walk counter-clockwise, as seen from outside the cell
+                let mut cycle = [side, side | u, side | u | v, side | v];
+                if side == 0 {
+                    cycle.reverse();
+                }
+                // List of (edge, is_exit) tuples, in order around the face
+                let mut crossings = vec![];
+                for k in 0..4 {
+                    let a = cycle[k];
+                    let b = cycle[(k + 1) % 4];
+                    if inside(a) != inside(b) {
+                        crossings.push((edge_index(a, b), inside(a)));
+                    }
+                }
+                // Crossings alternate between entering and exiting the
+                // filled region; connecting each exit to the preceding entry
+                // wraps the contour around filled corners.
+                let n = crossings.len();
+                for (k, &(edge, exit)) in crossings.iter().enumerate() {
+                    if exit {
+                        let (prev, prev_exit) = crossings[(k + n - 1) % n];
+                        assert!(!prev_exit);
+                        assert!(next_edge[edge].is_none());
+                        next_edge[edge] = Some(prev);
+                    }
+                }
+            }
+        }
+
+        // Follow contours around the cell, triangulating each one as a fan
+        let mut tris = vec![];
+        let mut done = [false; 12];
+        for start in 0..12 {
+            if done[start] || next_edge[start].is_none() {
+                continue;
+            }
+            let mut contour = vec![];
+            let mut e = start;
+            while !done[e] {
+                done[e] = true;
+                contour.push(e);
+                e = next_edge[e].unwrap();
+            }
+            assert_eq!(e, start);
+            for k in 1..contour.len() - 1 {
+                tris.push([contour[0], contour[k + 1], contour[k]]);
+            }
+        }
+        tri_table.push(tris);
+    }
+
+    let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    let dest_path = std::path::Path::new(&out_dir).join("mc_tables.rs");
+    let mut file =
+        std::fs::File::create(dest_path).expect("could not make output file");
+
+    writeln!(
+        &mut file,
+        "
+/// Lookup table of Marching Cubes triangles
+///
+/// Given a cell index `i` (as an 8-bit value, with set bits for filled
+/// corners), returns a list of triangles, each of which is a triple of edges.
+/// Triangles are wound counter-clockwise when seen from outside the shape.
+pub const CELL_TO_TRIANGLES: [&[[Edge; 3]]; 256] = ["
+    )?;
+    for tris in tri_table {
+        writeln!(&mut file, "    &[")?;
+        for [a, b, c] in tris {
+            writeln!(
+                &mut file,
+                "        [Edge::new({a}), Edge::new({b}), Edge::new({c})],"
+            )?;
+        }
+        writeln!(&mut file, "    ],")?;
+    }
+    writeln!(&mut file, "];")?;
+
+    Ok(())
+}
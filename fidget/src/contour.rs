@@ -0,0 +1,336 @@
+//! Extraction of 2D iso-contours as polylines
+//!
+//! A 2D shape (with `Z = 0`) is divided into a quadtree.  Cells which are
+//! entirely inside or outside the shape are pruned with interval arithmetic,
+//! and the remaining cells are sampled with Marching Squares.  The resulting
+//! line segments are joined into polylines, which are suitable for toolpaths
+//! or vector output.
+//!
+//! ```
+//! use fidget::{contour, rhai::eval, vm};
+//!
+//! let (circle, ctx) = eval("sqrt(x*x + y*y) - 0.5")?;
+//! let tape = ctx.get_tape::<vm::Eval>(circle)?;
+//! let settings = contour::Settings {
+//!     depth: 6,
+//!     ..Default::default()
+//! };
+//! let cs = contour::contours(&tape, &settings);
+//! assert_eq!(cs.len(), 1);
+//! assert!(cs[0].closed);
+//! for [x, y] in &cs[0].points {
+//!     assert!((x.hypot(*y) - 0.5).abs() < 1e-3);
+//! }
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::eval::{types::Interval, Family, Tape};
+use std::collections::{HashMap, HashSet};
+
+/// Number of quadtree levels at the bottom which are sampled as a block
+///
+/// Below this depth, cells are evaluated in bulk instead of being subdivided
+/// with interval arithmetic.
+const BLOCK_DEPTH: u8 = 3;
+
+/// Settings when extracting contours
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// Lower corner of the region to sample
+    pub lower: [f32; 2],
+
+    /// Upper corner of the region to sample
+    pub upper: [f32; 2],
+
+    /// Depth of the quadtree
+    ///
+    /// The region is sampled on a grid of `2^depth` cells along each axis.
+    pub depth: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            lower: [-1.0; 2],
+            upper: [1.0; 2],
+            depth: 8,
+        }
+    }
+}
+
+impl Settings {
+    /// Converts from a position on the global grid to a coordinate
+    fn pos(&self, axis: usize, i: u32) -> f32 {
+        let t = i as f32 / (1u32 << self.depth) as f32;
+        self.lower[axis] + (self.upper[axis] - self.lower[axis]) * t
+    }
+}
+
+/// A single polyline
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Contour {
+    /// Points along the polyline
+    ///
+    /// If the contour is closed, the last point connects back to the first
+    /// (without being repeated).
+    pub points: Vec<[f32; 2]>,
+
+    /// Whether the contour is a closed loop
+    ///
+    /// Contours are only open if they cross the edge of the sampled region.
+    pub closed: bool,
+}
+
+impl Contour {
+    /// Returns the signed area enclosed by a closed contour
+    ///
+    /// Contours are wound counter-clockwise around filled regions, so the
+    /// outer boundary of a shape has positive area and holes have negative
+    /// area.
+    pub fn area(&self) -> f32 {
+        let n = self.points.len();
+        (0..n)
+            .map(|i| {
+                let [x0, y0] = self.points[i];
+                let [x1, y1] = self.points[(i + 1) % n];
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f32>()
+            / 2.0
+    }
+}
+
+/// Key for a cell edge: its lower corner (on the global grid) and axis
+type EdgeKey = ([u32; 2], u8);
+
+/// Collection of line segments, keyed by the cell edges at their endpoints
+#[derive(Default)]
+struct Segments {
+    /// Map from each segment's start to its end
+    next: HashMap<EdgeKey, EdgeKey>,
+    /// Vertex position for each edge
+    verts: HashMap<EdgeKey, [f32; 2]>,
+}
+
+impl Segments {
+    /// Recursively subdivides the given cell, sampling cells near the contour
+    fn cell<F: Family>(
+        &mut self,
+        tape: &Tape<F>,
+        origin: [u32; 2],
+        depth: u8,
+        settings: &Settings,
+    ) {
+        let size = 1 << (settings.depth - depth);
+        let [x, y] = [0, 1].map(|i| {
+            Interval::new(
+                settings.pos(i, origin[i]),
+                settings.pos(i, origin[i] + size),
+            )
+        });
+        let z = Interval::from(0.0);
+        let (i, r) = tape.new_interval_evaluator().eval(x, y, z, &[]).unwrap();
+        if !i.has_nan() && (i.lower() > 0.0 || i.upper() < 0.0) {
+            return;
+        }
+        let tape = match r {
+            Some(r) => r.simplify().unwrap(),
+            None => tape.clone(),
+        };
+        if depth + BLOCK_DEPTH >= settings.depth {
+            self.block(&tape, origin, size, settings);
+        } else {
+            let half = size / 2;
+            for c in 0..4 {
+                let child = [0, 1].map(|i| origin[i] + half * ((c >> i) & 1));
+                self.cell(&tape, child, depth + 1, settings);
+            }
+        }
+    }
+
+    /// Samples a block of cells with Marching Squares
+    fn block<F: Family>(
+        &mut self,
+        tape: &Tape<F>,
+        origin: [u32; 2],
+        size: u32,
+        settings: &Settings,
+    ) {
+        let n = size as usize + 1;
+        let (mut xs, mut ys) = (vec![], vec![]);
+        for j in 0..n as u32 {
+            for i in 0..n as u32 {
+                xs.push(settings.pos(0, origin[0] + i));
+                ys.push(settings.pos(1, origin[1] + j));
+            }
+        }
+        let zs = vec![0.0; xs.len()];
+        let values = tape
+            .new_float_slice_evaluator()
+            .eval(&xs, &ys, &zs, &[])
+            .unwrap();
+        let index = |p: [usize; 2]| p[0] + n * p[1];
+
+        for j in 0..n - 1 {
+            for i in 0..n - 1 {
+                // Corners in counter-clockwise order
+                let cycle = [[i, j], [i + 1, j], [i + 1, j + 1], [i, j + 1]];
+                let inside = cycle.map(|p| values[index(p)] < 0.0);
+
+                // List of (edge, is_exit) tuples, in order around the cell
+                let mut crossings = vec![];
+                for k in 0..4 {
+                    let (a, b) = (cycle[k], cycle[(k + 1) % 4]);
+                    if inside[k] == inside[(k + 1) % 4] {
+                        continue;
+                    }
+                    let lo = [a[0].min(b[0]), a[1].min(b[1])];
+                    let key = (
+                        [origin[0] + lo[0] as u32, origin[1] + lo[1] as u32],
+                        (a[0] == b[0]) as u8,
+                    );
+                    self.verts.entry(key).or_insert_with(|| {
+                        let (va, vb) = (values[index(a)], values[index(b)]);
+                        let t = va / (va - vb);
+                        let (ia, ib) = (index(a), index(b));
+                        [
+                            xs[ia] + (xs[ib] - xs[ia]) * t,
+                            ys[ia] + (ys[ib] - ys[ia]) * t,
+                        ]
+                    });
+                    crossings.push((key, inside[k]));
+                }
+
+                // Connect each exit to the preceding entry, which wraps the
+                // contour around filled corners (so that filled corners on a
+                // diagonal are kept separate).
+                let m = crossings.len();
+                for (k, &(key, exit)) in crossings.iter().enumerate() {
+                    if exit {
+                        let (prev, prev_exit) = crossings[(k + m - 1) % m];
+                        debug_assert!(!prev_exit);
+                        self.next.insert(key, prev);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Joins segments into polylines
+    fn join(self) -> Vec<Contour> {
+        let mut starts: Vec<EdgeKey> = self.next.keys().cloned().collect();
+        starts.sort();
+
+        // Open contours start at an edge which isn't the end of any segment
+        let ends: HashSet<EdgeKey> = self.next.values().cloned().collect();
+        starts.sort_by_key(|k| ends.contains(k));
+
+        let mut done = HashSet::new();
+        let mut out = vec![];
+        for start in starts {
+            if done.contains(&start) {
+                continue;
+            }
+            let mut points = vec![];
+            let mut key = start;
+            let closed = loop {
+                done.insert(key);
+                points.push(self.verts[&key]);
+                match self.next.get(&key) {
+                    Some(k) if *k == start => break true,
+                    Some(k) => key = *k,
+                    None => break false,
+                }
+            };
+            out.push(Contour { points, closed });
+        }
+        out
+    }
+}
+
+/// Extracts contours of the given shape, evaluated on the `Z = 0` plane
+///
+/// Contours are wound counter-clockwise around filled regions (see
+/// [`Contour::area`]).
+pub fn contours<F: Family>(
+    tape: &Tape<F>,
+    settings: &Settings,
+) -> Vec<Contour> {
+    let mut segments = Segments::default();
+    segments.cell(tape, [0; 2], 0, settings);
+    segments.join()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::{Context, Node};
+
+    fn circle(ctx: &mut Context, center: [f64; 2], r: f64) -> Node {
+        let x = ctx.x();
+        let y = ctx.y();
+        let dx = ctx.sub(x, center[0]).unwrap();
+        let dy = ctx.sub(y, center[1]).unwrap();
+        let dx2 = ctx.square(dx).unwrap();
+        let dy2 = ctx.square(dy).unwrap();
+        let d = ctx.add(dx2, dy2).unwrap();
+        let d = ctx.sqrt(d).unwrap();
+        ctx.sub(d, r).unwrap()
+    }
+
+    #[test]
+    fn test_ring() {
+        let mut ctx = Context::new();
+        let outer = circle(&mut ctx, [0.0, 0.0], 0.8);
+        let inner = circle(&mut ctx, [0.0, 0.0], 0.4);
+        let inner = ctx.neg(inner).unwrap();
+        let ring = ctx.max(outer, inner).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(ring).unwrap();
+
+        let mut cs = contours(&tape, &Settings::default());
+        assert_eq!(cs.len(), 2);
+        cs.sort_by(|a, b| a.area().total_cmp(&b.area()));
+        let pi = std::f32::consts::PI;
+        assert!(cs.iter().all(|c| c.closed));
+        assert!((cs[0].area() + pi * 0.4 * 0.4).abs() < 1e-3);
+        assert!((cs[1].area() - pi * 0.8 * 0.8).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_open() {
+        // A circle which is clipped by the edge of the region
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, [1.0, 0.0], 0.5);
+        let tape = ctx.get_tape::<crate::vm::Eval>(c).unwrap();
+        let cs = contours(&tape, &Settings::default());
+        assert_eq!(cs.len(), 1);
+        assert!(!cs[0].closed);
+
+        // The contour runs counter-clockwise around the circle's center, i.e.
+        // from the top of the circle to the bottom
+        let first = cs[0].points.first().unwrap();
+        let last = cs[0].points.last().unwrap();
+        assert!(first[1] > 0.4 && last[1] < -0.4, "{first:?} {last:?}");
+        for [x, y] in &cs[0].points {
+            assert!(((x - 1.0).hypot(*y) - 0.5).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_diagonal() {
+        // Two circles which touch at a saddle point; their contours must stay
+        // separate rather than crossing over each other.
+        let mut ctx = Context::new();
+        let a = circle(&mut ctx, [-0.25, -0.25], 0.3);
+        let b = circle(&mut ctx, [0.25, 0.25], 0.3);
+        let u = ctx.min(a, b).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(u).unwrap();
+        for depth in 2..8 {
+            let settings = Settings {
+                depth,
+                ..Default::default()
+            };
+            let cs = contours(&tape, &settings);
+            assert!(cs.iter().all(|c| c.closed && c.area() > 0.0));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Tight bounding boxes by interval bisection
+//!
+//! [`Context::bounds`](crate::context::Context::bounds) finds bounds by
+//! constraint propagation, which is fast but can be very loose (or infinite)
+//! for complex shapes.  [`find_bounds`] instead searches a region with the
+//! interval evaluator, which gives a bounding box within a user-specified
+//! tolerance of the true extent.
+//!
+//! ```
+//! use fidget::{
+//!     bounds::find_bounds,
+//!     context::{BoundingBox, Context},
+//!     vm,
+//! };
+//!
+//! // A sphere with radius 0.5, cut off at X = 0.2
+//! let mut ctx = Context::new();
+//! let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+//! let (x2, y2, z2) = (ctx.square(x)?, ctx.square(y)?, ctx.square(z)?);
+//! let r = ctx.add(x2, y2)?;
+//! let r = ctx.add(r, z2)?;
+//! let r = ctx.sqrt(r)?;
+//! let sphere = ctx.sub(r, 0.5)?;
+//! let cut = ctx.sub(x, 0.2)?;
+//! let shape = ctx.max(sphere, cut)?;
+//! let tape = ctx.get_tape::<vm::Eval>(shape)?;
+//! let region = BoundingBox::new([-4.0; 3], [4.0; 3]);
+//! let b = find_bounds(&tape, region, 0.01)?;
+//! assert!((b.lower[0] - -0.5).abs() <= 0.01);
+//! assert!((b.upper[0] - 0.2).abs() <= 0.01);
+//! assert!((b.upper[2] - 0.5).abs() <= 0.01);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::BoundingBox,
+    eval::{types::Interval, Family, Tape},
+    Error,
+};
+use alloc::collections::BinaryHeap;
+
+/// A cell in the search, ordered by its extent along the search direction
+struct Cell<F: Family> {
+    /// Position of the cell's leading face along the search direction
+    ///
+    /// This is negated when searching for lower bounds, so that the max-heap
+    /// always pops the most extreme cell.
+    key: f64,
+    lower: [f64; 3],
+    upper: [f64; 3],
+    tape: Tape<F>,
+}
+
+impl<F: Family> PartialEq for Cell<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key.total_cmp(&other.key).is_eq()
+    }
+}
+
+impl<F: Family> Eq for Cell<F> {}
+
+impl<F: Family> PartialOrd for Cell<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F: Family> Ord for Cell<F> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.key.total_cmp(&other.key)
+    }
+}
+
+/// Finds the extreme position of the shape's interior along one axis
+///
+/// Cells are searched in order of their position along the axis, so the
+/// first cell which is known to contain interior points (or which is smaller
+/// than `tolerance` and may contain them) gives the bound.  Returns `None` if
+/// the region contains no interior points.
+fn search<F: Family>(
+    tape: &Tape<F>,
+    region: &BoundingBox,
+    tolerance: f64,
+    axis: usize,
+    upper: bool,
+) -> Result<Option<f64>, Error> {
+    let key = |lo: &[f64; 3], hi: &[f64; 3]| {
+        if upper {
+            hi[axis]
+        } else {
+            -lo[axis]
+        }
+    };
+    let mut heap = BinaryHeap::new();
+    heap.push(Cell {
+        key: key(&region.lower, &region.upper),
+        lower: region.lower,
+        upper: region.upper,
+        tape: tape.clone(),
+    });
+
+    while let Some(cell) = heap.pop() {
+        let [x, y, z] = [0, 1, 2]
+            .map(|i| Interval::new(cell.lower[i] as f32, cell.upper[i] as f32));
+        let (i, r, hint) =
+            cell.tape.new_interval_evaluator().eval_hint(x, y, z, &[])?;
+        let bound = if upper {
+            cell.upper[axis]
+        } else {
+            cell.lower[axis]
+        };
+        if i.has_nan() {
+            // We can't learn anything from this cell, so subdivide it
+        } else if i.lower() > 0.0 {
+            continue;
+        } else if i.upper() <= 0.0 {
+            return Ok(Some(bound));
+        }
+
+        // Split the cell along the axis which contributes most to the
+        // output's width, falling back to its longest axis
+        let size = |i: usize| cell.upper[i] - cell.lower[i];
+        let longest =
+            (0..3).max_by(|&a, &b| size(a).total_cmp(&size(b))).unwrap();
+        if size(longest) <= tolerance {
+            return Ok(Some(bound));
+        }
+        let split = hint
+            .axis()
+            .filter(|&i| size(i) > tolerance)
+            .unwrap_or(longest);
+        let tape = match r {
+            Some(r) => r.simplify()?,
+            None => cell.tape.clone(),
+        };
+        let mid = (cell.lower[split] + cell.upper[split]) / 2.0;
+        let mut hi = cell.lower;
+        hi[split] = mid;
+        let mut lo = cell.upper;
+        lo[split] = mid;
+        for (lower, upper) in [(cell.lower, lo), (hi, cell.upper)] {
+            heap.push(Cell {
+                key: key(&lower, &upper),
+                lower,
+                upper,
+                tape: tape.clone(),
+            });
+        }
+    }
+    Ok(None)
+}
+
+/// Finds a tight bounding box of the shape's interior (where it is `≤ 0`)
+///
+/// The search starts from `region`, which is clipped to the tape's own bounds
+/// (see [`Tape::bounds`]) and must be finite.  The result is conservative: it
+/// always contains every interior point within `region`, and each face is
+/// within about `tolerance` of the true extent (assuming that intervals are
+/// reasonably tight for small cells).
+///
+/// Returns [`BoundingBox::EMPTY`] if there are no interior points within the
+/// region, or [`Error::InfiniteRegion`] if the region isn't finite.
+pub fn find_bounds<F: Family>(
+    tape: &Tape<F>,
+    region: BoundingBox,
+    tolerance: f64,
+) -> Result<BoundingBox, Error> {
+    if !region.is_empty() && !region.is_finite() {
+        return Err(Error::InfiniteRegion);
+    }
+    let region = region.intersection(&tape.bounds());
+    if region.is_empty() {
+        return Ok(BoundingBox::EMPTY);
+    }
+    let mut out = region;
+    for axis in 0..3 {
+        let Some(lo) = search(tape, &out, tolerance, axis, false)? else {
+            return Ok(BoundingBox::EMPTY);
+        };
+        out.lower[axis] = lo;
+        // The interior is non-empty, so this search always finds a bound
+        out.upper[axis] =
+            search(tape, &out, tolerance, axis, true)?.unwrap_or(lo);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    fn test_find_bounds<F: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+
+        // Intersection of two diagonal slabs (i.e. a rotated rectangle), which
+        // constraint propagation can't bound tightly
+        let xy = ctx.add(x, y).unwrap();
+        let a = ctx.abs(xy).unwrap();
+        let a = ctx.sub(a, 1.0).unwrap();
+        let xmy = ctx.sub(x, y).unwrap();
+        let b = ctx.abs(xmy).unwrap();
+        let b = ctx.sub(b, 0.5).unwrap();
+        let zb = ctx.abs(z).unwrap();
+        let zb = ctx.sub(zb, 0.25).unwrap();
+        let shape = ctx.max(a, b).unwrap();
+        let shape = ctx.max(shape, zb).unwrap();
+        let tape = ctx.get_tape::<F>(shape).unwrap();
+
+        let region = BoundingBox::new([-8.0; 3], [8.0; 3]);
+        let tol = 1e-3;
+        let b = find_bounds(&tape, region, tol).unwrap();
+        for (axis, extent) in [0.75, 0.75, 0.25].into_iter().enumerate() {
+            let (lo, hi) = (b.lower[axis], b.upper[axis]);
+            // Bounds are conservative, and within the tolerance
+            assert!(lo <= -extent + 1e-6 && lo >= -extent - tol, "{lo}");
+            assert!(hi >= extent - 1e-6 && hi <= extent + tol, "{hi}");
+        }
+
+        // Regions which don't contain the shape give an empty result
+        let far = BoundingBox::new([2.0; 3], [3.0; 3]);
+        assert!(find_bounds(&tape, far, tol).unwrap().is_empty());
+
+        assert!(matches!(
+            find_bounds(&tape, BoundingBox::INFINITE, tol),
+            Err(Error::InfiniteRegion)
+        ));
+    }
+
+    #[test]
+    fn test_find_bounds_vm() {
+        test_find_bounds::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_find_bounds_jit() {
+        test_find_bounds::<crate::jit::Eval>();
+    }
+}
@@ -0,0 +1,199 @@
+//! Generational arena used to store operations in a [`Context`]
+//!
+//! [`Context`]: crate::context::Context
+use super::Op;
+use alloc::{vec, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+use hashbrown::HashMap;
+
+/// Source of unique arena IDs, so that handles can't cross between contexts
+static NEXT_ARENA_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Handle to an operation in a [`Context`](crate::context::Context)
+///
+/// In addition to its slot in the context, each handle records the context
+/// which created it and the generation of that slot.  This means that a handle
+/// from a different context, or a handle to a node which has since been
+/// reclaimed by [`Context::gc`](crate::context::Context::gc), is detected as
+/// invalid instead of silently referring to some other operation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Node {
+    index: u32,
+    generation: u32,
+    arena: u32,
+}
+
+impl Node {
+    /// Returns the slot index of this node within its context
+    ///
+    /// Slot indices are unique among live nodes in a context, but may be
+    /// reused after nodes are reclaimed.
+    pub(crate) fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+/// A single slot in the arena
+#[derive(Clone, Debug, Default)]
+struct Slot {
+    /// Operation stored in this slot, or `None` if the slot is free
+    op: Option<Op>,
+    /// Incremented every time the slot is freed
+    generation: u32,
+}
+
+/// Stores deduplicated operations, handing out generational [`Node`] handles
+///
+/// Freed slots are recycled by later insertions; their generation is bumped
+/// so that stale handles to the old contents are rejected.
+#[derive(Debug)]
+pub(crate) struct Arena {
+    id: u32,
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    map: HashMap<Op, Node>,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            id: NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed),
+            slots: vec![],
+            free: vec![],
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl Arena {
+    /// Removes every operation from the arena
+    ///
+    /// The arena picks up a fresh ID, so all existing handles are invalidated.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns the number of live operations
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of slots (live or free)
+    ///
+    /// Every live node's [`Node::index`] is less than this value.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Looks up an operation, returning `None` if the handle is invalid
+    pub fn get(&self, node: Node) -> Option<&Op> {
+        if node.arena != self.id {
+            return None;
+        }
+        let slot = self.slots.get(node.index())?;
+        if slot.generation == node.generation {
+            slot.op.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Inserts the given operation into the arena, returning a handle.
+    ///
+    /// If the operation is already in the arena, the handle will be to the
+    /// existing instance (so it will not be inserted twice).
+    pub fn insert(&mut self, op: Op) -> Node {
+        if let Some(n) = self.map.get(&op) {
+            return *n;
+        }
+        let index = match self.free.pop() {
+            Some(i) => i,
+            None => {
+                let i = u32::try_from(self.slots.len())
+                    .expect("too many nodes in arena");
+                self.slots.push(Slot::default());
+                i
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.op = Some(op.clone());
+        let node = Node {
+            index,
+            generation: slot.generation,
+            arena: self.id,
+        };
+        self.map.insert(op, node);
+        node
+    }
+
+    /// Removes an operation from the arena, returning it
+    ///
+    /// Returns `None` if the handle is invalid.
+    pub fn remove(&mut self, node: Node) -> Option<Op> {
+        self.get(node)?;
+        let slot = &mut self.slots[node.index()];
+        let op = slot.op.take().unwrap();
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(node.index);
+        self.map.remove(&op);
+        Some(op)
+    }
+
+    /// Iterates over handles to every live operation, in slot order
+    pub fn keys(&self) -> impl Iterator<Item = Node> + '_ {
+        self.slots.iter().enumerate().filter_map(|(i, s)| {
+            s.op.as_ref().map(|_| Node {
+                index: i as u32,
+                generation: s.generation,
+                arena: self.id,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ordered_float::OrderedFloat;
+
+    #[test]
+    fn test_arena_reuse() {
+        let mut a = Arena::default();
+        let n1 = a.insert(Op::Const(OrderedFloat(1.0)));
+        let n2 = a.insert(Op::Const(OrderedFloat(2.0)));
+        assert_eq!(a.insert(Op::Const(OrderedFloat(1.0))), n1);
+        assert_eq!(a.len(), 2);
+
+        assert_eq!(a.remove(n1), Some(Op::Const(OrderedFloat(1.0))));
+        assert_eq!(a.remove(n1), None);
+        assert!(a.get(n1).is_none());
+        assert_eq!(a.len(), 1);
+
+        // The slot is recycled, but the old handle stays invalid
+        let n3 = a.insert(Op::Const(OrderedFloat(3.0)));
+        assert_eq!(n3.index(), n1.index());
+        assert_ne!(n3, n1);
+        assert!(a.get(n1).is_none());
+        assert_eq!(a.get(n3), Some(&Op::Const(OrderedFloat(3.0))));
+        assert_eq!(a.keys().collect::<Vec<_>>(), vec![n3, n2]);
+        assert_eq!(a.slot_count(), 2);
+    }
+
+    #[test]
+    fn test_arena_foreign() {
+        let mut a = Arena::default();
+        let mut b = Arena::default();
+        let na = a.insert(Op::Const(OrderedFloat(1.0)));
+        let nb = b.insert(Op::Const(OrderedFloat(1.0)));
+        assert_ne!(na, nb);
+        assert!(a.get(nb).is_none());
+        assert!(b.get(na).is_none());
+
+        a.clear();
+        assert!(a.get(na).is_none());
+    }
+}
@@ -0,0 +1,844 @@
+//! Conservative bounding boxes for the interior of a shape
+//!
+//! Bounds are found by constraint propagation: starting from the requirement
+//! that the root node is `≤ 0`, we walk down the expression graph and narrow
+//! the X, Y, Z ranges which could possibly satisfy that requirement (for
+//! example, `sqrt(x² + y²) - r ≤ 0` implies `-r ≤ x ≤ r`).
+use super::{
+    BinaryOpcode, Context, NaryOpcode, Node, NodeBudget, Op, UnaryOpcode,
+    VarNode,
+};
+use crate::{eval::types::Interval, Error};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+/// Axis-aligned bounding box, which contains every point where a shape is
+/// inside or on its surface (i.e. its value is `≤ 0`)
+///
+/// Bounds may be infinite on some (or all) axes.  Outside of the box, the shape
+/// is known to be strictly positive, so renderers and meshers can skip those
+/// regions without evaluating the shape.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingBox {
+    /// Lower bounds on X, Y, Z
+    pub lower: [f64; 3],
+    /// Upper bounds on X, Y, Z
+    pub upper: [f64; 3],
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::INFINITE
+    }
+}
+
+impl BoundingBox {
+    /// Unbounded box, which contains all of space
+    pub const INFINITE: Self = Self {
+        lower: [f64::NEG_INFINITY; 3],
+        upper: [f64::INFINITY; 3],
+    };
+
+    /// Box which contains no points
+    pub const EMPTY: Self = Self {
+        lower: [f64::INFINITY; 3],
+        upper: [f64::NEG_INFINITY; 3],
+    };
+
+    /// Builds a new bounding box from lower and upper corners
+    pub fn new(lower: [f64; 3], upper: [f64; 3]) -> Self {
+        Self { lower, upper }
+    }
+
+    /// Checks whether the box contains no points
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| {
+            self.lower[i] > self.upper[i]
+                || self.lower[i].is_nan()
+                || self.upper[i].is_nan()
+        })
+    }
+
+    /// Checks whether the box is finite on every axis
+    pub fn is_finite(&self) -> bool {
+        !self.is_empty()
+            && self.lower.iter().chain(&self.upper).all(|v| v.is_finite())
+    }
+
+    /// Checks whether the box overlaps the given region
+    ///
+    /// Regions with `NaN` bounds are assumed to overlap.
+    pub fn intersects(&self, x: Interval, y: Interval, z: Interval) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        [x, y, z].iter().enumerate().all(|(i, r)| {
+            !((r.upper() as f64) < self.lower[i]
+                || (r.lower() as f64) > self.upper[i])
+        })
+    }
+
+    /// Returns the intersection of two boxes
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut out = *self;
+        for i in 0..3 {
+            out.lower[i] = out.lower[i].max(other.lower[i]);
+            out.upper[i] = out.upper[i].min(other.upper[i]);
+        }
+        out
+    }
+
+    /// Returns the smallest box containing both boxes
+    pub fn union(&self, other: &Self) -> Self {
+        if self.is_empty() {
+            return *other;
+        } else if other.is_empty() {
+            return *self;
+        }
+        let mut out = *self;
+        for i in 0..3 {
+            out.lower[i] = out.lower[i].min(other.lower[i]);
+            out.upper[i] = out.upper[i].max(other.upper[i]);
+        }
+        out
+    }
+
+    fn range(&self, axis: usize) -> Range {
+        Range::new(self.lower[axis], self.upper[axis])
+    }
+}
+
+/// Maximum number of narrowing passes for each `max` operation
+const BOUNDS_PASSES: usize = 4;
+
+/// Double-precision interval used during bounds analysis
+///
+/// Unlike [`Interval`], this may be empty (with `lo > hi`); `NaN` bounds are
+/// widened to infinity.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Range {
+    lo: f64,
+    hi: f64,
+}
+
+impl Range {
+    const ALL: Self = Self {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+    };
+
+    fn new(lo: f64, hi: f64) -> Self {
+        Self {
+            lo: if lo.is_nan() { f64::NEG_INFINITY } else { lo },
+            hi: if hi.is_nan() { f64::INFINITY } else { hi },
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.lo > self.hi
+    }
+    fn contains_zero(&self) -> bool {
+        self.lo <= 0.0 && self.hi >= 0.0
+    }
+    fn intersection(self, rhs: Self) -> Self {
+        Self::new(self.lo.max(rhs.lo), self.hi.min(rhs.hi))
+    }
+    fn hull(self, rhs: Self) -> Self {
+        Self::new(self.lo.min(rhs.lo), self.hi.max(rhs.hi))
+    }
+    fn neg(self) -> Self {
+        Self::new(-self.hi, -self.lo)
+    }
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.lo + rhs.lo, self.hi + rhs.hi)
+    }
+    fn sub(self, rhs: Self) -> Self {
+        self.add(rhs.neg())
+    }
+    fn mul(self, rhs: Self) -> Self {
+        let vs = [
+            self.lo * rhs.lo,
+            self.lo * rhs.hi,
+            self.hi * rhs.lo,
+            self.hi * rhs.hi,
+        ];
+        if vs.iter().any(|v| v.is_nan()) {
+            return Self::ALL;
+        }
+        Self::new(
+            vs.iter().cloned().fold(f64::INFINITY, f64::min),
+            vs.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+    fn recip(self) -> Self {
+        if self.contains_zero() {
+            Self::ALL
+        } else {
+            Self::new(1.0 / self.hi, 1.0 / self.lo)
+        }
+    }
+    fn div(self, rhs: Self) -> Self {
+        self.mul(rhs.recip())
+    }
+    fn abs(self) -> Self {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            self.neg()
+        } else {
+            Self::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+    fn square(self) -> Self {
+        let a = self.abs();
+        Self::new(a.lo * a.lo, a.hi * a.hi)
+    }
+    fn sqrt(self) -> Self {
+        if self.hi < 0.0 {
+            Self::ALL // NaN
+        } else {
+            Self::new(self.lo.max(0.0).sqrt(), self.hi.sqrt())
+        }
+    }
+}
+
+/// Ranges for the X, Y, Z axes
+type Axes = [Range; 3];
+
+impl Context {
+    /// Declares a bounding box for the given node
+    ///
+    /// This is useful for primitives whose bounds can't be found by analysis
+    /// (see [`Context::bounds`]).  The caller is responsible for making sure
+    /// that the box is correct, i.e. that the node is strictly positive
+    /// everywhere outside of it; incorrect bounds will cause renderers and
+    /// meshers to skip parts of the shape.
+    ///
+    /// Returns [`Error::BadNode`] if the node is invalid.
+    pub fn set_bounds(
+        &mut self,
+        node: Node,
+        bounds: BoundingBox,
+    ) -> Result<(), Error> {
+        self.check_node(node)?;
+        let b = self.bounds.entry(node).or_insert(BoundingBox::INFINITE);
+        *b = b.intersection(&bounds);
+        Ok(())
+    }
+
+    /// Computes a conservative bounding box for the given node
+    ///
+    /// The node is guaranteed to be strictly positive outside of the returned
+    /// box.  Bounds are found by analyzing the expression (and using any
+    /// bounds declared with [`Context::set_bounds`]), and may be infinite
+    /// if no bounds could be found.
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let x2 = ctx.square(x).unwrap();
+    /// let y2 = ctx.square(y).unwrap();
+    /// let r = ctx.add(x2, y2).unwrap();
+    /// let r = ctx.sqrt(r).unwrap();
+    /// let circle = ctx.sub(r, 0.5).unwrap();
+    ///
+    /// let b = ctx.bounds(circle).unwrap();
+    /// assert_eq!(b.lower, [-0.5, -0.5, f64::NEG_INFINITY]);
+    /// assert_eq!(b.upper, [0.5, 0.5, f64::INFINITY]);
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if the node is invalid, or
+    /// [`Error::TooManyNodes`] if the analysis visits more than
+    /// [`Context::node_limit`] nodes.
+    pub fn bounds(&self, node: Node) -> Result<BoundingBox, Error> {
+        self.check_node(node)?;
+        let mut b = Bounder {
+            ctx: self,
+            fuel: self.len() * BOUNDS_PASSES,
+            budget: NodeBudget::new(self),
+            cache: BTreeMap::new(),
+            cache_axes: [Range::ALL; 3],
+        };
+        let mut axes = [Range::ALL; 3];
+        let target = Range::new(f64::NEG_INFINITY, 0.0);
+        if !b.contract(node, target, &mut axes)? {
+            return Ok(BoundingBox::EMPTY);
+        }
+        Ok(BoundingBox {
+            lower: axes.map(|r| r.lo),
+            upper: axes.map(|r| r.hi),
+        })
+    }
+}
+
+/// Helper struct for bounds analysis
+struct Bounder<'a> {
+    ctx: &'a Context,
+
+    /// Remaining budget of [`contract`](Self::contract) calls for repeated
+    /// narrowing passes, which would otherwise take exponential time on
+    /// deeply nested expressions
+    fuel: usize,
+
+    /// Limit on the total number of nodes visited
+    budget: NodeBudget,
+
+    /// Ranges found by [`bounds_range`](Self::bounds_range), which are valid
+    /// for the region in `cache_axes`
+    cache: BTreeMap<Node, Range>,
+    cache_axes: Axes,
+}
+
+impl Bounder<'_> {
+    /// Evaluates the range of a node over the given region
+    fn bounds_range(
+        &mut self,
+        node: Node,
+        axes: &Axes,
+    ) -> Result<Range, Error> {
+        if *axes != self.cache_axes {
+            self.cache.clear();
+            self.cache_axes = *axes;
+        }
+        let mut done = core::mem::take(&mut self.cache);
+
+        // Depth-first recursion on the heap, to protect against stack overflows
+        let mut todo = vec![(false, node)];
+        while let Some((ready, n)) = todo.pop() {
+            if done.contains_key(&n) {
+                continue;
+            }
+            let op = self.ctx.get_op(n).unwrap();
+            if !ready {
+                self.budget.visit()?;
+                todo.push((true, n));
+                // Noise has a fixed range, so we don't need its arguments
+                if !matches!(op, Op::Noise(..)) {
+                    todo.extend(op.iter_children().map(|c| (false, c)));
+                }
+                continue;
+            }
+            let get = |c: &Node| done[c];
+            let r = match op {
+                Op::Input(v) => match self.axis(*v) {
+                    Some(i) => axes[i],
+                    None => Range::ALL,
+                },
+                Op::Var(..) => Range::ALL,
+                Op::Const(c) => Range::new(c.0, c.0),
+                Op::Binary(op, a, b) => {
+                    let (a, b) = (get(a), get(b));
+                    match op {
+                        BinaryOpcode::Add => a.add(b),
+                        BinaryOpcode::Sub => a.sub(b),
+                        BinaryOpcode::Mul => a.mul(b),
+                        BinaryOpcode::Div => a.div(b),
+                        BinaryOpcode::Min | BinaryOpcode::MinNc => {
+                            Range::new(a.lo.min(b.lo), a.hi.min(b.hi))
+                        }
+                        BinaryOpcode::Max | BinaryOpcode::MaxNc => {
+                            Range::new(a.lo.max(b.lo), a.hi.max(b.hi))
+                        }
+                        BinaryOpcode::Atan2 => {
+                            use core::f64::consts::PI;
+                            Range::new(-PI, PI)
+                        }
+                        BinaryOpcode::Hypot => {
+                            let (a, b) = (a.abs(), b.abs());
+                            Range::new(a.lo.hypot(b.lo), a.hi.hypot(b.hi))
+                        }
+                        // The result lies between zero and the divisor
+                        BinaryOpcode::Mod => {
+                            Range::new(b.lo.min(0.0), b.hi.max(0.0))
+                        }
+                    }
+                }
+                Op::Unary(op, a) => {
+                    let a = get(a);
+                    match op {
+                        UnaryOpcode::Neg => a.neg(),
+                        UnaryOpcode::Abs => a.abs(),
+                        UnaryOpcode::Recip => a.recip(),
+                        UnaryOpcode::Sqrt => a.sqrt(),
+                        UnaryOpcode::Square => a.square(),
+                        UnaryOpcode::Sin | UnaryOpcode::Cos => {
+                            Range::new(-1.0, 1.0)
+                        }
+                    }
+                }
+                Op::Custom(c, a, b) => {
+                    let (a, b) = (get(a), get(b));
+                    let f = |r: Range| Interval::new(r.lo as f32, r.hi as f32);
+                    let out = self.ctx.custom[c.0].eval_interval(f(a), f(b));
+                    Range::new(out.lower() as f64, out.upper() as f64)
+                }
+                Op::Noise(..) => Range::new(-1.0, 1.0),
+                Op::Clamp(x, lo, hi) => {
+                    let (x, lo, hi) = (get(x), get(lo), get(hi));
+                    Range::new(
+                        x.lo.min(hi.lo).max(lo.lo),
+                        x.hi.min(hi.hi).max(lo.hi),
+                    )
+                }
+                Op::Nary(op, args) => {
+                    let rs = args.iter().map(get);
+                    match op {
+                        NaryOpcode::Min => rs.reduce(|a, b| {
+                            Range::new(a.lo.min(b.lo), a.hi.min(b.hi))
+                        }),
+                        NaryOpcode::Max => rs.reduce(|a, b| {
+                            Range::new(a.lo.max(b.lo), a.hi.max(b.hi))
+                        }),
+                    }
+                    .unwrap()
+                }
+            };
+            done.insert(n, r);
+        }
+        let out = done[&node];
+        self.cache = done;
+        Ok(out)
+    }
+
+    /// Returns the axis index of an input variable, or `None`
+    fn axis(&self, v: VarNode) -> Option<usize> {
+        match self.ctx.get_var_by_index(v).ok()? {
+            "X" => Some(0),
+            "Y" => Some(1),
+            "Z" => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Returns the arguments of a tree of nested `max` operations
+    fn max_args(&self, node: Node) -> Vec<Node> {
+        let mut out = vec![];
+        let mut todo = vec![node];
+        while let Some(n) = todo.pop() {
+            match self.ctx.get_op(n).unwrap() {
+                Op::Binary(BinaryOpcode::Max | BinaryOpcode::MaxNc, a, b) => {
+                    todo.push(*b);
+                    todo.push(*a);
+                }
+                Op::Nary(NaryOpcode::Max, args) => {
+                    todo.extend(args.iter().rev());
+                }
+                _ => out.push(n),
+            }
+        }
+        out
+    }
+
+    /// Narrows `axes` to the region where `node` could be within `target`
+    ///
+    /// Returns `false` if there is no such region.
+    fn contract(
+        &mut self,
+        node: Node,
+        target: Range,
+        axes: &mut Axes,
+    ) -> Result<bool, Error> {
+        // Depth-first recursion on the heap, to protect against stack
+        // overflows.  Each task leaves its result in `ok`, which is then used
+        // by the continuation below it on the stack.
+        let mut todo = vec![Task::Contract(node, target)];
+        let mut ok = true;
+        while let Some(task) = todo.pop() {
+            match task {
+                Task::Contract(node, target) => {
+                    if let Some(r) =
+                        self.contract_node(node, target, axes, &mut todo)?
+                    {
+                        ok = r;
+                    }
+                }
+                Task::Second(..) | Task::And(..) | Task::Max { .. } if !ok => {}
+                Task::Second(op, a, b, target) => {
+                    let ra = self.bounds_range(a, axes)?;
+                    match op {
+                        BinaryOpcode::Add => {
+                            todo.push(Task::Contract(b, target.sub(ra)))
+                        }
+                        BinaryOpcode::Sub => {
+                            todo.push(Task::Contract(b, ra.sub(target)))
+                        }
+                        // We can only divide through by a range without zero
+                        BinaryOpcode::Mul if ra.contains_zero() => (),
+                        BinaryOpcode::Mul => {
+                            todo.push(Task::Contract(b, target.div(ra)))
+                        }
+                        _ => unreachable!("invalid opcode {op:?}"),
+                    }
+                }
+                Task::And(node, target) => {
+                    todo.push(Task::Contract(node, target))
+                }
+                Task::MinRight(b, target, start) => {
+                    todo.push(Task::MinJoin(*axes, ok));
+                    *axes = start;
+                    todo.push(Task::Contract(b, target));
+                }
+                Task::MinJoin(left, left_ok) => match (left_ok, ok) {
+                    (true, true) => {
+                        for i in 0..3 {
+                            axes[i] = left[i].hull(axes[i]);
+                        }
+                    }
+                    (true, false) => {
+                        *axes = left;
+                        ok = true;
+                    }
+                    // The result (and region) of the right side are used as-is
+                    (false, _) => (),
+                },
+                Task::Max {
+                    args,
+                    below,
+                    pass,
+                    next,
+                    prev,
+                } => {
+                    if let Some(&n) = args.get(next) {
+                        todo.push(Task::Max {
+                            args,
+                            below,
+                            pass,
+                            next: next + 1,
+                            prev,
+                        });
+                        todo.push(Task::Contract(n, below));
+                    } else if prev != *axes
+                        && pass + 1 < BOUNDS_PASSES
+                        && self.fuel > 0
+                    {
+                        let n = args[0];
+                        todo.push(Task::Max {
+                            args,
+                            below,
+                            pass: pass + 1,
+                            next: 1,
+                            prev: *axes,
+                        });
+                        todo.push(Task::Contract(n, below));
+                    }
+                }
+            }
+        }
+        Ok(ok)
+    }
+
+    /// Performs a single step of [`contract`](Self::contract)
+    ///
+    /// Returns the result if it's known immediately; otherwise, pushes tasks
+    /// to `todo` and returns `None`.  Returns [`Error::TooManyNodes`] if the
+    /// analysis has visited too many nodes.
+    fn contract_node(
+        &mut self,
+        node: Node,
+        target: Range,
+        axes: &mut Axes,
+        todo: &mut Vec<Task>,
+    ) -> Result<Option<bool>, Error> {
+        self.budget.visit()?;
+        self.fuel = self.fuel.saturating_sub(1);
+        if target.hi <= 0.0 {
+            if let Some(b) = self.ctx.bounds.get(&node) {
+                for (i, r) in axes.iter_mut().enumerate() {
+                    *r = r.intersection(b.range(i));
+                }
+            }
+        }
+        if axes.iter().any(Range::is_empty) {
+            return Ok(Some(false));
+        }
+        let op = self.ctx.get_op(node).unwrap().clone();
+
+        // Min and max nodes pass the target through to their children, so we
+        // skip evaluating their (potentially large) subtrees here.
+        let target = if matches!(
+            op,
+            Op::Binary(BinaryOpcode::Min | BinaryOpcode::Max, ..)
+                | Op::Nary(..)
+        ) {
+            target
+        } else {
+            let r = self.bounds_range(node, axes)?;
+            if r.lo >= target.lo && r.hi <= target.hi {
+                return Ok(Some(true)); // the entire region is within the target
+            }
+            target.intersection(r)
+        };
+        if target.is_empty() {
+            return Ok(Some(false));
+        }
+
+        match op {
+            Op::Input(v) => {
+                if let Some(i) = self.axis(v) {
+                    axes[i] = axes[i].intersection(target);
+                }
+                Ok(Some(true))
+            }
+            Op::Var(..) | Op::Const(..) => Ok(Some(true)),
+            // We don't know how to invert a user-defined operation, noise
+            // doesn't constrain its arguments, and a clamp's result may come
+            // from any of its arguments
+            Op::Custom(..) | Op::Noise(..) | Op::Clamp(..) => Ok(Some(true)),
+            Op::Nary(NaryOpcode::Min, args) => {
+                // As with a binary `min`, we take the union of the regions
+                // found for each argument, joining them one at a time.
+                for &b in args[1..].iter().rev() {
+                    todo.push(Task::MinRight(b, target, *axes));
+                }
+                todo.push(Task::Contract(args[0], target));
+                Ok(None)
+            }
+            Op::Nary(NaryOpcode::Max, ..) => {
+                self.contract_max(node, target, axes, todo);
+                Ok(None)
+            }
+            Op::Binary(op, a, b) => match op {
+                BinaryOpcode::Add | BinaryOpcode::Sub | BinaryOpcode::Mul => {
+                    // The second argument's target depends on the first
+                    // argument's range, which is found after narrowing
+                    todo.push(Task::Second(op, a, b, target));
+                    let rb = self.bounds_range(b, axes)?;
+                    let t = match op {
+                        BinaryOpcode::Add => target.sub(rb),
+                        BinaryOpcode::Sub => target.add(rb),
+                        // We can only divide through by a range without zero
+                        _ if rb.contains_zero() => return Ok(Some(true)),
+                        _ => target.div(rb),
+                    };
+                    todo.push(Task::Contract(a, t));
+                    Ok(None)
+                }
+                BinaryOpcode::Div => {
+                    let rb = self.bounds_range(b, axes)?;
+                    todo.push(Task::Contract(a, target.mul(rb)));
+                    Ok(None)
+                }
+                BinaryOpcode::Min | BinaryOpcode::MinNc => {
+                    // Either side may be within the target range, so we take
+                    // the union of the regions found for each side.
+                    todo.push(Task::MinRight(b, target, *axes));
+                    todo.push(Task::Contract(a, target));
+                    Ok(None)
+                }
+                BinaryOpcode::Max | BinaryOpcode::MaxNc => {
+                    self.contract_max(node, target, axes, todo);
+                    Ok(None)
+                }
+                // Neither the angle nor a periodic result constrains the
+                // arguments
+                BinaryOpcode::Atan2 | BinaryOpcode::Mod => Ok(Some(true)),
+                BinaryOpcode::Hypot => {
+                    // Each argument's magnitude is at most the result
+                    let t = Range::new(-target.hi, target.hi);
+                    todo.push(Task::And(b, t));
+                    todo.push(Task::Contract(a, t));
+                    Ok(None)
+                }
+            },
+            Op::Unary(op, a) => {
+                let t = match op {
+                    UnaryOpcode::Neg => target.neg(),
+                    UnaryOpcode::Abs => Range::new(-target.hi, target.hi),
+                    UnaryOpcode::Recip if !target.contains_zero() => {
+                        target.recip()
+                    }
+                    UnaryOpcode::Recip => Range::ALL,
+                    UnaryOpcode::Sqrt => {
+                        let t =
+                            target.intersection(Range::new(0.0, f64::INFINITY));
+                        Range::new(t.lo * t.lo, t.hi * t.hi)
+                    }
+                    UnaryOpcode::Square => {
+                        let h = target.hi.sqrt();
+                        Range::new(-h, h)
+                    }
+                    // Periodic functions don't constrain their argument
+                    UnaryOpcode::Sin | UnaryOpcode::Cos => Range::ALL,
+                };
+                todo.push(Task::Contract(a, t));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Pushes tasks to contract a tree of `max` operations rooted at `node`
+    fn contract_max(
+        &self,
+        node: Node,
+        target: Range,
+        axes: &Axes,
+        todo: &mut Vec<Task>,
+    ) {
+        // Every argument must be below the target's upper bound (and one must
+        // be above its lower bound, which we ignore)
+        let below = Range::new(f64::NEG_INFINITY, target.hi);
+        let args = self.max_args(node);
+        let first = args[0];
+
+        // Narrowing one argument's region may let us narrow the others (e.g. if
+        // the constraint on Y depends on X), so we repeat until the region
+        // stops changing.
+        todo.push(Task::Max {
+            args,
+            below,
+            pass: 0,
+            next: 1,
+            prev: *axes,
+        });
+        todo.push(Task::Contract(first, below));
+    }
+}
+
+/// Pending work for [`Bounder::contract`]
+///
+/// Every variant except `Contract` is a continuation, which runs after the
+/// tasks above it on the stack have finished and left their result in `ok`.
+enum Task {
+    /// Narrows the region to where a node could be within a target range
+    Contract(Node, Range),
+    /// If the first argument of an `add`, `sub`, or `mul` node succeeded,
+    /// narrows its second argument (with a target based on the first)
+    Second(BinaryOpcode, Node, Node, Range),
+    /// If the previous task succeeded, narrows the region for another node
+    And(Node, Range),
+    /// Narrows the region for the right side of a `min` node, starting from
+    /// the region before the left side was narrowed
+    MinRight(Node, Range, Axes),
+    /// Takes the union of the regions (and results) for each side of a `min`
+    /// node, given the region and result from the left side
+    MinJoin(Axes, bool),
+    /// If the previous argument succeeded, narrows the next argument of a
+    /// `max` tree, starting a new pass if the region changed
+    Max {
+        args: Vec<Node>,
+        below: Range,
+        pass: usize,
+        next: usize,
+        prev: Axes,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sphere_bounds() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x = ctx.sub(x, 1.0).unwrap();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let z2 = ctx.square(z).unwrap();
+        let r = ctx.add(x2, y2).unwrap();
+        let r = ctx.add(r, z2).unwrap();
+        let r = ctx.sqrt(r).unwrap();
+        let sphere = ctx.sub(r, 0.5).unwrap();
+
+        let b = ctx.bounds(sphere).unwrap();
+        assert_eq!(b.lower, [0.5, -0.5, -0.5]);
+        assert_eq!(b.upper, [1.5, 0.5, 0.5]);
+        assert!(b.is_finite());
+
+        // Scaling preserves bounds
+        let scaled = ctx.mul(sphere, 2.0).unwrap();
+        assert_eq!(ctx.bounds(scaled).unwrap(), b);
+
+        // Intersections narrow bounds
+        let x = ctx.x();
+        let y = ctx.y();
+        let plane = ctx.sub(y, x).unwrap(); // y <= x
+        let inter = ctx.max(sphere, plane).unwrap();
+        assert_eq!(ctx.bounds(inter).unwrap(), b);
+
+        let half = ctx.add(x, 2.0).unwrap(); // x <= -2
+        let inter = ctx.max(half, sphere).unwrap();
+        assert!(ctx.bounds(inter).unwrap().is_empty());
+
+        // Unions take the hull of both sides
+        let wall = ctx.max(half, plane).unwrap();
+        let union = ctx.min(sphere, wall).unwrap();
+        let b = ctx.bounds(union).unwrap();
+        assert_eq!(b.lower, [f64::NEG_INFINITY; 3]);
+        assert_eq!(b.upper, [1.5, 0.5, f64::INFINITY]);
+
+        // Complementing a shape makes it unbounded
+        let neg = ctx.neg(sphere).unwrap();
+        assert_eq!(ctx.bounds(neg).unwrap(), BoundingBox::INFINITE);
+    }
+
+    #[test]
+    fn test_hypot_bounds() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 2.0).unwrap();
+        let b = ctx.bounds(circle).unwrap();
+        assert_eq!(b.lower, [-2.0, -2.0, f64::NEG_INFINITY]);
+        assert_eq!(b.upper, [2.0, 2.0, f64::INFINITY]);
+
+        // A twist around the Z axis doesn't change the bounds
+        let a = ctx.atan2(y, x).unwrap();
+        let twist = ctx.mul(a, 0.1).unwrap();
+        let twisted = ctx.add(circle, twist).unwrap();
+        let slab = ctx.abs(z).unwrap();
+        let slab = ctx.sub(slab, 1.0).unwrap();
+        let shape = ctx.max(twisted, slab).unwrap();
+        let b = ctx.bounds(shape).unwrap();
+        assert!(b.is_finite());
+        assert!(b.upper[0] > 2.0 && b.upper[0] < 2.5);
+        assert_eq!(b.upper[2], 1.0);
+    }
+
+    #[test]
+    fn test_declared_bounds() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let s = ctx.mul(x, y).unwrap();
+        assert_eq!(ctx.bounds(s).unwrap(), BoundingBox::INFINITE);
+
+        let b = BoundingBox::new([-1.0; 3], [1.0; 3]);
+        ctx.set_bounds(s, b).unwrap();
+        assert_eq!(ctx.bounds(s).unwrap(), b);
+
+        // Declared bounds are used when analyzing parent nodes
+        let z = ctx.z();
+        let z = ctx.sub(z, 0.5).unwrap();
+        let inter = ctx.max(s, z).unwrap();
+        let b = ctx.bounds(inter).unwrap();
+        assert_eq!(b.lower, [-1.0; 3]);
+        assert_eq!(b.upper, [1.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_deep_bounds() {
+        // A long chain of operations doesn't overflow the stack, and is
+        // analyzed all the way down to its inputs
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x = ctx.abs(x).unwrap();
+        let y = ctx.abs(y).unwrap();
+        let mut s = ctx.max(x, y).unwrap();
+        for _ in 0..100_000 {
+            s = ctx.add(s, 0.5).unwrap();
+            s = ctx.sub(s, 0.5).unwrap();
+        }
+        let square = ctx.sub(s, 1.0).unwrap();
+
+        let b = ctx.bounds(square).unwrap();
+        assert_eq!(b.lower, [-1.0, -1.0, f64::NEG_INFINITY]);
+        assert_eq!(b.upper, [1.0, 1.0, f64::INFINITY]);
+    }
+}
@@ -3,7 +3,8 @@ use crate::{
     eval::{Family, Tape},
     Error,
 };
-use std::{cell::RefCell, rc::Rc};
+use alloc::rc::Rc;
+use core::cell::RefCell;
 
 /// Shareable context used in a [`BoundNode`]
 ///
@@ -12,14 +13,14 @@ use std::{cell::RefCell, rc::Rc};
 #[derive(Clone, Debug)]
 pub struct BoundContext(Rc<RefCell<Context>>);
 
-impl std::ops::Deref for BoundContext {
+impl core::ops::Deref for BoundContext {
     type Target = Rc<RefCell<Context>>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl std::ops::DerefMut for BoundContext {
+impl core::ops::DerefMut for BoundContext {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
@@ -71,7 +72,7 @@ pub struct BoundNode {
     ctx: BoundContext,
 }
 
-impl std::cmp::PartialEq for BoundNode {
+impl core::cmp::PartialEq for BoundNode {
     fn eq(&self, other: &Self) -> bool {
         self.node == other.node && self.ctx.as_ptr() == other.ctx.as_ptr()
     }
@@ -149,14 +150,14 @@ impl BoundNode {
 
 macro_rules! impl_binary {
     ($op:ident, $assign: ident, $base_fn:ident, $assign_fn:ident) => {
-        impl<A: IntoNode> std::ops::$op<A> for BoundNode {
+        impl<A: IntoNode> core::ops::$op<A> for BoundNode {
             type Output = Self;
 
             fn $base_fn(self, other: A) -> Self {
                 self.op_bin(other, Context::$base_fn)
             }
         }
-        impl std::ops::$op<BoundNode> for f32 {
+        impl core::ops::$op<BoundNode> for f32 {
             type Output = BoundNode;
 
             fn $base_fn(self, other: BoundNode) -> Self::Output {
@@ -167,7 +168,7 @@ macro_rules! impl_binary {
                 lhs.op_bin(other, Context::$base_fn)
             }
         }
-        impl<A: IntoNode> std::ops::$assign<A> for BoundNode {
+        impl<A: IntoNode> core::ops::$assign<A> for BoundNode {
             fn $assign_fn(&mut self, other: A) {
                 let lhs = self.clone();
                 self.node = lhs.op_bin(other, Context::$base_fn).node;
@@ -190,10 +191,10 @@ mod test {
     #[test]
     fn test_bound_node() {
         let (x, y, z) = BoundContext::new().axes();
-        assert_eq!(x.node.0, 0);
-        assert_eq!(y.node.0, 1);
-        assert_eq!(z.node.0, 2);
+        assert_eq!(x.node.index(), 0);
+        assert_eq!(y.node.index(), 1);
+        assert_eq!(z.node.index(), 2);
         let n = x + y + z + 1.0;
-        assert_eq!(n.node.0, 6);
+        assert_eq!(n.node.index(), 6);
     }
 }
@@ -0,0 +1,381 @@
+//! GraphViz export for a [`Context`]
+use super::{
+    BinaryOpcode, Context, NaryOpcode, Node, NodeBudget, Op, UnaryOpcode,
+};
+use crate::Error;
+
+use alloc::{
+    borrow::ToOwned,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, VecDeque},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+/// Builder for a GraphViz drawing of a [`Context`]
+///
+/// This is constructed with [`Context::dot_builder`].  By default, it draws
+/// every node in the context (like [`Context::dot`]); options can be used to
+/// make drawings of large models more readable.
+///
+/// ```
+/// # let mut ctx = fidget::context::Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let a = ctx.sub(x, 1.0).unwrap();
+/// let b = ctx.add(y, 2.0).unwrap();
+/// ctx.name(b, "b").unwrap();
+/// let m = ctx.min(a, b).unwrap();
+///
+/// let dot = ctx
+///     .dot_builder()
+///     .root(m)
+///     .collapse_constants(true)
+///     .highlight_choices(true)
+///     .cluster_names(true)
+///     .build()
+///     .unwrap();
+/// assert!(dot.contains("subgraph cluster_0"));
+/// assert!(dot.contains(r#"label = "sub(_, 1)""#));
+/// ```
+#[derive(Clone)]
+pub struct DotBuilder<'a> {
+    ctx: &'a Context,
+    roots: Vec<Node>,
+    max_depth: Option<usize>,
+    collapse_constants: bool,
+    highlight_choices: bool,
+    cluster_names: bool,
+    /// Ignores [`Context::node_limit`], for [`Context::dot`]
+    unlimited: bool,
+}
+
+impl<'a> DotBuilder<'a> {
+    fn new(ctx: &'a Context) -> Self {
+        Self {
+            ctx,
+            roots: vec![],
+            max_depth: None,
+            collapse_constants: false,
+            highlight_choices: false,
+            cluster_names: false,
+            unlimited: false,
+        }
+    }
+
+    /// Adds a root node to the drawing
+    ///
+    /// If any roots are given, only nodes reachable from them are drawn;
+    /// otherwise, the drawing includes the entire context.
+    pub fn root(mut self, node: Node) -> Self {
+        self.roots.push(node);
+        self
+    }
+
+    /// Limits the drawing to nodes within `depth` steps of a root
+    ///
+    /// Nodes with children beyond this depth are drawn with dashed outlines.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Folds constants into the labels of the operations which use them
+    ///
+    /// Constants are otherwise drawn as separate nodes, which (because they're
+    /// deduplicated) can produce long edges across the whole drawing.
+    pub fn collapse_constants(mut self, enable: bool) -> Self {
+        self.collapse_constants = enable;
+        self
+    }
+
+    /// Draws `min` and `max` nodes (which make choices during interval
+    /// evaluation) filled in, so that they stand out
+    pub fn highlight_choices(mut self, enable: bool) -> Self {
+        self.highlight_choices = enable;
+        self
+    }
+
+    /// Groups nodes into clusters based on their names
+    ///
+    /// Each node named with [`Context::name`] starts a cluster, which also
+    /// contains unnamed descendants (following the same rules as debug symbols
+    /// in tapes: a node inherits the name of the first named ancestor which
+    /// reaches it).
+    pub fn cluster_names(mut self, enable: bool) -> Self {
+        self.cluster_names = enable;
+        self
+    }
+
+    /// Builds the drawing
+    ///
+    /// Returns [`Error::BadNode`] if any of the roots is invalid, or
+    /// [`Error::TooManyNodes`] if the drawing would have more than
+    /// [`Context::node_limit`] nodes.
+    pub fn build(&self) -> Result<String, Error> {
+        let ctx = self.ctx;
+        let mut roots = self.roots.clone();
+        for r in &roots {
+            ctx.check_node(*r)?;
+        }
+        if roots.is_empty() {
+            let children: BTreeSet<Node> = ctx
+                .ops
+                .keys()
+                .flat_map(|n| ctx.get_op(n).unwrap().iter_children())
+                .collect();
+            roots = ctx.ops.keys().filter(|n| !children.contains(n)).collect();
+        }
+
+        // Breadth-first search, so that each node is found at its minimum
+        // depth (and inherits a cluster from its closest named ancestor)
+        let mut seen: BTreeMap<Node, Option<&str>> = BTreeMap::new();
+        let mut todo = VecDeque::new();
+        let mut budget = if self.unlimited {
+            NodeBudget::unlimited()
+        } else {
+            NodeBudget::new(ctx)
+        };
+        for r in roots {
+            if let Entry::Vacant(e) = seen.entry(r) {
+                budget.visit()?;
+                e.insert(ctx.names.get(&r).map(String::as_str));
+                todo.push_back((r, 0));
+            }
+        }
+        while let Some((node, depth)) = todo.pop_front() {
+            if self.max_depth.is_some_and(|d| depth >= d) {
+                continue;
+            }
+            let cluster = seen[&node];
+            for c in ctx.get_op(node).unwrap().iter_children() {
+                if self.is_collapsed(c) {
+                    continue;
+                }
+                if let Entry::Vacant(e) = seen.entry(c) {
+                    budget.visit()?;
+                    let name = ctx.names.get(&c).map(String::as_str);
+                    e.insert(name.or(cluster));
+                    todo.push_back((c, depth + 1));
+                }
+            }
+        }
+
+        let mut out = "digraph mygraph{\n".to_owned();
+        let mut clusters: BTreeMap<&str, Vec<Node>> = BTreeMap::new();
+        for (node, cluster) in &seen {
+            match cluster.filter(|_| self.cluster_names) {
+                Some(name) => clusters.entry(name).or_default().push(*node),
+                None => out += &self.dot_node(*node, &seen),
+            }
+        }
+        for (i, (name, nodes)) in clusters.iter().enumerate() {
+            writeln!(out, "subgraph cluster_{i} {{").unwrap();
+            writeln!(out, "label = \"{}\"", escape(name)).unwrap();
+            for n in nodes {
+                out += &self.dot_node(*n, &seen);
+            }
+            out += "}\n";
+        }
+        for node in seen.keys() {
+            let op = ctx.get_op(*node).unwrap();
+            for c in op.iter_children() {
+                if seen.contains_key(&c) && !self.is_collapsed(c) {
+                    out += &op.dot_edge(*node, c, "FF");
+                }
+            }
+        }
+        out += "}\n";
+        Ok(out)
+    }
+
+    /// Checks whether the given node is folded into its parents' labels
+    fn is_collapsed(&self, node: Node) -> bool {
+        self.collapse_constants
+            && matches!(self.ctx.get_op(node), Some(Op::Const(..)))
+    }
+
+    /// Converts the given node into a GraphViz node
+    ///
+    /// `seen` is the set of nodes being drawn, which is used to mark nodes
+    /// whose children are cut off by [`max_depth`](Self::max_depth).
+    fn dot_node<T>(&self, i: Node, seen: &BTreeMap<Node, T>) -> String {
+        let ctx = self.ctx;
+        let op = ctx.get_op(i).unwrap();
+        let truncated = op
+            .iter_children()
+            .any(|c| !self.is_collapsed(c) && !seen.contains_key(&c));
+        let mut label = match op {
+            Op::Const(c) => c.to_string(),
+            Op::Var(v) | Op::Input(v) => {
+                ctx.vars.get_by_index(*v).unwrap().to_owned()
+            }
+            Op::Binary(op, ..) => match op {
+                BinaryOpcode::Add => "add",
+                BinaryOpcode::Sub => "sub",
+                BinaryOpcode::Mul => "mul",
+                BinaryOpcode::Div => "div",
+                BinaryOpcode::Min => "min",
+                BinaryOpcode::Max => "max",
+                BinaryOpcode::MinNc => "min_nc",
+                BinaryOpcode::MaxNc => "max_nc",
+                BinaryOpcode::Atan2 => "atan2",
+                BinaryOpcode::Hypot => "hypot",
+                BinaryOpcode::Mod => "mod",
+            }
+            .to_owned(),
+            Op::Unary(op, ..) => match op {
+                UnaryOpcode::Neg => "neg",
+                UnaryOpcode::Abs => "abs",
+                UnaryOpcode::Recip => "recip",
+                UnaryOpcode::Sqrt => "sqrt",
+                UnaryOpcode::Square => "square",
+                UnaryOpcode::Sin => "sin",
+                UnaryOpcode::Cos => "cos",
+            }
+            .to_owned(),
+            Op::Custom(c, ..) => ctx.custom[c.0].name().to_owned(),
+            Op::Noise(seed, ..) => format!("noise[{seed}]"),
+            Op::Clamp(..) => "clamp".to_owned(),
+            Op::Nary(op, ..) => match op {
+                NaryOpcode::Min => "min_many",
+                NaryOpcode::Max => "max_many",
+            }
+            .to_owned(),
+        };
+        if op.iter_children().any(|c| self.is_collapsed(c)) {
+            let args = op
+                .iter_children()
+                .map(|c| match ctx.const_value(c).unwrap() {
+                    Some(v) if self.collapse_constants => v.to_string(),
+                    _ => "_".to_owned(),
+                })
+                .collect::<Vec<_>>();
+            write!(label, "({})", args.join(", ")).unwrap();
+        }
+        if truncated {
+            label += " ...";
+        }
+
+        let mut style = vec![];
+        if truncated {
+            style.push("dashed");
+        }
+        let choice = matches!(
+            op,
+            Op::Binary(BinaryOpcode::Min | BinaryOpcode::Max, ..)
+                | Op::Nary(..)
+        );
+        if choice && self.highlight_choices {
+            style.push("filled");
+        }
+
+        let mut out = format!(
+            r#"n{} [label = "{}" color="{2}1" shape="{3}" fontcolor="{2}4""#,
+            i.index(),
+            escape(&label),
+            op.dot_node_color(),
+            op.dot_node_shape()
+        );
+        if !style.is_empty() {
+            write!(out, r#" style="{}""#, style.join(",")).unwrap();
+        }
+        if choice && self.highlight_choices {
+            write!(out, r#" fillcolor="{}3""#, op.dot_node_color()).unwrap();
+        }
+        out += "]\n";
+        out
+    }
+}
+
+/// Escapes double quotes, so that the string can be used as a label
+fn escape(s: &str) -> String {
+    s.replace('"', "\\\"")
+}
+
+impl Context {
+    /// Converts the entire context into a GraphViz drawing
+    ///
+    /// Unlike [`Context::dot_builder`], this ignores [`Context::node_limit`].
+    pub fn dot(&self) -> String {
+        let mut builder = self.dot_builder();
+        builder.unlimited = true;
+        // Without roots or a node limit, building can't fail
+        builder.build().unwrap()
+    }
+
+    /// Returns a builder for a customized GraphViz drawing
+    ///
+    /// See [`DotBuilder`] for options.
+    pub fn dot_builder(&self) -> DotBuilder<'_> {
+        DotBuilder::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dot_depth() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.square(x).unwrap();
+        let b = ctx.sqrt(a).unwrap();
+        let c = ctx.add(b, y).unwrap();
+
+        let full = ctx.dot();
+        assert_eq!(full.matches("->").count(), 4);
+        assert_eq!(full.matches("[label").count(), 5);
+
+        let d = ctx.dot_builder().root(c).max_depth(1).build().unwrap();
+        assert_eq!(d.matches("[label").count(), 3); // c, b, y
+        assert!(d.contains(r#"label = "sqrt ...""#));
+        assert!(d.contains(r#"style="dashed""#));
+        assert_eq!(d.matches("->").count(), 2);
+
+        // `a` is a root, so `b` isn't truncated
+        let d = ctx.dot_builder().root(c).root(a).max_depth(1);
+        let d = d.build().unwrap();
+        assert_eq!(d.matches("[label").count(), 5);
+        assert!(!d.contains("sqrt ..."));
+        assert!(d.contains(r#"label = "Y""#));
+    }
+
+    #[test]
+    fn test_dot_options() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let a = ctx.sub(2.0, x).unwrap();
+        ctx.name(a, "left").unwrap();
+        let b = ctx.max(a, x).unwrap();
+
+        let d = ctx.dot_builder().build().unwrap();
+        assert!(d.contains(r#"label = "2""#));
+        assert!(!d.contains("subgraph"));
+        assert!(!d.contains("filled"));
+
+        let d = ctx
+            .dot_builder()
+            .root(b)
+            .collapse_constants(true)
+            .highlight_choices(true)
+            .cluster_names(true)
+            .build()
+            .unwrap();
+        assert!(!d.contains(r#"label = "2""#));
+        assert!(d.contains(r#"label = "sub(2, _)""#));
+        assert!(d.contains(r#"style="filled" fillcolor="dodgerblue3""#));
+        assert!(d.contains("subgraph cluster_0 {\nlabel = \"left\""));
+
+        let mut other = Context::new();
+        let z = other.z();
+        assert!(matches!(
+            ctx.dot_builder().root(z).build(),
+            Err(Error::BadNode)
+        ));
+    }
+}
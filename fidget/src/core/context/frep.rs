@@ -0,0 +1,529 @@
+//! Compact binary interchange format for math expressions
+//!
+//! A `.frep` file stores a set of named expressions, sharing common
+//! subexpressions, along with an optional bounding box.  It's intended for
+//! exchanging shapes between tools (e.g. the CLI and viewer, or third-party
+//! exporters), so it is precisely specified and versioned.
+//!
+//! All integers and floats are little-endian.  A file contains
+//!
+//! - The magic bytes `FIDGFREP`, followed by the format version as a `u32`
+//!   (currently [`FREP_VERSION`])
+//! - A flags byte; bit 0 indicates that a bounding box follows
+//! - If present, the bounding box, as six `f64` values (lower X, Y, Z, then
+//!   upper X, Y, Z)
+//! - The variable table: a `u32` count, then each variable's name
+//! - The node list: a `u32` count, then each node's opcode byte and arguments
+//! - The root table: a `u32` count, then each root's name and node index
+//!
+//! Strings are stored as a `u32` byte length followed by UTF-8 data.  Nodes
+//! refer to their arguments by `u32` index into the node list, and arguments
+//! must precede the nodes which use them.  Opcodes and their arguments are
+//!
+//! | Opcode      | Operation                  | Arguments                   |
+//! |-------------|----------------------------|-----------------------------|
+//! | `0..=2`     | X, Y, Z                    |                             |
+//! | `3`         | Variable                   | `u32` index into var table  |
+//! | `4`         | Constant                   | `f64` value                 |
+//! | `16..=22`   | neg, abs, recip, sqrt, square, sin, cos | one node       |
+//! | `32..=39`   | add, sub, mul, div, min, max, atan2, hypot | two nodes   |
+//! | `40..=41`   | min_nc, max_nc             | two nodes                   |
+//! | `42`        | mod                        | two nodes                   |
+//! | `48`        | Noise                      | `u16` seed, three nodes     |
+//! | `49`        | Clamp                      | three nodes (x, lo, hi)     |
+//! | `50..=51`   | min_many, max_many         | `u32` count, then nodes     |
+//!
+//! Custom operations can't be stored in a `.frep` file.
+use super::{
+    BinaryOpcode, BoundingBox, Context, NaryOpcode, Node, Op, UnaryOpcode,
+};
+use crate::Error;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+/// Header at the start of a `.frep` file
+const MAGIC: &[u8; 8] = b"FIDGFREP";
+
+/// Current version of the `.frep` format
+pub const FREP_VERSION: u32 = 1;
+
+const UNARY: [UnaryOpcode; 7] = [
+    UnaryOpcode::Neg,
+    UnaryOpcode::Abs,
+    UnaryOpcode::Recip,
+    UnaryOpcode::Sqrt,
+    UnaryOpcode::Square,
+    UnaryOpcode::Sin,
+    UnaryOpcode::Cos,
+];
+
+const BINARY: [BinaryOpcode; 11] = [
+    BinaryOpcode::Add,
+    BinaryOpcode::Sub,
+    BinaryOpcode::Mul,
+    BinaryOpcode::Div,
+    BinaryOpcode::Min,
+    BinaryOpcode::Max,
+    BinaryOpcode::Atan2,
+    BinaryOpcode::Hypot,
+    BinaryOpcode::MinNc,
+    BinaryOpcode::MaxNc,
+    BinaryOpcode::Mod,
+];
+
+const UNARY_BASE: u8 = 16;
+const BINARY_BASE: u8 = 32;
+const NOISE: u8 = 48;
+const CLAMP: u8 = 49;
+const MIN_MANY: u8 = 50;
+const MAX_MANY: u8 = 51;
+
+/// Metadata stored alongside the expressions in a `.frep` file
+///
+/// See [`Context::write_frep`] and [`Context::read_frep`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frep {
+    /// Named root expressions, in file order
+    pub roots: Vec<(String, Node)>,
+    /// Optional bounding box of the shapes
+    pub bounds: Option<BoundingBox>,
+}
+
+impl Frep {
+    /// Looks up a root by name
+    pub fn root(&self, name: &str) -> Option<Node> {
+        self.roots
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, node)| *node)
+    }
+}
+
+impl Context {
+    /// Writes the given roots (and the nodes they use) in `.frep` format
+    ///
+    /// See the [module documentation](crate::context::frep) for a
+    /// description of the format.  Only nodes reachable from the roots are
+    /// written.
+    ///
+    /// ```
+    /// use fidget::context::{frep::Frep, Context};
+    ///
+    /// let mut ctx = Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let r = ctx.hypot(x, y)?;
+    /// let circle = ctx.sub(r, 0.5)?;
+    ///
+    /// let frep = Frep {
+    ///     roots: vec![("circle".to_owned(), circle)],
+    ///     bounds: Some(ctx.bounds(circle)?),
+    /// };
+    /// let mut data = vec![];
+    /// ctx.write_frep(&frep, &mut data)?;
+    ///
+    /// let (ctx, frep) = Context::read_frep(&mut data.as_slice())?;
+    /// let circle = frep.root("circle").unwrap();
+    /// assert_eq!(ctx.eval_xyz(circle, 1.0, 0.0, 0.0)?, 0.5);
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if any root is invalid, or
+    /// [`Error::CustomOpInFrep`] if the roots use a custom operation.
+    pub fn write_frep<W: Write>(
+        &self,
+        frep: &Frep,
+        out: &mut W,
+    ) -> Result<(), Error> {
+        // Collect reachable nodes, with children before their parents.  This
+        // also checks for unsupported operations before writing anything, so
+        // that errors don't leave a truncated file.
+        let mut order = vec![];
+        let mut index: HashMap<Node, u32> = HashMap::new();
+        let mut todo = vec![];
+        for (_, root) in &frep.roots {
+            self.check_node(*root)?;
+            todo.push((*root, false));
+        }
+        while let Some((node, expanded)) = todo.pop() {
+            if index.contains_key(&node) {
+                continue;
+            }
+            let op = self.get_op(node).ok_or(Error::BadNode)?;
+            if let Op::Custom(..) = op {
+                return Err(Error::CustomOpInFrep);
+            } else if expanded {
+                index.insert(node, order.len() as u32);
+                order.push(op.clone());
+            } else {
+                todo.push((node, true));
+                todo.extend(op.iter_children().map(|c| (c, false)));
+            }
+        }
+
+        // Build the variable table
+        let mut vars: HashMap<_, u32> = HashMap::new();
+        let mut var_names = vec![];
+        for op in &order {
+            if let Op::Var(v) = op {
+                vars.entry(*v).or_insert_with(|| {
+                    var_names.push(self.get_var_by_index(*v).unwrap());
+                    var_names.len() as u32 - 1
+                });
+            }
+        }
+
+        let write_str = |out: &mut W, s: &str| -> Result<(), Error> {
+            out.write_all(&(s.len() as u32).to_le_bytes())?;
+            out.write_all(s.as_bytes())?;
+            Ok(())
+        };
+        let arg = |n: &Node| index[n].to_le_bytes();
+
+        out.write_all(MAGIC)?;
+        out.write_all(&FREP_VERSION.to_le_bytes())?;
+        out.write_all(&[frep.bounds.is_some() as u8])?;
+        if let Some(b) = frep.bounds {
+            for v in b.lower.iter().chain(&b.upper) {
+                out.write_all(&v.to_le_bytes())?;
+            }
+        }
+
+        out.write_all(&(var_names.len() as u32).to_le_bytes())?;
+        for name in &var_names {
+            write_str(out, name)?;
+        }
+
+        out.write_all(&(order.len() as u32).to_le_bytes())?;
+        for op in &order {
+            match op {
+                Op::Input(v) => {
+                    let tag = match self.get_var_by_index(*v)? {
+                        "X" => 0,
+                        "Y" => 1,
+                        "Z" => 2,
+                        _ => unreachable!("invalid input name"),
+                    };
+                    out.write_all(&[tag])?;
+                }
+                Op::Var(v) => {
+                    out.write_all(&[3])?;
+                    out.write_all(&vars[v].to_le_bytes())?;
+                }
+                Op::Const(c) => {
+                    out.write_all(&[4])?;
+                    out.write_all(&c.0.to_le_bytes())?;
+                }
+                Op::Unary(u, a) => {
+                    let i = UNARY.iter().position(|o| o == u).unwrap();
+                    out.write_all(&[UNARY_BASE + i as u8])?;
+                    out.write_all(&arg(a))?;
+                }
+                Op::Binary(b, lhs, rhs) => {
+                    let i = BINARY.iter().position(|o| o == b).unwrap();
+                    out.write_all(&[BINARY_BASE + i as u8])?;
+                    out.write_all(&arg(lhs))?;
+                    out.write_all(&arg(rhs))?;
+                }
+                Op::Noise(seed, x, y, z) => {
+                    out.write_all(&[NOISE])?;
+                    out.write_all(&seed.to_le_bytes())?;
+                    for a in [x, y, z] {
+                        out.write_all(&arg(a))?;
+                    }
+                }
+                Op::Clamp(x, lo, hi) => {
+                    out.write_all(&[CLAMP])?;
+                    for a in [x, lo, hi] {
+                        out.write_all(&arg(a))?;
+                    }
+                }
+                Op::Nary(n, args) => {
+                    let tag = match n {
+                        NaryOpcode::Min => MIN_MANY,
+                        NaryOpcode::Max => MAX_MANY,
+                    };
+                    out.write_all(&[tag])?;
+                    // Arguments are written in file order, so that re-writing
+                    // a file which was read back produces identical data
+                    let mut args: Vec<u32> =
+                        args.iter().map(|a| index[a]).collect();
+                    args.sort();
+                    out.write_all(&(args.len() as u32).to_le_bytes())?;
+                    for a in args {
+                        out.write_all(&a.to_le_bytes())?;
+                    }
+                }
+                Op::Custom(..) => unreachable!("custom ops are rejected above"),
+            }
+        }
+
+        out.write_all(&(frep.roots.len() as u32).to_le_bytes())?;
+        for (name, root) in &frep.roots {
+            write_str(out, name)?;
+            out.write_all(&arg(root))?;
+        }
+        Ok(())
+    }
+
+    /// Reads a new context from data in `.frep` format
+    ///
+    /// Returns the context and the file's metadata, whose roots refer to nodes
+    /// in the new context.  If the file has a bounding box, it's also declared
+    /// for every root with [`Context::set_bounds`], so tapes built from the
+    /// roots skip empty space.
+    ///
+    /// Returns [`Error::BadFrep`] if the data is invalid, or
+    /// [`Error::BadFrepVersion`] if it was written with an unsupported version
+    /// of the format.
+    pub fn read_frep<R: Read>(input: &mut R) -> Result<(Self, Frep), Error> {
+        fn read<const N: usize, R: Read>(r: &mut R) -> Result<[u8; N], Error> {
+            let mut buf = [0u8; N];
+            r.read_exact(&mut buf).map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => Error::BadFrep,
+                _ => e.into(),
+            })?;
+            Ok(buf)
+        }
+        fn read_u32<R: Read>(r: &mut R) -> Result<u32, Error> {
+            read::<4, R>(r).map(u32::from_le_bytes)
+        }
+        fn read_f64<R: Read>(r: &mut R) -> Result<f64, Error> {
+            read::<8, R>(r).map(f64::from_le_bytes)
+        }
+        fn read_str<R: Read>(r: &mut R) -> Result<String, Error> {
+            let n = read_u32(r)? as u64;
+            let mut buf = vec![];
+            r.take(n).read_to_end(&mut buf)?;
+            if buf.len() as u64 != n {
+                return Err(Error::BadFrep);
+            }
+            String::from_utf8(buf).map_err(|_| Error::BadFrep)
+        }
+
+        if &read::<8, R>(input)? != MAGIC {
+            return Err(Error::BadFrep);
+        }
+        let version = read_u32(input)?;
+        if version != FREP_VERSION {
+            return Err(Error::BadFrepVersion(version));
+        }
+        let [flags] = read::<1, R>(input)?;
+        if flags & !1 != 0 {
+            return Err(Error::BadFrep);
+        }
+        let bounds = if flags & 1 != 0 {
+            let mut b = BoundingBox::INFINITE;
+            for v in b.lower.iter_mut().chain(&mut b.upper) {
+                *v = read_f64(input)?;
+            }
+            Some(b)
+        } else {
+            None
+        };
+
+        let mut ctx = Context::new();
+        let var_count = read_u32(input)?;
+        let mut vars = vec![];
+        for _ in 0..var_count {
+            let name = read_str(input)?;
+            let v = ctx.var(&name).map_err(|_| Error::BadFrep)?;
+            vars.push(v);
+        }
+
+        let node_count = read_u32(input)?;
+        let mut nodes: Vec<Node> = vec![];
+        for _ in 0..node_count {
+            let arg = |input: &mut R| -> Result<Node, Error> {
+                let i = read_u32(input)? as usize;
+                nodes.get(i).copied().ok_or(Error::BadFrep)
+            };
+            let [tag] = read::<1, R>(input)?;
+            let node = match tag {
+                0 => ctx.x(),
+                1 => ctx.y(),
+                2 => ctx.z(),
+                3 => {
+                    let i = read_u32(input)? as usize;
+                    *vars.get(i).ok_or(Error::BadFrep)?
+                }
+                4 => ctx.constant(read_f64(input)?),
+                t if (UNARY_BASE..UNARY_BASE + UNARY.len() as u8)
+                    .contains(&t) =>
+                {
+                    let a = arg(input)?;
+                    ctx.op_unary(a, UNARY[(t - UNARY_BASE) as usize])?
+                }
+                t if (BINARY_BASE..BINARY_BASE + BINARY.len() as u8)
+                    .contains(&t) =>
+                {
+                    let (a, b) = (arg(input)?, arg(input)?);
+                    ctx.op_binary(a, b, BINARY[(t - BINARY_BASE) as usize])?
+                }
+                NOISE => {
+                    let seed = u16::from_le_bytes(read::<2, R>(input)?);
+                    let (x, y, z) = (arg(input)?, arg(input)?, arg(input)?);
+                    ctx.noise3(x, y, z, seed)?
+                }
+                CLAMP => {
+                    let (x, lo, hi) = (arg(input)?, arg(input)?, arg(input)?);
+                    ctx.clamp(x, lo, hi)?
+                }
+                MIN_MANY | MAX_MANY => {
+                    let n = read_u32(input)?;
+                    let args = (0..n)
+                        .map(|_| arg(input))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let op = if tag == MIN_MANY {
+                        NaryOpcode::Min
+                    } else {
+                        NaryOpcode::Max
+                    };
+                    ctx.op_nary(args, op)?
+                }
+                _ => return Err(Error::BadFrep),
+            };
+            nodes.push(node);
+        }
+
+        let root_count = read_u32(input)?;
+        let mut roots = vec![];
+        for _ in 0..root_count {
+            let name = read_str(input)?;
+            let i = read_u32(input)? as usize;
+            let node = *nodes.get(i).ok_or(Error::BadFrep)?;
+            if let Some(b) = bounds {
+                ctx.set_bounds(node, b)?;
+            }
+            roots.push((name, node));
+        }
+        Ok((ctx, Frep { roots, bounds }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval::custom::eval_tests::SquareSub;
+    use std::sync::Arc;
+
+    /// Round-trips the given roots through a `.frep` file
+    fn round_trip(ctx: &Context, frep: &Frep) -> (Context, Frep) {
+        let mut data = vec![];
+        ctx.write_frep(frep, &mut data).unwrap();
+        Context::read_frep(&mut data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_frep_round_trip() {
+        let mut ctx = Context::new();
+        let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+        let t = ctx.var("t").unwrap();
+        let _unused = ctx.var("unused").unwrap();
+
+        let a = ctx.mul(x, t).unwrap();
+        let b = ctx.atan2(y, a).unwrap();
+        let c = ctx.sin(b).unwrap();
+        let d = ctx.noise3(x, y, z, 17).unwrap();
+        let e = ctx.max(c, d).unwrap();
+        let f = ctx.recip(z).unwrap();
+        let f = ctx.modulo(f, 0.75).unwrap();
+        let g = ctx.hypot(e, f).unwrap();
+        let h = ctx.sub(g, 0.25).unwrap();
+        let h = ctx.min_many([h, c, z]).unwrap();
+        let bounds = BoundingBox::new([-1.0, -2.0, -3.0], [1.0, 2.0, 3.0]);
+        let frep = Frep {
+            roots: vec![("shape".to_owned(), h), ("part".to_owned(), c)],
+            bounds: Some(bounds),
+        };
+
+        let (out, read) = round_trip(&ctx, &frep);
+        assert_eq!(read.bounds, Some(bounds));
+        let names: Vec<_> =
+            read.roots.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["shape", "part"]);
+        assert_eq!(out.len(), 16); // the unused variable isn't written
+        let shape = read.root("shape").unwrap();
+        assert_eq!(out.bounds(shape).unwrap(), bounds);
+
+        for p in [[0.1, 0.2, 0.3], [-0.5, 0.7, 1.5], [2.0, -1.0, 0.25]] {
+            let vars = [("X", p[0]), ("Y", p[1]), ("Z", p[2]), ("t", 0.5)]
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v))
+                .collect();
+            for (name, node) in &frep.roots {
+                let expected = ctx.eval(*node, &vars).unwrap();
+                let actual = out.eval(read.root(name).unwrap(), &vars).unwrap();
+                assert_eq!(expected, actual);
+            }
+        }
+
+        // Writing the new context produces identical data
+        let mut a = vec![];
+        ctx.write_frep(&frep, &mut a).unwrap();
+        let mut b = vec![];
+        out.write_frep(&read, &mut b).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_frep_errors() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.square(x).unwrap();
+        let frep = Frep {
+            roots: vec![("y".to_owned(), y)],
+            bounds: None,
+        };
+        let mut data = vec![];
+        ctx.write_frep(&frep, &mut data).unwrap();
+
+        let read = |data: &[u8]| Context::read_frep(&mut &data[..]);
+        assert!(read(&data).is_ok());
+        for i in 0..data.len() {
+            assert!(matches!(read(&data[..i]), Err(Error::BadFrep)));
+        }
+
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(matches!(read(&bad), Err(Error::BadFrep)));
+
+        let mut bad = data.clone();
+        bad[8] = 2;
+        assert!(matches!(read(&bad), Err(Error::BadFrepVersion(2))));
+
+        // The square's argument must precede it
+        let mut bad = data.clone();
+        let n = bad.len();
+        bad[n - 4 - 4 - 1 - 4 - 4] = 1;
+        assert!(matches!(read(&bad), Err(Error::BadFrep)));
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let c = ctx.custom(Arc::new(SquareSub), x, y).unwrap();
+        let frep = Frep {
+            roots: vec![("c".to_owned(), c)],
+            bounds: None,
+        };
+        let mut data = vec![];
+        assert!(matches!(
+            ctx.write_frep(&frep, &mut data),
+            Err(Error::CustomOpInFrep)
+        ));
+        assert!(data.is_empty());
+
+        // Custom ops are found even when other roots come first
+        let frep = Frep {
+            roots: vec![("x".to_owned(), x), ("c".to_owned(), c)],
+            bounds: None,
+        };
+        assert!(matches!(
+            ctx.write_frep(&frep, &mut data),
+            Err(Error::CustomOpInFrep)
+        ));
+        assert!(data.is_empty());
+    }
+}
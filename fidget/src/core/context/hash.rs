@@ -0,0 +1,622 @@
+//! Comparing and copying expressions between contexts
+//!
+//! [`Node`] handles are only meaningful within the [`Context`] which created
+//! them.  To recognize the same expression in two different contexts (e.g. to
+//! reuse a tape which was built for a previous version of a model), each node
+//! can be summarized by a [`NodeHash`], which is computed from the node's
+//! operation and the hashes of its children (like a Merkle tree).
+use super::{BinaryOpcode, Context, Node, NodeBudget, Op};
+use crate::Error;
+use alloc::borrow::ToOwned;
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+
+/// Content hash of an expression
+///
+/// Identical expressions have the same hash, regardless of which [`Context`]
+/// they were built in; variables are identified by name, and arguments to
+/// commutative operations are hashed in a canonical order.  Node names and
+/// bounds are debug / optimization metadata, and are not included.
+///
+/// Hashes are stable across platforms and runs, with one exception: custom
+/// operations are identified by their address, so expressions using them
+/// only hash consistently within a single process.
+///
+/// Equal hashes imply identical expressions with overwhelming probability; use
+/// [`Context::structurally_eq`] for an exact check.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct NodeHash(u128);
+
+/// 128-bit FNV-1a hasher, which is deterministic across platforms and runs
+pub(crate) struct Fnv(u128);
+
+impl Fnv {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    /// Starts a new hash with a tag identifying the kind of operation
+    pub(crate) fn new(tag: u8) -> Self {
+        let mut out = Self(Self::OFFSET);
+        out.write(&[tag]);
+        out
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u128;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    fn write_hash(&mut self, h: NodeHash) {
+        self.write(&h.0.to_le_bytes());
+    }
+
+    fn finish(self) -> NodeHash {
+        NodeHash(self.0)
+    }
+
+    /// Returns the raw value of the hash
+    pub(crate) fn value(&self) -> u128 {
+        self.0
+    }
+}
+
+impl BinaryOpcode {
+    /// Checks whether the operation's arguments can be swapped
+    pub(crate) fn is_commutative(&self) -> bool {
+        matches!(
+            self,
+            BinaryOpcode::Add
+                | BinaryOpcode::Mul
+                | BinaryOpcode::Min
+                | BinaryOpcode::Max
+                | BinaryOpcode::MinNc
+                | BinaryOpcode::MaxNc
+                | BinaryOpcode::Hypot
+        )
+    }
+}
+
+impl Context {
+    /// Computes a content hash of the given node
+    ///
+    /// The hash identifies the expression itself, so it can be used to find
+    /// identical subtrees in different contexts, or as a cache key for tapes:
+    ///
+    /// ```
+    /// use fidget::{context::Context, vm};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut cache = BTreeMap::new();
+    /// for _ in 0..2 {
+    ///     // A fresh context, e.g. after reloading a model
+    ///     let mut ctx = Context::new();
+    ///     let x = ctx.x();
+    ///     let y = ctx.y();
+    ///     let sum = ctx.add(x, y)?;
+    ///
+    ///     let hash = ctx.content_hash(sum)?;
+    ///     if !cache.contains_key(&hash) {
+    ///         cache.insert(hash, ctx.get_tape::<vm::Eval>(sum)?);
+    ///     }
+    /// }
+    /// assert_eq!(cache.len(), 1);
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if the node is invalid, or
+    /// [`Error::TooManyNodes`] if the subtree has more than
+    /// [`Context::node_limit`] nodes.
+    pub fn content_hash(&self, node: Node) -> Result<NodeHash, Error> {
+        Ok(self.content_hashes(node)?[&node])
+    }
+
+    /// Computes content hashes of every node in the given subtree
+    fn content_hashes(
+        &self,
+        root: Node,
+    ) -> Result<BTreeMap<Node, NodeHash>, Error> {
+        self.check_node(root)?;
+
+        // Depth-first recursion on the heap, to protect against stack overflows
+        let mut todo = vec![(false, root)];
+        let mut done = BTreeMap::new();
+        let mut budget = NodeBudget::new(self);
+        while let Some((ready, node)) = todo.pop() {
+            if done.contains_key(&node) {
+                continue;
+            }
+            let op = self.get_op(node).unwrap();
+            if !ready {
+                budget.visit()?;
+                todo.push((true, node));
+                todo.extend(op.iter_children().map(|c| (false, c)));
+                continue;
+            }
+            let h = match op {
+                Op::Input(v) => {
+                    let mut h = Fnv::new(0);
+                    h.write_str(self.get_var_by_index(*v)?);
+                    h
+                }
+                Op::Var(v) => {
+                    let mut h = Fnv::new(1);
+                    h.write_str(self.get_var_by_index(*v)?);
+                    h
+                }
+                Op::Const(c) => {
+                    // Normalize values which compare equal (-0.0 and 0.0, and
+                    // every NaN) to the same bits
+                    let c = if c.0 == 0.0 {
+                        0.0
+                    } else if c.0.is_nan() {
+                        f64::NAN
+                    } else {
+                        c.0
+                    };
+                    let mut h = Fnv::new(2);
+                    h.write(&c.to_bits().to_le_bytes());
+                    h
+                }
+                Op::Unary(op, a) => {
+                    let mut h = Fnv::new(3);
+                    h.write(&[*op as u8]);
+                    h.write_hash(done[a]);
+                    h
+                }
+                Op::Binary(op, a, b) => {
+                    let mut args = [done[a], done[b]];
+                    if op.is_commutative() {
+                        args.sort();
+                    }
+                    let mut h = Fnv::new(4);
+                    h.write(&[*op as u8]);
+                    args.into_iter().for_each(|a| h.write_hash(a));
+                    h
+                }
+                Op::Custom(c, a, b) => {
+                    let op = &self.custom[c.index()];
+                    let ptr = Arc::as_ptr(op) as *const () as usize;
+                    let mut h = Fnv::new(5);
+                    h.write(&(ptr as u64).to_le_bytes());
+                    h.write_hash(done[a]);
+                    h.write_hash(done[b]);
+                    h
+                }
+                Op::Noise(seed, x, y, z) => {
+                    let mut h = Fnv::new(6);
+                    h.write(&seed.to_le_bytes());
+                    [x, y, z].into_iter().for_each(|a| h.write_hash(done[a]));
+                    h
+                }
+                Op::Clamp(x, lo, hi) => {
+                    let mut h = Fnv::new(7);
+                    [x, lo, hi].into_iter().for_each(|a| h.write_hash(done[a]));
+                    h
+                }
+                Op::Nary(op, args) => {
+                    let mut args: Vec<_> =
+                        args.iter().map(|a| done[a]).collect();
+                    args.sort();
+                    let mut h = Fnv::new(8);
+                    h.write(&[*op as u8]);
+                    h.write(&(args.len() as u64).to_le_bytes());
+                    args.into_iter().for_each(|a| h.write_hash(a));
+                    h
+                }
+            };
+            done.insert(node, h.finish());
+        }
+        Ok(done)
+    }
+
+    /// Checks whether a node in this context and a node in another context
+    /// (or the same context) represent the same expression
+    ///
+    /// Variables are compared by name, and the arguments of commutative
+    /// operations may be in either order.
+    ///
+    /// ```
+    /// # use fidget::context::Context;
+    /// let mut a = Context::new();
+    /// let ax = a.x();
+    /// let ay = a.y();
+    /// let a_sum = a.add(ax, ay).unwrap();
+    ///
+    /// let mut b = Context::new();
+    /// let by = b.y(); // built in a different order
+    /// let bx = b.x();
+    /// let b_sum = b.add(by, bx).unwrap();
+    /// let b_diff = b.sub(bx, by).unwrap();
+    ///
+    /// assert!(a.structurally_eq(a_sum, &b, b_sum).unwrap());
+    /// assert!(!a.structurally_eq(a_sum, &b, b_diff).unwrap());
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if either node is invalid, or
+    /// [`Error::TooManyNodes`] if either subtree has more than its context's
+    /// [`Context::node_limit`] nodes.
+    pub fn structurally_eq(
+        &self,
+        a: Node,
+        other: &Context,
+        b: Node,
+    ) -> Result<bool, Error> {
+        let ha = self.content_hashes(a)?;
+        let hb = other.content_hashes(b)?;
+        if ha[&a] != hb[&b] {
+            return Ok(false);
+        }
+
+        // Matching hashes are almost certainly identical, but we walk both
+        // trees to be sure.  Children of commutative operations are paired up
+        // by sorting on their hashes.
+        let mut seen = BTreeSet::new();
+        let mut todo = vec![(a, b)];
+        let mut budget = NodeBudget::new(self);
+        while let Some((a, b)) = todo.pop() {
+            if !seen.insert((a, b)) {
+                continue;
+            }
+            budget.visit()?;
+            let same = match (self.get_op(a).unwrap(), other.get_op(b).unwrap())
+            {
+                (Op::Input(va), Op::Input(vb)) | (Op::Var(va), Op::Var(vb)) => {
+                    self.get_var_by_index(*va)?
+                        == other.get_var_by_index(*vb)?
+                }
+                (Op::Const(ca), Op::Const(cb)) => ca == cb,
+                (Op::Unary(oa, xa), Op::Unary(ob, xb)) if oa == ob => {
+                    todo.push((*xa, *xb));
+                    true
+                }
+                (Op::Binary(oa, xa, ya), Op::Binary(ob, xb, yb))
+                    if oa == ob =>
+                {
+                    let mut lhs = [*xa, *ya];
+                    let mut rhs = [*xb, *yb];
+                    if oa.is_commutative() {
+                        lhs.sort_by_key(|n| ha[n]);
+                        rhs.sort_by_key(|n| hb[n]);
+                    }
+                    todo.extend(lhs.into_iter().zip(rhs));
+                    true
+                }
+                (Op::Custom(ca, xa, ya), Op::Custom(cb, xb, yb)) => {
+                    let pa = Arc::as_ptr(&self.custom[ca.index()]);
+                    let pb = Arc::as_ptr(&other.custom[cb.index()]);
+                    todo.extend([(*xa, *xb), (*ya, *yb)]);
+                    pa as *const () == pb as *const ()
+                }
+                (Op::Noise(sa, xa, ya, za), Op::Noise(sb, xb, yb, zb))
+                    if sa == sb =>
+                {
+                    todo.extend([(*xa, *xb), (*ya, *yb), (*za, *zb)]);
+                    true
+                }
+                (Op::Clamp(xa, la, ha), Op::Clamp(xb, lb, hb)) => {
+                    todo.extend([(*xa, *xb), (*la, *lb), (*ha, *hb)]);
+                    true
+                }
+                (Op::Nary(oa, xa), Op::Nary(ob, xb))
+                    if oa == ob && xa.len() == xb.len() =>
+                {
+                    let mut lhs = xa.to_vec();
+                    let mut rhs = xb.to_vec();
+                    lhs.sort_by_key(|n| ha[n]);
+                    rhs.sort_by_key(|n| hb[n]);
+                    todo.extend(lhs.into_iter().zip(rhs));
+                    true
+                }
+                _ => false,
+            };
+            if !same {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Copies an expression from another context into this one
+    ///
+    /// Variables are matched by name, and nodes are deduplicated against
+    /// existing nodes in this context, so importing an expression which is
+    /// already present returns the existing node.  Names and bounds attached
+    /// to the imported nodes are copied as well (without replacing this
+    /// context's own names).
+    ///
+    /// ```
+    /// # use fidget::context::Context;
+    /// let mut a = Context::new();
+    /// let x = a.x();
+    /// let sum = a.add(x, 1.0).unwrap();
+    ///
+    /// let mut b = Context::new();
+    /// let imported = b.import(&a, sum).unwrap();
+    /// assert_eq!(b.eval_xyz(imported, 2.0, 0.0, 0.0).unwrap(), 3.0);
+    ///
+    /// // Importing the same expression again reuses the existing nodes
+    /// let n = b.len();
+    /// assert_eq!(b.import(&a, sum).unwrap(), imported);
+    /// assert_eq!(b.len(), n);
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if the node is invalid in `other`, or
+    /// [`Error::TooManyNodes`] if the subtree has more than this context's
+    /// [`Context::node_limit`] nodes.
+    pub fn import(
+        &mut self,
+        other: &Context,
+        root: Node,
+    ) -> Result<Node, Error> {
+        Ok(self.import_many(other, [root])?[0])
+    }
+
+    /// Copies several expressions from another context into this one
+    ///
+    /// This is equivalent to calling [`Context::import`] on each root, but
+    /// only visits subtrees shared between the roots once, so it's the
+    /// cheapest way to merge a whole library of shapes into a model:
+    ///
+    /// ```
+    /// # use fidget::context::Context;
+    /// // A library of shapes, built once and shipped as a `Context`
+    /// let mut lib = Context::new();
+    /// let x = lib.x();
+    /// let y = lib.y();
+    /// let r = lib.hypot(x, y).unwrap();
+    /// let circle = lib.sub(r, 1.0).unwrap();
+    /// let ring = lib.sub(r, 2.0).unwrap();
+    /// let ring = lib.abs(ring).unwrap();
+    ///
+    /// let mut ctx = Context::new();
+    /// let [circle, ring] = ctx.import_many(&lib, [circle, ring])
+    ///     .unwrap()
+    ///     .try_into()
+    ///     .unwrap();
+    /// let both = ctx.min(circle, ring).unwrap();
+    /// assert_eq!(ctx.eval_xyz(both, 0.0, 2.5, 0.0).unwrap(), 0.5);
+    /// ```
+    ///
+    /// Returns a node in this context for each root, in order, or
+    /// [`Error::BadNode`] if any root is invalid in `other`.  As with
+    /// [`Context::import`], this returns [`Error::TooManyNodes`] if the roots
+    /// have more than this context's [`Context::node_limit`] nodes.
+    pub fn import_many<I: IntoIterator<Item = Node>>(
+        &mut self,
+        other: &Context,
+        roots: I,
+    ) -> Result<Vec<Node>, Error> {
+        let roots: Vec<Node> = roots.into_iter().collect();
+        roots.iter().try_for_each(|r| other.check_node(*r))?;
+
+        // Depth-first recursion on the heap, to protect against stack overflows
+        let mut todo: Vec<_> =
+            roots.iter().rev().map(|r| (false, *r)).collect();
+        let mut done = BTreeMap::new();
+        let mut budget = NodeBudget::new(self);
+        while let Some((ready, node)) = todo.pop() {
+            if done.contains_key(&node) {
+                continue;
+            }
+            let op = other.get_op(node).unwrap();
+            if !ready {
+                budget.visit()?;
+                todo.push((true, node));
+                todo.extend(op.iter_children().map(|c| (false, c)));
+                continue;
+            }
+            let n = match op {
+                Op::Input(v) => {
+                    let name = other.get_var_by_index(*v)?.to_owned();
+                    let v = self.vars.insert(name);
+                    self.ops.insert(Op::Input(v))
+                }
+                Op::Var(v) => {
+                    let name = other.get_var_by_index(*v)?.to_owned();
+                    let v = self.vars.insert(name);
+                    self.ops.insert(Op::Var(v))
+                }
+                Op::Const(c) => self.constant(c.0),
+                Op::Unary(op, a) => self.op_unary(done[a], *op)?,
+                Op::Binary(op, a, b) if op.is_commutative() => {
+                    self.op_binary_commutative(done[a], done[b], *op)?
+                }
+                Op::Binary(op, a, b) => {
+                    self.op_binary(done[a], done[b], *op)?
+                }
+                Op::Custom(c, a, b) => {
+                    let op = other.custom[c.index()].clone();
+                    self.custom(op, done[a], done[b])?
+                }
+                Op::Noise(seed, x, y, z) => {
+                    self.op_noise(done[x], done[y], done[z], *seed)?
+                }
+                Op::Clamp(x, lo, hi) => {
+                    self.op_clamp(done[x], done[lo], done[hi])?
+                }
+                Op::Nary(op, args) => {
+                    self.op_nary(args.iter().map(|a| done[a]), *op)?
+                }
+            };
+            if let Some(name) = other.names.get(&node) {
+                self.names.entry(n).or_insert_with(|| name.clone());
+            }
+            if let Some(b) = other.bounds.get(&node) {
+                self.set_bounds(n, *b)?;
+            }
+            done.insert(node, n);
+        }
+        Ok(roots.iter().map(|r| done[r]).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::BoundingBox;
+
+    #[test]
+    fn test_content_hash() {
+        let mut a = Context::new();
+        let ax = a.x();
+        let ay = a.y();
+        let a_min = a.min(ax, ay).unwrap();
+        let a_out = a.sub(a_min, 0.5).unwrap();
+
+        // Build the same expression with nodes created in a different order
+        let mut b = Context::new();
+        let bc = b.constant(0.5);
+        let by = b.y();
+        let bx = b.x();
+        let b_min = b.min(by, bx).unwrap();
+        let b_out = b.sub(b_min, bc).unwrap();
+        assert_eq!(
+            a.content_hash(a_out).unwrap(),
+            b.content_hash(b_out).unwrap()
+        );
+        assert!(a.structurally_eq(a_out, &b, b_out).unwrap());
+
+        // Non-commutative operations depend on argument order
+        let a_sub = a.sub(ax, ay).unwrap();
+        let b_sub = b.sub(by, bx).unwrap();
+        assert_ne!(
+            a.content_hash(a_sub).unwrap(),
+            b.content_hash(b_sub).unwrap()
+        );
+        assert!(!a.structurally_eq(a_sub, &b, b_sub).unwrap());
+
+        // Variables are identified by name
+        let va = a.var("a").unwrap();
+        let vb = b.var("b").unwrap();
+        assert_ne!(a.content_hash(va).unwrap(), b.content_hash(vb).unwrap());
+        let vb = b.var("a").unwrap();
+        assert_eq!(a.content_hash(va).unwrap(), b.content_hash(vb).unwrap());
+
+        // Different kinds of node don't collide
+        let mut hashes = BTreeSet::new();
+        for n in [ax, ay, a_min, a_out, a_sub, va] {
+            assert!(hashes.insert(a.content_hash(n).unwrap()));
+        }
+        let c = a.constant(-0.0);
+        assert!(hashes.insert(a.content_hash(c).unwrap()));
+        let c = b.constant(0.0);
+        assert!(!hashes.insert(b.content_hash(c).unwrap()));
+
+        // Arguments to n-ary operations may be in any order
+        let a_many = a.max_many([ax, ay, a_out]).unwrap();
+        let b_many = b.max_many([b_out, bx, by]).unwrap();
+        assert_eq!(
+            a.content_hash(a_many).unwrap(),
+            b.content_hash(b_many).unwrap()
+        );
+        assert!(a.structurally_eq(a_many, &b, b_many).unwrap());
+        let b_many = b.min_many([b_out, bx, by]).unwrap();
+        assert!(!a.structurally_eq(a_many, &b, b_many).unwrap());
+        let mut c = Context::new();
+        let c_many = c.import(&b, b_many).unwrap();
+        assert!(c.structurally_eq(c_many, &b, b_many).unwrap());
+
+        // Foreign nodes are rejected
+        assert!(matches!(a.content_hash(bx), Err(Error::BadNode)));
+        assert!(a.structurally_eq(bx, &b, bx).is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let mut a = Context::new();
+        let ax = a.x();
+        let ay = a.y();
+        let r = a.var("r").unwrap();
+        let hyp = a.hypot(ax, ay).unwrap();
+        let circle = a.sub(hyp, r).unwrap();
+        a.name(circle, "circle").unwrap();
+
+        // Import into a context which already contains part of the expression
+        let mut b = Context::new();
+        let by = b.y();
+        let bx = b.x();
+        let b_hyp = b.hypot(by, bx).unwrap();
+        let n = b.len();
+        let out = b.import(&a, circle).unwrap();
+        assert_eq!(b.len(), n + 2); // only `r` and the subtraction are new
+        assert!(a.structurally_eq(circle, &b, out).unwrap());
+        assert_eq!(b.get_name(out).unwrap(), Some("circle"));
+        assert_eq!(b.import(&a, hyp).unwrap(), b_hyp);
+
+        // Variables are matched by name
+        let br = b.var("r").unwrap();
+        let vars = [("X", 3.0), ("Y", 4.0), ("r", 1.0)];
+        let v = b
+            .eval(out, &vars.map(|(k, v)| (k.to_owned(), v)).into())
+            .unwrap();
+        assert_eq!(v, 4.0);
+        assert_eq!(b.import(&a, r).unwrap(), br);
+
+        // Foreign nodes are rejected
+        assert!(matches!(b.import(&a, bx), Err(Error::BadNode)));
+    }
+
+    #[test]
+    fn test_import_many() {
+        let mut a = Context::new();
+        let x = a.x();
+        let sx = a.square(x).unwrap();
+        let p = a.add(sx, 1.0).unwrap();
+        let q = a.mul(sx, 2.0).unwrap();
+        a.set_bounds(p, BoundingBox::new([-1.0; 3], [1.0; 3]))
+            .unwrap();
+
+        let mut b = Context::new();
+        let out = b.import_many(&a, [q, p, q]).unwrap();
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0], out[2]);
+        assert_eq!(b.len(), a.len());
+        assert!(a.structurally_eq(p, &b, out[1]).unwrap());
+        assert!(a.structurally_eq(q, &b, out[0]).unwrap());
+        assert_eq!(b.import(&a, p).unwrap(), out[1]);
+
+        // Bounds are carried over
+        let bb = b.bounds(out[1]).unwrap();
+        assert_eq!(bb, a.bounds(p).unwrap());
+
+        // A single bad root is an error
+        assert!(matches!(
+            b.import_many(&a, [p, out[0]]),
+            Err(Error::BadNode)
+        ));
+        assert!(b.import_many(&a, []).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_structurally_eq_custom() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op = Arc::new(SquareSub);
+
+        let mut a = Context::new();
+        let ax = a.x();
+        let a_out = a.custom(op.clone(), ax, 1.0).unwrap();
+        let mut b = Context::new();
+        let bx = b.x();
+        let b_out = b.custom(op, bx, 1.0).unwrap();
+        assert!(a.structurally_eq(a_out, &b, b_out).unwrap());
+
+        // A different instance of the same operation isn't identical
+        let c_out = b.custom(Arc::new(SquareSub), bx, 1.0).unwrap();
+        assert!(!a.structurally_eq(a_out, &b, c_out).unwrap());
+        assert_ne!(
+            a.content_hash(a_out).unwrap(),
+            b.content_hash(c_out).unwrap()
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! Container types with strongly-typed indexes.
-use crate::Error;
-use std::collections::HashMap;
+use alloc::{vec, vec::Vec};
+use hashbrown::HashMap;
 
 /// Stores a set of `(V, I)` tuples, with lookup in both directions.
 ///
@@ -35,20 +35,14 @@ pub(crate) trait Index {
 
 impl<V, I> IndexMap<V, I>
 where
-    V: Eq + std::hash::Hash + Clone,
-    I: Eq + std::hash::Hash + Copy + Index,
+    V: Eq + core::hash::Hash + Clone,
+    I: Eq + core::hash::Hash + Copy + Index,
 {
     pub fn clear(&mut self) {
         self.data.clear();
         self.map.clear();
     }
 
-    pub fn len(&self) -> usize {
-        self.data.len()
-    }
-    pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
-    }
     pub fn get_by_index(&self, i: I) -> Option<&V> {
         self.data.get(i.get())
     }
@@ -63,99 +57,11 @@ where
             out
         })
     }
-
-    /// Removes the last value stored in the container.
-    ///
-    /// This is _usually_ the most recently inserted value, except when
-    /// `insert` is called on a duplicate.
-    pub fn pop(&mut self) -> Result<V, Error> {
-        match self.data.pop() {
-            Some(v) => {
-                self.map.remove(&v);
-                Ok(v)
-            }
-            None => Err(Error::EmptyMap),
-        }
-    }
-    pub fn keys(&self) -> impl Iterator<Item = I> {
-        (0..self.data.len()).map(I::new)
-    }
-}
-
-////////////////////////////////////////////////////////////////////////////////
-
-/// A `Vec<V>` with strongly-typed indexes, used to improve the type-safety
-/// of data storage.
-///
-/// The `Index` type should be a wrapper around a `usize` and be convertible
-/// in both directions; it is typically passed around using `Copy`.  A suitable
-/// index type can be constructed with [define_index].
-#[derive(Clone, Debug)]
-pub struct IndexVec<V, I> {
-    data: Vec<V>,
-    _phantom: std::marker::PhantomData<*const I>,
-}
-
-impl<V, I> Default for IndexVec<V, I> {
-    fn default() -> Self {
-        Self {
-            data: vec![],
-            _phantom: std::marker::PhantomData,
-        }
-    }
-}
-
-impl<V, I> std::iter::IntoIterator for IndexVec<V, I> {
-    type Item = V;
-    type IntoIter = std::vec::IntoIter<V>;
-    fn into_iter(self) -> Self::IntoIter {
-        self.data.into_iter()
-    }
-}
-
-impl<V, I> FromIterator<V> for IndexVec<V, I> {
-    fn from_iter<T: IntoIterator<Item = V>>(iter: T) -> Self {
-        Vec::from_iter(iter).into()
-    }
-}
-
-impl<V, I> std::ops::Index<I> for IndexVec<V, I>
-where
-    I: Index,
-{
-    type Output = V;
-    fn index(&self, i: I) -> &V {
-        &self.data[i.get()]
-    }
-}
-
-impl<V, I> std::ops::IndexMut<I> for IndexVec<V, I>
-where
-    I: Index,
-{
-    fn index_mut(&mut self, i: I) -> &mut V {
-        &mut self.data[i.get()]
-    }
-}
-
-impl<V, I> From<Vec<V>> for IndexVec<V, I> {
-    fn from(data: Vec<V>) -> Self {
-        Self {
-            data,
-            _phantom: std::marker::PhantomData,
-        }
-    }
-}
-
-impl<V, I> IndexVec<V, I> {
-    pub fn len(&self) -> usize {
-        self.data.len()
-    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
-/// Defines an index type suitable for use in an [`IndexMap`] or [`IndexVec`].
+/// Defines an index type suitable for use in an [`IndexMap`].
 macro_rules! define_index {
     ($name:ident, $doc:literal) => {
         #[doc = $doc]
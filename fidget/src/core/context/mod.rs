@@ -1,37 +1,114 @@
 //! Infrastructure for representing math expressions as graphs
+mod arena;
+mod bbox;
+mod dot;
+#[cfg(feature = "std")]
+pub mod frep;
+mod hash;
 mod indexed;
 mod op;
+mod tree;
 
 #[cfg(test)]
 pub(crate) mod bound;
 
-use indexed::{define_index, Index, IndexMap, IndexVec};
-pub use op::{BinaryOpcode, Op, UnaryOpcode};
+use arena::Arena;
+pub use arena::Node;
+pub use bbox::BoundingBox;
+pub use dot::DotBuilder;
+pub(crate) use hash::Fnv;
+pub use hash::NodeHash;
+use indexed::{define_index, IndexMap};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+pub use op::{BinaryOpcode, NaryOpcode, Op, UnaryOpcode};
+pub use tree::Tree;
 
 use crate::{
-    eval::{Family, Tape},
-    ssa::Builder,
+    eval::{noise, CustomOp, Family, Tape},
+    ssa::{Builder, Tape as SsaTape},
     Error,
 };
 
-use std::collections::{BTreeMap, BTreeSet};
-use std::fmt::Write;
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
 use std::io::{BufRead, BufReader, Read};
 
 use ordered_float::OrderedFloat;
 
-define_index!(Node, "An index in the `Context::ops` map");
 define_index!(VarNode, "An index in the `Context::vars` map");
+define_index!(CustomNode, "An index in the `Context::custom` list");
+
+impl CustomNode {
+    /// Returns the index of this operation in its context's list
+    pub(crate) fn index(&self) -> usize {
+        self.0
+    }
+}
 
 /// A `Context` holds a set of deduplicated constants, variables, and
 /// operations.
 ///
 /// It should be used like an arena allocator: it grows over time, then frees
-/// all of its contents when dropped.
+/// all of its contents when dropped.  Long-lived contexts (e.g. in an
+/// interactive editor) can reclaim nodes which are no longer needed by calling
+/// [`Context::gc`].
 #[derive(Debug, Default)]
 pub struct Context {
-    ops: IndexMap<Op, Node>,
+    ops: Arena,
     vars: IndexMap<String, VarNode>,
+
+    /// Bounds declared with [`Context::set_bounds`]
+    bounds: BTreeMap<Node, BoundingBox>,
+
+    /// Names attached with [`Context::name`]
+    names: BTreeMap<Node, String>,
+
+    /// User-defined operations added with [`Context::custom`]
+    custom: Vec<Arc<dyn CustomOp>>,
+
+    /// Limit set with [`Context::set_node_limit`]
+    node_limit: Option<usize>,
+}
+
+/// Counts the nodes visited by a traversal, up to [`Context::node_limit`]
+pub(crate) struct NodeBudget {
+    limit: usize,
+    used: usize,
+}
+
+impl NodeBudget {
+    pub(crate) fn new(ctx: &Context) -> Self {
+        Self {
+            limit: ctx.node_limit(),
+            used: 0,
+        }
+    }
+
+    /// Builds a budget which never runs out
+    pub(crate) fn unlimited() -> Self {
+        Self {
+            limit: usize::MAX,
+            used: 0,
+        }
+    }
+
+    /// Records a visit, returning [`Error::TooManyNodes`] past the limit
+    pub(crate) fn visit(&mut self) -> Result<(), Error> {
+        self.used += 1;
+        if self.used > self.limit {
+            Err(Error::TooManyNodes(self.limit))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl Context {
@@ -55,6 +132,9 @@ impl Context {
     pub fn clear(&mut self) {
         self.ops.clear();
         self.vars.clear();
+        self.bounds.clear();
+        self.names.clear();
+        self.custom.clear();
     }
 
     /// Returns the number of [`Op`] nodes in the context
@@ -78,23 +158,90 @@ impl Context {
         self.ops.is_empty()
     }
 
+    /// Default value for [`Context::node_limit`]
+    pub const DEFAULT_NODE_LIMIT: usize = 1 << 26;
+
+    /// Returns the maximum number of nodes visited by a single traversal
+    ///
+    /// This applies to every traversal which walks the graph with an explicit
+    /// worklist: [`Context::gc`], [`Context::eval`], [`Context::bounds`],
+    /// tape building ([`Context::get_tape`] and [`Context::update_tape`]),
+    /// [`Context::remap_xyz`] and [`Context::bind_constant`],
+    /// [`Context::import`], [`Context::content_hash`],
+    /// [`Context::structurally_eq`], GraphViz export, and [`Tree`] conversion.
+    /// A traversal which would visit more nodes returns
+    /// [`Error::TooManyNodes`].
+    pub fn node_limit(&self) -> usize {
+        self.node_limit.unwrap_or(Self::DEFAULT_NODE_LIMIT)
+    }
+
+    /// Sets the maximum number of nodes visited by a single traversal
+    ///
+    /// See [`Context::node_limit`] for details.
+    pub fn set_node_limit(&mut self, limit: usize) {
+        self.node_limit = Some(limit);
+    }
+
     /// Checks whether the given [`Node`](Node) is valid in this context
     fn check_node(&self, node: Node) -> Result<(), Error> {
         self.get_op(node).ok_or(Error::BadNode).map(|_| ())
     }
 
-    /// Erases the most recently added node from the tree.
+    /// Erases the given node from the tree.
+    ///
+    /// Existing handles to the node will be invalidated, so this must be used
+    /// with caution.  In practice, this is only used to delete temporary
+    /// operation nodes during constant folding, which have no handles (because
+    /// they are never returned).
+    fn remove(&mut self, node: Node) -> Result<(), Error> {
+        self.ops.remove(node).map(|_| ()).ok_or(Error::BadNode)
+    }
+
+    /// Reclaims every node which isn't reachable from the given roots
     ///
-    /// A few caveats apply, so this must be used with caution:
-    /// - Existing handles to the node will be invalidated
-    /// - The most recently added node must be unique
+    /// Handles to reachable nodes remain valid; handles to reclaimed nodes
+    /// become invalid, and will return [`Error::BadNode`] if used.  Bounds
+    /// declared with [`Context::set_bounds`] and names attached with
+    /// [`Context::name`] are discarded along with their nodes.
+    /// Variable names are kept, so [`VarNode`] handles remain valid.
+    ///
+    /// Returns the number of nodes reclaimed.  The context isn't modified if
+    /// any of the roots is invalid ([`Error::BadNode`]) or more than
+    /// [`Context::node_limit`] nodes are reachable ([`Error::TooManyNodes`]).
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let sum = ctx.add(x, y).unwrap();
+    /// let scratch = ctx.mul(x, 3.0).unwrap();
+    /// assert_eq!(ctx.len(), 5);
     ///
-    /// In practice, this is only used to delete temporary operation nodes
-    /// during constant folding.  Such nodes which have no handles (because
-    /// they are never returned) and are guaranteed to be unique (because we
-    /// never store them persistently).
-    fn pop(&mut self) -> Result<(), Error> {
-        self.ops.pop().map(|_| ())
+    /// assert_eq!(ctx.gc(&[sum]).unwrap(), 2);
+    /// assert_eq!(ctx.eval_xyz(sum, 1.0, 2.0, 0.0).unwrap(), 3.0);
+    /// assert!(ctx.eval_xyz(scratch, 1.0, 2.0, 0.0).is_err());
+    /// ```
+    pub fn gc(&mut self, roots: &[Node]) -> Result<usize, Error> {
+        roots.iter().try_for_each(|n| self.check_node(*n))?;
+
+        let mut seen = BTreeSet::new();
+        let mut todo = roots.to_vec();
+        let mut budget = NodeBudget::new(self);
+        while let Some(node) = todo.pop() {
+            if seen.insert(node) {
+                budget.visit()?;
+                todo.extend(self.get_op(node).unwrap().iter_children());
+            }
+        }
+
+        let dead: Vec<Node> =
+            self.ops.keys().filter(|n| !seen.contains(n)).collect();
+        for n in &dead {
+            self.ops.remove(*n).unwrap();
+        }
+        self.bounds.retain(|n, _| seen.contains(n));
+        self.names.retain(|n, _| seen.contains(n));
+        Ok(dead.len())
     }
 
     /// Looks up the constant associated with the given node.
@@ -123,6 +270,40 @@ impl Context {
         }
     }
 
+    /// Attaches a name to the given node, replacing any previous name
+    ///
+    /// Names are debug symbols: they don't change evaluation, but are carried
+    /// into tapes built with [`Context::get_tape`], so that each instruction
+    /// can be traced back to the named subtree which produced it (see
+    /// [`Data::pretty_print`](crate::eval::Data::pretty_print)).  Operations
+    /// without a name of their own inherit the name of the nearest named
+    /// ancestor.
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let wheel = ctx.sub(x, 1.0).unwrap();
+    /// ctx.name(wheel, "wheel_left").unwrap();
+    /// assert_eq!(ctx.get_name(wheel).unwrap(), Some("wheel_left"));
+    /// assert_eq!(ctx.get_name(x).unwrap(), None);
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if the node is invalid.
+    pub fn name(&mut self, node: Node, name: &str) -> Result<(), Error> {
+        self.check_node(node)?;
+        self.names.insert(node, name.to_owned());
+        Ok(())
+    }
+
+    /// Looks up the name attached to the given node with [`Context::name`]
+    ///
+    /// If the node is invalid for this tree, returns an error; if the node
+    /// doesn't have a name, returns `Ok(None)`.
+    pub fn get_name(&self, node: Node) -> Result<Option<&str>, Error> {
+        self.check_node(node)?;
+        Ok(self.names.get(&node).map(String::as_str))
+    }
+
     /// Looks up the variable name associated with the given `VarNode`
     pub fn get_var_by_index(&self, n: VarNode) -> Result<&str, Error> {
         match self.vars.get_by_index(n) {
@@ -189,11 +370,12 @@ impl Context {
     /// Find or create a [Node] for the given unary operation, with constant
     /// folding.
     fn op_unary(&mut self, a: Node, op: UnaryOpcode) -> Result<Node, Error> {
-        let op_a = *self.get_op(a).ok_or(Error::BadNode)?;
+        let op_a = self.get_op(a).ok_or(Error::BadNode)?;
+        let is_const = matches!(op_a, Op::Const(_));
         let n = self.ops.insert(Op::Unary(op, a));
-        let out = if matches!(op_a, Op::Const(_)) {
+        let out = if is_const {
             let v = self.eval(n, &BTreeMap::new())?;
-            self.pop().unwrap(); // removes `n`
+            self.remove(n).unwrap();
             self.constant(v)
         } else {
             n
@@ -217,16 +399,17 @@ impl Context {
     where
         F: Fn(Node, Node) -> Op,
     {
-        let op_a = *self.get_op(a).ok_or(Error::BadNode)?;
-        let op_b = *self.get_op(b).ok_or(Error::BadNode)?;
+        let op_a = self.get_op(a).ok_or(Error::BadNode)?;
+        let op_b = self.get_op(b).ok_or(Error::BadNode)?;
+        let is_const = matches!((op_a, op_b), (Op::Const(_), Op::Const(_)));
 
         // This call to `insert` should always insert the node, because we
         // don't permanently store operations in the tree that could be
-        // constant-folded (indeed, we pop the node right afterwards)
+        // constant-folded (indeed, we remove the node right afterwards)
         let n = self.ops.insert(f(a, b));
-        let out = if matches!((op_a, op_b), (Op::Const(_), Op::Const(_))) {
+        let out = if is_const {
             let v = self.eval(n, &BTreeMap::new())?;
-            self.pop().unwrap(); // removes `n`
+            self.remove(n).unwrap();
             self.constant(v)
         } else {
             n
@@ -343,6 +526,339 @@ impl Context {
         }
     }
 
+    /// Builds a `min` node which doesn't record a choice
+    ///
+    /// This evaluates to the same value as [`Context::min`], but doesn't use
+    /// a slot in the choice array, so it is never pruned during
+    /// simplification.  It's meant for `min` operations within shape math
+    /// (e.g. limiting a parameter to a range), where pruning one branch
+    /// doesn't usefully shrink the tape; skipping choice tracking keeps
+    /// choice arrays small and makes interval evaluation cheaper.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let op = ctx.min_nc(x, 5.0).unwrap();
+    /// let v = ctx.eval_xyz(op, 2.0, 0.0, 0.0).unwrap();
+    /// assert_eq!(v, 2.0);
+    /// ```
+    pub fn min_nc<A: IntoNode, B: IntoNode>(
+        &mut self,
+        a: A,
+        b: B,
+    ) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        let b = b.into_node(self)?;
+        if a == b {
+            Ok(a)
+        } else {
+            self.op_binary_commutative(a, b, BinaryOpcode::MinNc)
+        }
+    }
+
+    /// Builds a `max` node which doesn't record a choice
+    ///
+    /// See [`Context::min_nc`] for details.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let op = ctx.max_nc(x, 5.0).unwrap();
+    /// let v = ctx.eval_xyz(op, 2.0, 0.0, 0.0).unwrap();
+    /// assert_eq!(v, 5.0);
+    /// ```
+    pub fn max_nc<A: IntoNode, B: IntoNode>(
+        &mut self,
+        a: A,
+        b: B,
+    ) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        let b = b.into_node(self)?;
+        if a == b {
+            Ok(a)
+        } else {
+            self.op_binary_commutative(a, b, BinaryOpcode::MaxNc)
+        }
+    }
+
+    /// Builds a four-quadrant arctangent node, i.e. the angle of the point
+    /// `(b, a)` (in radians, from -π to π)
+    ///
+    /// The argument order matches the usual `atan2(y, x)` convention.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let op = ctx.atan2(y, x).unwrap();
+    /// let v = ctx.eval_xyz(op, -1.0, 0.0, 0.0).unwrap();
+    /// assert_eq!(v, std::f64::consts::PI);
+    /// ```
+    pub fn atan2<A: IntoNode, B: IntoNode>(
+        &mut self,
+        a: A,
+        b: B,
+    ) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        let b = b.into_node(self)?;
+        self.op_binary(a, b, BinaryOpcode::Atan2)
+    }
+    /// Builds a `hypot` node, which computes `sqrt(a² + b²)`
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let op = ctx.hypot(x, y).unwrap();
+    /// let v = ctx.eval_xyz(op, 3.0, -4.0, 0.0).unwrap();
+    /// assert_eq!(v, 5.0);
+    /// ```
+    pub fn hypot<A: IntoNode, B: IntoNode>(
+        &mut self,
+        a: A,
+        b: B,
+    ) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        let b = b.into_node(self)?;
+        self.op_binary_commutative(a, b, BinaryOpcode::Hypot)
+    }
+
+    /// Builds a floored modulo node, which computes `a - b * floor(a / b)`
+    ///
+    /// Unlike Rust's `%` operator, the result has the same sign as `b`.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let op = ctx.modulo(x, 2.0).unwrap();
+    /// let v = ctx.eval_xyz(op, -0.5, 0.0, 0.0).unwrap();
+    /// assert_eq!(v, 1.5);
+    /// ```
+    pub fn modulo<A: IntoNode, B: IntoNode>(
+        &mut self,
+        a: A,
+        b: B,
+    ) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        let b = b.into_node(self)?;
+        self.op_binary(a, b, BinaryOpcode::Mod)
+    }
+
+    /// Builds a node which applies a user-defined operation to `a` and `b`
+    ///
+    /// Passing the same operation (i.e. a clone of the same `Arc`) multiple
+    /// times only registers it once.  See [`CustomOp`] for an example.
+    pub fn custom<A: IntoNode, B: IntoNode>(
+        &mut self,
+        op: Arc<dyn CustomOp>,
+        a: A,
+        b: B,
+    ) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        let b = b.into_node(self)?;
+        let ptr = Arc::as_ptr(&op) as *const ();
+        let i = match self
+            .custom
+            .iter()
+            .position(|c| Arc::as_ptr(c) as *const () == ptr)
+        {
+            Some(i) => i,
+            None if self.custom.len() > u16::MAX as usize => {
+                return Err(Error::TooManyCustomOps);
+            }
+            None => {
+                self.custom.push(op);
+                self.custom.len() - 1
+            }
+        };
+        let c = CustomNode(i);
+        self.op_binary_f(a, b, |lhs, rhs| Op::Custom(c, lhs, rhs))
+    }
+
+    /// Looks up a user-defined operation by index
+    pub(crate) fn custom_op(&self, c: CustomNode) -> &Arc<dyn CustomOp> {
+        &self.custom[c.0]
+    }
+
+    /// Builds a gradient noise node, with a seed selecting the pattern
+    ///
+    /// The result is deterministic, in the range `[-1, 1]`, and zero at every
+    /// integer lattice point; see [`fidget::eval::noise`](crate::eval::noise)
+    /// for details.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let z = ctx.z();
+    /// let op = ctx.noise3(x, y, z, 1).unwrap();
+    /// let v = ctx.eval_xyz(op, 0.5, 1.25, -3.0).unwrap();
+    /// assert!(v.abs() <= 1.0);
+    /// assert_eq!(ctx.eval_xyz(op, 1.0, 2.0, 3.0).unwrap(), 0.0);
+    /// ```
+    pub fn noise3<A: IntoNode, B: IntoNode, C: IntoNode>(
+        &mut self,
+        x: A,
+        y: B,
+        z: C,
+        seed: u16,
+    ) -> Result<Node, Error> {
+        let x = x.into_node(self)?;
+        let y = y.into_node(self)?;
+        let z = z.into_node(self)?;
+        self.op_noise(x, y, z, seed)
+    }
+
+    /// Find or create a [Node] for a noise operation, with constant folding
+    fn op_noise(
+        &mut self,
+        x: Node,
+        y: Node,
+        z: Node,
+        seed: u16,
+    ) -> Result<Node, Error> {
+        let mut all_const = true;
+        for n in [x, y, z] {
+            let op = self.get_op(n).ok_or(Error::BadNode)?;
+            all_const &= matches!(op, Op::Const(_));
+        }
+        let n = self.ops.insert(Op::Noise(seed, x, y, z));
+        let out = if all_const {
+            let v = self.eval(n, &BTreeMap::new())?;
+            self.remove(n).unwrap();
+            self.constant(v)
+        } else {
+            n
+        };
+        Ok(out)
+    }
+
+    /// Builds a `clamp` node, which limits `x` to the range `[lo, hi]`
+    ///
+    /// This is equivalent to `max(min(x, hi), lo)` (so the result is `lo` if
+    /// `lo > hi`), but is a single operation which doesn't make choices.
+    /// Shapes built from many clamps (e.g. exact boxes and capsules) therefore
+    /// don't bloat the choice array; the flip side is that a clamp is never
+    /// pruned during simplification.
+    ///
+    /// A `NaN` argument always produces a `NaN` result; the tape's
+    /// [`NanPolicy`](crate::eval::NanPolicy) only applies to `min` and `max`.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let op = ctx.clamp(x, -1.0, 2.0).unwrap();
+    /// assert_eq!(ctx.eval_xyz(op, 0.5, 0.0, 0.0).unwrap(), 0.5);
+    /// assert_eq!(ctx.eval_xyz(op, 3.0, 0.0, 0.0).unwrap(), 2.0);
+    /// assert_eq!(ctx.eval_xyz(op, -5.0, 0.0, 0.0).unwrap(), -1.0);
+    /// ```
+    pub fn clamp<A: IntoNode, B: IntoNode, C: IntoNode>(
+        &mut self,
+        x: A,
+        lo: B,
+        hi: C,
+    ) -> Result<Node, Error> {
+        let x = x.into_node(self)?;
+        let lo = lo.into_node(self)?;
+        let hi = hi.into_node(self)?;
+        self.op_clamp(x, lo, hi)
+    }
+
+    /// Find or create a [Node] for a clamp operation, with constant folding
+    fn op_clamp(&mut self, x: Node, lo: Node, hi: Node) -> Result<Node, Error> {
+        let mut all_const = true;
+        for n in [x, lo, hi] {
+            let op = self.get_op(n).ok_or(Error::BadNode)?;
+            all_const &= matches!(op, Op::Const(_));
+        }
+        let n = self.ops.insert(Op::Clamp(x, lo, hi));
+        let out = if all_const {
+            let v = self.eval(n, &BTreeMap::new())?;
+            self.remove(n).unwrap();
+            self.constant(v)
+        } else {
+            n
+        };
+        Ok(out)
+    }
+
+    /// Builds the `min` of many arguments, as a single n-ary node
+    ///
+    /// Nested `min_many` nodes are flattened into their parent, duplicate
+    /// arguments are removed, and constant arguments are folded together.
+    ///
+    /// In a tape, the node is lowered into a balanced tree of binary `min`
+    /// operations (with logarithmic depth), whose choices are treated as a
+    /// single group: simplification drops every argument which can't be the
+    /// minimum, then rebuilds a balanced tree from the rest.  For large unions
+    /// (e.g. in CSG), this produces much shorter tapes than a chain of
+    /// [`Context::min`] calls.
+    ///
+    /// Returns [`Error::EmptyArguments`] if `args` is empty.
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let c = ctx.constant(3.0);
+    /// let op = ctx.min_many([x, y, c])?;
+    /// let v = ctx.eval_xyz(op, 2.0, 4.0, 0.0)?;
+    /// assert_eq!(v, 2.0);
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn min_many<I, A>(&mut self, args: I) -> Result<Node, Error>
+    where
+        I: IntoIterator<Item = A>,
+        A: IntoNode,
+    {
+        self.op_nary(args, NaryOpcode::Min)
+    }
+
+    /// Builds the `max` of many arguments, as a single n-ary node
+    ///
+    /// See [`Context::min_many`] for details.
+    ///
+    /// Returns [`Error::EmptyArguments`] if `args` is empty.
+    pub fn max_many<I, A>(&mut self, args: I) -> Result<Node, Error>
+    where
+        I: IntoIterator<Item = A>,
+        A: IntoNode,
+    {
+        self.op_nary(args, NaryOpcode::Max)
+    }
+
+    /// Find or create a [Node] for an n-ary operation, with flattening,
+    /// deduplication, and constant folding
+    fn op_nary<I, A>(&mut self, args: I, op: NaryOpcode) -> Result<Node, Error>
+    where
+        I: IntoIterator<Item = A>,
+        A: IntoNode,
+    {
+        let mut todo = vec![];
+        for a in args {
+            todo.push(a.into_node(self)?);
+        }
+        let mut nodes = vec![];
+        let mut constant: Option<f64> = None;
+        while let Some(a) = todo.pop() {
+            match self.get_op(a).ok_or(Error::BadNode)? {
+                Op::Const(c) => {
+                    constant = Some(match (constant, op) {
+                        (None, _) => c.0,
+                        (Some(v), NaryOpcode::Min) => v.min(c.0),
+                        (Some(v), NaryOpcode::Max) => v.max(c.0),
+                    });
+                }
+                Op::Nary(o, args) if *o == op => {
+                    todo.extend(args.iter().copied())
+                }
+                _ => nodes.push(a),
+            }
+        }
+        nodes.sort();
+        nodes.dedup();
+        if let Some(c) = constant {
+            nodes.push(self.constant(c));
+        }
+        match nodes.len() {
+            0 => Err(Error::EmptyArguments),
+            1 => Ok(nodes[0]),
+            _ => Ok(self.ops.insert(Op::Nary(op, nodes.into()))),
+        }
+    }
+
     /// Builds a unary negation node
     /// ```
     /// # let mut ctx = fidget::context::Context::new();
@@ -397,6 +913,32 @@ impl Context {
         self.op_unary(a, UnaryOpcode::Sqrt)
     }
 
+    /// Builds a node which calculates the sine of its input (in radians)
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let op = ctx.sin(x).unwrap();
+    /// let v = ctx.eval_xyz(op, std::f64::consts::FRAC_PI_2, 0.0, 0.0).unwrap();
+    /// assert_eq!(v, 1.0);
+    /// ```
+    pub fn sin<A: IntoNode>(&mut self, a: A) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        self.op_unary(a, UnaryOpcode::Sin)
+    }
+
+    /// Builds a node which calculates the cosine of its input (in radians)
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let op = ctx.cos(x).unwrap();
+    /// let v = ctx.eval_xyz(op, 0.0, 0.0, 0.0).unwrap();
+    /// assert_eq!(v, 1.0);
+    /// ```
+    pub fn cos<A: IntoNode>(&mut self, a: A) -> Result<Node, Error> {
+        let a = a.into_node(self)?;
+        self.op_unary(a, UnaryOpcode::Cos)
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     // Derived functions
     /// Builds a node which squares its input
@@ -468,20 +1010,79 @@ impl Context {
     /// [`vm::Op::Load` / `vm::Op::Store`](crate::vm::Op) operations.
     ///
     /// This should always succeed unless the `root` is from a different
-    /// `Context`, in which case `Error::BadNode` will be returned.
+    /// `Context`, in which case `Error::BadNode` will be returned, or the tape
+    /// needs more than [`E::SLOT_LIMIT`](Family::SLOT_LIMIT) slots, in which
+    /// case `Error::TooManySlots` will be returned.  If the subtree has more
+    /// than [`Context::node_limit`] nodes, flattening it (or finding the tape's
+    /// bounds with [`Context::bounds`]) fails with `Error::TooManyNodes`.
     pub fn get_tape<E: Family>(&self, root: Node) -> Result<Tape<E>, Error> {
+        let tape = Tape::<E>::from_ssa(self.get_ssa(root)?);
+        if tape.slot_count() > E::SLOT_LIMIT {
+            return Err(Error::TooManySlots(tape.slot_count(), E::SLOT_LIMIT));
+        }
+        Ok(tape.with_bounds(self.bounds(root)?))
+    }
+
+    /// Builds a tape for `root`, reusing work from a previous tape
+    ///
+    /// This is meant for interactive editing, where a shape is rebuilt after
+    /// every change.  If the new expression has the same structure as the one
+    /// used to build `prev`, differing only in the values of its constants,
+    /// the new constants are patched into a copy of `prev` without repeating
+    /// register allocation or scheduling.  Otherwise, this falls back to
+    /// building a new tape, as in [`Context::get_tape`].
+    ///
+    /// In both cases, evaluation settings (e.g.
+    /// [`Tape::with_nan_policy`]) are inherited from `prev`, and the tape's
+    /// bounds are recomputed with [`Context::bounds`].
+    ///
+    /// Evaluators must be rebuilt for the new tape.  By default, JIT evaluators
+    /// compile immediates into their machine code; if `prev` uses a
+    /// [constant table](Tape::with_constant_table), they can instead be
+    /// [rebound](crate::eval::tracing::TracingEval::rebind) without recompiling.
+    ///
+    /// ```
+    /// # use fidget::{context::Context, vm};
+    /// let mut ctx = Context::new();
+    /// let x = ctx.x();
+    /// let a = ctx.add(x, 1.0)?;
+    /// let prev = ctx.get_tape::<vm::Eval>(a)?;
+    ///
+    /// let b = ctx.add(x, 2.0)?;
+    /// let tape = ctx.update_tape(b, &prev)?;
+    /// let eval = tape.new_point_evaluator();
+    /// assert_eq!(eval.eval(1.0, 0.0, 0.0, &[])?.0, 3.0);
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn update_tape<E: Family>(
+        &self,
+        root: Node,
+        prev: &Tape<E>,
+    ) -> Result<Tape<E>, Error> {
+        let tape = prev.update(self.get_ssa(root)?);
+        if tape.slot_count() > E::SLOT_LIMIT {
+            return Err(Error::TooManySlots(tape.slot_count(), E::SLOT_LIMIT));
+        }
+        Ok(tape.with_bounds(self.bounds(root)?))
+    }
+
+    /// Flattens a subtree of the graph into an SSA tape
+    fn get_ssa(&self, root: Node) -> Result<SsaTape, Error> {
         let mut parent_count: BTreeMap<Node, usize> = BTreeMap::new();
         let mut seen = BTreeSet::new();
         let mut todo = vec![root];
         let mut builder = Builder::new();
+        let mut budget = NodeBudget::new(self);
 
         // Accumulate parent counts and declare all the nodes into the builder
+        // (the second pass visits the same nodes, so it isn't counted)
         while let Some(node) = todo.pop() {
             if !seen.insert(node) {
                 continue;
             }
+            budget.visit()?;
             let op = self.get_op(node).ok_or(Error::BadNode)?;
-            builder.declare_node(node, *op);
+            builder.declare_node(node, op.clone());
             for child in op.iter_children() {
                 *parent_count.entry(child).or_default() += 1;
                 todo.push(child);
@@ -501,9 +1102,10 @@ impl Context {
                 todo.push(child);
                 *parent_count.get_mut(&child).unwrap() -= 1;
             }
-            builder.step(node, *op, self);
+            builder.step(node, op.clone(), self);
         }
         let mut ssa_tape = builder.finish();
+        ssa_tape.custom = Arc::new(self.custom.clone());
 
         // Special case if the Node is a single constant, which isn't usually
         // recorded in the tape
@@ -511,7 +1113,7 @@ impl Context {
             let c = self.const_value(root).unwrap().unwrap() as f32;
             ssa_tape.tape.push(crate::ssa::Op::CopyImm(0, c));
         }
-        Ok(Tape::from_ssa(ssa_tape))
+        Ok(ssa_tape)
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -525,11 +1127,61 @@ impl Context {
         self.check_node(root)?;
         xyz.iter().try_for_each(|x| self.check_node(*x))?;
 
-        let mut done = [self.x(), self.y(), self.z()]
+        let map = [self.x(), self.y(), self.z()]
             .into_iter()
             .zip(xyz)
             .collect::<BTreeMap<_, _>>();
+        self.remap(root, map)
+    }
+
+    /// Replaces a variable with a constant value, returning a new root node
+    ///
+    /// Any operations which become constant as a result are folded.
+    /// `var` may be any input or variable node, e.g. from
+    /// [`Context::x`](Self::x) or [`Context::var`](Self::var).
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let a = ctx.var("a").unwrap();
+    /// let a2 = ctx.square(a).unwrap();
+    /// let sum = ctx.add(x, a2).unwrap();
+    ///
+    /// let out = ctx.bind_constant(sum, a, 3.0).unwrap();
+    /// assert_eq!(ctx.eval_xyz(out, 1.0, 0.0, 0.0).unwrap(), 10.0);
+    ///
+    /// // The constant subtree `(square a)` is folded into a single constant
+    /// let out = ctx.bind_constant(a2, a, 3.0).unwrap();
+    /// assert_eq!(ctx.const_value(out).unwrap(), Some(9.0));
+    /// ```
+    ///
+    /// Returns [`Error::BadNode`] if either node is invalid, or
+    /// [`Error::BadVar`] if `var` is not an input or variable node.
+    pub fn bind_constant(
+        &mut self,
+        root: Node,
+        var: Node,
+        value: f64,
+    ) -> Result<Node, Error> {
+        self.check_node(root)?;
+        match self.get_op(var) {
+            Some(Op::Var(..) | Op::Input(..)) => (),
+            Some(_) => return Err(Error::BadVar),
+            None => return Err(Error::BadNode),
+        }
+        let c = self.constant(value);
+        self.remap(root, [(var, c)].into_iter().collect())
+    }
 
+    /// Rebuilds the tree, replacing leaf nodes based on the given map
+    ///
+    /// Operations are rebuilt with constant folding, so the result may be
+    /// smaller than the original tree.
+    fn remap(
+        &mut self,
+        root: Node,
+        mut done: BTreeMap<Node, Node>,
+    ) -> Result<Node, Error> {
         // Depth-first recursion on the heap, to protect against stack overflows
         enum Action {
             Down,
@@ -538,12 +1190,14 @@ impl Context {
 
         let mut todo = vec![(Action::Down, root)];
         let mut seen = BTreeSet::new();
+        let mut budget = NodeBudget::new(self);
         while let Some((action, node)) = todo.pop() {
             match action {
                 Action::Down => {
                     if !seen.insert(node) {
                         continue;
                     }
+                    budget.visit()?;
                     todo.push((Action::Up, node));
                     todo.extend(
                         self.get_op(node)
@@ -563,8 +1217,33 @@ impl Context {
                             let a = done.get(arg).unwrap();
                             self.op_unary(*a, *op).unwrap()
                         }
-                        Op::Var(..) | Op::Const(..) => node,
-                        Op::Input(..) => *done.get(&node).unwrap_or(&node),
+                        Op::Custom(c, lhs, rhs) => {
+                            let (c, a, b) = (*c, done[lhs], done[rhs]);
+                            self.op_binary_f(a, b, |lhs, rhs| {
+                                Op::Custom(c, lhs, rhs)
+                            })
+                            .unwrap()
+                        }
+                        Op::Noise(seed, x, y, z) => {
+                            let (x, y, z) = (done[x], done[y], done[z]);
+                            self.op_noise(x, y, z, *seed).unwrap()
+                        }
+                        Op::Clamp(x, lo, hi) => {
+                            let (x, lo, hi) = (done[x], done[lo], done[hi]);
+                            self.op_clamp(x, lo, hi).unwrap()
+                        }
+                        Op::Nary(op, args) => {
+                            let op = *op;
+                            let args = args
+                                .iter()
+                                .map(|a| done[a])
+                                .collect::<Vec<_>>();
+                            self.op_nary(args, op).unwrap()
+                        }
+                        Op::Const(..) => node,
+                        Op::Var(..) | Op::Input(..) => {
+                            *done.get(&node).unwrap_or(&node)
+                        }
                     };
                     done.insert(node, r);
                 }
@@ -607,63 +1286,98 @@ impl Context {
     ///
     /// This is extremely inefficient; consider calling
     /// [`get_tape`](Self::get_tape) and building an evaluator instead.
+    ///
+    /// Returns [`Error::TooManyNodes`] if evaluation would visit more than
+    /// [`Context::node_limit`] nodes.
     pub fn eval(
         &self,
         root: Node,
         vars: &BTreeMap<String, f64>,
     ) -> Result<f64, Error> {
-        let mut cache = vec![None; self.ops.len()].into();
-        self.eval_inner(root, vars, &mut cache)
-    }
+        self.check_node(root)?;
+        let mut cache: Vec<Option<f64>> = vec![None; self.ops.slot_count()];
 
-    fn eval_inner(
-        &self,
-        node: Node,
-        vars: &BTreeMap<String, f64>,
-        cache: &mut IndexVec<Option<f64>, Node>,
-    ) -> Result<f64, Error> {
-        if node.0 >= cache.len() {
-            return Err(Error::BadNode);
-        }
-        if let Some(v) = cache[node] {
-            return Ok(v);
-        }
-        let mut get = |n: Node| self.eval_inner(n, vars, cache);
-        let v = match self.get_op(node).ok_or(Error::BadNode)? {
-            Op::Var(v) | Op::Input(v) => {
-                let var_name = self.vars.get_by_index(*v).unwrap();
-                *vars.get(var_name).unwrap()
+        // Depth-first recursion on the heap, to protect against stack overflows
+        let mut todo = vec![(false, root)];
+        let mut budget = NodeBudget::new(self);
+        while let Some((ready, node)) = todo.pop() {
+            if cache[node.index()].is_some() {
+                continue;
             }
-            Op::Const(c) => c.0,
-
-            Op::Binary(op, a, b) => {
-                let a = get(*a)?;
-                let b = get(*b)?;
-                match op {
-                    BinaryOpcode::Add => a + b,
-                    BinaryOpcode::Sub => a - b,
-                    BinaryOpcode::Mul => a * b,
-                    BinaryOpcode::Div => a / b,
-                    BinaryOpcode::Min => a.min(b),
-                    BinaryOpcode::Max => a.max(b),
-                }
+            let op = self.get_op(node).unwrap();
+            if !ready {
+                budget.visit()?;
+                todo.push((true, node));
+                todo.extend(op.iter_children().map(|c| (false, c)));
+                continue;
             }
+            let get = |n: &Node| cache[n.index()].unwrap();
+            let v = match op {
+                Op::Var(v) | Op::Input(v) => {
+                    let var_name = self.vars.get_by_index(*v).unwrap();
+                    *vars.get(var_name).unwrap()
+                }
+                Op::Const(c) => c.0,
+
+                Op::Binary(op, a, b) => {
+                    let (a, b) = (get(a), get(b));
+                    match op {
+                        BinaryOpcode::Add => a + b,
+                        BinaryOpcode::Sub => a - b,
+                        BinaryOpcode::Mul => a * b,
+                        BinaryOpcode::Div => a / b,
+                        BinaryOpcode::Min | BinaryOpcode::MinNc => a.min(b),
+                        BinaryOpcode::Max | BinaryOpcode::MaxNc => a.max(b),
+                        BinaryOpcode::Atan2 => a.atan2(b),
+                        BinaryOpcode::Hypot => a.hypot(b),
+                        BinaryOpcode::Mod => a - b * (a / b).floor(),
+                    }
+                }
 
-            // Unary operations
-            Op::Unary(op, a) => {
-                let a = get(*a)?;
-                match op {
-                    UnaryOpcode::Neg => -a,
-                    UnaryOpcode::Abs => a.abs(),
-                    UnaryOpcode::Recip => 1.0 / a,
-                    UnaryOpcode::Sqrt => a.sqrt(),
-                    UnaryOpcode::Square => a * a,
+                // Unary operations
+                Op::Unary(op, a) => {
+                    let a = get(a);
+                    match op {
+                        UnaryOpcode::Neg => -a,
+                        UnaryOpcode::Abs => a.abs(),
+                        UnaryOpcode::Recip => 1.0 / a,
+                        UnaryOpcode::Sqrt => a.sqrt(),
+                        UnaryOpcode::Square => a * a,
+                        UnaryOpcode::Sin => a.sin(),
+                        UnaryOpcode::Cos => a.cos(),
+                    }
                 }
-            }
-        };
 
-        cache[node] = Some(v);
-        Ok(v)
+                Op::Custom(c, a, b) => {
+                    let (a, b) = (get(a), get(b));
+                    self.custom[c.0].eval_f32(a as f32, b as f32) as f64
+                }
+                Op::Noise(seed, x, y, z) => {
+                    let (x, y, z) =
+                        (get(x) as f32, get(y) as f32, get(z) as f32);
+                    noise::noise3(x, y, z, *seed) as f64
+                }
+                Op::Clamp(x, lo, hi) => {
+                    let (x, lo, hi) = (get(x), get(lo), get(hi));
+                    if x.is_nan() || lo.is_nan() || hi.is_nan() {
+                        f64::NAN
+                    } else {
+                        x.min(hi).max(lo)
+                    }
+                }
+                Op::Nary(op, args) => {
+                    let args = args.iter().map(get);
+                    match op {
+                        NaryOpcode::Min => args.fold(f64::INFINITY, f64::min),
+                        NaryOpcode::Max => {
+                            args.fold(f64::NEG_INFINITY, f64::max)
+                        }
+                    }
+                }
+            };
+            cache[node.index()] = Some(v);
+        }
+        Ok(cache[root.index()].unwrap())
     }
 
     /// Parses a flat text representation of a math tree. For example, the
@@ -685,7 +1399,8 @@ impl Context {
     /// ```
     ///
     /// This representation is loosely defined and only intended for use in
-    /// quick experiments.
+    /// quick experiments.  It requires the `std` feature.
+    #[cfg(feature = "std")]
     pub fn from_text<R: Read>(r: R) -> Result<(Self, Node), Error> {
         let reader = BufReader::new(r);
         let mut ctx = Self::new();
@@ -715,12 +1430,22 @@ impl Context {
                 "neg" => ctx.neg(pop()?)?,
                 "sqrt" => ctx.sqrt(pop()?)?,
                 "square" => ctx.square(pop()?)?,
+                "sin" => ctx.sin(pop()?)?,
+                "cos" => ctx.cos(pop()?)?,
                 "add" => ctx.add(pop()?, pop()?)?,
                 "mul" => ctx.mul(pop()?, pop()?)?,
                 "min" => ctx.min(pop()?, pop()?)?,
                 "max" => ctx.max(pop()?, pop()?)?,
                 "div" => ctx.div(pop()?, pop()?)?,
                 "sub" => ctx.sub(pop()?, pop()?)?,
+                "atan2" => ctx.atan2(pop()?, pop()?)?,
+                "hypot" => ctx.hypot(pop()?, pop()?)?,
+                "mod" => ctx.modulo(pop()?, pop()?)?,
+                "noise" => {
+                    let (x, y, z) = (pop()?, pop()?, pop()?);
+                    let seed = iter.next().unwrap().parse().unwrap();
+                    ctx.noise3(x, y, z, seed)?
+                }
                 op => return Err(Error::UnknownOpcode(op.to_owned())),
             };
             seen.insert(i, node);
@@ -732,60 +1457,9 @@ impl Context {
         }
     }
 
-    /// Converts the entire context into a GraphViz drawing
-    pub fn dot(&self) -> String {
-        let mut out = "digraph mygraph{\n".to_owned();
-        for node in self.ops.keys() {
-            let op = self.get_op(node).unwrap();
-            out += &self.dot_node(node);
-            out += &op.dot_edges(node);
-        }
-        out += "}\n";
-        out
-    }
-
-    /// Converts the given node into a GraphViz node
-    ///
-    /// (this is a local function instead of a function on `Op` because it
-    ///  requires looking up variables by name)
-    fn dot_node(&self, i: Node) -> String {
-        let mut out = format!(r#"n{} [label = ""#, i.get());
-        let op = self.get_op(i).unwrap();
-        match op {
-            Op::Const(c) => write!(out, "{}", c).unwrap(),
-            Op::Var(v) | Op::Input(v) => {
-                let v = self.vars.get_by_index(*v).unwrap();
-                out += v;
-            }
-            Op::Binary(op, ..) => match op {
-                BinaryOpcode::Add => out += "add",
-                BinaryOpcode::Sub => out += "sub",
-                BinaryOpcode::Mul => out += "mul",
-                BinaryOpcode::Div => out += "div",
-                BinaryOpcode::Min => out += "min",
-                BinaryOpcode::Max => out += "max",
-            },
-            Op::Unary(op, ..) => match op {
-                UnaryOpcode::Neg => out += "neg",
-                UnaryOpcode::Abs => out += "abs",
-                UnaryOpcode::Recip => out += "recip",
-                UnaryOpcode::Sqrt => out += "sqrt",
-                UnaryOpcode::Square => out += "square",
-            },
-        };
-        write!(
-            out,
-            r#"" color="{0}1" shape="{1}" fontcolor="{0}4"]"#,
-            op.dot_node_color(),
-            op.dot_node_shape()
-        )
-        .unwrap();
-        out
-    }
-
     /// Looks up an operation by `Node` handle
-    fn get_op(&self, node: Node) -> Option<&Op> {
-        self.ops.get_by_index(node)
+    pub(crate) fn get_op(&self, node: Node) -> Option<&Op> {
+        self.ops.get(node)
     }
 }
 
@@ -835,6 +1509,7 @@ impl IntoNode for f64 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::eval::Choice;
 
     // This can't be in a doctest, because it uses a private function
     #[test]
@@ -899,4 +1574,322 @@ mod test {
         let v = ctx.remap_xyz(s, [one, y, z]).unwrap();
         assert_eq!(ctx.eval_xyz(v, 0.0, 1.0, 0.0).unwrap(), 4.0);
     }
+
+    #[test]
+    fn test_bind_constant() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.var("a").unwrap();
+
+        let ax = ctx.mul(a, x).unwrap();
+        let ay = ctx.min(a, y).unwrap();
+        let sum = ctx.add(ax, ay).unwrap();
+
+        let v = ctx.bind_constant(sum, a, 2.0).unwrap();
+        assert_eq!(ctx.eval_xyz(v, 3.0, 5.0, 0.0).unwrap(), 8.0);
+        assert_eq!(ctx.eval_xyz(v, 3.0, 1.0, 0.0).unwrap(), 7.0);
+
+        let v = ctx.bind_constant(sum, x, 0.5).unwrap();
+        let vars = [("Y", 5.0), ("a", 4.0)]
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect();
+        assert_eq!(ctx.eval(v, &vars).unwrap(), 6.0);
+
+        let c = ctx.constant(1.0);
+        assert!(matches!(ctx.bind_constant(sum, c, 0.0), Err(Error::BadVar)));
+
+        // Check the tape-level equivalent
+        let tape = ctx.get_tape::<crate::vm::Eval>(sum).unwrap();
+        assert_eq!(tape.choice_count(), 1);
+        let t = tape.bind_constant("a", 2.0).unwrap();
+        assert_eq!(t.choice_count(), 1);
+        // The original tape fuses `a * x` into the addition, so the bound tape
+        // (which multiplies by an immediate instead) isn't any shorter
+        assert!(t.len() <= tape.len());
+        assert!(!t.iter_asm().any(|op| matches!(op, crate::vm::Op::Var(..))));
+        let eval = t.new_point_evaluator();
+        assert_eq!(eval.eval(3.0, 5.0, 0.0, &[0.0]).unwrap().0, 8.0);
+        assert_eq!(eval.eval(3.0, 1.0, 0.0, &[0.0]).unwrap().0, 7.0);
+
+        let m = ctx.min(a, 1.0).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(m).unwrap();
+        let t = tape.bind_constant("a", 3.0).unwrap();
+        assert_eq!(t.choice_count(), 0);
+        assert_eq!(t.len(), 1);
+        let eval = t.new_point_evaluator();
+        assert_eq!(eval.eval(0.0, 0.0, 0.0, &[0.0]).unwrap().0, 1.0);
+
+        assert!(tape.bind_constant("b", 3.0).is_err());
+    }
+
+    #[test]
+    fn test_update_tape() {
+        use crate::eval::NanPolicy;
+        fn shape(ctx: &mut Context, a: f64, b: f64, c: f64) -> Node {
+            let x = ctx.x();
+            let y = ctx.y();
+            let ax = ctx.mul(x, a).unwrap();
+            let by = ctx.add(y, b).unwrap();
+            let sum = ctx.add(ax, by).unwrap();
+            ctx.min(sum, c).unwrap()
+        }
+        let check = |ctx: &Context,
+                     root: Node,
+                     tape: &Tape<crate::vm::Eval>| {
+            let eval = tape.new_point_evaluator();
+            for (x, y) in [(0.0, 0.0), (1.0, -2.0), (0.5, 3.0)] {
+                let expected = ctx.eval_xyz(root, x, y, 0.0).unwrap() as f32;
+                let (v, _) = eval.eval(x as f32, y as f32, 0.0, &[]).unwrap();
+                assert_eq!(v, expected, "mismatch at ({x}, {y})");
+            }
+        };
+
+        let mut ctx = Context::new();
+        let a = shape(&mut ctx, 2.0, 3.0, 4.0);
+        let prev = ctx
+            .get_tape::<crate::vm::Eval>(a)
+            .unwrap()
+            .with_nan_policy(NanPolicy::Empty);
+
+        // Only constants change, so immediates are patched
+        let b = shape(&mut ctx, 0.5, -1.0, 1.5);
+        assert!(ctx
+            .get_ssa(a)
+            .unwrap()
+            .same_structure(&ctx.get_ssa(b).unwrap()));
+        let t = ctx.update_tape(b, &prev).unwrap();
+        assert_eq!(t.len(), prev.len());
+        assert_eq!(t.nan_policy(), NanPolicy::Empty);
+        assert!(t.iter_asm().any(|op| op.to_string().contains("-1")));
+        check(&ctx, b, &t);
+
+        // Two immediates which used to be equal now differ, so the VM tape
+        // can't be patched by value
+        let c = shape(&mut ctx, 4.0, 4.0, 4.0);
+        let prev = ctx.get_tape(c).unwrap();
+        let d = shape(&mut ctx, 4.0, 5.0, 6.0);
+        let t = ctx.update_tape(d, &prev).unwrap();
+        check(&ctx, d, &t);
+
+        // A structural change falls back to building a new tape
+        let x = ctx.x();
+        let e = ctx.sub(d, x).unwrap();
+        assert!(!ctx
+            .get_ssa(d)
+            .unwrap()
+            .same_structure(&ctx.get_ssa(e).unwrap()));
+        let t = ctx.update_tape(e, &prev).unwrap();
+        assert!(t.len() > prev.len());
+        check(&ctx, e, &t);
+    }
+
+    #[test]
+    fn test_min_max_many() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        assert_eq!(ctx.min_many([x, x]).unwrap(), x);
+        assert!(matches!(
+            ctx.max_many(Vec::<Node>::new()),
+            Err(Error::EmptyArguments)
+        ));
+
+        let c = ctx.min_many([4.0, 2.0, 3.0]).unwrap();
+        assert_eq!(ctx.const_value(c).unwrap(), Some(2.0));
+        let c = ctx.max_many([4.0, 2.0, 3.0]).unwrap();
+        assert_eq!(ctx.const_value(c).unwrap(), Some(4.0));
+
+        // Nested groups of the same operation are flattened, and argument
+        // order doesn't matter
+        let one = ctx.constant(1.0);
+        let a = ctx.min_many([x, y, one]).unwrap();
+        let b = ctx.min_many([y, one]).unwrap();
+        let b = ctx.min_many([b, x]).unwrap();
+        assert_eq!(a, b);
+        let Some(Op::Nary(NaryOpcode::Min, args)) = ctx.get_op(a) else {
+            panic!("expected an n-ary node");
+        };
+        assert_eq!(args.len(), 3);
+
+        let nodes = (0..1000)
+            .map(|i| ctx.add(x, i as f64).unwrap())
+            .chain([y])
+            .collect::<Vec<_>>();
+        let m = ctx.min_many(nodes.iter().cloned()).unwrap();
+        assert_eq!(ctx.eval_xyz(m, 1.0, 5.0, 0.0).unwrap(), 1.0);
+        assert_eq!(ctx.eval_xyz(m, 1.0, -5.0, 0.0).unwrap(), -5.0);
+        let m = ctx.max_many(nodes).unwrap();
+        assert_eq!(ctx.eval_xyz(m, 1.0, 5.0, 0.0).unwrap(), 1000.0);
+
+        // The node makes one choice per argument (minus one)
+        let tape = ctx.get_tape::<crate::vm::Eval>(m).unwrap();
+        assert_eq!(tape.choice_count(), 1000);
+    }
+
+    #[test]
+    fn test_custom() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op: Arc<dyn CustomOp> = Arc::new(SquareSub);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.custom(op.clone(), x, y).unwrap();
+        let b = ctx.custom(op.clone(), x, y).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(ctx.custom.len(), 1);
+
+        // A different instance is a different operation
+        let c = ctx.custom(Arc::new(SquareSub), x, y).unwrap();
+        assert_ne!(a, c);
+        assert_eq!(ctx.custom.len(), 2);
+
+        assert_eq!(ctx.eval_xyz(a, 3.0, 1.0, 0.0).unwrap(), 8.0);
+        let d = ctx.custom(op, 3.0, 2.0).unwrap();
+        assert_eq!(ctx.const_value(d).unwrap(), Some(7.0));
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.noise3(x, y, 0.5, 1).unwrap();
+        let b = ctx.noise3(x, y, 0.5, 1).unwrap();
+        assert_eq!(a, b);
+        let c = ctx.noise3(x, y, 0.5, 2).unwrap();
+        assert_ne!(a, c);
+
+        let v = ctx.eval_xyz(a, 0.25, 1.5, 0.0).unwrap();
+        assert_eq!(v, noise::noise3(0.25, 1.5, 0.5, 1) as f64);
+        let d = ctx.noise3(0.25, 1.5, 0.5, 1).unwrap();
+        assert_eq!(ctx.const_value(d).unwrap(), Some(v));
+    }
+
+    #[test]
+    fn test_names() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let left = ctx.sub(x, 1.0).unwrap();
+        let right = ctx.add(y, 2.0).unwrap();
+        let right = ctx.square(right).unwrap();
+        ctx.name(left, "left").unwrap();
+        ctx.name(right, "right").unwrap();
+        let root = ctx.min(left, right).unwrap();
+
+        let tape = ctx.get_tape::<crate::vm::Eval>(root).unwrap();
+        let names = tape.iter_asm_named().map(|(_, n)| n).collect::<Vec<_>>();
+        assert_eq!(names.len(), tape.len());
+        assert_eq!(names.last().unwrap(), &None); // the root `min`
+        assert_eq!(names.iter().filter(|n| **n == Some("left")).count(), 2);
+        assert_eq!(names.iter().filter(|n| **n == Some("right")).count(), 3);
+
+        // Names survive simplification
+        let tape = tape.simplify(&[Choice::Left]).unwrap();
+        assert_eq!(tape.len(), 2);
+        assert!(tape.iter_asm_named().all(|(_, n)| n == Some("left")));
+
+        // Names are dropped during garbage collection
+        ctx.gc(&[left]).unwrap();
+        assert_eq!(ctx.get_name(left).unwrap(), Some("left"));
+        assert!(ctx.get_name(right).is_err());
+        assert!(matches!(ctx.name(right, "oops"), Err(Error::BadNode)));
+    }
+
+    #[test]
+    fn test_foreign_node() {
+        let mut a = Context::new();
+        let mut b = Context::new();
+        let xa = a.x();
+        let xb = b.x();
+        assert_ne!(xa, xb);
+        assert!(matches!(a.neg(xb), Err(Error::BadNode)));
+        assert!(matches!(b.eval_xyz(xa, 1.0, 0.0, 0.0), Err(Error::BadNode)));
+        assert!(b.get_tape::<crate::vm::Eval>(xa).is_err());
+
+        a.clear();
+        assert!(matches!(a.neg(xa), Err(Error::BadNode)));
+    }
+
+    #[test]
+    fn test_gc() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let r = ctx.add(x2, y2).unwrap();
+        let circle = ctx.sub(r, 1.0).unwrap();
+        let scratch = ctx.mul(y2, 4.0).unwrap();
+        ctx.set_bounds(scratch, BoundingBox::EMPTY).unwrap();
+        let len = ctx.len();
+
+        assert!(matches!(ctx.gc(&[circle, scratch]), Ok(0)));
+        assert!(matches!(ctx.gc(&[circle]), Ok(2)));
+        assert_eq!(ctx.len(), len - 2);
+        assert!(ctx.bounds.is_empty());
+
+        // Stale handles are rejected, even after their slots are recycled
+        assert!(ctx.eval_xyz(scratch, 0.0, 0.0, 0.0).is_err());
+        assert!(matches!(ctx.square(scratch), Err(Error::BadNode)));
+        assert!(matches!(ctx.gc(&[scratch]), Err(Error::BadNode)));
+        let fresh = ctx.constant(12.0);
+        assert!(matches!(ctx.const_value(scratch), Err(Error::BadNode)));
+        assert_eq!(ctx.const_value(fresh).unwrap(), Some(12.0));
+
+        // Surviving handles are still usable
+        assert_eq!(ctx.eval_xyz(circle, 2.0, 0.0, 0.0).unwrap(), 3.0);
+        let tape = ctx.get_tape::<crate::vm::Eval>(circle).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(0.0, 1.0, 0.0, &[]).unwrap().0, 0.0);
+        let neg = ctx.neg(circle).unwrap();
+        assert_eq!(ctx.eval_xyz(neg, 0.0, 0.0, 0.0).unwrap(), 1.0);
+
+        let len = ctx.len();
+        assert_eq!(ctx.gc(&[]).unwrap(), len);
+        assert!(ctx.is_empty());
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let mut s = ctx.square(x).unwrap();
+        let mut t = Tree::x();
+        for i in 0..100 {
+            s = ctx.add(s, i as f64).unwrap();
+            t += i as f64;
+        }
+        let root = ctx.sub(s, 1.0).unwrap();
+
+        ctx.set_node_limit(50);
+        let too_many = |r| matches!(r, Err(Error::TooManyNodes(50)));
+        assert!(too_many(ctx.eval_xyz(root, 0.0, 0.0, 0.0).map(|_| ())));
+        assert!(too_many(ctx.bounds(root).map(|_| ())));
+        assert!(too_many(ctx.dot_builder().root(root).build().map(|_| ())));
+        assert!(!ctx.dot().is_empty());
+        assert!(too_many(t.clone().into_node(&mut ctx).map(|_| ())));
+        assert!(too_many(ctx.get_tape::<crate::vm::Eval>(root).map(|_| ())));
+        let mut other = Context::new();
+        other.set_node_limit(50);
+        assert!(too_many(other.import(&ctx, root).map(|_| ())));
+        assert!(too_many(ctx.content_hash(root).map(|_| ())));
+        assert!(too_many(ctx.bind_constant(root, x, 1.0).map(|_| ())));
+        let len = ctx.len();
+        assert!(too_many(ctx.gc(&[root]).map(|_| ())));
+        assert_eq!(ctx.len(), len);
+
+        ctx.set_node_limit(Context::DEFAULT_NODE_LIMIT);
+        assert_eq!(ctx.eval_xyz(root, 0.0, 0.0, 0.0).unwrap(), 4949.0);
+        assert!(ctx.bounds(root).is_ok());
+        assert!(ctx.dot_builder().root(root).build().is_ok());
+        assert!(t.into_node(&mut ctx).is_ok());
+        assert!(ctx.get_tape::<crate::vm::Eval>(root).is_ok());
+        other.set_node_limit(Context::DEFAULT_NODE_LIMIT);
+        assert!(other.import(&ctx, root).is_ok());
+        assert!(ctx.gc(&[root]).is_ok());
+    }
 }
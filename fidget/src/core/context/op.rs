@@ -1,4 +1,5 @@
-use crate::context::{indexed::Index, Node, VarNode};
+use crate::context::{CustomNode, Node, VarNode};
+use alloc::{borrow::ToOwned, format, string::String, sync::Arc};
 use ordered_float::OrderedFloat;
 
 /// A one-argument math operation
@@ -10,6 +11,8 @@ pub enum UnaryOpcode {
     Recip,
     Sqrt,
     Square,
+    Sin,
+    Cos,
 }
 
 /// A two-argument math operation
@@ -22,6 +25,31 @@ pub enum BinaryOpcode {
     Div,
     Min,
     Max,
+    Atan2,
+    Hypot,
+    /// Floored modulo (see [`Context::modulo`])
+    ///
+    /// [`Context::modulo`]: crate::context::Context::modulo
+    Mod,
+    /// Minimum which doesn't record a choice (see [`Context::min_nc`])
+    ///
+    /// [`Context::min_nc`]: crate::context::Context::min_nc
+    MinNc,
+    /// Maximum which doesn't record a choice (see [`Context::max_nc`])
+    ///
+    /// [`Context::max_nc`]: crate::context::Context::max_nc
+    MaxNc,
+}
+
+/// An operation with any number of arguments
+///
+/// These operations are associative, commutative, and idempotent, so their
+/// arguments are deduplicated and may be evaluated in any order.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum NaryOpcode {
+    Min,
+    Max,
 }
 
 /// An operation in a math expression.
@@ -34,13 +62,26 @@ pub enum BinaryOpcode {
 /// Each `Op` is tightly coupled to the [`Context`](crate::context::Context)
 /// which generated it, and will not be valid for a different `Context`.
 #[allow(missing_docs)]
-#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Op {
     Input(VarNode),
     Var(VarNode),
     Const(OrderedFloat<f64>),
     Binary(BinaryOpcode, Node, Node),
     Unary(UnaryOpcode, Node),
+    /// A user-defined operation, added with
+    /// [`Context::custom`](crate::context::Context::custom)
+    Custom(CustomNode, Node, Node),
+    /// Gradient noise with the given seed, added with
+    /// [`Context::noise3`](crate::context::Context::noise3)
+    Noise(u16, Node, Node, Node),
+    /// Clamps the first node to the range given by the second and third,
+    /// added with [`Context::clamp`](crate::context::Context::clamp)
+    Clamp(Node, Node, Node),
+    /// An operation on two or more arguments, added with
+    /// [`Context::min_many`](crate::context::Context::min_many) or
+    /// [`Context::max_many`](crate::context::Context::max_many)
+    Nary(NaryOpcode, Arc<[Node]>),
 }
 
 fn dot_color_to_rgb(s: &str) -> &'static str {
@@ -59,10 +100,18 @@ impl Op {
         match self {
             Op::Const(..) => "green",
             Op::Var(..) | Op::Input(..) => "red",
-            Op::Binary(BinaryOpcode::Min | BinaryOpcode::Max, ..) => {
-                "dodgerblue"
+            Op::Binary(
+                BinaryOpcode::Min
+                | BinaryOpcode::Max
+                | BinaryOpcode::MinNc
+                | BinaryOpcode::MaxNc,
+                ..,
+            )
+            | Op::Nary(..)
+            | Op::Clamp(..) => "dodgerblue",
+            Op::Binary(..) | Op::Unary(..) | Op::Custom(..) | Op::Noise(..) => {
+                "goldenrod"
             }
-            Op::Binary(..) | Op::Unary(..) => "goldenrod",
         }
     }
 
@@ -71,18 +120,40 @@ impl Op {
         match self {
             Op::Const(..) => "oval",
             Op::Var(..) | Op::Input(..) => "circle",
-            Op::Binary(..) | Op::Unary(..) => "box",
+            Op::Binary(..)
+            | Op::Unary(..)
+            | Op::Custom(..)
+            | Op::Noise(..)
+            | Op::Clamp(..)
+            | Op::Nary(..) => "box",
         }
     }
 
-    /// Iterates over children, producing 0, 1, or 2 values
+    /// Iterates over children
+    ///
+    /// This produces 0, 1, 2, or 3 values, except for [`Op::Nary`], which
+    /// produces each of its arguments.
     pub fn iter_children(&self) -> impl Iterator<Item = Node> {
+        let args = match self {
+            Op::Nary(_, args) => Some(args.clone()),
+            _ => None,
+        };
         let out = match self {
-            Op::Binary(_, a, b) => [Some(*a), Some(*b)],
-            Op::Unary(_, a) => [Some(*a), None],
-            Op::Var(..) | Op::Input(..) | Op::Const(..) => [None, None],
+            Op::Binary(_, a, b) | Op::Custom(_, a, b) => {
+                [Some(*a), Some(*b), None]
+            }
+            Op::Noise(_, x, y, z) | Op::Clamp(x, y, z) => {
+                [Some(*x), Some(*y), Some(*z)]
+            }
+            Op::Unary(_, a) => [Some(*a), None, None],
+            Op::Var(..) | Op::Input(..) | Op::Const(..) | Op::Nary(..) => {
+                [None, None, None]
+            }
         };
-        out.into_iter().flatten()
+        let args = args
+            .into_iter()
+            .flat_map(|args| (0..args.len()).map(move |i| args[i]));
+        out.into_iter().flatten().chain(args)
     }
 
     /// Returns a GraphViz string of edges from this node to its children
@@ -97,6 +168,6 @@ impl Op {
     /// Returns a single edge with user-specified transparency
     pub fn dot_edge(&self, a: Node, b: Node, alpha: &str) -> String {
         let color = dot_color_to_rgb(self.dot_node_color()).to_owned() + alpha;
-        format!("n{} -> n{} [color = \"{color}\"]\n", a.get(), b.get(),)
+        format!("n{} -> n{} [color = \"{color}\"]\n", a.index(), b.index())
     }
 }
@@ -0,0 +1,399 @@
+//! Expression trees which can be built with operator overloading
+use crate::{
+    context::{BinaryOpcode, Context, IntoNode, Node, NodeBudget, UnaryOpcode},
+    Error,
+};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+
+/// An expression tree, built with operator overloading
+///
+/// A `Tree` is a standalone value: it doesn't belong to a [`Context`], and
+/// building one can't fail, so expressions can be written without threading a
+/// context through every call or unwrapping every result:
+///
+/// ```
+/// use fidget::context::{Context, IntoNode, Tree};
+///
+/// let (x, y, z) = Tree::axes();
+/// let t = x + (y * 2.0).sqrt().min(z);
+///
+/// // Trees are converted into nodes in a particular `Context`, either with
+/// // `IntoNode::into_node` or by passing them to a builder function
+/// let mut ctx = Context::new();
+/// let root = t.into_node(&mut ctx)?;
+/// assert_eq!(ctx.eval_xyz(root, 1.0, 8.0, 5.0)?, 5.0);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Subtrees are shared by reference counting, and a subtree which is reused
+/// (by cloning the `Tree`) is only converted once.  Conversion uses the
+/// [`Context`] builder functions, so constants are folded and nodes are
+/// deduplicated as usual.  Converting a tree with more than
+/// [`Context::node_limit`] distinct subtrees returns
+/// [`Error::TooManyNodes`].
+#[derive(Clone, Debug)]
+pub struct Tree(Arc<TreeOp>);
+
+#[derive(Debug)]
+enum TreeOp {
+    X,
+    Y,
+    Z,
+    Var(String),
+    Const(f64),
+    Unary(UnaryOpcode, Tree),
+    Binary(BinaryOpcode, Tree, Tree),
+    Clamp(Tree, Tree, Tree),
+    Noise(u16, Tree, Tree, Tree),
+}
+
+impl From<TreeOp> for Tree {
+    fn from(op: TreeOp) -> Self {
+        Tree(Arc::new(op))
+    }
+}
+
+impl From<f64> for Tree {
+    fn from(v: f64) -> Self {
+        TreeOp::Const(v).into()
+    }
+}
+
+impl From<f32> for Tree {
+    fn from(v: f32) -> Self {
+        TreeOp::Const(v as f64).into()
+    }
+}
+
+impl Tree {
+    /// Returns a tree for the X axis
+    pub fn x() -> Self {
+        TreeOp::X.into()
+    }
+
+    /// Returns a tree for the Y axis
+    pub fn y() -> Self {
+        TreeOp::Y.into()
+    }
+
+    /// Returns a tree for the Z axis
+    pub fn z() -> Self {
+        TreeOp::Z.into()
+    }
+
+    /// Returns trees for the X, Y, and Z axes
+    pub fn axes() -> (Self, Self, Self) {
+        (Self::x(), Self::y(), Self::z())
+    }
+
+    /// Returns a tree for the variable with the given name
+    ///
+    /// The name is checked when the tree is converted into a node; the names
+    /// `X`, `Y`, and `Z` are reserved (see [`Context::var`]).
+    pub fn var(name: &str) -> Self {
+        TreeOp::Var(String::from(name)).into()
+    }
+
+    /// Returns a tree for the given constant
+    pub fn constant(v: f64) -> Self {
+        v.into()
+    }
+
+    fn unary(self, op: UnaryOpcode) -> Self {
+        TreeOp::Unary(op, self).into()
+    }
+
+    fn binary<T: Into<Tree>>(self, other: T, op: BinaryOpcode) -> Self {
+        TreeOp::Binary(op, self, other.into()).into()
+    }
+
+    /// Builds an absolute value operation
+    pub fn abs(self) -> Self {
+        self.unary(UnaryOpcode::Abs)
+    }
+
+    /// Builds a reciprocal operation
+    pub fn recip(self) -> Self {
+        self.unary(UnaryOpcode::Recip)
+    }
+
+    /// Builds a square root operation
+    pub fn sqrt(self) -> Self {
+        self.unary(UnaryOpcode::Sqrt)
+    }
+
+    /// Builds a squaring operation
+    pub fn square(self) -> Self {
+        self.unary(UnaryOpcode::Square)
+    }
+
+    /// Builds a sine operation
+    pub fn sin(self) -> Self {
+        self.unary(UnaryOpcode::Sin)
+    }
+
+    /// Builds a cosine operation
+    pub fn cos(self) -> Self {
+        self.unary(UnaryOpcode::Cos)
+    }
+
+    /// Builds a `min` operation
+    pub fn min<T: Into<Tree>>(self, other: T) -> Self {
+        self.binary(other, BinaryOpcode::Min)
+    }
+
+    /// Builds a `max` operation
+    pub fn max<T: Into<Tree>>(self, other: T) -> Self {
+        self.binary(other, BinaryOpcode::Max)
+    }
+
+    /// Builds an `atan2` operation, with `self` as the `y` argument
+    pub fn atan2<T: Into<Tree>>(self, x: T) -> Self {
+        self.binary(x, BinaryOpcode::Atan2)
+    }
+
+    /// Builds a `hypot` operation
+    pub fn hypot<T: Into<Tree>>(self, other: T) -> Self {
+        self.binary(other, BinaryOpcode::Hypot)
+    }
+
+    /// Builds a floored modulo operation (see [`Context::modulo`])
+    pub fn modulo<T: Into<Tree>>(self, other: T) -> Self {
+        self.binary(other, BinaryOpcode::Mod)
+    }
+
+    /// Builds a clamp operation (see [`Context::clamp`])
+    pub fn clamp<A: Into<Tree>, B: Into<Tree>>(self, lo: A, hi: B) -> Self {
+        TreeOp::Clamp(self, lo.into(), hi.into()).into()
+    }
+
+    /// Builds a gradient noise operation (see [`Context::noise3`])
+    pub fn noise3<A, B, C>(x: A, y: B, z: C, seed: u16) -> Self
+    where
+        A: Into<Tree>,
+        B: Into<Tree>,
+        C: Into<Tree>,
+    {
+        TreeOp::Noise(seed, x.into(), y.into(), z.into()).into()
+    }
+
+    /// Returns the children of this tree's root operation
+    fn children(&self) -> Vec<&Tree> {
+        match &*self.0 {
+            TreeOp::X
+            | TreeOp::Y
+            | TreeOp::Z
+            | TreeOp::Var(..)
+            | TreeOp::Const(..) => vec![],
+            TreeOp::Unary(_, a) => vec![a],
+            TreeOp::Binary(_, a, b) => vec![a, b],
+            TreeOp::Clamp(a, b, c) | TreeOp::Noise(_, a, b, c) => {
+                vec![a, b, c]
+            }
+        }
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        // Dropping a deep tree would recurse through each `Arc`, so we detach
+        // the children of uniquely-owned nodes and drop them from a worklist
+        let mut todo = vec![];
+        let mut next = Arc::get_mut(&mut self.0)
+            .map(|op| core::mem::replace(op, TreeOp::Const(0.0)));
+        while let Some(op) = next.take() {
+            match op {
+                TreeOp::X
+                | TreeOp::Y
+                | TreeOp::Z
+                | TreeOp::Var(..)
+                | TreeOp::Const(..) => (),
+                TreeOp::Unary(_, a) => todo.push(a),
+                TreeOp::Binary(_, a, b) => todo.extend([a, b]),
+                TreeOp::Clamp(a, b, c) | TreeOp::Noise(_, a, b, c) => {
+                    todo.extend([a, b, c])
+                }
+            }
+            while next.is_none() {
+                let Some(mut t) = todo.pop() else { break };
+                next = Arc::get_mut(&mut t.0)
+                    .map(|op| core::mem::replace(op, TreeOp::Const(0.0)));
+            }
+        }
+    }
+}
+
+impl IntoNode for &Tree {
+    fn into_node(self, ctx: &mut Context) -> Result<Node, Error> {
+        // Depth-first recursion on the heap, to protect against stack overflows
+        let mut todo = vec![(false, self)];
+        let mut done: BTreeMap<*const TreeOp, Node> = BTreeMap::new();
+        let mut budget = NodeBudget::new(ctx);
+        while let Some((ready, t)) = todo.pop() {
+            let ptr = Arc::as_ptr(&t.0);
+            if done.contains_key(&ptr) {
+                continue;
+            }
+            if !ready {
+                budget.visit()?;
+                todo.push((true, t));
+                todo.extend(t.children().into_iter().map(|c| (false, c)));
+                continue;
+            }
+            let get = |c: &Tree| done[&Arc::as_ptr(&c.0)];
+            let n = match &*t.0 {
+                TreeOp::X => ctx.x(),
+                TreeOp::Y => ctx.y(),
+                TreeOp::Z => ctx.z(),
+                TreeOp::Var(name) => ctx.var(name)?,
+                TreeOp::Const(v) => ctx.constant(*v),
+                TreeOp::Unary(op, a) => {
+                    let a = get(a);
+                    match op {
+                        UnaryOpcode::Neg => ctx.neg(a),
+                        UnaryOpcode::Abs => ctx.abs(a),
+                        UnaryOpcode::Recip => ctx.recip(a),
+                        UnaryOpcode::Sqrt => ctx.sqrt(a),
+                        UnaryOpcode::Square => ctx.square(a),
+                        UnaryOpcode::Sin => ctx.sin(a),
+                        UnaryOpcode::Cos => ctx.cos(a),
+                    }?
+                }
+                TreeOp::Binary(op, a, b) => {
+                    let (a, b) = (get(a), get(b));
+                    match op {
+                        BinaryOpcode::Add => ctx.add(a, b),
+                        BinaryOpcode::Sub => ctx.sub(a, b),
+                        BinaryOpcode::Mul => ctx.mul(a, b),
+                        BinaryOpcode::Div => ctx.div(a, b),
+                        BinaryOpcode::Min => ctx.min(a, b),
+                        BinaryOpcode::Max => ctx.max(a, b),
+                        BinaryOpcode::Atan2 => ctx.atan2(a, b),
+                        BinaryOpcode::Hypot => ctx.hypot(a, b),
+                        BinaryOpcode::Mod => ctx.modulo(a, b),
+                        BinaryOpcode::MinNc => ctx.min_nc(a, b),
+                        BinaryOpcode::MaxNc => ctx.max_nc(a, b),
+                    }?
+                }
+                TreeOp::Clamp(x, lo, hi) => {
+                    ctx.clamp(get(x), get(lo), get(hi))?
+                }
+                TreeOp::Noise(seed, x, y, z) => {
+                    ctx.noise3(get(x), get(y), get(z), *seed)?
+                }
+            };
+            done.insert(ptr, n);
+        }
+        Ok(done[&Arc::as_ptr(&self.0)])
+    }
+}
+
+impl IntoNode for Tree {
+    fn into_node(self, ctx: &mut Context) -> Result<Node, Error> {
+        (&self).into_node(ctx)
+    }
+}
+
+impl core::ops::Neg for Tree {
+    type Output = Tree;
+    fn neg(self) -> Tree {
+        self.unary(UnaryOpcode::Neg)
+    }
+}
+
+macro_rules! impl_binary {
+    ($op:ident, $assign:ident, $base_fn:ident, $assign_fn:ident) => {
+        impl<T: Into<Tree>> core::ops::$op<T> for Tree {
+            type Output = Tree;
+            fn $base_fn(self, other: T) -> Tree {
+                self.binary(other, BinaryOpcode::$op)
+            }
+        }
+        impl core::ops::$op<Tree> for f64 {
+            type Output = Tree;
+            fn $base_fn(self, other: Tree) -> Tree {
+                Tree::from(self).binary(other, BinaryOpcode::$op)
+            }
+        }
+        impl<T: Into<Tree>> core::ops::$assign<T> for Tree {
+            fn $assign_fn(&mut self, other: T) {
+                *self = self.clone().binary(other, BinaryOpcode::$op);
+            }
+        }
+    };
+}
+
+impl_binary!(Add, AddAssign, add, add_assign);
+impl_binary!(Sub, SubAssign, sub, sub_assign);
+impl_binary!(Mul, MulAssign, mul, mul_assign);
+impl_binary!(Div, DivAssign, div, div_assign);
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tree_ops() {
+        let (x, y, z) = Tree::axes();
+        let t = (2.0 * x.clone() - y / 4.0).max(-z) + Tree::var("a");
+        let mut ctx = Context::new();
+        let n = t.into_node(&mut ctx).unwrap();
+
+        let vars = [("X", 3.0), ("Y", 8.0), ("Z", 1.0), ("a", 0.5)]
+            .into_iter()
+            .map(|(k, v)| (String::from(k), v))
+            .collect();
+        assert_eq!(ctx.eval(n, &vars).unwrap(), 4.5);
+
+        let mut s = x.clone();
+        s *= 3.0;
+        s -= x.square();
+        let n = ctx.add(s, 1.0).unwrap();
+        assert_eq!(ctx.eval_xyz(n, 2.0, 0.0, 0.0).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_tree_shared() {
+        let mut t = Tree::x().sin();
+        for _ in 0..4 {
+            t = t.clone() + t;
+        }
+        let mut ctx = Context::new();
+        let n = (&t).into_node(&mut ctx).unwrap();
+        assert_eq!(ctx.eval_xyz(n, 1.0, 0.0, 0.0).unwrap(), 16.0 * 1f64.sin());
+
+        // The tree has 2^4 copies of sin(X), but should produce the same
+        // nodes as building the expression by hand
+        let mut expected = Context::new();
+        let x = expected.x();
+        let mut e = expected.sin(x).unwrap();
+        for _ in 0..4 {
+            e = expected.add(e, e).unwrap();
+        }
+        assert_eq!(ctx.len(), expected.len());
+    }
+
+    #[test]
+    fn test_tree_deep() {
+        let mut t = Tree::x();
+        for _ in 0..200_000 {
+            t += 1.0;
+        }
+        let mut ctx = Context::new();
+        let n = (&t).into_node(&mut ctx).unwrap();
+        assert_eq!(ctx.eval_xyz(n, 1.0, 0.0, 0.0).unwrap(), 200_001.0);
+        assert!(ctx.get_tape::<crate::vm::Eval>(n).is_ok());
+        drop(t);
+    }
+
+    #[test]
+    fn test_tree_bad_var() {
+        let mut ctx = Context::new();
+        assert!(matches!(
+            Tree::var("X").into_node(&mut ctx),
+            Err(Error::ReservedName)
+        ));
+    }
+}
@@ -18,6 +18,14 @@ use crate::{
     eval::{EvaluatorStorage, Family, Tape},
     Error,
 };
+use alloc::{vec, vec::Vec};
+
+/// Number of points evaluated in a single call by
+/// [`BulkEval::eval_into`](BulkEval::eval_into)
+///
+/// This bounds the size of evaluator scratch data, and is a multiple of every
+/// supported SIMD width (so only the final chunk has a partial vector).
+pub const CHUNK_SIZE: usize = 1024;
 
 /// Trait for bulk evaluation returning the given type `T`
 ///
@@ -86,13 +94,13 @@ pub struct BulkEval<T, E, F> {
     eval: E,
     tape: Tape<F>,
 
-    _p: std::marker::PhantomData<fn(T) -> T>,
+    _p: core::marker::PhantomData<fn(T) -> T>,
 }
 
 impl<T, E, F: Family> BulkEval<T, E, F>
 where
     E: BulkEvaluator<T, F> + EvaluatorStorage<F>,
-    T: Clone + From<f32>,
+    T: Clone + From<f32> + core::ops::Neg<Output = T>,
 {
     /// Builds a new evaluator for the given tape, allocating new storage
     pub fn new(tape: &Tape<F>) -> Self {
@@ -110,7 +118,22 @@ where
         Self {
             eval,
             tape: tape.clone(),
-            _p: std::marker::PhantomData,
+            _p: core::marker::PhantomData,
+        }
+    }
+
+    /// Builds an evaluator for a tape which differs from this evaluator's tape
+    /// only in the values of its constants
+    ///
+    /// This is much cheaper than building a new evaluator if the tape uses a
+    /// [constant table](Tape::with_constant_table) and the evaluator is
+    /// JIT-compiled, because the compiled code is reused.  Otherwise, it's
+    /// equivalent to [`Self::new`].
+    pub fn rebind(&self, tape: &Tape<F>) -> Self {
+        Self {
+            eval: self.eval.rebind(tape),
+            tape: tape.clone(),
+            _p: core::marker::PhantomData,
         }
     }
 
@@ -138,9 +161,148 @@ where
         data.prepare(&self.tape, x.len());
         self.eval
             .eval_with(x, y, z, vars, &mut data.out, &mut data.data);
+        self.tape.sign_slice(&mut data.out);
         Ok(&data.out)
     }
 
+    /// Evaluates the given slices, writing results into a caller-provided
+    /// buffer
+    ///
+    /// Unlike [`eval_with`](Self::eval_with), the output is not stored in
+    /// `data`, so this can write directly into image buffers (or similar)
+    /// without an extra copy.  Slices may be of arbitrary length; they are
+    /// evaluated in chunks of [`CHUNK_SIZE`] points, so the scratch memory in
+    /// `data` does not grow with the number of points.
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let sum = ctx.add(x, y).unwrap();
+    /// let tape = ctx.get_tape::<fidget::vm::Eval>(sum).unwrap();
+    /// let eval = tape.new_float_slice_evaluator();
+    ///
+    /// let xs = vec![1.0; 5000];
+    /// let ys: Vec<f32> = (0..5000).map(|i| i as f32).collect();
+    /// let zs = vec![0.0; 5000];
+    /// let mut out = vec![0.0; 5000];
+    /// eval.eval_into(&xs, &ys, &zs, &[], &mut out, &mut Default::default())
+    ///     .unwrap();
+    /// assert_eq!(out[4999], 5000.0);
+    /// ```
+    pub fn eval_into(
+        &self,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+        out: &mut [T],
+        data: &mut BulkEvalData<E::Data, T, F>,
+    ) -> Result<(), Error> {
+        if x.len() != y.len() || x.len() != z.len() || x.len() != out.len() {
+            return Err(Error::MismatchedSlices);
+        } else if vars.len() != self.tape.var_count() {
+            return Err(Error::BadVarSlice(vars.len(), self.tape.var_count()));
+        }
+        data.data.prepare(&self.tape, x.len().min(CHUNK_SIZE));
+        for (((x, y), z), out) in x
+            .chunks(CHUNK_SIZE)
+            .zip(y.chunks(CHUNK_SIZE))
+            .zip(z.chunks(CHUNK_SIZE))
+            .zip(out.chunks_mut(CHUNK_SIZE))
+        {
+            self.eval.eval_with(x, y, z, vars, out, &mut data.data);
+            self.tape.sign_slice(out);
+        }
+        Ok(())
+    }
+
+    /// Evaluates points from an interleaved buffer (`xyzxyz...`), writing
+    /// results into a caller-provided buffer
+    ///
+    /// This is equivalent to [`eval_strided_into`](Self::eval_strided_into)
+    /// with a stride of 3 and offsets of `[0, 1, 2]`.
+    pub fn eval_interleaved_into(
+        &self,
+        xyz: &[f32],
+        vars: &[f32],
+        out: &mut [T],
+        data: &mut BulkEvalData<E::Data, T, F>,
+    ) -> Result<(), Error> {
+        self.eval_strided_into(xyz, 3, [0, 1, 2], vars, out, data)
+    }
+
+    /// Evaluates points from a strided buffer, writing results into a
+    /// caller-provided buffer
+    ///
+    /// The X, Y, Z coordinates of point `i` are at `points[i * stride +
+    /// offsets[0..3]]`, so this can read directly from (for example) a vertex
+    /// array of interleaved positions and normals.  `points` must contain
+    /// exactly `out.len() * stride` values.
+    ///
+    /// Coordinates are gathered into scratch buffers in `data` one chunk of
+    /// [`CHUNK_SIZE`] points at a time, rather than transposing the entire
+    /// input up front.
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let z = ctx.z();
+    /// let sum = ctx.add(x, z).unwrap();
+    /// let tape = ctx.get_tape::<fidget::vm::Eval>(sum).unwrap();
+    /// let eval = tape.new_float_slice_evaluator();
+    ///
+    /// // Two vertices, each with a position and a normal
+    /// let verts = [
+    ///     1.0, 2.0, 3.0, 0.0, 0.0, 1.0, //
+    ///     4.0, 5.0, 6.0, 0.0, 1.0, 0.0, //
+    /// ];
+    /// let mut out = [0.0; 2];
+    /// eval.eval_strided_into(
+    ///     &verts,
+    ///     6,
+    ///     [0, 1, 2],
+    ///     &[],
+    ///     &mut out,
+    ///     &mut Default::default(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(out, [4.0, 10.0]);
+    /// ```
+    pub fn eval_strided_into(
+        &self,
+        points: &[f32],
+        stride: usize,
+        offsets: [usize; 3],
+        vars: &[f32],
+        out: &mut [T],
+        data: &mut BulkEvalData<E::Data, T, F>,
+    ) -> Result<(), Error> {
+        if offsets.iter().any(|o| *o >= stride) {
+            return Err(Error::BadStride(stride));
+        } else if points.len() != out.len() * stride {
+            return Err(Error::MismatchedSlices);
+        } else if vars.len() != self.tape.var_count() {
+            return Err(Error::BadVarSlice(vars.len(), self.tape.var_count()));
+        }
+        data.data.prepare(&self.tape, out.len().min(CHUNK_SIZE));
+        let [xs, ys, zs] = &mut data.input;
+        for (points, out) in points
+            .chunks(CHUNK_SIZE * stride)
+            .zip(out.chunks_mut(CHUNK_SIZE))
+        {
+            for (buf, offset) in
+                [&mut *xs, &mut *ys, &mut *zs].into_iter().zip(offsets)
+            {
+                buf.clear();
+                buf.extend(points.iter().skip(offset).step_by(stride));
+            }
+            self.eval.eval_with(xs, ys, zs, vars, out, &mut data.data);
+            self.tape.sign_slice(out);
+        }
+        Ok(())
+    }
+
     /// Evaluates the given slices, returning a fresh `Vec<T>`
     ///
     /// This function performs allocation; in a hot loop, consider using
@@ -171,7 +333,10 @@ pub struct BulkEvalData<D, T, F> {
     /// Inner data
     data: D,
 
-    _p: std::marker::PhantomData<*const F>,
+    /// Coordinates gathered from strided input
+    input: [Vec<f32>; 3],
+
+    _p: core::marker::PhantomData<*const F>,
 }
 
 impl<D: Default, T, F> Default for BulkEvalData<D, T, F> {
@@ -179,7 +344,8 @@ impl<D: Default, T, F> Default for BulkEvalData<D, T, F> {
         Self {
             out: vec![],
             data: D::default(),
-            _p: std::marker::PhantomData,
+            input: Default::default(),
+            _p: core::marker::PhantomData,
         }
     }
 }
@@ -193,8 +359,8 @@ where
     T: Clone + From<f32>,
 {
     fn prepare(&mut self, tape: &Tape<F>, size: usize) {
-        self.out.resize(size, std::f32::NAN.into());
-        self.out.fill(std::f32::NAN.into());
+        self.out.resize(size, core::f32::NAN.into());
+        self.out.fill(core::f32::NAN.into());
         self.data.prepare(tape, size);
     }
 }
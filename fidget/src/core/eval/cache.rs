@@ -0,0 +1,263 @@
+//! Caching of simplified tapes
+//!
+//! When rendering or meshing, many regions produce identical choice arrays
+//! (e.g. every tile which is entirely on one side of a `min` node), and
+//! therefore identical simplified tapes.  A [`TapeCache`] stores recently
+//! simplified tapes, keyed by their parent tape and a compact bitmap of
+//! choices, so that repeated simplifications are free.
+//!
+//! ```
+//! use fidget::{context::Context, eval::cache::TapeCache, vm};
+//!
+//! let mut ctx = Context::new();
+//! let (x, y) = (ctx.x(), ctx.y());
+//! let min = ctx.min(x, y)?;
+//! let tape = ctx.get_tape::<vm::Eval>(min)?;
+//!
+//! let eval = tape.new_interval_evaluator();
+//! let mut cache = TapeCache::new(16);
+//! for i in 0..4 {
+//!     let x = [0.0, 1.0];
+//!     let y = [2.0 + i as f32, 3.0 + i as f32];
+//!     let (_, trace) = eval.eval(x, y, [0.0; 2], &[])?;
+//!     let simple = trace.unwrap().simplify_cached(&mut cache)?;
+//!     assert_eq!(simple.len(), 1); // the tape only reads `x`
+//! }
+//! assert_eq!(cache.stats().misses, 1);
+//! assert_eq!(cache.stats().hits, 3);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{tape::Workspace, Choice, Family, Tape},
+    Error,
+};
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+
+/// Number of choices packed into each word of a bitmap
+const CHOICES_PER_WORD: usize = u64::BITS as usize / 2;
+
+/// Key for a cached tape: the parent tape's identity and a choice bitmap
+type Key = (usize, Vec<u64>);
+
+/// A single cached tape
+struct Entry<F> {
+    /// Parent tape, which is kept alive so that its identity isn't reused
+    _parent: Tape<F>,
+    /// Simplified tape
+    tape: Tape<F>,
+    /// Value of the cache's clock when this entry was last used
+    last_used: u64,
+}
+
+/// Statistics for a [`TapeCache`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    /// Number of simplifications which were found in the cache
+    pub hits: usize,
+    /// Number of simplifications which were computed
+    pub misses: usize,
+    /// Number of tapes which were evicted to make room for new tapes
+    pub evictions: usize,
+}
+
+impl CacheStats {
+    /// Returns the fraction of lookups which were found in the cache
+    ///
+    /// Returns 0 if the cache has never been used.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// Least-recently-used cache of simplified tapes
+///
+/// Cached tapes are shared with the caller (rather than copied), so they must
+/// not be passed to functions which expect to reclaim a tape's storage with
+/// [`Tape::take`].
+pub struct TapeCache<F> {
+    capacity: usize,
+    entries: HashMap<Key, Entry<F>>,
+    clock: u64,
+    stats: CacheStats,
+    workspace: Workspace,
+}
+
+impl<F: Family> TapeCache<F> {
+    /// Builds a new cache which stores up to `capacity` tapes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+            workspace: Workspace::default(),
+        }
+    }
+
+    /// Simplifies the given tape, returning a cached tape if possible
+    ///
+    /// The choice slice must be the same size as
+    /// [`tape.choice_count()`](crate::eval::tape::Data::choice_count).
+    pub fn simplify(
+        &mut self,
+        tape: &Tape<F>,
+        choices: &[Choice],
+    ) -> Result<Tape<F>, Error> {
+        self.clock += 1;
+        let key = (tape.id(), bitmap(choices));
+        if let Some(e) = self.entries.get_mut(&key) {
+            e.last_used = self.clock;
+            self.stats.hits += 1;
+            return Ok(e.tape.clone());
+        }
+
+        self.stats.misses += 1;
+        let out = tape.simplify_with(
+            choices,
+            &mut self.workspace,
+            Default::default(),
+        )?;
+        if self.capacity == 0 {
+            return Ok(out);
+        }
+        if self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+                .unwrap();
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                _parent: tape.clone(),
+                tape: out.clone(),
+                last_used: self.clock,
+            },
+        );
+        Ok(out)
+    }
+
+    /// Returns hit / miss statistics for this cache
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets hit / miss statistics, without clearing the cache
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Returns the number of tapes in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every tape from the cache
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Packs an array of choices into a bitmap, with two bits per choice
+fn bitmap(choices: &[Choice]) -> Vec<u64> {
+    choices
+        .chunks(CHOICES_PER_WORD)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, c)| acc | ((*c as u64) << (i * 2)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    #[test]
+    fn test_bitmap() {
+        use Choice::*;
+        assert_eq!(bitmap(&[]), Vec::<u64>::new());
+        assert_eq!(bitmap(&[Left, Right, Both]), vec![0b11_10_01]);
+        let choices = vec![Right; CHOICES_PER_WORD + 1];
+        assert_eq!(bitmap(&choices), vec![0xAAAA_AAAA_AAAA_AAAA, 0b10]);
+    }
+
+    type Region = ([f32; 2], [f32; 2], [f32; 2]);
+
+    fn test_cache<F: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let a = ctx.min(x, y).unwrap();
+        let b = ctx.max(a, z).unwrap();
+        let tape = ctx.get_tape::<F>(b).unwrap();
+        let eval = tape.new_interval_evaluator();
+
+        let mut cache = TapeCache::new(2);
+        let trace = |cache: &mut TapeCache<F>, (x, y, z): Region| {
+            let (_, r) = eval.eval(x, y, z, &[]).unwrap();
+            let r = r.unwrap();
+            let cached = r.simplify_cached(cache).unwrap();
+            let expected = r.simplify().unwrap();
+            assert_eq!(cached.len(), expected.len());
+            let v = cached.new_point_evaluator().eval(0.5, 0.5, 0.5, &[]);
+            let e = expected.new_point_evaluator().eval(0.5, 0.5, 0.5, &[]);
+            assert_eq!(v.unwrap().0, e.unwrap().0);
+        };
+
+        let left = ([0.0, 1.0], [2.0, 3.0], [-2.0, -1.0]);
+        let right = ([2.0, 3.0], [0.0, 1.0], [-2.0, -1.0]);
+        let z = ([2.0, 3.0], [0.0, 1.0], [4.0, 5.0]);
+        for r in [left, right, left, right] {
+            trace(&mut cache, r);
+        }
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 2,
+                evictions: 0
+            }
+        );
+        assert_eq!(cache.stats().hit_rate(), 0.5);
+
+        // Adding a third tape evicts the least-recently-used (`left`)
+        trace(&mut cache, z);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats().evictions, 1);
+        trace(&mut cache, right);
+        assert_eq!(cache.stats().hits, 3);
+        trace(&mut cache, left);
+        assert_eq!(cache.stats().misses, 4);
+    }
+
+    #[test]
+    fn test_cache_vm() {
+        test_cache::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_cache_jit() {
+        test_cache::<crate::jit::Eval>();
+    }
+}
@@ -0,0 +1,137 @@
+//! User-defined operations
+//!
+//! A [`CustomOp`] is added to a math graph with
+//! [`Context::custom`](crate::context::Context::custom), then evaluated by
+//! calling back into Rust.
+use crate::eval::types::{Grad, Hessian, Interval};
+
+/// A user-defined two-argument operation
+///
+/// Custom operations let domain-specific primitives (e.g. noise or splines)
+/// be embedded in a math graph without changing Fidget itself.  Every
+/// evaluator calls back into these functions, including the JIT (which saves
+/// its registers around the call), so they're much slower than built-in
+/// operations.
+///
+/// Operations which only need one argument may ignore `rhs`.
+///
+/// The methods must be consistent: `eval_interval` must contain every value
+/// of `eval_f32` for arguments within its input intervals, and `eval_grad`
+/// must return the value of `eval_f32` along with its partial derivatives.
+///
+/// A panic in any method propagates to the caller of the evaluator.  When
+/// called from JIT code, the panic is caught at the call boundary (because
+/// unwinding through JIT code would abort the process): the rest of the
+/// evaluation skips any further custom operations and its results are
+/// discarded, then the panic resumes once control returns to Rust.
+///
+/// ```
+/// use fidget::{
+///     context::Context,
+///     eval::{types::{Grad, Interval}, CustomOp},
+///     vm,
+/// };
+/// use std::sync::Arc;
+///
+/// #[derive(Debug)]
+/// struct Sin;
+///
+/// impl CustomOp for Sin {
+///     fn name(&self) -> &str {
+///         "sin"
+///     }
+///     fn eval_f32(&self, lhs: f32, _rhs: f32) -> f32 {
+///         lhs.sin()
+///     }
+///     fn eval_interval(&self, lhs: Interval, _rhs: Interval) -> Interval {
+///         if lhs.has_nan() {
+///             f32::NAN.into()
+///         } else {
+///             Interval::new(-1.0, 1.0)
+///         }
+///     }
+///     fn eval_grad(&self, lhs: Grad, _rhs: Grad) -> Grad {
+///         let (s, c) = lhs.v.sin_cos();
+///         Grad::new(s, lhs.dx * c, lhs.dy * c, lhs.dz * c)
+///     }
+/// }
+///
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let sin = ctx.custom(Arc::new(Sin), x, 0.0)?;
+/// let tape = ctx.get_tape::<vm::Eval>(sin)?;
+/// let eval = tape.new_point_evaluator();
+/// assert_eq!(eval.eval(1.0, 0.0, 0.0, &[])?.0, 1f32.sin());
+/// # Ok::<(), fidget::Error>(())
+/// ```
+pub trait CustomOp: Send + Sync + core::fmt::Debug {
+    /// Returns the operation's name, used when printing tapes and graphs
+    fn name(&self) -> &str;
+
+    /// Evaluates the operation at a single point
+    fn eval_f32(&self, lhs: f32, rhs: f32) -> f32;
+
+    /// Evaluates the operation over intervals
+    fn eval_interval(&self, lhs: Interval, rhs: Interval) -> Interval;
+
+    /// Evaluates the operation and its partial derivatives
+    fn eval_grad(&self, lhs: Grad, rhs: Grad) -> Grad;
+
+    /// Evaluates the operation and its first and second partial derivatives
+    ///
+    /// The default implementation uses [`eval_grad`](Self::eval_grad) for
+    /// the value and first derivatives, and returns `NaN` for every second
+    /// derivative.
+    fn eval_hessian(&self, lhs: Hessian, rhs: Hessian) -> Hessian {
+        let g = self.eval_grad(lhs.to_grad(), rhs.to_grad());
+        Hessian::new(g.v, [g.dx, g.dy, g.dz], [[f32::NAN; 3]; 3])
+    }
+}
+
+/// Custom operations used in evaluator test suites
+#[cfg(any(test, feature = "eval-tests"))]
+pub mod eval_tests {
+    use super::*;
+
+    /// Computes `lhs² - rhs`
+    #[derive(Debug)]
+    pub struct SquareSub;
+
+    impl CustomOp for SquareSub {
+        fn name(&self) -> &str {
+            "square-sub"
+        }
+        fn eval_f32(&self, lhs: f32, rhs: f32) -> f32 {
+            lhs * lhs - rhs
+        }
+        fn eval_interval(&self, lhs: Interval, rhs: Interval) -> Interval {
+            lhs.square() - rhs
+        }
+        fn eval_grad(&self, lhs: Grad, rhs: Grad) -> Grad {
+            lhs * lhs - rhs
+        }
+        fn eval_hessian(&self, lhs: Hessian, rhs: Hessian) -> Hessian {
+            lhs * lhs - rhs
+        }
+    }
+
+    /// Returns `lhs`, panicking if a single point is negative
+    #[derive(Debug)]
+    pub struct PanicNegative;
+
+    impl CustomOp for PanicNegative {
+        fn name(&self) -> &str {
+            "panic-negative"
+        }
+        fn eval_f32(&self, lhs: f32, _rhs: f32) -> f32 {
+            assert!(lhs >= 0.0, "negative input");
+            lhs
+        }
+        fn eval_interval(&self, lhs: Interval, _rhs: Interval) -> Interval {
+            lhs
+        }
+        fn eval_grad(&self, lhs: Grad, _rhs: Grad) -> Grad {
+            lhs
+        }
+    }
+}
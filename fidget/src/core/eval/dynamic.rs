@@ -0,0 +1,312 @@
+//! Evaluators with a backend chosen at runtime
+//!
+//! Most of Fidget is generic over an evaluator [`Family`], which means that an
+//! application which wants to pick between the interpreter and the JIT (e.g.
+//! from a config file) must monomorphize everything for both.  This module
+//! provides type-erased wrappers ([`DynIntervalEval`] and
+//! [`DynFloatSliceEval`]) and a [`Backend`] selector to avoid that.
+//!
+//! ```rust
+//! use fidget::context::Context;
+//! use fidget::eval::{dynamic::Backend, types::Interval};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let sum = ctx.add(x, y)?;
+//!
+//! let backend: Backend = "vm".parse()?;
+//! let eval = backend.interval_evaluator(&ctx, sum)?;
+//! let out = eval.eval(
+//!     Interval::new(0.0, 1.0),
+//!     Interval::new(2.0, 3.0),
+//!     Interval::new(0.0, 0.0),
+//!     &[],
+//! )?;
+//! assert_eq!(out, Interval::new(2.0, 4.0));
+//!
+//! // Pick the fastest backend that's available on this machine
+//! let eval = Backend::auto().float_slice_evaluator(&ctx, sum)?;
+//! let out = eval.eval(&[1.0, 2.0], &[3.0, 4.0], &[0.0, 0.0], &[])?;
+//! assert_eq!(out, [4.0, 6.0]);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{Context, Node},
+    eval::{
+        float_slice::FloatSliceEval, interval::IntervalEval, types::Interval,
+        Family, Tape,
+    },
+    Error,
+};
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+
+/// Evaluator backend, selectable at runtime
+///
+/// Backends can be parsed from (and printed as) the strings `"vm"` and `"jit"`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Backend {
+    /// Interpreter ([`vm::Eval`](crate::vm::Eval))
+    Vm,
+    /// JIT compiler ([`jit::Eval`](crate::jit::Eval))
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+impl Backend {
+    /// Picks the fastest backend which is usable on this machine
+    ///
+    /// This is the JIT if it's compiled in and the CPU supports the
+    /// instructions that it emits; otherwise, it's the interpreter.
+    pub fn auto() -> Self {
+        #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+        if std::is_x86_feature_detected!("avx2")
+            && std::is_x86_feature_detected!("fma")
+        {
+            return Backend::Jit;
+        }
+        #[cfg(all(feature = "jit", target_arch = "aarch64"))]
+        return Backend::Jit;
+
+        #[allow(unreachable_code)]
+        Backend::Vm
+    }
+
+    /// Returns the name of this backend, as accepted by [`str::parse`]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Vm => "vm",
+            #[cfg(feature = "jit")]
+            Backend::Jit => "jit",
+        }
+    }
+
+    /// Builds an interval evaluator for the given node
+    pub fn interval_evaluator(
+        &self,
+        ctx: &Context,
+        root: Node,
+    ) -> Result<DynIntervalEval, Error> {
+        Ok(match self {
+            Backend::Vm => {
+                DynIntervalEval::new(&ctx.get_tape::<crate::vm::Eval>(root)?)
+            }
+            #[cfg(feature = "jit")]
+            Backend::Jit => {
+                DynIntervalEval::new(&ctx.get_tape::<crate::jit::Eval>(root)?)
+            }
+        })
+    }
+
+    /// Builds a float slice evaluator for the given node
+    pub fn float_slice_evaluator(
+        &self,
+        ctx: &Context,
+        root: Node,
+    ) -> Result<DynFloatSliceEval, Error> {
+        Ok(match self {
+            Backend::Vm => {
+                DynFloatSliceEval::new(&ctx.get_tape::<crate::vm::Eval>(root)?)
+            }
+            #[cfg(feature = "jit")]
+            Backend::Jit => {
+                DynFloatSliceEval::new(&ctx.get_tape::<crate::jit::Eval>(root)?)
+            }
+        })
+    }
+}
+
+impl core::fmt::Display for Backend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl core::str::FromStr for Backend {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vm" => Ok(Backend::Vm),
+            #[cfg(feature = "jit")]
+            "jit" => Ok(Backend::Jit),
+            s => Err(Error::UnknownBackend(s.to_string())),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Object-safe subset of [`IntervalEval`]
+trait IntervalEvalObj: Send + Sync {
+    fn eval(
+        &self,
+        x: Interval,
+        y: Interval,
+        z: Interval,
+        vars: &[f32],
+        simplify: bool,
+    ) -> Result<(Interval, Option<DynIntervalEval>), Error>;
+    fn tape_len(&self) -> usize;
+}
+
+impl<F: Family + 'static> IntervalEvalObj for IntervalEval<F>
+where
+    IntervalEval<F>: Send + Sync,
+{
+    fn eval(
+        &self,
+        x: Interval,
+        y: Interval,
+        z: Interval,
+        vars: &[f32],
+        simplify: bool,
+    ) -> Result<(Interval, Option<DynIntervalEval>), Error> {
+        let (out, trace) = IntervalEval::eval(self, x, y, z, vars)?;
+        let next = match trace {
+            Some(t) if simplify => Some(DynIntervalEval::new(&t.simplify()?)),
+            _ => None,
+        };
+        Ok((out, next))
+    }
+    fn tape_len(&self) -> usize {
+        self.tape().len()
+    }
+}
+
+/// Interval evaluator with a type-erased evaluator family
+///
+/// This is built with [`Backend::interval_evaluator`] or
+/// [`DynIntervalEval::new`].
+pub struct DynIntervalEval(Box<dyn IntervalEvalObj>);
+
+impl DynIntervalEval {
+    /// Builds a new evaluator for the given tape
+    pub fn new<F: Family + 'static>(tape: &Tape<F>) -> Self
+    where
+        IntervalEval<F>: Send + Sync,
+    {
+        Self(Box::new(tape.new_interval_evaluator()))
+    }
+
+    /// Evaluates the given intervals
+    pub fn eval(
+        &self,
+        x: Interval,
+        y: Interval,
+        z: Interval,
+        vars: &[f32],
+    ) -> Result<Interval, Error> {
+        self.0.eval(x, y, z, vars, false).map(|(out, _)| out)
+    }
+
+    /// Evaluates the given intervals, returning an evaluator for the
+    /// simplified tape if simplification is possible
+    pub fn eval_and_simplify(
+        &self,
+        x: Interval,
+        y: Interval,
+        z: Interval,
+        vars: &[f32],
+    ) -> Result<(Interval, Option<DynIntervalEval>), Error> {
+        self.0.eval(x, y, z, vars, true)
+    }
+
+    /// Returns the length of the evaluator's tape
+    pub fn tape_len(&self) -> usize {
+        self.0.tape_len()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Object-safe subset of [`FloatSliceEval`]
+trait FloatSliceEvalObj: Send + Sync {
+    fn eval(
+        &self,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+    ) -> Result<Vec<f32>, Error>;
+}
+
+impl<F: Family + 'static> FloatSliceEvalObj for FloatSliceEval<F>
+where
+    FloatSliceEval<F>: Send + Sync,
+{
+    fn eval(
+        &self,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+    ) -> Result<Vec<f32>, Error> {
+        FloatSliceEval::eval(self, x, y, z, vars)
+    }
+}
+
+/// Float slice evaluator with a type-erased evaluator family
+///
+/// This is built with [`Backend::float_slice_evaluator`] or
+/// [`DynFloatSliceEval::new`].
+pub struct DynFloatSliceEval(Box<dyn FloatSliceEvalObj>);
+
+impl DynFloatSliceEval {
+    /// Builds a new evaluator for the given tape
+    pub fn new<F: Family + 'static>(tape: &Tape<F>) -> Self
+    where
+        FloatSliceEval<F>: Send + Sync,
+    {
+        Self(Box::new(tape.new_float_slice_evaluator()))
+    }
+
+    /// Evaluates the given slices, returning a fresh `Vec<f32>`
+    pub fn eval(
+        &self,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+    ) -> Result<Vec<f32>, Error> {
+        self.0.eval(x, y, z, vars)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backend_parse() {
+        assert_eq!("vm".parse::<Backend>().unwrap(), Backend::Vm);
+        assert_eq!(Backend::Vm.to_string(), "vm");
+        #[cfg(feature = "jit")]
+        assert_eq!("jit".parse::<Backend>().unwrap(), Backend::Jit);
+        assert!(matches!(
+            "gpu".parse::<Backend>(),
+            Err(Error::UnknownBackend(..))
+        ));
+    }
+
+    #[test]
+    fn test_dyn_simplify() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let m = ctx.min(x, y).unwrap();
+        let eval = Backend::Vm.interval_evaluator(&ctx, m).unwrap();
+        let (out, next) = eval
+            .eval_and_simplify(
+                Interval::new(0.0, 1.0),
+                Interval::new(2.0, 3.0),
+                Interval::new(0.0, 0.0),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(out, Interval::new(0.0, 1.0));
+        let next = next.unwrap();
+        assert!(next.tape_len() < eval.tape_len());
+    }
+}
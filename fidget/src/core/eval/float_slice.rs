@@ -25,7 +25,11 @@ pub type FloatSliceEvalStorage<F> =
 #[cfg(any(test, feature = "eval-tests"))]
 pub mod eval_tests {
     use super::*;
-    use crate::{context::Context, eval::Vars};
+    use crate::{
+        context::Context,
+        eval::{NanPolicy, SignConvention, Tape, Vars},
+        Error,
+    };
 
     pub fn test_give_take<I: Family>() {
         let mut ctx = Context::new();
@@ -132,6 +136,241 @@ pub mod eval_tests {
         assert_eq!(out, [2.0, 8.0, 8.0, -2.0, -4.0, -6.0, 0.0]);
     }
 
+    pub fn test_f_nan_policy<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(x, y).unwrap();
+        let max_imm = ctx.max(x, 1.0).unwrap();
+        let nan = f32::NAN;
+        let inf = f32::INFINITY;
+
+        // Use enough points to fill a full SIMD register
+        let xs = [nan, 1.0, nan, 2.0, 0.0, nan, 1.0, 3.0, nan];
+        let ys = [1.0, nan, nan, 3.0, nan, 0.0, 1.0, 2.0, 0.0];
+        let zs = [0.0; 9];
+
+        // Compares results, treating all NaN values as equal
+        let check = |tape: &Tape<I>, policy, expected: [f32; 9]| {
+            let tape = tape.clone().with_nan_policy(policy);
+            let eval = tape.new_float_slice_evaluator();
+            let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+            for (i, (a, b)) in out.iter().zip(expected).enumerate() {
+                assert!(
+                    a == &b || (a.is_nan() && b.is_nan()),
+                    "mismatch at {i} with {policy:?}: {a} != {b}"
+                );
+            }
+        };
+
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        let n = nan;
+        check(
+            &tape,
+            NanPolicy::Propagate,
+            [n, n, n, 2.0, n, n, 1.0, 2.0, n],
+        );
+        check(
+            &tape,
+            NanPolicy::Empty,
+            [1.0, 1.0, inf, 2.0, 0.0, 0.0, 1.0, 2.0, 0.0],
+        );
+
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        check(
+            &tape,
+            NanPolicy::Propagate,
+            [n, n, n, 3.0, n, n, 1.0, 3.0, n],
+        );
+        check(
+            &tape,
+            NanPolicy::Empty,
+            [inf, inf, inf, 3.0, inf, inf, 1.0, 3.0, inf],
+        );
+
+        let tape = ctx.get_tape::<I>(max_imm).unwrap();
+        check(
+            &tape,
+            NanPolicy::Propagate,
+            [n, 1.0, n, 2.0, 1.0, n, 1.0, 3.0, n],
+        );
+        check(
+            &tape,
+            NanPolicy::Empty,
+            [inf, 1.0, inf, 2.0, 1.0, inf, 1.0, 3.0, inf],
+        );
+    }
+
+    pub fn test_f_fma<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let mul = ctx.mul(x, y).unwrap();
+        let fma = ctx.add(mul, z).unwrap();
+        let tape = ctx.get_tape::<I>(fma).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        // The result is only rounded once
+        let xs = [0.1, 1.0, 2.0, -3.0, 1.0 + f32::EPSILON, 5.0, 6.0, 7.0, 8.0];
+        let ys = [0.3, 2.0, 0.5, 4.0, 1.0 - f32::EPSILON, 1.0, 1.0, 1.0, 1.0];
+        let zs = [-0.03, 1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 0.0, 1.0];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        for i in 0..xs.len() {
+            assert_eq!(out[i], xs[i].mul_add(ys[i], zs[i]), "{i}");
+        }
+        assert_ne!(out[4], 0.0);
+    }
+
+    pub fn test_f_trig<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let s = ctx.sin(x).unwrap();
+        let c = ctx.cos(y).unwrap();
+        let out = ctx.mul(s, c).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let xs = [0.0, 1.0, -1.0, 3.0, 0.5, -2.0, 4.0, 10.0, 100.0];
+        let ys = [0.0, 1.0, 2.0, -0.5, 4.0, -0.5, 2.0, 1.0, -7.0];
+        let out = eval.eval(&xs, &ys, &[0.0; 9], &[]).unwrap();
+        for i in 0..xs.len() {
+            let expected = xs[i].sin() * ys[i].cos();
+            assert!((out[i] - expected).abs() < 1e-6, "{i}");
+        }
+    }
+
+    pub fn test_f_polar<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let r = ctx.hypot(x, y).unwrap();
+        let a = ctx.atan2(y, x).unwrap();
+        let a = ctx.mul(a, z).unwrap();
+        let out = ctx.sub(r, a).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let xs = [1.0, 0.0, -1.0, -1.0, 3.0, 0.5, -2.0, 4.0, 0.0];
+        let ys = [0.0, 1.0, 0.0, -0.5, 4.0, -0.5, 2.0, 1.0, 0.0];
+        let zs = [1.0, 2.0, 0.5, 1.0, 0.0, -1.0, 1.0, 0.25, 1.0];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        for i in 0..xs.len() {
+            let expected = xs[i].hypot(ys[i]) - ys[i].atan2(xs[i]) * zs[i];
+            assert!((out[i] - expected).abs() < 1e-6, "{i}");
+        }
+    }
+
+    pub fn test_f_mod<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.modulo(x, y).unwrap();
+        let b = ctx.modulo(y, 1.5).unwrap();
+        let c = ctx.modulo(2.0, x).unwrap();
+        let out = ctx.add(a, b).unwrap();
+        let out = ctx.add(out, c).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let m = |a: f32, b: f32| a - b * (a / b).floor();
+        let xs = [1.0, -0.5, 3.5, -7.0, 2.0, 0.25, -2.0, 4.0, 9.0];
+        let ys = [2.0, 2.0, -2.0, -3.0, 0.5, 3.0, 1.0, -1.5, 4.0];
+        let out = eval.eval(&xs, &ys, &[0.0; 9], &[]).unwrap();
+        for i in 0..xs.len() {
+            let expected = m(xs[i], ys[i]) + m(ys[i], 1.5) + m(2.0, xs[i]);
+            assert_eq!(out[i], expected, "{i}");
+        }
+    }
+
+    pub fn test_f_noise<I: Family>() {
+        use crate::eval::noise::noise3;
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let n = ctx.noise3(x, y, z, 12).unwrap();
+        let tape = ctx.get_tape::<I>(n).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let xs = [0.1, 1.2, 2.3, -3.4, 0.5, 4.6, 5.7, -6.8, 7.9];
+        let ys = [1.0, 2.5, -1.5, 0.0, 3.25, 0.75, -2.0, 1.5, 0.0];
+        let zs = [0.3, -0.3, 0.6, -0.6, 0.9, -0.9, 1.2, -1.2, 1.5];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        for i in 0..xs.len() {
+            assert_eq!(out[i], noise3(xs[i], ys[i], zs[i], 12), "{i}");
+        }
+    }
+
+    pub fn test_f_clamp<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let c = ctx.clamp(x, y, z).unwrap();
+        let tape = ctx.get_tape::<I>(c).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let xs = [0.5, 2.0, -3.0, 0.0, 1.0, f32::NAN, 4.0, -6.0, 7.0];
+        let ys = [0.0, 0.0, -1.0, 0.5, 1.0, 0.0, f32::NAN, -7.0, 0.0];
+        let zs = [1.0, 1.0, 1.0, 1.0, 2.0, 1.0, 5.0, -6.5, 8.0];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        assert_eq!(&out[..5], &[0.5, 1.0, -1.0, 0.5, 1.0]);
+        assert!(out[5].is_nan());
+        assert!(out[6].is_nan());
+        assert_eq!(&out[7..], &[-6.5, 7.0]);
+    }
+
+    pub fn test_f_min_max_nc<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.min_nc(x, y).unwrap();
+        let b = ctx.max_nc(x, 1.0).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let xs = [0.5, 3.0, -1.0, 2.0, f32::NAN, 0.0, 1.0, 4.0, -5.0];
+        let ys = [2.0, -2.0, 0.0, 2.0, 0.0, f32::NAN, 1.0, 5.0, -6.0];
+        let out = eval.eval(&xs, &ys, &[0.0; 9], &[]).unwrap();
+        for i in 0..xs.len() {
+            let expected = xs[i].min(ys[i]) + xs[i].max(1.0);
+            if xs[i].is_nan() || ys[i].is_nan() {
+                assert!(out[i].is_nan(), "{i}");
+            } else {
+                assert_eq!(out[i], expected, "{i}");
+            }
+        }
+    }
+
+    pub fn test_f_custom<I: Family>() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op = alloc::sync::Arc::new(SquareSub);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.custom(op.clone(), x, y).unwrap();
+        let b = ctx.custom(op, y, 1.0).unwrap();
+        let out = ctx.mul(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        let xs = [0.0, 1.0, 2.0, -3.0, 0.5, 4.0, 5.0, -6.0, 7.0];
+        let ys = [1.0, 2.0, -1.0, 0.0, 3.0, 0.25, -2.0, 1.5, 0.0];
+        let zs = [0.0; 9];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        for i in 0..xs.len() {
+            let (x, y) = (xs[i], ys[i]);
+            assert_eq!(out[i], (x * x - y) * (y * y - 1.0), "{i}");
+        }
+    }
+
     pub fn test_f_var<I: Family>() {
         let mut ctx = Context::new();
         let a = ctx.var("a").unwrap();
@@ -174,6 +413,160 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_f_strided<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let a = ctx.var("a").unwrap();
+        let xy = ctx.mul(x, y).unwrap();
+        let sum = ctx.add(xy, z).unwrap();
+        let out = ctx.mul(sum, a).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        // Pick a length which isn't a multiple of the SIMD width or chunk size
+        let n = crate::eval::bulk::CHUNK_SIZE * 2 + 3;
+        let xs: Vec<f32> = (0..n).map(|i| i as f32).collect();
+        let ys: Vec<f32> = (0..n).map(|i| (n - i) as f32 * 0.5).collect();
+        let zs: Vec<f32> = (0..n).map(|i| (i % 7) as f32).collect();
+        let expected = eval.eval(&xs, &ys, &zs, &[2.0]).unwrap();
+
+        let mut data = Default::default();
+        let xyz: Vec<f32> =
+            (0..n).flat_map(|i| [xs[i], ys[i], zs[i]]).collect();
+        let mut out = vec![0.0; n];
+        eval.eval_interleaved_into(&xyz, &[2.0], &mut out, &mut data)
+            .unwrap();
+        assert_eq!(out, expected);
+
+        // Points with extra data and shuffled coordinates
+        let points: Vec<f32> = (0..n)
+            .flat_map(|i| [zs[i], -1.0, xs[i], ys[i], -2.0])
+            .collect();
+        let mut out = vec![0.0; n];
+        eval.eval_strided_into(
+            &points,
+            5,
+            [2, 3, 0],
+            &[2.0],
+            &mut out,
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(out, expected);
+
+        // Bad layouts are rejected
+        assert!(matches!(
+            eval.eval_strided_into(
+                &points,
+                5,
+                [2, 3, 5],
+                &[2.0],
+                &mut out,
+                &mut data
+            ),
+            Err(Error::BadStride(5))
+        ));
+        assert!(matches!(
+            eval.eval_interleaved_into(&xyz[1..], &[2.0], &mut out, &mut data),
+            Err(Error::MismatchedSlices)
+        ));
+        let mut out = vec![0.0; 2];
+        assert!(matches!(
+            eval.eval_interleaved_into(
+                &xyz[..6],
+                &[1.0, 2.0],
+                &mut out,
+                &mut data
+            ),
+            Err(Error::BadVarSlice(2, 1))
+        ));
+    }
+
+    pub fn test_f_eval_into<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.var("a").unwrap();
+        let sum = ctx.add(x, y).unwrap();
+        let out = ctx.mul(sum, a).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_float_slice_evaluator();
+
+        // Pick a length which isn't a multiple of the SIMD width or chunk size
+        let n = crate::eval::bulk::CHUNK_SIZE * 2 + 3;
+        let xs: Vec<f32> = (0..n).map(|i| i as f32).collect();
+        let ys: Vec<f32> = (0..n).map(|i| (n - i) as f32 * 0.5).collect();
+        let zs = vec![0.0; n];
+
+        let mut out = vec![0.0; n];
+        let mut data = Default::default();
+        eval.eval_into(&xs, &ys, &zs, &[2.0], &mut out, &mut data)
+            .unwrap();
+        let expected = eval.eval(&xs, &ys, &zs, &[2.0]).unwrap();
+        assert_eq!(out, expected);
+        for (i, o) in out.iter().enumerate() {
+            assert_eq!(*o, (xs[i] + ys[i]) * 2.0);
+        }
+
+        // Short slices are fine, too
+        let mut out = [0.0; 3];
+        eval.eval_into(
+            &xs[0..3],
+            &ys[0..3],
+            &zs[0..3],
+            &[1.0],
+            &mut out,
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(out, [xs[0] + ys[0], xs[1] + ys[1], xs[2] + ys[2]]);
+
+        let mut out = [0.0; 2];
+        assert!(eval
+            .eval_into(
+                &xs[0..3],
+                &ys[0..3],
+                &zs[0..3],
+                &[1.0],
+                &mut out,
+                &mut data
+            )
+            .is_err());
+    }
+
+    pub fn test_f_sign_convention<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let sum = ctx.add(x, y).unwrap();
+        let tape = ctx
+            .get_tape::<I>(sum)
+            .unwrap()
+            .with_sign_convention(SignConvention::PositiveInside);
+        let eval = tape.new_float_slice_evaluator();
+
+        // Use enough points to span more than one chunk
+        let n = crate::eval::bulk::CHUNK_SIZE + 7;
+        let xs: Vec<f32> = (0..n).map(|i| i as f32).collect();
+        let ys = vec![1.0; n];
+        let zs = vec![0.0; n];
+        let expected: Vec<f32> = xs.iter().map(|x| -(x + 1.0)).collect();
+        assert_eq!(eval.eval(&xs, &ys, &zs, &[]).unwrap(), expected);
+
+        let mut out = vec![0.0; n];
+        let mut data = Default::default();
+        eval.eval_into(&xs, &ys, &zs, &[], &mut out, &mut data)
+            .unwrap();
+        assert_eq!(out, expected);
+
+        let xyz: Vec<f32> = xs.iter().flat_map(|x| [*x, 1.0, 0.0]).collect();
+        eval.eval_interleaved_into(&xyz, &[], &mut out, &mut data)
+            .unwrap();
+        assert_eq!(out, expected);
+    }
+
     #[macro_export]
     macro_rules! float_slice_test {
         ($i:ident, $t:ty) => {
@@ -190,6 +583,18 @@ pub mod eval_tests {
             $crate::float_slice_test!(test_give_take, $t);
             $crate::float_slice_test!(test_vectorized, $t);
             $crate::float_slice_test!(test_f_var, $t);
+            $crate::float_slice_test!(test_f_fma, $t);
+            $crate::float_slice_test!(test_f_polar, $t);
+            $crate::float_slice_test!(test_f_mod, $t);
+            $crate::float_slice_test!(test_f_trig, $t);
+            $crate::float_slice_test!(test_f_custom, $t);
+            $crate::float_slice_test!(test_f_noise, $t);
+            $crate::float_slice_test!(test_f_clamp, $t);
+            $crate::float_slice_test!(test_f_min_max_nc, $t);
+            $crate::float_slice_test!(test_f_nan_policy, $t);
+            $crate::float_slice_test!(test_f_sign_convention, $t);
+            $crate::float_slice_test!(test_f_eval_into, $t);
+            $crate::float_slice_test!(test_f_strided, $t);
         };
     }
 }
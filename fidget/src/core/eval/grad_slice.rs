@@ -1,8 +1,11 @@
 //! Evaluation of partial derivatives
-use crate::eval::{
-    bulk::{BulkEval, BulkEvalData, BulkEvaluator},
-    types::Grad,
-    EvaluatorStorage, Family,
+use crate::{
+    eval::{
+        bulk::{BulkEval, BulkEvalData, BulkEvaluator, CHUNK_SIZE},
+        types::Grad,
+        EvaluatorStorage, Family,
+    },
+    Error,
 };
 
 /// Evaluator for many points, calculating partial derivatives
@@ -20,12 +23,94 @@ pub type GradSliceEvalData<F> = BulkEvalData<
 pub type GradSliceEvalStorage<F> =
     <<F as Family>::GradSliceEval as EvaluatorStorage<F>>::Storage;
 
+impl<E, F: Family> BulkEval<Grad, E, F>
+where
+    E: BulkEvaluator<Grad, F> + EvaluatorStorage<F>,
+{
+    /// Evaluates the given slices, writing values and partial derivatives
+    /// into separate caller-provided buffers
+    ///
+    /// `out` is `[v, dx, dy, dz]`, i.e. a structure-of-arrays equivalent to
+    /// the [`Grad`] values returned by [`eval_with`](Self::eval_with); each
+    /// slice must be the same length as `x`, `y`, and `z`.  This is useful
+    /// when the consumer wants planar data (e.g. separate channels of a
+    /// normal map, or SIMD code working on one component at a time).
+    ///
+    /// Points are evaluated in chunks of [`CHUNK_SIZE`], and each chunk is
+    /// scattered into `out` while it's still hot in the cache, so there's no
+    /// full-size intermediate buffer.
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let y2 = ctx.mul(y, 2.0).unwrap();
+    /// let sum = ctx.add(x, y2).unwrap();
+    /// let tape = ctx.get_tape::<fidget::vm::Eval>(sum).unwrap();
+    /// let eval = tape.new_grad_slice_evaluator();
+    ///
+    /// let mut v = [0.0; 2];
+    /// let mut dx = [0.0; 2];
+    /// let mut dy = [0.0; 2];
+    /// let mut dz = [0.0; 2];
+    /// eval.eval_soa_into(
+    ///     &[1.0, 2.0],
+    ///     &[3.0, 4.0],
+    ///     &[0.0, 0.0],
+    ///     &[],
+    ///     [&mut v, &mut dx, &mut dy, &mut dz],
+    ///     &mut Default::default(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(v, [7.0, 10.0]);
+    /// assert_eq!(dx, [1.0, 1.0]);
+    /// assert_eq!(dy, [2.0, 2.0]);
+    /// assert_eq!(dz, [0.0, 0.0]);
+    /// ```
+    pub fn eval_soa_into(
+        &self,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+        out: [&mut [f32]; 4],
+        data: &mut BulkEvalData<E::Data, Grad, F>,
+    ) -> Result<(), Error> {
+        if x.len() != y.len()
+            || x.len() != z.len()
+            || out.iter().any(|o| o.len() != x.len())
+        {
+            return Err(Error::MismatchedSlices);
+        }
+        let [v, dx, dy, dz] = out;
+        for (i, ((x, y), z)) in x
+            .chunks(CHUNK_SIZE)
+            .zip(y.chunks(CHUNK_SIZE))
+            .zip(z.chunks(CHUNK_SIZE))
+            .enumerate()
+        {
+            let start = i * CHUNK_SIZE;
+            let grads = self.eval_with(x, y, z, vars, data)?;
+            for (j, g) in grads.iter().enumerate() {
+                v[start + j] = g.v;
+                dx[start + j] = g.dx;
+                dy[start + j] = g.dy;
+                dz[start + j] = g.dz;
+            }
+        }
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(any(test, feature = "eval-tests"))]
 pub mod eval_tests {
     use super::*;
-    use crate::{context::Context, eval::Vars};
+    use crate::{
+        context::Context,
+        eval::{NanPolicy, Vars},
+    };
 
     pub fn test_g_x<I: Family>() {
         let mut ctx = Context::new();
@@ -122,6 +207,161 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_g_trig<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let s = ctx.sin(x).unwrap();
+        let c = ctx.cos(y).unwrap();
+        let out = ctx.add(s, c).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+
+        let eval = tape.new_grad_slice_evaluator();
+        for (x, y) in [(0.0, 0.0), (1.0, 2.0), (-3.0, 0.5)] {
+            let g = eval.eval(&[x], &[y], &[0.0], &[]).unwrap()[0];
+            assert_eq!(g, Grad::new(x.sin() + y.cos(), x.cos(), -y.sin(), 0.0));
+        }
+    }
+
+    pub fn test_g_atan2<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.atan2(y, x).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+
+        let eval = tape.new_grad_slice_evaluator();
+        assert_eq!(
+            eval.eval(&[1.0], &[1.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(core::f32::consts::FRAC_PI_4, -0.5, 0.5, 0.0)
+        );
+        assert_eq!(
+            eval.eval(&[0.0], &[2.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(core::f32::consts::FRAC_PI_2, -0.5, 0.0, 0.0)
+        );
+    }
+
+    pub fn test_g_hypot<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let h = ctx.hypot(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(h).unwrap();
+
+        let eval = tape.new_grad_slice_evaluator();
+        assert_eq!(
+            eval.eval(&[3.0], &[4.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(5.0, 0.6, 0.8, 0.0)
+        );
+        assert_eq!(
+            eval.eval(&[0.0], &[-2.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(2.0, 0.0, -1.0, 0.0)
+        );
+    }
+
+    pub fn test_g_mod<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let m = ctx.modulo(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(m).unwrap();
+
+        // The derivative with respect to the divisor is `-floor(x / y)`
+        let eval = tape.new_grad_slice_evaluator();
+        assert_eq!(
+            eval.eval(&[3.5], &[2.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(1.5, 1.0, -1.0, 0.0)
+        );
+        assert_eq!(
+            eval.eval(&[-0.5], &[2.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(1.5, 1.0, 1.0, 0.0)
+        );
+    }
+
+    pub fn test_g_noise<I: Family>() {
+        use crate::eval::noise::noise3_grad;
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x2 = ctx.mul(x, 2.0).unwrap();
+        let n = ctx.noise3(x2, y, z, 4).unwrap();
+        let tape = ctx.get_tape::<I>(n).unwrap();
+        let eval = tape.new_grad_slice_evaluator();
+
+        for (x, y, z) in [(0.5, 1.25, -2.0), (3.7, -0.2, 0.9)] {
+            let out = eval.eval(&[x], &[y], &[z], &[]).unwrap()[0];
+            let expected = noise3_grad(
+                Grad::new(x * 2.0, 2.0, 0.0, 0.0),
+                Grad::new(y, 0.0, 1.0, 0.0),
+                Grad::new(z, 0.0, 0.0, 1.0),
+                4,
+            );
+            assert_eq!(out, expected);
+        }
+    }
+
+    pub fn test_g_clamp<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let y2 = ctx.mul(y, 2.0).unwrap();
+        let c = ctx.clamp(x, y2, z).unwrap();
+        let tape = ctx.get_tape::<I>(c).unwrap();
+        let eval = tape.new_grad_slice_evaluator();
+
+        let out = eval
+            .eval(&[0.5, -3.0, 4.0], &[0.0, -1.0, 0.0], &[1.0, 1.0, 2.0], &[])
+            .unwrap();
+        assert_eq!(out[0], Grad::new(0.5, 1.0, 0.0, 0.0));
+        assert_eq!(out[1], Grad::new(-2.0, 0.0, 2.0, 0.0));
+        assert_eq!(out[2], Grad::new(2.0, 0.0, 0.0, 1.0));
+    }
+
+    pub fn test_g_min_max_nc<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.min_nc(x, y).unwrap();
+        let b = ctx.max_nc(y, 1.0).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_grad_slice_evaluator();
+
+        let out = eval
+            .eval(&[0.5, 3.0], &[2.0, -2.0], &[0.0; 2], &[])
+            .unwrap();
+        assert_eq!(out[0], Grad::new(2.5, 1.0, 1.0, 0.0));
+        assert_eq!(out[1], Grad::new(-1.0, 0.0, 1.0, 0.0));
+    }
+
+    pub fn test_g_custom<I: Family>() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op = alloc::sync::Arc::new(SquareSub);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.custom(op.clone(), x, y).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+
+        let eval = tape.new_grad_slice_evaluator();
+        assert_eq!(
+            eval.eval(&[3.0], &[2.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(7.0, 6.0, -1.0, 0.0)
+        );
+
+        let a = ctx.custom(op, y, 0.5).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+        let eval = tape.new_grad_slice_evaluator();
+        assert_eq!(
+            eval.eval(&[0.0], &[-2.0], &[0.0], &[]).unwrap()[0],
+            Grad::new(3.5, 0.0, -4.0, 0.0)
+        );
+    }
+
     pub fn test_g_mul<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -148,6 +388,26 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_g_fma<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let mul = ctx.mul(x, y).unwrap();
+        let fma = ctx.add(z, mul).unwrap();
+        let tape = ctx.get_tape::<I>(fma).unwrap();
+
+        let eval = tape.new_grad_slice_evaluator();
+        assert_eq!(
+            eval.eval(&[4.0, 1.0], &[2.0, -3.0], &[1.0, 0.5], &[])
+                .unwrap(),
+            [
+                Grad::new(9.0, 2.0, 4.0, 1.0),
+                Grad::new(-2.5, -3.0, 1.0, 1.0)
+            ]
+        );
+    }
+
     pub fn test_g_div<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -238,6 +498,46 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_g_nan_policy<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(x, y).unwrap();
+        let nan = f32::NAN;
+        let inf = f32::INFINITY;
+
+        for (node, is_min) in [(min, true), (max, false)] {
+            let tape = ctx.get_tape::<I>(node).unwrap();
+
+            // The NaN argument is returned, preferring the left-hand side
+            let eval = tape.new_grad_slice_evaluator();
+            let out = eval.eval(&[nan, 1.0], &[1.0, nan], &[0.0; 2], &[]);
+            let out = out.unwrap();
+            assert!(out[0].v.is_nan());
+            assert_eq!(out[0].dx, 1.0);
+            assert!(out[1].v.is_nan());
+            assert_eq!(out[1].dy, 1.0);
+            let out = eval.eval(&[nan], &[nan], &[0.0], &[]).unwrap();
+            assert_eq!(out[0].dx, 1.0);
+
+            // NaN values are replaced with +inf, keeping their derivatives
+            let tape = tape.with_nan_policy(NanPolicy::Empty);
+            let eval = tape.new_grad_slice_evaluator();
+            let out = eval.eval(&[nan, 1.0], &[1.0, nan], &[0.0; 2], &[]);
+            let out = out.unwrap();
+            if is_min {
+                assert_eq!(out[0], Grad::new(1.0, 0.0, 1.0, 0.0));
+                assert_eq!(out[1], Grad::new(1.0, 1.0, 0.0, 0.0));
+            } else {
+                assert_eq!(out[0], Grad::new(inf, 1.0, 0.0, 0.0));
+                assert_eq!(out[1], Grad::new(inf, 0.0, 1.0, 0.0));
+            }
+            let out = eval.eval(&[nan], &[nan], &[0.0], &[]).unwrap();
+            assert_eq!(out[0].v, inf);
+        }
+    }
+
     pub fn test_g_circle<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -340,6 +640,55 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_g_soa<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let s = ctx.sin(x).unwrap();
+        let s = ctx.mul(s, y).unwrap();
+        let s = ctx.add(s, z).unwrap();
+        let tape = ctx.get_tape::<I>(s).unwrap();
+        let eval = tape.new_grad_slice_evaluator();
+
+        // Spans more than one chunk, with a partial chunk at the end
+        let n = crate::eval::bulk::CHUNK_SIZE + 5;
+        let xs: Vec<f32> = (0..n).map(|i| i as f32 * 0.01).collect();
+        let ys: Vec<f32> = (0..n).map(|i| 1.0 - i as f32 * 0.002).collect();
+        let zs: Vec<f32> = (0..n).map(|i| i as f32 * 0.1).collect();
+        let expected = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+
+        let mut out = vec![vec![f32::NAN; n]; 4];
+        let [v, dx, dy, dz] = &mut out[..] else {
+            unreachable!()
+        };
+        let mut data = Default::default();
+        eval.eval_soa_into(&xs, &ys, &zs, &[], [v, dx, dy, dz], &mut data)
+            .unwrap();
+        for (i, g) in expected.iter().enumerate() {
+            assert_eq!(
+                [out[0][i], out[1][i], out[2][i], out[3][i]],
+                [g.v, g.dx, g.dy, g.dz]
+            );
+        }
+
+        let mut short = vec![0.0; n - 1];
+        let [v, dx, dy, _] = &mut out[..] else {
+            unreachable!()
+        };
+        assert!(matches!(
+            eval.eval_soa_into(
+                &xs,
+                &ys,
+                &zs,
+                &[],
+                [v, dx, dy, &mut short],
+                &mut data
+            ),
+            Err(Error::MismatchedSlices)
+        ));
+    }
+
     #[macro_export]
     macro_rules! grad_test {
         ($i:ident, $t:ty) => {
@@ -360,13 +709,24 @@ pub mod eval_tests {
             $crate::grad_test!(test_g_abs, $t);
             $crate::grad_test!(test_g_square, $t);
             $crate::grad_test!(test_g_sqrt, $t);
+            $crate::grad_test!(test_g_trig, $t);
+            $crate::grad_test!(test_g_atan2, $t);
+            $crate::grad_test!(test_g_hypot, $t);
+            $crate::grad_test!(test_g_mod, $t);
+            $crate::grad_test!(test_g_custom, $t);
+            $crate::grad_test!(test_g_noise, $t);
+            $crate::grad_test!(test_g_clamp, $t);
+            $crate::grad_test!(test_g_min_max_nc, $t);
             $crate::grad_test!(test_g_mul, $t);
+            $crate::grad_test!(test_g_fma, $t);
             $crate::grad_test!(test_g_min, $t);
             $crate::grad_test!(test_g_max, $t);
             $crate::grad_test!(test_g_min_max, $t);
+            $crate::grad_test!(test_g_nan_policy, $t);
             $crate::grad_test!(test_g_div, $t);
             $crate::grad_test!(test_g_recip, $t);
             $crate::grad_test!(test_g_var, $t);
+            $crate::grad_test!(test_g_soa, $t);
         };
     }
 }
@@ -0,0 +1,317 @@
+//! Evaluation of second partial derivatives
+//!
+//! ```
+//! use fidget::{context::Context, vm};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let x2 = ctx.square(x)?;
+//! let xy = ctx.mul(x, y)?;
+//! let sum = ctx.add(x2, xy)?;
+//! let tape = ctx.get_tape::<vm::Eval>(sum)?;
+//!
+//! let eval = tape.new_hessian_slice_evaluator();
+//! let h = eval.eval(&[1.0], &[2.0], &[0.0], &[])?[0];
+//! assert_eq!(h.v, 3.0);
+//! assert_eq!(h.grad, [4.0, 1.0, 0.0]);
+//! assert_eq!(h.hess, [[2.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0; 3]]);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::eval::{
+    bulk::{BulkEval, BulkEvalData, BulkEvaluator},
+    types::Hessian,
+    EvaluatorStorage, Family,
+};
+
+/// Evaluator for many points, calculating first and second partial derivatives
+pub type HessianSliceEval<F> =
+    BulkEval<Hessian, <F as Family>::HessianSliceEval, F>;
+
+/// Scratch data used by a bulk Hessian evaluator from a particular family `F`
+pub type HessianSliceEvalData<F> = BulkEvalData<
+    <<F as Family>::HessianSliceEval as BulkEvaluator<Hessian, F>>::Data,
+    Hessian,
+    F,
+>;
+
+/// Immutable data used by a bulk Hessian evaluator from a particular family
+/// `F`
+pub type HessianSliceEvalStorage<F> =
+    <<F as Family>::HessianSliceEval as EvaluatorStorage<F>>::Storage;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(any(test, feature = "eval-tests"))]
+pub mod eval_tests {
+    use super::*;
+    use crate::{context::Context, eval::types::Grad};
+    use alloc::{vec, vec::Vec};
+
+    /// Checks that two values are equal within a relative tolerance
+    fn close(a: f32, b: f32, tol: f32) -> bool {
+        (a - b).abs() <= tol * a.abs().max(b.abs()).max(1.0)
+    }
+
+    pub fn test_h_inputs<I: Family>() {
+        let mut ctx = Context::new();
+        let eval = |ctx: &Context, n| {
+            let tape = ctx.get_tape::<I>(n).unwrap();
+            let eval = tape.new_hessian_slice_evaluator();
+            eval.eval(&[2.0], &[3.0], &[4.0], &[]).unwrap()[0]
+        };
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        assert_eq!(eval(&ctx, x), Hessian::input(2.0, 0));
+        assert_eq!(eval(&ctx, y), Hessian::input(3.0, 1));
+        assert_eq!(eval(&ctx, z), Hessian::input(4.0, 2));
+    }
+
+    pub fn test_h_square<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x2 = ctx.square(x).unwrap();
+        let xy = ctx.mul(x, y).unwrap();
+        let sum = ctx.add(x2, xy).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+
+        let eval = tape.new_hessian_slice_evaluator();
+        let out = eval
+            .eval(&[1.0, -2.0], &[2.0, 1.0], &[0.0; 2], &[])
+            .unwrap();
+        let hess = [[2.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0; 3]];
+        assert_eq!(out[0], Hessian::new(3.0, [4.0, 1.0, 0.0], hess));
+        assert_eq!(out[1], Hessian::new(2.0, [-3.0, -2.0, 0.0], hess));
+    }
+
+    pub fn test_h_sphere<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let z2 = ctx.square(z).unwrap();
+        let sum = ctx.add(x2, y2).unwrap();
+        let sum = ctx.add(sum, z2).unwrap();
+        let r = ctx.sqrt(sum).unwrap();
+        let sphere = ctx.sub(r, 1.0).unwrap();
+        let tape = ctx.get_tape::<I>(sphere).unwrap();
+
+        let eval = tape.new_hessian_slice_evaluator();
+        let xs = [2.0, 0.0, 1.0];
+        let ys = [0.0, 0.5, 2.0];
+        let zs = [0.0, 0.0, 2.0];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        for (i, h) in out.iter().enumerate() {
+            // The Hessian of a distance to a point is (I - n nᵀ) / r
+            let p = [xs[i], ys[i], zs[i]];
+            let r = p.iter().map(|v| v * v).sum::<f32>().sqrt();
+            let n = p.map(|v| v / r);
+            assert!(close(h.v, r - 1.0, 1e-6), "{h:?}");
+            for a in 0..3 {
+                assert!(close(h.grad[a], n[a], 1e-6), "{h:?}");
+                for b in 0..3 {
+                    let eye = if a == b { 1.0 } else { 0.0 };
+                    let expected = (eye - n[a] * n[b]) / r;
+                    assert!(close(h.hess[a][b], expected, 1e-5), "{h:?}");
+                }
+            }
+            assert!(close(h.mean_curvature(), 1.0 / r, 1e-5), "{h:?}");
+        }
+    }
+
+    /// Builds a shape which uses most opcodes
+    fn shape(ctx: &mut Context) -> crate::context::Node {
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let r = ctx.hypot(x, y).unwrap();
+        let a = ctx.atan2(y, x).unwrap();
+        let s = ctx.sqrt(r).unwrap();
+        let q = ctx.square(z).unwrap();
+        let q = ctx.add(q, 1.0).unwrap();
+        let d = ctx.div(a, q).unwrap();
+        let m = ctx.mul(x, y).unwrap();
+        let fma = ctx.add(m, z).unwrap();
+        let neg = ctx.neg(fma).unwrap();
+        let abs = ctx.abs(neg).unwrap();
+        let abs = ctx.add(abs, 0.5).unwrap();
+        let recip = ctx.recip(abs).unwrap();
+        let min = ctx.min(s, d).unwrap();
+        let max = ctx.max(min, recip).unwrap();
+        let div = ctx.div(2.0, max).unwrap();
+        let e = ctx.atan2(1.5, z).unwrap();
+        let f = ctx.hypot(z, 0.5).unwrap();
+        let g = ctx.mul(e, f).unwrap();
+        let g = ctx.sub(g, x).unwrap();
+        let sin = ctx.sin(x).unwrap();
+        let cos = ctx.cos(y).unwrap();
+        let w = ctx.mul(sin, cos).unwrap();
+        let g = ctx.add(g, w).unwrap();
+        ctx.add(div, g).unwrap()
+    }
+
+    pub fn test_h_finite_differences<I: Family>() {
+        let mut ctx = Context::new();
+        let root = shape(&mut ctx);
+        let tape = ctx.get_tape::<I>(root).unwrap();
+
+        let mut xs = vec![];
+        let mut ys = vec![];
+        let mut zs = vec![];
+        for i in 0..16 {
+            let t = i as f32;
+            xs.push((t * 0.37).sin() * 2.0 + 0.1);
+            ys.push((t * 0.61 + 1.0).cos() * 1.5 - 0.2);
+            zs.push((t * 0.23 - 0.5).sin() * 1.2);
+        }
+        let hess = tape.new_hessian_slice_evaluator();
+        let grad = tape.new_grad_slice_evaluator();
+        let out = hess.eval(&xs, &ys, &zs, &[]).unwrap();
+
+        // The value and gradient must match the gradient evaluator
+        let g = grad.eval(&xs, &ys, &zs, &[]).unwrap();
+        for (h, g) in out.iter().zip(&g) {
+            let hg: Grad = h.to_grad();
+            assert!(close(hg.v, g.v, 1e-6), "{h:?} != {g:?}");
+            assert!(close(hg.dx, g.dx, 1e-5), "{h:?} != {g:?}");
+            assert!(close(hg.dy, g.dy, 1e-5), "{h:?} != {g:?}");
+            assert!(close(hg.dz, g.dz, 1e-5), "{h:?} != {g:?}");
+        }
+
+        // Second derivatives must match finite differences of the gradient
+        let eps = 1e-3;
+        for j in 0..3 {
+            let offset = |vs: &[f32], k: usize, s: f32| -> Vec<f32> {
+                vs.iter().map(|v| if j == k { v + s } else { *v }).collect()
+            };
+            let hi = grad
+                .eval(
+                    &offset(&xs, 0, eps),
+                    &offset(&ys, 1, eps),
+                    &offset(&zs, 2, eps),
+                    &[],
+                )
+                .unwrap();
+            let lo = grad
+                .eval(
+                    &offset(&xs, 0, -eps),
+                    &offset(&ys, 1, -eps),
+                    &offset(&zs, 2, -eps),
+                    &[],
+                )
+                .unwrap();
+            for (k, h) in out.iter().enumerate() {
+                let (hi, lo) = (hi[k], lo[k]);
+                let fd = [hi.dx - lo.dx, hi.dy - lo.dy, hi.dz - lo.dz]
+                    .map(|d| d / (2.0 * eps));
+                for (i, fd) in fd.iter().enumerate() {
+                    assert!(
+                        close(h.hess[i][j], *fd, 2e-2),
+                        "hess[{i}][{j}] at {k}: {} != {fd}",
+                        h.hess[i][j],
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn test_h_var<I: Family>() {
+        let mut ctx = Context::new();
+        let a = ctx.var("a").unwrap();
+        let x = ctx.x();
+        let ax = ctx.mul(a, x).unwrap();
+        let axx = ctx.mul(ax, x).unwrap();
+        let tape = ctx.get_tape::<I>(axx).unwrap();
+        let eval = tape.new_hessian_slice_evaluator();
+        for a in [1.0, 3.0] {
+            let h = eval.eval(&[2.0], &[0.0], &[0.0], &[a]).unwrap()[0];
+            assert_eq!(h.v, 4.0 * a);
+            assert_eq!(h.grad, [4.0 * a, 0.0, 0.0]);
+            assert_eq!(h.diagonal(), [2.0 * a, 0.0, 0.0]);
+        }
+    }
+
+    pub fn test_h_custom<I: Family>() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op = alloc::sync::Arc::new(SquareSub);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let xy = ctx.mul(x, y).unwrap();
+        let a = ctx.custom(op, xy, y).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+
+        // (xy)² - y
+        let eval = tape.new_hessian_slice_evaluator();
+        let h = eval.eval(&[2.0], &[3.0], &[0.0], &[]).unwrap()[0];
+        assert_eq!(h.v, 33.0);
+        assert_eq!(h.grad, [36.0, 23.0, 0.0]);
+        assert_eq!(
+            h.hess,
+            [[18.0, 24.0, 0.0], [24.0, 8.0, 0.0], [0.0, 0.0, 0.0]]
+        );
+    }
+
+    pub fn test_h_noise<I: Family>() {
+        use crate::eval::noise::noise3_hessian;
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x2 = ctx.mul(x, 2.0).unwrap();
+        let xy = ctx.mul(x, y).unwrap();
+        let n = ctx.noise3(x2, xy, z, 4).unwrap();
+        let tape = ctx.get_tape::<I>(n).unwrap();
+
+        let eval = tape.new_hessian_slice_evaluator();
+        let xs = [0.3, -1.7, 2.2];
+        let ys = [1.1, 0.4, -0.6];
+        let zs = [-0.9, 2.5, 0.05];
+        let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+        for i in 0..3 {
+            let (hx, hy, hz) = (
+                Hessian::input(xs[i], 0),
+                Hessian::input(ys[i], 1),
+                Hessian::input(zs[i], 2),
+            );
+            let expected = noise3_hessian(hx * 2.0.into(), hx * hy, hz, 4);
+            assert!(close(out[i].v, expected.v, 1e-6));
+            for a in 0..3 {
+                assert!(close(out[i].grad[a], expected.grad[a], 1e-5));
+                for b in 0..3 {
+                    let (h, e) = (out[i].hess[a][b], expected.hess[a][b]);
+                    assert!(close(h, e, 1e-5), "{h} != {e}");
+                }
+            }
+        }
+    }
+
+    #[macro_export]
+    macro_rules! hessian_test {
+        ($i:ident, $t:ty) => {
+            #[test]
+            fn $i() {
+                $crate::eval::hessian_slice::eval_tests::$i::<$t>()
+            }
+        };
+    }
+
+    #[macro_export]
+    macro_rules! hessian_slice_tests {
+        ($t:ty) => {
+            $crate::hessian_test!(test_h_inputs, $t);
+            $crate::hessian_test!(test_h_square, $t);
+            $crate::hessian_test!(test_h_sphere, $t);
+            $crate::hessian_test!(test_h_finite_differences, $t);
+            $crate::hessian_test!(test_h_var, $t);
+            $crate::hessian_test!(test_h_custom, $t);
+            $crate::hessian_test!(test_h_noise, $t);
+        };
+    }
+}
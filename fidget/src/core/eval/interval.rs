@@ -1,8 +1,14 @@
 //! Interval evaluation
-use crate::eval::{
-    tracing::{TracingEval, TracingEvalData, TracingEvaluator},
-    types::Interval,
-    EvaluatorStorage, Family,
+use crate::{
+    eval::{
+        tracing::{
+            BorrowedTracingEvalResult, OwnedTracingEvalResult, TracingEval,
+            TracingEvalData, TracingEvalResult, TracingEvaluator,
+        },
+        types::Interval,
+        EvaluatorStorage, Family,
+    },
+    Error,
 };
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -21,6 +27,111 @@ pub type IntervalEvalData<F> = TracingEvalData<
 pub type IntervalEvalStorage<F> =
     <<F as Family>::IntervalEval as EvaluatorStorage<F>>::Storage;
 
+/// Per-axis sensitivity of an interval evaluation
+///
+/// This is a heuristic for adaptive subdivision: splitting a region along the
+/// axis which contributes most to the output's width usually tightens the
+/// result faster than splitting uniformly.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SplitHint {
+    /// Reduction in output width when each axis is collapsed to its midpoint
+    ///
+    /// Axes which remove `NaN` or infinite bounds from the output have an
+    /// infinite sensitivity; axes with zero-width inputs have a sensitivity of
+    /// zero.
+    pub sensitivity: [f32; 3],
+}
+
+impl SplitHint {
+    /// Returns the axis which contributes most to the output width
+    ///
+    /// Returns `None` if no axis has a positive sensitivity, e.g. because the
+    /// output doesn't depend on X, Y, or Z within the region.
+    pub fn axis(&self) -> Option<usize> {
+        (0..3)
+            .filter(|&i| self.sensitivity[i] > 0.0)
+            .max_by(|&a, &b| {
+                self.sensitivity[a].total_cmp(&self.sensitivity[b])
+            })
+    }
+}
+
+impl<E, F: Family> TracingEval<Interval, E, F>
+where
+    E: TracingEvaluator<Interval, F> + EvaluatorStorage<F>,
+{
+    /// Evaluates using (and modifying) the given workspace, also returning a
+    /// [`SplitHint`]
+    ///
+    /// The hint costs one extra evaluation per axis with a non-zero width.
+    /// The returned trace (if any) is from the evaluation over the full
+    /// region, as in [`eval_with`](TracingEval::eval_with).
+    #[allow(clippy::type_complexity)]
+    pub fn eval_with_hint<'a, J: Into<Interval>>(
+        &self,
+        x: J,
+        y: J,
+        z: J,
+        vars: &[f32],
+        data: &'a mut TracingEvalData<E::Data, F>,
+    ) -> Result<
+        (
+            Interval,
+            Option<BorrowedTracingEvalResult<'a, Interval, F>>,
+            SplitHint,
+        ),
+        Error,
+    > {
+        // Evaluate the collapsed regions first, because each evaluation
+        // overwrites the choices in `data`
+        let inputs = [x.into(), y.into(), z.into()];
+        let mut collapsed = [None; 3];
+        for (i, c) in collapsed.iter_mut().enumerate() {
+            if inputs[i].width() > 0.0 {
+                let mut v = inputs;
+                v[i] = inputs[i].midpoint().into();
+                let [x, y, z] = v;
+                *c = Some(self.eval_with(x, y, z, vars, data)?.0);
+            }
+        }
+        let [x, y, z] = inputs;
+        let (out, r) = self.eval_with(x, y, z, vars, data)?;
+        let sensitivity = collapsed.map(|c| match c {
+            None => 0.0,
+            Some(c) if c.has_nan() => 0.0,
+            Some(_) if out.has_nan() => f32::INFINITY,
+            // `inf - inf` is NaN, which becomes 0
+            Some(c) => (out.width() - c.width()).max(0.0),
+        });
+        Ok((out, r, SplitHint { sensitivity }))
+    }
+
+    /// Evaluates, allocating scratch memory, and also returns a [`SplitHint`]
+    ///
+    /// See [`eval_with_hint`](Self::eval_with_hint) for details.
+    #[allow(clippy::type_complexity)]
+    pub fn eval_hint<J: Into<Interval>>(
+        &self,
+        x: J,
+        y: J,
+        z: J,
+        vars: &[f32],
+    ) -> Result<
+        (
+            Interval,
+            Option<OwnedTracingEvalResult<Interval, F>>,
+            SplitHint,
+        ),
+        Error,
+    > {
+        let mut data = Default::default();
+        let (out, r, hint) = self.eval_with_hint(x, y, z, vars, &mut data)?;
+        let r = r
+            .map(|r| TracingEvalResult::new(r.choices().to_vec(), self.tape()));
+        Ok((out, r, hint))
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -45,7 +156,7 @@ pub mod eval_tests {
     use super::*;
     use crate::{
         context::Context,
-        eval::{Choice, Vars},
+        eval::{Choice, NanPolicy, SignConvention, Vars},
     };
 
     pub fn test_interval<I: Family>() {
@@ -102,7 +213,7 @@ pub mod eval_tests {
         assert!(nanan.upper().is_nan());
 
         let (v, _) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
@@ -123,7 +234,7 @@ pub mod eval_tests {
         assert_eq!(eval.eval_x([-6.0, 1.0]), [0.0, 36.0].into());
 
         let (v, _) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
@@ -144,7 +255,7 @@ pub mod eval_tests {
         assert_eq!(eval.eval_x([-6.0, 1.0]), [-1.0, 6.0].into());
 
         let (v, _) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
@@ -171,18 +282,43 @@ pub mod eval_tests {
         );
 
         let (v, _) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
 
         let (v, _) = eval
-            .eval([0.0, 1.0], [std::f32::NAN; 2], [0.0; 2], &[])
+            .eval([0.0, 1.0], [core::f32::NAN; 2], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
     }
 
+    pub fn test_i_fma<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let mul = ctx.mul(x, y).unwrap();
+        let fma = ctx.add(mul, z).unwrap();
+
+        let tape = ctx.get_tape::<I>(fma).unwrap();
+        let eval = tape.new_interval_evaluator();
+        let (a, b, c) = ([-3.0, -1.0], [-2.0, 6.0], [0.5, 1.0]);
+        let (v, _) = eval.eval(a, b, c, &[]).unwrap();
+        assert_eq!(v, [-17.5, 7.0].into());
+
+        // Both the product and the sum are widened in conservative mode
+        let tape = tape.with_conservative_intervals(true);
+        let eval = tape.new_interval_evaluator();
+        let (a, b, c) = ([0.1, 0.3], [1.7, 2.9], [0.7, 1.3]);
+        let (v, _) = eval.eval(a, b, c, &[]).unwrap();
+        let expected = ((Interval::from(a) * Interval::from(b)).widen()
+            + Interval::from(c))
+        .widen();
+        assert_eq!(v, expected);
+    }
+
     pub fn test_i_mul_imm<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -253,6 +389,224 @@ pub mod eval_tests {
         assert_eq!(eval.eval_x([1.0, 2.0]), [0.5, 1.0].into());
     }
 
+    pub fn test_i_trig<I: Family>() {
+        use core::f32::consts::PI;
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let s = ctx.sin(x).unwrap();
+        let tape = ctx.get_tape::<I>(s).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x([0.0, 1.0]), [0.0, 1f32.sin()].into());
+        assert_eq!(eval.eval_x([0.0, 2.0]), [0.0, 1.0].into());
+        assert_eq!(eval.eval_x([3.0, 5.0]), [-1.0, 3f32.sin()].into());
+        assert_eq!(eval.eval_x([-10.0, 10.0]), [-1.0, 1.0].into());
+        assert_eq!(eval.eval_x([0.0, f32::INFINITY]), [-1.0, 1.0].into());
+        let (v, _) =
+            eval.eval([f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[]).unwrap();
+        assert!(v.lower().is_nan());
+        assert!(v.upper().is_nan());
+
+        let c = ctx.cos(x).unwrap();
+        let tape = ctx.get_tape::<I>(c).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x([-1.0, 1.0]), [1f32.cos(), 1.0].into());
+        assert_eq!(eval.eval_x([0.5, 1.0]), [1f32.cos(), 0.5f32.cos()].into());
+        assert_eq!(eval.eval_x([2.0, 4.0]), [-1.0, 2f32.cos()].into());
+        let out = eval.eval_x([2.0 * PI - 1.0, 2.0 * PI + 0.5]);
+        assert_eq!(out.upper(), 1.0);
+    }
+
+    pub fn test_i_atan2<I: Family>() {
+        use core::f32::consts::PI;
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.atan2(y, x).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+        let eval = tape.new_interval_evaluator();
+
+        // First quadrant
+        let out = eval.eval_xy([1.0, 2.0], [1.0, 2.0]);
+        assert_eq!(out, [1f32.atan2(2.0), 2f32.atan2(1.0)].into());
+
+        // Straddling the positive X axis
+        let out = eval.eval_xy([1.0, 2.0], [-1.0, 1.0]);
+        assert_eq!(out, [(-1f32).atan2(1.0), 1f32.atan2(1.0)].into());
+
+        // Upper half-plane, straddling the Y axis
+        let out = eval.eval_xy([-1.0, 1.0], [1.0, 2.0]);
+        assert_eq!(out, [1f32.atan2(1.0), 1f32.atan2(-1.0)].into());
+
+        // Straddling the branch cut along the negative X axis
+        let out = eval.eval_xy([-2.0, -1.0], [-1.0, 1.0]);
+        assert_eq!(out, [-PI, PI].into());
+
+        // Containing the origin
+        let out = eval.eval_xy([-1.0, 1.0], [-1.0, 1.0]);
+        assert_eq!(out, [-PI, PI].into());
+
+        let (v, _) =
+            eval.eval([f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[]).unwrap();
+        assert!(v.lower().is_nan());
+        assert!(v.upper().is_nan());
+    }
+
+    pub fn test_i_hypot<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let h = ctx.hypot(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(h).unwrap();
+        let eval = tape.new_interval_evaluator();
+
+        let out = eval.eval_xy([3.0, 5.0], [4.0, 12.0]);
+        assert_eq!(out, [5.0, 13.0].into());
+
+        let out = eval.eval_xy([-3.0, 1.0], [-4.0, -3.0]);
+        assert_eq!(out, [3.0, 5.0].into());
+
+        let out = eval.eval_xy([-1.0, 1.0], [-1.0, 1.0]);
+        assert_eq!(out, [0.0, 2f32.sqrt()].into());
+
+        let (v, _) =
+            eval.eval([0.0, 1.0], [f32::NAN; 2], [0.0; 2], &[]).unwrap();
+        assert!(v.lower().is_nan());
+        assert!(v.upper().is_nan());
+    }
+
+    pub fn test_i_mod<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let m = ctx.modulo(x, 2.0).unwrap();
+        let tape = ctx.get_tape::<I>(m).unwrap();
+        let eval = tape.new_interval_evaluator();
+
+        // Within a single period, the input is shifted
+        assert_eq!(eval.eval_x([0.5, 1.5]), [0.5, 1.5].into());
+        assert_eq!(eval.eval_x([2.5, 3.0]), [0.5, 1.0].into());
+        assert_eq!(eval.eval_x([-1.5, -0.5]), [0.5, 1.5].into());
+
+        // Crossing a period boundary covers the whole range
+        assert_eq!(eval.eval_x([1.0, 3.0]), [0.0, 2.0].into());
+
+        let m = ctx.modulo(x, -2.0).unwrap();
+        let tape = ctx.get_tape::<I>(m).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x([0.5, 1.5]), [-1.5, -0.5].into());
+        assert_eq!(eval.eval_x([1.0, 3.0]), [-2.0, 0.0].into());
+
+        let m = ctx.modulo(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(m).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_xy([0.25, 0.5], [1.0, 2.0]), [0.0, 2.0].into());
+
+        // A divisor which contains zero gives NaN
+        let v = eval.eval_xy([0.25, 0.5], [-1.0, 2.0]);
+        assert!(v.lower().is_nan());
+        assert!(v.upper().is_nan());
+    }
+
+    pub fn test_i_noise<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let n = ctx.noise3(x, y, z, 0).unwrap();
+        let tape = ctx.get_tape::<I>(n).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(
+            eval.eval([0.0, 1.0], [2.0, 3.0], [-1.0, 5.0], &[])
+                .unwrap()
+                .0,
+            [-1.0, 1.0].into()
+        );
+
+        let v = eval
+            .eval([0.0, f32::INFINITY], [0.0, 1.0], [0.0, 1.0], &[])
+            .unwrap()
+            .0;
+        assert!(v.lower().is_nan());
+        assert!(v.upper().is_nan());
+    }
+
+    pub fn test_i_clamp<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let c = ctx.clamp(x, y, z).unwrap();
+        let tape = ctx.get_tape::<I>(c).unwrap();
+        let eval = tape.new_interval_evaluator();
+
+        let v = |x: [f32; 2], y: [f32; 2], z: [f32; 2]| {
+            eval.eval(x, y, z, &[]).unwrap().0
+        };
+        assert_eq!(
+            v([-2.0, 0.5], [-1.0, -1.0], [1.0, 1.0]),
+            [-1.0, 0.5].into()
+        );
+        assert_eq!(v([-2.0, 3.0], [-1.0, 0.0], [1.0, 2.0]), [-1.0, 2.0].into());
+        assert_eq!(v([0.0, 0.5], [-1.0, 0.0], [1.0, 2.0]), [0.0, 0.5].into());
+        assert_eq!(v([5.0, 6.0], [-1.0, 0.0], [1.0, 2.0]), [1.0, 2.0].into());
+
+        let out = v([0.0, 1.0], [f32::NAN; 2], [1.0, 2.0]);
+        assert!(out.lower().is_nan());
+        assert!(out.upper().is_nan());
+
+        // Clamping never produces a simplified tape
+        let (_, simplify) =
+            eval.eval([-2.0, 3.0], [0.0; 2], [1.0; 2], &[]).unwrap();
+        assert!(simplify.is_none());
+    }
+
+    pub fn test_i_min_max_nc<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.min_nc(x, y).unwrap();
+        let b = ctx.max_nc(x, 1.0).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        assert_eq!(tape.choice_count(), 0);
+        let eval = tape.new_interval_evaluator();
+
+        // Even when one branch is always taken, there's nothing to simplify
+        let (v, simplify) =
+            eval.eval([0.0, 0.5], [2.0, 3.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(v, [1.0, 1.5].into());
+        assert!(simplify.is_none());
+
+        let v = eval.eval([-1.0, 2.0], [0.0, 1.0], [0.0; 2], &[]).unwrap().0;
+        assert_eq!(v, [0.0, 3.0].into());
+
+        let v = eval
+            .eval([0.0, 1.0], [f32::NAN; 2], [0.0; 2], &[])
+            .unwrap()
+            .0;
+        assert!(v.lower().is_nan());
+        assert!(v.upper().is_nan());
+    }
+
+    pub fn test_i_custom<I: Family>() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op = alloc::sync::Arc::new(SquareSub);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.custom(op.clone(), x, y).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_xy([-1.0, 2.0], [0.0, 1.0]), [-1.0, 4.0].into());
+        assert_eq!(eval.eval_xy([1.0, 2.0], [-1.0, 0.0]), [1.0, 5.0].into());
+
+        let a = ctx.custom(op, 2.0, x).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x([1.0, 3.0]), [1.0, 3.0].into());
+    }
+
     pub fn test_i_div<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -286,13 +640,13 @@ pub mod eval_tests {
         assert_eq!(out, [-2.0, 8.0].into());
 
         let (v, _) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
 
         let (v, _) = eval
-            .eval([0.0, 1.0], [std::f32::NAN; 2], [0.0; 2], &[])
+            .eval([0.0, 1.0], [core::f32::NAN; 2], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
@@ -322,20 +676,53 @@ pub mod eval_tests {
         assert_eq!(data.unwrap().choices(), &[Choice::Right]);
 
         let (v, data) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
         assert!(data.is_none());
 
         let (v, data) = eval
-            .eval([0.0, 1.0], [std::f32::NAN; 2], [0.0; 2], &[])
+            .eval([0.0, 1.0], [core::f32::NAN; 2], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
         assert!(data.is_none());
     }
 
+    pub fn test_i_reuse<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(min, 0.5).unwrap();
+
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        let eval = tape.new_interval_evaluator();
+        let mut data = Default::default();
+
+        // Stale choices from a previous evaluation must be overwritten
+        let (r, trace) = eval
+            .eval_with([0.0, 1.0], [2.0, 3.0], [0.0; 2], &[], &mut data)
+            .unwrap();
+        assert_eq!(r, [0.5, 1.0].into());
+        assert_eq!(trace.unwrap().choices(), &[Choice::Left, Choice::Both]);
+        assert_eq!(data.epoch(), 1);
+
+        let (r, trace) = eval
+            .eval_with([2.0, 3.0], [-1.0, 0.0], [0.0; 2], &[], &mut data)
+            .unwrap();
+        assert_eq!(r, [0.5, 0.5].into());
+        assert_eq!(trace.unwrap().choices(), &[Choice::Right, Choice::Right]);
+
+        let (r, trace) = eval
+            .eval_with([0.0, 1.0], [0.0, 1.0], [0.0; 2], &[], &mut data)
+            .unwrap();
+        assert_eq!(r, [0.5, 1.0].into());
+        assert!(trace.is_none());
+        assert_eq!(data.epoch(), 3);
+    }
+
     pub fn test_i_min_imm<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -381,14 +768,14 @@ pub mod eval_tests {
         assert_eq!(data.unwrap().choices(), &[Choice::Left]);
 
         let (v, data) = eval
-            .eval([std::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
+            .eval([core::f32::NAN; 2], [0.0, 1.0], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
         assert!(data.is_none());
 
         let (v, data) = eval
-            .eval([0.0, 1.0], [std::f32::NAN; 2], [0.0; 2], &[])
+            .eval([0.0, 1.0], [core::f32::NAN; 2], [0.0; 2], &[])
             .unwrap();
         assert!(v.lower().is_nan());
         assert!(v.upper().is_nan());
@@ -455,6 +842,61 @@ pub mod eval_tests {
         assert_eq!(data.unwrap().choices(), &[Choice::Left]);
     }
 
+    pub fn test_i_min_many<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let mut args = vec![x, y, z];
+        for i in 1..6 {
+            args.push(ctx.add(x, i as f64 * 10.0).unwrap());
+            args.push(ctx.add(y, i as f64 * 10.0).unwrap());
+        }
+        let min = ctx.min_many(args).unwrap();
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        assert_eq!(tape.choice_count(), 12);
+        let eval = tape.new_interval_evaluator();
+
+        // Ambiguous arguments stay in the group, which is rebuilt with a
+        // single choice per remaining argument
+        let (out, data) =
+            eval.eval([0.0, 1.0], [0.5, 1.5], [5.0, 6.0], &[]).unwrap();
+        assert_eq!(out, [0.0, 1.0].into());
+        let simple = data.unwrap().simplify().unwrap();
+        assert_eq!(simple.choice_count(), 1);
+        assert!(simple.len() < tape.len());
+        let eval = simple.new_interval_evaluator();
+        let (out, data) =
+            eval.eval([0.0, 1.0], [2.0, 3.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(out, [0.0, 1.0].into());
+        assert_eq!(data.unwrap().choices(), &[Choice::Left]);
+
+        // If only one argument is live, the group disappears entirely
+        let eval = tape.new_interval_evaluator();
+        let (out, data) = eval
+            .eval([10.0, 11.0], [5.0, 6.0], [0.0, 1.0], &[])
+            .unwrap();
+        assert_eq!(out, [0.0, 1.0].into());
+        let simple = data.unwrap().simplify().unwrap();
+        assert_eq!(simple.choice_count(), 0);
+        let eval = simple.new_interval_evaluator();
+        let (out, data) = eval
+            .eval([-5.0, 5.0], [-5.0, 5.0], [2.0, 3.0], &[])
+            .unwrap();
+        assert_eq!(out, [2.0, 3.0].into());
+        assert!(data.is_none());
+
+        let max = ctx.max_many([x, y, z]).unwrap();
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        assert_eq!(tape.choice_count(), 2);
+        let eval = tape.new_interval_evaluator();
+        let (out, data) =
+            eval.eval([0.0, 1.0], [2.0, 3.0], [2.5, 4.0], &[]).unwrap();
+        assert_eq!(out, [2.5, 4.0].into());
+        let simple = data.unwrap().simplify().unwrap();
+        assert_eq!(simple.choice_count(), 1);
+    }
+
     pub fn test_i_max_imm<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -523,6 +965,170 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_i_conservative<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let sum = ctx.add(x, 0.1).unwrap();
+
+        // 1.1 isn't representable, so round-to-nearest drops one bound
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        assert!(!tape.conservative_intervals());
+        let plain = tape.new_interval_evaluator().eval_x([1.0, 1.0]);
+        let tape = tape.with_conservative_intervals(true);
+        assert!(tape.conservative_intervals());
+        let eval = tape.new_interval_evaluator();
+        let out = eval.eval_x([1.0, 1.0]);
+        assert!(out.lower() < plain.lower());
+        assert!(out.upper() > plain.upper());
+        assert!((out.lower() as f64) < 1.1 && (out.upper() as f64) > 1.1);
+
+        // Infinite bounds are left alone
+        let out = eval.eval_x([f32::INFINITY, f32::INFINITY]);
+        assert_eq!(out, [f32::INFINITY, f32::INFINITY].into());
+        let out = eval.eval_x([f32::NEG_INFINITY, 0.0]);
+        assert_eq!(out.lower(), f32::NEG_INFINITY);
+
+        // Widening must match the reference implementation exactly
+        let mul = ctx.mul(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(mul).unwrap();
+        let eval = tape.with_conservative_intervals(true);
+        let eval = eval.new_interval_evaluator();
+        let a = Interval::new(-1.5, 2.0);
+        let b = Interval::new(3.0, 7.25);
+        assert_eq!(eval.eval_xy(a, b), (a * b).widen());
+
+        // Exact operations are not widened
+        let abs = ctx.abs(x).unwrap();
+        let neg = ctx.neg(abs).unwrap();
+        let min = ctx.min(neg, y).unwrap();
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        let tape = tape.with_conservative_intervals(true);
+        let eval = tape.new_interval_evaluator();
+        let (out, data) =
+            eval.eval([-1.0, 2.0], [5.0, 6.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(out, [-2.0, 0.0].into());
+
+        // Simplified tapes inherit the setting
+        let simple = data.unwrap().simplify().unwrap();
+        assert!(simple.conservative_intervals());
+    }
+
+    pub fn test_i_nan_policy<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(x, y).unwrap();
+        let min_imm = ctx.min(x, 1.0).unwrap();
+        let nan = [f32::NAN; 2];
+        let inf = f32::INFINITY;
+
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        let tape = tape.with_nan_policy(NanPolicy::Empty);
+        let eval = tape.new_interval_evaluator();
+        let (r, data) = eval.eval(nan, [0.0, 1.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [-inf, 1.0].into());
+        assert!(data.is_none());
+        let (r, _) = eval.eval([0.0, 1.0], nan, [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [-inf, 1.0].into());
+        let (r, _) = eval.eval(nan, nan, [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [-inf, inf].into());
+        let (r, data) =
+            eval.eval([0.0, 1.0], [2.0, 3.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [0.0, 1.0].into());
+        assert_eq!(data.unwrap().choices(), &[Choice::Left]);
+
+        // Simplified tapes inherit the setting
+        let (_, data) =
+            eval.eval([0.0, 1.0], [2.0, 3.0], [0.0; 2], &[]).unwrap();
+        let simple = data.unwrap().simplify().unwrap();
+        assert_eq!(simple.nan_policy(), NanPolicy::Empty);
+
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        let tape = tape.with_nan_policy(NanPolicy::Empty);
+        let eval = tape.new_interval_evaluator();
+        let (r, data) = eval.eval(nan, [0.0, 1.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [0.0, inf].into());
+        assert!(data.is_none());
+        let (r, _) = eval.eval([0.0, 1.0], nan, [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [0.0, inf].into());
+
+        let tape = ctx.get_tape::<I>(min_imm).unwrap();
+        let tape = tape.with_nan_policy(NanPolicy::Empty);
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x(nan), [-inf, 1.0].into());
+        assert_eq!(eval.eval_x([2.0, 3.0]), [1.0, 1.0].into());
+    }
+
+    pub fn test_i_sign_convention<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let min = ctx.min(x, 1.0).unwrap();
+
+        let tape = ctx.get_tape::<I>(min).unwrap().invert();
+        assert_eq!(tape.sign_convention(), SignConvention::PositiveInside);
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x([2.0, 3.0]), [-1.0, -1.0].into());
+        let (r, data) =
+            eval.eval([-1.0, 0.5], [0.0; 2], [0.0; 2], &[]).unwrap();
+        assert_eq!(r, [-0.5, 1.0].into());
+
+        // Simplified tapes inherit the setting
+        let simple = data.unwrap().simplify().unwrap();
+        assert_eq!(simple.sign_convention(), SignConvention::PositiveInside);
+        let eval = simple.new_interval_evaluator();
+        assert_eq!(eval.eval_x([-1.0, 0.5]), [-0.5, 1.0].into());
+
+        // Inverting twice restores the original convention
+        let tape = tape.invert();
+        assert_eq!(tape.sign_convention(), SignConvention::NegativeInside);
+        let eval = tape.new_interval_evaluator();
+        assert_eq!(eval.eval_x([2.0, 3.0]), [1.0, 1.0].into());
+    }
+
+    pub fn test_i_split_hint<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x2 = ctx.mul(x, 4.0).unwrap();
+        let sum = ctx.add(x2, y).unwrap();
+        let sum = ctx.max(sum, -10.0).unwrap();
+
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_interval_evaluator();
+        let (out, r, hint) = eval
+            .eval_hint([0.0, 1.0], [0.0, 2.0], [0.0, 8.0], &[])
+            .unwrap();
+        assert_eq!(out, [0.0, 6.0].into());
+        assert_eq!(hint.sensitivity, [4.0, 2.0, 0.0]);
+        assert_eq!(hint.axis(), Some(0));
+
+        // The trace is from the full region, not a collapsed one
+        assert_eq!(r.unwrap().choices(), &[Choice::Left]);
+
+        // Zero-width inputs aren't evaluated
+        let (_, _, hint) =
+            eval.eval_hint([0.5; 2], [0.0, 2.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(hint.sensitivity, [0.0, 2.0, 0.0]);
+        assert_eq!(hint.axis(), Some(1));
+        let (_, _, hint) =
+            eval.eval_hint([0.5; 2], [1.0; 2], [0.0, 1.0], &[]).unwrap();
+        assert_eq!(hint.axis(), None);
+
+        // Collapsing an axis which produces NaN is infinitely useful
+        let s = ctx.recip(x).unwrap();
+        let s = ctx.add(s, y).unwrap();
+        let tape = ctx.get_tape::<I>(s).unwrap();
+        let eval = tape.new_interval_evaluator();
+        let (out, _, hint) = eval
+            .eval_hint([-1.0, 3.0], [0.0, 1.0], [0.0; 2], &[])
+            .unwrap();
+        assert!(out.has_nan());
+        assert_eq!(hint.sensitivity, [f32::INFINITY, 0.0, 0.0]);
+        assert_eq!(hint.axis(), Some(0));
+    }
+
     #[macro_export]
     macro_rules! interval_test {
         ($i:ident, $t:ty) => {
@@ -540,19 +1146,34 @@ pub mod eval_tests {
             $crate::interval_test!(test_i_abs, $t);
             $crate::interval_test!(test_i_sqrt, $t);
             $crate::interval_test!(test_i_square, $t);
+            $crate::interval_test!(test_i_trig, $t);
             $crate::interval_test!(test_i_neg, $t);
             $crate::interval_test!(test_i_mul, $t);
+            $crate::interval_test!(test_i_fma, $t);
             $crate::interval_test!(test_i_mul_imm, $t);
             $crate::interval_test!(test_i_sub, $t);
             $crate::interval_test!(test_i_sub_imm, $t);
             $crate::interval_test!(test_i_recip, $t);
             $crate::interval_test!(test_i_div, $t);
+            $crate::interval_test!(test_i_atan2, $t);
+            $crate::interval_test!(test_i_hypot, $t);
+            $crate::interval_test!(test_i_mod, $t);
+            $crate::interval_test!(test_i_custom, $t);
+            $crate::interval_test!(test_i_noise, $t);
+            $crate::interval_test!(test_i_clamp, $t);
+            $crate::interval_test!(test_i_min_max_nc, $t);
             $crate::interval_test!(test_i_min, $t);
             $crate::interval_test!(test_i_min_imm, $t);
+            $crate::interval_test!(test_i_reuse, $t);
             $crate::interval_test!(test_i_max, $t);
             $crate::interval_test!(test_i_max_imm, $t);
             $crate::interval_test!(test_i_simplify, $t);
+            $crate::interval_test!(test_i_min_many, $t);
             $crate::interval_test!(test_i_var, $t);
+            $crate::interval_test!(test_i_conservative, $t);
+            $crate::interval_test!(test_i_nan_policy, $t);
+            $crate::interval_test!(test_i_sign_convention, $t);
+            $crate::interval_test!(test_i_split_hint, $t);
         };
     }
 }
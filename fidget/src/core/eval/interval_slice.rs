@@ -0,0 +1,523 @@
+//! Interval slice evaluation (i.e. `&[Interval]`)
+//!
+//! An interval slice evaluator classifies many regions in a single call, e.g.
+//! the 8 children of an octree cell during meshing.  Like an
+//! [`IntervalEval`](crate::eval::IntervalEval), it records a trace of `min` and
+//! `max` choices for each region, which can be used to simplify the tape.
+//!
+//! ```
+//! use fidget::{context::Context, eval::types::Interval, vm};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let min = ctx.min(x, 1.0)?;
+//! let tape = ctx.get_tape::<vm::Eval>(min)?;
+//!
+//! let eval = tape.new_interval_slice_evaluator();
+//! let xs = [Interval::new(0.0, 0.5), Interval::new(0.0, 2.0)];
+//! let zero = [Interval::from(0.0); 2];
+//! let mut data = Default::default();
+//! let r = eval.eval_with(&xs, &zero, &zero, &[], &mut data)?;
+//! assert_eq!(r.values(), [[0.0, 0.5].into(), [0.0, 1.0].into()]);
+//!
+//! // The first region only takes the left branch of the `min`
+//! assert_eq!(r.trace(0).unwrap().simplify()?.len(), 1);
+//! assert!(r.trace(1).is_none());
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{
+        bulk::BulkEvaluatorData,
+        tracing::{
+            BorrowedTracingEvalResult, TracingEvalResult, TracingEvaluator,
+            TracingEvaluatorData,
+        },
+        types::Interval,
+        Choice, EvaluatorStorage, Family, Tape,
+    },
+    Error,
+};
+use alloc::{vec, vec::Vec};
+
+/// Trait for evaluating many intervals in a single call
+///
+/// It's uncommon to use this trait outside the library itself; it's an
+/// abstraction to reduce code duplication, and is public because it's used as a
+/// constraint on other public APIs.
+pub trait IntervalSliceEvaluator<F> {
+    /// Scratch (mutable) data used during evaluation
+    type Data: BulkEvaluatorData<F> + Default + Send;
+
+    /// Evaluates many intervals, writing the results into `out` and the trace
+    /// of each evaluation into `choices`
+    ///
+    /// `choices` is divided into one chunk per interval, each of which has one
+    /// item per choice in the tape.  Like a
+    /// [`TracingEvaluator`](crate::eval::tracing::TracingEvaluator), every item
+    /// in `choices` must be assigned during evaluation.
+    ///
+    /// # Panics
+    /// This function may assume that the `x`, `y`, `z`, and `out` slices are of
+    /// equal length, that `choices` is sized as described above, and that
+    /// `vars` is correctly sized for the number of variables in the tape.
+    #[allow(clippy::too_many_arguments)]
+    fn eval_with(
+        &self,
+        x: &[Interval],
+        y: &[Interval],
+        z: &[Interval],
+        vars: &[f32],
+        out: &mut [Interval],
+        choices: &mut [Choice],
+        data: &mut Self::Data,
+    );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Interval slice evaluator which calls a tracing interval evaluator once per
+/// item
+///
+/// This is used by evaluator families which don't have a specialized interval
+/// slice evaluator (e.g. the JIT, which calls its compiled interval function).
+#[derive(Clone)]
+pub struct TracingSliceEval<E>(E);
+
+impl<E: EvaluatorStorage<F>, F> EvaluatorStorage<F> for TracingSliceEval<E> {
+    type Storage = E::Storage;
+    fn new_with_storage(tape: &Tape<F>, storage: Self::Storage) -> Self {
+        Self(E::new_with_storage(tape, storage))
+    }
+    fn rebind(&self, tape: &Tape<F>) -> Self {
+        Self(self.0.rebind(tape))
+    }
+    fn take(self) -> Option<Self::Storage> {
+        self.0.take()
+    }
+}
+
+/// Scratch data for a [`TracingSliceEval`]
+#[derive(Default)]
+pub struct TracingSliceData<D>(D);
+
+impl<D: TracingEvaluatorData<F>, F> BulkEvaluatorData<F>
+    for TracingSliceData<D>
+{
+    fn prepare(&mut self, tape: &Tape<F>, _size: usize) {
+        self.0.prepare(tape)
+    }
+}
+
+impl<E: TracingEvaluator<Interval, F>, F> IntervalSliceEvaluator<F>
+    for TracingSliceEval<E>
+{
+    type Data = TracingSliceData<E::Data>;
+
+    fn eval_with(
+        &self,
+        x: &[Interval],
+        y: &[Interval],
+        z: &[Interval],
+        vars: &[f32],
+        out: &mut [Interval],
+        choices: &mut [Choice],
+        data: &mut Self::Data,
+    ) {
+        let n = choices.len().checked_div(out.len()).unwrap_or(0);
+        for (i, o) in out.iter_mut().enumerate() {
+            (*o, _) = self.0.eval_with(
+                x[i],
+                y[i],
+                z[i],
+                vars,
+                &mut choices[i * n..(i + 1) * n],
+                &mut data.0,
+            );
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Evaluator for many intervals, capturing a trace for each one
+///
+/// The internal `tape` is planned with
+/// [`F::REG_LIMIT`](crate::eval::Family::REG_LIMIT) registers.
+#[derive(Clone)]
+pub struct IntervalSliceEval<F: Family> {
+    eval: F::IntervalSliceEval,
+    tape: Tape<F>,
+}
+
+/// Immutable data used by an interval slice evaluator from a particular family
+/// `F`
+pub type IntervalSliceEvalStorage<F> =
+    <<F as Family>::IntervalSliceEval as EvaluatorStorage<F>>::Storage;
+
+impl<F: Family> IntervalSliceEval<F> {
+    /// Builds a new evaluator for the given tape, allocating new storage
+    pub fn new(tape: &Tape<F>) -> Self {
+        Self::new_with_storage(tape, Default::default())
+    }
+
+    /// Returns a copy of the inner tape
+    pub fn tape(&self) -> Tape<F> {
+        self.tape.clone()
+    }
+
+    /// Builds a new evaluator for the given tape, reusing the given storage
+    pub fn new_with_storage(
+        tape: &Tape<F>,
+        storage: IntervalSliceEvalStorage<F>,
+    ) -> Self {
+        Self {
+            eval: F::IntervalSliceEval::new_with_storage(tape, storage),
+            tape: tape.clone(),
+        }
+    }
+
+    /// Consumes the evaluator, returning the inner storage type for reuse
+    pub fn take(self) -> Option<IntervalSliceEvalStorage<F>> {
+        self.eval.take()
+    }
+
+    /// Evaluates many intervals, using (and modifying) the given workspace
+    ///
+    /// Returns a handle to the results, which borrows from `data`.
+    pub fn eval_with<'a>(
+        &self,
+        x: &[Interval],
+        y: &[Interval],
+        z: &[Interval],
+        vars: &[f32],
+        data: &'a mut IntervalSliceEvalData<F>,
+    ) -> Result<IntervalSliceResult<'a, F>, Error> {
+        if x.len() != y.len() || x.len() != z.len() {
+            return Err(Error::MismatchedSlices);
+        } else if vars.len() != self.tape.var_count() {
+            return Err(Error::BadVarSlice(vars.len(), self.tape.var_count()));
+        }
+        let n = self.tape.choice_count();
+        data.out.resize(x.len(), f32::NAN.into());
+        data.choices.resize(x.len() * n, Choice::Unknown);
+        data.data.prepare(&self.tape, x.len());
+        self.eval.eval_with(
+            x,
+            y,
+            z,
+            vars,
+            &mut data.out,
+            &mut data.choices,
+            &mut data.data,
+        );
+        self.tape.sign_slice(&mut data.out);
+        Ok(IntervalSliceResult {
+            values: &data.out,
+            choices: &data.choices,
+            tape: self.tape.clone(),
+        })
+    }
+
+    /// Evaluates many intervals, returning a fresh `Vec<Interval>`
+    ///
+    /// This function performs allocation and discards the evaluation traces;
+    /// in a hot loop, consider using [`eval_with`](Self::eval_with) instead.
+    pub fn eval(
+        &self,
+        x: &[Interval],
+        y: &[Interval],
+        z: &[Interval],
+        vars: &[f32],
+    ) -> Result<Vec<Interval>, Error> {
+        let mut data = Default::default();
+        self.eval_with(x, y, z, vars, &mut data)?;
+        Ok(data.out)
+    }
+}
+
+/// Scratch data used by an interval slice evaluator from a particular family
+/// `F`
+pub struct IntervalSliceEvalData<F: Family> {
+    out: Vec<Interval>,
+    choices: Vec<Choice>,
+
+    /// Inner data
+    data: <F::IntervalSliceEval as IntervalSliceEvaluator<F>>::Data,
+}
+
+impl<F: Family> Default for IntervalSliceEvalData<F> {
+    fn default() -> Self {
+        Self {
+            out: vec![],
+            choices: vec![],
+            data: Default::default(),
+        }
+    }
+}
+
+/// Results from an interval slice evaluation
+///
+/// This borrows from an [`IntervalSliceEvalData`].
+pub struct IntervalSliceResult<'a, F> {
+    values: &'a [Interval],
+    choices: &'a [Choice],
+    tape: Tape<F>,
+}
+
+impl<'a, F: Family> IntervalSliceResult<'a, F> {
+    /// Returns the result of evaluating each interval
+    pub fn values(&self) -> &'a [Interval] {
+        self.values
+    }
+
+    /// Returns the choices made when evaluating the interval at index `i`
+    pub fn choices(&self, i: usize) -> &'a [Choice] {
+        let n = self.tape.choice_count();
+        &self.choices[i * n..(i + 1) * n]
+    }
+
+    /// Returns a handle to simplify the tape based on the interval at index
+    /// `i`, if simplification is possible
+    pub fn trace(
+        &self,
+        i: usize,
+    ) -> Option<BorrowedTracingEvalResult<'a, Interval, F>> {
+        let choices = self.choices(i);
+        if choices.iter().any(|c| *c != Choice::Both) {
+            Some(TracingEvalResult::new(choices, self.tape.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(any(test, feature = "eval-tests"))]
+pub mod eval_tests {
+    use super::*;
+    use crate::{context::Context, eval::NanPolicy};
+
+    /// Builds a set of intervals (including degenerate and `NaN` intervals)
+    fn intervals() -> [Vec<Interval>; 3] {
+        let mut xs = vec![];
+        let mut ys = vec![];
+        let mut zs = vec![];
+        let nan = Interval::from(f32::NAN);
+        for (i, (lo, hi)) in [
+            (-2.0, -1.0),
+            (-1.0, 1.0),
+            (0.0, 0.5),
+            (0.5, 3.0),
+            (1.0, 1.0),
+            (-0.25, 0.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            xs.push(Interval::new(lo, hi));
+            ys.push(Interval::new(-hi, 1.0 - lo * 0.5));
+            zs.push(Interval::new(i as f32 * 0.25 - 1.0, i as f32 * 0.5));
+        }
+        xs.push(nan);
+        ys.push(Interval::new(0.0, 1.0));
+        zs.push(Interval::new(-1.0, 0.0));
+        [xs, ys, zs]
+    }
+
+    /// Checks that slice evaluation matches single interval evaluation
+    fn check<I: Family>(tape: &Tape<I>) {
+        let [xs, ys, zs] = intervals();
+        let single = tape.new_interval_evaluator();
+        let eval = tape.new_interval_slice_evaluator();
+        let mut data = Default::default();
+        let r = eval.eval_with(&xs, &ys, &zs, &[], &mut data).unwrap();
+        assert_eq!(r.values().len(), xs.len());
+        for i in 0..xs.len() {
+            let (v, trace) = single.eval(xs[i], ys[i], zs[i], &[]).unwrap();
+            let s = r.values()[i];
+            assert!(
+                (s.lower() == v.lower() || (s.has_nan() && v.has_nan()))
+                    && (s.upper() == v.upper() || (s.has_nan() && v.has_nan())),
+                "mismatch at {i}: {s} != {v}"
+            );
+            assert_eq!(
+                trace.as_ref().map(|t| t.choices()),
+                r.trace(i).as_ref().map(|t| t.choices()),
+                "choice mismatch at {i}"
+            );
+        }
+    }
+
+    /// Builds a shape which uses most opcodes
+    fn shape(ctx: &mut Context) -> crate::context::Node {
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let r = ctx.hypot(x, y).unwrap();
+        let a = ctx.atan2(y, x).unwrap();
+        let s = ctx.sqrt(r).unwrap();
+        let q = ctx.square(z).unwrap();
+        let d = ctx.div(a, q).unwrap();
+        let m = ctx.mul(x, y).unwrap();
+        let fma = ctx.add(m, z).unwrap();
+        let neg = ctx.neg(fma).unwrap();
+        let abs = ctx.abs(neg).unwrap();
+        let recip = ctx.recip(abs).unwrap();
+        let min = ctx.min(s, d).unwrap();
+        let max = ctx.max(min, recip).unwrap();
+        let sub = ctx.sub(max, 0.5).unwrap();
+        let lo = ctx.min(sub, x).unwrap();
+        ctx.max(lo, -1.5).unwrap()
+    }
+
+    pub fn test_is_basic<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let sum = ctx.add(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_interval_slice_evaluator();
+
+        let out = eval
+            .eval(
+                &[[0.0, 1.0].into(), [2.0, 3.0].into()],
+                &[[1.0, 2.0].into(), [-1.0, 0.0].into()],
+                &[[0.0, 0.0].into(); 2],
+                &[],
+            )
+            .unwrap();
+        assert_eq!(out, [[1.0, 3.0].into(), [1.0, 3.0].into()]);
+
+        // Empty slices are allowed
+        assert!(eval.eval(&[], &[], &[], &[]).unwrap().is_empty());
+
+        // Mismatched slices are not
+        assert!(matches!(
+            eval.eval(&[[0.0, 1.0].into()], &[], &[], &[]),
+            Err(Error::MismatchedSlices)
+        ));
+        assert!(matches!(
+            eval.eval(&[], &[], &[], &[1.0]),
+            Err(Error::BadVarSlice(1, 0))
+        ));
+    }
+
+    pub fn test_is_simplify<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let min = ctx.min(x, 1.0).unwrap();
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        let eval = tape.new_interval_slice_evaluator();
+
+        let zero = [Interval::from(0.0); 3];
+        let mut data = Default::default();
+        let r = eval
+            .eval_with(
+                &[[0.0, 2.0].into(), [0.0, 0.5].into(), [1.5, 2.5].into()],
+                &zero,
+                &zero,
+                &[],
+                &mut data,
+            )
+            .unwrap();
+        assert_eq!(
+            r.values(),
+            [[0.0, 1.0].into(), [0.0, 0.5].into(), [1.0, 1.0].into()]
+        );
+        assert!(r.trace(0).is_none());
+        assert_eq!(r.choices(1), &[Choice::Left]);
+        assert_eq!(r.choices(2), &[Choice::Right]);
+
+        let left = r.trace(1).unwrap().simplify().unwrap();
+        assert_eq!(left.len(), 1);
+        let right = r.trace(2).unwrap().simplify().unwrap();
+        assert_eq!(right.len(), 1);
+        assert_eq!(
+            right.new_interval_evaluator().eval_x([5.0, 6.0]),
+            [1.0, 1.0].into()
+        );
+
+        // Choices are overwritten when the data is reused
+        let r = eval
+            .eval_with(
+                &[[0.0, 0.5].into()],
+                &zero[..1],
+                &zero[..1],
+                &[],
+                &mut data,
+            )
+            .unwrap();
+        assert_eq!(r.choices(0), &[Choice::Left]);
+    }
+
+    pub fn test_is_vars<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let v = ctx.var("v").unwrap();
+        let mul = ctx.mul(x, v).unwrap();
+        let tape = ctx.get_tape::<I>(mul).unwrap();
+        let eval = tape.new_interval_slice_evaluator();
+        let out = eval
+            .eval(
+                &[[1.0, 2.0].into(), [-1.0, 3.0].into()],
+                &[[0.0, 0.0].into(); 2],
+                &[[0.0, 0.0].into(); 2],
+                &[2.0],
+            )
+            .unwrap();
+        assert_eq!(out, [[2.0, 4.0].into(), [-2.0, 6.0].into()]);
+    }
+
+    pub fn test_is_agreement<I: Family>() {
+        let mut ctx = Context::new();
+        let root = shape(&mut ctx);
+        let tape = ctx.get_tape::<I>(root).unwrap();
+        check(&tape);
+        check(&tape.clone().with_conservative_intervals(true));
+        check(&tape.with_nan_policy(NanPolicy::Empty));
+    }
+
+    pub fn test_is_give_take<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let tape_x = ctx.get_tape::<I>(x).unwrap();
+        let tape_y = ctx.get_tape::<I>(y).unwrap();
+
+        let xs = [[0.0, 1.0].into(), [2.0, 3.0].into()];
+        let ys = [[4.0, 5.0].into(), [6.0, 7.0].into()];
+        let eval = IntervalSliceEval::<I>::new(&tape_y);
+        let mut t = eval.take().unwrap();
+        for _ in 0..100 {
+            let eval = IntervalSliceEval::<I>::new_with_storage(&tape_x, t);
+            assert_eq!(eval.eval(&xs, &ys, &xs, &[]).unwrap(), xs);
+            t = eval.take().unwrap();
+
+            let eval = IntervalSliceEval::<I>::new_with_storage(&tape_y, t);
+            assert_eq!(eval.eval(&xs, &ys, &xs, &[]).unwrap(), ys);
+            t = eval.take().unwrap();
+        }
+    }
+
+    #[macro_export]
+    macro_rules! interval_slice_test {
+        ($i:ident, $t:ty) => {
+            #[test]
+            fn $i() {
+                $crate::eval::interval_slice::eval_tests::$i::<$t>()
+            }
+        };
+    }
+
+    #[macro_export]
+    macro_rules! interval_slice_tests {
+        ($t:ty) => {
+            $crate::interval_slice_test!(test_is_basic, $t);
+            $crate::interval_slice_test!(test_is_simplify, $t);
+            $crate::interval_slice_test!(test_is_vars, $t);
+            $crate::interval_slice_test!(test_is_agreement, $t);
+            $crate::interval_slice_test!(test_is_give_take, $t);
+        };
+    }
+}
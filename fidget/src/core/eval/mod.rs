@@ -20,12 +20,23 @@
 // Bulk evaluators
 pub mod float_slice;
 pub mod grad_slice;
+pub mod hessian_slice;
 
 // Tracing evaluators
 pub mod interval;
 pub mod point;
 
+// Interval slice evaluators, which trace many intervals at once
+pub mod interval_slice;
+
 pub mod bulk;
+pub mod cache;
+pub mod custom;
+pub mod dynamic;
+pub mod noise;
+pub mod pool;
+#[cfg(feature = "stream")]
+pub mod stream;
 pub mod tape;
 pub mod tracing;
 pub mod types;
@@ -33,15 +44,20 @@ pub mod types;
 mod vars;
 
 // Re-export a few things
+pub use custom::CustomOp;
 pub use float_slice::FloatSliceEval;
 pub use grad_slice::GradSliceEval;
+pub use hessian_slice::HessianSliceEval;
 pub use interval::IntervalEval;
+pub use interval_slice::IntervalSliceEval;
 pub use point::PointEval;
 pub use tape::Tape;
 pub use tracing::Choice;
+pub use types::{NanPolicy, SignConvention};
 pub use vars::Vars;
 
 use bulk::BulkEvaluator;
+use interval_slice::IntervalSliceEvaluator;
 use tracing::TracingEvaluator;
 
 /// A "family" of evaluators (JIT, interpreter, etc)
@@ -49,6 +65,23 @@ pub trait Family: Clone {
     /// Register limit for this evaluator family.
     const REG_LIMIT: u8;
 
+    /// Maximum number of slots (registers plus memory) in a tape
+    ///
+    /// Building or simplifying a tape which needs more slots returns
+    /// [`Error::TooManySlots`](crate::Error::TooManySlots).
+    const SLOT_LIMIT: usize = usize::MAX;
+
+    /// Whether tapes are scheduled to hide instruction latency by default
+    ///
+    /// See [`Tape::with_scheduling`] for details.
+    const SCHEDULE: bool = false;
+
+    /// Number of points which the bulk float evaluator processes together
+    ///
+    /// Slices whose length is a multiple of this value are evaluated without
+    /// a scalar tail, so callers can round up their batch sizes to match.
+    const LANE_WIDTH: usize = 1;
+
     /// Single-point evaluator
     type PointEval: TracingEvaluator<f32, Self>
         + EvaluatorStorage<Self>
@@ -61,6 +94,16 @@ pub trait Family: Clone {
         + Clone
         + Send
         + Sync;
+    /// Interval slice evaluator
+    ///
+    /// Families without a specialized implementation can use a
+    /// [`TracingSliceEval`](interval_slice::TracingSliceEval), which calls
+    /// their interval evaluator once per item.
+    type IntervalSliceEval: IntervalSliceEvaluator<Self>
+        + EvaluatorStorage<Self>
+        + Clone
+        + Send
+        + Sync;
 
     /// Bulk point evaluator
     type FloatSliceEval: BulkEvaluator<f32, Self>
@@ -74,6 +117,16 @@ pub trait Family: Clone {
         + Clone
         + Send
         + Sync;
+    /// Bulk evaluator for first and second derivatives
+    ///
+    /// Families without a specialized implementation can use
+    /// [`vm::Interpreted`](crate::vm::Interpreted), which runs the tape in the
+    /// VM interpreter.
+    type HessianSliceEval: BulkEvaluator<types::Hessian, Self>
+        + EvaluatorStorage<Self>
+        + Clone
+        + Send
+        + Sync;
 
     /// Recommended tile sizes for 3D rendering
     fn tile_sizes_3d() -> &'static [usize];
@@ -107,6 +160,21 @@ pub trait EvaluatorStorage<F> {
     /// is too small).
     fn new_with_storage(tape: &Tape<F>, storage: Self::Storage) -> Self;
 
+    /// Builds an evaluator for a tape which differs from this evaluator's tape
+    /// only in the values of its constants
+    ///
+    /// Evaluators which are expensive to construct may reuse work here (e.g.
+    /// JIT evaluators with a [constant table](Tape::with_constant_table) reuse
+    /// their compiled code).  The default implementation builds a new
+    /// evaluator from scratch, which is always correct, even if `tape` has an
+    /// entirely different structure.
+    fn rebind(&self, tape: &Tape<F>) -> Self
+    where
+        Self: Sized,
+    {
+        Self::new_with_storage(tape, Default::default())
+    }
+
     /// Extract the internal storage for reuse, if possible
     fn take(self) -> Option<Self::Storage>;
 }
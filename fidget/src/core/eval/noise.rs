@@ -0,0 +1,343 @@
+//! Gradient noise
+//!
+//! This is Perlin's "improved noise", with a hash function instead of a
+//! permutation table, so that any 16-bit seed selects a different pattern.
+//! It's used to evaluate
+//! [`Context::noise3`](crate::context::Context::noise3) nodes; every evaluator
+//! (including the JIT, which calls back into these functions) produces the
+//! same values.
+use crate::eval::types::{Grad, Hessian, Interval};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+/// Gradient directions, indexed by the low four bits of a corner's hash
+///
+/// These are the midpoints of the cube's twelve edges, with four repeated to
+/// pad the table to a power of two (as in Perlin's reference implementation).
+const GRADIENTS: [[f32; 3]; 16] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, -1.0, 1.0],
+    [-1.0, 1.0, 0.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// Scale applied to the raw noise value
+///
+/// Each corner's contribution is at most the sum of its offsets along two
+/// axes, and the interpolated sum of offsets along a single axis is at most
+/// 1/2, so the raw value is within ±3/2.  Scaling keeps the output in `[-1,
+/// 1]`, which is the bound used by interval evaluation.
+const SCALE: f32 = 2.0 / 3.0;
+
+/// Hashes a lattice point and seed
+fn hash(x: i32, y: i32, z: i32, seed: u16) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ (seed as u32).wrapping_mul(0x9e37_79b9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// Quintic interpolation curve, `6t⁵ - 15t⁴ + 10t³`, and its derivative
+fn fade(t: f32) -> (f32, f32) {
+    let f = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let d = 30.0 * t * t * (t * (t - 2.0) + 1.0);
+    (f, d)
+}
+
+/// Second derivative of the quintic interpolation curve
+fn fade2(t: f32) -> f32 {
+    60.0 * t * (t * (2.0 * t - 3.0) + 1.0)
+}
+
+/// Evaluates noise and its partial derivatives, as `[v, dx, dy, dz]`
+#[inline]
+fn eval(x: f32, y: f32, z: f32, seed: u16) -> [f32; 4] {
+    let p = [x, y, z];
+    let cell = p.map(f32::floor);
+    let i = cell.map(|c| c as i32);
+    let f = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+    let fades = f.map(fade);
+
+    let mut out = [0.0; 4];
+    for corner in 0..8 {
+        let c = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let h = hash(
+            i[0].wrapping_add(c[0]),
+            i[1].wrapping_add(c[1]),
+            i[2].wrapping_add(c[2]),
+            seed,
+        );
+        let g = GRADIENTS[(h & 15) as usize];
+
+        // Offset from the corner, its weight along each axis, and the
+        // derivatives of those weights
+        let mut d = [0.0; 3];
+        let mut w = [0.0; 3];
+        let mut dw = [0.0; 3];
+        for a in 0..3 {
+            let (s, ds) = fades[a];
+            if c[a] == 0 {
+                (d[a], w[a], dw[a]) = (f[a], 1.0 - s, -ds);
+            } else {
+                (d[a], w[a], dw[a]) = (f[a] - 1.0, s, ds);
+            }
+        }
+        let n = g[0] * d[0] + g[1] * d[1] + g[2] * d[2];
+        let weight = w[0] * w[1] * w[2];
+
+        out[0] += weight * n;
+        out[1] += dw[0] * w[1] * w[2] * n + weight * g[0];
+        out[2] += w[0] * dw[1] * w[2] * n + weight * g[1];
+        out[3] += w[0] * w[1] * dw[2] * n + weight * g[2];
+    }
+    out.map(|v| v * SCALE)
+}
+
+/// Evaluates noise and its first and second partial derivatives
+///
+/// Returns the value, gradient, and Hessian matrix.
+fn eval_hessian(
+    x: f32,
+    y: f32,
+    z: f32,
+    seed: u16,
+) -> (f32, [f32; 3], [[f32; 3]; 3]) {
+    let p = [x, y, z];
+    let cell = p.map(f32::floor);
+    let i = cell.map(|c| c as i32);
+    let f = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+
+    let mut v = 0.0;
+    let mut grad = [0.0; 3];
+    let mut hess = [[0.0; 3]; 3];
+    for corner in 0..8 {
+        let c = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let h = hash(
+            i[0].wrapping_add(c[0]),
+            i[1].wrapping_add(c[1]),
+            i[2].wrapping_add(c[2]),
+            seed,
+        );
+        let g = GRADIENTS[(h & 15) as usize];
+
+        // Offset from the corner, and its weight along each axis (with first
+        // and second derivatives)
+        let mut d = [0.0; 3];
+        let mut w = [[0.0; 3]; 3];
+        for a in 0..3 {
+            let (s, ds) = fade(f[a]);
+            let dds = fade2(f[a]);
+            if c[a] == 0 {
+                (d[a], w[0][a], w[1][a], w[2][a]) = (f[a], 1.0 - s, -ds, -dds);
+            } else {
+                (d[a], w[0][a], w[1][a], w[2][a]) = (f[a] - 1.0, s, ds, dds);
+            }
+        }
+        let n = g[0] * d[0] + g[1] * d[1] + g[2] * d[2];
+
+        // Derivative of the combined weight, where `order[k]` is the order of
+        // the derivative taken along axis `k`
+        let weight = |order: [usize; 3]| {
+            w[order[0]][0] * w[order[1]][1] * w[order[2]][2]
+        };
+        let axis = |a: usize| core::array::from_fn(|k| usize::from(k == a));
+
+        v += weight([0; 3]) * n;
+        for a in 0..3 {
+            grad[a] += weight(axis(a)) * n + weight([0; 3]) * g[a];
+            for b in 0..3 {
+                let mut order: [usize; 3] = axis(a);
+                order[b] += 1;
+                hess[a][b] += weight(order) * n
+                    + weight(axis(a)) * g[b]
+                    + weight(axis(b)) * g[a];
+            }
+        }
+    }
+    (
+        v * SCALE,
+        grad.map(|d| d * SCALE),
+        hess.map(|row| row.map(|d| d * SCALE)),
+    )
+}
+
+/// Evaluates noise at a single point
+///
+/// The result is in the range `[-1, 1]`, and is zero at every integer lattice
+/// point.  If any coordinate is infinite or `NaN`, returns `NaN`.
+pub fn noise3(x: f32, y: f32, z: f32, seed: u16) -> f32 {
+    eval(x, y, z, seed)[0]
+}
+
+/// Evaluates noise over intervals
+///
+/// Returns `[-1, 1]`, or the `NaN` interval if any argument is unbounded or
+/// contains `NaN` (for which [`noise3`] would return `NaN`).
+pub fn noise3_interval(
+    x: Interval,
+    y: Interval,
+    z: Interval,
+    _seed: u16,
+) -> Interval {
+    if [x, y, z]
+        .iter()
+        .any(|i| !i.lower().is_finite() || !i.upper().is_finite())
+    {
+        f32::NAN.into()
+    } else {
+        Interval::new(-1.0, 1.0)
+    }
+}
+
+/// Evaluates noise and its partial derivatives
+///
+/// The arguments' partial derivatives are combined with the chain rule.
+pub fn noise3_grad(x: Grad, y: Grad, z: Grad, seed: u16) -> Grad {
+    let [v, nx, ny, nz] = eval(x.v, y.v, z.v, seed);
+    Grad::new(
+        v,
+        nx * x.dx + ny * y.dx + nz * z.dx,
+        nx * x.dy + ny * y.dy + nz * z.dy,
+        nx * x.dz + ny * y.dz + nz * z.dz,
+    )
+}
+
+/// Evaluates noise and its first and second partial derivatives
+///
+/// The arguments' derivatives are combined with the chain rule.
+pub fn noise3_hessian(
+    x: Hessian,
+    y: Hessian,
+    z: Hessian,
+    seed: u16,
+) -> Hessian {
+    let (v, d1, d2) = eval_hessian(x.v, y.v, z.v, seed);
+    let args = [x, y, z];
+    let mut out = Hessian::from(v);
+    for i in 0..3 {
+        for a in 0..3 {
+            out.grad[i] += d1[a] * args[a].grad[i];
+        }
+        for j in 0..3 {
+            for a in 0..3 {
+                out.hess[i][j] += d1[a] * args[a].hess[i][j];
+                for b in 0..3 {
+                    out.hess[i][j] +=
+                        d2[a][b] * args[a].grad[i] * args[b].grad[j];
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_noise_lattice() {
+        for i in -3..3 {
+            let v = i as f32;
+            assert_eq!(noise3(v, 2.0 * v, 0.0, 0), 0.0);
+        }
+        assert!(noise3(f32::INFINITY, 0.0, 0.0, 0).is_nan());
+        assert!(noise3(0.5, f32::NAN, 0.0, 0).is_nan());
+    }
+
+    #[test]
+    fn test_noise_seed() {
+        let (x, y, z) = (0.3, 1.7, -2.2);
+        assert_eq!(noise3(x, y, z, 1), noise3(x, y, z, 1));
+        assert_ne!(noise3(x, y, z, 1), noise3(x, y, z, 2));
+    }
+
+    #[test]
+    fn test_noise_bounds() {
+        let mut max = 0f32;
+        for i in 0..20000 {
+            let t = i as f32;
+            let v = noise3(t * 0.137, t * 0.0731 - 5.0, t * 0.219 + 1.0, 7);
+            assert!(v.abs() <= 1.0, "{v}");
+            max = max.max(v.abs());
+        }
+        assert!(max > 0.3, "noise is too flat ({max})");
+    }
+
+    #[test]
+    fn test_noise_grad() {
+        let eps = 1e-3;
+        for (x, y, z) in [(0.3, 1.7, -2.2), (-4.6, 0.1, 0.9), (10.2, 3.3, 5.5)]
+        {
+            let g = noise3_grad(
+                Grad::new(x, 1.0, 0.0, 0.0),
+                Grad::new(y, 0.0, 1.0, 0.0),
+                Grad::new(z, 0.0, 0.0, 1.0),
+                3,
+            );
+            assert_eq!(g.v, noise3(x, y, z, 3));
+            let fd = |dx, dy, dz| {
+                (noise3(x + dx, y + dy, z + dz, 3)
+                    - noise3(x - dx, y - dy, z - dz, 3))
+                    / (2.0 * eps)
+            };
+            assert!((g.dx - fd(eps, 0.0, 0.0)).abs() < 1e-2, "{g:?}");
+            assert!((g.dy - fd(0.0, eps, 0.0)).abs() < 1e-2, "{g:?}");
+            assert!((g.dz - fd(0.0, 0.0, eps)).abs() < 1e-2, "{g:?}");
+        }
+    }
+
+    #[test]
+    fn test_noise_hessian() {
+        let eps = 1e-3;
+        for (x, y, z) in [(0.3, 1.7, -2.2), (-4.6, 0.1, 0.9), (10.2, 3.3, 5.5)]
+        {
+            let grad = |x, y, z| {
+                let g = noise3_grad(
+                    Grad::new(x, 1.0, 0.0, 0.0),
+                    Grad::new(y, 0.0, 1.0, 0.0),
+                    Grad::new(z, 0.0, 0.0, 1.0),
+                    3,
+                );
+                [g.dx, g.dy, g.dz]
+            };
+            let h = noise3_hessian(
+                Hessian::input(x, 0),
+                Hessian::input(y, 1),
+                Hessian::input(z, 2),
+                3,
+            );
+            assert_eq!(h.v, noise3(x, y, z, 3));
+            for (a, b) in h.grad.iter().zip(grad(x, y, z)) {
+                assert!((a - b).abs() < 1e-5, "{h:?}");
+            }
+            for j in 0..3 {
+                let mut d = [0.0; 3];
+                d[j] = eps;
+                let hi = grad(x + d[0], y + d[1], z + d[2]);
+                let lo = grad(x - d[0], y - d[1], z - d[2]);
+                for i in 0..3 {
+                    let fd = (hi[i] - lo[i]) / (2.0 * eps);
+                    assert!((h.hess[i][j] - fd).abs() < 2e-2, "{h:?}");
+                }
+            }
+        }
+    }
+}
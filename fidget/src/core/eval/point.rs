@@ -28,7 +28,7 @@ pub mod eval_tests {
     use super::*;
     use crate::{
         context::Context,
-        eval::{Choice, Vars},
+        eval::{Choice, NanPolicy, Vars},
     };
 
     pub fn test_constant<I: Family>() {
@@ -92,15 +92,92 @@ pub mod eval_tests {
         assert_eq!(r, 0.0);
         assert_eq!(data.unwrap().choices(), &[Choice::Right]);
 
-        let (r, data) = eval.eval(std::f32::NAN, 0.0, 0.0, &[]).unwrap();
+        let (r, data) = eval.eval(core::f32::NAN, 0.0, 0.0, &[]).unwrap();
         assert!(r.is_nan());
         assert!(data.is_none());
 
-        let (r, data) = eval.eval(0.0, std::f32::NAN, 0.0, &[]).unwrap();
+        let (r, data) = eval.eval(0.0, core::f32::NAN, 0.0, &[]).unwrap();
         assert!(r.is_nan());
         assert!(data.is_none());
     }
 
+    pub fn test_p_choices<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(min, z).unwrap();
+
+        // Each operation writes its own choice, in evaluation order
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        let eval = tape.new_point_evaluator();
+        let (r, data) = eval.eval(1.0, 2.0, 3.0, &[]).unwrap();
+        assert_eq!(r, 3.0);
+        assert_eq!(data.unwrap().choices(), &[Choice::Left, Choice::Left]);
+
+        let (r, data) = eval.eval(2.0, 1.0, 0.0, &[]).unwrap();
+        assert_eq!(r, 1.0);
+        assert_eq!(data.unwrap().choices(), &[Choice::Right, Choice::Right]);
+
+        let (r, data) = eval.eval(2.0, 1.0, 5.0, &[]).unwrap();
+        assert_eq!(r, 5.0);
+        assert_eq!(data.unwrap().choices(), &[Choice::Right, Choice::Left]);
+    }
+
+    pub fn test_p_min_many<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let min = ctx.min_many([x, y, z]).unwrap();
+
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        assert_eq!(tape.choice_count(), 2);
+        let eval = tape.new_point_evaluator();
+        let (r, data) = eval.eval(1.0, 2.0, 3.0, &[]).unwrap();
+        assert_eq!(r, 1.0);
+        assert_eq!(data.unwrap().choices(), &[Choice::Left, Choice::Left]);
+        let (r, data) = eval.eval(3.0, 2.0, 1.0, &[]).unwrap();
+        assert_eq!(r, 1.0);
+
+        // Only the winning argument survives simplification
+        let simple = data.unwrap().simplify().unwrap();
+        assert_eq!(simple.choice_count(), 0);
+        let eval = simple.new_point_evaluator();
+        let (r, data) = eval.eval(0.0, 0.0, 5.0, &[]).unwrap();
+        assert_eq!(r, 5.0);
+        assert!(data.is_none());
+
+        let one = ctx.constant(1.0);
+        let max = ctx.max_many([x, y, z, one]).unwrap();
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        assert_eq!(tape.choice_count(), 3);
+        let eval = tape.new_point_evaluator();
+        let (r, _) = eval.eval(-1.0, -2.0, 0.5, &[]).unwrap();
+        assert_eq!(r, 1.0);
+        let (r, _) = eval.eval(-1.0, 4.0, 0.5, &[]).unwrap();
+        assert_eq!(r, 4.0);
+    }
+
+    pub fn test_p_reuse<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        let eval = tape.new_point_evaluator();
+        let mut data = Default::default();
+        let (_, trace) = eval.eval_with(0.0, 1.0, 0.0, &[], &mut data).unwrap();
+        assert_eq!(trace.unwrap().choices(), &[Choice::Left]);
+        let (_, trace) = eval.eval_with(2.0, 0.0, 0.0, &[], &mut data).unwrap();
+        assert_eq!(trace.unwrap().choices(), &[Choice::Right]);
+        let (_, trace) = eval.eval_with(1.0, 1.0, 0.0, &[], &mut data).unwrap();
+        assert!(trace.is_none());
+        assert_eq!(data.epoch(), 3);
+    }
+
     pub fn test_p_max<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -122,15 +199,60 @@ pub mod eval_tests {
         assert_eq!(r, 2.0);
         assert_eq!(data.unwrap().choices(), &[Choice::Left]);
 
-        let (r, data) = eval.eval(std::f32::NAN, 0.0, 0.0, &[]).unwrap();
+        let (r, data) = eval.eval(core::f32::NAN, 0.0, 0.0, &[]).unwrap();
         assert!(r.is_nan());
         assert!(data.is_none());
 
-        let (r, data) = eval.eval(0.0, std::f32::NAN, 0.0, &[]).unwrap();
+        let (r, data) = eval.eval(0.0, core::f32::NAN, 0.0, &[]).unwrap();
         assert!(r.is_nan());
         assert!(data.is_none());
     }
 
+    pub fn test_p_nan_policy<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(x, y).unwrap();
+        let min_imm = ctx.min(x, 1.0).unwrap();
+        let nan = f32::NAN;
+
+        let tape = ctx.get_tape::<I>(min).unwrap();
+        assert_eq!(tape.nan_policy(), NanPolicy::Propagate);
+        let tape = tape.with_nan_policy(NanPolicy::Empty);
+        let eval = tape.new_point_evaluator();
+        let (r, data) = eval.eval(nan, 1.0, 0.0, &[]).unwrap();
+        assert_eq!(r, 1.0);
+        assert_eq!(data.unwrap().choices(), &[Choice::Right]);
+        let (r, data) = eval.eval(1.0, nan, 0.0, &[]).unwrap();
+        assert_eq!(r, 1.0);
+        assert_eq!(data.unwrap().choices(), &[Choice::Left]);
+        let (r, data) = eval.eval(nan, nan, 0.0, &[]).unwrap();
+        assert_eq!(r, f32::INFINITY);
+        assert!(data.is_none());
+
+        let tape = ctx.get_tape::<I>(max).unwrap();
+        let eval = tape.with_nan_policy(NanPolicy::Empty);
+        let eval = eval.new_point_evaluator();
+        let (r, data) = eval.eval(nan, 1.0, 0.0, &[]).unwrap();
+        assert_eq!(r, f32::INFINITY);
+        assert_eq!(data.unwrap().choices(), &[Choice::Left]);
+        let (r, data) = eval.eval(1.0, nan, 0.0, &[]).unwrap();
+        assert_eq!(r, f32::INFINITY);
+        assert_eq!(data.unwrap().choices(), &[Choice::Right]);
+
+        let tape = ctx.get_tape::<I>(min_imm).unwrap();
+        for (policy, expected) in
+            [(NanPolicy::Propagate, nan), (NanPolicy::Empty, 1.0)]
+        {
+            let tape = tape.clone().with_nan_policy(policy);
+            let eval = tape.new_point_evaluator();
+            let (r, _data) = eval.eval(nan, 0.0, 0.0, &[]).unwrap();
+            assert!(r == expected || (r.is_nan() && expected.is_nan()));
+            assert_eq!(eval.eval(0.5, 0.0, 0.0, &[]).unwrap().0, 0.5);
+        }
+    }
+
     pub fn basic_interpreter<I: Family>() {
         let mut ctx = Context::new();
         let x = ctx.x();
@@ -266,6 +388,255 @@ pub mod eval_tests {
         );
     }
 
+    pub fn test_p_trig<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let s = ctx.sin(x).unwrap();
+        let c = ctx.cos(y).unwrap();
+        let out = ctx.sub(s, c).unwrap();
+        let tape = ctx.get_tape::<I>(out).unwrap();
+        let eval = tape.new_point_evaluator();
+        for (x, y) in [(0.0f32, 0.0), (1.0, 2.0), (-3.0, 0.5), (10.0, -7.0)] {
+            let v = eval.eval(x, y, 0.0, &[]).unwrap().0;
+            assert_eq!(v, x.sin() - y.cos());
+        }
+    }
+
+    pub fn test_p_atan2<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.atan2(y, x).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+        let eval = tape.new_point_evaluator();
+        for (x, y) in [(1.0f32, 0.0), (0.0, 1.0), (-1.0, 0.0), (-1.0, -0.5)] {
+            assert_eq!(eval.eval(x, y, 0.0, &[]).unwrap().0, y.atan2(x));
+        }
+
+        // Immediate arguments on either side
+        let a = ctx.atan2(y, 2.0).unwrap();
+        let b = ctx.atan2(-1.0, x).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(
+            eval.eval(3.0, 1.0, 0.0, &[]).unwrap().0,
+            1f32.atan2(2.0) + (-1f32).atan2(3.0)
+        );
+    }
+
+    pub fn test_p_hypot<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let h = ctx.hypot(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(h).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(3.0, -4.0, 0.0, &[]).unwrap().0, 5.0);
+        assert_eq!(eval.eval(0.0, 0.0, 0.0, &[]).unwrap().0, 0.0);
+
+        let h = ctx.hypot(x, 5.0).unwrap();
+        let tape = ctx.get_tape::<I>(h).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(12.0, 0.0, 0.0, &[]).unwrap().0, 13.0);
+    }
+
+    pub fn test_p_mod<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let m = ctx.modulo(x, y).unwrap();
+        let tape = ctx.get_tape::<I>(m).unwrap();
+        let eval = tape.new_point_evaluator();
+        for (x, y) in [(3.5f32, 2.0), (-0.5, 2.0), (0.5, -2.0), (-7.0, -3.0)] {
+            let v = eval.eval(x, y, 0.0, &[]).unwrap().0;
+            assert_eq!(v, x - y * (x / y).floor(), "{x} mod {y}");
+        }
+        assert_eq!(eval.eval(-0.5, 2.0, 0.0, &[]).unwrap().0, 1.5);
+
+        // Immediate arguments on either side
+        let a = ctx.modulo(x, 3.0).unwrap();
+        let b = ctx.modulo(-5.0, y).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(-1.0, 4.0, 0.0, &[]).unwrap().0, 2.0 + 3.0);
+    }
+
+    pub fn test_p_polar<I: Family>() {
+        // Build a shape where many values are live across each atan2 / hypot,
+        // to make sure that they survive the operation.
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let mut terms = vec![];
+        for i in 0..16 {
+            let dx = ctx.sub(x, i as f64 * 0.25).unwrap();
+            let r = ctx.hypot(dx, y).unwrap();
+            let a = ctx.atan2(y, dx).unwrap();
+            let a = ctx.mul(a, z).unwrap();
+            terms.push(ctx.sub(r, a).unwrap());
+        }
+        let root = ctx.min_many(terms).unwrap();
+        let tape = ctx.get_tape::<I>(root).unwrap();
+        let eval = tape.new_point_evaluator();
+        for (x, y, z) in [(0.0, 1.0, 0.5), (2.0, -1.0, 0.25), (-1.0, 0.5, 1.0)]
+        {
+            let expected = (0..16)
+                .map(|i| {
+                    let dx = x - i as f32 * 0.25;
+                    dx.hypot(y) - y.atan2(dx) * z
+                })
+                .fold(f32::INFINITY, f32::min);
+            let v = eval.eval(x, y, z, &[]).unwrap().0;
+            assert!((v - expected).abs() < 1e-5, "{v} != {expected}");
+        }
+    }
+
+    pub fn test_p_noise<I: Family>() {
+        use crate::eval::noise::noise3;
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let n = ctx.noise3(x, y, z, 3).unwrap();
+        let tape = ctx.get_tape::<I>(n).unwrap();
+        let eval = tape.new_point_evaluator();
+        for (x, y, z) in [(0.5, 1.25, -2.0), (3.7, -0.2, 0.9), (1.0, 2.0, 3.0)]
+        {
+            let v = eval.eval(x, y, z, &[]).unwrap().0;
+            assert_eq!(v, noise3(x, y, z, 3));
+        }
+
+        // Repeated and immediate arguments
+        let n = ctx.noise3(x, x, 0.75, 9).unwrap();
+        let tape = ctx.get_tape::<I>(n).unwrap();
+        let eval = tape.new_point_evaluator();
+        let v = eval.eval(1.5, 0.0, 0.0, &[]).unwrap().0;
+        assert_eq!(v, noise3(1.5, 1.5, 0.75, 9));
+
+        // Many values are live across each call
+        let mut terms = vec![];
+        for i in 0..16 {
+            let dx = ctx.sub(x, i as f64).unwrap();
+            terms.push(ctx.noise3(dx, y, z, i).unwrap());
+        }
+        let mut sum = terms[0];
+        for t in &terms[1..] {
+            sum = ctx.add(sum, *t).unwrap();
+        }
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_point_evaluator();
+        let v = eval.eval(0.3, 0.6, 0.9, &[]).unwrap().0;
+        let expected = (0..16)
+            .map(|i| noise3(0.3 - i as f32, 0.6, 0.9, i))
+            .fold(0.0, |a, b| a + b);
+        assert!((v - expected).abs() < 1e-5, "{v} != {expected}");
+    }
+
+    pub fn test_p_clamp<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let c = ctx.clamp(x, y, 1.0).unwrap();
+        let tape = ctx.get_tape::<I>(c).unwrap();
+        assert_eq!(tape.choice_count(), 0);
+
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(0.5, 0.0, 0.0, &[]).unwrap().0, 0.5);
+        assert_eq!(eval.eval(2.0, 0.0, 0.0, &[]).unwrap().0, 1.0);
+        assert_eq!(eval.eval(-2.0, -1.0, 0.0, &[]).unwrap().0, -1.0);
+        assert!(eval.eval(f32::NAN, 0.0, 0.0, &[]).unwrap().0.is_nan());
+        assert!(eval.eval(0.5, f32::NAN, 0.0, &[]).unwrap().0.is_nan());
+
+        // The evaluator should not have allocated any choices to simplify
+        let (_, simplify) = eval.eval(2.0, 0.0, 0.0, &[]).unwrap();
+        assert!(simplify.is_none());
+    }
+
+    pub fn test_p_min_max_nc<I: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.min_nc(x, y).unwrap();
+        let b = ctx.max_nc(x, 1.0).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        assert_eq!(tape.choice_count(), 0);
+
+        let eval = tape.new_point_evaluator();
+        let (v, simplify) = eval.eval(0.5, 2.0, 0.0, &[]).unwrap();
+        assert_eq!(v, 1.5);
+        assert!(simplify.is_none());
+        assert_eq!(eval.eval(3.0, -2.0, 0.0, &[]).unwrap().0, 1.0);
+        assert!(eval.eval(f32::NAN, 0.0, 0.0, &[]).unwrap().0.is_nan());
+        assert!(eval.eval(0.0, f32::NAN, 0.0, &[]).unwrap().0.is_nan());
+    }
+
+    pub fn test_p_custom<I: Family>() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let op = alloc::sync::Arc::new(SquareSub);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.custom(op.clone(), x, y).unwrap();
+        let tape = ctx.get_tape::<I>(a).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(3.0, 2.0, 0.0, &[]).unwrap().0, 7.0);
+        assert_eq!(eval.eval(-1.0, 0.5, 0.0, &[]).unwrap().0, 0.5);
+
+        // Immediate arguments on either side
+        let a = ctx.custom(op.clone(), x, 2.0).unwrap();
+        let b = ctx.custom(op.clone(), 3.0, y).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(4.0, 1.0, 0.0, &[]).unwrap().0, 14.0 + 8.0);
+
+        // Many values are live across each call
+        let mut terms = vec![];
+        for i in 0..16 {
+            let dx = ctx.sub(x, i as f64).unwrap();
+            terms.push(ctx.custom(op.clone(), dx, y).unwrap());
+        }
+        let root = ctx.max_many(terms).unwrap();
+        let tape = ctx.get_tape::<I>(root).unwrap();
+        let eval = tape.new_point_evaluator();
+        for (x, y) in [(0.0, 1.0), (7.5, -2.0), (20.0, 0.5)] {
+            let expected = (0..16)
+                .map(|i| (x - i as f32).powi(2) - y)
+                .fold(f32::NEG_INFINITY, f32::max);
+            assert_eq!(eval.eval(x, y, 0.0, &[]).unwrap().0, expected);
+        }
+    }
+
+    pub fn test_p_custom_panic<I: Family>() {
+        use crate::eval::custom::eval_tests::PanicNegative;
+        let op = alloc::sync::Arc::new(PanicNegative);
+
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.custom(op.clone(), x, 0.0).unwrap();
+        let b = ctx.custom(op, y, 0.0).unwrap();
+        let sum = ctx.add(a, b).unwrap();
+        let tape = ctx.get_tape::<I>(sum).unwrap();
+        let eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(1.0, 2.0, 0.0, &[]).unwrap().0, 3.0);
+
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            eval.eval(-1.0, 2.0, 0.0, &[])
+        }));
+        assert!(r.is_err());
+
+        // The evaluator is still usable after the panic
+        assert_eq!(eval.eval(3.0, 4.0, 0.0, &[]).unwrap().0, 7.0);
+    }
+
     #[macro_export]
     macro_rules! point_test {
         ($i:ident, $t:ty) => {
@@ -284,6 +655,20 @@ pub mod eval_tests {
             $crate::point_test!(test_circle, $t);
             $crate::point_test!(test_p_max, $t);
             $crate::point_test!(test_p_min, $t);
+            $crate::point_test!(test_p_choices, $t);
+            $crate::point_test!(test_p_min_many, $t);
+            $crate::point_test!(test_p_reuse, $t);
+            $crate::point_test!(test_p_nan_policy, $t);
+            $crate::point_test!(test_p_atan2, $t);
+            $crate::point_test!(test_p_trig, $t);
+            $crate::point_test!(test_p_hypot, $t);
+            $crate::point_test!(test_p_mod, $t);
+            $crate::point_test!(test_p_polar, $t);
+            $crate::point_test!(test_p_custom, $t);
+            $crate::point_test!(test_p_custom_panic, $t);
+            $crate::point_test!(test_p_noise, $t);
+            $crate::point_test!(test_p_clamp, $t);
+            $crate::point_test!(test_p_min_max_nc, $t);
             $crate::point_test!(basic_interpreter, $t);
             $crate::point_test!(test_push, $t);
             $crate::point_test!(test_var, $t);
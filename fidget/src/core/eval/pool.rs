@@ -0,0 +1,301 @@
+//! Per-thread pools of reusable evaluation allocations
+//!
+//! Rendering and meshing repeatedly build evaluators for simplified tapes,
+//! then throw them away once a region is finished.  An [`EvalContext`] keeps
+//! the allocations from those tapes, evaluators, and choice arrays, and hands
+//! them back out for the next region, so that a worker thread stops allocating
+//! once it has warmed up.
+//!
+//! ```
+//! use fidget::{context::Context, eval::pool::EvalContext, vm};
+//!
+//! let mut ctx = Context::new();
+//! let (x, y) = (ctx.x(), ctx.y());
+//! let min = ctx.min(x, y)?;
+//! let tape = ctx.get_tape::<vm::Eval>(min)?;
+//!
+//! let mut pool = EvalContext::new();
+//! let eval = tape.new_interval_evaluator();
+//! let mut data = Default::default();
+//! for i in 0..4 {
+//!     let x = [0.0, 1.0];
+//!     let y = [2.0 + i as f32, 3.0 + i as f32];
+//!     let (_, trace) = eval.eval_with(x, y, [0.0; 2], &[], &mut data)?;
+//!     let simple = pool.simplify(&trace.unwrap())?;
+//!     assert_eq!(simple.len(), 1); // the tape only reads `x`
+//!     pool.release_tape(simple);
+//! }
+//! // Only the first simplification needed a fresh allocation
+//! assert_eq!(pool.misses(), 1);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{
+        float_slice::{FloatSliceEval, FloatSliceEvalStorage},
+        grad_slice::{GradSliceEval, GradSliceEvalStorage},
+        interval::{IntervalEval, IntervalEvalStorage},
+        interval_slice::{IntervalSliceEval, IntervalSliceEvalStorage},
+        tape::{Data, Workspace},
+        tracing::{ChoiceHint, TracingEvalResult},
+        Choice, Family, Tape,
+    },
+    Error,
+};
+use alloc::{vec, vec::Vec};
+
+/// Pool of reusable allocations for a single thread
+///
+/// Objects are borrowed from the pool with the `new_*` functions (and
+/// [`simplify`](Self::simplify)), then returned with the matching `release_*`
+/// function when they're no longer needed.  If the pool is empty, a fresh
+/// object is allocated and counted in [`misses`](Self::misses).
+pub struct EvalContext<F: Family> {
+    /// Workspace for tape simplification
+    pub workspace: Workspace,
+
+    tapes: Vec<Data>,
+    choices: Vec<Vec<Choice>>,
+    interval_storage: Vec<IntervalEvalStorage<F>>,
+    interval_slice_storage: Vec<IntervalSliceEvalStorage<F>>,
+    float_storage: Vec<FloatSliceEvalStorage<F>>,
+    grad_storage: Vec<GradSliceEvalStorage<F>>,
+
+    misses: usize,
+}
+
+impl<F: Family> Default for EvalContext<F> {
+    fn default() -> Self {
+        Self {
+            workspace: Default::default(),
+            tapes: vec![],
+            choices: vec![],
+            interval_storage: vec![],
+            interval_slice_storage: vec![],
+            float_storage: vec![],
+            grad_storage: vec![],
+            misses: 0,
+        }
+    }
+}
+
+impl<F: Family> EvalContext<F> {
+    /// Builds a new, empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of times that an object was requested from an empty
+    /// pool (and therefore allocated from scratch)
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Pops an item from the given pool, counting a miss if it's empty
+    fn pop<T: Default>(pool: &mut Vec<T>, misses: &mut usize) -> T {
+        pool.pop().unwrap_or_else(|| {
+            *misses += 1;
+            T::default()
+        })
+    }
+
+    /// Simplifies a tape based on the most recent tracing evaluation, reusing
+    /// pooled tape data
+    pub fn simplify<T, B: core::borrow::Borrow<[Choice]>>(
+        &mut self,
+        trace: &TracingEvalResult<T, F, B>,
+    ) -> Result<Tape<F>, Error> {
+        let prev = Self::pop(&mut self.tapes, &mut self.misses);
+        trace.simplify_with(&mut self.workspace, prev)
+    }
+
+    /// Simplifies a tape based on the most recent tracing evaluation, returning
+    /// the hint's tape if it was built from the same choices
+    ///
+    /// On a mismatch, the hint is updated with the newly simplified tape, and
+    /// its previous tape is returned to the pool (if it's no longer shared).
+    pub fn simplify_hinted<T, B: core::borrow::Borrow<[Choice]>>(
+        &mut self,
+        trace: &TracingEvalResult<T, F, B>,
+        hint: &mut ChoiceHint<F>,
+    ) -> Result<Tape<F>, Error> {
+        if let Some(tape) = hint.check(trace) {
+            return Ok(tape);
+        }
+        let out = self.simplify(trace)?;
+        if let Some(prev) = hint.update(trace, out.clone()) {
+            self.release_tape(prev);
+        }
+        Ok(out)
+    }
+
+    /// Returns a tape's data to the pool
+    ///
+    /// If the tape is still shared (e.g. with an evaluator), this does
+    /// nothing.
+    pub fn release_tape(&mut self, tape: Tape<F>) {
+        self.tapes.extend(tape.take());
+    }
+
+    /// Copies a choice array into a pooled `Vec`
+    pub fn new_choices(&mut self, choices: &[Choice]) -> Vec<Choice> {
+        let mut out = Self::pop(&mut self.choices, &mut self.misses);
+        out.clear();
+        out.extend_from_slice(choices);
+        out
+    }
+
+    /// Returns a choice array to the pool
+    pub fn release_choices(&mut self, choices: Vec<Choice>) {
+        self.choices.push(choices);
+    }
+
+    /// Builds an interval evaluator, reusing pooled storage
+    pub fn new_interval_evaluator(
+        &mut self,
+        tape: &Tape<F>,
+    ) -> IntervalEval<F> {
+        let s = Self::pop(&mut self.interval_storage, &mut self.misses);
+        tape.new_interval_evaluator_with_storage(s)
+    }
+
+    /// Returns an interval evaluator's storage to the pool
+    pub fn release_interval_evaluator(&mut self, eval: IntervalEval<F>) {
+        self.interval_storage.extend(eval.take());
+    }
+
+    /// Builds an interval slice evaluator, reusing pooled storage
+    pub fn new_interval_slice_evaluator(
+        &mut self,
+        tape: &Tape<F>,
+    ) -> IntervalSliceEval<F> {
+        let s = Self::pop(&mut self.interval_slice_storage, &mut self.misses);
+        tape.new_interval_slice_evaluator_with_storage(s)
+    }
+
+    /// Returns an interval slice evaluator's storage to the pool
+    pub fn release_interval_slice_evaluator(
+        &mut self,
+        eval: IntervalSliceEval<F>,
+    ) {
+        self.interval_slice_storage.extend(eval.take());
+    }
+
+    /// Builds a float slice evaluator, reusing pooled storage
+    pub fn new_float_slice_evaluator(
+        &mut self,
+        tape: &Tape<F>,
+    ) -> FloatSliceEval<F> {
+        let s = Self::pop(&mut self.float_storage, &mut self.misses);
+        tape.new_float_slice_evaluator_with_storage(s)
+    }
+
+    /// Returns a float slice evaluator's storage to the pool
+    pub fn release_float_slice_evaluator(&mut self, eval: FloatSliceEval<F>) {
+        self.float_storage.extend(eval.take());
+    }
+
+    /// Builds a gradient slice evaluator, reusing pooled storage
+    pub fn new_grad_slice_evaluator(
+        &mut self,
+        tape: &Tape<F>,
+    ) -> GradSliceEval<F> {
+        let s = Self::pop(&mut self.grad_storage, &mut self.misses);
+        tape.new_grad_slice_evaluator_with_storage(s)
+    }
+
+    /// Returns a gradient slice evaluator's storage to the pool
+    pub fn release_grad_slice_evaluator(&mut self, eval: GradSliceEval<F>) {
+        self.grad_storage.extend(eval.take());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, vm};
+
+    #[test]
+    fn test_pool_reuse() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let max = ctx.max(min, -1.0).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(max).unwrap();
+
+        let mut pool = EvalContext::new();
+        let root = pool.new_interval_evaluator(&tape);
+        let mut data = Default::default();
+        let mut misses = vec![];
+        for i in 0..8 {
+            let y = [2.0 + i as f32, 3.0 + i as f32];
+            let (_, trace) = root
+                .eval_with([0.0, 1.0], y, [0.0; 2], &[], &mut data)
+                .unwrap();
+            let trace = trace.unwrap();
+            let choices = pool.new_choices(trace.choices());
+            assert_eq!(choices, trace.choices());
+
+            let sub = pool.simplify(&trace).unwrap();
+            assert!(sub.len() < tape.len());
+            let i = pool.new_interval_evaluator(&sub);
+            let s = pool.new_interval_slice_evaluator(&sub);
+            let f = pool.new_float_slice_evaluator(&sub);
+            let g = pool.new_grad_slice_evaluator(&sub);
+            assert_eq!(f.eval(&[0.5], &[3.0], &[0.0], &[]).unwrap(), [0.5]);
+
+            // Evaluators hold a reference to the tape, so they must be
+            // released first for the tape to be reclaimed.
+            pool.release_interval_evaluator(i);
+            pool.release_interval_slice_evaluator(s);
+            pool.release_float_slice_evaluator(f);
+            pool.release_grad_slice_evaluator(g);
+            pool.release_tape(sub);
+            pool.release_choices(choices);
+            misses.push(pool.misses());
+        }
+        // After the first iteration, everything comes from the pool
+        assert!(misses[0] > 0);
+        assert!(misses.iter().all(|m| *m == misses[0]), "{misses:?}");
+
+        // Releasing a shared tape does nothing
+        let shared = tape.clone();
+        pool.release_tape(shared);
+        assert_eq!(tape.len(), root.tape().len());
+    }
+
+    #[test]
+    fn test_simplify_hinted() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let min = ctx.min(x, y).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(min).unwrap();
+        let other = ctx.get_tape::<vm::Eval>(min).unwrap();
+
+        let mut pool = EvalContext::new();
+        let mut hint = ChoiceHint::new();
+        let eval = tape.new_interval_evaluator();
+        let mut simplify = |eval: &IntervalEval<vm::Eval>, x: [f32; 2]| {
+            let (_, trace) = eval.eval(x, [0.0, 1.0], [0.0; 2], &[]).unwrap();
+            pool.simplify_hinted(&trace.unwrap(), &mut hint).unwrap()
+        };
+
+        // Neighboring regions with the same choices share a tape
+        let a = simplify(&eval, [-3.0, -2.0]);
+        let b = simplify(&eval, [-2.0, -1.0]);
+        assert_eq!(a.id(), b.id());
+
+        // Different choices (or a different parent tape) don't match
+        let c = simplify(&eval, [2.0, 3.0]);
+        assert_ne!(a.id(), c.id());
+        let d = simplify(&other.new_interval_evaluator(), [2.0, 3.0]);
+        assert_ne!(c.id(), d.id());
+        assert_eq!(c.len(), d.len());
+
+        let stats = hint.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 3, 2));
+        assert_eq!(hint.take().unwrap().id(), d.id());
+        assert!(hint.tape().is_none());
+    }
+}
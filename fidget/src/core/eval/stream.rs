@@ -0,0 +1,354 @@
+//! Parallel evaluation of large point clouds
+//!
+//! The functions in this module split a set of points into chunks, which are
+//! evaluated in parallel by a pool of worker threads.  If
+//! [`simplify`](StreamSettings::simplify) is enabled, each worker first runs
+//! interval evaluation over the chunk's bounding box, then evaluates the chunk
+//! with a tape simplified for that region; this is a big win for spatially
+//! coherent inputs (e.g. voxel grids or sorted point clouds).
+//!
+//! ```
+//! use fidget::{context::Context, eval::stream, vm};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let sum = ctx.add(x, y).unwrap();
+//! let tape = ctx.get_tape::<vm::Eval>(sum).unwrap();
+//!
+//! let points: Vec<[f32; 3]> =
+//!     (0..10_000).map(|i| [i as f32, 1.0, 0.0]).collect();
+//! let out = stream::eval_points(&tape, &points, &[], &Default::default())
+//!     .unwrap();
+//! assert_eq!(out[9999], 10_000.0);
+//! ```
+use crate::{
+    eval::{
+        bulk::CHUNK_SIZE, types::Interval, Family, FloatSliceEval,
+        IntervalEval, Tape,
+    },
+    Error,
+};
+use std::sync::Mutex;
+
+/// Settings for streaming evaluation
+#[derive(Copy, Clone, Debug)]
+pub struct StreamSettings {
+    /// Number of threads to use; 8 by default
+    pub threads: usize,
+
+    /// Number of points in each chunk of work
+    ///
+    /// This is also the granularity of per-chunk simplification; it defaults
+    /// to [`CHUNK_SIZE`].
+    pub chunk_size: usize,
+
+    /// Simplify the tape for each chunk, based on its bounding box
+    pub simplify: bool,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            threads: 8,
+            chunk_size: CHUNK_SIZE,
+            simplify: true,
+        }
+    }
+}
+
+/// Per-thread evaluation state
+struct Worker<F: Family> {
+    tape: Tape<F>,
+
+    /// Evaluator for the original tape, built on first use
+    eval: Option<FloatSliceEval<F>>,
+
+    /// Interval evaluator, present if simplification is enabled
+    interval: Option<IntervalEval<F>>,
+
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    zs: Vec<f32>,
+}
+
+impl<F: Family> Worker<F> {
+    fn new(tape: &Tape<F>, settings: &StreamSettings) -> Self {
+        let simplify = settings.simplify && tape.choice_count() > 0;
+        Self {
+            tape: tape.clone(),
+            eval: None,
+            interval: simplify.then(|| tape.new_interval_evaluator()),
+            xs: vec![],
+            ys: vec![],
+            zs: vec![],
+        }
+    }
+
+    /// Evaluates a single chunk of points
+    fn run(
+        &mut self,
+        points: &[[f32; 3]],
+        vars: &[f32],
+        out: &mut [f32],
+    ) -> Result<(), Error> {
+        self.xs.clear();
+        self.ys.clear();
+        self.zs.clear();
+        for p in points {
+            self.xs.push(p[0]);
+            self.ys.push(p[1]);
+            self.zs.push(p[2]);
+        }
+
+        let simplified = match &self.interval {
+            Some(i) => {
+                let bounds = |v: &[f32]| {
+                    let lo = v.iter().cloned().fold(f32::INFINITY, f32::min);
+                    let hi =
+                        v.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                    Interval::new(lo, hi)
+                };
+                let (_, r) = i.eval(
+                    bounds(&self.xs),
+                    bounds(&self.ys),
+                    bounds(&self.zs),
+                    vars,
+                )?;
+                r.map(|r| r.simplify()).transpose()?
+            }
+            None => None,
+        };
+
+        let mut data = Default::default();
+        let (xs, ys, zs) = (&self.xs, &self.ys, &self.zs);
+        match simplified {
+            Some(tape) => tape
+                .new_float_slice_evaluator()
+                .eval_into(xs, ys, zs, vars, out, &mut data),
+            None => self
+                .eval
+                .get_or_insert_with(|| self.tape.new_float_slice_evaluator())
+                .eval_into(xs, ys, zs, vars, out, &mut data),
+        }
+    }
+}
+
+/// Evaluates chunks of `points` into `out`, using one thread per worker
+fn run<F: Family>(
+    workers: &mut [Worker<F>],
+    points: &[[f32; 3]],
+    vars: &[f32],
+    out: &mut [f32],
+    chunk_size: usize,
+) -> Result<(), Error> {
+    let mut jobs = points.chunks(chunk_size).zip(out.chunks_mut(chunk_size));
+    if let [w] = workers {
+        return jobs.try_for_each(|(p, o)| w.run(p, vars, o));
+    }
+    let jobs = Mutex::new(jobs);
+    std::thread::scope(|s| {
+        let handles = workers
+            .iter_mut()
+            .map(|w| {
+                let jobs = &jobs;
+                s.spawn(move || loop {
+                    let next = jobs.lock().unwrap().next();
+                    match next {
+                        Some((p, o)) => w.run(p, vars, o)?,
+                        None => return Ok(()),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().try_for_each(|h| h.join().unwrap())
+    })
+}
+
+/// Checks that `vars` has the right size for the tape
+fn check_vars<F: Family>(tape: &Tape<F>, vars: &[f32]) -> Result<(), Error> {
+    if vars.len() != tape.var_count() {
+        Err(Error::BadVarSlice(vars.len(), tape.var_count()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Evaluates many points in parallel, writing results into `out`
+///
+/// Returns [`Error::MismatchedSlices`] if `points` and `out` have different
+/// lengths.
+pub fn eval_points_into<F: Family>(
+    tape: &Tape<F>,
+    points: &[[f32; 3]],
+    vars: &[f32],
+    out: &mut [f32],
+    settings: &StreamSettings,
+) -> Result<(), Error> {
+    if points.len() != out.len() {
+        return Err(Error::MismatchedSlices);
+    }
+    check_vars(tape, vars)?;
+    let chunk_size = settings.chunk_size.max(1);
+    let threads = settings.threads.min(points.len().div_ceil(chunk_size));
+    let threads = threads.max(1);
+    let mut workers = (0..threads)
+        .map(|_| Worker::new(tape, settings))
+        .collect::<Vec<_>>();
+    run(&mut workers, points, vars, out, chunk_size)
+}
+
+/// Evaluates many points in parallel, returning a fresh `Vec<f32>`
+pub fn eval_points<F: Family>(
+    tape: &Tape<F>,
+    points: &[[f32; 3]],
+    vars: &[f32],
+    settings: &StreamSettings,
+) -> Result<Vec<f32>, Error> {
+    let mut out = vec![f32::NAN; points.len()];
+    eval_points_into(tape, points, vars, &mut out, settings)?;
+    Ok(out)
+}
+
+/// Evaluates a stream of points, passing results to `sink` as they're ready
+///
+/// Points are pulled from the iterator in batches of one chunk per thread, so
+/// memory use is bounded regardless of the number of points.  `sink` is
+/// called once per chunk, in order, with the chunk's points and results.
+///
+/// ```
+/// # use fidget::{context::Context, eval::stream, vm};
+/// # let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let tape = ctx.get_tape::<vm::Eval>(x).unwrap();
+///
+/// let mut inside = 0;
+/// let points = (0..1_000_000).map(|i| [i as f32 / 1e6 - 0.5, 0.0, 0.0]);
+/// stream::eval_stream(&tape, points, &[], &Default::default(), |_, out| {
+///     inside += out.iter().filter(|v| **v < 0.0).count()
+/// })
+/// .unwrap();
+/// assert_eq!(inside, 500_000);
+/// ```
+pub fn eval_stream<F, I, S>(
+    tape: &Tape<F>,
+    points: I,
+    vars: &[f32],
+    settings: &StreamSettings,
+    mut sink: S,
+) -> Result<(), Error>
+where
+    F: Family,
+    I: IntoIterator<Item = [f32; 3]>,
+    S: FnMut(&[[f32; 3]], &[f32]),
+{
+    check_vars(tape, vars)?;
+    let chunk_size = settings.chunk_size.max(1);
+    let mut workers = (0..settings.threads.max(1))
+        .map(|_| Worker::new(tape, settings))
+        .collect::<Vec<_>>();
+    let batch = chunk_size * workers.len();
+
+    let mut iter = points.into_iter();
+    let mut buf = Vec::with_capacity(batch);
+    let mut out = vec![];
+    loop {
+        buf.clear();
+        buf.extend(iter.by_ref().take(batch));
+        if buf.is_empty() {
+            break;
+        }
+        out.resize(buf.len(), f32::NAN);
+        let n = buf.len().div_ceil(chunk_size);
+        run(&mut workers[..n], &buf, vars, &mut out, chunk_size)?;
+        for (p, o) in buf.chunks(chunk_size).zip(out.chunks(chunk_size)) {
+            sink(p, o);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, gen::Rng};
+
+    fn test_stream<F: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let a = ctx.var("a").unwrap();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let r = ctx.add(x2, y2).unwrap();
+        let r = ctx.sqrt(r).unwrap();
+        let sphere = ctx.sub(r, a).unwrap();
+        let plane = ctx.sub(z, 0.25).unwrap();
+        let shape = ctx.max(sphere, plane).unwrap();
+        let tape = ctx.get_tape::<F>(shape).unwrap();
+        let vars = [0.75];
+
+        // Sorted points, so that per-chunk simplification kicks in
+        let mut rng = Rng::new(0);
+        let mut points = (0..5000)
+            .map(|_| [0; 3].map(|_| rng.range(-1.0, 1.0)))
+            .collect::<Vec<_>>();
+        points.sort_by(|a, b| a[2].total_cmp(&b[2]));
+
+        let eval = tape.new_point_evaluator();
+        let expected = points
+            .iter()
+            .map(|p| eval.eval(p[0], p[1], p[2], &vars).unwrap().0)
+            .collect::<Vec<_>>();
+
+        for threads in [1, 4] {
+            for simplify in [false, true] {
+                let settings = StreamSettings {
+                    threads,
+                    chunk_size: 256,
+                    simplify,
+                };
+                let out =
+                    eval_points(&tape, &points, &vars, &settings).unwrap();
+                assert_eq!(out, expected);
+
+                let mut streamed = vec![];
+                eval_stream(&tape, points.clone(), &vars, &settings, |p, o| {
+                    assert_eq!(p.len(), o.len());
+                    assert!(o.len() <= 256);
+                    streamed.extend_from_slice(o);
+                })
+                .unwrap();
+                assert_eq!(streamed, expected);
+            }
+        }
+
+        assert!(matches!(
+            eval_points(&tape, &points, &[], &Default::default()),
+            Err(Error::BadVarSlice(0, 1))
+        ));
+        let mut out = vec![0.0; 3];
+        assert!(matches!(
+            eval_points_into(
+                &tape,
+                &points,
+                &vars,
+                &mut out,
+                &Default::default()
+            ),
+            Err(Error::MismatchedSlices)
+        ));
+    }
+
+    #[test]
+    fn test_stream_vm() {
+        test_stream::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_stream_jit() {
+        test_stream::<crate::jit::Eval>();
+    }
+}
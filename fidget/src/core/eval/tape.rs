@@ -1,12 +1,16 @@
 //! General-purpose tapes for use during evaluation or further compilation
 use crate::{
-    context::{Context, Node},
-    eval::{self, Choice, Family},
-    ssa::{Op as SsaOp, Tape as SsaTape},
+    context::{BoundingBox, Context, Fnv, Node},
+    eval::{self, Choice, CustomOp, Family, NanPolicy, SignConvention},
+    ssa::{push_symbol, Op as SsaOp, Tape as SsaTape},
     vm::{Op as VmOp, RegisterAllocator, Tape as VmTape},
     Error,
 };
-use std::{collections::BTreeMap, sync::Arc};
+use alloc::{
+    borrow::ToOwned, collections::BTreeMap, format, string::String, sync::Arc,
+    vec, vec::Vec,
+};
+use core::fmt::Write;
 
 /// Light-weight handle for tape data, which deferences to
 /// [`Data`].
@@ -15,11 +19,11 @@ use std::{collections::BTreeMap, sync::Arc};
 ///
 /// It is parameterized by an [`Family`](Family) type, which sets the register
 /// count of the inner VM tape.
-pub struct Tape<R>(Arc<Data>, std::marker::PhantomData<*const R>);
+pub struct Tape<R>(Arc<Data>, core::marker::PhantomData<*const R>);
 
 impl<R> Clone for Tape<R> {
     fn clone(&self) -> Self {
-        Self(self.0.clone(), std::marker::PhantomData)
+        Self(self.0.clone(), core::marker::PhantomData)
     }
 }
 
@@ -32,8 +36,29 @@ unsafe impl<R> Sync for Tape<R> {}
 impl<E: Family> Tape<E> {
     /// Converts an SSA tape into a tape useable in evaluation
     pub fn from_ssa(ssa: SsaTape) -> Self {
-        let t = Data::from_ssa(ssa, E::REG_LIMIT);
-        Self(Arc::new(t), std::marker::PhantomData)
+        let mut t = Data::from_ssa(ssa, E::REG_LIMIT);
+        if E::SCHEDULE {
+            t.scheduled = true;
+            t.asm.schedule();
+        }
+        Self(Arc::new(t), core::marker::PhantomData)
+    }
+
+    /// Returns a value which uniquely identifies this tape's data
+    ///
+    /// Clones of a tape share the same identity.  The identity may be reused
+    /// after every clone of the tape has been dropped.
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+
+    /// Reinterprets this tape for a different evaluator family
+    ///
+    /// The tape's register allocation is unchanged, so `G` must be able to
+    /// evaluate tapes which were planned with this tape's register limit.
+    pub(crate) fn cast<G: Family>(&self) -> Tape<G> {
+        assert!(self.reg_limit() <= G::REG_LIMIT);
+        Tape(self.0.clone(), core::marker::PhantomData)
     }
 
     /// Simplifies a tape based on the array of choices
@@ -52,10 +77,167 @@ impl<E: Family> Tape<E> {
         workspace: &mut Workspace,
         prev: Data,
     ) -> Result<Self, Error> {
+        let t = self.0.simplify_with(choices, workspace, prev)?;
+        if t.slot_count() > E::SLOT_LIMIT {
+            return Err(Error::TooManySlots(t.slot_count(), E::SLOT_LIMIT));
+        }
+        Ok(Tape(Arc::new(t), core::marker::PhantomData))
+    }
+
+    /// Replaces the named variable with a constant value, folding any
+    /// operations which become constant as a result.
+    ///
+    /// This works directly on the tape, so it's much cheaper than calling
+    /// [`Context::bind_constant`] and building a new tape.  See
+    /// [`Data::bind_constant`] for details.
+    pub fn bind_constant(&self, var: &str, value: f32) -> Result<Self, Error> {
         self.0
-            .simplify_with(choices, workspace, prev)
+            .bind_constant(var, value)
             .map(Arc::new)
-            .map(|t| Tape(t, std::marker::PhantomData))
+            .map(|t| Tape(t, core::marker::PhantomData))
+    }
+
+    /// Builds a tape from a new SSA tape, reusing this tape's work if possible
+    ///
+    /// If `ssa` differs from this tape's SSA tape only in its immediates, they
+    /// are patched into a copy of this tape, skipping register allocation and
+    /// scheduling; otherwise, the tape is built from scratch.  Either way,
+    /// evaluation settings are inherited from this tape.
+    pub(crate) fn update(&self, ssa: SsaTape) -> Self {
+        let t = if self.ssa.same_structure(&ssa) {
+            self.0.with_immediates(ssa)
+        } else {
+            Err(ssa)
+        };
+        let t = t.unwrap_or_else(|ssa| {
+            let mut t = Data::from_ssa(ssa, E::REG_LIMIT);
+            if self.scheduled {
+                t.asm.schedule();
+            }
+            Data {
+                bounds: self.bounds,
+                conservative: self.conservative,
+                nan_policy: self.nan_policy,
+                sign: self.sign,
+                scheduled: self.scheduled,
+                constant_table: self.constant_table,
+                ..t
+            }
+        });
+        Tape(Arc::new(t), core::marker::PhantomData)
+    }
+
+    /// Attaches a bounding box to the tape
+    ///
+    /// The tape's value must be strictly positive outside of the box, which
+    /// lets renderers and meshers skip those regions entirely.  Tapes built
+    /// with [`Context::get_tape`] are automatically given the bounds from
+    /// [`Context::bounds`].
+    pub fn with_bounds(mut self, bounds: BoundingBox) -> Self {
+        Arc::make_mut(&mut self.0).bounds = bounds;
+        self
+    }
+
+    /// Selects conservative interval arithmetic for this tape
+    ///
+    /// By default, interval evaluators use ordinary `f32` operations, which
+    /// round to nearest and may under-approximate a result's bounds by a
+    /// fraction of an ulp.  In conservative mode, the VM and JIT interval
+    /// evaluators widen the result of every inexact operation with
+    /// [`Interval::widen`](crate::eval::types::Interval::widen), so the
+    /// true value is always contained in the result (at a small cost in
+    /// speed and tightness).
+    ///
+    /// This setting is inherited by simplified tapes.  It must be selected
+    /// before building evaluators, because JIT evaluators bake it into their
+    /// compiled code.
+    pub fn with_conservative_intervals(mut self, enable: bool) -> Self {
+        Arc::make_mut(&mut self.0).conservative = enable;
+        self
+    }
+
+    /// Selects how `min` and `max` handle `NaN` arguments
+    ///
+    /// The default is [`NanPolicy::Propagate`]; see [`NanPolicy`] for details.
+    /// Like [`Tape::with_conservative_intervals`], this setting is inherited
+    /// by simplified tapes and must be selected before building evaluators.
+    pub fn with_nan_policy(mut self, policy: NanPolicy) -> Self {
+        Arc::make_mut(&mut self.0).nan_policy = policy;
+        self
+    }
+
+    /// Selects the sign convention of the tape's expression
+    ///
+    /// The default is [`SignConvention::NegativeInside`].  For a
+    /// [`SignConvention::PositiveInside`] tape, evaluators negate every
+    /// result (including derivatives and intervals), so renderers, meshers,
+    /// and raycasters see the shape's interior as negative without an extra
+    /// operation in the tape.  The tape's [bounds](Tape::with_bounds) apply to
+    /// the negated value.
+    ///
+    /// Like [`Tape::with_conservative_intervals`], this setting is inherited
+    /// by simplified tapes and must be selected before building evaluators.
+    pub fn with_sign_convention(mut self, sign: SignConvention) -> Self {
+        Arc::make_mut(&mut self.0).sign = sign;
+        self
+    }
+
+    /// Swaps the inside and outside of the shape
+    ///
+    /// This inverts the tape's [sign convention](Tape::with_sign_convention).
+    ///
+    /// ```
+    /// # let mut ctx = fidget::context::Context::new();
+    /// let x = ctx.x();
+    /// let tape = ctx.get_tape::<fidget::vm::Eval>(x)?.invert();
+    /// let eval = tape.new_point_evaluator();
+    /// assert_eq!(eval.eval(2.0, 0.0, 0.0, &[])?.0, -2.0);
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn invert(self) -> Self {
+        let sign = self.sign_convention().invert();
+        self.with_sign_convention(sign)
+    }
+
+    /// Selects whether operations are reordered to hide instruction latency
+    ///
+    /// After register allocation, most operations depend on the result of the
+    /// operation immediately before them, which stalls pipelined CPUs.  When
+    /// scheduling is enabled, independent operations are interleaved (without
+    /// changing the tape's result or choices), which speeds up JIT-compiled
+    /// code.  It is enabled by default for families which set
+    /// [`Family::SCHEDULE`].
+    ///
+    /// Like [`Tape::with_conservative_intervals`], this setting is inherited
+    /// by simplified tapes and must be selected before building evaluators.
+    pub fn with_scheduling(mut self, enable: bool) -> Self {
+        let data = Arc::make_mut(&mut self.0);
+        if data.scheduled != enable {
+            data.scheduled = enable;
+            data.asm = data.ssa.get_asm(data.asm.reg_limit());
+            if enable {
+                data.asm.schedule();
+            }
+        }
+        self
+    }
+
+    /// Selects whether JIT evaluators load immediates from a constant table
+    ///
+    /// By default, immediates are compiled directly into the JIT's machine
+    /// code.  With a constant table, they are instead loaded from an array
+    /// which is passed to the compiled function when it's called, which
+    /// shrinks code for tapes with many constants.  More importantly, a JIT
+    /// evaluator can then be [rebound](crate::eval::tracing::TracingEval::rebind) to a
+    /// tape which differs only in its constants (e.g. from
+    /// [`Context::update_tape`]) without compiling new code.
+    ///
+    /// This has no effect on the interpreter.  Like
+    /// [`Tape::with_conservative_intervals`], this setting is inherited by
+    /// simplified tapes and must be selected before building evaluators.
+    pub fn with_constant_table(mut self, enable: bool) -> Self {
+        Arc::make_mut(&mut self.0).constant_table = enable;
+        self
     }
 
     /// Tries to claim the inner [`Data`]
@@ -83,6 +265,22 @@ impl<E: Family> Tape<E> {
         eval::interval::IntervalEval::new_with_storage(self, storage)
     }
 
+    /// Builds an interval slice evaluator from the given `Tape`
+    pub fn new_interval_slice_evaluator(
+        &self,
+    ) -> eval::interval_slice::IntervalSliceEval<E> {
+        eval::interval_slice::IntervalSliceEval::new(self)
+    }
+
+    /// Builds an interval slice evaluator from the given `Tape`, reusing
+    /// storage
+    pub fn new_interval_slice_evaluator_with_storage(
+        &self,
+        storage: eval::interval_slice::IntervalSliceEvalStorage<E>,
+    ) -> eval::interval_slice::IntervalSliceEval<E> {
+        eval::interval_slice::IntervalSliceEval::new_with_storage(self, storage)
+    }
+
     /// Builds a float evaluator from the given `Tape`
     pub fn new_float_slice_evaluator(
         &self,
@@ -112,9 +310,31 @@ impl<E: Family> Tape<E> {
     ) -> eval::grad_slice::GradSliceEval<E> {
         eval::grad_slice::GradSliceEval::new_with_storage(self, storage)
     }
+
+    /// Builds a Hessian slice evaluator from the given `Tape`
+    pub fn new_hessian_slice_evaluator(
+        &self,
+    ) -> eval::hessian_slice::HessianSliceEval<E> {
+        eval::hessian_slice::HessianSliceEval::new(self)
+    }
+
+    /// Builds a Hessian slice evaluator from the given `Tape`, reusing storage
+    pub fn new_hessian_slice_evaluator_with_storage(
+        &self,
+        storage: eval::hessian_slice::HessianSliceEvalStorage<E>,
+    ) -> eval::hessian_slice::HessianSliceEval<E> {
+        eval::hessian_slice::HessianSliceEval::new_with_storage(self, storage)
+    }
 }
 
-impl<E> std::ops::Deref for Tape<E> {
+/// Content hash of a tape, returned by [`Data::content_hash`]
+///
+/// Unlike a tape's identity, this is stable across runs, so it's suitable for
+/// use as a key in persistent caches.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct TapeHash(pub(crate) u128);
+
+impl<E> core::ops::Deref for Tape<E> {
     type Target = Data;
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -137,10 +357,16 @@ impl<E: crate::eval::Family> TryFrom<(Node, Context)> for Tape<E> {
 ///   suitable for use during tape simplification
 /// - A tape in register-allocated form ([`vm::Tape`](VmTape)), which can be
 ///   efficiently evaluated or lowered into machine assembly
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Data {
     ssa: SsaTape,
     asm: VmTape,
+    bounds: BoundingBox,
+    conservative: bool,
+    nan_policy: NanPolicy,
+    sign: SignConvention,
+    scheduled: bool,
+    constant_table: bool,
 }
 
 impl Data {
@@ -171,7 +397,120 @@ impl Data {
     /// complete [`Data`](Self).
     pub fn from_ssa(ssa: SsaTape, reg_limit: u8) -> Self {
         let asm = ssa.get_asm(reg_limit);
-        Self { ssa, asm }
+        Self {
+            ssa,
+            asm,
+            bounds: BoundingBox::INFINITE,
+            conservative: false,
+            nan_policy: NanPolicy::default(),
+            sign: SignConvention::default(),
+            scheduled: false,
+            constant_table: false,
+        }
+    }
+
+    /// Returns the bounding box of the tape's interior
+    ///
+    /// The tape is guaranteed to be strictly positive outside of this box;
+    /// it is infinite if no bounds are known.
+    pub fn bounds(&self) -> BoundingBox {
+        self.bounds
+    }
+
+    /// Checks whether interval evaluation should widen inexact results
+    ///
+    /// See [`Tape::with_conservative_intervals`] for details.
+    pub fn conservative_intervals(&self) -> bool {
+        self.conservative
+    }
+
+    /// Returns the policy for `NaN` arguments to `min` and `max`
+    ///
+    /// See [`Tape::with_nan_policy`] for details.
+    pub fn nan_policy(&self) -> NanPolicy {
+        self.nan_policy
+    }
+
+    /// Returns the sign convention of the tape's expression
+    ///
+    /// See [`Tape::with_sign_convention`] for details.
+    pub fn sign_convention(&self) -> SignConvention {
+        self.sign
+    }
+
+    /// Converts a raw result into a negative-inside value
+    pub(crate) fn signed<T: core::ops::Neg<Output = T>>(&self, v: T) -> T {
+        match self.sign {
+            SignConvention::NegativeInside => v,
+            SignConvention::PositiveInside => -v,
+        }
+    }
+
+    /// Converts a slice of raw results into negative-inside values
+    pub(crate) fn sign_slice<T: Clone + core::ops::Neg<Output = T>>(
+        &self,
+        out: &mut [T],
+    ) {
+        if self.sign == SignConvention::PositiveInside {
+            for v in out {
+                *v = -v.clone();
+            }
+        }
+    }
+
+    /// Checks whether the VM tape's operations have been scheduled
+    ///
+    /// See [`Tape::with_scheduling`] for details.
+    pub fn scheduled(&self) -> bool {
+        self.scheduled
+    }
+
+    /// Checks whether JIT evaluators load immediates from a constant table
+    ///
+    /// See [`Tape::with_constant_table`] for details.
+    pub fn constant_table(&self) -> bool {
+        self.constant_table
+    }
+
+    /// Returns the immediates of the VM tape, in evaluation order
+    ///
+    /// This is the constant table used by JIT evaluators (see
+    /// [`Tape::with_constant_table`]).
+    #[cfg(feature = "jit")]
+    pub(crate) fn immediates(&self) -> Vec<f32> {
+        self.iter_asm().filter_map(|op| op.imm()).collect()
+    }
+
+    /// Checks whether two tapes compile to the same code when their
+    /// immediates are loaded from a constant table
+    ///
+    /// Custom operations must be the same objects, not merely equivalent.
+    #[cfg(feature = "jit")]
+    pub(crate) fn same_code(&self, other: &Self) -> bool {
+        let strip = |mut op: VmOp| {
+            if let Some(imm) = op.imm_mut() {
+                *imm = 0.0;
+            }
+            op
+        };
+        self.constant_table
+            && other.constant_table
+            && self.conservative == other.conservative
+            && self.nan_policy == other.nan_policy
+            && self.var_count() == other.var_count()
+            && self.slot_count() == other.slot_count()
+            && self.len() == other.len()
+            && self.custom_ops().len() == other.custom_ops().len()
+            && self
+                .custom_ops()
+                .iter()
+                .zip(other.custom_ops())
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+            && self
+                .asm
+                .iter()
+                .zip(other.asm.iter())
+                .all(|(a, b)| strip(*a) == strip(*b))
     }
 
     /// Returns the number of slots used by the inner VM tape
@@ -184,6 +523,21 @@ impl Data {
         self.ssa.vars.len()
     }
 
+    /// Returns the user-defined operations used by this tape
+    ///
+    /// These are indexed by [`vm::Op::CustomRegReg`](crate::vm::Op).
+    pub fn custom_ops(&self) -> &[Arc<dyn CustomOp>] {
+        &self.ssa.custom
+    }
+
+    /// Returns a shared handle to the user-defined operations
+    ///
+    /// JIT functions point into this list, so they must keep it alive.
+    #[cfg(feature = "jit")]
+    pub(crate) fn shared_custom_ops(&self) -> Arc<Vec<Arc<dyn CustomOp>>> {
+        self.ssa.custom.clone()
+    }
+
     /// Returns the register limit of the VM tape
     ///
     /// Note that this may exceed the slot count on particularly short (or
@@ -192,6 +546,80 @@ impl Data {
         self.asm.reg_limit()
     }
 
+    /// Computes a content hash of this tape
+    ///
+    /// The hash covers everything which affects evaluation results (the SSA
+    /// operations, variable names, and evaluation settings), so tapes built
+    /// from identical expressions have the same hash, even in different
+    /// processes.  Custom operations are identified by
+    /// [`name`](CustomOp::name), so each custom operation should have a
+    /// unique name.
+    pub fn content_hash(&self) -> TapeHash {
+        /// Adapter to format operations directly into the hasher
+        struct Writer(Fnv);
+        impl Write for Writer {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                self.0.write(s.as_bytes());
+                Ok(())
+            }
+        }
+
+        let mut w = Writer(Fnv::new(0));
+        for (name, i) in self.ssa.vars.iter() {
+            w.0.write_str(name);
+            w.0.write(&i.to_le_bytes());
+        }
+        for op in &*self.ssa.custom {
+            w.0.write_str(op.name());
+        }
+        for op in &self.ssa.tape {
+            writeln!(w, "{op}").unwrap();
+        }
+        writeln!(
+            w,
+            "{} {:?} {:?}",
+            self.conservative, self.nan_policy, self.sign
+        )
+        .unwrap();
+        TapeHash(w.0.value())
+    }
+
+    /// Copies this tape, taking immediates from an SSA tape which differs from
+    /// this tape's SSA tape only in their values
+    ///
+    /// Immediates in the VM tape are matched by value, so this fails (returning
+    /// the SSA tape) if two operations which shared an immediate now have
+    /// different immediates.
+    fn with_immediates(&self, ssa: SsaTape) -> Result<Self, SsaTape> {
+        let mut remap = BTreeMap::new();
+        for (a, b) in self.ssa.tape.iter().zip(&ssa.tape) {
+            let (mut a, mut b) = (*a, *b);
+            if let (Some(a), Some(b)) = (a.imm_mut(), b.imm_mut()) {
+                let prev = remap.entry(a.to_bits()).or_insert(b.to_bits());
+                if *prev != b.to_bits() {
+                    return Err(ssa);
+                }
+            }
+        }
+        let mut asm = self.asm.clone();
+        for imm in asm.iter_mut().filter_map(VmOp::imm_mut) {
+            match remap.get(&imm.to_bits()) {
+                Some(v) => *imm = f32::from_bits(*v),
+                None => return Err(ssa),
+            }
+        }
+        Ok(Data {
+            ssa,
+            asm,
+            bounds: self.bounds,
+            conservative: self.conservative,
+            nan_policy: self.nan_policy,
+            sign: self.sign,
+            scheduled: self.scheduled,
+            constant_table: self.constant_table,
+        })
+    }
+
     /// Simplifies both inner tapes, using the provided choice array
     ///
     /// To minimize allocations, this function takes a [`Workspace`](Workspace)
@@ -202,6 +630,7 @@ impl Data {
         workspace: &mut Workspace,
         mut tape: Data,
     ) -> Result<Self, Error> {
+        span!(TRACE, "simplify", len = self.len());
         if choices.len() != self.choice_count() {
             return Err(Error::BadChoiceSlice(
                 choices.len(),
@@ -225,8 +654,9 @@ impl Data {
         let mut choice_iter = choices.iter().rev();
 
         let mut ops_out = tape.ssa.tape;
+        let mut symbols_out = tape.ssa.symbols;
 
-        for mut op in self.ssa.tape.iter().cloned() {
+        for (i, mut op) in self.ssa.tape.iter().cloned().enumerate() {
             let index = op.output();
 
             if workspace.active(index).is_none() {
@@ -251,6 +681,8 @@ impl Data {
                 | SsaOp::AbsReg(index, arg)
                 | SsaOp::RecipReg(index, arg)
                 | SsaOp::SqrtReg(index, arg)
+                | SsaOp::SinReg(index, arg)
+                | SsaOp::CosReg(index, arg)
                 | SsaOp::SquareReg(index, arg) => {
                     *index = new_index;
                     *arg = workspace.get_or_insert_active(*arg);
@@ -328,7 +760,13 @@ impl Data {
                 SsaOp::AddRegReg(index, lhs, rhs)
                 | SsaOp::MulRegReg(index, lhs, rhs)
                 | SsaOp::SubRegReg(index, lhs, rhs)
-                | SsaOp::DivRegReg(index, lhs, rhs) => {
+                | SsaOp::DivRegReg(index, lhs, rhs)
+                | SsaOp::Atan2RegReg(index, lhs, rhs)
+                | SsaOp::HypotRegReg(index, lhs, rhs)
+                | SsaOp::ModRegReg(index, lhs, rhs)
+                | SsaOp::MinNcRegReg(index, lhs, rhs)
+                | SsaOp::MaxNcRegReg(index, lhs, rhs)
+                | SsaOp::CustomRegReg(index, lhs, rhs, ..) => {
                     *index = new_index;
                     *lhs = workspace.get_or_insert_active(*lhs);
                     *rhs = workspace.get_or_insert_active(*rhs);
@@ -338,40 +776,333 @@ impl Data {
                 | SsaOp::SubRegImm(index, arg, _imm)
                 | SsaOp::SubImmReg(index, arg, _imm)
                 | SsaOp::DivRegImm(index, arg, _imm)
-                | SsaOp::DivImmReg(index, arg, _imm) => {
+                | SsaOp::DivImmReg(index, arg, _imm)
+                | SsaOp::Atan2RegImm(index, arg, _imm)
+                | SsaOp::Atan2ImmReg(index, arg, _imm)
+                | SsaOp::HypotRegImm(index, arg, _imm)
+                | SsaOp::ModRegImm(index, arg, _imm)
+                | SsaOp::ModImmReg(index, arg, _imm)
+                | SsaOp::MinNcRegImm(index, arg, _imm)
+                | SsaOp::MaxNcRegImm(index, arg, _imm) => {
                     *index = new_index;
                     *arg = workspace.get_or_insert_active(*arg);
                 }
+                SsaOp::Noise(index, base, _) | SsaOp::Clamp(index, base) => {
+                    // The arguments are only written immediately before this
+                    // operation, so they haven't been seen yet, and are
+                    // assigned consecutive slots here.
+                    *index = new_index;
+                    let new_base = workspace.get_or_insert_active(*base);
+                    for i in 1..3 {
+                        let slot = workspace.get_or_insert_active(*base + i);
+                        assert_eq!(slot, new_base + i);
+                    }
+                    *base = new_base;
+                }
+                SsaOp::MinMany(index, base, count)
+                | SsaOp::MaxMany(index, base, count) => {
+                    // The group's choices are stored in evaluation order, so
+                    // we can pick them out of the array as a single slice.
+                    let n = *count as usize - 1;
+                    let end = choice_iter.len();
+                    let group = &choices[end - n..end];
+                    choice_iter.nth(n - 1).unwrap();
+
+                    let mut live = core::mem::take(&mut workspace.live);
+                    live.clear();
+                    live_args(group, *base, *count, &mut live);
+                    if let [arg] = live[..] {
+                        // As with a binary min/max, the output is an alias for
+                        // the only live argument (which hasn't been seen yet)
+                        workspace.set_active(arg, new_index);
+                        workspace.live = live;
+                        continue;
+                    }
+                    choice_count += live.len() - 1;
+                    *index = new_index;
+                    let new_base = workspace.get_or_insert_active(live[0]);
+                    for (i, arg) in live.iter().enumerate().skip(1) {
+                        let slot = workspace.get_or_insert_active(*arg);
+                        assert_eq!(slot, new_base + i as u32);
+                    }
+                    *base = new_base;
+                    *count = live.len() as u32;
+                    workspace.live = live;
+                }
             }
-            workspace.alloc.op(op);
+            let symbol = self.ssa.symbols.get(i).cloned().unwrap_or(u32::MAX);
+            push_symbol(&mut symbols_out, ops_out.len(), symbol);
             ops_out.push(op);
         }
 
         assert_eq!(workspace.count as usize, ops_out.len());
-        let asm_tape = workspace.alloc.finalize();
+        let ssa = SsaTape {
+            tape: ops_out,
+            choice_count,
+            vars: self.ssa.vars.clone(),
+            names: self.ssa.names.clone(),
+            symbols: symbols_out,
+            custom: self.ssa.custom.clone(),
+        };
+
+        // Register allocation is done in a second pass, because fusing
+        // operations requires knowing how many times each value is used.
+        ssa.allocate(&mut workspace.alloc);
+        let mut asm_tape = workspace.alloc.finalize();
+        if self.scheduled {
+            asm_tape.schedule();
+        }
+        event!(TRACE, len = asm_tape.len(), "simplified tape");
 
         Ok(Data {
-            ssa: SsaTape {
-                tape: ops_out,
-                choice_count,
-                vars: self.ssa.vars.clone(),
-            },
+            ssa,
             asm: asm_tape,
+            bounds: self.bounds,
+            conservative: self.conservative,
+            nan_policy: self.nan_policy,
+            sign: self.sign,
+            scheduled: self.scheduled,
+            constant_table: self.constant_table,
         })
     }
 
+    /// Replaces the named variable with a constant value, folding any
+    /// operations which become constant as a result.
+    ///
+    /// The variable array is unchanged, so evaluators built from the new tape
+    /// still expect a value for `var` (which is ignored).  The number of
+    /// choices may decrease, because `min` and `max` nodes can be folded away.
+    ///
+    /// Returns [`Error::UnknownVariable`] if `var` is not used in this tape.
+    pub fn bind_constant(&self, var: &str, value: f32) -> Result<Self, Error> {
+        let index = *self
+            .ssa
+            .vars
+            .get(var)
+            .ok_or_else(|| Error::UnknownVariable(var.to_owned()))?;
+        let ssa = self.ssa.bind_var(index, value);
+
+        // Run a simplification pass (without making any choices) to remove
+        // dead code and perform register allocation.
+        let choices = vec![Choice::Both; ssa.choice_count];
+        let folded = Data {
+            ssa,
+            asm: VmTape::new(self.asm.reg_limit()),
+            bounds: self.bounds,
+            conservative: self.conservative,
+            nan_policy: self.nan_policy,
+            sign: self.sign,
+            scheduled: self.scheduled,
+            constant_table: self.constant_table,
+        };
+        folded.simplify_with(
+            &choices,
+            &mut Default::default(),
+            Default::default(),
+        )
+    }
+
     /// Produces an iterator that visits [`vm::Op`](crate::vm::Op) values in
     /// evaluation order.
     pub fn iter_asm(&self) -> impl Iterator<Item = VmOp> + '_ {
         self.asm.iter().cloned().rev()
     }
 
+    /// Produces an iterator that visits [`vm::Op`](crate::vm::Op) values in
+    /// evaluation order, along with the name of the node which produced each
+    /// operation (see [`Context::name`](crate::context::Context::name))
+    pub fn iter_asm_named(
+        &self,
+    ) -> impl Iterator<Item = (VmOp, Option<&str>)> + '_ {
+        self.asm.iter().enumerate().rev().map(|(i, op)| {
+            let name = self
+                .asm
+                .symbol(i)
+                .and_then(|s| self.ssa.names.get(s as usize))
+                .map(String::as_str);
+            (*op, name)
+        })
+    }
+
+    /// Returns a human-readable listing of the inner VM tape
+    ///
+    /// Operations are listed in evaluation order, one per line, showing
+    /// registers (`r0`), memory slots (`m256`), and immediates.  Each `min` and
+    /// `max` operation is annotated with its index in the choice array, and
+    /// operations from named nodes (see
+    /// [`Context::name`](crate::context::Context::name)) with their name.
+    ///
+    /// ```
+    /// # use fidget::{context::Context, vm};
+    /// let mut ctx = Context::new();
+    /// let x = ctx.x();
+    /// let y = ctx.y();
+    /// let y1 = ctx.add(y, 1.0).unwrap();
+    /// ctx.name(y1, "shifted").unwrap();
+    /// let m = ctx.min(x, y1).unwrap();
+    ///
+    /// let tape = ctx.get_tape::<vm::Eval>(m).unwrap();
+    /// assert_eq!(
+    ///     tape.disassemble(),
+    ///     "\
+    /// ; 4 ops, 2 slots, 1 choices
+    ///    0  r0 = INPUT 0
+    ///    1  r1 = INPUT 1          ; shifted
+    ///    2  r1 = ADD r1 1         ; shifted
+    ///    3  r0 = MIN r0 r1        ; choice 0
+    /// "
+    /// );
+    /// ```
+    pub fn disassemble(&self) -> String {
+        let mut out = format!(
+            "; {} ops, {} slots, {} choices\n",
+            self.len(),
+            self.slot_count(),
+            self.choice_count()
+        );
+        let mut choice = 0;
+        for (i, (op, name)) in self.iter_asm_named().enumerate() {
+            let mut notes = vec![];
+            if matches!(
+                op,
+                VmOp::MinRegImm(..)
+                    | VmOp::MaxRegImm(..)
+                    | VmOp::MinRegReg(..)
+                    | VmOp::MaxRegReg(..)
+            ) {
+                notes.push(format!("choice {choice}"));
+                choice += 1;
+            }
+            notes.extend(name.map(str::to_owned));
+
+            let line = format!("{i:>4}  {op}");
+            if notes.is_empty() {
+                writeln!(out, "{line}").unwrap();
+            } else {
+                writeln!(out, "{line:<27} ; {}", notes.join(", ")).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Returns the names attached to nodes in the original context
+    ///
+    /// Names are added with
+    /// [`Context::name`](crate::context::Context::name); a name's index in
+    /// this slice is its material ID (see [`material`](Self::material)).
+    pub fn names(&self) -> &[String] {
+        &self.ssa.names
+    }
+
+    /// Finds the material which produced a result, given its choice array
+    ///
+    /// Starting from the root, this follows the branch taken by each `min` and
+    /// `max` (a tie takes the left-hand branch), stopping at the first other
+    /// operation.  The result is the material ID of that operation's name
+    /// (i.e. the name of the nearest named ancestor of the winning branch),
+    /// as an index into [`names`](Self::names), or `None` if it's unnamed.
+    ///
+    /// The choice array is typically the result of a tracing evaluation at a
+    /// single point, so that each primitive in a union of named shapes can be
+    /// colored separately without evaluating any extra fields.
+    ///
+    /// ```
+    /// # use fidget::{context::Context, vm};
+    /// let mut ctx = Context::new();
+    /// let x = ctx.x();
+    /// let left = ctx.add(x, 1.0)?;
+    /// ctx.name(left, "left")?;
+    /// let right = ctx.sub(1.0, x)?;
+    /// ctx.name(right, "right")?;
+    /// let root = ctx.max(left, right)?;
+    ///
+    /// let tape = ctx.get_tape::<vm::Eval>(root)?;
+    /// let eval = tape.new_point_evaluator();
+    /// let (_, trace) = eval.eval(-0.5, 0.0, 0.0, &[])?;
+    /// let id = tape.material(trace.unwrap().choices())?.unwrap();
+    /// assert_eq!(tape.names()[id as usize], "right");
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    ///
+    /// Returns [`Error::BadChoiceSlice`] if the choice array has the wrong
+    /// length.
+    pub fn material(&self, choices: &[Choice]) -> Result<Option<u32>, Error> {
+        if choices.len() != self.choice_count() {
+            return Err(Error::BadChoiceSlice(
+                choices.len(),
+                self.choice_count(),
+            ));
+        }
+        // The SSA tape is root-first, so choices are consumed in reverse
+        let mut choice = choices.len();
+        let mut target = 0;
+        let mut symbol = None;
+        for (i, op) in self.ssa.tape.iter().enumerate() {
+            choice -= op.choice_count();
+            if op.output() != target {
+                continue;
+            }
+            symbol = self.ssa.symbols.get(i).filter(|s| **s != u32::MAX);
+            let next = match *op {
+                SsaOp::MinRegReg(_, lhs, rhs)
+                | SsaOp::MaxRegReg(_, lhs, rhs) => match choices[choice] {
+                    Choice::Left | Choice::Both => Some(lhs),
+                    Choice::Right => Some(rhs),
+                    Choice::Unknown => None,
+                },
+                SsaOp::MinRegImm(_, arg, _) | SsaOp::MaxRegImm(_, arg, _) => {
+                    match choices[choice] {
+                        Choice::Left | Choice::Both => Some(arg),
+                        Choice::Right | Choice::Unknown => None,
+                    }
+                }
+                SsaOp::CopyReg(_, src) => Some(src),
+                _ => None,
+            };
+            match next {
+                Some(n) => target = n,
+                None => break,
+            }
+        }
+        Ok(symbol.cloned())
+    }
+
     /// Pretty-prints the inner SSA tape
+    ///
+    /// Operations are annotated with the name of the node which produced them
+    /// (see [`Context::name`](crate::context::Context::name)).  This requires
+    /// the `std` feature.
+    #[cfg(feature = "std")]
     pub fn pretty_print(&self) {
         self.ssa.pretty_print()
     }
 }
 
+/// Collects the live arguments of an n-ary `min` or `max`
+///
+/// The arguments are in `count` consecutive slots starting at `base`, and are
+/// combined with a balanced tree of binary operations when lowered to the VM.
+/// `choices` are the tree's choices in evaluation order: those of the left
+/// subtree, then those of the right subtree, then its own.
+fn live_args(choices: &[Choice], base: u32, count: u32, out: &mut Vec<u32>) {
+    if count == 1 {
+        out.push(base);
+        return;
+    }
+    let mid = count / 2;
+    let (lhs, rest) = choices.split_at(mid as usize - 1);
+    let (rhs, own) = rest.split_at((count - mid) as usize - 1);
+    match own[0] {
+        Choice::Left => live_args(lhs, base, mid, out),
+        Choice::Right => live_args(rhs, base + mid, count - mid, out),
+        Choice::Both => {
+            live_args(lhs, base, mid, out);
+            live_args(rhs, base + mid, count - mid, out);
+        }
+        Choice::Unknown => panic!("oh no"),
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Data structures used during [`Tape::simplify`]
@@ -389,6 +1120,9 @@ pub struct Workspace {
     /// This value is monotonically increasing; each SSA variable gets the next
     /// value if it is unassigned when encountered.
     count: u32,
+
+    /// Live arguments of an n-ary operation, reused between operations
+    live: Vec<u32>,
 }
 
 impl Default for Workspace {
@@ -397,6 +1131,7 @@ impl Default for Workspace {
             alloc: RegisterAllocator::empty(),
             bind: vec![],
             count: 0,
+            live: vec![],
         }
     }
 }
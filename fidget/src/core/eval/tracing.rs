@@ -11,9 +11,10 @@
 //! they're implementation details to minimize code duplication.
 
 use crate::{
-    eval::{EvaluatorStorage, Family, Tape},
+    eval::{cache::CacheStats, EvaluatorStorage, Family, Tape},
     Error,
 };
+use alloc::vec::Vec;
 
 /// A single choice made at a min/max node.
 ///
@@ -45,7 +46,7 @@ pub enum Choice {
     Both = 3,
 }
 
-impl std::ops::BitOrAssign<Choice> for Choice {
+impl core::ops::BitOrAssign<Choice> for Choice {
     fn bitor_assign(&mut self, other: Self) {
         *self = match (*self as u8) | (other as u8) {
             0 => Self::Unknown,
@@ -57,6 +58,52 @@ impl std::ops::BitOrAssign<Choice> for Choice {
     }
 }
 
+/// Reusable buffer of [`Choice`] values
+///
+/// Clearing the choice array before every evaluation is a significant source
+/// of memory traffic for large tapes (e.g. when evaluating many tiles during
+/// rendering).  Instead, tracing evaluators assign every choice in the tape, so
+/// stale values from a previous evaluation are always overwritten.  The buffer
+/// tracks an epoch, which is incremented each time it is prepared, to
+/// distinguish successive evaluations.
+#[derive(Clone, Debug, Default)]
+pub struct ChoiceBuffer {
+    choices: Vec<Choice>,
+    epoch: u64,
+}
+
+impl ChoiceBuffer {
+    /// Builds a new, empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepares the buffer for an evaluation with `n` choices
+    ///
+    /// This only initializes newly-allocated items; existing items are left
+    /// as-is, and must be overwritten by the evaluator.
+    pub fn prepare(&mut self, n: usize) -> &mut [Choice] {
+        self.choices.resize(n, Choice::Unknown);
+        self.epoch += 1;
+        &mut self.choices
+    }
+
+    /// Returns the number of times that this buffer has been prepared
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Returns the choices from the most recent evaluation
+    pub fn as_slice(&self) -> &[Choice] {
+        &self.choices
+    }
+
+    /// Consumes the buffer, returning the inner array of choices
+    pub fn into_vec(self) -> Vec<Choice> {
+        self.choices
+    }
+}
+
 /// A tracing evaluator performs evaluation of a single `T`, capturing a trace
 /// of execution for further simplification.
 ///
@@ -68,6 +115,10 @@ pub trait TracingEvaluator<T, F> {
 
     /// Evaluates the given value, using `choices` and `data` as scratch memory.
     ///
+    /// Every item in `choices` must be assigned during evaluation (rather than
+    /// combined with its previous value), because the buffer is reused between
+    /// evaluations without being cleared; see [`ChoiceBuffer`].
+    ///
     /// # Panics
     /// If `vars` or `choices` is of the incorrect length, this function is
     /// allowed (encouraged, even) to panic.
@@ -110,12 +161,13 @@ pub struct TracingEval<T, E, F> {
     eval: E,
     tape: Tape<F>,
 
-    _p: std::marker::PhantomData<fn(T) -> T>,
+    _p: core::marker::PhantomData<fn(T) -> T>,
 }
 
 impl<T, E, F: Family> TracingEval<T, E, F>
 where
     E: TracingEvaluator<T, F> + EvaluatorStorage<F>,
+    T: core::ops::Neg<Output = T>,
 {
     /// Builds a new evaluator for the given tape, allocating new storage
     pub fn new(tape: &Tape<F>) -> Self {
@@ -133,7 +185,22 @@ where
         Self {
             eval,
             tape: tape.clone(),
-            _p: std::marker::PhantomData,
+            _p: core::marker::PhantomData,
+        }
+    }
+
+    /// Builds an evaluator for a tape which differs from this evaluator's tape
+    /// only in the values of its constants
+    ///
+    /// This is much cheaper than building a new evaluator if the tape uses a
+    /// [constant table](Tape::with_constant_table) and the evaluator is
+    /// JIT-compiled, because the compiled code is reused.  Otherwise, it's
+    /// equivalent to [`Self::new`].
+    pub fn rebind(&self, tape: &Tape<F>) -> Self {
+        Self {
+            eval: self.eval.rebind(tape),
+            tape: tape.clone(),
+            _p: core::marker::PhantomData,
         }
     }
 
@@ -164,19 +231,19 @@ where
             y.into(),
             z.into(),
             vars,
-            &mut data.choices,
+            data.choices.prepare(self.tape.choice_count()),
             &mut data.data,
         );
         let r = if simplify {
             Some(TracingEvalResult {
                 choices: data.choices.as_slice(),
                 tape: self.tape.clone(),
-                _p: std::marker::PhantomData,
+                _p: core::marker::PhantomData,
             })
         } else {
             None
         };
-        Ok((value, r))
+        Ok((self.tape.signed(value), r))
     }
 
     /// Evaluates, allocating scratch memory if required.
@@ -197,9 +264,9 @@ where
         // Vec<Choice> itself.
         let r = if r.is_some() {
             Some(TracingEvalResult {
-                choices: data.choices,
+                choices: data.choices.into_vec(),
                 tape: self.tape.clone(),
-                _p: std::marker::PhantomData,
+                _p: core::marker::PhantomData,
             })
         } else {
             None
@@ -212,7 +279,7 @@ where
 impl<T, E, F: Family> TracingEval<T, E, F>
 where
     E: TracingEvaluator<T, F> + EvaluatorStorage<F>,
-    T: From<f32>,
+    T: From<f32> + core::ops::Neg<Output = T>,
 {
     /// Performs interval evaluation, using zeros for Y and Z and no `vars`
     ///
@@ -237,26 +304,28 @@ where
 
 /// Generic data associated with a tracing evaluator
 ///
-/// This data is used during evaluator.
+/// This data is used during evaluation, and should be reused between
+/// successive evaluations (e.g. of tiles when rendering) to avoid reallocating
+/// and clearing the choice array.
 pub struct TracingEvalData<D, F> {
-    choices: Vec<Choice>,
+    choices: ChoiceBuffer,
 
     /// Inner data
     data: D,
 
-    _p: std::marker::PhantomData<*const F>,
+    _p: core::marker::PhantomData<*const F>,
 }
 
 // SAFETY: this can't be derived because of Rust limitations, but we're sending
-// around a Vec<Choice> and a D, which should be fine.
+// around a ChoiceBuffer and a D, which should be fine.
 unsafe impl<D: Send, F> Send for TracingEvalData<D, F> {}
 
 impl<D: Default, F> Default for TracingEvalData<D, F> {
     fn default() -> Self {
         Self {
-            choices: vec![],
+            choices: ChoiceBuffer::new(),
             data: D::default(),
-            _p: std::marker::PhantomData,
+            _p: core::marker::PhantomData,
         }
     }
 }
@@ -271,7 +340,7 @@ impl<D: Default, F> Default for TracingEvalData<D, F> {
 pub struct TracingEvalResult<D, F, B> {
     choices: B,
     tape: Tape<F>,
-    _p: std::marker::PhantomData<*const D>,
+    _p: core::marker::PhantomData<*const D>,
 }
 
 /// Result of a tracing evaluation using owned data for the `Choice` array
@@ -283,23 +352,47 @@ pub type BorrowedTracingEvalResult<'a, T, F> =
 
 impl<D: TracingEvaluatorData<F>, F: Family> TracingEvalData<D, F> {
     /// Prepares for a tracing evaluation with the given tape size
+    ///
+    /// The choice array is prepared separately, since it's borrowed mutably
+    /// during evaluation.
     fn prepare(&mut self, tape: &Tape<F>) {
-        self.choices.resize(tape.choice_count(), Choice::Unknown);
-        self.choices.fill(Choice::Unknown);
         self.data.prepare(tape);
     }
+
+    /// Returns the number of evaluations which have used this data
+    pub fn epoch(&self) -> u64 {
+        self.choices.epoch()
+    }
 }
 
 impl<D, F, B> TracingEvalResult<D, F, B>
 where
     F: Family,
-    B: std::borrow::Borrow<[Choice]>,
+    B: core::borrow::Borrow<[Choice]>,
 {
+    /// Builds a trace from a choice array and the tape which produced it
+    pub(crate) fn new(choices: B, tape: Tape<F>) -> Self {
+        Self {
+            choices,
+            tape,
+            _p: core::marker::PhantomData,
+        }
+    }
+
     /// Simplifies the tape based on the most recent evaluation
     pub fn simplify(&self) -> Result<Tape<F>, Error> {
         self.simplify_with(&mut Default::default(), Default::default())
     }
 
+    /// Simplifies the tape based on the most recent evaluation, returning a
+    /// cached tape if the same choices have been seen before
+    pub fn simplify_cached(
+        &self,
+        cache: &mut crate::eval::cache::TapeCache<F>,
+    ) -> Result<Tape<F>, Error> {
+        cache.simplify(&self.tape, self.choices.borrow())
+    }
+
     /// Returns a read-only view into the [`Choice`](Choice) slice.
     ///
     /// This is a convenience function for unit testing.
@@ -307,6 +400,27 @@ where
         self.choices.borrow()
     }
 
+    /// Returns the tape which produced this trace
+    pub(crate) fn tape(&self) -> &Tape<F> {
+        &self.tape
+    }
+
+    /// Simplifies the tape based on the most recent evaluation, returning the
+    /// hint's tape if it was built from the same choices
+    ///
+    /// On a mismatch, the hint is updated with the newly simplified tape.
+    pub fn simplify_hinted(
+        &self,
+        hint: &mut ChoiceHint<F>,
+    ) -> Result<Tape<F>, Error> {
+        if let Some(tape) = hint.check(self) {
+            return Ok(tape);
+        }
+        let out = self.simplify()?;
+        hint.update(self, out.clone());
+        Ok(out)
+    }
+
     /// Simplifies the tape based on the most recent evaluation, reusing
     /// allocations to reduce memory churn.
     pub fn simplify_with(
@@ -317,3 +431,133 @@ where
         Tape::simplify_with(&self.tape, self.choices.borrow(), workspace, prev)
     }
 }
+
+/// Choices and simplified tape from a previous evaluation, used as a hint when
+/// evaluating a nearby region
+///
+/// Adjacent tiles usually take the same branches at every `min` and `max`
+/// node, so they produce identical simplified tapes.  Passing the same hint to
+/// [`TracingEvalResult::simplify_hinted`] for each tile in a coherent scan
+/// checks the new choices against the previous tile's choices, and reuses the
+/// previous tape (rather than traversing the full tape to simplify it) if they
+/// match.  Unlike a [`TapeCache`](crate::eval::cache::TapeCache), a hint only
+/// remembers one tape, but checking it doesn't require hashing the choices.
+///
+/// ```
+/// use fidget::{context::Context, eval::tracing::ChoiceHint, vm};
+///
+/// let mut ctx = Context::new();
+/// let (x, y) = (ctx.x(), ctx.y());
+/// let min = ctx.min(x, y)?;
+/// let tape = ctx.get_tape::<vm::Eval>(min)?;
+///
+/// let eval = tape.new_interval_evaluator();
+/// let mut hint = ChoiceHint::new();
+/// for i in 0..4 {
+///     let x = [i as f32, i as f32 + 1.0];
+///     let (_, trace) = eval.eval(x, [10.0, 11.0], [0.0; 2], &[])?;
+///     let simple = trace.unwrap().simplify_hinted(&mut hint)?;
+///     assert_eq!(simple.len(), 1); // the tape only reads `x`
+/// }
+/// assert_eq!(hint.stats().misses, 1);
+/// assert_eq!(hint.stats().hits, 3);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// The hint keeps its tapes alive, so tapes which it returns are shared and
+/// can't be reclaimed with [`Tape::take`] until the hint is cleared.
+pub struct ChoiceHint<F> {
+    /// Tape which was simplified, which is kept alive so that its identity
+    /// isn't reused
+    parent: Option<Tape<F>>,
+    /// Choices used to simplify `parent`
+    choices: Vec<Choice>,
+    /// Simplified tape
+    tape: Option<Tape<F>>,
+    stats: CacheStats,
+}
+
+impl<F> Default for ChoiceHint<F> {
+    fn default() -> Self {
+        Self {
+            parent: None,
+            choices: Vec::new(),
+            tape: None,
+            stats: CacheStats::default(),
+        }
+    }
+}
+
+impl<F: Family> ChoiceHint<F> {
+    /// Builds a new, empty hint
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks whether the hint's tape can be used for the given trace
+    pub fn matches<T, B: core::borrow::Borrow<[Choice]>>(
+        &self,
+        trace: &TracingEvalResult<T, F, B>,
+    ) -> bool {
+        match &self.parent {
+            Some(p) => {
+                p.id() == trace.tape().id()
+                    && self.choices == trace.choices.borrow()
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the hint's tape if it matches the given trace, recording a hit
+    /// or miss
+    pub(crate) fn check<T, B: core::borrow::Borrow<[Choice]>>(
+        &mut self,
+        trace: &TracingEvalResult<T, F, B>,
+    ) -> Option<Tape<F>> {
+        if self.matches(trace) {
+            self.stats.hits += 1;
+            self.tape.clone()
+        } else {
+            self.stats.misses += 1;
+            None
+        }
+    }
+
+    /// Replaces the hint with a newly simplified tape, returning the previous
+    /// tape (if present)
+    pub(crate) fn update<T, B: core::borrow::Borrow<[Choice]>>(
+        &mut self,
+        trace: &TracingEvalResult<T, F, B>,
+        tape: Tape<F>,
+    ) -> Option<Tape<F>> {
+        if self.tape.is_some() {
+            self.stats.evictions += 1;
+        }
+        self.parent = Some(trace.tape().clone());
+        self.choices.clear();
+        self.choices.extend_from_slice(trace.choices.borrow());
+        self.tape.replace(tape)
+    }
+
+    /// Returns the hint's simplified tape, if present
+    pub fn tape(&self) -> Option<&Tape<F>> {
+        self.tape.as_ref()
+    }
+
+    /// Clears the hint, returning its simplified tape (if present)
+    ///
+    /// The returned tape may be passed to
+    /// [`EvalContext::release_tape`](crate::eval::pool::EvalContext::release_tape)
+    /// to reuse its allocations.
+    pub fn take(&mut self) -> Option<Tape<F>> {
+        self.parent = None;
+        self.tape.take()
+    }
+
+    /// Returns hit / miss statistics for this hint
+    ///
+    /// `evictions` counts the number of times that the hint was replaced.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
@@ -1,5 +1,6 @@
 //! Custom types used during evaluation
 use crate::eval::Choice;
+use num_traits::{Float, FloatConst};
 
 /// A point in space with associated partial derivatives.
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
@@ -72,9 +73,69 @@ impl Grad {
         }
     }
 
+    /// Sine (in radians)
+    pub fn sin(self) -> Self {
+        let (s, c) = (self.v.sin(), self.v.cos());
+        Grad {
+            v: s,
+            dx: self.dx * c,
+            dy: self.dy * c,
+            dz: self.dz * c,
+        }
+    }
+
+    /// Cosine (in radians)
+    pub fn cos(self) -> Self {
+        let (s, c) = (self.v.sin(), self.v.cos());
+        Grad {
+            v: c,
+            dx: -self.dx * s,
+            dy: -self.dy * s,
+            dz: -self.dz * s,
+        }
+    }
+
+    /// Four-quadrant arctangent of `self / rhs` (i.e. `atan2(y, x)`)
+    pub fn atan2(self, rhs: Self) -> Self {
+        let d = self.v.powi(2) + rhs.v.powi(2);
+        Grad {
+            v: self.v.atan2(rhs.v),
+            dx: (rhs.v * self.dx - self.v * rhs.dx) / d,
+            dy: (rhs.v * self.dy - self.v * rhs.dy) / d,
+            dz: (rhs.v * self.dz - self.v * rhs.dz) / d,
+        }
+    }
+
+    /// Length of the hypotenuse, `sqrt(self² + rhs²)`
+    pub fn hypot(self, rhs: Self) -> Self {
+        let v = self.v.hypot(rhs.v);
+        Grad {
+            v,
+            dx: (self.v * self.dx + rhs.v * rhs.dx) / v,
+            dy: (self.v * self.dy + rhs.v * rhs.dy) / v,
+            dz: (self.v * self.dz + rhs.v * rhs.dz) / v,
+        }
+    }
+
+    /// Floored modulo, `self - rhs * floor(self / rhs)`
+    ///
+    /// The quotient is piecewise constant, so it doesn't contribute to the
+    /// partial derivatives.
+    pub fn modulo(self, rhs: Self) -> Self {
+        let k = (self.v / rhs.v).floor();
+        Grad {
+            v: self.v - rhs.v * k,
+            dx: self.dx - rhs.dx * k,
+            dy: self.dy - rhs.dy * k,
+            dz: self.dz - rhs.dz * k,
+        }
+    }
+
     /// Minimum of two values
+    ///
+    /// If either value is `NaN`, returns the `NaN` argument (preferring `self`)
     pub fn min(self, rhs: Self) -> Self {
-        if self.v < rhs.v {
+        if self.v.is_nan() || self.v < rhs.v {
             self
         } else {
             rhs
@@ -82,13 +143,23 @@ impl Grad {
     }
 
     /// Maximum of two values
+    ///
+    /// If either value is `NaN`, returns the `NaN` argument (preferring `self`)
     pub fn max(self, rhs: Self) -> Self {
-        if self.v > rhs.v {
+        if self.v.is_nan() || self.v > rhs.v {
             self
         } else {
             rhs
         }
     }
+
+    /// Clamps the value to the range `[lo, hi]`, i.e. `max(min(self, hi), lo)`
+    ///
+    /// If any value is `NaN`, returns the first `NaN` argument (in the order
+    /// `self`, `hi`, `lo`)
+    pub fn clamp(self, lo: Self, hi: Self) -> Self {
+        self.min(hi).max(lo)
+    }
 }
 
 impl From<f32> for Grad {
@@ -102,13 +173,14 @@ impl From<f32> for Grad {
     }
 }
 
+#[cfg(any(feature = "render", feature = "mesh"))]
 impl From<Grad> for nalgebra::Vector4<f32> {
     fn from(g: Grad) -> Self {
         nalgebra::Vector4::new(g.dx, g.dy, g.dz, g.v)
     }
 }
 
-impl std::ops::Add<Grad> for Grad {
+impl core::ops::Add<Grad> for Grad {
     type Output = Self;
     fn add(self, rhs: Self) -> Self {
         Grad {
@@ -120,7 +192,7 @@ impl std::ops::Add<Grad> for Grad {
     }
 }
 
-impl std::ops::Mul<Grad> for Grad {
+impl core::ops::Mul<Grad> for Grad {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self {
         Self {
@@ -132,7 +204,7 @@ impl std::ops::Mul<Grad> for Grad {
     }
 }
 
-impl std::ops::Div<Grad> for Grad {
+impl core::ops::Div<Grad> for Grad {
     type Output = Self;
     fn div(self, rhs: Self) -> Self {
         let d = rhs.v.powi(2);
@@ -145,7 +217,7 @@ impl std::ops::Div<Grad> for Grad {
     }
 }
 
-impl std::ops::Sub<Grad> for Grad {
+impl core::ops::Sub<Grad> for Grad {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self {
         Self {
@@ -157,7 +229,7 @@ impl std::ops::Sub<Grad> for Grad {
     }
 }
 
-impl std::ops::Neg for Grad {
+impl core::ops::Neg for Grad {
     type Output = Self;
     fn neg(self) -> Self {
         Self {
@@ -171,23 +243,324 @@ impl std::ops::Neg for Grad {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// A point in space with associated first and second partial derivatives
+///
+/// Derivatives are propagated with nested forward-mode automatic
+/// differentiation: each operation applies the chain rule to both the gradient
+/// and the (symmetric) Hessian matrix.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct Hessian {
+    /// Value of the distance field at this point
+    pub v: f32,
+    /// Partial derivatives with respect to `x`, `y`, and `z`
+    pub grad: [f32; 3],
+    /// Second partial derivatives, where `hess[i][j]` is the derivative with
+    /// respect to axes `i` and `j`
+    pub hess: [[f32; 3]; 3],
+}
+
+impl Hessian {
+    /// Constructs a new value with first and second derivatives
+    pub fn new(v: f32, grad: [f32; 3], hess: [[f32; 3]; 3]) -> Self {
+        Self { v, grad, hess }
+    }
+
+    /// Builds the value of an input coordinate on the given axis
+    ///
+    /// The input has a unit derivative along its own axis, and no second
+    /// derivatives.
+    ///
+    /// # Panics
+    /// If `axis >= 3`
+    pub fn input(v: f32, axis: usize) -> Self {
+        let mut out = Self::from(v);
+        out.grad[axis] = 1.0;
+        out
+    }
+
+    /// Returns the value and first derivatives, discarding second derivatives
+    pub fn to_grad(&self) -> Grad {
+        Grad::new(self.v, self.grad[0], self.grad[1], self.grad[2])
+    }
+
+    /// Returns the diagonal of the Hessian matrix
+    ///
+    /// These are the second derivatives with respect to `x`, `y`, and `z`.
+    pub fn diagonal(&self) -> [f32; 3] {
+        [self.hess[0][0], self.hess[1][1], self.hess[2][2]]
+    }
+
+    /// Returns the mean curvature of the level set passing through this point
+    ///
+    /// This is half of the divergence of the normalized gradient; it's `NaN`
+    /// if the gradient is zero.
+    pub fn mean_curvature(&self) -> f32 {
+        let g = self.grad;
+        let n2 = g[0] * g[0] + g[1] * g[1] + g[2] * g[2];
+        let trace = self.hess[0][0] + self.hess[1][1] + self.hess[2][2];
+        let mut ghg = 0.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                ghg += g[i] * self.hess[i][j] * g[j];
+            }
+        }
+        (n2 * trace - ghg) / (2.0 * n2 * n2.sqrt())
+    }
+
+    /// Applies a function using the chain rule
+    ///
+    /// `v` is the function's value, and `d1` and `d2` are its first and second
+    /// derivatives, all evaluated at `self.v`.
+    fn chain(self, v: f32, d1: f32, d2: f32) -> Self {
+        let mut out = Self::from(v);
+        for i in 0..3 {
+            out.grad[i] = d1 * self.grad[i];
+            for j in 0..3 {
+                out.hess[i][j] =
+                    d2 * self.grad[i] * self.grad[j] + d1 * self.hess[i][j];
+            }
+        }
+        out
+    }
+
+    /// Applies a two-argument function `f(self, rhs)` using the chain rule
+    ///
+    /// `v` is the function's value, `[fa, fb]` are its partial derivatives
+    /// with respect to each argument, and `[faa, fab, fbb]` are its second
+    /// partial derivatives.
+    fn chain2(
+        self,
+        rhs: Self,
+        v: f32,
+        [fa, fb]: [f32; 2],
+        [faa, fab, fbb]: [f32; 3],
+    ) -> Self {
+        let (a, b) = (self.grad, rhs.grad);
+        let mut out = Self::from(v);
+        for i in 0..3 {
+            out.grad[i] = fa * a[i] + fb * b[i];
+            for j in 0..3 {
+                out.hess[i][j] = faa * a[i] * a[j]
+                    + fab * (a[i] * b[j] + b[i] * a[j])
+                    + fbb * b[i] * b[j]
+                    + fa * self.hess[i][j]
+                    + fb * rhs.hess[i][j];
+            }
+        }
+        out
+    }
+
+    /// Applies a function to every value and derivative
+    fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Self {
+            v: f(self.v),
+            grad: self.grad.map(&f),
+            hess: self.hess.map(|row| row.map(&f)),
+        }
+    }
+
+    /// Combines every value and derivative of two values
+    fn zip(self, rhs: Self, f: impl Fn(f32, f32) -> f32) -> Self {
+        Self {
+            v: f(self.v, rhs.v),
+            grad: core::array::from_fn(|i| f(self.grad[i], rhs.grad[i])),
+            hess: core::array::from_fn(|i| {
+                core::array::from_fn(|j| f(self.hess[i][j], rhs.hess[i][j]))
+            }),
+        }
+    }
+
+    /// Absolute value
+    pub fn abs(self) -> Self {
+        if self.v < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    /// Square root
+    pub fn sqrt(self) -> Self {
+        let v = self.v.sqrt();
+        self.chain(v, 0.5 / v, -0.25 / (v * self.v))
+    }
+
+    /// Reciprocal
+    pub fn recip(self) -> Self {
+        let r = 1.0 / self.v;
+        self.chain(r, -r * r, 2.0 * r * r * r)
+    }
+
+    /// Sine (in radians)
+    pub fn sin(self) -> Self {
+        let (s, c) = (self.v.sin(), self.v.cos());
+        self.chain(s, c, -s)
+    }
+
+    /// Cosine (in radians)
+    pub fn cos(self) -> Self {
+        let (s, c) = (self.v.sin(), self.v.cos());
+        self.chain(c, -s, -c)
+    }
+
+    /// Four-quadrant arctangent of `self / rhs` (i.e. `atan2(y, x)`)
+    pub fn atan2(self, rhs: Self) -> Self {
+        let (y, x) = (self.v, rhs.v);
+        let d = y.powi(2) + x.powi(2);
+        let d2 = d * d;
+        self.chain2(
+            rhs,
+            y.atan2(x),
+            [x / d, -y / d],
+            [-2.0 * x * y / d2, (y * y - x * x) / d2, 2.0 * x * y / d2],
+        )
+    }
+
+    /// Length of the hypotenuse, `sqrt(self² + rhs²)`
+    pub fn hypot(self, rhs: Self) -> Self {
+        let (a, b) = (self.v, rhs.v);
+        let v = a.hypot(b);
+        let v3 = v * v * v;
+        self.chain2(
+            rhs,
+            v,
+            [a / v, b / v],
+            [b * b / v3, -a * b / v3, a * a / v3],
+        )
+    }
+
+    /// Floored modulo, `self - rhs * floor(self / rhs)`
+    ///
+    /// The result is piecewise linear, so it has no second derivatives of its
+    /// own.
+    pub fn modulo(self, rhs: Self) -> Self {
+        let k = (self.v / rhs.v).floor();
+        self.chain2(rhs, self.v - rhs.v * k, [1.0, -k], [0.0; 3])
+    }
+
+    /// Minimum of two values
+    ///
+    /// If either value is `NaN`, returns the `NaN` argument (preferring `self`)
+    pub fn min(self, rhs: Self) -> Self {
+        if self.v.is_nan() || self.v < rhs.v {
+            self
+        } else {
+            rhs
+        }
+    }
+
+    /// Maximum of two values
+    ///
+    /// If either value is `NaN`, returns the `NaN` argument (preferring `self`)
+    pub fn max(self, rhs: Self) -> Self {
+        if self.v.is_nan() || self.v > rhs.v {
+            self
+        } else {
+            rhs
+        }
+    }
+
+    /// Clamps the value to the range `[lo, hi]`, i.e. `max(min(self, hi), lo)`
+    ///
+    /// If any value is `NaN`, returns the first `NaN` argument (in the order
+    /// `self`, `hi`, `lo`)
+    pub fn clamp(self, lo: Self, hi: Self) -> Self {
+        self.min(hi).max(lo)
+    }
+}
+
+impl From<f32> for Hessian {
+    fn from(v: f32) -> Self {
+        Hessian {
+            v,
+            grad: [0.0; 3],
+            hess: [[0.0; 3]; 3],
+        }
+    }
+}
+
+impl core::ops::Add<Hessian> for Hessian {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        self.zip(rhs, |a, b| a + b)
+    }
+}
+
+impl core::ops::Mul<Hessian> for Hessian {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        self.chain2(rhs, self.v * rhs.v, [rhs.v, self.v], [0.0, 1.0, 0.0])
+    }
+}
+
+impl core::ops::Div<Hessian> for Hessian {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        let (a, b) = (self.v, rhs.v);
+        let b2 = b * b;
+        self.chain2(
+            rhs,
+            a / b,
+            [1.0 / b, -a / b2],
+            [0.0, -1.0 / b2, 2.0 * a / (b2 * b)],
+        )
+    }
+}
+
+impl core::ops::Sub<Hessian> for Hessian {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self.zip(rhs, |a, b| a - b)
+    }
+}
+
+impl core::ops::Neg for Hessian {
+    type Output = Self;
+    fn neg(self) -> Self {
+        self.map(|v| -v)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Floating-point type used for the bounds of an [`Interval`]
+///
+/// This is implemented for `f32`, which is used by every evaluator family, and
+/// for `f64`, which is used by the
+/// [double-precision interpreter](crate::vm::double).
+pub trait IntervalFloat:
+    Float + FloatConst + core::fmt::Debug + core::fmt::Display
+{
+}
+
+impl IntervalFloat for f32 {}
+impl IntervalFloat for f64 {}
+
 /// Stores a range, with conservative calculations to guarantee that it always
 /// contains the actual value.
 ///
+/// The bounds are single-precision by default; `Interval<f64>` is used by the
+/// [double-precision interpreter](crate::vm::double).
+///
 /// # Warning
-/// This implementation does not set rounding modes, so it may not be _perfect_.
+/// This implementation does not set rounding modes, so it may not be _perfect_:
+/// each arithmetic operation may under-approximate its bounds by up to half
+/// an ulp.  Use [`Interval::widen`] (or a tape with
+/// [conservative intervals](crate::eval::Tape::with_conservative_intervals))
+/// when that matters.
 #[derive(Copy, Clone, PartialEq)]
 #[repr(C)]
-pub struct Interval {
-    lower: f32,
-    upper: f32,
+pub struct Interval<T = f32> {
+    lower: T,
+    upper: T,
 }
 
-impl std::fmt::Debug for Interval {
+impl<T: core::fmt::Debug> core::fmt::Debug for Interval<T> {
     fn fmt(
         &self,
-        f: &mut std::fmt::Formatter<'_>,
-    ) -> Result<(), std::fmt::Error> {
+        f: &mut core::fmt::Formatter<'_>,
+    ) -> Result<(), core::fmt::Error> {
         f.debug_tuple("")
             .field(&self.lower)
             .field(&self.upper)
@@ -195,7 +568,7 @@ impl std::fmt::Debug for Interval {
     }
 }
 
-impl Interval {
+impl<T: IntervalFloat> Interval<T> {
     /// Builds a new interval
     ///
     /// There are two kinds of valid interval:
@@ -205,23 +578,23 @@ impl Interval {
     /// # Panics
     /// Panics if the resulting interval would be invalid
     #[inline]
-    pub fn new(lower: f32, upper: f32) -> Self {
+    pub fn new(lower: T, upper: T) -> Self {
         assert!(upper >= lower || (lower.is_nan() && upper.is_nan()));
         Self { lower, upper }
     }
     /// Returns the lower bound of the interval
     #[inline]
-    pub fn lower(&self) -> f32 {
+    pub fn lower(&self) -> T {
         self.lower
     }
     /// Returns the upper bound of the interval
     #[inline]
-    pub fn upper(&self) -> f32 {
+    pub fn upper(&self) -> T {
         self.upper
     }
     /// Checks whether the given value is (strictly) contained in the interval
     #[inline]
-    pub fn contains(&self, v: f32) -> bool {
+    pub fn contains(&self, v: T) -> bool {
         v >= self.lower && v <= self.upper
     }
     /// Returns `true` if either bound of the interval is `NaN`
@@ -230,9 +603,10 @@ impl Interval {
     }
     /// Calculates the absolute value of the interval
     pub fn abs(self) -> Self {
-        if self.lower < 0.0 {
-            if self.upper > 0.0 {
-                Interval::new(0.0, self.upper.max(-self.lower))
+        let zero = T::zero();
+        if self.lower < zero {
+            if self.upper > zero {
+                Interval::new(zero, self.upper.max(-self.lower))
             } else {
                 Interval::new(-self.upper, -self.lower)
             }
@@ -245,14 +619,15 @@ impl Interval {
     /// Note that this has tighter bounds than multiplication, because we know
     /// that both sides of the multiplication are the same value.
     pub fn square(self) -> Self {
-        if self.upper < 0.0 {
+        let zero = T::zero();
+        if self.upper < zero {
             Interval::new(self.upper.powi(2), self.lower.powi(2))
-        } else if self.lower > 0.0 {
+        } else if self.lower > zero {
             Interval::new(self.lower.powi(2), self.upper.powi(2))
         } else if self.has_nan() {
-            std::f32::NAN.into()
+            T::nan().into()
         } else {
-            Interval::new(0.0, self.lower.abs().max(self.upper.abs()).powi(2))
+            Interval::new(zero, self.lower.abs().max(self.upper.abs()).powi(2))
         }
     }
     /// Calculates the square root of the interval
@@ -260,11 +635,12 @@ impl Interval {
     /// If the entire interval is below 0, returns a `NAN` interval; otherwise,
     /// returns the valid (positive) interval.
     pub fn sqrt(self) -> Self {
-        if self.lower < 0.0 {
-            if self.upper > 0.0 {
-                Interval::new(0.0, self.upper.sqrt())
+        let zero = T::zero();
+        if self.lower < zero {
+            if self.upper >= zero {
+                Interval::new(zero, self.upper.sqrt())
             } else {
-                std::f32::NAN.into()
+                T::nan().into()
             }
         } else {
             Interval::new(self.lower.sqrt(), self.upper.sqrt())
@@ -274,11 +650,100 @@ impl Interval {
     ///
     /// If the interval includes 0, returns the `NAN` interval
     pub fn recip(self) -> Self {
-        if self.lower > 0.0 || self.upper < 0.0 {
-            Interval::new(1.0 / self.upper, 1.0 / self.lower)
+        if self.lower > T::zero() || self.upper < T::zero() {
+            Interval::new(self.upper.recip(), self.lower.recip())
         } else {
-            std::f32::NAN.into()
+            T::nan().into()
+        }
+    }
+    /// Calculates the sine of the interval (in radians)
+    ///
+    /// If either bound is infinite or the interval is wider than a full
+    /// period, returns `[-1, 1]`; if either bound is `NAN`, returns the `NAN`
+    /// interval.
+    pub fn sin(self) -> Self {
+        self.periodic(|v| v.sin(), T::FRAC_PI_2())
+    }
+    /// Calculates the cosine of the interval (in radians)
+    ///
+    /// If either bound is infinite or the interval is wider than a full
+    /// period, returns `[-1, 1]`; if either bound is `NAN`, returns the `NAN`
+    /// interval.
+    pub fn cos(self) -> Self {
+        self.periodic(|v| v.cos(), T::zero())
+    }
+    /// Bounds a function with period 2π, which has a maximum of 1 at `peak`
+    /// and a minimum of -1 at `peak + π`
+    ///
+    /// The function's extrema over the interval are at its bounds, unless it
+    /// contains a peak or trough.
+    fn periodic(self, f: impl Fn(T) -> T, peak: T) -> Self {
+        let (pi, tau, one) = (T::PI(), T::TAU(), T::one());
+        if self.has_nan() {
+            return T::nan().into();
+        }
+        let (lo, hi) = (self.lower, self.upper);
+        if !lo.is_finite() || !hi.is_finite() || hi - lo >= tau {
+            return Interval::new(-one, one);
+        }
+        // Checks whether `p + 2πk` is in the interval for some integer `k`
+        let contains = |p: T| p + ((lo - p) / tau).ceil() * tau <= hi;
+        let (a, b) = (f(lo), f(hi));
+        Interval::new(
+            if contains(peak + pi) { -one } else { a.min(b) },
+            if contains(peak) { one } else { a.max(b) },
+        )
+    }
+    /// Calculates the four-quadrant arctangent of `self / rhs` (i.e.
+    /// `atan2(y, x)`)
+    ///
+    /// `atan2` is continuous everywhere except the origin and its branch cut
+    /// (along the negative X axis), and its extrema over any other box lie at
+    /// the box's corners.  If the box touches the origin or the branch cut,
+    /// returns the full range `[-π, π]`.
+    ///
+    /// If either side is `NAN`, returns the `NAN` interval.
+    pub fn atan2(self, rhs: Self) -> Self {
+        if self.has_nan() || rhs.has_nan() {
+            return T::nan().into();
+        }
+        let zero = T::zero();
+        if self.lower <= zero && self.upper >= zero && rhs.lower <= zero {
+            return Interval::new(-T::PI(), T::PI());
+        }
+        self.corners(rhs, T::atan2)
+    }
+    /// Calculates the length of the hypotenuse, `sqrt(self² + rhs²)`
+    ///
+    /// This is monotonic in the absolute value of each argument.
+    pub fn hypot(self, rhs: Self) -> Self {
+        if self.has_nan() || rhs.has_nan() {
+            return T::nan().into();
+        }
+        let (a, b) = (self.abs(), rhs.abs());
+        Interval::new(a.lower.hypot(b.lower), a.upper.hypot(b.upper))
+    }
+    /// Calculates the floored modulo, `self - rhs * floor(self / rhs)`
+    ///
+    /// If `rhs` is a single value and `self` lies within one period, the
+    /// result is `self` shifted by a multiple of that period; otherwise, it's
+    /// the range between zero and `rhs`.
+    ///
+    /// If either side is `NAN` or `rhs` contains zero, returns the `NAN`
+    /// interval.
+    pub fn modulo(self, rhs: Self) -> Self {
+        let zero = T::zero();
+        if self.has_nan() || !(rhs.lower > zero || rhs.upper < zero) {
+            return T::nan().into();
+        }
+        if rhs.lower == rhs.upper {
+            let p = rhs.lower;
+            let k = (self.lower / p).floor();
+            if k.is_finite() && k == (self.upper / p).floor() {
+                return Interval::new(self.lower - p * k, self.upper - p * k);
+            }
         }
+        Interval::new(rhs.lower.min(zero), rhs.upper.max(zero))
     }
     /// Calculates the minimum of two intervals
     ///
@@ -288,7 +753,7 @@ impl Interval {
     /// If either side is `NAN`, returns the `NAN` interval and `Choice::Both`.
     pub fn min_choice(self, rhs: Self) -> (Self, Choice) {
         if self.has_nan() || rhs.has_nan() {
-            return (std::f32::NAN.into(), Choice::Both);
+            return (T::nan().into(), Choice::Both);
         }
         let choice = if self.upper < rhs.lower {
             Choice::Left
@@ -310,7 +775,7 @@ impl Interval {
     /// If either side is `NAN`, returns the `NAN` interval and `Choice::Both`.
     pub fn max_choice(self, rhs: Self) -> (Self, Choice) {
         if self.has_nan() || rhs.has_nan() {
-            return (std::f32::NAN.into(), Choice::Both);
+            return (T::nan().into(), Choice::Both);
         }
         let choice = if self.lower > rhs.upper {
             Choice::Left
@@ -325,9 +790,24 @@ impl Interval {
         )
     }
 
+    /// Clamps the interval to the range `[lo, hi]`, i.e. `max(min(self, hi),
+    /// lo)`
+    ///
+    /// Clamping is monotonic in every argument, so the result is exact.  If
+    /// any argument has a `NAN`, returns the `NAN` interval.
+    pub fn clamp(self, lo: Self, hi: Self) -> Self {
+        if self.has_nan() || lo.has_nan() || hi.has_nan() {
+            return T::nan().into();
+        }
+        Interval::new(
+            self.lower.min(hi.lower).max(lo.lower),
+            self.upper.min(hi.upper).max(lo.upper),
+        )
+    }
+
     /// Returns the midpoint of the interval
-    pub fn midpoint(self) -> f32 {
-        (self.lower + self.upper) / 2.0
+    pub fn midpoint(self) -> T {
+        (self.lower + self.upper) / (T::one() + T::one())
     }
 
     /// Splits the interval at the midpoint
@@ -356,8 +836,8 @@ impl Interval {
     /// assert_eq!(a.lerp(0.75), 1.5);
     /// assert_eq!(a.lerp(2.0), 4.0);
     /// ```
-    pub fn lerp(self, frac: f32) -> f32 {
-        self.lower * (1.0 - frac) + self.upper * frac
+    pub fn lerp(self, frac: T) -> T {
+        self.lower * (T::one() - frac) + self.upper * frac
     }
 
     /// Calculates the width of the interval
@@ -369,98 +849,229 @@ impl Interval {
     /// let b = Interval::new(2.0, 5.0);
     /// assert_eq!(b.width(), 3.0);
     /// ```
-    pub fn width(self) -> f32 {
+    pub fn width(self) -> T {
         self.upper - self.lower
     }
+
+    /// Widens the interval outwards to absorb rounding error
+    ///
+    /// Each bound moves by a relative epsilon (e.g. [`f32::EPSILON`]) plus an
+    /// absolute minimum positive value (e.g. [`f32::MIN_POSITIVE`]), which is
+    /// always at least one ulp; this covers the error from a single
+    /// round-to-nearest operation.  Infinite and `NaN` bounds are unchanged.
+    ///
+    /// ```
+    /// # use fidget::eval::types::Interval;
+    /// let a = Interval::new(1.0f32, 2.0).widen();
+    /// assert!(a.lower() < 1.0 && a.upper() > 2.0);
+    /// assert!(a.width() < 1.0 + 1e-6);
+    /// ```
+    pub fn widen(self) -> Self {
+        let lo = self.lower.abs() * T::epsilon() + T::min_positive_value();
+        let hi = self.upper.abs() * T::epsilon() + T::min_positive_value();
+        // `Float::min` and `Float::max` ignore a NaN argument, which leaves
+        // infinite bounds unchanged (e.g. `inf - inf` in the lower bound)
+        Self {
+            lower: (self.lower - lo).min(self.lower),
+            upper: (self.upper + hi).max(self.upper),
+        }
+    }
+
+    /// Applies `f` to each pair of bounds, returning the range of results
+    fn corners(self, rhs: Self, f: impl Fn(T, T) -> T) -> Self {
+        let out = [
+            f(self.lower, rhs.lower),
+            f(self.lower, rhs.upper),
+            f(self.upper, rhs.lower),
+            f(self.upper, rhs.upper),
+        ];
+        let mut lower = out[0];
+        let mut upper = out[0];
+        for &v in &out[1..] {
+            lower = lower.min(v);
+            upper = upper.max(v);
+        }
+        Interval::new(lower, upper)
+    }
+}
+
+impl Interval<f64> {
+    /// Converts to a single-precision interval which contains this interval
+    ///
+    /// ```
+    /// # use fidget::eval::types::Interval;
+    /// let a = Interval::new(0.1f64, 0.2).to_f32();
+    /// assert!(a.lower() as f64 <= 0.1 && a.upper() as f64 >= 0.2);
+    /// ```
+    pub fn to_f32(self) -> Interval {
+        if self.has_nan() {
+            f32::NAN.into()
+        } else {
+            Interval::new(self.lower as f32, self.upper as f32).widen()
+        }
+    }
 }
 
-impl std::fmt::Display for Interval {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: core::fmt::Display> core::fmt::Display for Interval<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "({}, {})", self.lower, self.upper)
     }
 }
 
-impl From<[f32; 2]> for Interval {
-    fn from(i: [f32; 2]) -> Interval {
+impl<T: IntervalFloat> From<[T; 2]> for Interval<T> {
+    fn from(i: [T; 2]) -> Interval<T> {
         Interval::new(i[0], i[1])
     }
 }
 
-impl From<f32> for Interval {
-    fn from(f: f32) -> Self {
+impl<T: IntervalFloat> From<T> for Interval<T> {
+    fn from(f: T) -> Self {
         Interval::new(f, f)
     }
 }
 
-impl std::ops::Add<Interval> for Interval {
+impl From<Interval> for Interval<f64> {
+    fn from(i: Interval) -> Interval<f64> {
+        Interval::new(i.lower.into(), i.upper.into())
+    }
+}
+
+impl<T: IntervalFloat> core::ops::Add<Interval<T>> for Interval<T> {
     type Output = Self;
     fn add(self, rhs: Self) -> Self {
         Interval::new(self.lower + rhs.lower, self.upper + rhs.upper)
     }
 }
 
-impl std::ops::Mul<Interval> for Interval {
+impl<T: IntervalFloat> core::ops::Mul<Interval<T>> for Interval<T> {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self {
         if self.has_nan() || rhs.has_nan() {
-            return std::f32::NAN.into();
-        }
-        let mut out = [0.0; 4];
-        let mut k = 0;
-        for i in [self.lower, self.upper] {
-            for j in [rhs.lower, rhs.upper] {
-                out[k] = i * j;
-                k += 1;
-            }
+            return T::nan().into();
         }
-        let mut lower = out[0];
-        let mut upper = out[0];
-        for &v in &out[1..] {
-            lower = lower.min(v);
-            upper = upper.max(v);
-        }
-        Interval::new(lower, upper)
+        self.corners(rhs, |a, b| a * b)
     }
 }
 
-impl std::ops::Div<Interval> for Interval {
+impl<T: IntervalFloat> core::ops::Div<Interval<T>> for Interval<T> {
     type Output = Self;
     fn div(self, rhs: Self) -> Self {
         if self.has_nan() {
-            return std::f32::NAN.into();
-        }
-        if rhs.lower > 0.0 || rhs.upper < 0.0 {
-            let mut out = [0.0; 4];
-            let mut k = 0;
-            for i in [self.lower, self.upper] {
-                for j in [rhs.lower, rhs.upper] {
-                    out[k] = i / j;
-                    k += 1;
-                }
-            }
-            let mut lower = out[0];
-            let mut upper = out[0];
-            for &v in &out[1..] {
-                lower = lower.min(v);
-                upper = upper.max(v);
-            }
-            Interval::new(lower, upper)
+            return T::nan().into();
+        }
+        if rhs.lower > T::zero() || rhs.upper < T::zero() {
+            self.corners(rhs, |a, b| a / b)
         } else {
-            std::f32::NAN.into()
+            T::nan().into()
         }
     }
 }
 
-impl std::ops::Sub<Interval> for Interval {
+impl<T: IntervalFloat> core::ops::Sub<Interval<T>> for Interval<T> {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self {
         Interval::new(self.lower - rhs.upper, self.upper - rhs.lower)
     }
 }
 
-impl std::ops::Neg for Interval {
+impl<T: IntervalFloat> core::ops::Neg for Interval<T> {
     type Output = Self;
     fn neg(self) -> Self {
         Interval::new(-self.upper, -self.lower)
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Policy for handling `NaN` arguments to `min` and `max`
+///
+/// Every evaluator (VM and JIT, for all evaluation types) implements the same
+/// policy, so that different evaluators agree about a shape's interior.  It is
+/// selected per tape with [`Tape::with_nan_policy`](crate::eval::Tape::with_nan_policy).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum NanPolicy {
+    /// `NaN` arguments propagate to the result
+    ///
+    /// If either argument to `min` or `max` is `NaN`, the result is `NaN`
+    /// (for gradients, the `NaN` argument is returned, preferring the
+    /// left-hand side) and the choice is [`Choice::Both`].
+    #[default]
+    Propagate,
+
+    /// `NaN` arguments are treated as empty space
+    ///
+    /// A `NaN` argument to `min` or `max` is replaced by `+∞` before the
+    /// operation, so a `NaN` branch of a union disappears and a `NaN` branch
+    /// of an intersection is outside the model.  For gradients (and
+    /// Hessians), only the value is replaced; an interval containing `NaN` is
+    /// replaced by `[-∞, +∞]`, which contains every point result.
+    Empty,
+}
+
+impl NanPolicy {
+    /// Applies this policy to a single argument to `min` or `max`
+    pub(crate) fn float(self, v: f32) -> f32 {
+        match self {
+            NanPolicy::Empty if v.is_nan() => f32::INFINITY,
+            _ => v,
+        }
+    }
+
+    /// Applies this policy to a single gradient argument to `min` or `max`
+    pub(crate) fn grad(self, g: Grad) -> Grad {
+        Grad {
+            v: self.float(g.v),
+            ..g
+        }
+    }
+
+    /// Applies this policy to a single Hessian argument to `min` or `max`
+    pub(crate) fn hessian(self, h: Hessian) -> Hessian {
+        Hessian {
+            v: self.float(h.v),
+            ..h
+        }
+    }
+
+    /// Applies this policy to a single interval argument to `min` or `max`
+    pub(crate) fn interval(self, i: Interval) -> Interval {
+        match self {
+            NanPolicy::Empty if i.has_nan() => {
+                Interval::new(-f32::INFINITY, f32::INFINITY)
+            }
+            _ => i,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sign convention for a shape's value
+///
+/// Fidget's renderers, meshers, and raycasters treat negative values as the
+/// inside of a shape.  Expressions from other tools may use the opposite
+/// convention; rather than wrapping them in a `neg` node, the convention can
+/// be selected per tape with
+/// [`Tape::with_sign_convention`](crate::eval::Tape::with_sign_convention).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SignConvention {
+    /// Negative values are inside the shape
+    #[default]
+    NegativeInside,
+
+    /// Positive values are inside the shape
+    ///
+    /// Evaluators negate the expression's result (and derivatives), so
+    /// callers always see negative-inside values.
+    PositiveInside,
+}
+
+impl SignConvention {
+    /// Returns the opposite convention
+    pub fn invert(self) -> Self {
+        match self {
+            SignConvention::NegativeInside => SignConvention::PositiveInside,
+            SignConvention::PositiveInside => SignConvention::NegativeInside,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::eval::tape::Data;
-use std::{collections::BTreeMap, sync::Arc};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
 
 /// `Vars` contains the mapping of variable names to indexes, and a `Vec<f32>`
 /// which is suitably sized for use in evaluation.
@@ -26,7 +26,7 @@ impl Vars {
         &mut self,
         iter: I,
     ) -> &[f32] {
-        self.values.fill(std::f32::NAN);
+        self.values.fill(core::f32::NAN);
         for i in iter {
             if let Some(v) = self.names.get(i.0) {
                 self.values[*v as usize] = i.1;
@@ -44,11 +44,15 @@
 //! //           XXXXXXXXXX
 //! # Ok::<(), fidget::Error>(())
 //! ```
+pub mod bounds;
 pub mod context;
 pub use context::Context;
 
 pub mod eval;
+pub mod raycast;
+pub mod roots;
 pub mod ssa;
+pub mod validate;
 pub mod vm;
 
 #[cfg(test)]
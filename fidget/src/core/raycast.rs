@@ -0,0 +1,332 @@
+//! Ray-surface intersection
+//!
+//! Rays are marched with sphere tracing, which takes steps based on the
+//! shape's value (assuming that it's a distance field).  If a step crosses the
+//! surface (i.e. the value changes sign), the crossing is refined by
+//! bisection, so shapes which aren't exact distance fields still produce
+//! accurate hits.
+//!
+//! All rays in a bundle are evaluated together with the float slice
+//! evaluator, and the final gradients are found with the gradient slice
+//! evaluator.
+//!
+//! ```
+//! use fidget::{
+//!     context::Context,
+//!     raycast::{raycast, Ray, Settings},
+//!     vm,
+//! };
+//!
+//! let mut ctx = Context::new();
+//! let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+//! let (x2, y2, z2) = (ctx.square(x)?, ctx.square(y)?, ctx.square(z)?);
+//! let r = ctx.add(x2, y2)?;
+//! let r = ctx.add(r, z2)?;
+//! let r = ctx.sqrt(r)?;
+//! let sphere = ctx.sub(r, 0.5)?;
+//! let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+//!
+//! let rays = [
+//!     Ray::new([0.0, 0.0, -2.0], [0.0, 0.0, 1.0]),
+//!     Ray::new([1.0, 0.0, -2.0], [0.0, 0.0, 1.0]),
+//! ];
+//! let hits = raycast(&tape, &rays, &Settings::default())?;
+//! let hit = hits[0].unwrap();
+//! assert!((hit.distance - 1.5).abs() < 1e-4);
+//! assert!((hit.grad.dz - -1.0).abs() < 1e-3); // the normal faces the ray
+//! assert!(hits[1].is_none());
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{types::Grad, Family, Tape},
+    Error,
+};
+use alloc::{vec, vec::Vec};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+/// A single ray, with an origin and direction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    /// Starting point of the ray
+    pub origin: [f32; 3],
+    /// Direction of the ray, which is normalized during raycasting
+    pub dir: [f32; 3],
+}
+
+impl Ray {
+    /// Builds a new ray
+    pub fn new(origin: [f32; 3], dir: [f32; 3]) -> Self {
+        Self { origin, dir }
+    }
+
+    /// Returns the point at distance `t` along the (normalized) ray
+    fn at(&self, t: f32) -> [f32; 3] {
+        let n = self.dir.iter().map(|d| d * d).sum::<f32>().sqrt();
+        [0, 1, 2].map(|i| self.origin[i] + self.dir[i] / n * t)
+    }
+}
+
+/// Settings for raycasting
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// Maximum distance to travel along each ray; 10 by default
+    pub max_distance: f32,
+
+    /// Maximum number of sphere tracing steps; 256 by default
+    pub max_steps: usize,
+
+    /// Rays stop when the shape's value is below this threshold; `1e-5` by
+    /// default
+    pub epsilon: f32,
+
+    /// Minimum step size, which guarantees progress for shapes whose value
+    /// approaches zero without crossing the surface; `1e-4` by default
+    pub min_step: f32,
+
+    /// Scale applied to each sphere tracing step; 1 by default
+    ///
+    /// Use a value below 1 for shapes which overestimate their distance to the
+    /// surface (e.g. after non-uniform scaling), so that thin features aren't
+    /// skipped.
+    pub step_scale: f32,
+
+    /// Number of bisection steps used to refine a crossing; 24 by default
+    pub bisection_steps: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_distance: 10.0,
+            max_steps: 256,
+            epsilon: 1e-5,
+            min_step: 1e-4,
+            step_scale: 1.0,
+            bisection_steps: 24,
+        }
+    }
+}
+
+/// A ray-surface intersection
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// Distance along the ray (in units of the normalized direction)
+    pub distance: f32,
+    /// Position of the hit
+    pub pos: [f32; 3],
+    /// Value and partial derivatives at the hit
+    ///
+    /// The partial derivatives point away from the shape's interior, so they
+    /// can be normalized to find a surface normal.
+    pub grad: Grad,
+}
+
+/// State of a single ray during marching
+#[derive(Copy, Clone)]
+enum State {
+    /// Still sphere tracing, with the current distance
+    March(f32),
+    /// The surface is between two distances (outside, inside)
+    Bracket(f32, f32),
+    /// The ray hit the surface at the given distance
+    Hit(f32),
+    /// The ray escaped
+    Miss,
+}
+
+/// Evaluates the tape at the given distance along each ray
+fn eval_at<F: Family>(
+    eval: &crate::eval::FloatSliceEval<F>,
+    rays: &[Ray],
+    ts: &[(usize, f32)],
+) -> Result<Vec<f32>, Error> {
+    let mut xs = Vec::with_capacity(ts.len());
+    let mut ys = Vec::with_capacity(ts.len());
+    let mut zs = Vec::with_capacity(ts.len());
+    for &(i, t) in ts {
+        let [x, y, z] = rays[i].at(t);
+        xs.push(x);
+        ys.push(y);
+        zs.push(z);
+    }
+    eval.eval(&xs, &ys, &zs, &[])
+}
+
+/// Casts a bundle of rays against the shape
+///
+/// Returns one result per ray: either the first intersection with the
+/// surface, or `None` if the ray doesn't hit anything within
+/// [`Settings::max_distance`].  Rays which start inside the shape hit it at a
+/// distance of 0.
+pub fn raycast<F: Family>(
+    tape: &Tape<F>,
+    rays: &[Ray],
+    settings: &Settings,
+) -> Result<Vec<Option<Hit>>, Error> {
+    let eval = tape.new_float_slice_evaluator();
+    let mut state = vec![State::March(0.0); rays.len()];
+
+    // Sphere tracing, vectorized over active rays
+    let mut prev = vec![0.0; rays.len()];
+    for _ in 0..settings.max_steps {
+        let active: Vec<(usize, f32)> = state
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| match s {
+                State::March(t) => Some((i, *t)),
+                _ => None,
+            })
+            .collect();
+        if active.is_empty() {
+            break;
+        }
+        let values = eval_at(&eval, rays, &active)?;
+        for (&(i, t), v) in active.iter().zip(values) {
+            state[i] = if v.abs() < settings.epsilon {
+                State::Hit(t)
+            } else if v < 0.0 {
+                if t == 0.0 {
+                    State::Hit(0.0)
+                } else {
+                    State::Bracket(prev[i], t)
+                }
+            } else if v.is_nan() || t >= settings.max_distance {
+                State::Miss
+            } else {
+                prev[i] = t;
+                let step = (v * settings.step_scale).max(settings.min_step);
+                State::March((t + step).min(settings.max_distance))
+            };
+        }
+    }
+
+    // Bisection, also vectorized over rays
+    for _ in 0..settings.bisection_steps {
+        let mids: Vec<(usize, f32)> = state
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| match s {
+                State::Bracket(a, b) => Some((i, (a + b) / 2.0)),
+                _ => None,
+            })
+            .collect();
+        if mids.is_empty() {
+            break;
+        }
+        let values = eval_at(&eval, rays, &mids)?;
+        for (&(i, mid), v) in mids.iter().zip(values) {
+            let State::Bracket(a, b) = state[i] else {
+                unreachable!()
+            };
+            state[i] = if v < 0.0 {
+                State::Bracket(a, mid)
+            } else {
+                State::Bracket(mid, b)
+            };
+        }
+    }
+
+    // Find gradients at every hit
+    let hits: Vec<(usize, f32)> = state
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| match s {
+            State::Hit(t) => Some((i, *t)),
+            // Use the outside end of the bracket, so that hits are never
+            // inside the shape
+            State::Bracket(a, _) => Some((i, *a)),
+            State::March(..) | State::Miss => None,
+        })
+        .collect();
+    let pos: Vec<[f32; 3]> = hits.iter().map(|&(i, t)| rays[i].at(t)).collect();
+    let grads = tape.new_grad_slice_evaluator().eval(
+        &pos.iter().map(|p| p[0]).collect::<Vec<_>>(),
+        &pos.iter().map(|p| p[1]).collect::<Vec<_>>(),
+        &pos.iter().map(|p| p[2]).collect::<Vec<_>>(),
+        &[],
+    )?;
+
+    let mut out = vec![None; rays.len()];
+    for ((&(i, distance), pos), grad) in hits.iter().zip(pos).zip(grads) {
+        out[i] = Some(Hit {
+            distance,
+            pos,
+            grad,
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    fn test_raycast<F: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+
+        // A box which is scaled non-uniformly, so its value is not a true
+        // distance field (and sphere tracing overshoots)
+        let x3 = ctx.mul(x, 3.0).unwrap();
+        let bx = ctx.abs(x3).unwrap();
+        let bx = ctx.sub(bx, 1.5).unwrap();
+        let by = ctx.abs(y).unwrap();
+        let by = ctx.sub(by, 0.5).unwrap();
+        let bz = ctx.abs(z).unwrap();
+        let bz = ctx.sub(bz, 0.5).unwrap();
+        let b = ctx.max(bx, by).unwrap();
+        let b = ctx.max(b, bz).unwrap();
+        let tape = ctx.get_tape::<F>(b).unwrap();
+
+        let rays = [
+            Ray::new([-3.0, 0.1, 0.2], [1.0, 0.0, 0.0]),
+            Ray::new([3.0, 0.1, 0.2], [-2.0, 0.0, 0.0]),
+            Ray::new([0.1, 0.2, 4.0], [0.0, 0.0, -1.0]),
+            Ray::new([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            Ray::new([0.0, 2.0, 0.0], [0.0, 1.0, 0.0]),
+            Ray::new([0.0, 0.0, 30.0], [0.0, 0.0, -1.0]),
+        ];
+        // With this step scale, the first step along X lands inside the box,
+        // so the hit is found by bisection.
+        let settings = Settings {
+            step_scale: 0.4,
+            ..Default::default()
+        };
+        let hits = raycast(&tape, &rays, &settings).unwrap();
+
+        let h = hits[0].unwrap();
+        assert!((h.distance - 2.5).abs() < 1e-4, "{h:?}");
+        assert!(h.grad.v >= 0.0 && h.grad.dx < 0.0);
+
+        let h = hits[1].unwrap();
+        assert!((h.distance - 2.5).abs() < 1e-4, "{h:?}");
+        assert!((h.pos[0] - 0.5).abs() < 1e-4);
+        assert!(h.grad.dx > 0.0);
+
+        let h = hits[2].unwrap();
+        assert!((h.distance - 3.5).abs() < 1e-4, "{h:?}");
+        assert_eq!(h.grad.dz, 1.0);
+
+        // Starting inside the shape
+        assert_eq!(hits[3].unwrap().distance, 0.0);
+
+        // Pointing away from the shape, or too far away
+        assert!(hits[4].is_none());
+        assert!(hits[5].is_none());
+    }
+
+    #[test]
+    fn test_raycast_vm() {
+        test_raycast::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_raycast_jit() {
+        test_raycast::<crate::jit::Eval>();
+    }
+}
@@ -0,0 +1,227 @@
+//! Root isolation along line segments
+//!
+//! A [`Segment`] is parameterized by `t`, running from its start (`t = 0`) to
+//! its end (`t = 1`).  [`isolate_roots`] recursively subdivides the segment,
+//! using interval evaluation to discard sections where the shape's value
+//! can't be zero; as in rendering, the tape is simplified as the sections get
+//! smaller.  Once a section is narrower than [`Settings::min_width`], it's
+//! checked for a sign change by evaluating its endpoints.
+//!
+//! This is useful for slicing, trimming lattices against a boundary, and
+//! finding exact surface positions along edges.
+//!
+//! ```
+//! use fidget::{
+//!     context::Context,
+//!     roots::{isolate_roots, Segment, Settings},
+//!     vm,
+//! };
+//!
+//! let mut ctx = Context::new();
+//! let (x, y) = (ctx.x(), ctx.y());
+//! let r = ctx.hypot(x, y)?;
+//! let circle = ctx.sub(r, 0.5)?;
+//! let tape = ctx.get_tape::<vm::Eval>(circle)?;
+//!
+//! // This segment crosses the circle at x = ±0.5
+//! let seg = Segment::new([-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+//! let roots = isolate_roots(&tape, &seg, &Settings::default())?;
+//! assert_eq!(roots.len(), 2);
+//! assert!(roots[0].contains(0.25) && roots[1].contains(0.75));
+//! assert!(roots.iter().all(|r| r.width() <= 1e-4));
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{interval::IntervalEval, types::Interval, Family, Tape},
+    Error,
+};
+use alloc::{vec, vec::Vec};
+
+/// A line segment, parameterized by `t` in `[0, 1]`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Segment {
+    /// Position at `t = 0`
+    pub start: [f32; 3],
+    /// Position at `t = 1`
+    pub end: [f32; 3],
+}
+
+impl Segment {
+    /// Builds a new segment
+    pub fn new(start: [f32; 3], end: [f32; 3]) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the position at the given value of `t`
+    pub fn at(&self, t: f32) -> [f32; 3] {
+        [0, 1, 2].map(|i| self.start[i] + (self.end[i] - self.start[i]) * t)
+    }
+
+    /// Returns the bounds of each coordinate over a range of `t`
+    ///
+    /// The bounds are widened slightly, so that they contain every point on
+    /// the segment despite rounding.
+    fn bounds(&self, t: Interval) -> [Interval; 3] {
+        let (a, b) = (self.at(t.lower()), self.at(t.upper()));
+        [0, 1, 2].map(|i| Interval::new(a[i].min(b[i]), a[i].max(b[i])).widen())
+    }
+}
+
+/// Settings for root isolation
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// Sections of the segment are subdivided until they're narrower than
+    /// this width (in units of `t`); `1e-4` by default
+    pub min_width: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { min_width: 1e-4 }
+    }
+}
+
+/// Finds every section of a segment in which the shape's value changes sign
+///
+/// Returns ranges of `t`, sorted in increasing order, each of which is no
+/// wider than [`Settings::min_width`] and contains at least one root.
+/// Ranges don't overlap, though adjacent ranges may share an endpoint.
+///
+/// Roots where the shape touches zero without changing sign (e.g. a segment
+/// which is tangent to a sphere) aren't reported.
+pub fn isolate_roots<F: Family>(
+    tape: &Tape<F>,
+    segment: &Segment,
+    settings: &Settings,
+) -> Result<Vec<Interval>, Error> {
+    let mut candidates = vec![];
+    let eval = tape.new_interval_evaluator();
+    recurse(
+        tape,
+        &eval,
+        segment,
+        Interval::new(0.0, 1.0),
+        settings,
+        &mut candidates,
+    )?;
+
+    // Check each candidate's endpoints for a sign change.  A root which lies
+    // exactly on a shared endpoint is only reported once, because zero is
+    // treated as being inside the shape.
+    let mut xs = Vec::with_capacity(candidates.len() * 2);
+    let mut ys = Vec::with_capacity(candidates.len() * 2);
+    let mut zs = Vec::with_capacity(candidates.len() * 2);
+    for c in &candidates {
+        for t in [c.lower(), c.upper()] {
+            let [x, y, z] = segment.at(t);
+            xs.push(x);
+            ys.push(y);
+            zs.push(z);
+        }
+    }
+    let values = tape.new_float_slice_evaluator().eval(&xs, &ys, &zs, &[])?;
+    Ok(candidates
+        .into_iter()
+        .zip(values.chunks(2))
+        .filter(|(_, v)| (v[0] <= 0.0) != (v[1] <= 0.0))
+        .map(|(c, _)| c)
+        .collect())
+}
+
+/// Recursively finds sections of the segment which may contain a root
+fn recurse<F: Family>(
+    tape: &Tape<F>,
+    eval: &IntervalEval<F>,
+    segment: &Segment,
+    t: Interval,
+    settings: &Settings,
+    out: &mut Vec<Interval>,
+) -> Result<(), Error> {
+    let [x, y, z] = segment.bounds(t);
+    let (v, trace) = eval.eval(x, y, z, &[])?;
+    if v.lower() > 0.0 || v.upper() < 0.0 {
+        return Ok(());
+    }
+    if t.width() <= settings.min_width {
+        out.push(t);
+        return Ok(());
+    }
+
+    // Use a simplified tape for subdivision, if it's any shorter
+    let simplified = match trace {
+        Some(trace) => {
+            let next = trace.simplify()?;
+            (next.len() < tape.len()).then(|| {
+                let eval = next.new_interval_evaluator();
+                (next, eval)
+            })
+        }
+        None => None,
+    };
+    let (tape, eval) = match &simplified {
+        Some((tape, eval)) => (tape, eval),
+        None => (tape, eval),
+    };
+    let (lo, hi) = t.split();
+    recurse(tape, eval, segment, lo, settings, out)?;
+    recurse(tape, eval, segment, hi, settings, out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    fn test_isolate_roots<F: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+
+        // A band along X, intersected with a sine wave, plus a function which
+        // touches zero at x = 0.8 without changing sign
+        let s = ctx.mul(x, 10.0).unwrap();
+        let s = ctx.sin(s).unwrap();
+        let band = ctx.sub(x, 0.5).unwrap();
+        let band = ctx.abs(band).unwrap();
+        let band = ctx.sub(band, 0.3).unwrap();
+        let touch = ctx.sub(x, 0.8).unwrap();
+        let touch = ctx.square(touch).unwrap();
+        let shape = ctx.max(band, s).unwrap();
+        let shape = ctx.min(shape, touch).unwrap();
+        let shape = ctx.add(shape, y).unwrap();
+        let tape = ctx.get_tape::<F>(shape).unwrap();
+
+        let seg = Segment::new([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let settings = Settings { min_width: 1e-5 };
+        let roots = isolate_roots(&tape, &seg, &settings).unwrap();
+
+        // max(|x - 0.5| - 0.3, sin(10x)) is negative for x in (π/10, 2π/10)
+        let expected = [
+            core::f32::consts::PI / 10.0,
+            2.0 * core::f32::consts::PI / 10.0,
+        ];
+        assert_eq!(roots.len(), expected.len(), "{roots:?}");
+        for (r, e) in roots.iter().zip(expected) {
+            assert!(r.width() <= 1e-5);
+            assert!(
+                r.lower() - 1e-6 <= e && e <= r.upper() + 1e-6,
+                "{r:?} {e}"
+            );
+        }
+
+        // No roots along a segment which stays outside the shape
+        let seg = Segment::new([0.0, 2.0, 0.0], [1.0, 2.0, 0.0]);
+        assert!(isolate_roots(&tape, &seg, &settings).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_isolate_roots_vm() {
+        test_isolate_roots::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_isolate_roots_jit() {
+        test_isolate_roots::<crate::jit::Eval>();
+    }
+}
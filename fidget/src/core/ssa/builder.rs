@@ -1,11 +1,17 @@
 use crate::{
-    context::{BinaryOpcode, Context, Node, Op, UnaryOpcode, VarNode},
-    ssa::{Op as SsaOp, Tape},
+    context::{
+        BinaryOpcode, Context, NaryOpcode, Node, Op, UnaryOpcode, VarNode,
+    },
+    ssa::{push_symbol, Op as SsaOp, Tape},
 };
 
-use std::{
+use alloc::{
+    borrow::ToOwned,
     collections::{btree_map::Entry, BTreeMap},
+    string::String,
     sync::Arc,
+    vec,
+    vec::Vec,
 };
 
 pub(crate) struct Builder {
@@ -16,6 +22,21 @@ pub(crate) struct Builder {
     var_names: BTreeMap<String, u32>,
     constants: BTreeMap<Node, f32>,
     choice_count: usize,
+
+    /// Debug symbol for each operation in `tape` (see [`Tape::symbols`])
+    symbols: Vec<u32>,
+    /// Symbols inherited from already-visited parents
+    scopes: BTreeMap<Node, u32>,
+    /// Table of names from the original context, indexed by symbol
+    names: Vec<String>,
+    name_map: BTreeMap<String, u32>,
+
+    /// Number of extra slots, allocated for immediates (see
+    /// [`Builder::slot`]) and multi-argument operations (see
+    /// [`Builder::slots`])
+    extra_slots: usize,
+    /// Copies into extra slots, to be pushed after the current operation
+    pending: Vec<SsaOp>,
 }
 
 #[derive(Debug)]
@@ -33,6 +54,12 @@ impl Builder {
             var_names: BTreeMap::new(),
             constants: BTreeMap::new(),
             choice_count: 0,
+            symbols: vec![],
+            scopes: BTreeMap::new(),
+            names: vec![],
+            name_map: BTreeMap::new(),
+            extra_slots: 0,
+            pending: vec![],
         }
     }
 
@@ -41,9 +68,38 @@ impl Builder {
             tape: self.tape,
             choice_count: self.choice_count,
             vars: Arc::new(self.var_names),
+            names: Arc::new(self.names),
+            symbols: self.symbols,
+            custom: Arc::default(),
         }
     }
 
+    /// Returns the debug symbol for the given node
+    ///
+    /// This is the node's own name (if it has one), or the name inherited from
+    /// the first parent to be stepped; it is then passed down to children
+    /// which haven't already inherited a name.
+    fn symbol(&mut self, node: Node, op: &Op, ctx: &Context) -> u32 {
+        let symbol = match ctx.get_name(node).unwrap() {
+            Some(name) => match self.name_map.get(name) {
+                Some(i) => *i,
+                None => {
+                    let i = self.names.len().try_into().unwrap();
+                    self.names.push(name.to_owned());
+                    self.name_map.insert(name.to_owned(), i);
+                    i
+                }
+            },
+            None => self.scopes.get(&node).cloned().unwrap_or(u32::MAX),
+        };
+        if symbol != u32::MAX {
+            for child in op.iter_children() {
+                self.scopes.entry(child).or_insert(symbol);
+            }
+        }
+        symbol
+    }
+
     fn get_allocated_value(&mut self, node: Node) -> Location {
         if let Some(r) = self.mapping.get(&node).cloned() {
             Location::Slot(r)
@@ -53,6 +109,50 @@ impl Builder {
         }
     }
 
+    /// Returns a slot containing the given node's value
+    ///
+    /// This is used by operations without an immediate form; immediates are
+    /// assigned a new slot, and are copied into it after the current
+    /// operation is pushed (i.e. before it in evaluation order).
+    fn slot(&mut self, node: Node) -> u32 {
+        match self.get_allocated_value(node) {
+            Location::Slot(r) => r,
+            Location::Immediate(imm) => {
+                let slot = self.extra_slot();
+                self.pending.push(SsaOp::CopyImm(slot, imm));
+                slot
+            }
+        }
+    }
+
+    /// Copies the given nodes' values into consecutive new slots, returning
+    /// the first slot
+    ///
+    /// Like [`Builder::slot`], the copies are pushed after the current
+    /// operation.
+    fn slots(&mut self, nodes: &[Node]) -> u32 {
+        let slots: Vec<u32> = nodes
+            .iter()
+            .map(|&node| {
+                let slot = self.extra_slot();
+                let op = match self.get_allocated_value(node) {
+                    Location::Slot(r) => SsaOp::CopyReg(slot, r),
+                    Location::Immediate(imm) => SsaOp::CopyImm(slot, imm),
+                };
+                self.pending.push(op);
+                slot
+            })
+            .collect();
+        slots[0]
+    }
+
+    /// Allocates a slot which doesn't belong to any node
+    fn extra_slot(&mut self) -> u32 {
+        let slot = self.mapping.len() + self.extra_slots;
+        self.extra_slots += 1;
+        slot.try_into().unwrap()
+    }
+
     /// Ensure that the given node is mapped.
     ///
     /// This must be called before `step` uses the node (as either parent or
@@ -71,6 +171,7 @@ impl Builder {
 
     pub fn step(&mut self, node: Node, op: Op, ctx: &Context) {
         let index = self.mapping.get(&node).cloned();
+        let symbol = self.symbol(node, &op, ctx);
         let op = match op {
             Op::Input(v) => {
                 let arg = match ctx.get_var_by_index(v).unwrap() {
@@ -127,6 +228,29 @@ impl Builder {
                     BinaryOpcode::Max => {
                         (SsaOp::MaxRegReg, SsaOp::MaxRegImm, SsaOp::MaxRegImm)
                     }
+                    BinaryOpcode::MinNc => (
+                        SsaOp::MinNcRegReg,
+                        SsaOp::MinNcRegImm,
+                        SsaOp::MinNcRegImm,
+                    ),
+                    BinaryOpcode::MaxNc => (
+                        SsaOp::MaxNcRegReg,
+                        SsaOp::MaxNcRegImm,
+                        SsaOp::MaxNcRegImm,
+                    ),
+                    BinaryOpcode::Atan2 => (
+                        SsaOp::Atan2RegReg,
+                        SsaOp::Atan2RegImm,
+                        SsaOp::Atan2ImmReg,
+                    ),
+                    BinaryOpcode::Hypot => (
+                        SsaOp::HypotRegReg,
+                        SsaOp::HypotRegImm,
+                        SsaOp::HypotRegImm,
+                    ),
+                    BinaryOpcode::Mod => {
+                        (SsaOp::ModRegReg, SsaOp::ModRegImm, SsaOp::ModImmReg)
+                    }
                 };
 
                 if matches!(op, BinaryOpcode::Min | BinaryOpcode::Max) {
@@ -163,12 +287,46 @@ impl Builder {
                     UnaryOpcode::Recip => SsaOp::RecipReg,
                     UnaryOpcode::Sqrt => SsaOp::SqrtReg,
                     UnaryOpcode::Square => SsaOp::SquareReg,
+                    UnaryOpcode::Sin => SsaOp::SinReg,
+                    UnaryOpcode::Cos => SsaOp::CosReg,
                 };
                 Some(op(index, lhs))
             }
+            Op::Custom(c, lhs, rhs) => {
+                let lhs = self.slot(lhs);
+                let rhs = self.slot(rhs);
+                let i = c.index().try_into().unwrap();
+                Some(SsaOp::CustomRegReg(index.unwrap(), lhs, rhs, i))
+            }
+            Op::Noise(seed, x, y, z) => {
+                let base = self.slots(&[x, y, z]);
+                Some(SsaOp::Noise(index.unwrap(), base, seed))
+            }
+            Op::Clamp(x, lo, hi) => {
+                let base = self.slots(&[x, lo, hi]);
+                Some(SsaOp::Clamp(index.unwrap(), base))
+            }
+            Op::Nary(op, args) => {
+                let base = self.slots(&args);
+                let count = args.len().try_into().unwrap();
+                self.choice_count += args.len() - 1;
+                Some(match op {
+                    NaryOpcode::Min => {
+                        SsaOp::MinMany(index.unwrap(), base, count)
+                    }
+                    NaryOpcode::Max => {
+                        SsaOp::MaxMany(index.unwrap(), base, count)
+                    }
+                })
+            }
         };
 
         if let Some(op) = op {
+            push_symbol(&mut self.symbols, self.tape.len(), symbol);
+            self.tape.push(op);
+        }
+        for op in core::mem::take(&mut self.pending) {
+            push_symbol(&mut self.symbols, self.tape.len(), symbol);
             self.tape.push(op);
         }
     }
@@ -5,4 +5,5 @@ mod tape;
 
 pub(crate) use builder::Builder;
 pub use op::Op;
+pub(crate) use tape::push_symbol;
 pub use tape::Tape;
@@ -1,5 +1,5 @@
 /// Opcode for use in an SSA [`Tape`](super::Tape)
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Op {
     /// Reads one of the inputs (X, Y, Z).  This is the most flexible variable,
     /// and may vary between terms in vector / SIMD evaluation.
@@ -21,6 +21,10 @@ pub enum Op {
     SqrtReg(u32, u32),
     /// Squares a register
     SquareReg(u32, u32),
+    /// Takes the sine of a register
+    SinReg(u32, u32),
+    /// Takes the cosine of a register
+    CosReg(u32, u32),
 
     /// Copies the given register
     CopyReg(u32, u32),
@@ -37,6 +41,16 @@ pub enum Op {
     SubImmReg(u32, u32, f32),
     /// Subtract an immediate from a register
     SubRegImm(u32, u32, f32),
+    /// Computes `atan2` of a register (as `y`) and an immediate (as `x`)
+    Atan2RegImm(u32, u32, f32),
+    /// Computes `atan2` of an immediate (as `y`) and a register (as `x`)
+    Atan2ImmReg(u32, u32, f32),
+    /// Computes the hypotenuse of a register and an immediate
+    HypotRegImm(u32, u32, f32),
+    /// Computes the floored modulo of a register by an immediate
+    ModRegImm(u32, u32, f32),
+    /// Computes the floored modulo of an immediate by a register
+    ModImmReg(u32, u32, f32),
 
     /// Adds two registers
     AddRegReg(u32, u32, u32),
@@ -46,6 +60,12 @@ pub enum Op {
     DivRegReg(u32, u32, u32),
     /// Subtracts two registers
     SubRegReg(u32, u32, u32),
+    /// Computes `atan2` of two registers (`y`, then `x`)
+    Atan2RegReg(u32, u32, u32),
+    /// Computes the hypotenuse of two registers
+    HypotRegReg(u32, u32, u32),
+    /// Computes the floored modulo of two registers
+    ModRegReg(u32, u32, u32),
 
     /// Compute the minimum of a register and an immediate
     MinRegImm(u32, u32, f32),
@@ -55,6 +75,43 @@ pub enum Op {
     MinRegReg(u32, u32, u32),
     /// Compute the maximum of two registers
     MaxRegReg(u32, u32, u32),
+    /// Compute the minimum of a register and an immediate, without a choice
+    MinNcRegImm(u32, u32, f32),
+    /// Compute the maximum of a register and an immediate, without a choice
+    MaxNcRegImm(u32, u32, f32),
+    /// Compute the minimum of two registers, without a choice
+    MinNcRegReg(u32, u32, u32),
+    /// Compute the maximum of two registers, without a choice
+    MaxNcRegReg(u32, u32, u32),
+
+    /// Applies a user-defined operation to two registers
+    ///
+    /// The last argument is an index into [`Tape::custom`](super::Tape::custom)
+    CustomRegReg(u32, u32, u32, u16),
+
+    /// Evaluates gradient noise with the given seed
+    ///
+    /// The `x`, `y`, and `z` coordinates are read from three consecutive slots,
+    /// starting at the second argument (this keeps the operation small enough
+    /// to fit alongside the others).
+    Noise(u32, u32, u16),
+
+    /// Clamps a value to a range, i.e. `max(min(x, hi), lo)`
+    ///
+    /// As with [`Op::Noise`], the `x`, `lo`, and `hi` arguments are read from
+    /// three consecutive slots, starting at the second argument.
+    Clamp(u32, u32),
+
+    /// Computes the minimum of many registers
+    ///
+    /// The arguments are read from consecutive slots, starting at the second
+    /// argument; the third argument is the number of slots.  This makes
+    /// `count - 1` choices, which are simplified as a single group.
+    MinMany(u32, u32, u32),
+    /// Computes the maximum of many registers
+    ///
+    /// See [`Op::MinMany`] for details.
+    MaxMany(u32, u32, u32),
 }
 
 impl Op {
@@ -68,6 +125,8 @@ impl Op {
             | Op::AbsReg(out, ..)
             | Op::RecipReg(out, ..)
             | Op::SqrtReg(out, ..)
+            | Op::SinReg(out, ..)
+            | Op::CosReg(out, ..)
             | Op::SquareReg(out, ..)
             | Op::CopyReg(out, ..)
             | Op::AddRegImm(out, ..)
@@ -76,19 +135,92 @@ impl Op {
             | Op::DivImmReg(out, ..)
             | Op::SubImmReg(out, ..)
             | Op::SubRegImm(out, ..)
+            | Op::Atan2RegImm(out, ..)
+            | Op::Atan2ImmReg(out, ..)
+            | Op::HypotRegImm(out, ..)
+            | Op::ModRegImm(out, ..)
+            | Op::ModImmReg(out, ..)
             | Op::AddRegReg(out, ..)
             | Op::MulRegReg(out, ..)
             | Op::DivRegReg(out, ..)
             | Op::SubRegReg(out, ..)
+            | Op::Atan2RegReg(out, ..)
+            | Op::HypotRegReg(out, ..)
+            | Op::ModRegReg(out, ..)
             | Op::MinRegImm(out, ..)
             | Op::MaxRegImm(out, ..)
             | Op::MinRegReg(out, ..)
-            | Op::MaxRegReg(out, ..) => *out,
+            | Op::MaxRegReg(out, ..)
+            | Op::MinNcRegImm(out, ..)
+            | Op::MaxNcRegImm(out, ..)
+            | Op::MinNcRegReg(out, ..)
+            | Op::MaxNcRegReg(out, ..)
+            | Op::CustomRegReg(out, ..)
+            | Op::Noise(out, ..)
+            | Op::Clamp(out, ..)
+            | Op::MinMany(out, ..)
+            | Op::MaxMany(out, ..) => *out,
         }
     }
+    /// Returns the registers read by the given opcode
+    pub fn args(&self) -> impl Iterator<Item = u32> {
+        let many = match *self {
+            Op::MinMany(_, base, count) | Op::MaxMany(_, base, count) => {
+                base..base + count
+            }
+            _ => 0..0,
+        };
+        let args = match *self {
+            Op::Input(..)
+            | Op::Var(..)
+            | Op::CopyImm(..)
+            | Op::MinMany(..)
+            | Op::MaxMany(..) => [None, None, None],
+            Op::NegReg(_, arg)
+            | Op::AbsReg(_, arg)
+            | Op::RecipReg(_, arg)
+            | Op::SqrtReg(_, arg)
+            | Op::SinReg(_, arg)
+            | Op::CosReg(_, arg)
+            | Op::SquareReg(_, arg)
+            | Op::CopyReg(_, arg)
+            | Op::AddRegImm(_, arg, ..)
+            | Op::MulRegImm(_, arg, ..)
+            | Op::DivRegImm(_, arg, ..)
+            | Op::DivImmReg(_, arg, ..)
+            | Op::SubImmReg(_, arg, ..)
+            | Op::SubRegImm(_, arg, ..)
+            | Op::Atan2RegImm(_, arg, ..)
+            | Op::Atan2ImmReg(_, arg, ..)
+            | Op::HypotRegImm(_, arg, ..)
+            | Op::ModRegImm(_, arg, ..)
+            | Op::ModImmReg(_, arg, ..)
+            | Op::MinRegImm(_, arg, ..)
+            | Op::MaxRegImm(_, arg, ..)
+            | Op::MinNcRegImm(_, arg, ..)
+            | Op::MaxNcRegImm(_, arg, ..) => [Some(arg), None, None],
+            Op::AddRegReg(_, lhs, rhs)
+            | Op::MulRegReg(_, lhs, rhs)
+            | Op::DivRegReg(_, lhs, rhs)
+            | Op::SubRegReg(_, lhs, rhs)
+            | Op::Atan2RegReg(_, lhs, rhs)
+            | Op::HypotRegReg(_, lhs, rhs)
+            | Op::ModRegReg(_, lhs, rhs)
+            | Op::MinRegReg(_, lhs, rhs)
+            | Op::MaxRegReg(_, lhs, rhs)
+            | Op::MinNcRegReg(_, lhs, rhs)
+            | Op::MaxNcRegReg(_, lhs, rhs)
+            | Op::CustomRegReg(_, lhs, rhs, ..) => [Some(lhs), Some(rhs), None],
+            Op::Noise(_, base, _) | Op::Clamp(_, base) => {
+                [Some(base), Some(base + 1), Some(base + 2)]
+            }
+        };
+        args.into_iter().flatten().chain(many)
+    }
     /// Returns the number of choices made by the given opcode
     ///
-    /// This is always zero or one.
+    /// This is zero or one, except for [`Op::MinMany`] and [`Op::MaxMany`],
+    /// which make one fewer choice than they have arguments.
     pub fn choice_count(&self) -> usize {
         match self {
             Op::Input(..)
@@ -98,6 +230,8 @@ impl Op {
             | Op::AbsReg(..)
             | Op::RecipReg(..)
             | Op::SqrtReg(..)
+            | Op::SinReg(..)
+            | Op::CosReg(..)
             | Op::SquareReg(..)
             | Op::CopyReg(..)
             | Op::AddRegImm(..)
@@ -109,11 +243,197 @@ impl Op {
             | Op::SubRegReg(..)
             | Op::DivRegReg(..)
             | Op::DivRegImm(..)
-            | Op::DivImmReg(..) => 0,
+            | Op::DivImmReg(..)
+            | Op::Atan2RegReg(..)
+            | Op::Atan2RegImm(..)
+            | Op::Atan2ImmReg(..)
+            | Op::HypotRegReg(..)
+            | Op::ModRegReg(..)
+            | Op::HypotRegImm(..)
+            | Op::ModRegImm(..)
+            | Op::ModImmReg(..)
+            | Op::MinNcRegImm(..)
+            | Op::MaxNcRegImm(..)
+            | Op::MinNcRegReg(..)
+            | Op::MaxNcRegReg(..)
+            | Op::CustomRegReg(..)
+            | Op::Noise(..)
+            | Op::Clamp(..) => 0,
             Op::MinRegImm(..)
             | Op::MaxRegImm(..)
             | Op::MinRegReg(..)
             | Op::MaxRegReg(..) => 1,
+            Op::MinMany(_, _, count) | Op::MaxMany(_, _, count) => {
+                *count as usize - 1
+            }
+        }
+    }
+    /// Returns a mutable reference to the operation's immediate, if present
+    pub(crate) fn imm_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            Op::CopyImm(_, imm)
+            | Op::AddRegImm(_, _, imm)
+            | Op::MulRegImm(_, _, imm)
+            | Op::DivRegImm(_, _, imm)
+            | Op::DivImmReg(_, _, imm)
+            | Op::SubImmReg(_, _, imm)
+            | Op::SubRegImm(_, _, imm)
+            | Op::Atan2RegImm(_, _, imm)
+            | Op::Atan2ImmReg(_, _, imm)
+            | Op::HypotRegImm(_, _, imm)
+            | Op::ModRegImm(_, _, imm)
+            | Op::ModImmReg(_, _, imm)
+            | Op::MinRegImm(_, _, imm)
+            | Op::MaxRegImm(_, _, imm)
+            | Op::MinNcRegImm(_, _, imm)
+            | Op::MaxNcRegImm(_, _, imm) => Some(imm),
+            Op::Input(..)
+            | Op::Var(..)
+            | Op::NegReg(..)
+            | Op::AbsReg(..)
+            | Op::RecipReg(..)
+            | Op::SqrtReg(..)
+            | Op::SinReg(..)
+            | Op::CosReg(..)
+            | Op::SquareReg(..)
+            | Op::CopyReg(..)
+            | Op::AddRegReg(..)
+            | Op::MulRegReg(..)
+            | Op::DivRegReg(..)
+            | Op::SubRegReg(..)
+            | Op::Atan2RegReg(..)
+            | Op::HypotRegReg(..)
+            | Op::ModRegReg(..)
+            | Op::MinRegReg(..)
+            | Op::MaxRegReg(..)
+            | Op::MinNcRegReg(..)
+            | Op::MaxNcRegReg(..)
+            | Op::CustomRegReg(..)
+            | Op::Noise(..)
+            | Op::Clamp(..)
+            | Op::MinMany(..)
+            | Op::MaxMany(..) => None,
+        }
+    }
+}
+
+impl core::fmt::Display for Op {
+    /// Formats the operation as `$out = OPCODE args...`
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            Op::Input(out, i) => write!(f, "${out} = INPUT {i}"),
+            Op::Var(out, i) => write!(f, "${out} = VAR {i}"),
+            Op::CopyImm(out, imm) => write!(f, "${out} = COPY {imm}"),
+            Op::CustomRegReg(out, lhs, rhs, i) => {
+                write!(f, "${out} = CUSTOM[{i}] ${lhs} ${rhs}")
+            }
+            Op::Noise(out, base, seed) => {
+                let (y, z) = (base + 1, base + 2);
+                write!(f, "${out} = NOISE[{seed}] ${base} ${y} ${z}")
+            }
+            Op::Clamp(out, base) => {
+                let (lo, hi) = (base + 1, base + 2);
+                write!(f, "${out} = CLAMP ${base} ${lo} ${hi}")
+            }
+            Op::MinMany(out, base, count) | Op::MaxMany(out, base, count) => {
+                let op = match self {
+                    Op::MinMany(..) => "MIN_MANY",
+                    Op::MaxMany(..) => "MAX_MANY",
+                    _ => unreachable!(),
+                };
+                write!(f, "${out} = {op}")?;
+                for i in base..base + count {
+                    write!(f, " ${i}")?;
+                }
+                Ok(())
+            }
+            Op::NegReg(out, arg)
+            | Op::AbsReg(out, arg)
+            | Op::RecipReg(out, arg)
+            | Op::SqrtReg(out, arg)
+            | Op::SinReg(out, arg)
+            | Op::CosReg(out, arg)
+            | Op::CopyReg(out, arg)
+            | Op::SquareReg(out, arg) => {
+                let op = match self {
+                    Op::NegReg(..) => "NEG",
+                    Op::AbsReg(..) => "ABS",
+                    Op::RecipReg(..) => "RECIP",
+                    Op::SqrtReg(..) => "SQRT",
+                    Op::SquareReg(..) => "SQUARE",
+                    Op::SinReg(..) => "SIN",
+                    Op::CosReg(..) => "COS",
+                    Op::CopyReg(..) => "COPY",
+                    _ => unreachable!(),
+                };
+                write!(f, "${out} = {op} ${arg}")
+            }
+            Op::AddRegReg(out, lhs, rhs)
+            | Op::MulRegReg(out, lhs, rhs)
+            | Op::DivRegReg(out, lhs, rhs)
+            | Op::SubRegReg(out, lhs, rhs)
+            | Op::Atan2RegReg(out, lhs, rhs)
+            | Op::HypotRegReg(out, lhs, rhs)
+            | Op::ModRegReg(out, lhs, rhs)
+            | Op::MinRegReg(out, lhs, rhs)
+            | Op::MaxRegReg(out, lhs, rhs)
+            | Op::MinNcRegReg(out, lhs, rhs)
+            | Op::MaxNcRegReg(out, lhs, rhs) => {
+                let op = match self {
+                    Op::AddRegReg(..) => "ADD",
+                    Op::MulRegReg(..) => "MUL",
+                    Op::DivRegReg(..) => "DIV",
+                    Op::SubRegReg(..) => "SUB",
+                    Op::Atan2RegReg(..) => "ATAN2",
+                    Op::HypotRegReg(..) => "HYPOT",
+                    Op::ModRegReg(..) => "MOD",
+                    Op::MinRegReg(..) => "MIN",
+                    Op::MaxRegReg(..) => "MAX",
+                    Op::MinNcRegReg(..) => "MIN_NC",
+                    Op::MaxNcRegReg(..) => "MAX_NC",
+                    _ => unreachable!(),
+                };
+                write!(f, "${out} = {op} ${lhs} ${rhs}")
+            }
+            Op::AddRegImm(out, arg, imm)
+            | Op::MulRegImm(out, arg, imm)
+            | Op::DivRegImm(out, arg, imm)
+            | Op::DivImmReg(out, arg, imm)
+            | Op::SubImmReg(out, arg, imm)
+            | Op::SubRegImm(out, arg, imm)
+            | Op::Atan2RegImm(out, arg, imm)
+            | Op::Atan2ImmReg(out, arg, imm)
+            | Op::HypotRegImm(out, arg, imm)
+            | Op::ModRegImm(out, arg, imm)
+            | Op::ModImmReg(out, arg, imm)
+            | Op::MinRegImm(out, arg, imm)
+            | Op::MaxRegImm(out, arg, imm)
+            | Op::MinNcRegImm(out, arg, imm)
+            | Op::MaxNcRegImm(out, arg, imm) => {
+                let (op, swap) = match self {
+                    Op::AddRegImm(..) => ("ADD", false),
+                    Op::MulRegImm(..) => ("MUL", false),
+                    Op::DivImmReg(..) => ("DIV", true),
+                    Op::DivRegImm(..) => ("DIV", false),
+                    Op::SubImmReg(..) => ("SUB", true),
+                    Op::SubRegImm(..) => ("SUB", false),
+                    Op::Atan2ImmReg(..) => ("ATAN2", true),
+                    Op::Atan2RegImm(..) => ("ATAN2", false),
+                    Op::HypotRegImm(..) => ("HYPOT", false),
+                    Op::ModImmReg(..) => ("MOD", true),
+                    Op::ModRegImm(..) => ("MOD", false),
+                    Op::MinRegImm(..) => ("MIN", false),
+                    Op::MaxRegImm(..) => ("MAX", false),
+                    Op::MinNcRegImm(..) => ("MIN_NC", false),
+                    Op::MaxNcRegImm(..) => ("MAX_NC", false),
+                    _ => unreachable!(),
+                };
+                if swap {
+                    write!(f, "${out} = {op} {imm} ${arg}")
+                } else {
+                    write!(f, "${out} = {op} ${arg} {imm}")
+                }
+            }
         }
     }
 }
@@ -124,6 +444,32 @@ mod test {
 
     #[test]
     fn test_op_size() {
-        assert_eq!(std::mem::size_of::<Op>(), 16);
+        assert_eq!(core::mem::size_of::<Op>(), 16);
+    }
+
+    #[test]
+    fn test_op_display() {
+        assert_eq!(Op::Input(1, 2).to_string(), "$1 = INPUT 2");
+        assert_eq!(Op::SqrtReg(3, 1).to_string(), "$3 = SQRT $1");
+        assert_eq!(Op::MinRegReg(0, 1, 2).to_string(), "$0 = MIN $1 $2");
+        assert_eq!(
+            Op::MaxNcRegImm(0, 1, 2.5).to_string(),
+            "$0 = MAX_NC $1 2.5"
+        );
+        assert_eq!(Op::SubRegImm(4, 2, 1.5).to_string(), "$4 = SUB $2 1.5");
+        assert_eq!(Op::SubImmReg(4, 2, 1.5).to_string(), "$4 = SUB 1.5 $2");
+        assert_eq!(Op::Atan2ImmReg(4, 2, 1.5).to_string(), "$4 = ATAN2 1.5 $2");
+        assert_eq!(Op::ModRegImm(4, 2, 1.5).to_string(), "$4 = MOD $2 1.5");
+        assert_eq!(
+            Op::CustomRegReg(0, 1, 2, 3).to_string(),
+            "$0 = CUSTOM[3] $1 $2"
+        );
+        assert_eq!(Op::Noise(0, 4, 7).to_string(), "$0 = NOISE[7] $4 $5 $6");
+        assert_eq!(Op::Clamp(0, 4).to_string(), "$0 = CLAMP $4 $5 $6");
+        assert_eq!(Op::MinMany(0, 4, 2).to_string(), "$0 = MIN_MANY $4 $5");
+        assert_eq!(
+            Op::MaxMany(1, 2, 4).to_string(),
+            "$1 = MAX_MANY $2 $3 $4 $5"
+        );
     }
 }
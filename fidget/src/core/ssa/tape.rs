@@ -1,9 +1,12 @@
 use crate::{
+    eval::{noise, CustomOp},
     ssa::Op,
     vm::{RegisterAllocator, Tape as VmTape},
 };
 
-use std::{collections::BTreeMap, sync::Arc};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
 
 /// Instruction tape, storing [`Op`](crate::ssa::Op) in SSA form
 ///
@@ -30,102 +33,525 @@ pub struct Tape {
     /// This is an `Arc` so it can be trivially shared by all of the tape's
     /// descendents, since the variable array order does not change.
     pub vars: Arc<BTreeMap<String, u32>>,
+
+    /// Names attached to nodes in the original
+    /// [`Context`](crate::context::Context) with
+    /// [`Context::name`](crate::context::Context::name)
+    ///
+    /// Like `vars`, this is shared by all of the tape's descendents.
+    pub names: Arc<Vec<String>>,
+
+    /// Debug symbol for each operation in `tape`, as an index into `names`
+    ///
+    /// Operations without a name are marked with `u32::MAX`.  This may be
+    /// shorter than `tape` (and is empty if the context has no names), in
+    /// which case the missing operations are unnamed.
+    pub symbols: Vec<u32>,
+
+    /// User-defined operations, indexed by
+    /// [`Op::CustomRegReg`](crate::ssa::Op::CustomRegReg)
+    ///
+    /// Like `vars`, this is shared by all of the tape's descendents.
+    pub custom: Arc<Vec<Arc<dyn CustomOp>>>,
 }
 
 impl Tape {
     /// Resets to an empty tape, preserving allocations
     pub fn reset(&mut self) {
         self.tape.clear();
+        self.symbols.clear();
         self.choice_count = 0;
     }
+
+    /// Returns the name associated with the operation at the given index
+    pub fn symbol(&self, i: usize) -> Option<&str> {
+        let s = *self.symbols.get(i)?;
+        self.names.get(s as usize).map(String::as_str)
+    }
+    /// Checks whether two tapes differ only in the values of their immediates
+    ///
+    /// Custom operations must be the same objects, not merely equivalent.
+    pub(crate) fn same_structure(&self, other: &Self) -> bool {
+        let strip = |mut op: Op| {
+            if let Some(imm) = op.imm_mut() {
+                *imm = 0.0;
+            }
+            op
+        };
+        self.tape.len() == other.tape.len()
+            && self.choice_count == other.choice_count
+            && self.vars == other.vars
+            && self.names == other.names
+            && self.symbols == other.symbols
+            && self.custom.len() == other.custom.len()
+            && self
+                .custom
+                .iter()
+                .zip(other.custom.iter())
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+            && self
+                .tape
+                .iter()
+                .zip(&other.tape)
+                .all(|(a, b)| strip(*a) == strip(*b))
+    }
+
     /// Pretty-prints the given tape to `stdout`
+    ///
+    /// Operations which came from a named node (see [`Tape::names`]) are
+    /// annotated with that name.  This requires the `std` feature.
+    #[cfg(feature = "std")]
     pub fn pretty_print(&self) {
-        for &op in self.tape.iter().rev() {
-            match op {
-                Op::Input(out, i) => {
-                    println!("${out} = INPUT {i}");
-                }
-                Op::Var(out, i) => {
-                    println!("${out} = VAR {i}");
-                }
-                Op::NegReg(out, arg)
-                | Op::AbsReg(out, arg)
-                | Op::RecipReg(out, arg)
-                | Op::SqrtReg(out, arg)
-                | Op::CopyReg(out, arg)
-                | Op::SquareReg(out, arg) => {
-                    let op = match op {
-                        Op::NegReg(..) => "NEG",
-                        Op::AbsReg(..) => "ABS",
-                        Op::RecipReg(..) => "RECIP",
-                        Op::SqrtReg(..) => "SQRT",
-                        Op::SquareReg(..) => "SQUARE",
-                        Op::CopyReg(..) => "COPY",
-                        _ => unreachable!(),
-                    };
-                    println!("${out} = {op} ${arg}");
-                }
+        for (index, op) in self.tape.iter().enumerate().rev() {
+            let line = op.to_string();
+            match self.symbol(index) {
+                Some(name) => println!("{line:<24} # {name}"),
+                None => println!("{line}"),
+            }
+        }
+    }
 
-                Op::AddRegReg(out, lhs, rhs)
-                | Op::MulRegReg(out, lhs, rhs)
-                | Op::DivRegReg(out, lhs, rhs)
-                | Op::SubRegReg(out, lhs, rhs)
-                | Op::MinRegReg(out, lhs, rhs)
-                | Op::MaxRegReg(out, lhs, rhs) => {
-                    let op = match op {
-                        Op::AddRegReg(..) => "ADD",
-                        Op::MulRegReg(..) => "MUL",
-                        Op::DivRegReg(..) => "DIV",
-                        Op::SubRegReg(..) => "SUB",
-                        Op::MinRegReg(..) => "MIN",
-                        Op::MaxRegReg(..) => "MAX",
-                        _ => unreachable!(),
-                    };
-                    println!("${out} = {op} ${lhs} ${rhs}");
+    /// Returns a copy of this tape with the given variable replaced by a
+    /// constant, folding any operations which become constant as a result.
+    ///
+    /// Operations are rewritten in place (preserving their output slots), so
+    /// the resulting tape may contain dead code; it should be cleaned up with a
+    /// simplification pass before use.  `var` is an index into the variable
+    /// array, i.e. a value from [`self.vars`](Self::vars).
+    pub fn bind_var(&self, var: u32, value: f32) -> Self {
+        let size = self
+            .tape
+            .iter()
+            .map(|op| op.output() as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut consts: Vec<Option<f32>> = vec![None; size];
+        let mut tape = self.tape.clone();
+
+        // Walk the tape in evaluation order, so arguments are seen before the
+        // operations which use them.
+        for op in tape.iter_mut().rev() {
+            let c = |i: u32| consts[i as usize];
+            let folded = match *op {
+                Op::Var(_, i) if i == var => Some(value),
+                Op::Input(..) | Op::Var(..) => None,
+                Op::CopyImm(_, imm) => Some(imm),
+                Op::NegReg(_, arg) => c(arg).map(|a| -a),
+                Op::AbsReg(_, arg) => c(arg).map(|a| a.abs()),
+                Op::RecipReg(_, arg) => c(arg).map(|a| 1.0 / a),
+                Op::SqrtReg(_, arg) => c(arg).map(|a| a.sqrt()),
+                Op::SquareReg(_, arg) => c(arg).map(|a| a * a),
+                Op::SinReg(_, arg) => c(arg).map(|a| a.sin()),
+                Op::CosReg(_, arg) => c(arg).map(|a| a.cos()),
+                Op::CopyReg(_, arg) => c(arg),
+
+                Op::AddRegImm(_, arg, imm) => c(arg).map(|a| a + imm),
+                Op::MulRegImm(_, arg, imm) => c(arg).map(|a| a * imm),
+                Op::DivRegImm(_, arg, imm) => c(arg).map(|a| a / imm),
+                Op::DivImmReg(_, arg, imm) => c(arg).map(|a| imm / a),
+                Op::SubRegImm(_, arg, imm) => c(arg).map(|a| a - imm),
+                Op::SubImmReg(_, arg, imm) => c(arg).map(|a| imm - a),
+                Op::MinRegImm(_, arg, imm) => c(arg).map(|a| fold_min(a, imm)),
+                Op::MaxRegImm(_, arg, imm) => c(arg).map(|a| fold_max(a, imm)),
+                Op::MinNcRegImm(_, arg, imm) => {
+                    c(arg).map(|a| fold_min(a, imm))
                 }
+                Op::MaxNcRegImm(_, arg, imm) => {
+                    c(arg).map(|a| fold_max(a, imm))
+                }
+                Op::Atan2RegImm(_, arg, imm) => c(arg).map(|a| a.atan2(imm)),
+                Op::Atan2ImmReg(_, arg, imm) => c(arg).map(|a| imm.atan2(a)),
+                Op::HypotRegImm(_, arg, imm) => c(arg).map(|a| a.hypot(imm)),
+                Op::ModRegImm(_, arg, imm) => c(arg).map(|a| fold_mod(a, imm)),
+                Op::ModImmReg(_, arg, imm) => c(arg).map(|a| fold_mod(imm, a)),
 
-                Op::AddRegImm(out, arg, imm)
-                | Op::MulRegImm(out, arg, imm)
-                | Op::DivRegImm(out, arg, imm)
-                | Op::DivImmReg(out, arg, imm)
-                | Op::SubImmReg(out, arg, imm)
-                | Op::SubRegImm(out, arg, imm)
-                | Op::MinRegImm(out, arg, imm)
-                | Op::MaxRegImm(out, arg, imm) => {
-                    let (op, swap) = match op {
-                        Op::AddRegImm(..) => ("ADD", false),
-                        Op::MulRegImm(..) => ("MUL", false),
-                        Op::DivImmReg(..) => ("DIV", true),
-                        Op::DivRegImm(..) => ("DIV", false),
-                        Op::SubImmReg(..) => ("SUB", true),
-                        Op::SubRegImm(..) => ("SUB", false),
-                        Op::MinRegImm(..) => ("MIN", false),
-                        Op::MaxRegImm(..) => ("MAX", false),
-                        _ => unreachable!(),
-                    };
-                    if swap {
-                        println!("${out} = {op} {imm} ${arg}");
-                    } else {
-                        println!("${out} = {op} ${arg} {imm}");
+                Op::AddRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (Some(a), None) => {
+                        *op = Op::AddRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::AddRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::MulRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(a * b),
+                    (Some(a), None) => {
+                        *op = Op::MulRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::MulRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::SubRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(a - b),
+                    (Some(a), None) => {
+                        *op = Op::SubImmReg(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::SubRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::DivRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(a / b),
+                    (Some(a), None) => {
+                        *op = Op::DivImmReg(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::DivRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::Atan2RegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(a.atan2(b)),
+                    (Some(a), None) => {
+                        *op = Op::Atan2ImmReg(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::Atan2RegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::HypotRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(a.hypot(b)),
+                    (Some(a), None) => {
+                        *op = Op::HypotRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::HypotRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::ModRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(fold_mod(a, b)),
+                    (Some(a), None) => {
+                        *op = Op::ModImmReg(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::ModRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::MinRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(fold_min(a, b)),
+                    (Some(a), None) => {
+                        *op = Op::MinRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::MinRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::MaxRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(fold_max(a, b)),
+                    (Some(a), None) => {
+                        *op = Op::MaxRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::MaxRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::MinNcRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(fold_min(a, b)),
+                    (Some(a), None) => {
+                        *op = Op::MinNcRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::MinNcRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::MaxNcRegReg(out, lhs, rhs) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => Some(fold_max(a, b)),
+                    (Some(a), None) => {
+                        *op = Op::MaxNcRegImm(out, rhs, a);
+                        None
+                    }
+                    (None, Some(b)) => {
+                        *op = Op::MaxNcRegImm(out, lhs, b);
+                        None
+                    }
+                    (None, None) => None,
+                },
+                Op::CustomRegReg(_, lhs, rhs, i) => match (c(lhs), c(rhs)) {
+                    (Some(a), Some(b)) => {
+                        Some(self.custom[i as usize].eval_f32(a, b))
+                    }
+                    _ => None,
+                },
+                Op::Noise(_, base, seed) => {
+                    match (c(base), c(base + 1), c(base + 2)) {
+                        (Some(x), Some(y), Some(z)) => {
+                            Some(noise::noise3(x, y, z, seed))
+                        }
+                        _ => None,
                     }
                 }
-                Op::CopyImm(out, imm) => {
-                    println!("${out} = COPY {imm}");
+                Op::Clamp(_, base) => {
+                    match (c(base), c(base + 1), c(base + 2)) {
+                        (Some(x), Some(lo), Some(hi)) => {
+                            Some(fold_max(fold_min(x, hi), lo))
+                        }
+                        _ => None,
+                    }
                 }
+                Op::MinMany(_, base, count) => (base..base + count)
+                    .map(c)
+                    .reduce(|a, b| Some(fold_min(a?, b?)))
+                    .flatten(),
+                Op::MaxMany(_, base, count) => (base..base + count)
+                    .map(c)
+                    .reduce(|a, b| Some(fold_max(a?, b?)))
+                    .flatten(),
+            };
+            if let Some(v) = folded {
+                let out = op.output();
+                *op = Op::CopyImm(out, v);
+                consts[out as usize] = Some(v);
             }
         }
+
+        let choice_count = tape.iter().map(Op::choice_count).sum();
+        Self {
+            tape,
+            choice_count,
+            vars: self.vars.clone(),
+            names: self.names.clone(),
+            symbols: self.symbols.clone(),
+            custom: self.custom.clone(),
+        }
     }
 
     /// Lowers the tape to assembly with a particular register limit
     ///
     /// Note that if you _also_ want to simplify the tape, it's more efficient
-    /// to use [`simplify`](crate::eval::Tape::simplify), which simultaneously
-    /// simplifies **and** performs register allocation in a single pass.
+    /// to use [`simplify`](crate::eval::Tape::simplify), which simplifies
+    /// **and** performs register allocation (reusing a single workspace).
     pub fn get_asm(&self, reg_limit: u8) -> VmTape {
         let mut alloc = RegisterAllocator::new(reg_limit, self.tape.len());
-        for &op in self.tape.iter() {
-            alloc.op(op)
-        }
+        self.allocate(&mut alloc);
         alloc.finalize()
     }
+
+    /// Passes every operation in the tape to a register allocator
+    ///
+    /// Multiplications whose only use is an addition are fused with that
+    /// addition into a single
+    /// [`FmaRegRegReg`](crate::vm::Op::FmaRegRegReg) operation.
+    pub(crate) fn allocate(&self, alloc: &mut RegisterAllocator) {
+        let size = self
+            .tape
+            .iter()
+            .map(|op| op.output() as usize + 1)
+            .max()
+            .unwrap_or(0);
+
+        // Find multiplications which are only used once, recording their
+        // arguments.  Fusion requires a few spare registers, so it's skipped
+        // on tapes with a very low register limit.
+        let mut products: Vec<Option<(u32, u32)>> = vec![None; size];
+        if alloc.reg_limit() >= 4 {
+            let mut uses = vec![0u32; size];
+            for op in &self.tape {
+                for arg in op.args() {
+                    uses[arg as usize] += 1;
+                }
+            }
+            for op in &self.tape {
+                if let Op::MulRegReg(out, lhs, rhs) = *op {
+                    if uses[out as usize] == 1 {
+                        products[out as usize] = Some((lhs, rhs));
+                    }
+                }
+            }
+        }
+
+        let mut fused = vec![false; size];
+        for (i, &op) in self.tape.iter().enumerate() {
+            let symbol = self.symbols.get(i).cloned().unwrap_or(u32::MAX);
+            match op {
+                Op::AddRegReg(out, lhs, rhs) if lhs != rhs => {
+                    let fma = [(lhs, rhs), (rhs, lhs)].into_iter().find_map(
+                        |(t, c)| products[t as usize].map(|p| (t, c, p)),
+                    );
+                    if let Some((t, c, (a, b))) = fma {
+                        fused[t as usize] = true;
+                        alloc.fma_with_symbol(out, a, b, c, symbol);
+                        continue;
+                    }
+                }
+                Op::MulRegReg(out, ..) if fused[out as usize] => continue,
+                _ => (),
+            }
+            alloc.op_with_symbol(op, symbol)
+        }
+    }
+}
+
+/// Records the debug symbol for the operation at index `i` of a tape
+///
+/// Unnamed operations are only recorded (as `u32::MAX`) once a named operation
+/// has been seen, so tapes without names don't pay for symbols.
+pub(crate) fn push_symbol(symbols: &mut Vec<u32>, i: usize, symbol: u32) {
+    if symbol != u32::MAX || !symbols.is_empty() {
+        symbols.resize(i, u32::MAX);
+        symbols.push(symbol);
+    }
+}
+
+/// Folds a `min` operation, propagating `NaN` like the evaluators do
+fn fold_min(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        a.min(b)
+    }
+}
+
+/// Folds a `max` operation, propagating `NaN` like the evaluators do
+fn fold_max(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        a.max(b)
+    }
+}
+
+/// Folds a floored modulo operation, `a - b * floor(a / b)`
+fn fold_mod(a: f32, b: f32) -> f32 {
+    a - b * (a / b).floor()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{context::Context, eval::Family, vm::Op};
+
+    fn test_fma<F: Family>() {
+        // x * y + z * 2 + x * x * z
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let xy = ctx.mul(x, y).unwrap();
+        let z2 = ctx.mul(z, 2.0).unwrap();
+        let a = ctx.add(xy, z2).unwrap();
+        let xx = ctx.square(x).unwrap();
+        let xz = ctx.mul(xx, z).unwrap();
+        let b = ctx.add(a, xz).unwrap();
+        let tape = ctx.get_tape::<F>(b).unwrap();
+        let count = |tape: &crate::eval::Tape<F>| {
+            tape.iter_asm()
+                .filter(|op| matches!(op, Op::FmaRegRegReg(..)))
+                .count()
+        };
+        assert_eq!(count(&tape), 2);
+        assert!(tape.iter_asm().all(|op| !matches!(op, Op::MulRegReg(..))));
+
+        let eval = tape.new_point_evaluator();
+        for (x, y, z) in [(1.0f32, 2.0, 3.0), (-0.5, 0.25, 4.0)] {
+            let v = eval.eval(x, y, z, &[]).unwrap().0;
+            let expected = (x * x).mul_add(z, x.mul_add(y, z * 2.0));
+            assert_eq!(v, expected);
+        }
+
+        // Products which are used more than once aren't fused
+        let c = ctx.max(b, xy).unwrap();
+        let tape = ctx.get_tape::<F>(c).unwrap();
+        assert_eq!(count(&tape), 1);
+
+        // ...unless simplification removes the other uses
+        let (_, trace) =
+            tape.new_point_evaluator().eval(1.0, 2.0, 3.0, &[]).unwrap();
+        let simple = trace.unwrap().simplify().unwrap();
+        assert_eq!(count(&simple), 2);
+    }
+
+    #[test]
+    fn test_fma_vm() {
+        test_fma::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_fma_jit() {
+        test_fma::<crate::jit::Eval>();
+    }
+
+    fn test_noise<F: Family>() {
+        use crate::eval::noise::noise3;
+
+        // noise3(min(x, y), x, z + a)
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let a = ctx.var("a").unwrap();
+        let m = ctx.min(x, y).unwrap();
+        let za = ctx.add(z, a).unwrap();
+        let n = ctx.noise3(m, x, za, 6).unwrap();
+        let tape = ctx.get_tape::<F>(n).unwrap();
+
+        // Simplifying (repeatedly) keeps the arguments in order
+        let (v, trace) = tape
+            .new_point_evaluator()
+            .eval(1.0, 2.0, 3.0, &[0.5])
+            .unwrap();
+        assert_eq!(v, noise3(1.0, 1.0, 3.5, 6));
+        let mut simple = trace.unwrap().simplify().unwrap();
+        assert!(simple.len() < tape.len());
+        for _ in 0..2 {
+            let eval = simple.new_point_evaluator();
+            let (v, trace) = eval.eval(-1.0, 0.0, 0.25, &[1.0]).unwrap();
+            assert_eq!(v, noise3(-1.0, -1.0, 1.25, 6));
+            assert!(trace.is_none());
+            simple = simple.simplify(&[]).unwrap();
+        }
+
+        // Binding every argument to a constant folds the noise
+        let b = ctx.noise3(a, 0.5, 1.5, 6).unwrap();
+        let tape = ctx.get_tape::<F>(b).unwrap();
+        let t = tape.bind_constant("a", 0.25).unwrap();
+        assert!(t.iter_asm().all(|op| !matches!(op, Op::NoiseRegRegReg(..))));
+        let v = t
+            .new_point_evaluator()
+            .eval(0.0, 0.0, 0.0, &[0.0])
+            .unwrap()
+            .0;
+        assert_eq!(v, noise3(0.25, 0.5, 1.5, 6));
+    }
+
+    #[test]
+    fn test_noise_vm() {
+        test_noise::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_noise_jit() {
+        test_noise::<crate::jit::Eval>();
+    }
 }
@@ -0,0 +1,374 @@
+//! Checking whether a shape is a metric distance field
+//!
+//! Many algorithms assume that a shape's value is the distance to its surface:
+//! sphere tracing (see [`raycast`](crate::raycast)) takes steps of that size,
+//! and offsetting a shape by adding a constant only moves the surface by that
+//! amount if the value is a true distance.  Both rely on the gradient
+//! magnitude `|∇f|` being (close to) 1 everywhere.
+//!
+//! [`check_distance_field`] samples the gradient over a region and reports
+//! where it deviates from the expected magnitude.  Each of the worst samples
+//! is traced back through `min` and `max` operations to the subtree which
+//! produced the value, so that badly-scaled primitives in a large model can be
+//! found (and identified by their [`name`](Context::name)).
+//!
+//! ```
+//! use fidget::{
+//!     context::{BoundingBox, Context},
+//!     validate::{check_distance_field, Settings},
+//!     vm,
+//! };
+//!
+//! let mut ctx = Context::new();
+//! let (x, y) = (ctx.x(), ctx.y());
+//! let r = ctx.hypot(x, y)?;
+//! let circle = ctx.sub(r, 0.25)?;
+//!
+//! // A badly-scaled square, which is twice as steep as a distance field
+//! let dx = ctx.sub(x, 0.6)?;
+//! let (ax, ay) = (ctx.abs(dx)?, ctx.abs(y)?);
+//! let square = ctx.max(ax, ay)?;
+//! let square = ctx.sub(square, 0.2)?;
+//! let square = ctx.mul(square, 2.0)?;
+//! ctx.name(square, "square")?;
+//! let shape = ctx.min(circle, square)?;
+//!
+//! let region = BoundingBox::new([-1.0, -1.0, 0.0], [1.0, 1.0, 0.0]);
+//! let report =
+//!     check_distance_field::<vm::Eval>(&ctx, shape, region, &Settings::default())?;
+//! assert!(!report.is_valid());
+//! assert_eq!(report.max_norm, 2.0);
+//! assert_eq!(report.worst[0].name.as_deref(), Some("square"));
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{BinaryOpcode, BoundingBox, Context, Node, Op, UnaryOpcode},
+    eval::{types::Grad, Family},
+    Error,
+};
+use alloc::{string::String, vec, vec::Vec};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+/// Settings for distance field validation
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// Number of samples along each axis of the region; 32 by default
+    ///
+    /// Axes along which the region is flat (e.g. `z` for a 2D shape) are only
+    /// sampled once.
+    pub resolution: usize,
+
+    /// Expected gradient magnitude; 1 by default
+    ///
+    /// Use this if the shape is intentionally scaled, e.g. if it's modeled in
+    /// millimeters but its value is in centimeters.
+    pub scale: f32,
+
+    /// Allowed relative deviation from [`scale`](Self::scale); `0.05` by
+    /// default
+    pub tolerance: f32,
+
+    /// If present, only samples with `|f| <= band` are checked
+    ///
+    /// Some algorithms only need a valid distance field near the surface.
+    pub band: Option<f32>,
+
+    /// Maximum number of offenders to return in [`Report::worst`]; 8 by
+    /// default
+    pub max_offenders: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            resolution: 32,
+            scale: 1.0,
+            tolerance: 0.05,
+            band: None,
+            max_offenders: 8,
+        }
+    }
+}
+
+/// A sample where the gradient magnitude is out of tolerance
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offender {
+    /// Position of the sample
+    pub pos: [f32; 3],
+    /// Value and partial derivatives at the sample
+    pub grad: Grad,
+    /// Gradient magnitude at the sample (which may be `NaN`)
+    pub norm: f32,
+    /// Node which produced the value at this sample
+    ///
+    /// This is found by starting at the root and following the active branch
+    /// of each `min` or `max` operation (and through negation, which doesn't
+    /// change the gradient magnitude), so it's typically the primitive (or
+    /// transformed primitive) responsible for the bad gradient.
+    pub node: Node,
+    /// Name of `node`, or of its nearest named ancestor along the path from
+    /// the root
+    pub name: Option<String>,
+}
+
+/// Result of validating a distance field
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// Number of samples which were checked
+    pub samples: usize,
+    /// Smallest gradient magnitude among checked samples
+    pub min_norm: f32,
+    /// Largest gradient magnitude among checked samples
+    ///
+    /// This is an estimate of the shape's Lipschitz constant; sphere tracing
+    /// should scale its steps by (at most) its reciprocal.
+    pub max_norm: f32,
+    /// Mean gradient magnitude among checked samples (ignoring non-finite
+    /// gradients)
+    pub mean_norm: f32,
+    /// Number of samples where the gradient is too steep
+    ///
+    /// These are the most dangerous, because sphere tracing may step through
+    /// the surface.
+    pub too_steep: usize,
+    /// Number of samples where the gradient is too shallow
+    pub too_shallow: usize,
+    /// Number of samples where the gradient isn't finite
+    pub non_finite: usize,
+    /// The worst samples, sorted by decreasing deviation from the expected
+    /// magnitude
+    pub worst: Vec<Offender>,
+}
+
+impl Report {
+    /// Checks whether every sample was within tolerance
+    pub fn is_valid(&self) -> bool {
+        self.too_steep == 0 && self.too_shallow == 0 && self.non_finite == 0
+    }
+}
+
+/// Samples the gradient of a shape over a region, reporting where it deviates
+/// from a metric distance field
+///
+/// The shape must only depend on `x`, `y`, and `z`.  Returns
+/// [`Error::InfiniteRegion`] if the region isn't finite.
+pub fn check_distance_field<F: Family>(
+    ctx: &Context,
+    root: Node,
+    region: BoundingBox,
+    settings: &Settings,
+) -> Result<Report, Error> {
+    if !region.is_finite() {
+        return Err(Error::InfiniteRegion);
+    }
+    let tape = ctx.get_tape::<F>(root)?;
+
+    // Sample at the centers of a grid of cells
+    let axis = |i: usize| -> Vec<f32> {
+        let (lo, hi) = (region.lower[i], region.upper[i]);
+        let n = if hi > lo {
+            settings.resolution.max(1)
+        } else {
+            1
+        };
+        (0..n)
+            .map(|j| (lo + (hi - lo) * (j as f64 + 0.5) / n as f64) as f32)
+            .collect()
+    };
+    let [ax, ay, az] = [0, 1, 2].map(axis);
+    let count = ax.len() * ay.len() * az.len();
+    let (mut xs, mut ys, mut zs) = (
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+    );
+    for z in &az {
+        for y in &ay {
+            for x in &ax {
+                xs.push(*x);
+                ys.push(*y);
+                zs.push(*z);
+            }
+        }
+    }
+    let mut out = vec![Grad::default(); count];
+    let eval = tape.new_grad_slice_evaluator();
+    eval.eval_into(&xs, &ys, &zs, &[], &mut out, &mut Default::default())?;
+
+    let mut report = Report {
+        samples: 0,
+        min_norm: f32::INFINITY,
+        max_norm: 0.0,
+        mean_norm: 0.0,
+        too_steep: 0,
+        too_shallow: 0,
+        non_finite: 0,
+        worst: vec![],
+    };
+    let lo = settings.scale * (1.0 - settings.tolerance);
+    let hi = settings.scale * (1.0 + settings.tolerance);
+    let mut bad = vec![];
+    for (i, g) in out.iter().enumerate() {
+        if settings.band.is_some_and(|b| g.v.abs() > b) {
+            continue;
+        }
+        report.samples += 1;
+        let norm = (g.dx * g.dx + g.dy * g.dy + g.dz * g.dz).sqrt();
+        let deviation = if !norm.is_finite() {
+            report.non_finite += 1;
+            f32::INFINITY
+        } else {
+            report.min_norm = report.min_norm.min(norm);
+            report.max_norm = report.max_norm.max(norm);
+            report.mean_norm += norm;
+            if norm > hi {
+                report.too_steep += 1;
+            } else if norm < lo {
+                report.too_shallow += 1;
+            } else {
+                continue;
+            }
+            (norm / settings.scale - 1.0).abs()
+        };
+        bad.push((deviation, i, norm));
+    }
+    if report.min_norm > report.max_norm {
+        report.min_norm = 0.0;
+    }
+    let finite = report.samples - report.non_finite;
+    if finite > 0 {
+        report.mean_norm /= finite as f32;
+    }
+
+    // Find the worst samples, breaking ties by sample order
+    bad.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    bad.truncate(settings.max_offenders);
+    for (_, i, norm) in bad {
+        let pos = [xs[i], ys[i], zs[i]];
+        let (node, name) = locate(ctx, root, pos)?;
+        report.worst.push(Offender {
+            pos,
+            grad: out[i],
+            norm,
+            node,
+            name,
+        });
+    }
+    Ok(report)
+}
+
+/// Follows active branches from the root to the node which produced the value
+/// at the given position, returning it and the nearest name along the way
+fn locate(
+    ctx: &Context,
+    root: Node,
+    pos: [f32; 3],
+) -> Result<(Node, Option<String>), Error> {
+    let [x, y, z] = pos.map(|p| p as f64);
+    let mut node = root;
+    let mut name = None;
+    loop {
+        if let Some(n) = ctx.get_name(node)? {
+            name = Some(String::from(n));
+        }
+        node = match *ctx.get_op(node).ok_or(Error::BadNode)? {
+            Op::Binary(op @ (BinaryOpcode::Min | BinaryOpcode::Max), a, b) => {
+                let va = ctx.eval_xyz(a, x, y, z)?;
+                let vb = ctx.eval_xyz(b, x, y, z)?;
+                let pick_a = if op == BinaryOpcode::Min {
+                    va <= vb
+                } else {
+                    va >= vb
+                };
+                if pick_a {
+                    a
+                } else {
+                    b
+                }
+            }
+            Op::Unary(UnaryOpcode::Neg, a) => a,
+            _ => break,
+        };
+    }
+    Ok((node, name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_check_distance_field<F: Family>() {
+        let mut ctx = Context::new();
+        let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+        let r = ctx.hypot(x, y).unwrap();
+        let r = ctx.hypot(r, z).unwrap();
+        let sphere = ctx.sub(r, 0.5).unwrap();
+
+        let region = BoundingBox::new([-1.0; 3], [1.0; 3]);
+        let settings = Settings {
+            resolution: 8,
+            ..Settings::default()
+        };
+        let report =
+            check_distance_field::<F>(&ctx, sphere, region, &settings).unwrap();
+        assert!(report.is_valid(), "{report:?}");
+        assert_eq!(report.samples, 512);
+        assert!(report.worst.is_empty());
+        assert!((report.max_norm - 1.0).abs() < 1e-5);
+        assert!((report.mean_norm - 1.0).abs() < 1e-5);
+
+        // An implicit sphere (r² - 0.25) isn't a distance field
+        let r2 = ctx.square(r).unwrap();
+        let implicit = ctx.sub(r2, 0.25).unwrap();
+        ctx.name(implicit, "implicit").unwrap();
+        let slab = ctx.sub(z, 0.8).unwrap();
+        let shape = ctx.max(implicit, slab).unwrap();
+        let shape = ctx.neg(shape).unwrap();
+        let report =
+            check_distance_field::<F>(&ctx, shape, region, &settings).unwrap();
+        assert!(!report.is_valid());
+        assert!(report.too_steep > 0 && report.too_shallow > 0);
+        assert_eq!(report.worst.len(), settings.max_offenders);
+        let w = &report.worst[0];
+        assert_eq!(w.node, implicit);
+        assert_eq!(w.name.as_deref(), Some("implicit"));
+        assert_eq!(w.norm, report.max_norm);
+        assert!(report.worst.windows(2).all(|w| w[0].norm >= w[1].norm));
+
+        // Checking near the surface only, with a matching scale
+        let settings = Settings {
+            resolution: 16,
+            scale: 1.0,
+            tolerance: 0.25,
+            band: Some(0.05),
+            ..settings
+        };
+        let report =
+            check_distance_field::<F>(&ctx, implicit, region, &settings)
+                .unwrap();
+        assert!(report.samples > 0 && report.samples < 16 * 16 * 16);
+        assert!(report.is_valid(), "{report:?}");
+
+        assert!(matches!(
+            check_distance_field::<F>(
+                &ctx,
+                sphere,
+                BoundingBox::INFINITE,
+                &settings
+            ),
+            Err(Error::InfiniteRegion)
+        ));
+    }
+
+    #[test]
+    fn test_check_distance_field_vm() {
+        test_check_distance_field::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_check_distance_field_jit() {
+        test_check_distance_field::<crate::jit::Eval>();
+    }
+}
@@ -0,0 +1,650 @@
+//! Affine arithmetic interpreter
+//!
+//! Interval arithmetic treats every appearance of a value as independent, so
+//! it can badly over-estimate the range of expressions with correlated terms:
+//! for `x` in `[0, 1]`, `x - x` evaluates to `[-1, 1]`.  The [`Affine`] family
+//! instead represents each intermediate value as an affine form
+//!
+//! ```text
+//! c + dx·εx + dy·εy + dz·εz ± e
+//! ```
+//!
+//! where `εx`, `εy`, and `εz` are unknowns in `[-1, 1]` which are shared by
+//! every value derived from the `x`, `y`, and `z` inputs.  Linear operations
+//! are exact; nonlinear operations are replaced by their best linear
+//! approximation over the argument's range, with the approximation error
+//! folded into `e`.
+//!
+//! Each value also carries an ordinary interval, which is intersected with the
+//! range of its affine form, so results are never looser than plain interval
+//! arithmetic.  This is more expensive than [`vm::Eval`](crate::vm::Eval)'s
+//! interval evaluator, but can prune much more space when rendering or meshing
+//! shapes built from correlated terms; select it by passing [`Affine`] as the
+//! evaluator family.
+//!
+//! ```
+//! use fidget::{context::Context, vm};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let sum = ctx.add(x, y)?;
+//! let out = ctx.sub(sum, x)?; // (x + y) - x
+//!
+//! let tape = ctx.get_tape::<vm::Eval>(out)?;
+//! let eval = tape.new_interval_evaluator();
+//! let (i, _) = eval.eval([0.0, 1.0], [2.0, 3.0], [0.0; 2], &[])?;
+//! assert_eq!(i, [1.0, 4.0].into());
+//!
+//! let tape = ctx.get_tape::<vm::Affine>(out)?;
+//! let eval = tape.new_interval_evaluator();
+//! let (i, _) = eval.eval([0.0, 1.0], [2.0, 3.0], [0.0; 2], &[])?;
+//! assert_eq!(i, [2.0, 3.0].into());
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{
+        interval_slice::TracingSliceEval, noise, tracing::TracingEvaluator,
+        types::Interval, Choice, EvaluatorStorage, Family, Tape,
+    },
+    vm::{
+        eval::{AsmTracingEvalData, SlotArray},
+        Interpreted, Op,
+    },
+};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Family of evaluators that use affine arithmetic for interval evaluation
+///
+/// Every other kind of evaluation runs in the [`vm::Eval`](crate::vm::Eval)
+/// interpreter.
+#[derive(Clone)]
+pub enum Affine {}
+
+impl Family for Affine {
+    /// This is interpreted, so we can use the maximum number of registers
+    const REG_LIMIT: u8 = u8::MAX;
+    const LANE_WIDTH: usize = super::eval::LANES;
+
+    type IntervalEval = AffineEval;
+    type IntervalSliceEval = TracingSliceEval<AffineEval>;
+    type PointEval = Interpreted;
+    type FloatSliceEval = Interpreted;
+    type GradSliceEval = Interpreted;
+    type HessianSliceEval = Interpreted;
+
+    fn tile_sizes_3d() -> &'static [usize] {
+        &[256, 128, 64, 32, 16, 8]
+    }
+
+    fn tile_sizes_2d() -> &'static [usize] {
+        &[256, 128, 64, 32, 16, 8]
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Affine form `c + Σ dᵢ·εᵢ ± e`, along with an interval bounding its value
+///
+/// The noise symbols `εᵢ` are associated with the `x`, `y`, and `z` inputs.
+#[derive(Copy, Clone, Debug)]
+pub struct AffineForm {
+    /// Central value
+    center: f32,
+    /// Partial deviations for each input's noise symbol
+    terms: [f32; 3],
+    /// Magnitude of the uncorrelated error term
+    err: f32,
+    /// Result of the same calculation in interval arithmetic
+    range: Interval,
+}
+
+impl From<f32> for AffineForm {
+    fn from(v: f32) -> Self {
+        Self::from_interval(v.into())
+    }
+}
+
+impl AffineForm {
+    /// Builds an affine form which isn't correlated with any input
+    fn from_interval(range: Interval) -> Self {
+        Self {
+            center: range.midpoint(),
+            terms: [0.0; 3],
+            err: range.width() / 2.0,
+            range,
+        }
+    }
+
+    /// Builds an affine form for an input on the given axis
+    fn input(range: Interval, axis: usize) -> Self {
+        let mut terms = [0.0; 3];
+        terms[axis] = range.width() / 2.0;
+        Self {
+            center: range.midpoint(),
+            terms,
+            err: 0.0,
+            range,
+        }
+    }
+
+    /// Returns the total magnitude of every deviation
+    fn radius(&self) -> f32 {
+        self.terms.iter().map(|t| t.abs()).sum::<f32>() + self.err
+    }
+
+    /// Returns the tightest known bounds on the value
+    ///
+    /// This is the intersection of the interval with the affine form's range,
+    /// falling back to the interval if the affine form isn't finite.
+    fn bounds(&self) -> Interval {
+        if self.terms == [0.0; 3]
+            || self.range.has_nan()
+            || !self.center.is_finite()
+        {
+            return self.range;
+        }
+        let r = self.radius();
+        let lower = self.range.lower().max(self.center - r);
+        let upper = self.range.upper().min(self.center + r);
+        if lower <= upper {
+            Interval::new(lower, upper)
+        } else {
+            // Either the radius isn't finite, or rounding error made the two
+            // ranges disagree
+            self.range
+        }
+    }
+
+    /// Scales the affine form, then adds a constant and extra error
+    ///
+    /// `range` is the interval result of the whole operation.
+    fn affine(
+        self,
+        scale: f32,
+        offset: f32,
+        err: f32,
+        range: Interval,
+    ) -> Self {
+        Self {
+            center: self.center * scale + offset,
+            terms: self.terms.map(|t| t * scale),
+            err: self.err * scale.abs() + err,
+            range,
+        }
+    }
+
+    /// Widens the affine form (but not its interval) to absorb rounding error
+    fn widen_form(mut self) -> Self {
+        let r = self.center.abs() + self.radius();
+        self.err += r * 2.0 * f32::EPSILON + f32::MIN_POSITIVE;
+        self
+    }
+
+    /// Widens the affine form and its interval to absorb rounding error
+    fn widen(self) -> Self {
+        let mut out = self.widen_form();
+        out.range = out.range.widen();
+        out
+    }
+
+    /// Applies a convex or concave function, using its minimax linear
+    /// approximation over the value's bounds
+    ///
+    /// `tangent` returns the point at which `f` has a given slope, and `range`
+    /// is the result of applying `f` with interval arithmetic.
+    fn chebyshev(
+        self,
+        f: impl Fn(f32) -> f32,
+        tangent: impl Fn(f32) -> f32,
+        range: Interval,
+    ) -> Self {
+        let b = self.bounds();
+        let (lo, hi) = (b.lower(), b.upper());
+        if lo == hi || b.has_nan() {
+            // Single values (and NaN) don't need an approximation
+            return Self::from_interval(range);
+        }
+        let (flo, fhi) = (f(lo), f(hi));
+        let slope = (fhi - flo) / (hi - lo);
+        let u = tangent(slope).clamp(lo, hi);
+
+        // Distance from the secant to the function, at the point of maximum
+        // deviation; the best approximation runs halfway between them.
+        let gap = f(u) - (flo + slope * (u - lo));
+        let offset = flo - slope * lo + gap / 2.0;
+        self.affine(slope, offset, gap.abs() / 2.0, range)
+    }
+
+    fn neg(self) -> Self {
+        self.affine(-1.0, 0.0, 0.0, -self.range)
+    }
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            center: self.center + rhs.center,
+            terms: core::array::from_fn(|i| self.terms[i] + rhs.terms[i]),
+            err: self.err + rhs.err,
+            range: self.bounds() + rhs.bounds(),
+        }
+    }
+
+    fn sub(self, rhs: Self) -> Self {
+        self.add(rhs.neg())
+    }
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            center: self.center * rhs.center,
+            terms: core::array::from_fn(|i| {
+                self.center * rhs.terms[i] + rhs.center * self.terms[i]
+            }),
+            err: self.center.abs() * rhs.err
+                + rhs.center.abs() * self.err
+                + self.radius() * rhs.radius(),
+            range: self.bounds() * rhs.bounds(),
+        }
+    }
+
+    fn div(self, rhs: Self) -> Self {
+        // Use the interval quotient, which is tighter than `lhs * (1 / rhs)`
+        let range = self.bounds() / rhs.bounds();
+        Self {
+            range,
+            ..self.mul(rhs.recip())
+        }
+    }
+
+    fn abs(self) -> Self {
+        let b = self.bounds();
+        if b.lower() >= 0.0 {
+            self
+        } else if b.upper() <= 0.0 {
+            self.neg()
+        } else {
+            self.chebyshev(|v| v.abs(), |_| 0.0, b.abs())
+        }
+    }
+
+    fn square(self) -> Self {
+        self.chebyshev(|v| v * v, |s| s / 2.0, self.bounds().square())
+    }
+
+    fn sqrt(self) -> Self {
+        let b = self.bounds();
+        if b.lower() < 0.0 {
+            Self::from_interval(b.sqrt())
+        } else {
+            self.chebyshev(|v| v.sqrt(), |s| 1.0 / (4.0 * s * s), b.sqrt())
+        }
+    }
+
+    fn recip(self) -> Self {
+        let b = self.bounds();
+        let range = b.recip();
+        if range.has_nan() {
+            return Self::from_interval(range);
+        }
+        let sign = if b.lower() < 0.0 { -1.0 } else { 1.0 };
+        self.chebyshev(|v| 1.0 / v, |s| (-1.0 / s).sqrt() * sign, range)
+    }
+
+    /// Picks the result of `min` or `max`, keeping the affine form of a branch
+    /// which is always chosen
+    ///
+    /// (`NaN` arguments always produce [`Choice::Both`])
+    fn choose(self, rhs: Self, (range, choice): (Interval, Choice)) -> Self {
+        match choice {
+            Choice::Left => self,
+            Choice::Right => rhs,
+            _ => Self::from_interval(range),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Affine arithmetic evaluator
+#[derive(Clone)]
+pub struct AffineEval {
+    /// Instruction tape, in reverse-evaluation order
+    tape: Tape<Affine>,
+}
+
+impl EvaluatorStorage<Affine> for AffineEval {
+    type Storage = ();
+    fn new_with_storage(tape: &Tape<Affine>, _storage: ()) -> Self {
+        Self { tape: tape.clone() }
+    }
+    fn take(self) -> Option<Self::Storage> {
+        Some(())
+    }
+}
+
+impl TracingEvaluator<Interval, Affine> for AffineEval {
+    type Data = AsmTracingEvalData<AffineForm>;
+
+    fn eval_with(
+        &self,
+        x: Interval,
+        y: Interval,
+        z: Interval,
+        vars: &[f32],
+        choices: &mut [Choice],
+        data: &mut Self::Data,
+    ) -> (Interval, bool) {
+        let mut simplify = false;
+        let conservative = self.tape.conservative_intervals();
+        let nan = self.tape.nan_policy();
+        assert_eq!(vars.len(), self.tape.var_count());
+
+        let mut choice_index = 0;
+        let mut v = SlotArray(&mut data.slots);
+        for op in self.tape.iter_asm() {
+            match op {
+                Op::Input(out, i) => {
+                    v[out] = match i {
+                        0 => AffineForm::input(x, 0),
+                        1 => AffineForm::input(y, 1),
+                        2 => AffineForm::input(z, 2),
+                        _ => panic!("Invalid input: {}", i),
+                    }
+                }
+                Op::Var(out, i) => {
+                    v[out] = vars[i as usize].into();
+                }
+                Op::NegReg(out, arg) => {
+                    v[out] = v[arg].neg();
+                }
+                Op::AbsReg(out, arg) => {
+                    v[out] = v[arg].abs();
+                }
+                Op::RecipReg(out, arg) => {
+                    v[out] = v[arg].recip();
+                }
+                Op::SqrtReg(out, arg) => {
+                    v[out] = v[arg].sqrt();
+                }
+                Op::SquareReg(out, arg) => {
+                    v[out] = v[arg].square();
+                }
+                Op::SinReg(out, arg) => {
+                    v[out] = AffineForm::from_interval(v[arg].bounds().sin());
+                }
+                Op::CosReg(out, arg) => {
+                    v[out] = AffineForm::from_interval(v[arg].bounds().cos());
+                }
+                Op::CopyReg(out, arg) => v[out] = v[arg],
+                Op::AddRegImm(out, arg, imm) => {
+                    v[out] = v[arg].add(imm.into());
+                }
+                Op::MulRegImm(out, arg, imm) => {
+                    let arg = v[arg];
+                    v[out] =
+                        arg.affine(imm, 0.0, 0.0, arg.bounds() * imm.into());
+                }
+                Op::DivRegImm(out, arg, imm) => {
+                    v[out] = v[arg].div(imm.into());
+                }
+                Op::DivImmReg(out, arg, imm) => {
+                    v[out] = AffineForm::from(imm).div(v[arg]);
+                }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    let range = v[arg].bounds().atan2(imm.into());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    let range = Interval::from(imm).atan2(v[arg].bounds());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    let range = v[arg].bounds().hypot(imm.into());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    let range = v[arg].bounds().modulo(imm.into());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    let range = Interval::from(imm).modulo(v[arg].bounds());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::SubImmReg(out, arg, imm) => {
+                    v[out] = AffineForm::from(imm).sub(v[arg]);
+                }
+                Op::SubRegImm(out, arg, imm) => {
+                    v[out] = v[arg].sub(imm.into());
+                }
+                Op::MinRegImm(out, arg, imm) => {
+                    let (lhs, rhs) = (v[arg], AffineForm::from(imm));
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .min_choice(nan.interval(rhs.range));
+                    v[out] = lhs.choose(rhs, r);
+                    choices[choice_index] = r.1;
+                    choice_index += 1;
+                    simplify |= r.1 != Choice::Both;
+                }
+                Op::MaxRegImm(out, arg, imm) => {
+                    let (lhs, rhs) = (v[arg], AffineForm::from(imm));
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .max_choice(nan.interval(rhs.range));
+                    v[out] = lhs.choose(rhs, r);
+                    choices[choice_index] = r.1;
+                    choice_index += 1;
+                    simplify |= r.1 != Choice::Both;
+                }
+                Op::MinNcRegImm(out, arg, imm) => {
+                    let (lhs, rhs) = (v[arg], AffineForm::from(imm));
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .min_choice(nan.interval(rhs.range));
+                    v[out] = lhs.choose(rhs, r);
+                }
+                Op::MaxNcRegImm(out, arg, imm) => {
+                    let (lhs, rhs) = (v[arg], AffineForm::from(imm));
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .max_choice(nan.interval(rhs.range));
+                    v[out] = lhs.choose(rhs, r);
+                }
+                Op::AddRegReg(out, lhs, rhs) => v[out] = v[lhs].add(v[rhs]),
+                Op::MulRegReg(out, lhs, rhs) => v[out] = v[lhs].mul(v[rhs]),
+                Op::DivRegReg(out, lhs, rhs) => v[out] = v[lhs].div(v[rhs]),
+                Op::SubRegReg(out, lhs, rhs) => v[out] = v[lhs].sub(v[rhs]),
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    let range = v[lhs].bounds().atan2(v[rhs].bounds());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    let range =
+                        op.eval_interval(v[lhs].bounds(), v[rhs].bounds());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    let range = noise::noise3_interval(
+                        v[x].bounds(),
+                        v[y].bounds(),
+                        v[z].bounds(),
+                        seed,
+                    );
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
+                    let (lo, hi) = (v[lo].bounds(), v[hi].bounds());
+                    let range = v[x].bounds();
+                    // If the clamp never applies, the result is exactly `x`,
+                    // so we can keep its correlations with other values
+                    v[out] = if range.lower() >= lo.upper()
+                        && range.upper() <= hi.lower()
+                    {
+                        v[x]
+                    } else {
+                        AffineForm::from_interval(range.clamp(lo, hi))
+                    };
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    let range = v[lhs].bounds().hypot(v[rhs].bounds());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    let range = v[lhs].bounds().modulo(v[rhs].bounds());
+                    v[out] = AffineForm::from_interval(range);
+                }
+                Op::FmaRegRegReg(out, a, b, c) => {
+                    let mut p = v[a].mul(v[b]);
+                    if conservative {
+                        // The product is rounded separately, so widen it too
+                        p = p.widen();
+                    }
+                    v[out] = p.add(v[c]);
+                }
+                Op::MinRegReg(out, lhs, rhs) => {
+                    let (lhs, rhs) = (v[lhs], v[rhs]);
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .min_choice(nan.interval(rhs.bounds()));
+                    v[out] = lhs.choose(rhs, r);
+                    choices[choice_index] = r.1;
+                    simplify |= r.1 != Choice::Both;
+                    choice_index += 1;
+                }
+                Op::MaxRegReg(out, lhs, rhs) => {
+                    let (lhs, rhs) = (v[lhs], v[rhs]);
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .max_choice(nan.interval(rhs.bounds()));
+                    v[out] = lhs.choose(rhs, r);
+                    choices[choice_index] = r.1;
+                    simplify |= r.1 != Choice::Both;
+                    choice_index += 1;
+                }
+                Op::MinNcRegReg(out, lhs, rhs) => {
+                    let (lhs, rhs) = (v[lhs], v[rhs]);
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .min_choice(nan.interval(rhs.bounds()));
+                    v[out] = lhs.choose(rhs, r);
+                }
+                Op::MaxNcRegReg(out, lhs, rhs) => {
+                    let (lhs, rhs) = (v[lhs], v[rhs]);
+                    let r = nan
+                        .interval(lhs.bounds())
+                        .max_choice(nan.interval(rhs.bounds()));
+                    v[out] = lhs.choose(rhs, r);
+                }
+                Op::CopyImm(out, imm) => {
+                    v[out] = imm.into();
+                }
+                Op::Load(out, mem) => {
+                    v[out] = v[mem];
+                }
+                Op::Store(out, mem) => {
+                    v[mem] = v[out];
+                }
+            }
+            if conservative {
+                match op {
+                    // These are exact in interval arithmetic, but their affine
+                    // forms may be rounded
+                    Op::Input(out, ..) | Op::AbsReg(out, ..) => {
+                        v[out] = v[out].widen_form();
+                    }
+                    _ => {
+                        if let Some(out) = op.rounded_output() {
+                            v[out] = v[out].widen();
+                        }
+                    }
+                }
+            }
+        }
+        (data.slots[0].bounds(), simplify)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    #[test]
+    fn test_affine_cancellation() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.mul(x, 2.0).unwrap();
+        let b = ctx.add(x, y).unwrap();
+        let out = ctx.sub(a, b).unwrap(); // 2x - (x + y) = x - y
+        let tape = ctx.get_tape::<Affine>(out).unwrap();
+        let eval = tape.new_interval_evaluator();
+        let i = eval.eval_xy([1.0, 2.0], [0.0, 0.5]);
+        assert_eq!(i, [0.5, 2.0].into());
+
+        // A nonlinear term is approximated, but tracks its input
+        let sq = ctx.square(x).unwrap();
+        let out = ctx.sub(sq, x).unwrap(); // x² - x
+        let tape = ctx.get_tape::<Affine>(out).unwrap();
+        let eval = tape.new_interval_evaluator();
+        let i = eval.eval_x([2.0, 3.0]);
+        assert!(i.lower() > 1.7 && i.upper() < 6.1, "{i:?}");
+        let plain = ctx.get_tape::<crate::vm::Eval>(out).unwrap();
+        let plain = plain.new_interval_evaluator().eval_x([2.0, 3.0]);
+        assert_eq!(plain, [1.0, 7.0].into());
+    }
+
+    #[test]
+    fn test_affine_encloses() {
+        // Every point result must be within the interval result
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let r = ctx.hypot(x, y).unwrap();
+        let s = ctx.sqrt(r).unwrap();
+        let q = ctx.square(z).unwrap();
+        let q = ctx.add(q, 1.0).unwrap();
+        let d = ctx.div(x, q).unwrap();
+        let p = ctx.mul(d, y).unwrap();
+        let a = ctx.abs(p).unwrap();
+        let a = ctx.add(a, 0.5).unwrap();
+        let a = ctx.recip(a).unwrap();
+        let m = ctx.min(s, a).unwrap();
+        let out = ctx.sub(m, d).unwrap();
+        let tape = ctx.get_tape::<Affine>(out).unwrap();
+        let ieval = tape.new_interval_evaluator();
+        let peval = tape.new_point_evaluator();
+
+        let ranges = [[-1.0, 0.5], [0.25, 2.0], [-3.0, -1.5], [-0.5, 1.5]];
+        for xs in ranges {
+            for ys in ranges {
+                for zs in ranges {
+                    let (i, _) = ieval.eval(xs, ys, zs, &[]).unwrap();
+                    for k in 0..=8 {
+                        let t = k as f32 / 8.0;
+                        let lerp =
+                            |v: [f32; 2], t: f32| v[0] * (1.0 - t) + v[1] * t;
+                        let (px, py, pz) =
+                            (lerp(xs, t), lerp(ys, 1.0 - t), lerp(zs, t * t));
+                        let (p, _) = peval.eval(px, py, pz, &[]).unwrap();
+                        assert!(
+                            p >= i.lower() - 1e-5 && p <= i.upper() + 1e-5,
+                            "{p} not in {i:?} at ({px}, {py}, {pz})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    crate::interval_tests!(Affine);
+    crate::interval_slice_tests!(Affine);
+    crate::point_tests!(Affine);
+    crate::float_slice_tests!(Affine);
+    crate::grad_slice_tests!(Affine);
+    crate::hessian_slice_tests!(Affine);
+}
@@ -3,6 +3,7 @@ use crate::{
     vm::{lru::Lru, Op, Tape},
 };
 
+use alloc::{vec, vec::Vec};
 use arrayvec::ArrayVec;
 
 #[derive(Copy, Clone, Debug)]
@@ -117,7 +118,13 @@ impl RegisterAllocator {
     /// Claims the internal `Vec<Op>`, leaving it empty
     #[inline]
     pub fn finalize(&mut self) -> Tape {
-        std::mem::take(&mut self.out)
+        core::mem::take(&mut self.out)
+    }
+
+    /// Returns the register limit with which this allocator was built
+    #[inline]
+    pub fn reg_limit(&self) -> u8 {
+        self.reg_limit
     }
 
     /// Returns an available memory slot.
@@ -268,12 +275,25 @@ impl RegisterAllocator {
             SsaOp::RecipReg(out, arg) => (out, arg, Op::RecipReg),
             SsaOp::SqrtReg(out, arg) => (out, arg, Op::SqrtReg),
             SsaOp::SquareReg(out, arg) => (out, arg, Op::SquareReg),
+            SsaOp::SinReg(out, arg) => (out, arg, Op::SinReg),
+            SsaOp::CosReg(out, arg) => (out, arg, Op::CosReg),
             SsaOp::CopyReg(out, arg) => (out, arg, Op::CopyReg),
             _ => panic!("Bad opcode: {op:?}"),
         };
         self.op_reg_fn(out, arg, op);
     }
 
+    /// Allocates an operation, tagging the resulting instructions (including
+    /// any loads and stores) with the given debug symbol
+    ///
+    /// See [`ssa::Tape::symbols`](crate::ssa::Tape::symbols) for details.
+    #[inline]
+    pub fn op_with_symbol(&mut self, op: SsaOp, symbol: u32) {
+        let start = self.out.len();
+        self.op(op);
+        self.out.set_symbol(start, symbol);
+    }
+
     #[inline(always)]
     pub fn op(&mut self, op: SsaOp) {
         match op {
@@ -285,6 +305,8 @@ impl RegisterAllocator {
             | SsaOp::AbsReg(..)
             | SsaOp::RecipReg(..)
             | SsaOp::SqrtReg(..)
+            | SsaOp::SinReg(..)
+            | SsaOp::CosReg(..)
             | SsaOp::SquareReg(..)
             | SsaOp::CopyReg(..) => self.op_reg(op),
 
@@ -294,15 +316,159 @@ impl RegisterAllocator {
             | SsaOp::MulRegImm(..)
             | SsaOp::DivRegImm(..)
             | SsaOp::DivImmReg(..)
+            | SsaOp::Atan2RegImm(..)
+            | SsaOp::Atan2ImmReg(..)
+            | SsaOp::HypotRegImm(..)
+            | SsaOp::ModRegImm(..)
+            | SsaOp::ModImmReg(..)
             | SsaOp::MinRegImm(..)
-            | SsaOp::MaxRegImm(..) => self.op_reg_imm(op),
+            | SsaOp::MaxRegImm(..)
+            | SsaOp::MinNcRegImm(..)
+            | SsaOp::MaxNcRegImm(..) => self.op_reg_imm(op),
 
             SsaOp::AddRegReg(..)
             | SsaOp::SubRegReg(..)
             | SsaOp::MulRegReg(..)
             | SsaOp::DivRegReg(..)
+            | SsaOp::Atan2RegReg(..)
+            | SsaOp::HypotRegReg(..)
+            | SsaOp::ModRegReg(..)
             | SsaOp::MinRegReg(..)
-            | SsaOp::MaxRegReg(..) => self.op_reg_reg(op),
+            | SsaOp::MaxRegReg(..)
+            | SsaOp::MinNcRegReg(..)
+            | SsaOp::MaxNcRegReg(..) => self.op_reg_reg(op),
+
+            SsaOp::CustomRegReg(out, lhs, rhs, i) => {
+                self.op_reg_reg_fn(out, lhs, rhs, |out, lhs, rhs| {
+                    Op::CustomRegReg(out, lhs, rhs, i)
+                })
+            }
+
+            SsaOp::Noise(out, base, seed) => {
+                let (x, y, z) = (base, base + 1, base + 2);
+                self.op_reg_reg_reg_fn(out, x, y, z, |out, x, y, z| {
+                    Op::NoiseRegRegReg(out, x, y, z, seed)
+                })
+            }
+
+            SsaOp::Clamp(out, base) => {
+                let (x, lo, hi) = (base, base + 1, base + 2);
+                self.op_reg_reg_reg_fn(out, x, lo, hi, Op::ClampRegRegReg)
+            }
+
+            SsaOp::MinMany(out, base, count) => {
+                self.op_many(out, base, count, Op::MinRegReg)
+            }
+            SsaOp::MaxMany(out, base, count) => {
+                self.op_many(out, base, count, Op::MaxRegReg)
+            }
+        }
+    }
+
+    /// Lowers an n-ary `min` or `max` into a balanced tree of two-register
+    /// operations, pushing them to the internal tape.
+    ///
+    /// The arguments are read from `count` consecutive SSA slots, starting at
+    /// `base`; intermediate results are assigned new slots past the end of the
+    /// SSA tape.  Because the tape is built in reverse, the tree is evaluated
+    /// depth-first (left before right), which is the order in which
+    /// simplification consumes its choices.
+    fn op_many(
+        &mut self,
+        out: u32,
+        base: u32,
+        count: u32,
+        op: fn(u8, u8, u8) -> Op,
+    ) {
+        assert!(count >= 2);
+        let mid = count / 2;
+        let mut child = |start: u32, len: u32| {
+            if len == 1 {
+                start
+            } else {
+                self.allocations.push(u32::MAX);
+                self.allocations.len() as u32 - 1
+            }
+        };
+        let lhs = child(base, mid);
+        let rhs = child(base + mid, count - mid);
+        self.op_reg_reg_fn(out, lhs, rhs, op);
+        if count - mid > 1 {
+            self.op_many(rhs, base + mid, count - mid, op);
+        }
+        if mid > 1 {
+            self.op_many(lhs, base, mid, op);
+        }
+    }
+
+    /// Allocates a fused multiply-add (`out = a * b + c`), tagging the
+    /// resulting instructions with the given debug symbol
+    ///
+    /// `a`, `b`, and `c` are SSA registers; this lowers to an
+    /// [`FmaRegRegReg`](crate::vm::Op::FmaRegRegReg) operation.  The register
+    /// limit must be at least 4, so that binding one argument never evicts
+    /// another.
+    #[inline]
+    pub fn fma_with_symbol(
+        &mut self,
+        out: u32,
+        a: u32,
+        b: u32,
+        c: u32,
+        symbol: u32,
+    ) {
+        let start = self.out.len();
+        self.op_reg_reg_reg_fn(out, a, b, c, Op::FmaRegRegReg);
+        self.out.set_symbol(start, symbol);
+    }
+
+    /// Lowers a function taking three registers into an
+    /// [`Op`](crate::vm::Op), pushing it to the internal tape.
+    ///
+    /// Rather than enumerating every combination of allocations (as in
+    /// [`Self::op_reg_reg_fn`]), the output register is released, then each
+    /// argument is bound to a register in turn (storing it to its previous
+    /// memory slot, if it had one).  The register limit must be at least 4,
+    /// so that binding one argument never evicts another.
+    fn op_reg_reg_reg_fn(
+        &mut self,
+        out: u32,
+        a: u32,
+        b: u32,
+        c: u32,
+        op: impl Fn(u8, u8, u8, u8) -> Op,
+    ) {
+        assert!(self.reg_limit >= 4);
+        let r_x = self.get_out_reg(out);
+        self.release_reg(r_x);
+
+        // Mark arguments which are already in registers as recently used, so
+        // they won't be evicted when binding the other arguments.
+        for n in [a, b, c] {
+            self.get_allocation(n);
+        }
+        let mut regs = [0; 3];
+        let mut stores = ArrayVec::<(u8, u32), 3>::new();
+        for (r, n) in regs.iter_mut().zip([a, b, c]) {
+            *r = match self.get_allocation(n) {
+                Allocation::Register(r_y) => r_y,
+                Allocation::Memory(m_y) => {
+                    let r_y = self.get_register();
+                    self.bind_register(n, r_y);
+                    stores.push((r_y, m_y));
+                    r_y
+                }
+                Allocation::Unassigned => {
+                    let r_y = self.get_register();
+                    self.bind_register(n, r_y);
+                    r_y
+                }
+            };
+        }
+        let [r_a, r_b, r_c] = regs;
+        self.out.push(op(r_x, r_a, r_b, r_c));
+        for (r, m) in stores {
+            self.push_store(r, m);
         }
     }
 
@@ -390,7 +556,12 @@ impl RegisterAllocator {
                 self.push_store(r_x, m_y);
             }
             Allocation::Unassigned => {
-                self.out.push(op(r_x, r_x));
+                // Copying a register into itself is a no-op, which is common
+                // for the arguments of multi-argument operations
+                let op = op(r_x, r_x);
+                if !matches!(op, Op::CopyReg(..)) {
+                    self.out.push(op);
+                }
                 self.rebind_register(arg, r_x);
             }
         }
@@ -403,10 +574,48 @@ impl RegisterAllocator {
     ///
     /// If there aren't enough spare registers, this may also push `Load` or
     /// `Store` instructions to the internal tape.  It's trickier than it
-    /// sounds; look at the source code for a table showing all 18 (!) possible
-    /// configurations.
+    /// sounds; look at the source code of [`Self::op_reg_reg_fn`] for a table
+    /// showing all 18 (!) possible configurations.
     #[inline(always)]
     fn op_reg_reg(&mut self, op: SsaOp) {
+        let (out, lhs, rhs, op): (_, _, _, fn(u8, u8, u8) -> Op) = match op {
+            SsaOp::AddRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::AddRegReg),
+            SsaOp::SubRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::SubRegReg),
+            SsaOp::MulRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::MulRegReg),
+            SsaOp::DivRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::DivRegReg),
+            SsaOp::Atan2RegReg(out, lhs, rhs) => {
+                (out, lhs, rhs, Op::Atan2RegReg)
+            }
+            SsaOp::HypotRegReg(out, lhs, rhs) => {
+                (out, lhs, rhs, Op::HypotRegReg)
+            }
+            SsaOp::ModRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::ModRegReg),
+            SsaOp::MinRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::MinRegReg),
+            SsaOp::MaxRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::MaxRegReg),
+            SsaOp::MinNcRegReg(out, lhs, rhs) => {
+                (out, lhs, rhs, Op::MinNcRegReg)
+            }
+            SsaOp::MaxNcRegReg(out, lhs, rhs) => {
+                (out, lhs, rhs, Op::MaxNcRegReg)
+            }
+            _ => panic!("Bad opcode: {op:?}"),
+        };
+        self.op_reg_reg_fn(out, lhs, rhs, op);
+    }
+
+    /// Lowers a function taking two registers into an
+    /// [`Op`](crate::vm::Op), pushing it to the internal tape.
+    ///
+    /// This is the implementation of [`Self::op_reg_reg`], which is also used
+    /// for operations with extra (non-register) arguments.
+    #[inline(always)]
+    fn op_reg_reg_fn(
+        &mut self,
+        out: u32,
+        lhs: u32,
+        rhs: u32,
+        op: impl Fn(u8, u8, u8) -> Op,
+    ) {
         // Looking at this horrific table, you may be tempted to think "surely
         // there's a clean abstraction that wraps this up in a few functions".
         // You may be right, but I spent a few days chasing down terrible memory
@@ -515,15 +724,6 @@ impl RegisterAllocator {
         //       |      |      | former r_a], [m_b points to the former r_b]
         //  -----|------|------|----------------------------------------------
         //   m_x  | U   | m_z  | ibid
-        let (out, lhs, rhs, op): (_, _, _, fn(u8, u8, u8) -> Op) = match op {
-            SsaOp::AddRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::AddRegReg),
-            SsaOp::SubRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::SubRegReg),
-            SsaOp::MulRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::MulRegReg),
-            SsaOp::DivRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::DivRegReg),
-            SsaOp::MinRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::MinRegReg),
-            SsaOp::MaxRegReg(out, lhs, rhs) => (out, lhs, rhs, Op::MaxRegReg),
-            _ => panic!("Bad opcode: {op:?}"),
-        };
         let r_x = self.get_out_reg(out);
         match (self.get_allocation(lhs), self.get_allocation(rhs)) {
             (Allocation::Register(r_y), Allocation::Register(r_z)) => {
@@ -612,8 +812,25 @@ impl RegisterAllocator {
             SsaOp::MulRegImm(out, arg, imm) => (out, arg, imm, Op::MulRegImm),
             SsaOp::DivRegImm(out, arg, imm) => (out, arg, imm, Op::DivRegImm),
             SsaOp::DivImmReg(out, arg, imm) => (out, arg, imm, Op::DivImmReg),
+            SsaOp::Atan2RegImm(out, arg, imm) => {
+                (out, arg, imm, Op::Atan2RegImm)
+            }
+            SsaOp::Atan2ImmReg(out, arg, imm) => {
+                (out, arg, imm, Op::Atan2ImmReg)
+            }
+            SsaOp::HypotRegImm(out, arg, imm) => {
+                (out, arg, imm, Op::HypotRegImm)
+            }
+            SsaOp::ModRegImm(out, arg, imm) => (out, arg, imm, Op::ModRegImm),
+            SsaOp::ModImmReg(out, arg, imm) => (out, arg, imm, Op::ModImmReg),
             SsaOp::MinRegImm(out, arg, imm) => (out, arg, imm, Op::MinRegImm),
             SsaOp::MaxRegImm(out, arg, imm) => (out, arg, imm, Op::MaxRegImm),
+            SsaOp::MinNcRegImm(out, arg, imm) => {
+                (out, arg, imm, Op::MinNcRegImm)
+            }
+            SsaOp::MaxNcRegImm(out, arg, imm) => {
+                (out, arg, imm, Op::MaxNcRegImm)
+            }
             _ => panic!("Bad opcode: {op:?}"),
         };
         self.op_reg_fn(out, arg, |out, arg| op(out, arg, imm));
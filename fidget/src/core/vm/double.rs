@@ -0,0 +1,495 @@
+//! Double-precision evaluation
+//!
+//! The evaluator families in [`fidget::eval`](crate::eval) all work in single
+//! precision: constants in a [`Context`] are stored as `f64`, but are rounded
+//! to `f32` when a tape is built, and every operation is evaluated in `f32`.
+//! For models which span a large coordinate range, this may not be precise
+//! enough.
+//!
+//! This module is a separate interpreter which works in double precision.  A
+//! [`Tape`] is built directly from a `Context` (keeping constants as `f64`),
+//! then evaluated at single points with a [`PointEval`] or over intervals with
+//! an [`IntervalEval`].  Both share one interpreter loop, which is generic
+//! over the value type; interval arithmetic uses the same
+//! [`Interval`](crate::eval::types::Interval) type as the single-precision
+//! evaluators, with `f64` bounds.
+//!
+//! Compared to the single-precision families, there are a few limitations:
+//!
+//! - This isn't an [`eval::Family`](crate::eval::Family), so it can't be used
+//!   by the renderers and meshers (which are built around `f32` values)
+//! - There's no JIT compiler, because the JIT backends are built around
+//!   single-precision SIMD registers
+//! - Tapes are not simplified, so interval evaluation doesn't record choices
+//! - Custom operations and noise are evaluated in single precision (with
+//!   intervals widened to absorb rounding)
+//!
+//! ```
+//! use fidget::{context::Context, vm::double};
+//!
+//! // A sphere of radius 1, far from the origin
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let x = ctx.sub(x, 1e9)?;
+//! let r = ctx.square(x)?;
+//! let r = ctx.sqrt(r)?;
+//! let sphere = ctx.sub(r, 1.0)?;
+//!
+//! let tape = double::Tape::new(&ctx, sphere)?;
+//! let mut eval = tape.new_point_evaluator();
+//! assert_eq!(eval.eval(1e9 + 0.5, 0.0, 0.0, &[])?, -0.5);
+//!
+//! let mut eval = tape.new_interval_evaluator();
+//! let i = eval.eval(
+//!     double::Interval::new(1e9 + 1.5, 1e9 + 2.0),
+//!     0.0.into(),
+//!     0.0.into(),
+//!     &[],
+//! )?;
+//! assert_eq!(i, double::Interval::new(0.5, 1.0));
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{
+        BinaryOpcode, Context, NaryOpcode, Node, NodeBudget, Op as CtxOp,
+        UnaryOpcode,
+    },
+    eval::{noise, types, CustomOp},
+    Error,
+};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+/// Single operation in a double-precision [`Tape`]
+///
+/// Each operation writes to the slot matching its own index, and reads from
+/// the slots of earlier operations.
+#[derive(Copy, Clone, Debug)]
+enum Op {
+    Input(u8),
+    Var(u32),
+    Const(f64),
+    Unary(UnaryOpcode, u32),
+    Binary(BinaryOpcode, u32, u32),
+    Custom(u32, u32, u32),
+    Noise(u16, u32, u32, u32),
+    Clamp(u32, u32, u32),
+}
+
+struct TapeData {
+    ops: Vec<Op>,
+    vars: Arc<BTreeMap<String, u32>>,
+    custom: Vec<Arc<dyn CustomOp>>,
+}
+
+/// Double-precision tape, built directly from a [`Context`]
+///
+/// Cloning a tape is cheap, because its data is shared.
+#[derive(Clone)]
+pub struct Tape(Arc<TapeData>);
+
+impl Tape {
+    /// Flattens the subtree of `ctx` rooted at `root` into a tape
+    ///
+    /// Returns [`Error::BadNode`] if `root` is not from this `Context`,
+    /// [`Error::BadVar`] if the expression uses an input other than `X`, `Y`,
+    /// or `Z`, and [`Error::TooManyNodes`] if the expression is larger than
+    /// [`Context::node_limit`].
+    pub fn new(ctx: &Context, root: Node) -> Result<Self, Error> {
+        let mut ops = vec![];
+        let mut slots: BTreeMap<Node, u32> = BTreeMap::new();
+        let mut vars: BTreeMap<String, u32> = BTreeMap::new();
+        let mut custom: Vec<Arc<dyn CustomOp>> = vec![];
+        let mut custom_index = BTreeMap::new();
+
+        // Iterative post-order traversal, so that children are always pushed
+        // to the tape before their parents.
+        let mut todo = vec![(root, false)];
+        let mut budget = NodeBudget::new(ctx);
+        while let Some((node, expanded)) = todo.pop() {
+            if slots.contains_key(&node) {
+                continue;
+            }
+            let op = ctx.get_op(node).ok_or(Error::BadNode)?.clone();
+            if !expanded {
+                budget.visit()?;
+                todo.push((node, true));
+                todo.extend(op.iter_children().map(|c| (c, false)));
+                continue;
+            }
+            let slot = |n: Node| slots[&n];
+            let out = match op {
+                CtxOp::Input(v) => match ctx.get_var_by_index(v)? {
+                    "X" => Op::Input(0),
+                    "Y" => Op::Input(1),
+                    "Z" => Op::Input(2),
+                    _ => return Err(Error::BadVar),
+                },
+                CtxOp::Var(v) => {
+                    let name = ctx.get_var_by_index(v)?;
+                    let next = vars.len() as u32;
+                    Op::Var(*vars.entry(name.into()).or_insert(next))
+                }
+                CtxOp::Const(c) => Op::Const(c.0),
+                CtxOp::Unary(op, a) => Op::Unary(op, slot(a)),
+                CtxOp::Binary(op, a, b) => Op::Binary(op, slot(a), slot(b)),
+                CtxOp::Custom(c, a, b) => {
+                    let i = *custom_index.entry(c).or_insert_with(|| {
+                        custom.push(ctx.custom_op(c).clone());
+                        custom.len() as u32 - 1
+                    });
+                    Op::Custom(i, slot(a), slot(b))
+                }
+                CtxOp::Noise(seed, x, y, z) => {
+                    Op::Noise(seed, slot(x), slot(y), slot(z))
+                }
+                CtxOp::Clamp(x, lo, hi) => {
+                    Op::Clamp(slot(x), slot(lo), slot(hi))
+                }
+                CtxOp::Nary(op, args) => {
+                    // Lowered into a chain of binary operations
+                    let op = match op {
+                        NaryOpcode::Min => BinaryOpcode::Min,
+                        NaryOpcode::Max => BinaryOpcode::Max,
+                    };
+                    let mut acc = slot(args[0]);
+                    for &b in &args[1..args.len() - 1] {
+                        ops.push(Op::Binary(op, acc, slot(b)));
+                        acc = ops.len() as u32 - 1;
+                    }
+                    Op::Binary(op, acc, slot(args[args.len() - 1]))
+                }
+            };
+            slots.insert(node, ops.len() as u32);
+            ops.push(out);
+        }
+        Ok(Self(Arc::new(TapeData {
+            ops,
+            vars: Arc::new(vars),
+            custom,
+        })))
+    }
+
+    /// Returns the number of operations in the tape
+    pub fn len(&self) -> usize {
+        self.0.ops.len()
+    }
+
+    /// Checks whether the tape is empty
+    ///
+    /// Tapes built with [`Tape::new`] always have at least one operation.
+    pub fn is_empty(&self) -> bool {
+        self.0.ops.is_empty()
+    }
+
+    /// Returns a map from variable names to indices in the `vars` slice
+    pub fn vars(&self) -> Arc<BTreeMap<String, u32>> {
+        self.0.vars.clone()
+    }
+
+    /// Returns the number of variables used by the tape
+    pub fn var_count(&self) -> usize {
+        self.0.vars.len()
+    }
+
+    /// Builds a new point evaluator for this tape
+    pub fn new_point_evaluator(&self) -> PointEval {
+        PointEval(Evaluator::new(self))
+    }
+
+    /// Builds a new interval evaluator for this tape
+    pub fn new_interval_evaluator(&self) -> IntervalEval {
+        IntervalEval(Evaluator::new(self))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Double-precision interval
+///
+/// This is [`eval::types::Interval`](crate::eval::types::Interval) with `f64`
+/// bounds, so it has the same semantics (including the same caveats about
+/// rounding).
+pub type Interval = types::Interval<f64>;
+
+/// Value type which can be used in double-precision evaluation
+///
+/// This is implemented for `f64` and [`Interval`].
+trait Value: Copy + From<f64> {
+    /// Evaluates a unary operation
+    fn unary(op: UnaryOpcode, a: Self) -> Self;
+    /// Evaluates a binary operation
+    fn binary(op: BinaryOpcode, a: Self, b: Self) -> Self;
+    /// Evaluates a user-defined operation (in single precision)
+    fn custom(op: &dyn CustomOp, a: Self, b: Self) -> Self;
+    /// Evaluates gradient noise (in single precision)
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self;
+}
+
+impl Value for f64 {
+    fn unary(op: UnaryOpcode, a: Self) -> Self {
+        match op {
+            UnaryOpcode::Neg => -a,
+            UnaryOpcode::Abs => a.abs(),
+            UnaryOpcode::Recip => 1.0 / a,
+            UnaryOpcode::Sqrt => a.sqrt(),
+            UnaryOpcode::Square => a * a,
+            UnaryOpcode::Sin => a.sin(),
+            UnaryOpcode::Cos => a.cos(),
+        }
+    }
+    fn binary(op: BinaryOpcode, a: Self, b: Self) -> Self {
+        match op {
+            BinaryOpcode::Add => a + b,
+            BinaryOpcode::Sub => a - b,
+            BinaryOpcode::Mul => a * b,
+            BinaryOpcode::Div => a / b,
+            // Propagate NaN, matching the single-precision evaluators
+            BinaryOpcode::Min | BinaryOpcode::MinNc
+                if a.is_nan() || b.is_nan() =>
+            {
+                f64::NAN
+            }
+            BinaryOpcode::Max | BinaryOpcode::MaxNc
+                if a.is_nan() || b.is_nan() =>
+            {
+                f64::NAN
+            }
+            BinaryOpcode::Min | BinaryOpcode::MinNc => a.min(b),
+            BinaryOpcode::Max | BinaryOpcode::MaxNc => a.max(b),
+            BinaryOpcode::Atan2 => a.atan2(b),
+            BinaryOpcode::Hypot => a.hypot(b),
+            BinaryOpcode::Mod => a - b * (a / b).floor(),
+        }
+    }
+    fn custom(op: &dyn CustomOp, a: Self, b: Self) -> Self {
+        op.eval_f32(a as f32, b as f32) as f64
+    }
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3(x as f32, y as f32, z as f32, seed) as f64
+    }
+}
+
+impl Value for Interval {
+    fn unary(op: UnaryOpcode, a: Self) -> Self {
+        match op {
+            UnaryOpcode::Neg => -a,
+            UnaryOpcode::Abs => a.abs(),
+            UnaryOpcode::Recip => a.recip(),
+            UnaryOpcode::Sqrt => a.sqrt(),
+            UnaryOpcode::Square => a.square(),
+            UnaryOpcode::Sin => a.sin(),
+            UnaryOpcode::Cos => a.cos(),
+        }
+    }
+    fn binary(op: BinaryOpcode, a: Self, b: Self) -> Self {
+        match op {
+            BinaryOpcode::Add => a + b,
+            BinaryOpcode::Sub => a - b,
+            BinaryOpcode::Mul => a * b,
+            BinaryOpcode::Div => a / b,
+            BinaryOpcode::Min | BinaryOpcode::MinNc => a.min_choice(b).0,
+            BinaryOpcode::Max | BinaryOpcode::MaxNc => a.max_choice(b).0,
+            BinaryOpcode::Atan2 => a.atan2(b),
+            BinaryOpcode::Hypot => a.hypot(b),
+            BinaryOpcode::Mod => a.modulo(b),
+        }
+    }
+    fn custom(op: &dyn CustomOp, a: Self, b: Self) -> Self {
+        op.eval_interval(a.to_f32(), b.to_f32()).into()
+    }
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3_interval(x.to_f32(), y.to_f32(), z.to_f32(), seed).into()
+    }
+}
+
+/// Shared evaluator, generic over the value type
+#[derive(Clone)]
+struct Evaluator<V> {
+    tape: Tape,
+    slots: Vec<V>,
+}
+
+impl<V: Value> Evaluator<V> {
+    fn new(tape: &Tape) -> Self {
+        Self {
+            tape: tape.clone(),
+            slots: vec![V::from(f64::NAN); tape.len()],
+        }
+    }
+
+    fn eval(&mut self, x: V, y: V, z: V, vars: &[f64]) -> Result<V, Error> {
+        let t = &self.tape.0;
+        if vars.len() != t.vars.len() {
+            return Err(Error::BadVarSlice(vars.len(), t.vars.len()));
+        }
+        let v = &mut self.slots;
+        for (i, op) in t.ops.iter().enumerate() {
+            let get = |j: u32| v[j as usize];
+            v[i] = match *op {
+                Op::Input(0) => x,
+                Op::Input(1) => y,
+                Op::Input(2) => z,
+                Op::Input(_) => unreachable!(),
+                Op::Var(j) => vars[j as usize].into(),
+                Op::Const(c) => c.into(),
+                Op::Unary(op, a) => V::unary(op, get(a)),
+                Op::Binary(op, a, b) => V::binary(op, get(a), get(b)),
+                Op::Custom(c, a, b) => {
+                    V::custom(t.custom[c as usize].as_ref(), get(a), get(b))
+                }
+                Op::Noise(seed, x, y, z) => {
+                    V::noise(get(x), get(y), get(z), seed)
+                }
+                Op::Clamp(x, lo, hi) => {
+                    // Clamping is monotonic in every argument, so composing
+                    // `min` and `max` is exact for intervals
+                    let m = V::binary(BinaryOpcode::Min, get(x), get(hi));
+                    V::binary(BinaryOpcode::Max, m, get(lo))
+                }
+            };
+        }
+        Ok(*v.last().unwrap())
+    }
+}
+
+/// Double-precision point evaluator
+#[derive(Clone)]
+pub struct PointEval(Evaluator<f64>);
+
+impl PointEval {
+    /// Evaluates the tape at a single point
+    ///
+    /// Returns [`Error::BadVarSlice`] if `vars` is the wrong length.
+    pub fn eval(
+        &mut self,
+        x: f64,
+        y: f64,
+        z: f64,
+        vars: &[f64],
+    ) -> Result<f64, Error> {
+        self.0.eval(x, y, z, vars)
+    }
+}
+
+/// Double-precision interval evaluator
+#[derive(Clone)]
+pub struct IntervalEval(Evaluator<Interval>);
+
+impl IntervalEval {
+    /// Evaluates the tape over an interval region
+    ///
+    /// Returns [`Error::BadVarSlice`] if `vars` is the wrong length.
+    pub fn eval(
+        &mut self,
+        x: Interval,
+        y: Interval,
+        z: Interval,
+        vars: &[f64],
+    ) -> Result<Interval, Error> {
+        self.0.eval(x, y, z, vars)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    #[test]
+    fn test_precision() {
+        // This constant can't be represented exactly in single precision
+        let big = 16_777_217.0;
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let out = ctx.sub(x, big).unwrap();
+        let tape = Tape::new(&ctx, out).unwrap();
+        let mut eval = tape.new_point_evaluator();
+        assert_eq!(eval.eval(big, 0.0, 0.0, &[]).unwrap(), 0.0);
+        assert_eq!(eval.eval(big + 0.25, 0.0, 0.0, &[]).unwrap(), 0.25);
+
+        let mut eval = tape.new_interval_evaluator();
+        let i = eval
+            .eval(
+                Interval::new(big - 1.0, big + 1.0),
+                0.0.into(),
+                0.0.into(),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(i, Interval::new(-1.0, 1.0));
+    }
+
+    #[test]
+    fn test_vs_context() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let a = ctx.var("a").unwrap();
+        let xy = ctx.atan2(y, x).unwrap();
+        let r = ctx.hypot(x, y).unwrap();
+        let s = ctx.sin(z).unwrap();
+        let m = ctx.min(r, s).unwrap();
+        let d = ctx.div(xy, a).unwrap();
+        let out = ctx.max(m, d).unwrap();
+        let out = ctx.mul(out, a).unwrap();
+
+        let tape = Tape::new(&ctx, out).unwrap();
+        assert_eq!(tape.var_count(), 1);
+        let mut point = tape.new_point_evaluator();
+        let mut interval = tape.new_interval_evaluator();
+        for (x, y, z) in [(1.0, 2.0, 3.0), (-0.5, 0.25, -4.0), (0.0, -1.0, 1.5)]
+        {
+            let vars = BTreeMap::from([
+                ("X".into(), x),
+                ("Y".into(), y),
+                ("Z".into(), z),
+                ("a".into(), 1.5),
+            ]);
+            let expected = ctx.eval(out, &vars).unwrap();
+            let v = point.eval(x, y, z, &[1.5]).unwrap();
+            assert_eq!(v, expected);
+
+            let i =
+                interval.eval(x.into(), y.into(), z.into(), &[1.5]).unwrap();
+            assert!(i.contains(v), "{i} does not contain {v}");
+
+            let i = interval
+                .eval(
+                    Interval::new(x - 0.5, x + 0.5),
+                    Interval::new(y - 0.5, y + 0.5),
+                    Interval::new(z - 0.5, z + 0.5),
+                    &[1.5],
+                )
+                .unwrap();
+            assert!(i.contains(v), "{i} does not contain {v}");
+        }
+
+        assert!(matches!(
+            point.eval(0.0, 0.0, 0.0, &[]),
+            Err(Error::BadVarSlice(0, 1))
+        ));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let mut ctx = Context::new();
+        let mut out = ctx.x();
+        for i in 0..100 {
+            out = ctx.add(out, i as f64).unwrap();
+        }
+        assert!(Tape::new(&ctx, out).is_ok());
+        ctx.set_node_limit(50);
+        assert!(matches!(Tape::new(&ctx, out), Err(Error::TooManyNodes(50))));
+    }
+
+    #[test]
+    fn test_bad_node() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let other = Context::new();
+        assert!(matches!(Tape::new(&other, x), Err(Error::BadNode)));
+    }
+}
@@ -1,12 +1,19 @@
 use crate::{
     eval::{
         bulk::{BulkEvaluator, BulkEvaluatorData},
+        interval_slice::IntervalSliceEvaluator,
+        noise,
         tracing::{TracingEvaluator, TracingEvaluatorData},
-        types::{Grad, Interval},
-        Choice, EvaluatorStorage, Family, Tape,
+        types::{Grad, Hessian, Interval},
+        Choice, EvaluatorStorage, Family, NanPolicy, Tape,
     },
     vm::Op,
 };
+use alloc::{vec, vec::Vec};
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+#[cfg(feature = "simd")]
+use std::simd::{num::SimdFloat, Select, StdFloat};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -17,11 +24,14 @@ pub enum Eval {}
 impl Family for Eval {
     /// This is interpreted, so we can use the maximum number of registers
     const REG_LIMIT: u8 = u8::MAX;
+    const LANE_WIDTH: usize = LANES;
 
     type IntervalEval = AsmEval;
+    type IntervalSliceEval = AsmEval;
     type PointEval = AsmEval;
     type FloatSliceEval = AsmEval;
     type GradSliceEval = AsmEval;
+    type HessianSliceEval = AsmEval;
 
     fn tile_sizes_3d() -> &'static [usize] {
         &[256, 128, 64, 32, 16, 8]
@@ -34,26 +44,153 @@ impl Family for Eval {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Minimum of two values, propagating `NaN` (unlike [`f32::min`])
+pub(super) fn nan_min(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        a.min(b)
+    }
+}
+
+/// Maximum of two values, propagating `NaN` (unlike [`f32::max`])
+pub(super) fn nan_max(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        a.max(b)
+    }
+}
+
+/// Floored modulo, `a - b * floor(a / b)` (unlike the `%` operator)
+pub(super) fn modulo(a: f32, b: f32) -> f32 {
+    a - b * (a / b).floor()
+}
+
+/// Number of points which the float slice interpreter evaluates together
+///
+/// With the `simd` feature, this is the width of a `std::simd` vector;
+/// otherwise, the interpreter runs one point at a time.
+#[cfg(feature = "simd")]
+pub(super) const LANES: usize = 8;
+#[cfg(not(feature = "simd"))]
+pub(super) const LANES: usize = 1;
+
+/// SIMD vector type used by the float slice interpreter
+#[cfg(feature = "simd")]
+type Lanes = core::simd::Simd<f32, LANES>;
+
+/// Operations used by the float slice interpreter
+///
+/// This is implemented for both `f32` and (with the `simd` feature) a vector of
+/// [`LANES`] values, so that a single expression can be used for both the
+/// vectorized body and the scalar tail of a slice.
+trait Lane: Copy {
+    fn splat(v: f32) -> Self;
+    fn nan_min(self, other: Self) -> Self;
+    fn nan_max(self, other: Self) -> Self;
+    fn modulo(self, other: Self) -> Self;
+    fn nan_policy(self, nan: NanPolicy) -> Self;
+}
+
+impl Lane for f32 {
+    fn splat(v: f32) -> Self {
+        v
+    }
+    fn nan_min(self, other: Self) -> Self {
+        nan_min(self, other)
+    }
+    fn nan_max(self, other: Self) -> Self {
+        nan_max(self, other)
+    }
+    fn modulo(self, other: Self) -> Self {
+        modulo(self, other)
+    }
+    fn nan_policy(self, nan: NanPolicy) -> Self {
+        nan.float(self)
+    }
+}
+
+#[cfg(feature = "simd")]
+impl Lane for Lanes {
+    fn splat(v: f32) -> Self {
+        Lanes::splat(v)
+    }
+    fn nan_min(self, other: Self) -> Self {
+        let nan = self.is_nan() | other.is_nan();
+        nan.select(Lanes::splat(f32::NAN), self.simd_min(other))
+    }
+    fn nan_max(self, other: Self) -> Self {
+        let nan = self.is_nan() | other.is_nan();
+        nan.select(Lanes::splat(f32::NAN), self.simd_max(other))
+    }
+    fn modulo(self, other: Self) -> Self {
+        self - other * (self / other).floor()
+    }
+    fn nan_policy(self, nan: NanPolicy) -> Self {
+        match nan {
+            NanPolicy::Empty => {
+                self.is_nan().select(Lanes::splat(f32::INFINITY), self)
+            }
+            _ => self,
+        }
+    }
+}
+
+/// Evaluates an expression across a slice, [`LANES`] points at a time
+///
+/// The expression is written in terms of a type `$t`, which implements
+/// [`Lane`], and named slot values, e.g.
+/// `lanes!(v, size, out, |T, a = lhs, b = rhs| a + b)`.  It's evaluated once
+/// with `$t` as the SIMD type, then again with `$t = f32` for the remainder.
+macro_rules! lanes {
+    ($v:ident, $size:ident, $out:ident,
+     |$t:ident $(, $name:ident = $arg:ident)*| $body:expr) => {{
+        #[allow(unused_mut)]
+        let mut start = 0;
+        #[cfg(feature = "simd")]
+        {
+            #[allow(dead_code)]
+            type $t = Lanes;
+            while start + LANES <= $size {
+                let r = start..start + LANES;
+                $(let $name = $t::from_slice(&$v[$arg][r.clone()]);)*
+                let res: $t = $body;
+                res.copy_to_slice(&mut $v[$out][r]);
+                start += LANES;
+            }
+        }
+        {
+            #[allow(dead_code)]
+            type $t = f32;
+            for i in start..$size {
+                $(let $name = $v[$arg][i];)*
+                $v[$out][i] = $body;
+            }
+        }
+    }};
+}
+
 /// Helper struct to reduce boilerplate conversions
-struct SlotArray<'a, T>(&'a mut [T]);
-impl<T> std::ops::Index<u8> for SlotArray<'_, T> {
+pub(super) struct SlotArray<'a, T>(pub(super) &'a mut [T]);
+impl<T> core::ops::Index<u8> for SlotArray<'_, T> {
     type Output = T;
     fn index(&self, i: u8) -> &Self::Output {
         &self.0[i as usize]
     }
 }
-impl<T> std::ops::IndexMut<u8> for SlotArray<'_, T> {
+impl<T> core::ops::IndexMut<u8> for SlotArray<'_, T> {
     fn index_mut(&mut self, i: u8) -> &mut T {
         &mut self.0[i as usize]
     }
 }
-impl<T> std::ops::Index<u32> for SlotArray<'_, T> {
+impl<T> core::ops::Index<u32> for SlotArray<'_, T> {
     type Output = T;
     fn index(&self, i: u32) -> &Self::Output {
         &self.0[i as usize]
     }
 }
-impl<T> std::ops::IndexMut<u32> for SlotArray<'_, T> {
+impl<T> core::ops::IndexMut<u32> for SlotArray<'_, T> {
     fn index_mut(&mut self, i: u32) -> &mut T {
         &mut self.0[i as usize]
     }
@@ -70,7 +207,7 @@ pub struct AsmEval {
 
 /// Generic scratch data a tracing evaluator
 pub struct AsmTracingEvalData<T> {
-    slots: Vec<T>,
+    pub(super) slots: Vec<T>,
 }
 
 impl<T> Default for AsmTracingEvalData<T> {
@@ -79,16 +216,16 @@ impl<T> Default for AsmTracingEvalData<T> {
     }
 }
 
-impl<T> TracingEvaluatorData<Eval> for AsmTracingEvalData<T>
+impl<T, F> TracingEvaluatorData<F> for AsmTracingEvalData<T>
 where
     T: From<f32> + Clone,
 {
-    fn prepare(&mut self, tape: &Tape<Eval>) {
-        assert!(tape.reg_limit() == u8::MAX);
-
+    fn prepare(&mut self, tape: &Tape<F>) {
+        // The interpreter can evaluate tapes which were planned with any
+        // register limit (e.g. for lazy JIT evaluation), so we don't check it.
         let slot_count = tape.slot_count();
-        self.slots.resize(slot_count, T::from(std::f32::NAN));
-        self.slots.fill(T::from(std::f32::NAN));
+        self.slots.resize(slot_count, T::from(core::f32::NAN));
+        self.slots.fill(T::from(core::f32::NAN));
     }
 }
 
@@ -117,6 +254,8 @@ impl TracingEvaluator<Interval, Eval> for AsmEval {
         data: &mut Self::Data,
     ) -> (Interval, bool) {
         let mut simplify = false;
+        let conservative = self.tape.conservative_intervals();
+        let nan = self.tape.nan_policy();
         assert_eq!(vars.len(), self.tape.var_count());
 
         let mut choice_index = 0;
@@ -146,6 +285,12 @@ impl TracingEvaluator<Interval, Eval> for AsmEval {
                 Op::SqrtReg(out, arg) => {
                     v[out] = v[arg].sqrt();
                 }
+                Op::SinReg(out, arg) => {
+                    v[out] = v[arg].sin();
+                }
+                Op::CosReg(out, arg) => {
+                    v[out] = v[arg].cos();
+                }
                 Op::SquareReg(out, arg) => {
                     v[out] = v[arg].square();
                 }
@@ -163,6 +308,21 @@ impl TracingEvaluator<Interval, Eval> for AsmEval {
                     let imm: Interval = imm.into();
                     v[out] = imm / v[arg];
                 }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    v[out] = v[arg].atan2(imm.into());
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    v[out] = Interval::from(imm).atan2(v[arg]);
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    v[out] = v[arg].hypot(imm.into());
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    v[out] = v[arg].modulo(imm.into());
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    v[out] = Interval::from(imm).modulo(v[arg]);
+                }
                 Op::SubImmReg(out, arg, imm) => {
                     v[out] = Interval::from(imm) - v[arg];
                 }
@@ -170,37 +330,86 @@ impl TracingEvaluator<Interval, Eval> for AsmEval {
                     v[out] = v[arg] - imm.into();
                 }
                 Op::MinRegImm(out, arg, imm) => {
-                    let (value, choice) = v[arg].min_choice(imm.into());
+                    let (value, choice) = nan
+                        .interval(v[arg])
+                        .min_choice(nan.interval(imm.into()));
                     v[out] = value;
-                    choices[choice_index] |= choice;
+                    choices[choice_index] = choice;
                     choice_index += 1;
                     simplify |= choice != Choice::Both;
                 }
                 Op::MaxRegImm(out, arg, imm) => {
-                    let (value, choice) = v[arg].max_choice(imm.into());
+                    let (value, choice) = nan
+                        .interval(v[arg])
+                        .max_choice(nan.interval(imm.into()));
                     v[out] = value;
-                    choices[choice_index] |= choice;
+                    choices[choice_index] = choice;
                     choice_index += 1;
                     simplify |= choice != Choice::Both;
                 }
+                Op::MinNcRegImm(out, arg, imm) => {
+                    let imm = nan.interval(imm.into());
+                    v[out] = nan.interval(v[arg]).min_choice(imm).0;
+                }
+                Op::MaxNcRegImm(out, arg, imm) => {
+                    let imm = nan.interval(imm.into());
+                    v[out] = nan.interval(v[arg]).max_choice(imm).0;
+                }
                 Op::AddRegReg(out, lhs, rhs) => v[out] = v[lhs] + v[rhs],
                 Op::MulRegReg(out, lhs, rhs) => v[out] = v[lhs] * v[rhs],
                 Op::DivRegReg(out, lhs, rhs) => v[out] = v[lhs] / v[rhs],
                 Op::SubRegReg(out, lhs, rhs) => v[out] = v[lhs] - v[rhs],
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    v[out] = v[lhs].atan2(v[rhs]);
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    v[out] = op.eval_interval(v[lhs], v[rhs]);
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    v[out] = noise::noise3_interval(v[x], v[y], v[z], seed);
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
+                    v[out] = v[x].clamp(v[lo], v[hi]);
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    v[out] = v[lhs].hypot(v[rhs]);
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    v[out] = v[lhs].modulo(v[rhs]);
+                }
+                Op::FmaRegRegReg(out, a, b, c) => {
+                    let mut p = v[a] * v[b];
+                    if conservative {
+                        // The product is rounded separately, so widen it too
+                        p = p.widen();
+                    }
+                    v[out] = p + v[c];
+                }
                 Op::MinRegReg(out, lhs, rhs) => {
-                    let (value, choice) = v[lhs].min_choice(v[rhs]);
+                    let (value, choice) =
+                        nan.interval(v[lhs]).min_choice(nan.interval(v[rhs]));
                     v[out] = value;
-                    choices[choice_index] |= choice;
+                    choices[choice_index] = choice;
                     simplify |= choice != Choice::Both;
                     choice_index += 1;
                 }
                 Op::MaxRegReg(out, lhs, rhs) => {
-                    let (value, choice) = v[lhs].max_choice(v[rhs]);
+                    let (value, choice) =
+                        nan.interval(v[lhs]).max_choice(nan.interval(v[rhs]));
                     v[out] = value;
-                    choices[choice_index] |= choice;
+                    choices[choice_index] = choice;
                     simplify |= choice != Choice::Both;
                     choice_index += 1;
                 }
+                Op::MinNcRegReg(out, lhs, rhs) => {
+                    let rhs = nan.interval(v[rhs]);
+                    v[out] = nan.interval(v[lhs]).min_choice(rhs).0;
+                }
+                Op::MaxNcRegReg(out, lhs, rhs) => {
+                    let rhs = nan.interval(v[rhs]);
+                    v[out] = nan.interval(v[lhs]).max_choice(rhs).0;
+                }
                 Op::CopyImm(out, imm) => {
                     v[out] = imm.into();
                 }
@@ -211,6 +420,9 @@ impl TracingEvaluator<Interval, Eval> for AsmEval {
                     v[mem] = v[out];
                 }
             }
+            if let Some(out) = op.rounded_output().filter(|_| conservative) {
+                v[out] = v[out].widen();
+            }
         }
         (data.slots[0], simplify)
     }
@@ -231,6 +443,7 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
         assert_eq!(vars.len(), self.tape.var_count());
         let mut choice_index = 0;
         let mut simplify = false;
+        let nan = self.tape.nan_policy();
         let mut v = SlotArray(&mut data.slots);
         for op in self.tape.iter_asm() {
             match op {
@@ -255,6 +468,12 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                 Op::SqrtReg(out, arg) => {
                     v[out] = v[arg].sqrt();
                 }
+                Op::SinReg(out, arg) => {
+                    v[out] = v[arg].sin();
+                }
+                Op::CosReg(out, arg) => {
+                    v[out] = v[arg].cos();
+                }
                 Op::SquareReg(out, arg) => {
                     let s = v[arg];
                     v[out] = s * s;
@@ -274,6 +493,21 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                 Op::DivImmReg(out, arg, imm) => {
                     v[out] = imm / v[arg];
                 }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    v[out] = v[arg].atan2(imm);
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    v[out] = imm.atan2(v[arg]);
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    v[out] = v[arg].hypot(imm);
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    v[out] = modulo(v[arg], imm);
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    v[out] = modulo(imm, v[arg]);
+                }
                 Op::SubImmReg(out, arg, imm) => {
                     v[out] = imm - v[arg];
                 }
@@ -281,17 +515,18 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                     v[out] = v[arg] - imm;
                 }
                 Op::MinRegImm(out, arg, imm) => {
-                    let a = v[arg];
+                    let a = nan.float(v[arg]);
+                    let imm = nan.float(imm);
                     v[out] = if a < imm {
-                        choices[choice_index] |= Choice::Left;
+                        choices[choice_index] = Choice::Left;
                         a
                     } else if imm < a {
-                        choices[choice_index] |= Choice::Right;
+                        choices[choice_index] = Choice::Right;
                         imm
                     } else {
-                        choices[choice_index] |= Choice::Both;
+                        choices[choice_index] = Choice::Both;
                         if a.is_nan() || imm.is_nan() {
-                            std::f32::NAN
+                            core::f32::NAN
                         } else {
                             imm
                         }
@@ -300,17 +535,18 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                     choice_index += 1;
                 }
                 Op::MaxRegImm(out, arg, imm) => {
-                    let a = v[arg];
+                    let a = nan.float(v[arg]);
+                    let imm = nan.float(imm);
                     v[out] = if a > imm {
-                        choices[choice_index] |= Choice::Left;
+                        choices[choice_index] = Choice::Left;
                         a
                     } else if imm > a {
-                        choices[choice_index] |= Choice::Right;
+                        choices[choice_index] = Choice::Right;
                         imm
                     } else {
-                        choices[choice_index] |= Choice::Both;
+                        choices[choice_index] = Choice::Both;
                         if a.is_nan() || imm.is_nan() {
-                            std::f32::NAN
+                            core::f32::NAN
                         } else {
                             imm
                         }
@@ -318,6 +554,12 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                     simplify |= choices[choice_index] != Choice::Both;
                     choice_index += 1;
                 }
+                Op::MinNcRegImm(out, arg, imm) => {
+                    v[out] = nan_min(nan.float(v[arg]), nan.float(imm));
+                }
+                Op::MaxNcRegImm(out, arg, imm) => {
+                    v[out] = nan_max(nan.float(v[arg]), nan.float(imm));
+                }
                 Op::AddRegReg(out, lhs, rhs) => {
                     v[out] = v[lhs] + v[rhs];
                 }
@@ -330,19 +572,41 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                 Op::SubRegReg(out, lhs, rhs) => {
                     v[out] = v[lhs] - v[rhs];
                 }
+                Op::FmaRegRegReg(out, a, b, c) => {
+                    v[out] = v[a].mul_add(v[b], v[c]);
+                }
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    v[out] = v[lhs].atan2(v[rhs]);
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    v[out] = op.eval_f32(v[lhs], v[rhs]);
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    v[out] = noise::noise3(v[x], v[y], v[z], seed);
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
+                    v[out] = nan_max(nan_min(v[x], v[hi]), v[lo]);
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    v[out] = v[lhs].hypot(v[rhs]);
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    v[out] = modulo(v[lhs], v[rhs]);
+                }
                 Op::MinRegReg(out, lhs, rhs) => {
-                    let a = v[lhs];
-                    let b = v[rhs];
+                    let a = nan.float(v[lhs]);
+                    let b = nan.float(v[rhs]);
                     v[out] = if a < b {
-                        choices[choice_index] |= Choice::Left;
+                        choices[choice_index] = Choice::Left;
                         a
                     } else if b < a {
-                        choices[choice_index] |= Choice::Right;
+                        choices[choice_index] = Choice::Right;
                         b
                     } else {
-                        choices[choice_index] |= Choice::Both;
+                        choices[choice_index] = Choice::Both;
                         if a.is_nan() || b.is_nan() {
-                            std::f32::NAN
+                            core::f32::NAN
                         } else {
                             b
                         }
@@ -351,18 +615,18 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                     choice_index += 1;
                 }
                 Op::MaxRegReg(out, lhs, rhs) => {
-                    let a = v[lhs];
-                    let b = v[rhs];
+                    let a = nan.float(v[lhs]);
+                    let b = nan.float(v[rhs]);
                     v[out] = if a > b {
-                        choices[choice_index] |= Choice::Left;
+                        choices[choice_index] = Choice::Left;
                         a
                     } else if b > a {
-                        choices[choice_index] |= Choice::Right;
+                        choices[choice_index] = Choice::Right;
                         b
                     } else {
-                        choices[choice_index] |= Choice::Both;
+                        choices[choice_index] = Choice::Both;
                         if a.is_nan() || b.is_nan() {
-                            std::f32::NAN
+                            core::f32::NAN
                         } else {
                             b
                         }
@@ -370,6 +634,12 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
                     simplify |= choices[choice_index] != Choice::Both;
                     choice_index += 1;
                 }
+                Op::MinNcRegReg(out, lhs, rhs) => {
+                    v[out] = nan_min(nan.float(v[lhs]), nan.float(v[rhs]));
+                }
+                Op::MaxNcRegReg(out, lhs, rhs) => {
+                    v[out] = nan_max(nan.float(v[lhs]), nan.float(v[rhs]));
+                }
                 Op::CopyImm(out, imm) => {
                     v[out] = imm;
                 }
@@ -387,51 +657,17 @@ impl TracingEvaluator<f32, Eval> for AsmEval {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-/// Float-point interpreter-style evaluator for a tape of [`Op`]
-pub struct AsmBulkEvalData<T> {
-    /// Workspace for data
-    slots: Vec<Vec<T>>,
-    /// Current slice size in `self.slots`
-    slice_size: usize,
-}
-
-impl<T> Default for AsmBulkEvalData<T> {
-    fn default() -> Self {
-        Self {
-            slots: vec![],
-            slice_size: 0,
-        }
-    }
-}
-
-impl<T> BulkEvaluatorData<Eval> for AsmBulkEvalData<T>
-where
-    T: From<f32> + Clone,
-{
-    fn prepare(&mut self, tape: &Tape<Eval>, size: usize) {
-        assert!(tape.reg_limit() == u8::MAX);
-        self.slots.resize_with(tape.slot_count(), || {
-            vec![std::f32::NAN.into(); size.max(self.slice_size)]
-        });
-        if size > self.slice_size {
-            for s in self.slots.iter_mut() {
-                s.resize(size, std::f32::NAN.into());
-            }
-            self.slice_size = size;
-        }
-    }
-}
-
-impl BulkEvaluator<f32, Eval> for AsmEval {
-    type Data = AsmBulkEvalData<f32>;
+impl IntervalSliceEvaluator<Eval> for AsmEval {
+    type Data = AsmBulkEvalData<Interval>;
 
     fn eval_with(
         &self,
-        xs: &[f32],
-        ys: &[f32],
-        zs: &[f32],
+        xs: &[Interval],
+        ys: &[Interval],
+        zs: &[Interval],
         vars: &[f32],
-        out: &mut [f32],
+        out: &mut [Interval],
+        choices: &mut [Choice],
         data: &mut Self::Data,
     ) {
         assert_eq!(xs.len(), ys.len());
@@ -441,8 +677,15 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
         assert_eq!(data.slots.len(), self.tape.slot_count());
 
         let size = xs.len();
+        let conservative = self.tape.conservative_intervals();
+        let nan = self.tape.nan_policy();
+        let choice_count = self.tape.choice_count();
+        assert_eq!(choices.len(), size * choice_count);
         assert!(data.slice_size >= size);
 
+        // Choices are stored in one chunk per item, so each operation writes a
+        // strided column of the choice array.
+        let mut choice_index = 0;
         let mut v = SlotArray(&mut data.slots);
         for op in self.tape.iter_asm() {
             match op {
@@ -452,7 +695,9 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
                     2 => zs,
                     _ => panic!("Invalid input: {}", i),
                 }),
-                Op::Var(out, i) => v[out][0..size].fill(vars[i as usize]),
+                Op::Var(out, i) => {
+                    v[out][0..size].fill(vars[i as usize].into())
+                }
                 Op::NegReg(out, arg) => {
                     for i in 0..size {
                         v[out][i] = -v[arg][i];
@@ -465,7 +710,7 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
                 }
                 Op::RecipReg(out, arg) => {
                     for i in 0..size {
-                        v[out][i] = 1.0 / v[arg][i];
+                        v[out][i] = v[arg][i].recip();
                     }
                 }
                 Op::SqrtReg(out, arg) => {
@@ -473,10 +718,19 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
                         v[out][i] = v[arg][i].sqrt();
                     }
                 }
+                Op::SinReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].sin();
+                    }
+                }
+                Op::CosReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].cos();
+                    }
+                }
                 Op::SquareReg(out, arg) => {
                     for i in 0..size {
-                        let s = v[arg][i];
-                        v[out][i] = s * s;
+                        v[out][i] = v[arg][i].square();
                     }
                 }
                 Op::CopyReg(out, arg) => {
@@ -485,43 +739,101 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
                     }
                 }
                 Op::AddRegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
                     for i in 0..size {
                         v[out][i] = v[arg][i] + imm;
                     }
                 }
                 Op::MulRegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
                     for i in 0..size {
                         v[out][i] = v[arg][i] * imm;
                     }
                 }
                 Op::DivRegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
                     for i in 0..size {
                         v[out][i] = v[arg][i] / imm;
                     }
                 }
                 Op::DivImmReg(out, arg, imm) => {
+                    let imm: Interval = imm.into();
                     for i in 0..size {
                         v[out][i] = imm / v[arg][i];
                     }
                 }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].atan2(imm);
+                    }
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    let imm: Interval = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm.atan2(v[arg][i]);
+                    }
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].hypot(imm);
+                    }
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].modulo(imm);
+                    }
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    let imm: Interval = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm.modulo(v[arg][i]);
+                    }
+                }
                 Op::SubImmReg(out, arg, imm) => {
+                    let imm: Interval = imm.into();
                     for i in 0..size {
                         v[out][i] = imm - v[arg][i];
                     }
                 }
                 Op::SubRegImm(out, arg, imm) => {
+                    let imm: Interval = imm.into();
                     for i in 0..size {
                         v[out][i] = v[arg][i] - imm;
                     }
                 }
                 Op::MinRegImm(out, arg, imm) => {
+                    let imm = nan.interval(imm.into());
                     for i in 0..size {
-                        v[out][i] = v[arg][i].min(imm);
+                        let (value, choice) =
+                            nan.interval(v[arg][i]).min_choice(imm);
+                        v[out][i] = value;
+                        choices[i * choice_count + choice_index] = choice;
                     }
+                    choice_index += 1;
                 }
                 Op::MaxRegImm(out, arg, imm) => {
+                    let imm = nan.interval(imm.into());
+                    for i in 0..size {
+                        let (value, choice) =
+                            nan.interval(v[arg][i]).max_choice(imm);
+                        v[out][i] = value;
+                        choices[i * choice_count + choice_index] = choice;
+                    }
+                    choice_index += 1;
+                }
+                Op::MinNcRegImm(out, arg, imm) => {
+                    let imm = nan.interval(imm.into());
+                    for i in 0..size {
+                        v[out][i] = nan.interval(v[arg][i]).min_choice(imm).0;
+                    }
+                }
+                Op::MaxNcRegImm(out, arg, imm) => {
+                    let imm = nan.interval(imm.into());
                     for i in 0..size {
-                        v[out][i] = v[arg][i].max(imm);
+                        v[out][i] = nan.interval(v[arg][i]).max_choice(imm).0;
                     }
                 }
                 Op::AddRegReg(out, lhs, rhs) => {
@@ -544,21 +856,84 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
                         v[out][i] = v[lhs][i] - v[rhs][i];
                     }
                 }
+                Op::FmaRegRegReg(out, a, b, c) => {
+                    for i in 0..size {
+                        let mut p = v[a][i] * v[b][i];
+                        if conservative {
+                            // The product is rounded separately, so widen it
+                            p = p.widen();
+                        }
+                        v[out][i] = p + v[c][i];
+                    }
+                }
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].atan2(v[rhs][i]);
+                    }
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    for i in 0..size {
+                        v[out][i] = op.eval_interval(v[lhs][i], v[rhs][i]);
+                    }
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    for i in 0..size {
+                        v[out][i] = noise::noise3_interval(
+                            v[x][i], v[y][i], v[z][i], seed,
+                        );
+                    }
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
+                    for i in 0..size {
+                        v[out][i] = v[x][i].clamp(v[lo][i], v[hi][i]);
+                    }
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].hypot(v[rhs][i]);
+                    }
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].modulo(v[rhs][i]);
+                    }
+                }
                 Op::MinRegReg(out, lhs, rhs) => {
                     for i in 0..size {
-                        v[out][i] = v[lhs][i].min(v[rhs][i]);
+                        let (value, choice) = nan
+                            .interval(v[lhs][i])
+                            .min_choice(nan.interval(v[rhs][i]));
+                        v[out][i] = value;
+                        choices[i * choice_count + choice_index] = choice;
                     }
+                    choice_index += 1;
                 }
                 Op::MaxRegReg(out, lhs, rhs) => {
                     for i in 0..size {
-                        v[out][i] = v[lhs][i].max(v[rhs][i]);
+                        let (value, choice) = nan
+                            .interval(v[lhs][i])
+                            .max_choice(nan.interval(v[rhs][i]));
+                        v[out][i] = value;
+                        choices[i * choice_count + choice_index] = choice;
                     }
+                    choice_index += 1;
                 }
-                Op::CopyImm(out, imm) => {
+                Op::MinNcRegReg(out, lhs, rhs) => {
                     for i in 0..size {
-                        v[out][i] = imm;
+                        let rhs = nan.interval(v[rhs][i]);
+                        v[out][i] = nan.interval(v[lhs][i]).min_choice(rhs).0;
+                    }
+                }
+                Op::MaxNcRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        let rhs = nan.interval(v[rhs][i]);
+                        v[out][i] = nan.interval(v[lhs][i]).max_choice(rhs).0;
                     }
                 }
+                Op::CopyImm(out, imm) => {
+                    v[out][0..size].fill(imm.into());
+                }
                 Op::Load(out, mem) => {
                     for i in 0..size {
                         v[out][i] = v[mem][i];
@@ -570,15 +945,54 @@ impl BulkEvaluator<f32, Eval> for AsmEval {
                     }
                 }
             }
+            if let Some(out) = op.rounded_output().filter(|_| conservative) {
+                for i in 0..size {
+                    v[out][i] = v[out][i].widen();
+                }
+            }
         }
-        out[0..size].copy_from_slice(&data.slots[0][0..size])
+        out.copy_from_slice(&data.slots[0][0..size])
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
-impl BulkEvaluator<Grad, Eval> for AsmEval {
-    type Data = AsmBulkEvalData<Grad>;
+/// Float-point interpreter-style evaluator for a tape of [`Op`]
+pub struct AsmBulkEvalData<T> {
+    /// Workspace for data
+    slots: Vec<Vec<T>>,
+    /// Current slice size in `self.slots`
+    slice_size: usize,
+}
+
+impl<T> Default for AsmBulkEvalData<T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            slice_size: 0,
+        }
+    }
+}
+
+impl<T, F> BulkEvaluatorData<F> for AsmBulkEvalData<T>
+where
+    T: From<f32> + Clone,
+{
+    fn prepare(&mut self, tape: &Tape<F>, size: usize) {
+        self.slots.resize_with(tape.slot_count(), || {
+            vec![core::f32::NAN.into(); size.max(self.slice_size)]
+        });
+        if size > self.slice_size {
+            for s in self.slots.iter_mut() {
+                s.resize(size, core::f32::NAN.into());
+            }
+            self.slice_size = size;
+        }
+    }
+}
+
+impl BulkEvaluator<f32, Eval> for AsmEval {
+    type Data = AsmBulkEvalData<f32>;
 
     fn eval_with(
         &self,
@@ -586,7 +1000,7 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
         ys: &[f32],
         zs: &[f32],
         vars: &[f32],
-        out: &mut [Grad],
+        out: &mut [f32],
         data: &mut Self::Data,
     ) {
         assert_eq!(xs.len(), ys.len());
@@ -596,31 +1010,210 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
         assert_eq!(data.slots.len(), self.tape.slot_count());
 
         let size = xs.len();
+        let nan = self.tape.nan_policy();
         assert!(data.slice_size >= size);
 
         let mut v = SlotArray(&mut data.slots);
         for op in self.tape.iter_asm() {
             match op {
-                Op::Input(out, j) => {
-                    for i in 0..size {
-                        v[out][i] = match j {
-                            0 => Grad::new(xs[i], 1.0, 0.0, 0.0),
-                            1 => Grad::new(ys[i], 0.0, 1.0, 0.0),
-                            2 => Grad::new(zs[i], 0.0, 0.0, 1.0),
-                            _ => panic!("Invalid input: {}", i),
-                        }
-                    }
+                Op::Input(out, i) => v[out][0..size].copy_from_slice(match i {
+                    0 => xs,
+                    1 => ys,
+                    2 => zs,
+                    _ => panic!("Invalid input: {}", i),
+                }),
+                Op::Var(out, i) => v[out][0..size].fill(vars[i as usize]),
+                Op::NegReg(out, arg) => lanes!(v, size, out, |T, a = arg| -a),
+                Op::AbsReg(out, arg) => {
+                    lanes!(v, size, out, |T, a = arg| a.abs())
                 }
-                Op::Var(out, j) => {
-                    // TODO: error handling?
-                    v[out][0..size].fill(Grad::new(
-                        vars[j as usize],
-                        0.0,
-                        0.0,
-                        0.0,
-                    ));
+                Op::RecipReg(out, arg) => {
+                    lanes!(v, size, out, |T, a = arg| T::splat(1.0) / a)
                 }
-                Op::NegReg(out, arg) => {
+                Op::SqrtReg(out, arg) => {
+                    lanes!(v, size, out, |T, a = arg| a.sqrt())
+                }
+                Op::SinReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].sin();
+                    }
+                }
+                Op::CosReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].cos();
+                    }
+                }
+                Op::SquareReg(out, arg) => {
+                    lanes!(v, size, out, |T, a = arg| a * a)
+                }
+                Op::CopyReg(out, arg) => lanes!(v, size, out, |T, a = arg| a),
+                Op::AddRegImm(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| a + T::splat(imm))
+                }
+                Op::MulRegImm(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| a * T::splat(imm))
+                }
+                Op::DivRegImm(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| a / T::splat(imm))
+                }
+                Op::DivImmReg(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| T::splat(imm) / a)
+                }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].atan2(imm);
+                    }
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = imm.atan2(v[arg][i]);
+                    }
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].hypot(imm);
+                    }
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| a.modulo(T::splat(imm)))
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| T::splat(imm).modulo(a))
+                }
+                Op::SubImmReg(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| T::splat(imm) - a)
+                }
+                Op::SubRegImm(out, arg, imm) => {
+                    lanes!(v, size, out, |T, a = arg| a - T::splat(imm))
+                }
+                Op::MinRegImm(out, arg, imm)
+                | Op::MinNcRegImm(out, arg, imm) => {
+                    let imm = nan.float(imm);
+                    lanes!(v, size, out, |T, a = arg| {
+                        a.nan_policy(nan).nan_min(T::splat(imm))
+                    })
+                }
+                Op::MaxRegImm(out, arg, imm)
+                | Op::MaxNcRegImm(out, arg, imm) => {
+                    let imm = nan.float(imm);
+                    lanes!(v, size, out, |T, a = arg| {
+                        a.nan_policy(nan).nan_max(T::splat(imm))
+                    })
+                }
+                Op::AddRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| a + b)
+                }
+                Op::MulRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| a * b)
+                }
+                Op::DivRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| a / b)
+                }
+                Op::SubRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| a - b)
+                }
+                Op::FmaRegRegReg(out, a, b, c) => {
+                    lanes!(v, size, out, |T, x = a, y = b, z = c| x
+                        .mul_add(y, z))
+                }
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].atan2(v[rhs][i]);
+                    }
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    for i in 0..size {
+                        v[out][i] = op.eval_f32(v[lhs][i], v[rhs][i]);
+                    }
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    for i in 0..size {
+                        v[out][i] =
+                            noise::noise3(v[x][i], v[y][i], v[z][i], seed);
+                    }
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
+                    lanes!(v, size, out, |T, a = x, l = lo, h = hi| {
+                        a.nan_min(h).nan_max(l)
+                    })
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].hypot(v[rhs][i]);
+                    }
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| a.modulo(b))
+                }
+                Op::MinRegReg(out, lhs, rhs)
+                | Op::MinNcRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| {
+                        a.nan_policy(nan).nan_min(b.nan_policy(nan))
+                    })
+                }
+                Op::MaxRegReg(out, lhs, rhs)
+                | Op::MaxNcRegReg(out, lhs, rhs) => {
+                    lanes!(v, size, out, |T, a = lhs, b = rhs| {
+                        a.nan_policy(nan).nan_max(b.nan_policy(nan))
+                    })
+                }
+                Op::CopyImm(out, imm) => v[out][0..size].fill(imm),
+                Op::Load(out, mem) => lanes!(v, size, out, |T, a = mem| a),
+                Op::Store(out, mem) => lanes!(v, size, mem, |T, a = out| a),
+            }
+        }
+        out[0..size].copy_from_slice(&data.slots[0][0..size])
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+impl BulkEvaluator<Grad, Eval> for AsmEval {
+    type Data = AsmBulkEvalData<Grad>;
+
+    fn eval_with(
+        &self,
+        xs: &[f32],
+        ys: &[f32],
+        zs: &[f32],
+        vars: &[f32],
+        out: &mut [Grad],
+        data: &mut Self::Data,
+    ) {
+        assert_eq!(xs.len(), ys.len());
+        assert_eq!(ys.len(), zs.len());
+        assert_eq!(zs.len(), out.len());
+        assert_eq!(vars.len(), self.tape.var_count());
+        assert_eq!(data.slots.len(), self.tape.slot_count());
+
+        let size = xs.len();
+        let nan = self.tape.nan_policy();
+        assert!(data.slice_size >= size);
+
+        let mut v = SlotArray(&mut data.slots);
+        for op in self.tape.iter_asm() {
+            match op {
+                Op::Input(out, j) => {
+                    for i in 0..size {
+                        v[out][i] = match j {
+                            0 => Grad::new(xs[i], 1.0, 0.0, 0.0),
+                            1 => Grad::new(ys[i], 0.0, 1.0, 0.0),
+                            2 => Grad::new(zs[i], 0.0, 0.0, 1.0),
+                            _ => panic!("Invalid input: {}", i),
+                        }
+                    }
+                }
+                Op::Var(out, j) => {
+                    // TODO: error handling?
+                    v[out][0..size].fill(Grad::new(
+                        vars[j as usize],
+                        0.0,
+                        0.0,
+                        0.0,
+                    ));
+                }
+                Op::NegReg(out, arg) => {
                     for i in 0..size {
                         v[out][i] = -v[arg][i];
                     }
@@ -641,6 +1234,16 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
                         v[out][i] = v[arg][i].sqrt();
                     }
                 }
+                Op::SinReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].sin();
+                    }
+                }
+                Op::CosReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].cos();
+                    }
+                }
                 Op::SquareReg(out, arg) => {
                     for i in 0..size {
                         let s = v[arg][i];
@@ -673,6 +1276,33 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
                         v[out][i] = imm / v[arg][i];
                     }
                 }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].atan2(imm.into());
+                    }
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    let imm: Grad = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm.atan2(v[arg][i]);
+                    }
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].hypot(imm.into());
+                    }
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].modulo(imm.into());
+                    }
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    let imm: Grad = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm.modulo(v[arg][i]);
+                    }
+                }
                 Op::SubImmReg(out, arg, imm) => {
                     let imm: Grad = imm.into();
                     for i in 0..size {
@@ -685,16 +1315,18 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
                         v[out][i] = v[arg][i] - imm;
                     }
                 }
-                Op::MinRegImm(out, arg, imm) => {
-                    let imm: Grad = imm.into();
+                Op::MinRegImm(out, arg, imm)
+                | Op::MinNcRegImm(out, arg, imm) => {
+                    let imm = nan.grad(imm.into());
                     for i in 0..size {
-                        v[out][i] = v[arg][i].min(imm);
+                        v[out][i] = nan.grad(v[arg][i]).min(imm);
                     }
                 }
-                Op::MaxRegImm(out, arg, imm) => {
-                    let imm: Grad = imm.into();
+                Op::MaxRegImm(out, arg, imm)
+                | Op::MaxNcRegImm(out, arg, imm) => {
+                    let imm = nan.grad(imm.into());
                     for i in 0..size {
-                        v[out][i] = v[arg][i].max(imm);
+                        v[out][i] = nan.grad(v[arg][i]).max(imm);
                     }
                 }
                 Op::AddRegReg(out, lhs, rhs) => {
@@ -717,14 +1349,55 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
                         v[out][i] = v[lhs][i] - v[rhs][i];
                     }
                 }
-                Op::MinRegReg(out, lhs, rhs) => {
+                Op::FmaRegRegReg(out, a, b, c) => {
                     for i in 0..size {
-                        v[out][i] = v[lhs][i].min(v[rhs][i]);
+                        v[out][i] = v[a][i] * v[b][i] + v[c][i];
                     }
                 }
-                Op::MaxRegReg(out, lhs, rhs) => {
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].atan2(v[rhs][i]);
+                    }
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    for i in 0..size {
+                        v[out][i] = op.eval_grad(v[lhs][i], v[rhs][i]);
+                    }
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    for i in 0..size {
+                        v[out][i] =
+                            noise::noise3_grad(v[x][i], v[y][i], v[z][i], seed);
+                    }
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
                     for i in 0..size {
-                        v[out][i] = v[lhs][i].max(v[rhs][i]);
+                        v[out][i] = v[x][i].clamp(v[lo][i], v[hi][i]);
+                    }
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].hypot(v[rhs][i]);
+                    }
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].modulo(v[rhs][i]);
+                    }
+                }
+                Op::MinRegReg(out, lhs, rhs)
+                | Op::MinNcRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] =
+                            nan.grad(v[lhs][i]).min(nan.grad(v[rhs][i]));
+                    }
+                }
+                Op::MaxRegReg(out, lhs, rhs)
+                | Op::MaxNcRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] =
+                            nan.grad(v[lhs][i]).max(nan.grad(v[rhs][i]));
                     }
                 }
                 Op::CopyImm(out, imm) => {
@@ -749,11 +1422,357 @@ impl BulkEvaluator<Grad, Eval> for AsmEval {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+
+impl BulkEvaluator<Hessian, Eval> for AsmEval {
+    type Data = AsmBulkEvalData<Hessian>;
+
+    fn eval_with(
+        &self,
+        xs: &[f32],
+        ys: &[f32],
+        zs: &[f32],
+        vars: &[f32],
+        out: &mut [Hessian],
+        data: &mut Self::Data,
+    ) {
+        assert_eq!(xs.len(), ys.len());
+        assert_eq!(ys.len(), zs.len());
+        assert_eq!(zs.len(), out.len());
+        assert_eq!(vars.len(), self.tape.var_count());
+        assert_eq!(data.slots.len(), self.tape.slot_count());
+
+        let size = xs.len();
+        let nan = self.tape.nan_policy();
+        assert!(data.slice_size >= size);
+
+        let mut v = SlotArray(&mut data.slots);
+        for op in self.tape.iter_asm() {
+            match op {
+                Op::Input(out, j) => {
+                    for i in 0..size {
+                        v[out][i] = match j {
+                            0 => Hessian::input(xs[i], 0),
+                            1 => Hessian::input(ys[i], 1),
+                            2 => Hessian::input(zs[i], 2),
+                            _ => panic!("Invalid input: {}", i),
+                        }
+                    }
+                }
+                Op::Var(out, j) => {
+                    v[out][0..size].fill(vars[j as usize].into());
+                }
+                Op::NegReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = -v[arg][i];
+                    }
+                }
+                Op::AbsReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].abs();
+                    }
+                }
+                Op::RecipReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].recip();
+                    }
+                }
+                Op::SqrtReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].sqrt();
+                    }
+                }
+                Op::SinReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].sin();
+                    }
+                }
+                Op::CosReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].cos();
+                    }
+                }
+                Op::SquareReg(out, arg) => {
+                    for i in 0..size {
+                        let s = v[arg][i];
+                        v[out][i] = s * s;
+                    }
+                }
+                Op::CopyReg(out, arg) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i];
+                    }
+                }
+                Op::AddRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i] + imm.into();
+                    }
+                }
+                Op::MulRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i] * imm.into();
+                    }
+                }
+                Op::DivRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i] / imm.into();
+                    }
+                }
+                Op::DivImmReg(out, arg, imm) => {
+                    let imm: Hessian = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm / v[arg][i];
+                    }
+                }
+                Op::Atan2RegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].atan2(imm.into());
+                    }
+                }
+                Op::Atan2ImmReg(out, arg, imm) => {
+                    let imm: Hessian = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm.atan2(v[arg][i]);
+                    }
+                }
+                Op::HypotRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].hypot(imm.into());
+                    }
+                }
+                Op::ModRegImm(out, arg, imm) => {
+                    for i in 0..size {
+                        v[out][i] = v[arg][i].modulo(imm.into());
+                    }
+                }
+                Op::ModImmReg(out, arg, imm) => {
+                    let imm: Hessian = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm.modulo(v[arg][i]);
+                    }
+                }
+                Op::SubImmReg(out, arg, imm) => {
+                    let imm: Hessian = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm - v[arg][i];
+                    }
+                }
+                Op::SubRegImm(out, arg, imm) => {
+                    let imm: Hessian = imm.into();
+                    for i in 0..size {
+                        v[out][i] = v[arg][i] - imm;
+                    }
+                }
+                Op::MinRegImm(out, arg, imm)
+                | Op::MinNcRegImm(out, arg, imm) => {
+                    let imm = nan.hessian(imm.into());
+                    for i in 0..size {
+                        v[out][i] = nan.hessian(v[arg][i]).min(imm);
+                    }
+                }
+                Op::MaxRegImm(out, arg, imm)
+                | Op::MaxNcRegImm(out, arg, imm) => {
+                    let imm = nan.hessian(imm.into());
+                    for i in 0..size {
+                        v[out][i] = nan.hessian(v[arg][i]).max(imm);
+                    }
+                }
+                Op::AddRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i] + v[rhs][i];
+                    }
+                }
+                Op::MulRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i] * v[rhs][i];
+                    }
+                }
+                Op::DivRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i] / v[rhs][i];
+                    }
+                }
+                Op::SubRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i] - v[rhs][i];
+                    }
+                }
+                Op::FmaRegRegReg(out, a, b, c) => {
+                    for i in 0..size {
+                        v[out][i] = v[a][i] * v[b][i] + v[c][i];
+                    }
+                }
+                Op::Atan2RegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].atan2(v[rhs][i]);
+                    }
+                }
+                Op::CustomRegReg(out, lhs, rhs, c) => {
+                    let op = &self.tape.custom_ops()[c as usize];
+                    for i in 0..size {
+                        v[out][i] = op.eval_hessian(v[lhs][i], v[rhs][i]);
+                    }
+                }
+                Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                    for i in 0..size {
+                        v[out][i] = noise::noise3_hessian(
+                            v[x][i], v[y][i], v[z][i], seed,
+                        );
+                    }
+                }
+                Op::ClampRegRegReg(out, x, lo, hi) => {
+                    for i in 0..size {
+                        v[out][i] = v[x][i].clamp(v[lo][i], v[hi][i]);
+                    }
+                }
+                Op::HypotRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].hypot(v[rhs][i]);
+                    }
+                }
+                Op::ModRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] = v[lhs][i].modulo(v[rhs][i]);
+                    }
+                }
+                Op::MinRegReg(out, lhs, rhs)
+                | Op::MinNcRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] =
+                            nan.hessian(v[lhs][i]).min(nan.hessian(v[rhs][i]));
+                    }
+                }
+                Op::MaxRegReg(out, lhs, rhs)
+                | Op::MaxNcRegReg(out, lhs, rhs) => {
+                    for i in 0..size {
+                        v[out][i] =
+                            nan.hessian(v[lhs][i]).max(nan.hessian(v[rhs][i]));
+                    }
+                }
+                Op::CopyImm(out, imm) => {
+                    let imm: Hessian = imm.into();
+                    for i in 0..size {
+                        v[out][i] = imm;
+                    }
+                }
+                Op::Load(out, mem) => {
+                    for i in 0..size {
+                        v[out][i] = v[mem][i];
+                    }
+                }
+                Op::Store(out, mem) => {
+                    for i in 0..size {
+                        v[mem][i] = v[out][i];
+                    }
+                }
+            }
+        }
+        out.copy_from_slice(&data.slots[0][0..size])
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Evaluator which runs a tape from any family in the interpreter
+///
+/// This is used by families which don't have a native implementation of a
+/// particular evaluator (e.g. the JIT's
+/// [`HessianSliceEval`](Family::HessianSliceEval), or every non-interval
+/// evaluator in [`Affine`](crate::vm::Affine)).
+#[derive(Clone)]
+pub struct Interpreted(AsmEval);
+
+impl<F: Family> EvaluatorStorage<F> for Interpreted {
+    type Storage = ();
+    fn new_with_storage(tape: &Tape<F>, _storage: ()) -> Self {
+        Self(AsmEval::new_with_storage(&tape.cast(), ()))
+    }
+    fn take(self) -> Option<Self::Storage> {
+        Some(())
+    }
+}
+
+impl<T, F> TracingEvaluator<T, F> for Interpreted
+where
+    T: From<f32> + Clone + Send,
+    AsmEval: TracingEvaluator<T, Eval, Data = AsmTracingEvalData<T>>,
+{
+    type Data = AsmTracingEvalData<T>;
+
+    fn eval_with(
+        &self,
+        x: T,
+        y: T,
+        z: T,
+        vars: &[f32],
+        choices: &mut [Choice],
+        data: &mut Self::Data,
+    ) -> (T, bool) {
+        TracingEvaluator::<T, Eval>::eval_with(
+            &self.0, x, y, z, vars, choices, data,
+        )
+    }
+}
+
+impl<T, F> BulkEvaluator<T, F> for Interpreted
+where
+    T: From<f32> + Clone + Send,
+    AsmEval: BulkEvaluator<T, Eval, Data = AsmBulkEvalData<T>>,
+{
+    type Data = AsmBulkEvalData<T>;
+
+    fn eval_with(
+        &self,
+        xs: &[f32],
+        ys: &[f32],
+        zs: &[f32],
+        vars: &[f32],
+        out: &mut [T],
+        data: &mut Self::Data,
+    ) {
+        BulkEvaluator::<T, Eval>::eval_with(
+            &self.0, xs, ys, zs, vars, out, data,
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     crate::grad_slice_tests!(Eval);
+    crate::hessian_slice_tests!(Eval);
     crate::interval_tests!(Eval);
+    crate::interval_slice_tests!(Eval);
     crate::float_slice_tests!(Eval);
     crate::point_tests!(Eval);
+
+    #[test]
+    fn test_float_slice_tail() {
+        use crate::context::Context;
+
+        // Slices which aren't a multiple of the lane width must give the same
+        // results as point-by-point evaluation, including NaN propagation
+        let mut ctx = Context::new();
+        let (x, y) = (ctx.x(), ctx.y());
+        let s = ctx.sqrt(x).unwrap();
+        let a = ctx.min(s, y).unwrap();
+        let b = ctx.div(y, x).unwrap();
+        let b = ctx.max(b, 0.5).unwrap();
+        let root = ctx.sub(a, b).unwrap();
+        let tape = ctx.get_tape::<Eval>(root).unwrap();
+
+        let eval = tape.new_float_slice_evaluator();
+        let point = tape.new_point_evaluator();
+        for n in 0..LANES * 3 + 2 {
+            let xs: Vec<f32> = (0..n).map(|i| i as f32 - 2.5).collect();
+            let ys: Vec<f32> = (0..n).map(|i| (i as f32 * 0.7).cos()).collect();
+            let zs = vec![0.0; n];
+            let out = eval.eval(&xs, &ys, &zs, &[]).unwrap();
+            assert_eq!(out.len(), n);
+            for i in 0..n {
+                let (v, _) = point.eval(xs[i], ys[i], 0.0, &[]).unwrap();
+                assert_eq!(out[i].to_bits(), v.to_bits(), "{i} of {n}");
+            }
+        }
+    }
 }
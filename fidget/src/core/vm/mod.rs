@@ -1,12 +1,20 @@
 //! Instruction tapes in the form of assembly for a simple virtual machine
+mod affine;
 mod alloc;
+pub mod double;
 mod eval;
 mod lru;
 mod op;
+mod schedule;
 mod tape;
+mod threaded;
 
 pub(super) use alloc::RegisterAllocator;
 
-pub use eval::Eval;
+pub use affine::Affine;
+#[cfg(feature = "jit")]
+pub(crate) use eval::AsmEval;
+pub use eval::{Eval, Interpreted};
 pub use op::Op;
 pub use tape::Tape;
+pub use threaded::Threaded;
@@ -7,7 +7,7 @@
 /// - Output register
 /// - LHS register (or input slot for [`Input`](Op::Input))
 /// - RHS register (or immediate for `*Imm`)
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Op {
     /// Read one of the inputs (X, Y, Z)
     Input(u8, u8),
@@ -30,6 +30,12 @@ pub enum Op {
     /// Square the given register
     SquareReg(u8, u8),
 
+    /// Take the sine of the given register
+    SinReg(u8, u8),
+
+    /// Take the cosine of the given register
+    CosReg(u8, u8),
+
     /// Copies the given register
     CopyReg(u8, u8),
 
@@ -49,6 +55,22 @@ pub enum Op {
     MinRegImm(u8, u8, f32),
     /// Compute the maximum of a register and an immediate
     MaxRegImm(u8, u8, f32),
+    /// Compute the minimum of a register and an immediate, without writing a
+    /// choice
+    MinNcRegImm(u8, u8, f32),
+    /// Compute the maximum of a register and an immediate, without writing a
+    /// choice
+    MaxNcRegImm(u8, u8, f32),
+    /// Computes `atan2(y, x)` with a register `y` and an immediate `x`
+    Atan2RegImm(u8, u8, f32),
+    /// Computes `atan2(y, x)` with an immediate `y` and a register `x`
+    Atan2ImmReg(u8, u8, f32),
+    /// Computes the hypotenuse of a register and an immediate
+    HypotRegImm(u8, u8, f32),
+    /// Computes the floored modulo of a register by an immediate
+    ModRegImm(u8, u8, f32),
+    /// Computes the floored modulo of an immediate by a register
+    ModImmReg(u8, u8, f32),
 
     /// Add two registers
     AddRegReg(u8, u8, u8),
@@ -62,6 +84,40 @@ pub enum Op {
     MinRegReg(u8, u8, u8),
     /// Take the maximum of two registers
     MaxRegReg(u8, u8, u8),
+    /// Take the minimum of two registers, without writing a choice
+    MinNcRegReg(u8, u8, u8),
+    /// Take the maximum of two registers, without writing a choice
+    MaxNcRegReg(u8, u8, u8),
+    /// Computes `atan2(y, x)` with registers `y` and `x` (in that order)
+    Atan2RegReg(u8, u8, u8),
+    /// Computes the hypotenuse of two registers, `sqrt(lhs² + rhs²)`
+    HypotRegReg(u8, u8, u8),
+    /// Computes the floored modulo of two registers, `lhs mod rhs`
+    ModRegReg(u8, u8, u8),
+
+    /// Fused multiply-add, computing `a * b + c` for registers `a`, `b`, `c`
+    /// (in order after the output register)
+    ///
+    /// Point and float slice evaluators round the result once; interval and
+    /// gradient evaluators compute the multiplication and addition
+    /// separately.
+    FmaRegRegReg(u8, u8, u8, u8),
+
+    /// Applies a user-defined operation to two registers
+    ///
+    /// The last argument is an index into the tape's list of
+    /// [`CustomOp`](crate::eval::CustomOp) objects.
+    CustomRegReg(u8, u8, u8, u16),
+
+    /// Evaluates gradient noise of registers `x`, `y`, `z` (in order after the
+    /// output register), with the given seed
+    NoiseRegRegReg(u8, u8, u8, u8, u16),
+
+    /// Clamps register `x` to the range given by registers `lo` and `hi` (in
+    /// order after the output register), i.e. `max(min(x, hi), lo)`
+    ///
+    /// Unlike `min` and `max`, this doesn't make a choice.
+    ClampRegRegReg(u8, u8, u8, u8),
 
     /// Copy an immediate to a register
     CopyImm(u8, f32),
@@ -72,11 +128,268 @@ pub enum Op {
     Store(u8, u32),
 }
 
+impl Op {
+    /// Returns the output register if this operation may round its result
+    ///
+    /// Operations which only move, compare, or flip the sign of values (e.g.
+    /// `min`, `abs`, or `neg`) are exact and return `None`.  This is used to
+    /// decide where conservative interval evaluators need to widen results
+    /// (see [`Tape::with_conservative_intervals`](crate::eval::Tape)).
+    pub fn rounded_output(&self) -> Option<u8> {
+        match *self {
+            Op::RecipReg(out, ..)
+            | Op::SqrtReg(out, ..)
+            | Op::SinReg(out, ..)
+            | Op::CosReg(out, ..)
+            | Op::SquareReg(out, ..)
+            | Op::AddRegImm(out, ..)
+            | Op::MulRegImm(out, ..)
+            | Op::DivRegImm(out, ..)
+            | Op::DivImmReg(out, ..)
+            | Op::SubImmReg(out, ..)
+            | Op::SubRegImm(out, ..)
+            | Op::AddRegReg(out, ..)
+            | Op::MulRegReg(out, ..)
+            | Op::DivRegReg(out, ..)
+            | Op::SubRegReg(out, ..)
+            | Op::FmaRegRegReg(out, ..)
+            | Op::Atan2RegImm(out, ..)
+            | Op::Atan2ImmReg(out, ..)
+            | Op::HypotRegImm(out, ..)
+            | Op::ModRegImm(out, ..)
+            | Op::ModImmReg(out, ..)
+            | Op::Atan2RegReg(out, ..)
+            | Op::HypotRegReg(out, ..)
+            | Op::ModRegReg(out, ..)
+            | Op::CustomRegReg(out, ..)
+            | Op::NoiseRegRegReg(out, ..)
+            | Op::ClampRegRegReg(out, ..) => Some(out),
+            Op::Input(..)
+            | Op::Var(..)
+            | Op::NegReg(..)
+            | Op::AbsReg(..)
+            | Op::CopyReg(..)
+            | Op::MinRegImm(..)
+            | Op::MaxRegImm(..)
+            | Op::MinNcRegImm(..)
+            | Op::MaxNcRegImm(..)
+            | Op::MinRegReg(..)
+            | Op::MaxRegReg(..)
+            | Op::MinNcRegReg(..)
+            | Op::MaxNcRegReg(..)
+            | Op::CopyImm(..)
+            | Op::Load(..)
+            | Op::Store(..) => None,
+        }
+    }
+
+    /// Returns the operation's immediate, if present
+    #[cfg(feature = "jit")]
+    pub(crate) fn imm(&self) -> Option<f32> {
+        let mut op = *self;
+        op.imm_mut().copied()
+    }
+
+    /// Returns a mutable reference to the operation's immediate, if present
+    pub(crate) fn imm_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            Op::CopyImm(_, imm)
+            | Op::AddRegImm(_, _, imm)
+            | Op::MulRegImm(_, _, imm)
+            | Op::DivRegImm(_, _, imm)
+            | Op::DivImmReg(_, _, imm)
+            | Op::SubImmReg(_, _, imm)
+            | Op::SubRegImm(_, _, imm)
+            | Op::MinRegImm(_, _, imm)
+            | Op::MaxRegImm(_, _, imm)
+            | Op::MinNcRegImm(_, _, imm)
+            | Op::MaxNcRegImm(_, _, imm)
+            | Op::Atan2RegImm(_, _, imm)
+            | Op::Atan2ImmReg(_, _, imm)
+            | Op::ModRegImm(_, _, imm)
+            | Op::ModImmReg(_, _, imm)
+            | Op::HypotRegImm(_, _, imm) => Some(imm),
+            Op::Input(..)
+            | Op::Var(..)
+            | Op::NegReg(..)
+            | Op::AbsReg(..)
+            | Op::RecipReg(..)
+            | Op::SqrtReg(..)
+            | Op::SquareReg(..)
+            | Op::SinReg(..)
+            | Op::CosReg(..)
+            | Op::CopyReg(..)
+            | Op::AddRegReg(..)
+            | Op::MulRegReg(..)
+            | Op::DivRegReg(..)
+            | Op::SubRegReg(..)
+            | Op::MinRegReg(..)
+            | Op::MaxRegReg(..)
+            | Op::MinNcRegReg(..)
+            | Op::MaxNcRegReg(..)
+            | Op::Atan2RegReg(..)
+            | Op::HypotRegReg(..)
+            | Op::ModRegReg(..)
+            | Op::FmaRegRegReg(..)
+            | Op::CustomRegReg(..)
+            | Op::NoiseRegRegReg(..)
+            | Op::ClampRegRegReg(..)
+            | Op::Load(..)
+            | Op::Store(..) => None,
+        }
+    }
+}
+
+impl core::fmt::Display for Op {
+    /// Formats the operation as `rOUT = OPCODE args...`
+    ///
+    /// Registers are written as `r0`, `r1`, etc, and memory slots (used by
+    /// [`Load`](Op::Load) and [`Store`](Op::Store)) as `m256`, `m257`, etc.
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            Op::Input(out, i) => write!(f, "r{out} = INPUT {i}"),
+            Op::Var(out, i) => write!(f, "r{out} = VAR {i}"),
+            Op::CopyImm(out, imm) => write!(f, "r{out} = COPY {imm}"),
+            Op::Load(reg, mem) => write!(f, "r{reg} = LOAD m{mem}"),
+            Op::Store(reg, mem) => write!(f, "m{mem} = STORE r{reg}"),
+            Op::NegReg(out, arg)
+            | Op::AbsReg(out, arg)
+            | Op::RecipReg(out, arg)
+            | Op::SqrtReg(out, arg)
+            | Op::SinReg(out, arg)
+            | Op::CosReg(out, arg)
+            | Op::CopyReg(out, arg)
+            | Op::SquareReg(out, arg) => {
+                let op = match self {
+                    Op::NegReg(..) => "NEG",
+                    Op::AbsReg(..) => "ABS",
+                    Op::RecipReg(..) => "RECIP",
+                    Op::SqrtReg(..) => "SQRT",
+                    Op::SquareReg(..) => "SQUARE",
+                    Op::SinReg(..) => "SIN",
+                    Op::CosReg(..) => "COS",
+                    Op::CopyReg(..) => "COPY",
+                    _ => unreachable!(),
+                };
+                write!(f, "r{out} = {op} r{arg}")
+            }
+            Op::AddRegReg(out, lhs, rhs)
+            | Op::MulRegReg(out, lhs, rhs)
+            | Op::DivRegReg(out, lhs, rhs)
+            | Op::SubRegReg(out, lhs, rhs)
+            | Op::MinRegReg(out, lhs, rhs)
+            | Op::MaxRegReg(out, lhs, rhs)
+            | Op::MinNcRegReg(out, lhs, rhs)
+            | Op::MaxNcRegReg(out, lhs, rhs)
+            | Op::Atan2RegReg(out, lhs, rhs)
+            | Op::ModRegReg(out, lhs, rhs)
+            | Op::HypotRegReg(out, lhs, rhs) => {
+                let op = match self {
+                    Op::AddRegReg(..) => "ADD",
+                    Op::MulRegReg(..) => "MUL",
+                    Op::DivRegReg(..) => "DIV",
+                    Op::SubRegReg(..) => "SUB",
+                    Op::MinRegReg(..) => "MIN",
+                    Op::MaxRegReg(..) => "MAX",
+                    Op::MinNcRegReg(..) => "MIN_NC",
+                    Op::MaxNcRegReg(..) => "MAX_NC",
+                    Op::Atan2RegReg(..) => "ATAN2",
+                    Op::HypotRegReg(..) => "HYPOT",
+                    Op::ModRegReg(..) => "MOD",
+                    _ => unreachable!(),
+                };
+                write!(f, "r{out} = {op} r{lhs} r{rhs}")
+            }
+            Op::FmaRegRegReg(out, a, b, c) => {
+                write!(f, "r{out} = FMA r{a} r{b} r{c}")
+            }
+            Op::CustomRegReg(out, lhs, rhs, i) => {
+                write!(f, "r{out} = CUSTOM[{i}] r{lhs} r{rhs}")
+            }
+            Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                write!(f, "r{out} = NOISE[{seed}] r{x} r{y} r{z}")
+            }
+            Op::ClampRegRegReg(out, x, lo, hi) => {
+                write!(f, "r{out} = CLAMP r{x} r{lo} r{hi}")
+            }
+            Op::AddRegImm(out, arg, imm)
+            | Op::MulRegImm(out, arg, imm)
+            | Op::DivRegImm(out, arg, imm)
+            | Op::DivImmReg(out, arg, imm)
+            | Op::SubImmReg(out, arg, imm)
+            | Op::SubRegImm(out, arg, imm)
+            | Op::MinRegImm(out, arg, imm)
+            | Op::MaxRegImm(out, arg, imm)
+            | Op::MinNcRegImm(out, arg, imm)
+            | Op::MaxNcRegImm(out, arg, imm)
+            | Op::Atan2RegImm(out, arg, imm)
+            | Op::Atan2ImmReg(out, arg, imm)
+            | Op::ModRegImm(out, arg, imm)
+            | Op::ModImmReg(out, arg, imm)
+            | Op::HypotRegImm(out, arg, imm) => {
+                let (op, swap) = match self {
+                    Op::AddRegImm(..) => ("ADD", false),
+                    Op::MulRegImm(..) => ("MUL", false),
+                    Op::DivImmReg(..) => ("DIV", true),
+                    Op::DivRegImm(..) => ("DIV", false),
+                    Op::SubImmReg(..) => ("SUB", true),
+                    Op::SubRegImm(..) => ("SUB", false),
+                    Op::MinRegImm(..) => ("MIN", false),
+                    Op::MaxRegImm(..) => ("MAX", false),
+                    Op::MinNcRegImm(..) => ("MIN_NC", false),
+                    Op::MaxNcRegImm(..) => ("MAX_NC", false),
+                    Op::Atan2RegImm(..) => ("ATAN2", false),
+                    Op::Atan2ImmReg(..) => ("ATAN2", true),
+                    Op::HypotRegImm(..) => ("HYPOT", false),
+                    Op::ModRegImm(..) => ("MOD", false),
+                    Op::ModImmReg(..) => ("MOD", true),
+                    _ => unreachable!(),
+                };
+                if swap {
+                    write!(f, "r{out} = {op} {imm} r{arg}")
+                } else {
+                    write!(f, "r{out} = {op} r{arg} {imm}")
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     #[test]
     fn test_vm_op_size() {
-        assert_eq!(std::mem::size_of::<Op>(), 8);
+        assert_eq!(core::mem::size_of::<Op>(), 8);
+    }
+
+    #[test]
+    fn test_vm_op_display() {
+        assert_eq!(Op::AddRegImm(0, 1, 2.5).to_string(), "r0 = ADD r1 2.5");
+        assert_eq!(Op::DivImmReg(0, 1, 2.5).to_string(), "r0 = DIV 2.5 r1");
+        assert_eq!(Op::MaxRegReg(2, 0, 1).to_string(), "r2 = MAX r0 r1");
+        assert_eq!(
+            Op::MinNcRegImm(2, 0, 1.5).to_string(),
+            "r2 = MIN_NC r0 1.5"
+        );
+        assert_eq!(Op::Atan2ImmReg(0, 1, 2.5).to_string(), "r0 = ATAN2 2.5 r1");
+        assert_eq!(Op::Load(3, 256).to_string(), "r3 = LOAD m256");
+        assert_eq!(Op::Store(3, 256).to_string(), "m256 = STORE r3");
+        assert_eq!(
+            Op::FmaRegRegReg(0, 1, 2, 3).to_string(),
+            "r0 = FMA r1 r2 r3"
+        );
+        assert_eq!(
+            Op::CustomRegReg(0, 1, 2, 3).to_string(),
+            "r0 = CUSTOM[3] r1 r2"
+        );
+        assert_eq!(
+            Op::NoiseRegRegReg(0, 1, 2, 3, 4).to_string(),
+            "r0 = NOISE[4] r1 r2 r3"
+        );
+        assert_eq!(
+            Op::ClampRegRegReg(0, 1, 2, 3).to_string(),
+            "r0 = CLAMP r1 r2 r3"
+        );
     }
 }
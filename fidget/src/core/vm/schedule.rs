@@ -0,0 +1,383 @@
+//! Instruction scheduling for register-allocated tapes
+//!
+//! After register allocation, tapes are in depth-first order, so each
+//! operation usually depends on the one immediately before it.  On a pipelined
+//! CPU, this means that most operations wait for their inputs to be computed,
+//! even when independent work is available.
+//!
+//! The scheduler reorders operations (without changing their registers) to put
+//! distance between each operation and its dependents.  It is a simple list
+//! scheduler: at each step, it picks the ready operation with the longest
+//! critical path to the end of the tape.
+use crate::vm::Op;
+use alloc::{collections::BinaryHeap, vec, vec::Vec};
+use core::cmp::Reverse;
+
+/// Approximate latency of an operation, in cycles
+fn latency(op: &Op) -> usize {
+    match op {
+        Op::Input(..)
+        | Op::Var(..)
+        | Op::CopyImm(..)
+        | Op::CopyReg(..)
+        | Op::NegReg(..)
+        | Op::AbsReg(..)
+        | Op::Store(..) => 1,
+        Op::Load(..)
+        | Op::SquareReg(..)
+        | Op::AddRegImm(..)
+        | Op::MulRegImm(..)
+        | Op::SubImmReg(..)
+        | Op::SubRegImm(..)
+        | Op::MinRegImm(..)
+        | Op::MaxRegImm(..)
+        | Op::MinNcRegImm(..)
+        | Op::MaxNcRegImm(..)
+        | Op::AddRegReg(..)
+        | Op::MulRegReg(..)
+        | Op::SubRegReg(..)
+        | Op::MinRegReg(..)
+        | Op::MaxRegReg(..)
+        | Op::MinNcRegReg(..)
+        | Op::MaxNcRegReg(..)
+        | Op::FmaRegRegReg(..) => 4,
+        // A min followed by a max
+        Op::ClampRegRegReg(..) => 8,
+        Op::RecipReg(..)
+        | Op::DivRegImm(..)
+        | Op::DivImmReg(..)
+        | Op::DivRegReg(..) => 12,
+        Op::SqrtReg(..) => 14,
+        // These are function calls in the JIT
+        Op::Atan2RegImm(..)
+        | Op::Atan2ImmReg(..)
+        | Op::HypotRegImm(..)
+        | Op::ModRegImm(..)
+        | Op::ModImmReg(..)
+        | Op::Atan2RegReg(..)
+        | Op::HypotRegReg(..)
+        | Op::ModRegReg(..)
+        | Op::SinReg(..)
+        | Op::CosReg(..)
+        | Op::CustomRegReg(..)
+        | Op::NoiseRegRegReg(..) => 20,
+    }
+}
+
+/// Returns the slots read by an operation
+///
+/// Registers and memory slots share the same index space.
+pub(super) fn inputs(op: &Op) -> [Option<u32>; 3] {
+    match *op {
+        Op::Input(..) | Op::Var(..) | Op::CopyImm(..) => [None, None, None],
+        Op::NegReg(_, arg)
+        | Op::AbsReg(_, arg)
+        | Op::RecipReg(_, arg)
+        | Op::SqrtReg(_, arg)
+        | Op::SinReg(_, arg)
+        | Op::CosReg(_, arg)
+        | Op::SquareReg(_, arg)
+        | Op::CopyReg(_, arg)
+        | Op::AddRegImm(_, arg, _)
+        | Op::MulRegImm(_, arg, _)
+        | Op::DivRegImm(_, arg, _)
+        | Op::DivImmReg(_, arg, _)
+        | Op::SubImmReg(_, arg, _)
+        | Op::SubRegImm(_, arg, _)
+        | Op::MinRegImm(_, arg, _)
+        | Op::MaxRegImm(_, arg, _)
+        | Op::MinNcRegImm(_, arg, _)
+        | Op::MaxNcRegImm(_, arg, _)
+        | Op::Atan2RegImm(_, arg, _)
+        | Op::Atan2ImmReg(_, arg, _)
+        | Op::ModRegImm(_, arg, _)
+        | Op::ModImmReg(_, arg, _)
+        | Op::HypotRegImm(_, arg, _) => [Some(arg as u32), None, None],
+        Op::AddRegReg(_, lhs, rhs)
+        | Op::MulRegReg(_, lhs, rhs)
+        | Op::DivRegReg(_, lhs, rhs)
+        | Op::SubRegReg(_, lhs, rhs)
+        | Op::MinRegReg(_, lhs, rhs)
+        | Op::MaxRegReg(_, lhs, rhs)
+        | Op::MinNcRegReg(_, lhs, rhs)
+        | Op::MaxNcRegReg(_, lhs, rhs)
+        | Op::Atan2RegReg(_, lhs, rhs)
+        | Op::HypotRegReg(_, lhs, rhs)
+        | Op::ModRegReg(_, lhs, rhs)
+        | Op::CustomRegReg(_, lhs, rhs, ..) => {
+            [Some(lhs as u32), Some(rhs as u32), None]
+        }
+        Op::FmaRegRegReg(_, a, b, c)
+        | Op::ClampRegRegReg(_, a, b, c)
+        | Op::NoiseRegRegReg(_, a, b, c, _) => {
+            [Some(a as u32), Some(b as u32), Some(c as u32)]
+        }
+        Op::Load(_, mem) => [Some(mem), None, None],
+        Op::Store(reg, _) => [Some(reg as u32), None, None],
+    }
+}
+
+/// Returns the slot written by an operation
+pub(super) fn output(op: &Op) -> u32 {
+    match *op {
+        Op::Store(_, mem) => mem,
+        Op::Input(out, ..)
+        | Op::Var(out, ..)
+        | Op::CopyImm(out, ..)
+        | Op::NegReg(out, ..)
+        | Op::AbsReg(out, ..)
+        | Op::RecipReg(out, ..)
+        | Op::SqrtReg(out, ..)
+        | Op::SinReg(out, ..)
+        | Op::CosReg(out, ..)
+        | Op::SquareReg(out, ..)
+        | Op::CopyReg(out, ..)
+        | Op::AddRegImm(out, ..)
+        | Op::MulRegImm(out, ..)
+        | Op::DivRegImm(out, ..)
+        | Op::DivImmReg(out, ..)
+        | Op::SubImmReg(out, ..)
+        | Op::SubRegImm(out, ..)
+        | Op::MinRegImm(out, ..)
+        | Op::MaxRegImm(out, ..)
+        | Op::MinNcRegImm(out, ..)
+        | Op::MaxNcRegImm(out, ..)
+        | Op::AddRegReg(out, ..)
+        | Op::MulRegReg(out, ..)
+        | Op::DivRegReg(out, ..)
+        | Op::SubRegReg(out, ..)
+        | Op::MinRegReg(out, ..)
+        | Op::MaxRegReg(out, ..)
+        | Op::MinNcRegReg(out, ..)
+        | Op::MaxNcRegReg(out, ..)
+        | Op::FmaRegRegReg(out, ..)
+        | Op::Atan2RegImm(out, ..)
+        | Op::Atan2ImmReg(out, ..)
+        | Op::HypotRegImm(out, ..)
+        | Op::ModRegImm(out, ..)
+        | Op::ModImmReg(out, ..)
+        | Op::Atan2RegReg(out, ..)
+        | Op::HypotRegReg(out, ..)
+        | Op::ModRegReg(out, ..)
+        | Op::CustomRegReg(out, ..)
+        | Op::NoiseRegRegReg(out, ..)
+        | Op::ClampRegRegReg(out, ..)
+        | Op::Load(out, ..) => out as u32,
+    }
+}
+
+/// Checks whether the operation writes to the choice array
+fn is_choice(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::MinRegImm(..)
+            | Op::MaxRegImm(..)
+            | Op::MinRegReg(..)
+            | Op::MaxRegReg(..)
+    )
+}
+
+/// Builds a new evaluation order for the given operations
+///
+/// `ops` must be in evaluation order.  Returns a permutation of
+/// `0..ops.len()`, which respects every dependency through registers and
+/// memory, and keeps `min` and `max` operations in their original order
+/// (because they write to the choice array sequentially).
+pub(super) fn schedule(ops: &[Op]) -> Vec<usize> {
+    let n = ops.len();
+    let slot_count = ops
+        .iter()
+        .flat_map(|op| inputs(op).into_iter().flatten().chain([output(op)]))
+        .max()
+        .map(|s| s as usize + 1)
+        .unwrap_or(0);
+
+    // Build a dependency graph, where each edge has a minimum delay
+    let mut succs: Vec<Vec<(usize, usize)>> = vec![vec![]; n];
+    let mut pred_count = vec![0; n];
+    let mut last_write = vec![None; slot_count];
+    let mut readers: Vec<Vec<usize>> = vec![vec![]; slot_count];
+    let mut last_choice = None;
+    let mut edge = |from: usize, to: usize, delay: usize| {
+        succs[from].push((to, delay));
+        pred_count[to] += 1;
+    };
+    for (i, op) in ops.iter().enumerate() {
+        for s in inputs(op).into_iter().flatten() {
+            if let Some(w) = last_write[s as usize] {
+                edge(w, i, latency(&ops[w]));
+            }
+            readers[s as usize].push(i);
+        }
+        let out = output(op) as usize;
+        if let Some(w) = last_write[out] {
+            edge(w, i, 1);
+        }
+        for r in readers[out].drain(..).filter(|r| *r != i) {
+            edge(r, i, 1);
+        }
+        last_write[out] = Some(i);
+        if is_choice(op) {
+            if let Some(c) = last_choice {
+                edge(c, i, 1);
+            }
+            last_choice = Some(i);
+        }
+    }
+
+    // The priority of each operation is the length of its critical path
+    let mut height = vec![0; n];
+    for i in (0..n).rev() {
+        height[i] = latency(&ops[i])
+            + succs[i].iter().map(|(s, _)| height[*s]).max().unwrap_or(0);
+    }
+
+    // Operations whose predecessors have been scheduled, keyed by the cycle
+    // at which their inputs are available
+    let mut pending = BinaryHeap::new();
+    // Operations whose inputs are available, keyed by priority
+    let mut ready = BinaryHeap::new();
+    let mut start = vec![0; n];
+    for i in (0..n).filter(|i| pred_count[*i] == 0) {
+        pending.push(Reverse((0, i)));
+    }
+
+    let mut cycle = 0;
+    let mut out = Vec::with_capacity(n);
+    while out.len() < n {
+        while let Some(Reverse((t, i))) = pending.peek().cloned() {
+            if t > cycle {
+                break;
+            }
+            pending.pop();
+            ready.push((height[i], Reverse(i)));
+        }
+        let Some((_, Reverse(i))) = ready.pop() else {
+            // Stall until the next operation is available
+            let Reverse((t, _)) = pending.peek().unwrap();
+            cycle = *t;
+            continue;
+        };
+        out.push(i);
+        for &(s, delay) in &succs[i] {
+            start[s] = start[s].max(cycle + delay);
+            pred_count[s] -= 1;
+            if pred_count[s] == 0 {
+                pending.push(Reverse((start[s], s)));
+            }
+        }
+        cycle += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, eval::Family};
+
+    #[test]
+    fn test_schedule_independent() {
+        // Two independent chains are interleaved
+        let ops = [
+            Op::Input(0, 0),
+            Op::SqrtReg(0, 0),
+            Op::AddRegImm(0, 0, 1.0),
+            Op::Input(1, 1),
+            Op::SqrtReg(1, 1),
+            Op::AddRegImm(1, 1, 1.0),
+            Op::AddRegReg(0, 0, 1),
+        ];
+        let order = schedule(&ops);
+        assert_eq!(order, [0, 3, 1, 4, 2, 5, 6]);
+    }
+
+    #[test]
+    fn test_schedule_dependencies() {
+        // Register 1 is reused, so the second chain must wait until the first
+        // chain has read it.
+        let ops = [
+            Op::Input(1, 0),
+            Op::SqrtReg(0, 1),
+            Op::Input(1, 1),
+            Op::MinRegReg(0, 0, 1),
+            Op::Input(1, 2),
+            Op::MaxRegImm(1, 1, 0.0),
+            Op::Store(1, 2),
+            Op::Load(1, 2),
+            Op::MaxRegReg(0, 0, 1),
+        ];
+        let order = schedule(&ops);
+        let pos = |i: usize| order.iter().position(|j| *j == i).unwrap();
+        assert!(pos(1) < pos(2)); // WAR on r1
+        assert!(pos(3) < pos(4)); // WAR on r1
+        assert!(pos(3) < pos(5)); // min before max
+        assert!(pos(5) < pos(8)); // max before max
+        assert!(pos(6) < pos(7)); // store before load
+    }
+
+    fn test_schedule_equivalent<F: Family>() {
+        // Build a wide expression, so that the JIT's register limit forces
+        // some values to be spilled to memory
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let mut terms = vec![];
+        for i in 0..24 {
+            let a = ctx.mul(x, i as f64 * 0.1 + 1.0).unwrap();
+            let b = ctx.sub(y, i as f64 * 0.05).unwrap();
+            let c = ctx.mul(a, b).unwrap();
+            let c = ctx.sqrt(c).unwrap();
+            let t = if i % 3 == 0 {
+                ctx.min(c, a).unwrap()
+            } else {
+                ctx.add(c, b).unwrap()
+            };
+            terms.push(t);
+        }
+        let root = ctx.max_many(terms).unwrap();
+        let tape = ctx.get_tape::<F>(root).unwrap();
+        let plain = tape.clone().with_scheduling(false);
+        let sched = tape.with_scheduling(true);
+        assert!(sched.scheduled());
+        assert!(!plain.scheduled());
+        assert_eq!(plain.len(), sched.len());
+
+        let pe = plain.new_point_evaluator();
+        let se = sched.new_point_evaluator();
+        for (x, y) in [(0.5, 0.25), (2.0, 0.0), (1.0, 3.0), (0.1, 0.9)] {
+            let (a, ta) = pe.eval(x, y, 0.0, &[]).unwrap();
+            let (b, tb) = se.eval(x, y, 0.0, &[]).unwrap();
+            assert_eq!(a.to_bits(), b.to_bits());
+            let (ta, tb) = (ta.unwrap(), tb.unwrap());
+            assert_eq!(ta.choices(), tb.choices());
+
+            // Simplified tapes inherit the setting
+            let simple = tb.simplify().unwrap();
+            assert!(simple.scheduled());
+            let v = simple.new_point_evaluator().eval(x, y, 0.0, &[]).unwrap();
+            assert_eq!(v.0.to_bits(), a.to_bits());
+        }
+
+        let pe = plain.new_interval_evaluator();
+        let se = sched.new_interval_evaluator();
+        let (a, ta) = pe.eval([0.0, 1.0], [0.5, 2.0], [0.0; 2], &[]).unwrap();
+        let (b, tb) = se.eval([0.0, 1.0], [0.5, 2.0], [0.0; 2], &[]).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            ta.map(|t| t.choices().to_vec()),
+            tb.map(|t| t.choices().to_vec())
+        );
+    }
+
+    #[test]
+    fn test_schedule_equivalent_vm() {
+        test_schedule_equivalent::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_schedule_equivalent_jit() {
+        test_schedule_equivalent::<crate::jit::Eval>();
+    }
+}
@@ -1,5 +1,6 @@
 //! Tape used for evaluation
 use crate::vm::Op;
+use alloc::{vec, vec::Vec};
 
 /// Low-level tape for use with the Fidget virtual machine (or to be lowered
 /// further into machine instructions).
@@ -7,6 +8,12 @@ use crate::vm::Op;
 pub struct Tape {
     tape: Vec<Op>,
 
+    /// Debug symbol for each operation in `tape`, which may be shorter than
+    /// `tape` if trailing operations are unnamed
+    ///
+    /// See [`ssa::Tape::symbols`](crate::ssa::Tape::symbols) for details.
+    symbols: Vec<u32>,
+
     /// Total allocated slots
     pub(super) slot_count: u32,
 
@@ -19,6 +26,7 @@ impl Tape {
     pub fn new(reg_limit: u8) -> Self {
         Self {
             tape: vec![],
+            symbols: vec![],
             slot_count: 1,
             reg_limit,
         }
@@ -26,6 +34,7 @@ impl Tape {
     /// Resets this tape, retaining its allocations
     pub fn reset(&mut self, reg_limit: u8) {
         self.tape.clear();
+        self.symbols.clear();
         self.slot_count = 1;
         self.reg_limit = reg_limit;
     }
@@ -54,18 +63,67 @@ impl Tape {
     /// This is the opposite of evaluation order; it will visit the root of the
     /// tree first, and end at the leaves.
     #[inline]
-    pub fn iter(&self) -> std::slice::Iter<'_, Op> {
+    pub fn iter(&self) -> core::slice::Iter<'_, Op> {
         self.into_iter()
     }
+    /// Returns the debug symbol of the operation at the given index
+    ///
+    /// This is an index into the
+    /// [`ssa::Tape::names`](crate::ssa::Tape::names) table of the tape from
+    /// which this tape was allocated, or `None` if the operation is unnamed.
+    pub fn symbol(&self, i: usize) -> Option<u32> {
+        self.symbols.get(i).cloned().filter(|s| *s != u32::MAX)
+    }
+    /// Reorders operations to hide instruction latency
+    ///
+    /// Registers are unchanged, and the result is equivalent to the original
+    /// tape (including the order in which choices are written).  See
+    /// [`Tape::with_scheduling`](crate::eval::Tape::with_scheduling) for
+    /// details.
+    pub fn schedule(&mut self) {
+        // Operations are stored in reverse-evaluation order
+        let n = self.tape.len();
+        let ops: Vec<Op> = self.tape.iter().rev().cloned().collect();
+        let order = super::schedule::schedule(&ops);
+        self.tape = order.iter().rev().map(|i| ops[*i]).collect();
+        if !self.symbols.is_empty() {
+            let symbols = order
+                .iter()
+                .rev()
+                .map(|i| self.symbol(n - 1 - i).unwrap_or(u32::MAX))
+                .collect::<Vec<_>>();
+            let len = symbols
+                .iter()
+                .rposition(|s| *s != u32::MAX)
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            self.symbols = symbols;
+            self.symbols.truncate(len);
+        }
+    }
+    /// Returns a mutable iterator over operations, in the same order as
+    /// [`iter`](Self::iter)
+    #[inline]
+    pub(crate) fn iter_mut(&mut self) -> core::slice::IterMut<'_, Op> {
+        self.tape.iter_mut()
+    }
     #[inline]
     pub(crate) fn push(&mut self, op: Op) {
         self.tape.push(op)
     }
+    /// Tags every operation from `start` onwards with the given symbol
+    #[inline]
+    pub(crate) fn set_symbol(&mut self, start: usize, symbol: u32) {
+        if symbol != u32::MAX || !self.symbols.is_empty() {
+            self.symbols.resize(start, u32::MAX);
+            self.symbols.resize(self.tape.len(), symbol);
+        }
+    }
 }
 
 impl<'a> IntoIterator for &'a Tape {
     type Item = &'a Op;
-    type IntoIter = std::slice::Iter<'a, Op>;
+    type IntoIter = core::slice::Iter<'a, Op>;
     fn into_iter(self) -> Self::IntoIter {
         self.tape.iter()
     }
@@ -0,0 +1,1382 @@
+//! Threaded-code interpreter
+//!
+//! The [`Threaded`] family evaluates the same tapes as the [`vm::Eval`]
+//! interpreter, but first translates each tape into an array of function
+//! pointers, with operands decoded ahead of time.  Evaluation then calls each
+//! function in turn, rather than matching on every [`Op`] (and re-checking
+//! evaluation flags) inside the hot loop.
+//!
+//! Translation happens once, when the evaluator is built, and its output is
+//! recycled as the evaluator's storage.  Bulk operations also run as tight
+//! loops over slices, which the compiler can vectorize.  Unlike the JIT, this
+//! is portable Rust and works on every target.
+//!
+//! [`vm::Eval`]: crate::vm::Eval
+use crate::{
+    eval::{
+        bulk::{BulkEvaluator, BulkEvaluatorData},
+        interval_slice::TracingSliceEval,
+        noise,
+        tracing::{TracingEvaluator, TracingEvaluatorData},
+        types::{Grad, Hessian, Interval},
+        Choice, CustomOp, EvaluatorStorage, Family, NanPolicy, Tape,
+    },
+    vm::{
+        eval::{nan_max, nan_min},
+        Op,
+    },
+};
+use alloc::{sync::Arc, vec, vec::Vec};
+use num_traits::Float;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Family of evaluators that use a threaded-code interpreter
+///
+/// ```
+/// use fidget::{rhai::eval, vm};
+///
+/// let (sum, ctx) = eval("x + y")?;
+/// let tape = ctx.get_tape::<vm::Threaded>(sum)?;
+/// let eval = tape.new_point_evaluator();
+/// assert_eq!(eval.eval(0.1, 0.3, 0.0, &[])?.0, 0.1 + 0.3);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+#[derive(Clone)]
+pub enum Threaded {}
+
+impl Family for Threaded {
+    /// This is interpreted, so we can use the maximum number of registers
+    const REG_LIMIT: u8 = u8::MAX;
+
+    type IntervalEval = ThreadedTracingEval<Interval>;
+    type IntervalSliceEval = TracingSliceEval<ThreadedTracingEval<Interval>>;
+    type PointEval = ThreadedTracingEval<f32>;
+    type FloatSliceEval = ThreadedBulkEval<f32>;
+    type GradSliceEval = ThreadedBulkEval<Grad>;
+    type HessianSliceEval = ThreadedBulkEval<Hessian>;
+
+    fn tile_sizes_3d() -> &'static [usize] {
+        &[256, 128, 64, 32, 16, 8]
+    }
+
+    fn tile_sizes_2d() -> &'static [usize] {
+        &[256, 128, 64, 32, 16, 8]
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Operations on a single value, shared by every evaluator type
+pub trait Value:
+    Copy
+    + From<f32>
+    + core::ops::Neg<Output = Self>
+    + core::ops::Add<Output = Self>
+    + core::ops::Sub<Output = Self>
+    + core::ops::Mul<Output = Self>
+    + core::ops::Div<Output = Self>
+{
+    fn abs(self) -> Self;
+    fn recip(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn square(self) -> Self;
+    fn atan2(self, rhs: Self) -> Self;
+    fn hypot(self, rhs: Self) -> Self;
+    /// Computes the floored modulo, `self - rhs * floor(self / rhs)`
+    fn modulo(self, rhs: Self) -> Self;
+    /// Computes `self * b + c`
+    fn fma(self, b: Self, c: Self) -> Self;
+    fn custom(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self;
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self;
+    /// Computes `max(min(self, hi), lo)`, propagating `NaN`
+    fn clamp(self, lo: Self, hi: Self) -> Self;
+    /// Applies a [`NanPolicy`] to an argument of `min` or `max`
+    fn nan(self, policy: NanPolicy) -> Self;
+}
+
+/// Operations on values used in tracing evaluation
+pub trait TracingValue: Value {
+    /// Whether [`widen`](Self::widen) does anything
+    const WIDENS: bool = false;
+
+    fn min_choice(self, rhs: Self) -> (Self, Choice);
+    fn max_choice(self, rhs: Self) -> (Self, Choice);
+    /// Widens a value to account for rounding error
+    fn widen(self) -> Self {
+        self
+    }
+}
+
+/// Operations on values used in bulk evaluation
+pub trait BulkValue: Value {
+    fn min(self, rhs: Self) -> Self;
+    fn max(self, rhs: Self) -> Self;
+    /// Builds a value from an input coordinate on the given axis
+    fn input(v: f32, axis: u32) -> Self;
+}
+
+impl Value for f32 {
+    fn abs(self) -> Self {
+        f32::abs(self)
+    }
+    fn recip(self) -> Self {
+        1.0 / self
+    }
+    fn sqrt(self) -> Self {
+        Float::sqrt(self)
+    }
+    fn sin(self) -> Self {
+        Float::sin(self)
+    }
+    fn cos(self) -> Self {
+        Float::cos(self)
+    }
+    fn square(self) -> Self {
+        self * self
+    }
+    fn atan2(self, rhs: Self) -> Self {
+        Float::atan2(self, rhs)
+    }
+    fn hypot(self, rhs: Self) -> Self {
+        Float::hypot(self, rhs)
+    }
+    fn modulo(self, rhs: Self) -> Self {
+        self - rhs * Float::floor(self / rhs)
+    }
+    fn fma(self, b: Self, c: Self) -> Self {
+        Float::mul_add(self, b, c)
+    }
+    fn custom(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self {
+        op.eval_f32(lhs, rhs)
+    }
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3(x, y, z, seed)
+    }
+    fn clamp(self, lo: Self, hi: Self) -> Self {
+        nan_max(nan_min(self, hi), lo)
+    }
+    fn nan(self, policy: NanPolicy) -> Self {
+        policy.float(self)
+    }
+}
+
+impl TracingValue for f32 {
+    fn min_choice(self, rhs: Self) -> (Self, Choice) {
+        if self < rhs {
+            (self, Choice::Left)
+        } else if rhs < self {
+            (rhs, Choice::Right)
+        } else if self.is_nan() || rhs.is_nan() {
+            (f32::NAN, Choice::Both)
+        } else {
+            (rhs, Choice::Both)
+        }
+    }
+    fn max_choice(self, rhs: Self) -> (Self, Choice) {
+        if self > rhs {
+            (self, Choice::Left)
+        } else if rhs > self {
+            (rhs, Choice::Right)
+        } else if self.is_nan() || rhs.is_nan() {
+            (f32::NAN, Choice::Both)
+        } else {
+            (rhs, Choice::Both)
+        }
+    }
+}
+
+impl BulkValue for f32 {
+    fn min(self, rhs: Self) -> Self {
+        nan_min(self, rhs)
+    }
+    fn max(self, rhs: Self) -> Self {
+        nan_max(self, rhs)
+    }
+    fn input(v: f32, _axis: u32) -> Self {
+        v
+    }
+}
+
+impl Value for Interval {
+    fn abs(self) -> Self {
+        Interval::abs(self)
+    }
+    fn recip(self) -> Self {
+        Interval::recip(self)
+    }
+    fn sqrt(self) -> Self {
+        Interval::sqrt(self)
+    }
+    fn sin(self) -> Self {
+        Interval::sin(self)
+    }
+    fn cos(self) -> Self {
+        Interval::cos(self)
+    }
+    fn square(self) -> Self {
+        Interval::square(self)
+    }
+    fn atan2(self, rhs: Self) -> Self {
+        Interval::atan2(self, rhs)
+    }
+    fn hypot(self, rhs: Self) -> Self {
+        Interval::hypot(self, rhs)
+    }
+    fn modulo(self, rhs: Self) -> Self {
+        Interval::modulo(self, rhs)
+    }
+    fn fma(self, b: Self, c: Self) -> Self {
+        self * b + c
+    }
+    fn custom(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self {
+        op.eval_interval(lhs, rhs)
+    }
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3_interval(x, y, z, seed)
+    }
+    fn clamp(self, lo: Self, hi: Self) -> Self {
+        Interval::clamp(self, lo, hi)
+    }
+    fn nan(self, policy: NanPolicy) -> Self {
+        policy.interval(self)
+    }
+}
+
+impl TracingValue for Interval {
+    const WIDENS: bool = true;
+
+    fn min_choice(self, rhs: Self) -> (Self, Choice) {
+        Interval::min_choice(self, rhs)
+    }
+    fn max_choice(self, rhs: Self) -> (Self, Choice) {
+        Interval::max_choice(self, rhs)
+    }
+    fn widen(self) -> Self {
+        Interval::widen(self)
+    }
+}
+
+impl Value for Grad {
+    fn abs(self) -> Self {
+        Grad::abs(self)
+    }
+    fn recip(self) -> Self {
+        Grad::from(1.0) / self
+    }
+    fn sqrt(self) -> Self {
+        Grad::sqrt(self)
+    }
+    fn sin(self) -> Self {
+        Grad::sin(self)
+    }
+    fn cos(self) -> Self {
+        Grad::cos(self)
+    }
+    fn square(self) -> Self {
+        self * self
+    }
+    fn atan2(self, rhs: Self) -> Self {
+        Grad::atan2(self, rhs)
+    }
+    fn hypot(self, rhs: Self) -> Self {
+        Grad::hypot(self, rhs)
+    }
+    fn modulo(self, rhs: Self) -> Self {
+        Grad::modulo(self, rhs)
+    }
+    fn fma(self, b: Self, c: Self) -> Self {
+        self * b + c
+    }
+    fn custom(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self {
+        op.eval_grad(lhs, rhs)
+    }
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3_grad(x, y, z, seed)
+    }
+    fn clamp(self, lo: Self, hi: Self) -> Self {
+        Grad::clamp(self, lo, hi)
+    }
+    fn nan(self, policy: NanPolicy) -> Self {
+        policy.grad(self)
+    }
+}
+
+impl BulkValue for Grad {
+    fn min(self, rhs: Self) -> Self {
+        Grad::min(self, rhs)
+    }
+    fn max(self, rhs: Self) -> Self {
+        Grad::max(self, rhs)
+    }
+    fn input(v: f32, axis: u32) -> Self {
+        match axis {
+            0 => Grad::new(v, 1.0, 0.0, 0.0),
+            1 => Grad::new(v, 0.0, 1.0, 0.0),
+            2 => Grad::new(v, 0.0, 0.0, 1.0),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Value for Hessian {
+    fn abs(self) -> Self {
+        Hessian::abs(self)
+    }
+    fn recip(self) -> Self {
+        Hessian::recip(self)
+    }
+    fn sqrt(self) -> Self {
+        Hessian::sqrt(self)
+    }
+    fn sin(self) -> Self {
+        Hessian::sin(self)
+    }
+    fn cos(self) -> Self {
+        Hessian::cos(self)
+    }
+    fn square(self) -> Self {
+        self * self
+    }
+    fn atan2(self, rhs: Self) -> Self {
+        Hessian::atan2(self, rhs)
+    }
+    fn hypot(self, rhs: Self) -> Self {
+        Hessian::hypot(self, rhs)
+    }
+    fn modulo(self, rhs: Self) -> Self {
+        Hessian::modulo(self, rhs)
+    }
+    fn fma(self, b: Self, c: Self) -> Self {
+        self * b + c
+    }
+    fn custom(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self {
+        op.eval_hessian(lhs, rhs)
+    }
+    fn noise(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3_hessian(x, y, z, seed)
+    }
+    fn clamp(self, lo: Self, hi: Self) -> Self {
+        Hessian::clamp(self, lo, hi)
+    }
+    fn nan(self, policy: NanPolicy) -> Self {
+        policy.hessian(self)
+    }
+}
+
+impl BulkValue for Hessian {
+    fn min(self, rhs: Self) -> Self {
+        Hessian::min(self, rhs)
+    }
+    fn max(self, rhs: Self) -> Self {
+        Hessian::max(self, rhs)
+    }
+    fn input(v: f32, axis: u32) -> Self {
+        Hessian::input(v, axis as usize)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Opcodes for the generic unary and binary kernels.  These are used as const
+// generic parameters, so that each opcode is monomorphized into its own
+// function (and the `match` in `unary` / `binary` is resolved at compile time).
+const NEG: u8 = 0;
+const ABS: u8 = 1;
+const RECIP: u8 = 2;
+const SQRT: u8 = 3;
+const SQUARE: u8 = 4;
+const COPY: u8 = 5;
+const SIN: u8 = 6;
+const COS: u8 = 7;
+
+const ADD: u8 = 0;
+const SUB: u8 = 1;
+const MUL: u8 = 2;
+const DIV: u8 = 3;
+const ATAN2: u8 = 4;
+const HYPOT: u8 = 5;
+const MOD: u8 = 6;
+
+#[inline(always)]
+fn unary<T: Value>(op: u8, v: T) -> T {
+    match op {
+        NEG => -v,
+        ABS => v.abs(),
+        RECIP => v.recip(),
+        SQRT => v.sqrt(),
+        SQUARE => v.square(),
+        COPY => v,
+        SIN => v.sin(),
+        COS => v.cos(),
+        _ => unreachable!(),
+    }
+}
+
+#[inline(always)]
+fn binary<T: Value>(op: u8, a: T, b: T) -> T {
+    match op {
+        ADD => a + b,
+        SUB => a - b,
+        MUL => a * b,
+        DIV => a / b,
+        ATAN2 => a.atan2(b),
+        HYPOT => a.hypot(b),
+        MOD => a.modulo(b),
+        _ => unreachable!(),
+    }
+}
+
+/// Pre-decoded operands for a single instruction
+///
+/// Register and memory slots are stored in the same index space; `index` is
+/// an auxiliary index (input axis, variable, choice, custom operation, or
+/// noise seed) whose meaning depends on the instruction.
+#[derive(Copy, Clone, Default)]
+pub struct Args {
+    out: u32,
+    lhs: u32,
+    rhs: u32,
+    arg: u32,
+    index: u32,
+    imm: f32,
+}
+
+/// A single instruction: a function pointer and its operands
+#[derive(Copy, Clone)]
+pub struct Instr<F> {
+    f: F,
+    args: Args,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// State used by tracing kernels
+pub struct TracingState<'a, T> {
+    slots: &'a mut [T],
+    inputs: [T; 3],
+    vars: &'a [f32],
+    choices: &'a mut [Choice],
+    simplify: bool,
+    custom: &'a [Arc<dyn CustomOp>],
+}
+
+pub type TracingFn<T> = fn(&mut TracingState<'_, T>, &Args);
+
+fn t_input<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = s.inputs[a.index as usize];
+}
+
+fn t_var<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = s.vars[a.index as usize].into();
+}
+
+fn t_imm<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = a.imm.into();
+}
+
+fn t_unary<T: Value, const OP: u8>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = unary(OP, s.slots[a.lhs as usize]);
+}
+
+fn t_reg_reg<T: Value, const OP: u8>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] =
+        binary(OP, s.slots[a.lhs as usize], s.slots[a.rhs as usize]);
+}
+
+fn t_reg_imm<T: Value, const OP: u8>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = binary(OP, s.slots[a.lhs as usize], a.imm.into());
+}
+
+fn t_imm_reg<T: Value, const OP: u8>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = binary(OP, a.imm.into(), s.slots[a.lhs as usize]);
+}
+
+/// Applies [`NanPolicy::Empty`] to an argument of `min` or `max` if `EMPTY`
+/// is set
+///
+/// The policy is resolved when translating the tape, rather than checked for
+/// every argument.
+#[inline(always)]
+fn nan_arg<T: Value, const EMPTY: bool>(v: T) -> T {
+    if EMPTY {
+        v.nan(NanPolicy::Empty)
+    } else {
+        v
+    }
+}
+
+/// Records a `min` / `max` choice
+#[inline(always)]
+fn t_choice<T>(s: &mut TracingState<'_, T>, a: &Args, choice: Choice) {
+    s.choices[a.index as usize] = choice;
+    s.simplify |= choice != Choice::Both;
+}
+
+fn t_min_reg_reg<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    let rhs = nan_arg::<T, EMPTY>(s.slots[a.rhs as usize]);
+    let (v, choice) = lhs.min_choice(rhs);
+    s.slots[a.out as usize] = v;
+    t_choice(s, a, choice);
+}
+
+fn t_max_reg_reg<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    let rhs = nan_arg::<T, EMPTY>(s.slots[a.rhs as usize]);
+    let (v, choice) = lhs.max_choice(rhs);
+    s.slots[a.out as usize] = v;
+    t_choice(s, a, choice);
+}
+
+fn t_min_reg_imm<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    let (v, choice) = lhs.min_choice(nan_arg::<T, EMPTY>(a.imm.into()));
+    s.slots[a.out as usize] = v;
+    t_choice(s, a, choice);
+}
+
+fn t_max_reg_imm<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    let (v, choice) = lhs.max_choice(nan_arg::<T, EMPTY>(a.imm.into()));
+    s.slots[a.out as usize] = v;
+    t_choice(s, a, choice);
+}
+
+fn t_min_nc_reg_reg<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    let rhs = nan_arg::<T, EMPTY>(s.slots[a.rhs as usize]);
+    s.slots[a.out as usize] = lhs.min_choice(rhs).0;
+}
+
+fn t_max_nc_reg_reg<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    let rhs = nan_arg::<T, EMPTY>(s.slots[a.rhs as usize]);
+    s.slots[a.out as usize] = lhs.max_choice(rhs).0;
+}
+
+fn t_min_nc_reg_imm<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    s.slots[a.out as usize] =
+        lhs.min_choice(nan_arg::<T, EMPTY>(a.imm.into())).0;
+}
+
+fn t_max_nc_reg_imm<T: TracingValue, const EMPTY: bool>(
+    s: &mut TracingState<'_, T>,
+    a: &Args,
+) {
+    let lhs = nan_arg::<T, EMPTY>(s.slots[a.lhs as usize]);
+    s.slots[a.out as usize] =
+        lhs.max_choice(nan_arg::<T, EMPTY>(a.imm.into())).0;
+}
+
+fn t_fma<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = s.slots[a.lhs as usize]
+        .fma(s.slots[a.rhs as usize], s.slots[a.arg as usize]);
+}
+
+/// Fused multiply-add which widens the (separately rounded) product
+fn t_fma_widen<T: TracingValue>(s: &mut TracingState<'_, T>, a: &Args) {
+    let p = s.slots[a.lhs as usize] * s.slots[a.rhs as usize];
+    s.slots[a.out as usize] = p.widen() + s.slots[a.arg as usize];
+}
+
+fn t_widen<T: TracingValue>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = s.slots[a.out as usize].widen();
+}
+
+fn t_custom<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    let op = &*s.custom[a.index as usize];
+    s.slots[a.out as usize] =
+        T::custom(op, s.slots[a.lhs as usize], s.slots[a.rhs as usize]);
+}
+
+fn t_clamp<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = s.slots[a.lhs as usize]
+        .clamp(s.slots[a.rhs as usize], s.slots[a.arg as usize]);
+}
+
+fn t_noise<T: Value>(s: &mut TracingState<'_, T>, a: &Args) {
+    s.slots[a.out as usize] = T::noise(
+        s.slots[a.lhs as usize],
+        s.slots[a.rhs as usize],
+        s.slots[a.arg as usize],
+        a.index as u16,
+    );
+}
+
+/// Translates a tape into tracing instructions, appending them to `code`
+fn compile_tracing<T: TracingValue>(
+    tape: &Tape<Threaded>,
+    code: &mut Vec<Instr<TracingFn<T>>>,
+) {
+    code.reserve(tape.len());
+    let widen = T::WIDENS && tape.conservative_intervals();
+    let empty = tape.nan_policy() == NanPolicy::Empty;
+    let mut choice_index = 0;
+    for op in tape.iter_asm() {
+        let (f, args): (TracingFn<T>, _) = match op {
+            Op::Input(out, i) => {
+                assert!(i < 3, "Invalid input: {i}");
+                (t_input, Args::index(out, i as u32))
+            }
+            Op::Var(out, i) => (t_var, Args::index(out, i)),
+            Op::CopyImm(out, imm) => (t_imm, Args::imm(out, 0, imm)),
+            Op::NegReg(out, arg) => (t_unary::<T, NEG>, Args::reg(out, arg)),
+            Op::AbsReg(out, arg) => (t_unary::<T, ABS>, Args::reg(out, arg)),
+            Op::RecipReg(out, arg) => {
+                (t_unary::<T, RECIP>, Args::reg(out, arg))
+            }
+            Op::SqrtReg(out, arg) => (t_unary::<T, SQRT>, Args::reg(out, arg)),
+            Op::SinReg(out, arg) => (t_unary::<T, SIN>, Args::reg(out, arg)),
+            Op::CosReg(out, arg) => (t_unary::<T, COS>, Args::reg(out, arg)),
+            Op::SquareReg(out, arg) => {
+                (t_unary::<T, SQUARE>, Args::reg(out, arg))
+            }
+            Op::CopyReg(out, arg) => (t_unary::<T, COPY>, Args::reg(out, arg)),
+            Op::Load(out, mem) => (t_unary::<T, COPY>, Args::load(out, mem)),
+            Op::Store(out, mem) => (t_unary::<T, COPY>, Args::store(out, mem)),
+            Op::AddRegImm(out, arg, imm) => {
+                (t_reg_imm::<T, ADD>, Args::imm(out, arg, imm))
+            }
+            Op::MulRegImm(out, arg, imm) => {
+                (t_reg_imm::<T, MUL>, Args::imm(out, arg, imm))
+            }
+            Op::SubRegImm(out, arg, imm) => {
+                (t_reg_imm::<T, SUB>, Args::imm(out, arg, imm))
+            }
+            Op::DivRegImm(out, arg, imm) => {
+                (t_reg_imm::<T, DIV>, Args::imm(out, arg, imm))
+            }
+            Op::Atan2RegImm(out, arg, imm) => {
+                (t_reg_imm::<T, ATAN2>, Args::imm(out, arg, imm))
+            }
+            Op::HypotRegImm(out, arg, imm) => {
+                (t_reg_imm::<T, HYPOT>, Args::imm(out, arg, imm))
+            }
+            Op::ModRegImm(out, arg, imm) => {
+                (t_reg_imm::<T, MOD>, Args::imm(out, arg, imm))
+            }
+            Op::ModImmReg(out, arg, imm) => {
+                (t_imm_reg::<T, MOD>, Args::imm(out, arg, imm))
+            }
+            Op::SubImmReg(out, arg, imm) => {
+                (t_imm_reg::<T, SUB>, Args::imm(out, arg, imm))
+            }
+            Op::DivImmReg(out, arg, imm) => {
+                (t_imm_reg::<T, DIV>, Args::imm(out, arg, imm))
+            }
+            Op::Atan2ImmReg(out, arg, imm) => {
+                (t_imm_reg::<T, ATAN2>, Args::imm(out, arg, imm))
+            }
+            Op::AddRegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, ADD>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::MulRegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, MUL>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::SubRegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, SUB>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::DivRegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, DIV>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::Atan2RegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, ATAN2>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::HypotRegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, HYPOT>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::ModRegReg(out, lhs, rhs) => {
+                (t_reg_reg::<T, MOD>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::MinRegImm(out, arg, imm) => (
+                if empty {
+                    t_min_reg_imm::<T, true>
+                } else {
+                    t_min_reg_imm::<T, false>
+                },
+                Args {
+                    index: choice_index,
+                    ..Args::imm(out, arg, imm)
+                },
+            ),
+            Op::MaxRegImm(out, arg, imm) => (
+                if empty {
+                    t_max_reg_imm::<T, true>
+                } else {
+                    t_max_reg_imm::<T, false>
+                },
+                Args {
+                    index: choice_index,
+                    ..Args::imm(out, arg, imm)
+                },
+            ),
+            Op::MinRegReg(out, lhs, rhs) => (
+                if empty {
+                    t_min_reg_reg::<T, true>
+                } else {
+                    t_min_reg_reg::<T, false>
+                },
+                Args {
+                    index: choice_index,
+                    ..Args::reg_reg(out, lhs, rhs)
+                },
+            ),
+            Op::MaxRegReg(out, lhs, rhs) => (
+                if empty {
+                    t_max_reg_reg::<T, true>
+                } else {
+                    t_max_reg_reg::<T, false>
+                },
+                Args {
+                    index: choice_index,
+                    ..Args::reg_reg(out, lhs, rhs)
+                },
+            ),
+            Op::MinNcRegImm(out, arg, imm) => (
+                if empty {
+                    t_min_nc_reg_imm::<T, true>
+                } else {
+                    t_min_nc_reg_imm::<T, false>
+                },
+                Args::imm(out, arg, imm),
+            ),
+            Op::MaxNcRegImm(out, arg, imm) => (
+                if empty {
+                    t_max_nc_reg_imm::<T, true>
+                } else {
+                    t_max_nc_reg_imm::<T, false>
+                },
+                Args::imm(out, arg, imm),
+            ),
+            Op::MinNcRegReg(out, lhs, rhs) => (
+                if empty {
+                    t_min_nc_reg_reg::<T, true>
+                } else {
+                    t_min_nc_reg_reg::<T, false>
+                },
+                Args::reg_reg(out, lhs, rhs),
+            ),
+            Op::MaxNcRegReg(out, lhs, rhs) => (
+                if empty {
+                    t_max_nc_reg_reg::<T, true>
+                } else {
+                    t_max_nc_reg_reg::<T, false>
+                },
+                Args::reg_reg(out, lhs, rhs),
+            ),
+            Op::FmaRegRegReg(out, a, b, c) => (
+                if widen { t_fma_widen } else { t_fma },
+                Args::reg_reg_reg(out, a, b, c),
+            ),
+            Op::CustomRegReg(out, lhs, rhs, c) => (
+                t_custom,
+                Args {
+                    index: c as u32,
+                    ..Args::reg_reg(out, lhs, rhs)
+                },
+            ),
+            Op::ClampRegRegReg(out, x, lo, hi) => {
+                (t_clamp, Args::reg_reg_reg(out, x, lo, hi))
+            }
+            Op::NoiseRegRegReg(out, x, y, z, seed) => (
+                t_noise,
+                Args {
+                    index: seed as u32,
+                    ..Args::reg_reg_reg(out, x, y, z)
+                },
+            ),
+        };
+        code.push(Instr { f, args });
+        if matches!(
+            op,
+            Op::MinRegImm(..)
+                | Op::MaxRegImm(..)
+                | Op::MinRegReg(..)
+                | Op::MaxRegReg(..)
+        ) {
+            choice_index += 1;
+        }
+        if let Some(out) = op.rounded_output().filter(|_| widen) {
+            code.push(Instr {
+                f: t_widen,
+                args: Args::reg(out, out),
+            });
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// State used by bulk kernels
+pub struct BulkState<'a, T> {
+    slots: &'a mut [Vec<T>],
+    size: usize,
+    inputs: [&'a [f32]; 3],
+    vars: &'a [f32],
+    custom: &'a [Arc<dyn CustomOp>],
+}
+
+pub type BulkFn<T> = fn(&mut BulkState<'_, T>, &Args);
+
+fn b_input<T: BulkValue>(s: &mut BulkState<'_, T>, a: &Args) {
+    let input = &s.inputs[a.index as usize][0..s.size];
+    for (o, v) in s.slots[a.out as usize].iter_mut().zip(input) {
+        *o = T::input(*v, a.index);
+    }
+}
+
+fn b_var<T: Value>(s: &mut BulkState<'_, T>, a: &Args) {
+    let v = s.vars[a.index as usize].into();
+    s.slots[a.out as usize][0..s.size].fill(v);
+}
+
+fn b_imm<T: Value>(s: &mut BulkState<'_, T>, a: &Args) {
+    s.slots[a.out as usize][0..s.size].fill(a.imm.into());
+}
+
+/// Applies `f` to every item in the `arg` slot, writing to the `out` slot
+///
+/// The output slot is temporarily moved out of `slots`, so that we can
+/// iterate over slices (rather than indexing into `slots` for every item).
+#[inline(always)]
+fn map1<T: Copy>(
+    s: &mut BulkState<'_, T>,
+    out: u32,
+    arg: u32,
+    f: impl Fn(T) -> T,
+) {
+    let mut o = core::mem::take(&mut s.slots[out as usize]);
+    let os = &mut o[0..s.size];
+    if arg == out {
+        os.iter_mut().for_each(|o| *o = f(*o));
+    } else {
+        let args = &s.slots[arg as usize][0..s.size];
+        os.iter_mut().zip(args).for_each(|(o, a)| *o = f(*a));
+    }
+    s.slots[out as usize] = o;
+}
+
+/// Applies `f` to every pair of items in the `lhs` and `rhs` slots, writing
+/// to the `out` slot
+#[inline(always)]
+fn map2<T: Copy>(
+    s: &mut BulkState<'_, T>,
+    out: u32,
+    lhs: u32,
+    rhs: u32,
+    f: impl Fn(T, T) -> T,
+) {
+    let mut o = core::mem::take(&mut s.slots[out as usize]);
+    let os = &mut o[0..s.size];
+    match (lhs == out, rhs == out) {
+        (true, true) => os.iter_mut().for_each(|o| *o = f(*o, *o)),
+        (true, false) => {
+            let rs = &s.slots[rhs as usize][0..s.size];
+            os.iter_mut().zip(rs).for_each(|(o, r)| *o = f(*o, *r));
+        }
+        (false, true) => {
+            let ls = &s.slots[lhs as usize][0..s.size];
+            os.iter_mut().zip(ls).for_each(|(o, l)| *o = f(*l, *o));
+        }
+        (false, false) => {
+            let ls = &s.slots[lhs as usize][0..s.size];
+            let rs = &s.slots[rhs as usize][0..s.size];
+            os.iter_mut()
+                .zip(ls.iter().zip(rs))
+                .for_each(|(o, (l, r))| *o = f(*l, *r));
+        }
+    }
+    s.slots[out as usize] = o;
+}
+
+fn b_unary<T: Value, const OP: u8>(s: &mut BulkState<'_, T>, a: &Args) {
+    map1(s, a.out, a.lhs, |v| unary(OP, v));
+}
+
+fn b_reg_reg<T: Value, const OP: u8>(s: &mut BulkState<'_, T>, a: &Args) {
+    map2(s, a.out, a.lhs, a.rhs, |l, r| binary(OP, l, r));
+}
+
+fn b_reg_imm<T: Value, const OP: u8>(s: &mut BulkState<'_, T>, a: &Args) {
+    let imm = a.imm.into();
+    map1(s, a.out, a.lhs, |v| binary(OP, v, imm));
+}
+
+fn b_imm_reg<T: Value, const OP: u8>(s: &mut BulkState<'_, T>, a: &Args) {
+    let imm = a.imm.into();
+    map1(s, a.out, a.lhs, |v| binary(OP, imm, v));
+}
+
+fn b_min_reg_reg<T: BulkValue, const EMPTY: bool>(
+    s: &mut BulkState<'_, T>,
+    a: &Args,
+) {
+    map2(s, a.out, a.lhs, a.rhs, |l, r| {
+        nan_arg::<T, EMPTY>(l).min(nan_arg::<T, EMPTY>(r))
+    });
+}
+
+fn b_max_reg_reg<T: BulkValue, const EMPTY: bool>(
+    s: &mut BulkState<'_, T>,
+    a: &Args,
+) {
+    map2(s, a.out, a.lhs, a.rhs, |l, r| {
+        nan_arg::<T, EMPTY>(l).max(nan_arg::<T, EMPTY>(r))
+    });
+}
+
+fn b_min_reg_imm<T: BulkValue, const EMPTY: bool>(
+    s: &mut BulkState<'_, T>,
+    a: &Args,
+) {
+    let imm = nan_arg::<T, EMPTY>(a.imm.into());
+    map1(s, a.out, a.lhs, |v| nan_arg::<T, EMPTY>(v).min(imm));
+}
+
+fn b_max_reg_imm<T: BulkValue, const EMPTY: bool>(
+    s: &mut BulkState<'_, T>,
+    a: &Args,
+) {
+    let imm = nan_arg::<T, EMPTY>(a.imm.into());
+    map1(s, a.out, a.lhs, |v| nan_arg::<T, EMPTY>(v).max(imm));
+}
+
+fn b_fma<T: Value>(s: &mut BulkState<'_, T>, a: &Args) {
+    for i in 0..s.size {
+        s.slots[a.out as usize][i] = s.slots[a.lhs as usize][i]
+            .fma(s.slots[a.rhs as usize][i], s.slots[a.arg as usize][i]);
+    }
+}
+
+fn b_custom<T: Value>(s: &mut BulkState<'_, T>, a: &Args) {
+    let op = &*s.custom[a.index as usize];
+    for i in 0..s.size {
+        s.slots[a.out as usize][i] = T::custom(
+            op,
+            s.slots[a.lhs as usize][i],
+            s.slots[a.rhs as usize][i],
+        );
+    }
+}
+
+fn b_clamp<T: Value>(s: &mut BulkState<'_, T>, a: &Args) {
+    for i in 0..s.size {
+        s.slots[a.out as usize][i] = s.slots[a.lhs as usize][i]
+            .clamp(s.slots[a.rhs as usize][i], s.slots[a.arg as usize][i]);
+    }
+}
+
+fn b_noise<T: Value>(s: &mut BulkState<'_, T>, a: &Args) {
+    for i in 0..s.size {
+        s.slots[a.out as usize][i] = T::noise(
+            s.slots[a.lhs as usize][i],
+            s.slots[a.rhs as usize][i],
+            s.slots[a.arg as usize][i],
+            a.index as u16,
+        );
+    }
+}
+
+/// Translates a tape into bulk instructions, appending them to `code`
+fn compile_bulk<T: BulkValue>(
+    tape: &Tape<Threaded>,
+    code: &mut Vec<Instr<BulkFn<T>>>,
+) {
+    code.reserve(tape.len());
+    let empty = tape.nan_policy() == NanPolicy::Empty;
+    for op in tape.iter_asm() {
+        let (f, args): (BulkFn<T>, _) = match op {
+            Op::Input(out, i) => {
+                assert!(i < 3, "Invalid input: {i}");
+                (b_input, Args::index(out, i as u32))
+            }
+            Op::Var(out, i) => (b_var, Args::index(out, i)),
+            Op::CopyImm(out, imm) => (b_imm, Args::imm(out, 0, imm)),
+            Op::NegReg(out, arg) => (b_unary::<T, NEG>, Args::reg(out, arg)),
+            Op::AbsReg(out, arg) => (b_unary::<T, ABS>, Args::reg(out, arg)),
+            Op::RecipReg(out, arg) => {
+                (b_unary::<T, RECIP>, Args::reg(out, arg))
+            }
+            Op::SqrtReg(out, arg) => (b_unary::<T, SQRT>, Args::reg(out, arg)),
+            Op::SinReg(out, arg) => (b_unary::<T, SIN>, Args::reg(out, arg)),
+            Op::CosReg(out, arg) => (b_unary::<T, COS>, Args::reg(out, arg)),
+            Op::SquareReg(out, arg) => {
+                (b_unary::<T, SQUARE>, Args::reg(out, arg))
+            }
+            Op::CopyReg(out, arg) => (b_unary::<T, COPY>, Args::reg(out, arg)),
+            Op::Load(out, mem) => (b_unary::<T, COPY>, Args::load(out, mem)),
+            Op::Store(out, mem) => (b_unary::<T, COPY>, Args::store(out, mem)),
+            Op::AddRegImm(out, arg, imm) => {
+                (b_reg_imm::<T, ADD>, Args::imm(out, arg, imm))
+            }
+            Op::MulRegImm(out, arg, imm) => {
+                (b_reg_imm::<T, MUL>, Args::imm(out, arg, imm))
+            }
+            Op::SubRegImm(out, arg, imm) => {
+                (b_reg_imm::<T, SUB>, Args::imm(out, arg, imm))
+            }
+            Op::DivRegImm(out, arg, imm) => {
+                (b_reg_imm::<T, DIV>, Args::imm(out, arg, imm))
+            }
+            Op::Atan2RegImm(out, arg, imm) => {
+                (b_reg_imm::<T, ATAN2>, Args::imm(out, arg, imm))
+            }
+            Op::HypotRegImm(out, arg, imm) => {
+                (b_reg_imm::<T, HYPOT>, Args::imm(out, arg, imm))
+            }
+            Op::ModRegImm(out, arg, imm) => {
+                (b_reg_imm::<T, MOD>, Args::imm(out, arg, imm))
+            }
+            Op::ModImmReg(out, arg, imm) => {
+                (b_imm_reg::<T, MOD>, Args::imm(out, arg, imm))
+            }
+            Op::SubImmReg(out, arg, imm) => {
+                (b_imm_reg::<T, SUB>, Args::imm(out, arg, imm))
+            }
+            Op::DivImmReg(out, arg, imm) => {
+                (b_imm_reg::<T, DIV>, Args::imm(out, arg, imm))
+            }
+            Op::Atan2ImmReg(out, arg, imm) => {
+                (b_imm_reg::<T, ATAN2>, Args::imm(out, arg, imm))
+            }
+            Op::AddRegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, ADD>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::MulRegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, MUL>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::SubRegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, SUB>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::DivRegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, DIV>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::Atan2RegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, ATAN2>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::HypotRegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, HYPOT>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::ModRegReg(out, lhs, rhs) => {
+                (b_reg_reg::<T, MOD>, Args::reg_reg(out, lhs, rhs))
+            }
+            Op::MinRegImm(out, arg, imm) | Op::MinNcRegImm(out, arg, imm) => (
+                if empty {
+                    b_min_reg_imm::<T, true>
+                } else {
+                    b_min_reg_imm::<T, false>
+                },
+                Args::imm(out, arg, imm),
+            ),
+            Op::MaxRegImm(out, arg, imm) | Op::MaxNcRegImm(out, arg, imm) => (
+                if empty {
+                    b_max_reg_imm::<T, true>
+                } else {
+                    b_max_reg_imm::<T, false>
+                },
+                Args::imm(out, arg, imm),
+            ),
+            Op::MinRegReg(out, lhs, rhs) | Op::MinNcRegReg(out, lhs, rhs) => (
+                if empty {
+                    b_min_reg_reg::<T, true>
+                } else {
+                    b_min_reg_reg::<T, false>
+                },
+                Args::reg_reg(out, lhs, rhs),
+            ),
+            Op::MaxRegReg(out, lhs, rhs) | Op::MaxNcRegReg(out, lhs, rhs) => (
+                if empty {
+                    b_max_reg_reg::<T, true>
+                } else {
+                    b_max_reg_reg::<T, false>
+                },
+                Args::reg_reg(out, lhs, rhs),
+            ),
+            Op::FmaRegRegReg(out, a, b, c) => {
+                (b_fma, Args::reg_reg_reg(out, a, b, c))
+            }
+            Op::CustomRegReg(out, lhs, rhs, c) => (
+                b_custom,
+                Args {
+                    index: c as u32,
+                    ..Args::reg_reg(out, lhs, rhs)
+                },
+            ),
+            Op::ClampRegRegReg(out, x, lo, hi) => {
+                (b_clamp, Args::reg_reg_reg(out, x, lo, hi))
+            }
+            Op::NoiseRegRegReg(out, x, y, z, seed) => (
+                b_noise,
+                Args {
+                    index: seed as u32,
+                    ..Args::reg_reg_reg(out, x, y, z)
+                },
+            ),
+        };
+        code.push(Instr { f, args });
+    }
+}
+
+impl Args {
+    fn index(out: u8, index: u32) -> Self {
+        Self {
+            out: out as u32,
+            index,
+            ..Self::default()
+        }
+    }
+    fn reg(out: u8, arg: u8) -> Self {
+        Self {
+            out: out as u32,
+            lhs: arg as u32,
+            ..Self::default()
+        }
+    }
+    fn load(reg: u8, mem: u32) -> Self {
+        Self {
+            out: reg as u32,
+            lhs: mem,
+            ..Self::default()
+        }
+    }
+    fn store(reg: u8, mem: u32) -> Self {
+        Self {
+            out: mem,
+            lhs: reg as u32,
+            ..Self::default()
+        }
+    }
+    fn imm(out: u8, arg: u8, imm: f32) -> Self {
+        Self {
+            out: out as u32,
+            lhs: arg as u32,
+            imm,
+            ..Self::default()
+        }
+    }
+    fn reg_reg(out: u8, lhs: u8, rhs: u8) -> Self {
+        Self {
+            out: out as u32,
+            lhs: lhs as u32,
+            rhs: rhs as u32,
+            ..Self::default()
+        }
+    }
+    fn reg_reg_reg(out: u8, lhs: u8, rhs: u8, arg: u8) -> Self {
+        Self {
+            out: out as u32,
+            lhs: lhs as u32,
+            rhs: rhs as u32,
+            arg: arg as u32,
+            ..Self::default()
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Threaded-code evaluator for single points or intervals
+#[derive(Clone)]
+pub struct ThreadedTracingEval<T> {
+    tape: Tape<Threaded>,
+    code: Vec<Instr<TracingFn<T>>>,
+}
+
+/// Scratch data for a [`ThreadedTracingEval`]
+pub struct ThreadedTracingData<T> {
+    slots: Vec<T>,
+}
+
+impl<T> Default for ThreadedTracingData<T> {
+    fn default() -> Self {
+        Self { slots: vec![] }
+    }
+}
+
+impl<T: From<f32> + Clone> TracingEvaluatorData<Threaded>
+    for ThreadedTracingData<T>
+{
+    fn prepare(&mut self, tape: &Tape<Threaded>) {
+        let slot_count = tape.slot_count();
+        self.slots.resize(slot_count, T::from(f32::NAN));
+        self.slots.fill(T::from(f32::NAN));
+    }
+}
+
+impl<T: TracingValue> EvaluatorStorage<Threaded> for ThreadedTracingEval<T> {
+    type Storage = Vec<Instr<TracingFn<T>>>;
+    fn new_with_storage(
+        tape: &Tape<Threaded>,
+        mut storage: Self::Storage,
+    ) -> Self {
+        storage.clear();
+        compile_tracing(tape, &mut storage);
+        Self {
+            tape: tape.clone(),
+            code: storage,
+        }
+    }
+    fn take(self) -> Option<Self::Storage> {
+        Some(self.code)
+    }
+}
+
+impl<T: TracingValue + Send> TracingEvaluator<T, Threaded>
+    for ThreadedTracingEval<T>
+{
+    type Data = ThreadedTracingData<T>;
+
+    fn eval_with(
+        &self,
+        x: T,
+        y: T,
+        z: T,
+        vars: &[f32],
+        choices: &mut [Choice],
+        data: &mut Self::Data,
+    ) -> (T, bool) {
+        assert_eq!(vars.len(), self.tape.var_count());
+        let mut s = TracingState {
+            slots: &mut data.slots,
+            inputs: [x, y, z],
+            vars,
+            choices,
+            simplify: false,
+            custom: self.tape.custom_ops(),
+        };
+        for i in &self.code {
+            (i.f)(&mut s, &i.args);
+        }
+        let simplify = s.simplify;
+        (data.slots[0], simplify)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Threaded-code evaluator for slices of points or gradients
+#[derive(Clone)]
+pub struct ThreadedBulkEval<T> {
+    tape: Tape<Threaded>,
+    code: Vec<Instr<BulkFn<T>>>,
+}
+
+/// Scratch data for a [`ThreadedBulkEval`]
+pub struct ThreadedBulkData<T> {
+    /// Workspace for data
+    slots: Vec<Vec<T>>,
+    /// Current slice size in `self.slots`
+    slice_size: usize,
+}
+
+impl<T> Default for ThreadedBulkData<T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            slice_size: 0,
+        }
+    }
+}
+
+impl<T: From<f32> + Clone> BulkEvaluatorData<Threaded> for ThreadedBulkData<T> {
+    fn prepare(&mut self, tape: &Tape<Threaded>, size: usize) {
+        self.slots.resize_with(tape.slot_count(), || {
+            vec![f32::NAN.into(); size.max(self.slice_size)]
+        });
+        if size > self.slice_size {
+            for s in self.slots.iter_mut() {
+                s.resize(size, f32::NAN.into());
+            }
+            self.slice_size = size;
+        }
+    }
+}
+
+impl<T: BulkValue> EvaluatorStorage<Threaded> for ThreadedBulkEval<T> {
+    type Storage = Vec<Instr<BulkFn<T>>>;
+    fn new_with_storage(
+        tape: &Tape<Threaded>,
+        mut storage: Self::Storage,
+    ) -> Self {
+        storage.clear();
+        compile_bulk(tape, &mut storage);
+        Self {
+            tape: tape.clone(),
+            code: storage,
+        }
+    }
+    fn take(self) -> Option<Self::Storage> {
+        Some(self.code)
+    }
+}
+
+impl<T: BulkValue + Send> BulkEvaluator<T, Threaded> for ThreadedBulkEval<T> {
+    type Data = ThreadedBulkData<T>;
+
+    fn eval_with(
+        &self,
+        xs: &[f32],
+        ys: &[f32],
+        zs: &[f32],
+        vars: &[f32],
+        out: &mut [T],
+        data: &mut Self::Data,
+    ) {
+        assert_eq!(xs.len(), ys.len());
+        assert_eq!(ys.len(), zs.len());
+        assert_eq!(zs.len(), out.len());
+        assert_eq!(vars.len(), self.tape.var_count());
+        assert_eq!(data.slots.len(), self.tape.slot_count());
+
+        let size = xs.len();
+        assert!(data.slice_size >= size);
+
+        let mut s = BulkState {
+            slots: &mut data.slots,
+            size,
+            inputs: [xs, ys, zs],
+            vars,
+            custom: self.tape.custom_ops(),
+        };
+        for i in &self.code {
+            (i.f)(&mut s, &i.args);
+        }
+        out.copy_from_slice(&data.slots[0][0..size])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    crate::grad_slice_tests!(Threaded);
+    crate::hessian_slice_tests!(Threaded);
+    crate::interval_tests!(Threaded);
+    crate::interval_slice_tests!(Threaded);
+    crate::float_slice_tests!(Threaded);
+    crate::point_tests!(Threaded);
+}
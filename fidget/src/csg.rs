@@ -0,0 +1,900 @@
+//! Import of OpenSCAD-style CSG trees
+//!
+//! The [`import`] function parses a CSG tree in the format written by
+//! OpenSCAD's `.csg` export, e.g.
+//!
+//! ```text
+//! difference() {
+//!     cube(size = [2, 2, 2], center = true);
+//!     multmatrix([[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0.5], [0, 0, 0, 1]]) {
+//!         sphere($fn = 0, $fa = 12, $fs = 2, r = 1.2);
+//!     }
+//! }
+//! ```
+//!
+//! and lowers it into a single expression, negative inside and positive
+//! outside.  Curved primitives are exact (so `$fn`, `$fa`, and `$fs` are
+//! ignored), and primitives are distance fields; Booleans and non-uniform
+//! scaling preserve the sign of the result, but not necessarily its distance.
+//!
+//! The following operations are supported:
+//!
+//! - 3D primitives: `cube`, `sphere`, and `cylinder` (including cones)
+//! - 2D primitives: `square`, `circle`, and `polygon`; these are independent
+//!   of Z, so they can be rendered in 2D or extruded
+//! - Booleans: `union`, `difference`, and `intersection`, along with
+//!   `group`, `render`, and `color` (which act like `union`)
+//! - Transforms: `multmatrix`, which OpenSCAD uses for every affine transform
+//! - `linear_extrude` (without `twist` or `scale`), `rotate_extrude` (for a
+//!   full turn), and `offset`
+//!
+//! Other operations (e.g. `hull`, `minkowski`, or `import`) return an error.
+//! Objects with the `%` (background) or `*` (disable) modifiers are skipped,
+//! since OpenSCAD leaves them out of the rendered model.
+//!
+//! ```
+//! use fidget::{context::Context, csg};
+//!
+//! let mut ctx = Context::new();
+//! let shape = csg::import(
+//!     &mut ctx,
+//!     "difference() {
+//!          cube(size = [2, 2, 2], center = true);
+//!          sphere(r = 1.2);
+//!      }",
+//! )?;
+//! assert_eq!(ctx.eval_xyz(shape, 0.0, 0.0, 0.0)?, 1.2);
+//! assert!(ctx.eval_xyz(shape, 0.95, 0.95, 0.95)? < 0.0);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{Context, Node},
+    outline::{FillRule, Outline},
+    shapes::{self, Axis, Correction},
+    Error,
+};
+
+/// Parses a CSG tree and converts it into an expression
+///
+/// Multiple top-level objects are combined with a union.  If the tree contains
+/// no objects, then the result is a constant (positive) infinity.
+///
+/// Returns [`Error::BadCsg`] if the text is malformed or uses an unsupported
+/// operation.
+pub fn import(ctx: &mut Context, text: &str) -> Result<Node, Error> {
+    let mut parser = Parser::new(text);
+    let mut calls = vec![];
+    while let Some(c) = parser.call()? {
+        calls.extend(c);
+    }
+    union(ctx, &calls)
+}
+
+/// Argument value
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Bool(bool),
+    Str,
+    Vector(Vec<Value>),
+    Undef,
+}
+
+impl Value {
+    fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns a vector of exactly `N` numbers
+    fn numbers<const N: usize>(&self) -> Option<[f64; N]> {
+        let Value::Vector(vs) = self else {
+            return None;
+        };
+        if vs.len() != N {
+            return None;
+        }
+        let mut out = [0.0; N];
+        for (o, v) in out.iter_mut().zip(vs) {
+            *o = v.number()?;
+        }
+        Some(out)
+    }
+}
+
+/// A single operation in the tree, e.g. `cube(size = 1);`
+#[derive(Debug)]
+struct Call {
+    name: String,
+    /// Byte offset of the operation's name, for error reporting
+    pos: usize,
+    args: Vec<(Option<String>, Value)>,
+    children: Vec<Call>,
+}
+
+impl Call {
+    fn error(&self, msg: &str) -> Error {
+        Error::BadCsg(self.pos, format!("`{}`: {msg}", self.name))
+    }
+
+    /// Looks up a named argument, treating `undef` as missing
+    fn arg(&self, name: &str) -> Option<&Value> {
+        self.args
+            .iter()
+            .find(|(n, v)| {
+                n.as_deref() == Some(name) && !matches!(v, Value::Undef)
+            })
+            .map(|(_, v)| v)
+    }
+
+    /// Looks up a named numeric argument
+    fn number(&self, name: &str) -> Result<Option<f64>, Error> {
+        self.arg(name)
+            .map(|v| {
+                v.number().ok_or_else(|| {
+                    self.error(&format!("`{name}` must be a number"))
+                })
+            })
+            .transpose()
+    }
+
+    /// Looks up a named boolean argument, which defaults to `false`
+    fn flag(&self, name: &str) -> Result<bool, Error> {
+        match self.arg(name) {
+            None => Ok(false),
+            Some(Value::Bool(b)) => Ok(*b),
+            Some(_) => Err(self.error(&format!("`{name}` must be a boolean"))),
+        }
+    }
+
+    /// Looks up a size, which is either a number or a vector of `N` numbers
+    fn size<const N: usize>(&self) -> Result<[f64; N], Error> {
+        match self.arg("size") {
+            None => Ok([1.0; N]),
+            Some(Value::Number(v)) => Ok([*v; N]),
+            Some(v) => v.numbers().ok_or_else(|| self.error("invalid `size`")),
+        }
+    }
+
+    /// Looks up a radius, given either as `r{suffix}` or `d{suffix}`
+    fn radius(&self, suffix: &str) -> Result<Option<f64>, Error> {
+        if let Some(r) = self.number(&format!("r{suffix}"))? {
+            Ok(Some(r))
+        } else {
+            Ok(self.number(&format!("d{suffix}"))?.map(|d| d / 2.0))
+        }
+    }
+}
+
+/// Tokenizer and parser for CSG trees
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            data: data.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::BadCsg(self.pos, msg.to_owned())
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    /// Skips whitespace and comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.pos += 1;
+            }
+            if self.data[self.pos..].starts_with(b"//") {
+                while self.peek().is_some_and(|c| c != b'\n') {
+                    self.pos += 1;
+                }
+            } else if self.data[self.pos..].starts_with(b"/*") {
+                match self.data[self.pos..].windows(2).position(|w| w == b"*/")
+                {
+                    Some(i) => self.pos += i + 2,
+                    None => self.pos = self.data.len(),
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consumes the given character (after whitespace), if present
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c as char)))
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| {
+            c.is_ascii_alphabetic()
+                || c == b'_'
+                || c == b'$'
+                || (self.pos > start && c.is_ascii_digit())
+        }) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            None
+        } else {
+            // The slice is ASCII, so this can't fail
+            Some(
+                std::str::from_utf8(&self.data[start..self.pos])
+                    .unwrap()
+                    .to_owned(),
+            )
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, Error> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| {
+            c.is_ascii_digit() || matches!(c, b'+' | b'-' | b'.' | b'e' | b'E')
+        }) {
+            self.pos += 1;
+        }
+        // The slice is ASCII, so this can't fail
+        let s = std::str::from_utf8(&self.data[start..self.pos]).unwrap();
+        s.parse().map_err(|_| {
+            self.pos = start;
+            self.error("invalid number")
+        })
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
+                let mut out = vec![];
+                if !self.eat(b']') {
+                    loop {
+                        out.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Value::Vector(out))
+            }
+            Some(b'"') => {
+                self.pos += 1;
+                while let Some(c) = self.peek() {
+                    self.pos += 1;
+                    match c {
+                        b'"' => return Ok(Value::Str),
+                        b'\\' => self.pos += 1,
+                        _ => (),
+                    }
+                }
+                Err(self.error("unterminated string"))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                match self.ident().as_deref() {
+                    Some("true") => Ok(Value::Bool(true)),
+                    Some("false") => Ok(Value::Bool(false)),
+                    Some("undef") => Ok(Value::Undef),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("unknown value"))
+                    }
+                }
+            }
+            _ => self.number().map(Value::Number),
+        }
+    }
+
+    /// Parses a single operation and its children
+    ///
+    /// Returns `None` at the end of input or before a closing brace, or
+    /// `Some(None)` if the operation is disabled by a modifier.
+    fn call(&mut self) -> Result<Option<Option<Call>>, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some(b'}') => return Ok(None),
+            Some(b';') => {
+                self.pos += 1;
+                return Ok(Some(None));
+            }
+            _ => (),
+        }
+
+        // Modifiers, which may be stacked
+        let mut skip = false;
+        while let Some(c @ (b'%' | b'*' | b'#' | b'!')) = self.peek() {
+            skip |= matches!(c, b'%' | b'*');
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+
+        let pos = self.pos;
+        let name = self
+            .ident()
+            .ok_or_else(|| self.error("expected operation name"))?;
+        self.expect(b'(')?;
+        let mut args = vec![];
+        if !self.eat(b')') {
+            loop {
+                // Arguments are either `name = value` or positional
+                let start = self.pos;
+                let key = match self.ident() {
+                    Some(k) if self.eat(b'=') => Some(k),
+                    _ => {
+                        self.pos = start;
+                        None
+                    }
+                };
+                args.push((key, self.value()?));
+                if self.eat(b')') {
+                    break;
+                }
+                self.expect(b',')?;
+            }
+        }
+
+        let mut children = vec![];
+        if self.eat(b'{') {
+            while let Some(c) = self.call()? {
+                children.extend(c);
+            }
+            self.expect(b'}')?;
+        } else {
+            self.expect(b';')?;
+        }
+        let call = Call {
+            name,
+            pos,
+            args,
+            children,
+        };
+        Ok(Some((!skip).then_some(call)))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Converts a single operation into an expression
+fn lower(ctx: &mut Context, call: &Call) -> Result<Node, Error> {
+    match call.name.as_str() {
+        "union" | "group" | "render" | "color" => union(ctx, &call.children),
+        "intersection" => {
+            let children = call
+                .children
+                .iter()
+                .map(|c| lower(ctx, c))
+                .collect::<Result<Vec<_>, _>>()?;
+            if children.is_empty() {
+                Ok(ctx.constant(f64::INFINITY))
+            } else {
+                ctx.max_many(children)
+            }
+        }
+        "difference" => {
+            let Some((first, rest)) = call.children.split_first() else {
+                return Ok(ctx.constant(f64::INFINITY));
+            };
+            let first = lower(ctx, first)?;
+            if rest.is_empty() {
+                return Ok(first);
+            }
+            let rest = union(ctx, rest)?;
+            let rest = ctx.neg(rest)?;
+            ctx.max(first, rest)
+        }
+        "multmatrix" => multmatrix(ctx, call),
+        "linear_extrude" => {
+            if call.number("twist")?.is_some_and(|t| t != 0.0) {
+                return Err(call.error("`twist` is not supported"));
+            }
+            match call.arg("scale") {
+                None => (),
+                Some(Value::Number(s)) if *s == 1.0 => (),
+                Some(s) if s.numbers() == Some([1.0; 2]) => (),
+                Some(_) => return Err(call.error("`scale` is not supported")),
+            }
+            let height = call.number("height")?.unwrap_or(100.0);
+            let shape = union(ctx, &call.children)?;
+            let shape = shapes::extrude(ctx, shape, height)?;
+            if call.flag("center")? {
+                translate(ctx, shape, [0.0, 0.0, -height / 2.0])
+            } else {
+                Ok(shape)
+            }
+        }
+        "rotate_extrude" => {
+            if call.number("angle")?.is_some_and(|a| a.abs() < 360.0) {
+                return Err(call.error("partial revolutions are not supported"));
+            }
+            let shape = union(ctx, &call.children)?;
+            shapes::revolve(ctx, shape, Axis::Z)
+        }
+        "offset" => {
+            let amount = match (call.number("r")?, call.number("delta")?) {
+                (Some(r), _) => r,
+                (None, Some(d)) => d,
+                (None, None) => 1.0,
+            };
+            let shape = union(ctx, &call.children)?;
+            shapes::offset(ctx, shape, amount, Correction::None)
+        }
+        "cube" => {
+            let size = call.size::<3>()?;
+            let lower = if call.flag("center")? {
+                size.map(|s| -s / 2.0)
+            } else {
+                [0.0; 3]
+            };
+            rectangle(ctx, lower, size)
+        }
+        "square" => {
+            let size = call.size::<2>()?;
+            let lower = if call.flag("center")? {
+                size.map(|s| -s / 2.0)
+            } else {
+                [0.0; 2]
+            };
+            rectangle(ctx, lower, size)
+        }
+        "sphere" => {
+            let r = call.radius("")?.unwrap_or(1.0);
+            if r <= 0.0 {
+                return Ok(ctx.constant(f64::INFINITY));
+            }
+            let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+            let x2 = ctx.square(x)?;
+            let y2 = ctx.square(y)?;
+            let z2 = ctx.square(z)?;
+            let sum = ctx.add(x2, y2)?;
+            let sum = ctx.add(sum, z2)?;
+            let dist = ctx.sqrt(sum)?;
+            ctx.sub(dist, r)
+        }
+        "circle" => {
+            let r = call.radius("")?.unwrap_or(1.0);
+            if r <= 0.0 {
+                return Ok(ctx.constant(f64::INFINITY));
+            }
+            let (x, y) = (ctx.x(), ctx.y());
+            let dist = ctx.hypot(x, y)?;
+            ctx.sub(dist, r)
+        }
+        "cylinder" => cylinder(ctx, call),
+        "polygon" => polygon(ctx, call),
+        _ => Err(call.error("unsupported operation")),
+    }
+}
+
+/// Combines operations with a union, returning infinity if there are none
+fn union(ctx: &mut Context, calls: &[Call]) -> Result<Node, Error> {
+    let children = calls
+        .iter()
+        .map(|c| lower(ctx, c))
+        .collect::<Result<Vec<_>, _>>()?;
+    if children.is_empty() {
+        Ok(ctx.constant(f64::INFINITY))
+    } else {
+        ctx.min_many(children)
+    }
+}
+
+/// Builds an axis-aligned box (in 2D or 3D) with the given corner and size
+fn rectangle<const N: usize>(
+    ctx: &mut Context,
+    lower: [f64; N],
+    size: [f64; N],
+) -> Result<Node, Error> {
+    if size.iter().any(|s| *s <= 0.0) {
+        return Ok(ctx.constant(f64::INFINITY));
+    }
+    let axes = [ctx.x(), ctx.y(), ctx.z()];
+
+    // Per-axis signed distance to the slab
+    let mut q = vec![];
+    for i in 0..N {
+        let half = size[i] / 2.0;
+        let d = ctx.sub(axes[i], lower[i] + half)?;
+        let d = ctx.abs(d)?;
+        q.push(ctx.sub(d, half)?);
+    }
+
+    // Exterior distance, if any axis is outside
+    let mut outside = ctx.constant(0.0);
+    for d in &q {
+        let d = ctx.max(*d, 0.0)?;
+        let d = ctx.square(d)?;
+        outside = ctx.add(outside, d)?;
+    }
+    let outside = ctx.sqrt(outside)?;
+
+    // Interior distance, if every axis is inside
+    let inside = ctx.max_many(q)?;
+    let inside = ctx.min(inside, 0.0)?;
+    ctx.add(outside, inside)
+}
+
+/// Builds a cylinder or (truncated) cone along the Z axis
+fn cylinder(ctx: &mut Context, call: &Call) -> Result<Node, Error> {
+    let h = call.number("h")?.unwrap_or(1.0);
+    let r = call.radius("")?.unwrap_or(1.0);
+    let r1 = call.radius("1")?.unwrap_or(r);
+    let r2 = call.radius("2")?.unwrap_or(r);
+    if h <= 0.0 || r1 < 0.0 || r2 < 0.0 || (r1 == 0.0 && r2 == 0.0) {
+        return Ok(ctx.constant(f64::INFINITY));
+    }
+    let z0 = if call.flag("center")? { -h / 2.0 } else { 0.0 };
+
+    // Distance to the (infinite) side surface, measured perpendicular to it
+    let slope = (r2 - r1) / h;
+    let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+    let radius = ctx.hypot(x, y)?;
+    let dz = ctx.sub(z, z0)?;
+    let rz = ctx.mul(dz, slope)?;
+    let rz = ctx.add(rz, r1)?;
+    let side = ctx.sub(radius, rz)?;
+    let side = ctx.div(side, (1.0 + slope * slope).sqrt())?;
+
+    // Distance to the end caps
+    let caps = ctx.sub(z, z0 + h / 2.0)?;
+    let caps = ctx.abs(caps)?;
+    let caps = ctx.sub(caps, h / 2.0)?;
+    ctx.max(side, caps)
+}
+
+/// Builds a 2D polygon, treating secondary paths as holes
+fn polygon(ctx: &mut Context, call: &Call) -> Result<Node, Error> {
+    let points = match call.arg("points") {
+        Some(Value::Vector(ps)) => ps
+            .iter()
+            .map(|p| p.numbers::<2>())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| call.error("invalid `points`"))?,
+        None => vec![],
+        Some(_) => return Err(call.error("invalid `points`")),
+    };
+    let paths = match call.arg("paths") {
+        None => vec![(0..points.len()).collect()],
+        Some(Value::Vector(paths)) => paths
+            .iter()
+            .map(|p| match p {
+                Value::Vector(p) => p
+                    .iter()
+                    .map(|i| {
+                        i.number()
+                            .filter(|i| {
+                                *i >= 0.0 && (*i as usize) < points.len()
+                            })
+                            .map(|i| i as usize)
+                    })
+                    .collect::<Option<Vec<usize>>>(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| call.error("invalid `paths`"))?,
+        Some(_) => return Err(call.error("invalid `paths`")),
+    };
+
+    let mut out = Outline::new([1.0; 2], [0.0; 2]);
+    for path in paths {
+        let mut iter = path.into_iter().map(|i| points[i]);
+        if let Some([x, y]) = iter.next() {
+            out.move_to(x, y);
+            for [x, y] in iter {
+                out.line_to(x, y);
+            }
+            out.close();
+        }
+    }
+    out.build(ctx, FillRule::EvenOdd)
+}
+
+/// Applies an affine transform from a `multmatrix` operation
+fn multmatrix(ctx: &mut Context, call: &Call) -> Result<Node, Error> {
+    let m = call
+        .arg("m")
+        .or_else(|| call.args.iter().find(|(n, _)| n.is_none()).map(|(_, v)| v))
+        .ok_or_else(|| call.error("missing matrix"))?;
+    let Value::Vector(rows) = m else {
+        return Err(call.error("invalid matrix"));
+    };
+    if rows.len() < 3 {
+        return Err(call.error("invalid matrix"));
+    }
+    let mut a = [[0.0; 3]; 3];
+    let mut t = [0.0; 3];
+    for i in 0..3 {
+        let [r0, r1, r2, ti] = rows[i]
+            .numbers::<4>()
+            .ok_or_else(|| call.error("invalid matrix"))?;
+        a[i] = [r0, r1, r2];
+        t[i] = ti;
+    }
+    let shape = union(ctx, &call.children)?;
+    if a == [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]] {
+        return translate(ctx, shape, t);
+    }
+
+    // Invert the linear part, by cofactors
+    let det = a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+        - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+        + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0]);
+    if det == 0.0 || !det.is_finite() {
+        return Err(call.error("matrix is singular"));
+    }
+    let mut inv = [[0.0; 3]; 3];
+    for (i, row) in inv.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+            let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+            *v = (a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0]) / det;
+        }
+    }
+
+    // Evaluate the child at A⁻¹ (p - t)
+    let axes = [ctx.x(), ctx.y(), ctx.z()];
+    let mut xyz = [axes[0]; 3];
+    for i in 0..3 {
+        let offset: f64 = (0..3).map(|j| inv[i][j] * t[j]).sum();
+        let mut out = ctx.constant(-offset);
+        for j in 0..3 {
+            if inv[i][j] != 0.0 {
+                let term = ctx.mul(axes[j], inv[i][j])?;
+                out = ctx.add(out, term)?;
+            }
+        }
+        xyz[i] = out;
+    }
+    let shape = ctx.remap_xyz(shape, xyz)?;
+
+    // A similarity transform scales distances uniformly, so we can correct
+    // for it and keep distance fields exact.
+    let scale = det.abs().cbrt();
+    let similar = (0..3).all(|i| {
+        (0..3).all(|j| {
+            let dot: f64 = (0..3).map(|k| a[k][i] * a[k][j]).sum();
+            let expected = if i == j { scale * scale } else { 0.0 };
+            (dot - expected).abs() <= 1e-9 * scale * scale
+        })
+    });
+    if similar && scale != 1.0 {
+        ctx.mul(shape, scale)
+    } else {
+        Ok(shape)
+    }
+}
+
+/// Moves a shape by the given offset
+fn translate(
+    ctx: &mut Context,
+    shape: Node,
+    offset: [f64; 3],
+) -> Result<Node, Error> {
+    if offset == [0.0; 3] {
+        return Ok(shape);
+    }
+    let axes = [ctx.x(), ctx.y(), ctx.z()];
+    let mut xyz = axes;
+    for i in 0..3 {
+        xyz[i] = ctx.sub(axes[i], offset[i])?;
+    }
+    ctx.remap_xyz(shape, xyz)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(ctx: &Context, shape: Node, p: [f64; 3]) -> f64 {
+        ctx.eval_xyz(shape, p[0], p[1], p[2]).unwrap()
+    }
+
+    #[test]
+    fn test_primitives() {
+        let mut ctx = Context::new();
+        let cube = import(&mut ctx, "cube(size = [2, 4, 6], center = false);")
+            .unwrap();
+        assert_eq!(eval(&ctx, cube, [1.0, 2.0, 3.0]), -1.0);
+        assert_eq!(eval(&ctx, cube, [3.0, 2.0, 3.0]), 1.0);
+        assert_eq!(eval(&ctx, cube, [3.0, 5.0, 3.0]), 2f64.sqrt());
+
+        let sphere = import(&mut ctx, "sphere($fn = 0, d = 4);").unwrap();
+        assert_eq!(eval(&ctx, sphere, [0.0, 0.0, 3.0]), 1.0);
+
+        let cyl =
+            import(&mut ctx, "cylinder(h = 2, r1 = 1, r2 = 1, center = true);")
+                .unwrap();
+        assert_eq!(eval(&ctx, cyl, [0.0, 0.0, 0.0]), -1.0);
+        assert_eq!(eval(&ctx, cyl, [2.0, 0.0, 0.5]), 1.0);
+        assert_eq!(eval(&ctx, cyl, [0.0, 0.0, 1.5]), 0.5);
+
+        // A cone with its tip at Z = 1
+        let cone =
+            import(&mut ctx, "cylinder(h = 1, r1 = 1, r2 = 0);").unwrap();
+        assert!(eval(&ctx, cone, [0.0, 0.0, 0.9]) < 0.0);
+        assert!(eval(&ctx, cone, [0.5, 0.0, 0.6]) > 0.0);
+        assert!(eval(&ctx, cone, [0.5, 0.0, 0.4]) < 0.0);
+
+        let square =
+            import(&mut ctx, "square(size = 2, center = true);").unwrap();
+        assert_eq!(eval(&ctx, square, [0.0, 0.0, 5.0]), -1.0);
+        assert_eq!(eval(&ctx, square, [3.0, 0.0, 5.0]), 2.0);
+
+        let circle = import(&mut ctx, "circle(r = 2);").unwrap();
+        assert_eq!(eval(&ctx, circle, [3.0, 0.0, 0.0]), 1.0);
+    }
+
+    #[test]
+    fn test_polygon() {
+        let mut ctx = Context::new();
+        let shape = import(
+            &mut ctx,
+            "polygon(
+                points = [[-2, -2], [2, -2], [2, 2], [-2, 2],
+                          [-1, -1], [1, -1], [1, 1], [-1, 1]],
+                paths = [[0, 1, 2, 3], [4, 5, 6, 7]],
+                convexity = 1
+            );",
+        )
+        .unwrap();
+        assert!(eval(&ctx, shape, [0.0, 0.0, 0.0]) > 0.0);
+        assert!((eval(&ctx, shape, [1.5, 0.0, 0.0]) + 0.5).abs() < 1e-6);
+        assert!(eval(&ctx, shape, [3.0, 0.0, 0.0]) > 0.0);
+
+        let shape = import(
+            &mut ctx,
+            "polygon(points = [[0, 0], [1, 0], [0, 1]], paths = undef);",
+        )
+        .unwrap();
+        assert!(eval(&ctx, shape, [0.2, 0.2, 0.0]) < 0.0);
+        assert!(eval(&ctx, shape, [0.6, 0.6, 0.0]) > 0.0);
+    }
+
+    #[test]
+    fn test_booleans() {
+        let mut ctx = Context::new();
+        let shape = import(
+            &mut ctx,
+            "
+            // Comments are allowed
+            group() {
+                difference() {
+                    cube(size = [2, 2, 2], center = true);
+                    sphere(r = 1.2);
+                    %sphere(r = 10);
+                }
+                intersection() {
+                    cube(size = 1);
+                    cube(size = 1, center = true);
+                }
+                group();
+            }",
+        )
+        .unwrap();
+        assert_eq!(eval(&ctx, shape, [0.0, 0.0, 0.0]), 0.0);
+        assert!(eval(&ctx, shape, [0.25, 0.25, 0.25]) < 0.0);
+        assert!(eval(&ctx, shape, [0.0, 0.0, 0.8]) > 0.0);
+        assert!(eval(&ctx, shape, [0.95, 0.95, 0.95]) < 0.0);
+
+        let empty = import(&mut ctx, "group() { *cube(size = 1); }").unwrap();
+        assert_eq!(eval(&ctx, empty, [0.0; 3]), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_multmatrix() {
+        let mut ctx = Context::new();
+
+        // Translated and scaled by 2 (uniformly)
+        let shape = import(
+            &mut ctx,
+            "multmatrix([[2, 0, 0, 1], [0, 2, 0, 0], [0, 0, 2, 0], [0, 0, 0, 1]]) {
+                sphere(r = 1);
+            }",
+        )
+        .unwrap();
+        assert_eq!(eval(&ctx, shape, [1.0, 0.0, 0.0]), -2.0);
+        assert_eq!(eval(&ctx, shape, [4.0, 0.0, 0.0]), 1.0);
+
+        // Rotated 90° around Z
+        let shape = import(
+            &mut ctx,
+            "multmatrix(m = [[0, -1, 0, 0], [1, 0, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]]) {
+                cube(size = [4, 1, 1]);
+            }",
+        )
+        .unwrap();
+        assert!(eval(&ctx, shape, [-0.5, 3.5, 0.5]) < 0.0);
+        assert!(eval(&ctx, shape, [3.5, 0.5, 0.5]) > 0.0);
+
+        assert!(matches!(
+            import(
+                &mut ctx,
+                "multmatrix([[0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0]]) { }"
+            ),
+            Err(Error::BadCsg(0, _))
+        ));
+    }
+
+    #[test]
+    fn test_extrude() {
+        let mut ctx = Context::new();
+        let shape = import(
+            &mut ctx,
+            "linear_extrude(height = 2, center = true, convexity = 1,
+                            twist = 0, slices = 1, scale = [1, 1]) {
+                circle(r = 1);
+            }",
+        )
+        .unwrap();
+        assert_eq!(eval(&ctx, shape, [0.0, 0.0, 0.0]), -1.0);
+        assert_eq!(eval(&ctx, shape, [0.0, 0.0, 1.5]), 0.5);
+
+        // A torus
+        let shape = import(
+            &mut ctx,
+            "rotate_extrude(angle = 360, $fn = 0) {
+                multmatrix([[1, 0, 0, 2], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]]) {
+                    circle(r = 0.5);
+                }
+            }",
+        )
+        .unwrap();
+        assert_eq!(eval(&ctx, shape, [0.0, 2.0, 0.0]), -0.5);
+        assert_eq!(eval(&ctx, shape, [0.0, 0.0, 0.0]), 1.5);
+
+        let shape = import(
+            &mut ctx,
+            "offset(r = 0.5) { square(size = 2, center = true); }",
+        )
+        .unwrap();
+        assert_eq!(eval(&ctx, shape, [1.5, 0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut ctx = Context::new();
+        for (text, pos) in [
+            ("hull() { cube(); }", 0),
+            ("group() { minkowski(); }", 10),
+            ("cube(size = [1, 2]);", 0),
+            ("cube(size = 1)", 14),
+            ("cube(size = foo);", 12),
+            ("group() { cube(); ", 18),
+            ("linear_extrude(height = 1, twist = 90) { }", 0),
+        ] {
+            match import(&mut ctx, text) {
+                Err(Error::BadCsg(p, _)) => assert_eq!(p, pos, "{text}"),
+                r => panic!("unexpected result for {text}: {r:?}"),
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 //! Module containing the Fidget universal error type
+use alloc::string::String;
 use thiserror::Error;
 
 /// Universal error type for Fidget
@@ -24,6 +25,13 @@ pub enum Error {
     /// Unknown variable {0}
     #[error("unknown variable {0}")]
     UnknownVariable(String),
+    /// Unknown evaluator backend {0}
+    #[error("unknown evaluator backend {0}")]
+    UnknownBackend(String),
+
+    /// Argument list is empty
+    #[error("argument list is empty")]
+    EmptyArguments,
 
     /// Empty file
     #[error("empty file")]
@@ -49,7 +57,80 @@ pub enum Error {
     #[error("this name has already been used")]
     DuplicateName,
 
+    /// Too many custom operations have been added to this `Context`
+    #[error("too many custom operations (the limit is 65536)")]
+    TooManyCustomOps,
+
+    /// A traversal of the graph visited more nodes than its `Context` allows
+    #[error("too many nodes in traversal (the limit is {0})")]
+    TooManyNodes(usize),
+
+    /// Invalid SVG path data
+    #[error("invalid SVG path data at byte {0}: {1}")]
+    BadPathData(usize, String),
+
+    /// Invalid or unsupported CSG tree
+    #[error("invalid CSG tree at byte {0}: {1}")]
+    BadCsg(usize, String),
+
+    /// Voxel grid settings are invalid (zero brick size or infinite region)
+    #[error("invalid voxel grid settings")]
+    BadVoxelSettings,
+
+    /// Region must be finite
+    #[error("region must be finite")]
+    InfiniteRegion,
+
+    /// Iso-level is outside of the range sampled when building an octree
+    #[error("iso-level {0} is outside of the octree's sampled range")]
+    BadIsoLevel(f32),
+
+    /// Gradient magnitude must be positive and finite
+    #[error("gradient magnitude {0} must be positive and finite")]
+    BadGradientScale(f64),
+
+    /// Arrays must have at least one copy
+    #[error("arrays must have at least one copy")]
+    EmptyArray,
+
+    /// Primitive shape has invalid dimensions
+    #[error("invalid shape dimensions")]
+    BadDimensions,
+
+    /// Tape needs more slots than the evaluator family supports
+    #[error("tape needs {0} slots, but the evaluator supports at most {1}")]
+    TooManySlots(usize, usize),
+
+    /// Fused function has more outputs than the JIT supports
+    #[error("fused function has {0} outputs, but at most {1} are supported")]
+    TooManyOutputs(usize, usize),
+
+    /// Strided input has a coordinate offset which isn't less than its stride
+    #[error("coordinate offsets must be less than the stride ({0})")]
+    BadStride(usize),
+
+    /// Framebuffer stride or length is too small for the image
+    #[error("framebuffer (stride {0}, length {1}) is too small for the image")]
+    BadFramebuffer(usize, usize),
+
+    /// Render cache data is invalid or was written by a different version
+    #[error("invalid render cache data")]
+    BadRenderCache,
+
+    /// `.frep` data is invalid
+    #[error("invalid .frep data")]
+    BadFrep,
+
+    /// `.frep` data was written with an unsupported format version
+    #[error("unsupported .frep version {0}")]
+    BadFrepVersion(u32),
+
+    /// Custom operations can't be written to a `.frep` file
+    #[error("custom operations can't be written to a .frep file")]
+    CustomOpInFrep,
+
     /// io error; see inner code for details
+    #[cfg(feature = "std")]
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -62,4 +143,24 @@ pub enum Error {
     /// Dynasm error; see inner code for details
     #[error("dynasm error: {0}")]
     DynasmError(#[from] dynasmrt::DynasmError),
+
+    #[cfg(feature = "gpu")]
+    /// No GPU adapter is available
+    #[error("no GPU adapter is available")]
+    NoGpuAdapter,
+
+    #[cfg(feature = "gpu")]
+    /// Custom operations can't be evaluated on the GPU
+    #[error("custom operations can't be evaluated on the GPU")]
+    CustomOpOnGpu,
+
+    #[cfg(feature = "gpu")]
+    /// GPU device error; see inner code for details
+    #[error("GPU device error: {0}")]
+    GpuDeviceError(#[from] wgpu::RequestDeviceError),
+
+    #[cfg(feature = "gpu")]
+    /// GPU buffer error; see inner code for details
+    #[error("GPU buffer error: {0}")]
+    GpuBufferError(#[from] wgpu::BufferAsyncError),
 }
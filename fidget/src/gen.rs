@@ -0,0 +1,299 @@
+//! Reproducible random expressions
+//!
+//! This module builds random expressions from a seeded [`Rng`], for use in
+//! differential testing (see [`fidget::test_utils`](crate::test_utils)) and
+//! benchmarks.  The same seed and [`Settings`] always produce the same
+//! expression, on every platform.
+//!
+//! ```
+//! use fidget::{context::{Context, UnaryOpcode}, gen};
+//!
+//! // Deep expressions which only use X, Y, and a variable `r`, with no
+//! // transcendental functions
+//! let settings = gen::Settings {
+//!     depth: 8,
+//!     axes: [true, true, false],
+//!     vars: vec!["r".to_owned()],
+//!     ..Default::default()
+//! }
+//! .without_unary(&[UnaryOpcode::Sin, UnaryOpcode::Cos]);
+//!
+//! let mut ctx = Context::new();
+//! let a = gen::random_expr(&mut ctx, &mut gen::Rng::new(1), &settings)?;
+//! let b = gen::random_expr(&mut ctx, &mut gen::Rng::new(1), &settings)?;
+//! assert_eq!(a, b);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{BinaryOpcode, Context, Node, UnaryOpcode},
+    Error,
+};
+use alloc::{string::String, vec, vec::Vec};
+
+/// Small deterministic pseudo-random number generator
+///
+/// This is SplitMix64, which is plenty for generating test cases and doesn't
+/// require an external dependency.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Builds a new generator from the given seed
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Returns the next 64-bit value
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in the range `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns a value in the range `lo..hi`
+    pub fn range(&mut self, lo: f32, hi: f32) -> f32 {
+        let t = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        lo + (hi - lo) * t
+    }
+}
+
+/// Settings for [`random_expr`]
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Maximum number of nested operations
+    pub depth: usize,
+
+    /// Odds of stopping early at each level
+    ///
+    /// Each node becomes a leaf with probability `1 / early_leaf`, so that
+    /// trees aren't all perfectly balanced; 0 disables early leaves.
+    pub early_leaf: usize,
+
+    /// Unary opcodes which may appear, with relative weights
+    pub unary: Vec<(UnaryOpcode, usize)>,
+
+    /// Binary opcodes which may appear, with relative weights
+    pub binary: Vec<(BinaryOpcode, usize)>,
+
+    /// Whether X, Y, and Z may appear as leaves
+    pub axes: [bool; 3],
+
+    /// Names of variables which may appear as leaves
+    pub vars: Vec<String>,
+
+    /// Range of constant leaves
+    ///
+    /// If this is `None`, constants don't appear as leaves.
+    pub constants: Option<[f32; 2]>,
+}
+
+impl Default for Settings {
+    /// Returns settings for expressions up to 6 levels deep, using every
+    /// arithmetic opcode, X, Y, Z, and constants in the range `-2..2`
+    fn default() -> Self {
+        use BinaryOpcode as B;
+        use UnaryOpcode as U;
+        Self {
+            depth: 6,
+            early_leaf: 4,
+            unary: [
+                U::Neg,
+                U::Abs,
+                U::Recip,
+                U::Sqrt,
+                U::Square,
+                U::Sin,
+                U::Cos,
+            ]
+            .map(|op| (op, 1))
+            .to_vec(),
+            binary: [B::Add, B::Sub, B::Mul, B::Div, B::Min, B::Max]
+                .map(|op| (op, 1))
+                .to_vec(),
+            axes: [true; 3],
+            vars: vec![],
+            constants: Some([-2.0, 2.0]),
+        }
+    }
+}
+
+impl Settings {
+    /// Removes the given unary opcodes from the mix
+    pub fn without_unary(mut self, ops: &[UnaryOpcode]) -> Self {
+        self.unary.retain(|(op, _)| !ops.contains(op));
+        self
+    }
+
+    /// Removes the given binary opcodes from the mix
+    pub fn without_binary(mut self, ops: &[BinaryOpcode]) -> Self {
+        self.binary.retain(|(op, _)| !ops.contains(op));
+        self
+    }
+}
+
+/// Builds a random expression with at most `settings.depth` levels of
+/// operations
+///
+/// If no opcodes are enabled, the result is a single leaf.
+///
+/// Returns [`Error::EmptyArguments`] if no leaves are enabled, or
+/// [`Error::ReservedName`] if one of the variables is named `X`, `Y`, or `Z`.
+pub fn random_expr(
+    ctx: &mut Context,
+    rng: &mut Rng,
+    settings: &Settings,
+) -> Result<Node, Error> {
+    let leaf_count = settings.axes.iter().filter(|a| **a).count()
+        + settings.vars.len()
+        + usize::from(settings.constants.is_some());
+    if leaf_count == 0 {
+        return Err(Error::EmptyArguments);
+    }
+    let op_weight = settings.unary.iter().map(|(_, w)| w).sum::<usize>()
+        + settings.binary.iter().map(|(_, w)| w).sum::<usize>();
+    build(ctx, rng, settings, settings.depth, leaf_count, op_weight)
+}
+
+fn build(
+    ctx: &mut Context,
+    rng: &mut Rng,
+    settings: &Settings,
+    depth: usize,
+    leaf_count: usize,
+    op_weight: usize,
+) -> Result<Node, Error> {
+    if depth == 0
+        || op_weight == 0
+        || (settings.early_leaf > 0 && rng.below(settings.early_leaf) == 0)
+    {
+        let mut i = rng.below(leaf_count);
+        for (axis, enabled) in settings.axes.iter().enumerate() {
+            if !enabled {
+                continue;
+            } else if i == 0 {
+                return Ok(match axis {
+                    0 => ctx.x(),
+                    1 => ctx.y(),
+                    _ => ctx.z(),
+                });
+            }
+            i -= 1;
+        }
+        return match settings.vars.get(i) {
+            Some(v) => ctx.var(v),
+            None => {
+                let [lo, hi] = settings.constants.unwrap();
+                Ok(ctx.constant(rng.range(lo, hi) as f64))
+            }
+        };
+    }
+
+    let a = build(ctx, rng, settings, depth - 1, leaf_count, op_weight)?;
+    let mut i = rng.below(op_weight);
+    for &(op, w) in &settings.unary {
+        if i < w {
+            return match op {
+                UnaryOpcode::Neg => ctx.neg(a),
+                UnaryOpcode::Abs => ctx.abs(a),
+                UnaryOpcode::Recip => ctx.recip(a),
+                UnaryOpcode::Sqrt => ctx.sqrt(a),
+                UnaryOpcode::Square => ctx.square(a),
+                UnaryOpcode::Sin => ctx.sin(a),
+                UnaryOpcode::Cos => ctx.cos(a),
+            };
+        }
+        i -= w;
+    }
+    let mut op = None;
+    for &(o, w) in &settings.binary {
+        if i < w {
+            op = Some(o);
+            break;
+        }
+        i -= w;
+    }
+    let b = build(ctx, rng, settings, depth - 1, leaf_count, op_weight)?;
+    match op.unwrap() {
+        BinaryOpcode::Add => ctx.add(a, b),
+        BinaryOpcode::Sub => ctx.sub(a, b),
+        BinaryOpcode::Mul => ctx.mul(a, b),
+        BinaryOpcode::Div => ctx.div(a, b),
+        BinaryOpcode::Min => ctx.min(a, b),
+        BinaryOpcode::Max => ctx.max(a, b),
+        BinaryOpcode::Atan2 => ctx.atan2(a, b),
+        BinaryOpcode::Hypot => ctx.hypot(a, b),
+        BinaryOpcode::Mod => ctx.modulo(a, b),
+        BinaryOpcode::MinNc => ctx.min_nc(a, b),
+        BinaryOpcode::MaxNc => ctx.max_nc(a, b),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Op;
+
+    #[test]
+    fn test_rng() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let v = rng.range(-1.0, 3.0);
+            assert!((-1.0..3.0).contains(&v));
+            assert!(rng.below(5) < 5);
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = Settings {
+            depth: 5,
+            early_leaf: 0,
+            unary: vec![],
+            binary: vec![(BinaryOpcode::Add, 1)],
+            axes: [false, true, false],
+            vars: vec!["a".into()],
+            constants: None,
+        };
+        for seed in 0..16 {
+            let mut ctx = Context::new();
+            let n =
+                random_expr(&mut ctx, &mut Rng::new(seed), &settings).unwrap();
+            let mut todo = vec![n];
+            while let Some(n) = todo.pop() {
+                let op = ctx.get_op(n).unwrap().clone();
+                match op {
+                    // `a + a` is built as `a * 2`
+                    Op::Binary(BinaryOpcode::Add | BinaryOpcode::Mul, ..) => (),
+                    Op::Const(c) => assert_eq!(c.0, 2.0),
+                    Op::Input(..) => assert_eq!(n, ctx.y()),
+                    Op::Var(..) => {
+                        assert_eq!(ctx.var_name(n).unwrap(), Some("a"))
+                    }
+                    op => panic!("unexpected op {op:?}"),
+                }
+                todo.extend(op.iter_children());
+            }
+        }
+
+        let settings = Settings {
+            axes: [false; 3],
+            constants: None,
+            ..Default::default()
+        };
+        let mut ctx = Context::new();
+        assert!(matches!(
+            random_expr(&mut ctx, &mut Rng::new(0), &settings),
+            Err(Error::EmptyArguments)
+        ));
+    }
+}
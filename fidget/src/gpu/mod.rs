@@ -0,0 +1,776 @@
+//! GPU evaluation using compute shaders
+//!
+//! Tapes are encoded into a compact bytecode, which is interpreted by a WGSL
+//! compute shader; each shader invocation evaluates a single point.  Because
+//! the shader is an interpreter, it only needs to be compiled once, and
+//! simplified tapes can be evaluated without recompiling anything.
+//!
+//! Tracing evaluation (interval arithmetic and tape simplification) still
+//! runs on the CPU, using any evaluator [`Family`].  In
+//! [`Evaluator::render2d`], it is used to fill empty and full regions of the
+//! image without touching the GPU; the remaining pixels are evaluated on the
+//! GPU in a single batch, using per-tile simplified tapes.
+//!
+//! ```no_run
+//! use fidget::{context::Context, gpu, vm};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let sum = ctx.add(x, y)?;
+//! let tape = ctx.get_tape::<vm::Eval>(sum)?;
+//!
+//! let gpu = gpu::Evaluator::new()?;
+//! let out = gpu.eval(&tape, &[1.0, 2.0], &[3.0, 4.0], &[0.0, 0.0], &[])?;
+//! assert_eq!(out, [4.0, 6.0]);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{
+        interval::IntervalEval, tape::Data as TapeData, types::Interval,
+        Family, SignConvention, Tape,
+    },
+    render::{config::AlignedRenderConfig, RenderConfig, RenderMode},
+    vm::Op,
+    Error,
+};
+use nalgebra::{Point2, Vector2};
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+
+/// Number of invocations in a single workgroup; must match the shader
+const WORKGROUP_SIZE: usize = 64;
+
+/// Maximum number of points to evaluate in a single dispatch
+const MAX_POINTS: usize = 1 << 20;
+
+/// Opcodes understood by the shader
+///
+/// Each operation is encoded as two words: the first packs the opcode and
+/// output / LHS / RHS registers as bytes (from least to most significant),
+/// and the second holds an immediate, a variable index, a memory offset, or
+/// the third argument register of a multiply-add or clamp.
+#[derive(Copy, Clone)]
+enum Opcode {
+    Input = 0,
+    Var,
+    CopyImm,
+    Load,
+    Store,
+    NegReg,
+    AbsReg,
+    RecipReg,
+    SqrtReg,
+    SquareReg,
+    CopyReg,
+    AddRegImm,
+    MulRegImm,
+    DivRegImm,
+    DivImmReg,
+    SubImmReg,
+    SubRegImm,
+    MinRegImm,
+    MaxRegImm,
+    AddRegReg,
+    MulRegReg,
+    DivRegReg,
+    SubRegReg,
+    MinRegReg,
+    MaxRegReg,
+    FmaRegRegReg,
+    Atan2RegImm,
+    Atan2ImmReg,
+    HypotRegImm,
+    Atan2RegReg,
+    HypotRegReg,
+    NoiseRegRegReg,
+    SinReg,
+    CosReg,
+    ClampRegRegReg,
+    ModRegImm,
+    ModImmReg,
+    ModRegReg,
+}
+
+/// Packs an opcode and its registers into a single word
+fn pack(op: Opcode, out: u8, lhs: u8, rhs: u8) -> u32 {
+    op as u32
+        | ((out as u32) << 8)
+        | ((lhs as u32) << 16)
+        | ((rhs as u32) << 24)
+}
+
+/// Encodes a single operation as a pair of words
+///
+/// Memory slots are numbered starting at `reg_limit` in the VM tape; they are
+/// shifted to start at 0 here, since they're stored separately.
+fn encode(op: Op, reg_limit: u32) -> [u32; 2] {
+    match op {
+        Op::Input(out, i) => [pack(Opcode::Input, out, i, 0), 0],
+        Op::Var(out, i) => [pack(Opcode::Var, out, 0, 0), i],
+        Op::CopyImm(out, imm) => {
+            [pack(Opcode::CopyImm, out, 0, 0), imm.to_bits()]
+        }
+        Op::Load(out, mem) => [pack(Opcode::Load, out, 0, 0), mem - reg_limit],
+        Op::Store(out, mem) => {
+            [pack(Opcode::Store, out, 0, 0), mem - reg_limit]
+        }
+        Op::NegReg(out, arg) => [pack(Opcode::NegReg, out, arg, 0), 0],
+        Op::AbsReg(out, arg) => [pack(Opcode::AbsReg, out, arg, 0), 0],
+        Op::RecipReg(out, arg) => [pack(Opcode::RecipReg, out, arg, 0), 0],
+        Op::SqrtReg(out, arg) => [pack(Opcode::SqrtReg, out, arg, 0), 0],
+        Op::SquareReg(out, arg) => [pack(Opcode::SquareReg, out, arg, 0), 0],
+        Op::CopyReg(out, arg) => [pack(Opcode::CopyReg, out, arg, 0), 0],
+        Op::SinReg(out, arg) => [pack(Opcode::SinReg, out, arg, 0), 0],
+        Op::CosReg(out, arg) => [pack(Opcode::CosReg, out, arg, 0), 0],
+        Op::AddRegImm(out, arg, imm) => {
+            [pack(Opcode::AddRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::MulRegImm(out, arg, imm) => {
+            [pack(Opcode::MulRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::DivRegImm(out, arg, imm) => {
+            [pack(Opcode::DivRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::DivImmReg(out, arg, imm) => {
+            [pack(Opcode::DivImmReg, out, arg, 0), imm.to_bits()]
+        }
+        Op::SubImmReg(out, arg, imm) => {
+            [pack(Opcode::SubImmReg, out, arg, 0), imm.to_bits()]
+        }
+        Op::SubRegImm(out, arg, imm) => {
+            [pack(Opcode::SubRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::MinRegImm(out, arg, imm) | Op::MinNcRegImm(out, arg, imm) => {
+            [pack(Opcode::MinRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::MaxRegImm(out, arg, imm) | Op::MaxNcRegImm(out, arg, imm) => {
+            [pack(Opcode::MaxRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::AddRegReg(out, lhs, rhs) => {
+            [pack(Opcode::AddRegReg, out, lhs, rhs), 0]
+        }
+        Op::MulRegReg(out, lhs, rhs) => {
+            [pack(Opcode::MulRegReg, out, lhs, rhs), 0]
+        }
+        Op::DivRegReg(out, lhs, rhs) => {
+            [pack(Opcode::DivRegReg, out, lhs, rhs), 0]
+        }
+        Op::SubRegReg(out, lhs, rhs) => {
+            [pack(Opcode::SubRegReg, out, lhs, rhs), 0]
+        }
+        Op::MinRegReg(out, lhs, rhs) | Op::MinNcRegReg(out, lhs, rhs) => {
+            [pack(Opcode::MinRegReg, out, lhs, rhs), 0]
+        }
+        Op::MaxRegReg(out, lhs, rhs) | Op::MaxNcRegReg(out, lhs, rhs) => {
+            [pack(Opcode::MaxRegReg, out, lhs, rhs), 0]
+        }
+        Op::FmaRegRegReg(out, a, b, c) => {
+            [pack(Opcode::FmaRegRegReg, out, a, b), c as u32]
+        }
+        Op::ClampRegRegReg(out, x, lo, hi) => {
+            [pack(Opcode::ClampRegRegReg, out, x, lo), hi as u32]
+        }
+        Op::CustomRegReg(..) => unreachable!("checked in push_tape"),
+        Op::Atan2RegImm(out, arg, imm) => {
+            [pack(Opcode::Atan2RegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::Atan2ImmReg(out, arg, imm) => {
+            [pack(Opcode::Atan2ImmReg, out, arg, 0), imm.to_bits()]
+        }
+        Op::HypotRegImm(out, arg, imm) => {
+            [pack(Opcode::HypotRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::Atan2RegReg(out, lhs, rhs) => {
+            [pack(Opcode::Atan2RegReg, out, lhs, rhs), 0]
+        }
+        Op::HypotRegReg(out, lhs, rhs) => {
+            [pack(Opcode::HypotRegReg, out, lhs, rhs), 0]
+        }
+        Op::ModRegImm(out, arg, imm) => {
+            [pack(Opcode::ModRegImm, out, arg, 0), imm.to_bits()]
+        }
+        Op::ModImmReg(out, arg, imm) => {
+            [pack(Opcode::ModImmReg, out, arg, 0), imm.to_bits()]
+        }
+        Op::ModRegReg(out, lhs, rhs) => {
+            [pack(Opcode::ModRegReg, out, lhs, rhs), 0]
+        }
+        Op::NoiseRegRegReg(out, x, y, z, seed) => [
+            pack(Opcode::NoiseRegRegReg, out, x, y),
+            z as u32 | (seed as u32) << 16,
+        ],
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A set of encoded tapes and points to be evaluated in a single batch
+#[derive(Default)]
+struct Batch {
+    /// Encoded tapes, each of which is its length followed by operations
+    tapes: Vec<u32>,
+
+    /// Points to evaluate, as `[x, y, z, tape offset]`
+    points: Vec<[u32; 4]>,
+
+    /// Number of memory slots required by the largest tape
+    scratch: u32,
+}
+
+impl Batch {
+    /// Encodes the given tape, returning its offset
+    ///
+    /// Returns [`Error::CustomOpOnGpu`] if the tape contains a custom
+    /// operation, which can only be evaluated on the CPU.
+    fn push_tape(&mut self, tape: &TapeData) -> Result<u32, Error> {
+        if tape.iter_asm().any(|op| matches!(op, Op::CustomRegReg(..))) {
+            return Err(Error::CustomOpOnGpu);
+        }
+        let offset = self.tapes.len() as u32;
+        let reg_limit = tape.reg_limit() as u32;
+        self.scratch = self
+            .scratch
+            .max((tape.slot_count() as u32).saturating_sub(reg_limit));
+
+        // Positive-inside tapes get an extra negation of the output register
+        let negate = tape.sign_convention() == SignConvention::PositiveInside;
+        self.tapes.push(tape.len() as u32 + negate as u32);
+        for op in tape.iter_asm() {
+            self.tapes.extend(encode(op, reg_limit));
+        }
+        if negate {
+            self.tapes.extend(encode(Op::NegReg(0, 0), reg_limit));
+        }
+        Ok(offset)
+    }
+
+    /// Adds a point to be evaluated with the tape at the given offset
+    fn push_point(&mut self, x: f32, y: f32, z: f32, tape: u32) {
+        self.points
+            .push([x.to_bits(), y.to_bits(), z.to_bits(), tape]);
+    }
+}
+
+/// Converts a slice of words into bytes for upload
+fn bytes<T: Copy, const N: usize>(data: &[T], f: fn(T) -> [u8; N]) -> Vec<u8> {
+    if data.is_empty() {
+        // Bindings must not be empty, so upload a single dummy value
+        return vec![0; N];
+    }
+    data.iter().flat_map(|v| f(*v)).collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Handle to a GPU device, ready to evaluate tapes
+pub struct Evaluator {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl Evaluator {
+    /// Opens the default GPU and compiles the evaluation shader
+    ///
+    /// Returns [`Error::NoGpuAdapter`] if no suitable GPU is found.
+    pub fn new() -> Result<Self, Error> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            },
+        ))
+        .ok_or(Error::NoGpuAdapter)?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("fidget"),
+                features: wgpu::Features::empty(),
+                limits: adapter.limits(),
+            },
+            None,
+        ))?;
+
+        let module =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("fidget interpreter"),
+                source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+            });
+        let pipeline =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("fidget interpreter"),
+                layout: None,
+                module: &module,
+                entry_point: "main",
+            });
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+        })
+    }
+
+    /// Evaluates a tape at a set of points
+    ///
+    /// Returns [`Error::MismatchedSlices`] if `x`, `y`, and `z` are not all
+    /// the same length, [`Error::BadVarSlice`] if `vars` doesn't match the
+    /// tape's variable count, or [`Error::CustomOpOnGpu`] if the tape uses a
+    /// custom operation.
+    pub fn eval<F: Family>(
+        &self,
+        tape: &Tape<F>,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+    ) -> Result<Vec<f32>, Error> {
+        if x.len() != y.len() || x.len() != z.len() {
+            return Err(Error::MismatchedSlices);
+        }
+        if vars.len() != tape.var_count() {
+            return Err(Error::BadVarSlice(vars.len(), tape.var_count()));
+        }
+        let mut batch = Batch::default();
+        let offset = batch.push_tape(tape)?;
+        for i in 0..x.len() {
+            batch.push_point(x[i], y[i], z[i], offset);
+        }
+        self.run(&batch, vars)
+    }
+
+    /// Renders the given tape into a 2D image at Z = 0
+    ///
+    /// This is equivalent to [`fidget::render::render2d`](crate::render::render2d()),
+    /// but uses the GPU for per-pixel evaluation.  Interval evaluation and
+    /// tape simplification are performed on the CPU with the tape's family.
+    pub fn render2d<I: Family, M: RenderMode>(
+        &self,
+        tape: Tape<I>,
+        config: &RenderConfig<2>,
+        mode: &M,
+    ) -> Result<Vec<M::Output>, Error> {
+        let config = config.align();
+        let mut r = Renderer {
+            config: &config,
+            mode,
+            image: vec![M::Output::default(); config.image_size.pow(2)],
+            batch: Batch::default(),
+            pixels: vec![],
+            offsets: HashMap::new(),
+            tapes: vec![],
+        };
+
+        let eval = tape.new_interval_evaluator();
+        let tile_size = config.tile_sizes[0];
+        for j in 0..config.image_size / tile_size {
+            for i in 0..config.image_size / tile_size {
+                r.render_tile(&eval, 0, [i * tile_size, j * tile_size])?;
+            }
+        }
+
+        let out = self.run(&r.batch, &[])?;
+        for (p, v) in r.pixels.iter().zip(out) {
+            r.image[*p] = mode.pixel(v);
+        }
+
+        // Crop and flip the image, to match the CPU renderer
+        let size = config.orig_image_size;
+        let mut image = vec![M::Output::default(); size.pow(2)];
+        for y in 0..size {
+            for x in 0..size {
+                image[(size - y - 1) * size + x] =
+                    r.image[x + y * config.image_size];
+            }
+        }
+        Ok(image)
+    }
+
+    /// Evaluates every point in the batch, in chunks of at most `MAX_POINTS`
+    fn run(&self, batch: &Batch, vars: &[f32]) -> Result<Vec<f32>, Error> {
+        let max_binding =
+            self.device.limits().max_storage_buffer_binding_size as usize;
+        let chunk_size = (max_binding / (batch.scratch.max(1) as usize * 4))
+            .clamp(WORKGROUP_SIZE, MAX_POINTS);
+
+        let storage = |label, contents: &[u8]| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: wgpu::BufferUsages::STORAGE,
+                })
+        };
+        let tapes = storage("tapes", &bytes(&batch.tapes, u32::to_le_bytes));
+        let vars = storage("vars", &bytes(vars, f32::to_le_bytes));
+
+        let mut out = Vec::with_capacity(batch.points.len());
+        for points in batch.points.chunks(chunk_size) {
+            let n = points.len();
+            let points = storage("points", &bytes(points, point_bytes));
+            let config = self.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("config"),
+                    contents: &bytes(
+                        &[n as u32, batch.scratch, 0, 0],
+                        u32::to_le_bytes,
+                    ),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                },
+            );
+            let scratch = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("scratch"),
+                size: (n * batch.scratch.max(1) as usize * 4) as u64,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            });
+            let size = (n * 4) as u64;
+            let result = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("out"),
+                size,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("readback"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let bind_group =
+                self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &self.pipeline.get_bind_group_layout(0),
+                    entries: &[
+                        &tapes, &points, &vars, &scratch, &result, &config,
+                    ]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| wgpu::BindGroupEntry {
+                        binding: i as u32,
+                        resource: b.as_entire_binding(),
+                    })
+                    .collect::<Vec<_>>(),
+                });
+
+            let mut encoder = self.device.create_command_encoder(
+                &wgpu::CommandEncoderDescriptor { label: None },
+            );
+            {
+                let mut pass =
+                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: None,
+                    });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                let groups = n.div_ceil(WORKGROUP_SIZE);
+                pass.dispatch_workgroups(groups as u32, 1, 1);
+            }
+            encoder.copy_buffer_to_buffer(&result, 0, &readback, 0, size);
+            self.queue.submit(Some(encoder.finish()));
+
+            let slice = readback.slice(..);
+            let (tx, rx) = std::sync::mpsc::channel();
+            slice.map_async(wgpu::MapMode::Read, move |r| {
+                let _ = tx.send(r);
+            });
+            self.device.poll(wgpu::Maintain::Wait);
+            rx.recv().expect("map_async callback was dropped")?;
+
+            out.extend(
+                slice
+                    .get_mapped_range()
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap())),
+            );
+            readback.unmap();
+        }
+        Ok(out)
+    }
+}
+
+/// Converts a packed point into bytes
+fn point_bytes(p: [u32; 4]) -> [u8; 16] {
+    let mut out = [0; 16];
+    for (o, v) in out.chunks_exact_mut(4).zip(p) {
+        o.copy_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+/// Source code for the interpreter shader
+const SHADER: &str = include_str!("shader.wgsl");
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// CPU-side state for [`Evaluator::render2d`]
+struct Renderer<'a, I, M: RenderMode> {
+    config: &'a AlignedRenderConfig<2>,
+    mode: &'a M,
+
+    /// Aligned image, in row-major order with Y increasing
+    image: Vec<M::Output>,
+
+    /// Batch of points to evaluate on the GPU
+    batch: Batch,
+    /// Image index for each point in the batch
+    pixels: Vec<usize>,
+
+    /// Offsets of tapes in the batch, keyed by address
+    offsets: HashMap<*const TapeData, u32>,
+    /// Tapes in the batch, which are kept alive so that their addresses
+    /// can't be reused by a different tape
+    tapes: Vec<Tape<I>>,
+}
+
+impl<I: Family, M: RenderMode> Renderer<'_, I, M> {
+    fn render_tile(
+        &mut self,
+        eval: &IntervalEval<I>,
+        depth: usize,
+        corner: [usize; 2],
+    ) -> Result<(), Error> {
+        let tile_size = self.config.tile_sizes[depth];
+
+        // Find the (interval) bounding box of the region
+        let mut x_min = f32::INFINITY;
+        let mut x_max = f32::NEG_INFINITY;
+        let mut y_min = f32::INFINITY;
+        let mut y_max = f32::NEG_INFINITY;
+        let base = Point2::from(corner);
+        for i in 0..4 {
+            let offset = Vector2::new(
+                if (i & 1) == 0 { 0 } else { tile_size },
+                if (i & 2) == 0 { 0 } else { tile_size },
+            );
+            let p = (base + offset).cast::<f32>();
+            let p = self.config.mat.transform_point(&p);
+            x_min = x_min.min(p.x);
+            x_max = x_max.max(p.x);
+            y_min = y_min.min(p.y);
+            y_max = y_max.max(p.y);
+        }
+        let x = Interval::new(x_min, x_max);
+        let y = Interval::new(y_min, y_max);
+        let z = Interval::new(0.0, 0.0);
+
+        let fill = if !eval.tape().bounds().intersects(x, y, z) {
+            let outside = Interval::new(f32::MIN_POSITIVE, f32::INFINITY);
+            self.mode.interval(outside, depth)
+        } else {
+            None
+        };
+        let (fill, simplify) = match fill {
+            Some(fill) => (Some(fill), None),
+            None => {
+                let (i, simplify) = eval.eval(x, y, z, &[])?;
+                (self.mode.interval(i, depth), simplify)
+            }
+        };
+        if let Some(fill) = fill {
+            for j in 0..tile_size {
+                let start =
+                    corner[0] + (corner[1] + j) * self.config.image_size;
+                self.image[start..][..tile_size].fill(fill);
+            }
+            return Ok(());
+        }
+
+        let tape = match simplify {
+            Some(s) => s.simplify()?,
+            None => eval.tape(),
+        };
+        if let Some(next) = self.config.tile_sizes.get(depth + 1) {
+            let eval = tape.new_interval_evaluator();
+            let n = tile_size / next;
+            for j in 0..n {
+                for i in 0..n {
+                    self.render_tile(
+                        &eval,
+                        depth + 1,
+                        [corner[0] + i * next, corner[1] + j * next],
+                    )?;
+                }
+            }
+        } else {
+            let key = &*tape as *const TapeData;
+            let offset = match self.offsets.get(&key) {
+                Some(offset) => *offset,
+                None => {
+                    let offset = self.batch.push_tape(&tape)?;
+                    self.offsets.insert(key, offset);
+                    self.tapes.push(tape);
+                    offset
+                }
+            };
+            for j in 0..tile_size {
+                for i in 0..tile_size {
+                    let (px, py) = (corner[0] + i, corner[1] + j);
+                    let p = self
+                        .config
+                        .mat
+                        .transform_point(&Point2::new(px as f32, py as f32));
+                    self.batch.push_point(p.x, p.y, 0.0, offset);
+                    self.pixels.push(px + py * self.config.image_size);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, render::BitRenderMode, vm};
+
+    #[test]
+    fn test_shader_is_valid() {
+        let module = naga::front::wgsl::parse_str(SHADER).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let s = ctx.sub(x, y).unwrap();
+        let s = ctx.min(s, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(s).unwrap();
+
+        let mut batch = Batch::default();
+        assert_eq!(batch.push_tape(&tape).unwrap(), 0);
+        assert_eq!(batch.tapes[0] as usize, tape.len());
+        assert_eq!(batch.tapes.len(), 1 + tape.len() * 2);
+        assert_eq!(batch.scratch, 0);
+        assert_eq!(
+            batch.push_tape(&tape).unwrap() as usize,
+            1 + tape.len() * 2
+        );
+
+        let last = batch.tapes[tape.len() * 2 - 1];
+        assert_eq!(last & 0xFF, Opcode::MinRegImm as u32);
+        assert_eq!(batch.tapes[tape.len() * 2], 0.5f32.to_bits());
+    }
+
+    #[test]
+    fn test_encode_custom() {
+        use crate::eval::custom::eval_tests::SquareSub;
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let s = ctx.custom(std::sync::Arc::new(SquareSub), x, 1.0).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(s).unwrap();
+
+        let mut batch = Batch::default();
+        assert!(matches!(batch.push_tape(&tape), Err(Error::CustomOpOnGpu)));
+        assert!(batch.tapes.is_empty());
+    }
+
+    /// Opens the GPU, returning `None` (and skipping the test) if unavailable
+    fn gpu() -> Option<Evaluator> {
+        match Evaluator::new() {
+            Ok(e) => Some(e),
+            Err(e) => {
+                eprintln!("skipping GPU test: {e}");
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_gpu_eval() {
+        let Some(gpu) = gpu() else { return };
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.var("a").unwrap();
+        let s = ctx.mul(x, y).unwrap();
+        let s = ctx.max(s, a).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(s).unwrap();
+
+        let out = gpu
+            .eval(&tape, &[1.0, 2.0, 3.0], &[2.0, 3.0, 4.0], &[0.0; 3], &[5.0])
+            .unwrap();
+        assert_eq!(out, [5.0, 6.0, 12.0]);
+
+        assert!(matches!(
+            gpu.eval(&tape, &[1.0], &[], &[], &[5.0]),
+            Err(Error::MismatchedSlices)
+        ));
+    }
+
+    #[test]
+    fn test_gpu_mod() {
+        let Some(gpu) = gpu() else { return };
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.modulo(x, 2.0).unwrap();
+        let b = ctx.modulo(3.0, y).unwrap();
+        let s = ctx.add(a, b).unwrap();
+        let s = ctx.modulo(s, y).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(s).unwrap();
+
+        let xs = [-0.5, 2.5, 7.0];
+        let ys = [2.0, -4.0, 10.0];
+        let out = gpu.eval(&tape, &xs, &ys, &[0.0; 3], &[]).unwrap();
+        for i in 0..3 {
+            let v = ctx.eval_xyz(s, xs[i] as f64, ys[i] as f64, 0.0).unwrap();
+            assert_eq!(out[i], v as f32);
+        }
+    }
+
+    #[test]
+    fn test_gpu_noise() {
+        let Some(gpu) = gpu() else { return };
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let n = ctx.noise3(x, y, z, 5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(n).unwrap();
+
+        let xs = [0.3, -1.7, 4.25];
+        let ys = [1.1, 0.6, -2.9];
+        let zs = [-0.4, 3.3, 0.0];
+        let out = gpu.eval(&tape, &xs, &ys, &zs, &[]).unwrap();
+        for i in 0..3 {
+            let v = crate::eval::noise::noise3(xs[i], ys[i], zs[i], 5);
+            assert!((out[i] - v).abs() < 1e-5, "{} != {v}", out[i]);
+        }
+    }
+
+    #[test]
+    fn test_gpu_render2d() {
+        let Some(gpu) = gpu() else { return };
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let r = ctx.add(x2, y2).unwrap();
+        let r = ctx.sqrt(r).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![64, 16],
+            ..Default::default()
+        };
+        let cpu =
+            crate::render::render2d(tape.clone(), &config, &BitRenderMode);
+        let out = gpu.render2d(tape, &config, &BitRenderMode).unwrap();
+        assert_eq!(cpu, out);
+    }
+}
@@ -0,0 +1,210 @@
+// Interpreter for register-allocated tapes
+//
+// Opcodes and their encoding must match `Opcode` in `gpu/mod.rs`.
+//
+// Each invocation evaluates one point, using the tape at the point's offset
+// into the `tapes` array.  Registers are stored in a private array; memory
+// slots (for tapes which spill) live in the `scratch` buffer.
+
+struct Point {
+    pos: vec3<f32>,
+    tape: u32,
+}
+
+struct Config {
+    count: u32,
+    scratch: u32,
+}
+
+@group(0) @binding(0) var<storage, read> tapes: array<u32>;
+@group(0) @binding(1) var<storage, read> points: array<Point>;
+@group(0) @binding(2) var<storage, read> vars: array<f32>;
+@group(0) @binding(3) var<storage, read_write> scratch: array<f32>;
+@group(0) @binding(4) var<storage, read_write> out: array<f32>;
+@group(0) @binding(5) var<uniform> config: Config;
+
+// Gradient noise, matching `eval::noise` (see that module for details)
+fn noise_hash(i: vec3<i32>, seed: u32) -> u32 {
+    var h = (bitcast<u32>(i.x) * 0x8da6b343u)
+        ^ (bitcast<u32>(i.y) * 0xd8163841u)
+        ^ (bitcast<u32>(i.z) * 0xcb1ab31fu)
+        ^ (seed * 0x9e3779b9u);
+    h = h ^ (h >> 16u);
+    h = h * 0x7feb352du;
+    h = h ^ (h >> 15u);
+    h = h * 0x846ca68bu;
+    return h ^ (h >> 16u);
+}
+
+// Dot product of `d` with the gradient selected by the low bits of `h`
+//
+// This is equivalent to the `GRADIENTS` table on the CPU side.
+fn noise_grad(h: u32, d: vec3<f32>) -> f32 {
+    let u = select(d.y, d.x, h < 8u);
+    let v = select(select(d.z, d.x, h == 12u || h == 14u), d.y, h < 4u);
+    return select(-u, u, (h & 1u) == 0u) + select(-v, v, (h & 2u) == 0u);
+}
+
+fn noise3(p: vec3<f32>, seed: u32) -> f32 {
+    let cell = floor(p);
+    let i = vec3<i32>(cell);
+    let f = p - cell;
+    let s = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+
+    var sum = 0.0;
+    for (var corner = 0u; corner < 8u; corner = corner + 1u) {
+        let c = vec3<u32>(corner & 1u, (corner >> 1u) & 1u, corner >> 2u);
+        let h = noise_hash(i + vec3<i32>(c), seed) & 15u;
+        let d = f - vec3<f32>(c);
+        let w = select(vec3<f32>(1.0) - s, s, c == vec3<u32>(1u));
+        sum = sum + w.x * w.y * w.z * noise_grad(h, d);
+    }
+    return sum * (2.0 / 3.0);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= config.count) {
+        return;
+    }
+    let p = points[index];
+    let base = index * config.scratch;
+
+    var regs: array<f32, 256>;
+    var pc = p.tape + 1u;
+    let end = pc + tapes[p.tape] * 2u;
+    loop {
+        if (pc >= end) {
+            break;
+        }
+        let word = tapes[pc];
+        let arg = tapes[pc + 1u];
+        let imm = bitcast<f32>(arg);
+        pc += 2u;
+
+        let op = word & 0xFFu;
+        let o = (word >> 8u) & 0xFFu;
+        let a = (word >> 16u) & 0xFFu;
+        let b = word >> 24u;
+        switch op {
+            case 0u: { // Input
+                regs[o] = p.pos[a];
+            }
+            case 1u: { // Var
+                regs[o] = vars[arg];
+            }
+            case 2u: { // CopyImm
+                regs[o] = imm;
+            }
+            case 3u: { // Load
+                regs[o] = scratch[base + arg];
+            }
+            case 4u: { // Store
+                scratch[base + arg] = regs[o];
+            }
+            case 5u: { // NegReg
+                regs[o] = -regs[a];
+            }
+            case 6u: { // AbsReg
+                regs[o] = abs(regs[a]);
+            }
+            case 7u: { // RecipReg
+                regs[o] = 1.0 / regs[a];
+            }
+            case 8u: { // SqrtReg
+                regs[o] = sqrt(regs[a]);
+            }
+            case 9u: { // SquareReg
+                regs[o] = regs[a] * regs[a];
+            }
+            case 10u: { // CopyReg
+                regs[o] = regs[a];
+            }
+            case 11u: { // AddRegImm
+                regs[o] = regs[a] + imm;
+            }
+            case 12u: { // MulRegImm
+                regs[o] = regs[a] * imm;
+            }
+            case 13u: { // DivRegImm
+                regs[o] = regs[a] / imm;
+            }
+            case 14u: { // DivImmReg
+                regs[o] = imm / regs[a];
+            }
+            case 15u: { // SubImmReg
+                regs[o] = imm - regs[a];
+            }
+            case 16u: { // SubRegImm
+                regs[o] = regs[a] - imm;
+            }
+            case 17u: { // MinRegImm
+                regs[o] = min(regs[a], imm);
+            }
+            case 18u: { // MaxRegImm
+                regs[o] = max(regs[a], imm);
+            }
+            case 19u: { // AddRegReg
+                regs[o] = regs[a] + regs[b];
+            }
+            case 20u: { // MulRegReg
+                regs[o] = regs[a] * regs[b];
+            }
+            case 21u: { // DivRegReg
+                regs[o] = regs[a] / regs[b];
+            }
+            case 22u: { // SubRegReg
+                regs[o] = regs[a] - regs[b];
+            }
+            case 23u: { // MinRegReg
+                regs[o] = min(regs[a], regs[b]);
+            }
+            case 24u: { // MaxRegReg
+                regs[o] = max(regs[a], regs[b]);
+            }
+            case 25u: { // FmaRegRegReg, with the addend's register in `arg`
+                regs[o] = fma(regs[a], regs[b], regs[arg]);
+            }
+            case 26u: { // Atan2RegImm
+                regs[o] = atan2(regs[a], imm);
+            }
+            case 27u: { // Atan2ImmReg
+                regs[o] = atan2(imm, regs[a]);
+            }
+            case 28u: { // HypotRegImm
+                regs[o] = length(vec2<f32>(regs[a], imm));
+            }
+            case 29u: { // Atan2RegReg
+                regs[o] = atan2(regs[a], regs[b]);
+            }
+            case 30u: { // HypotRegReg
+                regs[o] = length(vec2<f32>(regs[a], regs[b]));
+            }
+            case 31u: { // NoiseRegRegReg, with the `z` register and seed in `arg`
+                let z = regs[arg & 0xFFu];
+                regs[o] = noise3(vec3<f32>(regs[a], regs[b], z), arg >> 16u);
+            }
+            case 32u: { // SinReg
+                regs[o] = sin(regs[a]);
+            }
+            case 33u: { // CosReg
+                regs[o] = cos(regs[a]);
+            }
+            case 34u: { // ClampRegRegReg, with the `hi` register in `arg`
+                regs[o] = max(min(regs[a], regs[arg]), regs[b]);
+            }
+            case 35u: { // ModRegImm
+                regs[o] = regs[a] - imm * floor(regs[a] / imm);
+            }
+            case 36u: { // ModImmReg
+                regs[o] = imm - regs[a] * floor(imm / regs[a]);
+            }
+            case 37u: { // ModRegReg
+                regs[o] = regs[a] - regs[b] * floor(regs[a] / regs[b]);
+            }
+            default: {}
+        }
+    }
+    out[index] = regs[0];
+}
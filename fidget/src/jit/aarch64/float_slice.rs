@@ -1,6 +1,6 @@
 use crate::jit::{
-    float_slice::FloatSliceAssembler, mmap::Mmap, reg, AssemblerData,
-    AssemblerT, Error, IMM_REG, OFFSET, REGISTER_LIMIT,
+    call, float_slice::FloatSliceAssembler, fused::FusedAssembler, mmap::Mmap,
+    reg, AssemblerData, AssemblerT, Error, IMM_REG, OFFSET, REGISTER_LIMIT,
 };
 use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
 
@@ -158,6 +158,78 @@ impl AssemblerT for FloatSliceAssembler {
             ; fmul V(reg(out_reg)).s4, V(reg(lhs_reg)).s4, V(reg(rhs_reg)).s4
         )
     }
+    fn build_fma(
+        &mut self,
+        out_reg: u8,
+        a_reg: u8,
+        b_reg: u8,
+        c_reg: u8,
+        _widen: bool,
+    ) {
+        if out_reg == c_reg {
+            dynasm!(self.0.ops
+                ; fmla V(reg(out_reg)).s4, V(reg(a_reg)).s4, V(reg(b_reg)).s4
+            )
+        } else {
+            dynasm!(self.0.ops
+                ; mov v4.b16, V(reg(c_reg)).b16
+                ; fmla v4.s4, V(reg(a_reg)).s4, V(reg(b_reg)).s4
+                ; mov V(reg(out_reg)).b16, v4.b16
+            )
+        }
+    }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            ; fdiv v4.s4, V(reg(lhs_reg)).s4, V(reg(rhs_reg)).s4
+            ; frintm v4.s4, v4.s4
+            ; fmul v4.s4, v4.s4, V(reg(rhs_reg)).s4
+            ; fsub V(reg(out_reg)).s4, V(reg(lhs_reg)).s4, v4.s4
+        )
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<[f32; SIMD_WIDTH]>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<[f32; SIMD_WIDTH]>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        // Slice evaluators don't make choices, so this can reuse `min` and
+        // `max`, with the immediate register as a temporary
+        let tmp = IMM_REG.wrapping_sub(OFFSET);
+        self.build_min(tmp, x_reg, hi_reg);
+        self.build_max(out_reg, tmp, lo_reg);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; fdiv V(reg(out_reg)).s4, V(reg(lhs_reg)).s4, V(reg(rhs_reg)).s4
@@ -174,6 +246,28 @@ impl AssemblerT for FloatSliceAssembler {
         )
     }
 
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        // This evaluator doesn't record choices, so `max` is already suitable
+        self.build_max(out_reg, lhs_reg, rhs_reg)
+    }
+
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        self.build_min(out_reg, lhs_reg, rhs_reg)
+    }
+
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        let inf = f32::INFINITY.to_bits();
+        dynasm!(self.0.ops
+            ; movz w9, #(inf >> 16), lsl 16
+            ; dup v4.s4, w9
+            // fminnm ignores a NaN argument, so this replaces NaN with +inf
+            // and leaves other values unchanged
+            ; fminnm v5.s4, V(reg(lhs_reg)).s4, v4.s4
+            ; fminnm v6.s4, V(reg(rhs_reg)).s4, v4.s4
+        );
+        (5u8.wrapping_sub(OFFSET), 6u8.wrapping_sub(OFFSET))
+    }
+
     /// Loads an immediate into register V4, using W9 as an intermediary
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
@@ -198,3 +292,23 @@ impl AssemblerT for FloatSliceAssembler {
         self.0.ops.finalize()
     }
 }
+
+impl FusedAssembler for FloatSliceAssembler {
+    fn build_output(&mut self, out_reg: u8, index: usize) {
+        assert!(index < 4096);
+        dynasm!(self.0.ops
+            ; str Q(reg(out_reg)), [x4, #(index as u32 * 16)]
+        );
+    }
+    fn finalize_fused(mut self, count: usize) -> Result<Mmap, Error> {
+        assert!(count * 16 < 4096);
+        dynasm!(self.0.ops
+            // Skip over every output (x5 was already decremented in the loop
+            // header)
+            ; add x4, x4, #(count as u32 * 16)
+            ; b ->L
+        );
+
+        self.0.ops.finalize()
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     eval::types::Grad,
     jit::{
-        grad_slice::GradSliceAssembler, mmap::Mmap, reg, AssemblerData,
+        call, grad_slice::GradSliceAssembler, mmap::Mmap, reg, AssemblerData,
         AssemblerT, IMM_REG, OFFSET, REGISTER_LIMIT,
     },
     Error,
@@ -213,6 +213,54 @@ impl AssemblerT for GradSliceAssembler {
         )
     }
 
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Grad, call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Grad, call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Grad, call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Grad, call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Grad, call::Mod>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<Grad>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<Grad>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        // Slice evaluators don't make choices, so this can reuse `min` and
+        // `max`, with the immediate register as a temporary
+        let tmp = IMM_REG.wrapping_sub(OFFSET);
+        self.build_min(tmp, x_reg, hi_reg);
+        self.build_max(out_reg, tmp, lo_reg);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; fmov w9, S(reg(rhs_reg))
@@ -242,8 +290,10 @@ impl AssemblerT for GradSliceAssembler {
     fn build_max(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; fcmp S(reg(lhs_reg)), S(reg(rhs_reg))
-            ; b.gt #12 // -> lhs
-            // Happy path: v >= 0, so we just copy the register
+            ; b.gt #20 // -> lhs (false if either is NaN)
+            // If lhs is NaN, then return it
+            ; fcmp S(reg(lhs_reg)), S(reg(lhs_reg))
+            ; b.vs #12 // -> lhs
             ; mov V(reg(out_reg)).b16, V(reg(rhs_reg)).b16
             ; b #8 // -> end
             // lhs:
@@ -254,8 +304,10 @@ impl AssemblerT for GradSliceAssembler {
     fn build_min(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; fcmp S(reg(lhs_reg)), S(reg(rhs_reg))
-            ; b.lt #12 // -> lhs
-            // Happy path: v >= 0, so we just copy the register
+            ; b.mi #20 // -> lhs (false if either is NaN)
+            // If lhs is NaN, then return it
+            ; fcmp S(reg(lhs_reg)), S(reg(lhs_reg))
+            ; b.vs #12 // -> lhs
             ; mov V(reg(out_reg)).b16, V(reg(rhs_reg)).b16
             ; b #8 // -> end
             // lhs:
@@ -264,6 +316,32 @@ impl AssemblerT for GradSliceAssembler {
         )
     }
 
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        // This evaluator doesn't record choices, so `max` is already suitable
+        self.build_max(out_reg, lhs_reg, rhs_reg)
+    }
+
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        self.build_min(out_reg, lhs_reg, rhs_reg)
+    }
+
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        let inf = f32::INFINITY.to_bits();
+        dynasm!(self.0.ops
+            ; movz w9, #(inf >> 16), lsl 16
+            ; fmov s4, w9
+            // fminnm ignores a NaN argument, so this replaces a NaN value
+            // with +inf; we then splice it into a copy of the gradient
+            ; fminnm s6, S(reg(lhs_reg)), s4
+            ; mov v5.b16, V(reg(lhs_reg)).b16
+            ; mov v5.s[0], v6.s[0]
+            ; fminnm s6, S(reg(rhs_reg)), s4
+            ; mov v7.b16, V(reg(rhs_reg)).b16
+            ; mov v7.s[0], v6.s[0]
+        );
+        (5u8.wrapping_sub(OFFSET), 7u8.wrapping_sub(OFFSET))
+    }
+
     /// Loads an immediate into register S4, using W9 as an intermediary
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
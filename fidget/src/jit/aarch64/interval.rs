@@ -1,7 +1,7 @@
 use crate::{
     eval::types::Interval,
     jit::{
-        interval::IntervalAssembler, mmap::Mmap, reg, AssemblerData,
+        call, interval::IntervalAssembler, mmap::Mmap, reg, AssemblerData,
         AssemblerT, CHOICE_BOTH, CHOICE_LEFT, CHOICE_RIGHT, IMM_REG, OFFSET,
         REGISTER_LIMIT,
     },
@@ -149,8 +149,8 @@ impl AssemblerT for IntervalAssembler {
     fn build_sqrt(&mut self, out_reg: u8, lhs_reg: u8) {
         let nan_u32 = f32::NAN.to_bits();
         dynasm!(self.0.ops
-            // Store lhs <= 0.0 in x15
-            ; fcmle v4.s2, V(reg(lhs_reg)).s2, #0.0
+            // Store lhs < 0.0 in x15
+            ; fcmlt v4.s2, V(reg(lhs_reg)).s2, #0.0
             ; fmov x15, d4
 
             // Check whether lhs.upper < 0
@@ -256,6 +256,51 @@ impl AssemblerT for IntervalAssembler {
             );
         }
     }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Interval, call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Interval, call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Interval, call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Interval, call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Interval, call::Mod>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<Interval>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<Interval>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        let f = call::ternary::<Interval, call::Clamp>;
+        self.0.call_fn_ternary(out_reg, x_reg, lo_reg, hi_reg, f);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         let nan_u32 = f32::NAN.to_bits();
         dynasm!(self.0.ops
@@ -302,7 +347,7 @@ impl AssemblerT for IntervalAssembler {
             ; zip1 v5.s2, V(reg(rhs_reg)).s2, V(reg(lhs_reg)).s2
             ; fcmgt v5.s2, v5.s2, v4.s2
             ; fmov x15, d5
-            ; ldrb w14, [x1]
+            ; mov w14, wzr
 
             ; tst x15, #0x1_0000_0000
             ; b.ne #28 // -> lhs
@@ -333,13 +378,13 @@ impl AssemblerT for IntervalAssembler {
     fn build_min(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             //  if lhs.upper < rhs.lower
-            //      *choices++ |= CHOICE_LEFT
+            //      *choices++ = CHOICE_LEFT
             //      out = lhs
             //  elif rhs.upper < lhs.lower
-            //      *choices++ |= CHOICE_RIGHT
+            //      *choices++ = CHOICE_RIGHT
             //      out = rhs
             //  else
-            //      *choices++ |= CHOICE_BOTH
+            //      *choices++ = CHOICE_BOTH
             //      out = fmin(lhs, rhs)
 
             // v4 = [lhs.upper, rhs.upper]
@@ -351,7 +396,7 @@ impl AssemblerT for IntervalAssembler {
             // v5 = [rhs.lower > lhs.upper, lhs.lower > rhs.upper]
             ; fcmgt v5.s2, v5.s2, v4.s2
             ; fmov x15, d5
-            ; ldrb w14, [x1]
+            ; mov w14, wzr
 
             ; tst x15, #0x1_0000_0000
             ; b.ne #28 // -> rhs
@@ -380,7 +425,67 @@ impl AssemblerT for IntervalAssembler {
         )
     }
 
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            // This matches the ambiguous case in build_max
+            ; fmax V(reg(out_reg)).s2, V(reg(lhs_reg)).s2, V(reg(rhs_reg)).s2
+        )
+    }
+
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            ; fmin V(reg(out_reg)).s2, V(reg(lhs_reg)).s2, V(reg(rhs_reg)).s2
+        )
+    }
+
     /// Loads an immediate into register S4, using W9 as an intermediary
+    fn build_widen(&mut self, out_reg: u8) {
+        let eps = f32::EPSILON.to_bits();
+        let tiny = f32::MIN_POSITIVE.to_bits();
+        dynasm!(self.0.ops
+            // v4 = abs(reg) * EPSILON + MIN_POSITIVE
+            ; fabs v4.s2, V(reg(out_reg)).s2
+            ; movz w15, #(eps >> 16), lsl 16
+            ; movk w15, #(eps)
+            ; dup v5.s2, w15
+            ; fmul v4.s2, v4.s2, v5.s2
+            ; movz w15, #(tiny >> 16), lsl 16
+            ; movk w15, #(tiny)
+            ; dup v5.s2, w15
+            ; fadd v4.s2, v4.s2, v5.s2
+
+            // Move the lower bound down and the upper bound up.  fminnm and
+            // fmaxnm ignore a NaN (from inf - inf), leaving the original bound
+            // unchanged.
+            ; fsub v5.s2, V(reg(out_reg)).s2, v4.s2
+            ; fminnm v5.s2, v5.s2, V(reg(out_reg)).s2
+            ; fadd v4.s2, V(reg(out_reg)).s2, v4.s2
+            ; fmaxnm v4.s2, v4.s2, V(reg(out_reg)).s2
+
+            // Splice together [lower, upper]
+            ; mov V(reg(out_reg)).s[0], v5.s[0]
+            ; mov V(reg(out_reg)).s[1], v4.s[1]
+        )
+    }
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        let inf = f32::INFINITY.to_bits();
+        dynasm!(self.0.ops
+            // v4 = [-inf, +inf]
+            ; movz w15, #(inf >> 16), lsl 16
+            ; dup v4.s2, w15
+            ; fneg v5.s2, v4.s2
+            ; mov v4.s[0], v5.s[0]
+
+            // Replace NaN bounds with the corresponding bound from v4, using
+            // a mask which is all 1s for non-NaN lanes.  v6 and v7 are not
+            // touched by build_min / build_max.
+            ; fcmeq v6.s2, V(reg(lhs_reg)).s2, V(reg(lhs_reg)).s2
+            ; bsl v6.b8, V(reg(lhs_reg)).b8, v4.b8
+            ; fcmeq v7.s2, V(reg(rhs_reg)).s2, V(reg(rhs_reg)).s2
+            ; bsl v7.b8, V(reg(rhs_reg)).b8, v4.b8
+        );
+        (6u8.wrapping_sub(OFFSET), 7u8.wrapping_sub(OFFSET))
+    }
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
         dynasm!(self.0.ops
@@ -1,7 +1,7 @@
 use crate::{
     jit::{
-        mmap::Mmap, point::PointAssembler, reg, AssemblerData, AssemblerT,
-        CHOICE_BOTH, CHOICE_LEFT, CHOICE_RIGHT, IMM_REG, OFFSET,
+        call, mmap::Mmap, point::PointAssembler, reg, AssemblerData,
+        AssemblerT, CHOICE_BOTH, CHOICE_LEFT, CHOICE_RIGHT, IMM_REG, OFFSET,
         REGISTER_LIMIT,
     },
     Error,
@@ -100,6 +100,67 @@ impl AssemblerT for PointAssembler {
             ; fmul S(reg(out_reg)), S(reg(lhs_reg)), S(reg(rhs_reg))
         )
     }
+    fn build_fma(
+        &mut self,
+        out_reg: u8,
+        a_reg: u8,
+        b_reg: u8,
+        c_reg: u8,
+        _widen: bool,
+    ) {
+        dynasm!(self.0.ops
+            ; fmadd S(reg(out_reg)), S(reg(a_reg)), S(reg(b_reg)), S(reg(c_reg))
+        )
+    }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<f32, call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<f32, call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<f32, call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<f32, call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            ; fdiv s4, S(reg(lhs_reg)), S(reg(rhs_reg))
+            ; frintm s4, s4
+            ; fmul s4, s4, S(reg(rhs_reg))
+            ; fsub S(reg(out_reg)), S(reg(lhs_reg)), s4
+        )
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<f32>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<f32>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        let f = call::ternary::<f32, call::Clamp>;
+        self.0.call_fn_ternary(out_reg, x_reg, lo_reg, hi_reg, f);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; fdiv S(reg(out_reg)), S(reg(lhs_reg)), S(reg(rhs_reg))
@@ -107,7 +168,7 @@ impl AssemblerT for PointAssembler {
     }
     fn build_max(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
-            ; ldrb w14, [x1]
+            ; mov w14, wzr
             ; fcmp S(reg(lhs_reg)), S(reg(rhs_reg))
             ; b.mi #20 // -> RHS
             ; b.gt #32 // -> LHS
@@ -135,7 +196,7 @@ impl AssemblerT for PointAssembler {
     }
     fn build_min(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
-            ; ldrb w14, [x1]
+            ; mov w14, wzr
             ; fcmp S(reg(lhs_reg)), S(reg(rhs_reg))
             ; b.mi #20
             ; b.gt #32
@@ -162,6 +223,32 @@ impl AssemblerT for PointAssembler {
         )
     }
 
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            // fmax returns NaN if either argument is NaN
+            ; fmax S(reg(out_reg)), S(reg(lhs_reg)), S(reg(rhs_reg))
+        )
+    }
+
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            ; fmin S(reg(out_reg)), S(reg(lhs_reg)), S(reg(rhs_reg))
+        )
+    }
+
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        let inf = f32::INFINITY.to_bits();
+        dynasm!(self.0.ops
+            ; movz w9, #(inf >> 16), lsl 16
+            ; fmov s4, w9
+            // fminnm ignores a NaN argument, so this replaces NaN with +inf
+            // and leaves other values unchanged
+            ; fminnm s5, S(reg(lhs_reg)), s4
+            ; fminnm s6, S(reg(rhs_reg)), s4
+        );
+        (5u8.wrapping_sub(OFFSET), 6u8.wrapping_sub(OFFSET))
+    }
+
     /// Loads an immediate into register S4, using W9 as an intermediary
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
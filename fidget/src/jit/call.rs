@@ -0,0 +1,658 @@
+//! Calls from JIT code back into Rust
+//!
+//! Operations without a native instruction (e.g. `sin`, `atan2`, noise, or
+//! a user-defined [`CustomOp`]) are implemented by calling a Rust function.
+//! Arguments and the result are passed through pointers to the stack, which
+//! lets every evaluator use the same calling sequence regardless of its data
+//! type.
+//!
+//! Every register which holds tape data (or evaluator state) is saved before
+//! the call and restored afterwards, so these operations are much slower than
+//! native instructions.
+use crate::{
+    eval::{
+        noise,
+        types::{Grad, Interval},
+        CustomOp,
+    },
+    jit::{arch::float_slice::SIMD_WIDTH, AssemblerData},
+};
+use dynasmrt::{dynasm, DynasmApi};
+use std::{any::Any, cell::Cell, panic::AssertUnwindSafe, sync::Arc};
+
+#[cfg(target_arch = "x86_64")]
+use crate::jit::{reg, REGISTER_LIMIT};
+
+#[cfg(target_arch = "aarch64")]
+use crate::jit::reg;
+
+/// Function which applies a binary operation to values behind pointers
+///
+/// Arguments are `lhs`, `rhs`, and `out`.
+pub(crate) type BinaryFn<T> =
+    jit_fn!(unsafe fn(*const T, *const T, *mut T) -> ());
+
+/// Pointer to a [`CustomOp`], which is passed to a [`CustomFn`]
+pub(crate) type CustomPtr = *const Arc<dyn CustomOp>;
+
+/// Function which applies a [`CustomOp`] to values behind pointers
+///
+/// Arguments are `lhs`, `rhs`, `out`, and the operation itself.
+pub(crate) type CustomFn<T> =
+    jit_fn!(unsafe fn(*const T, *const T, *mut T, CustomPtr) -> ());
+
+/// Function which evaluates noise of values behind pointers
+///
+/// Arguments are `x`, `y`, `z`, `out`, and the seed.
+pub(crate) type NoiseFn<T> =
+    jit_fn!(unsafe fn(*const T, *const T, *const T, *mut T, u16) -> ());
+
+/// Function which applies a ternary operation to values behind pointers
+///
+/// Arguments are `a`, `b`, `c`, and `out`.
+pub(crate) type TernaryFn<T> =
+    jit_fn!(unsafe fn(*const T, *const T, *const T, *mut T) -> ());
+
+/// Binary operation which is evaluated by calling back into Rust
+pub(crate) trait BinaryOp<T> {
+    /// Applies the operation
+    fn apply(lhs: T, rhs: T) -> T;
+}
+
+/// Entry point for JIT code, which calls `F::apply`
+///
+/// # Safety
+/// All pointers must be valid and aligned for `T`
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe extern "sysv64" fn binary<T, F: BinaryOp<T>>(
+    lhs: *const T,
+    rhs: *const T,
+    out: *mut T,
+) {
+    out.write(F::apply(lhs.read(), rhs.read()))
+}
+
+/// Entry point for JIT code, which calls `F::apply`
+///
+/// # Safety
+/// All pointers must be valid and aligned for `T`
+#[cfg(target_arch = "aarch64")]
+pub(crate) unsafe extern "C" fn binary<T, F: BinaryOp<T>>(
+    lhs: *const T,
+    rhs: *const T,
+    out: *mut T,
+) {
+    out.write(F::apply(lhs.read(), rhs.read()))
+}
+
+/// Ternary operation which is evaluated by calling back into Rust
+pub(crate) trait TernaryOp<T> {
+    /// Applies the operation
+    fn apply(a: T, b: T, c: T) -> T;
+}
+
+/// Entry point for JIT code, which calls `F::apply`
+///
+/// # Safety
+/// All pointers must be valid and aligned for `T`
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe extern "sysv64" fn ternary<T, F: TernaryOp<T>>(
+    a: *const T,
+    b: *const T,
+    c: *const T,
+    out: *mut T,
+) {
+    out.write(F::apply(a.read(), b.read(), c.read()))
+}
+
+/// Entry point for JIT code, which calls `F::apply`
+///
+/// # Safety
+/// All pointers must be valid and aligned for `T`
+#[cfg(target_arch = "aarch64")]
+pub(crate) unsafe extern "C" fn ternary<T, F: TernaryOp<T>>(
+    a: *const T,
+    b: *const T,
+    c: *const T,
+    out: *mut T,
+) {
+    out.write(F::apply(a.read(), b.read(), c.read()))
+}
+
+/// Entry point for JIT code, which applies a [`CustomOp`]
+///
+/// # Safety
+/// All pointers must be valid and aligned for their types
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe extern "sysv64" fn custom<T: CustomData>(
+    lhs: *const T,
+    rhs: *const T,
+    out: *mut T,
+    op: CustomPtr,
+) {
+    apply_custom(lhs, rhs, out, op)
+}
+
+/// Entry point for JIT code, which applies a [`CustomOp`]
+///
+/// # Safety
+/// All pointers must be valid and aligned for their types
+#[cfg(target_arch = "aarch64")]
+pub(crate) unsafe extern "C" fn custom<T: CustomData>(
+    lhs: *const T,
+    rhs: *const T,
+    out: *mut T,
+    op: CustomPtr,
+) {
+    apply_custom(lhs, rhs, out, op)
+}
+
+/// Applies a [`CustomOp`], catching any panic
+///
+/// Unwinding through JIT code (and the `extern` entry points) would abort the
+/// process, so a panic is stashed in [`CUSTOM_PANIC`] instead; `out` is left
+/// unchanged, and later operations are skipped.  Once JIT code returns, the
+/// caller must call [`resume_custom_panic`] to continue unwinding.
+///
+/// # Safety
+/// All pointers must be valid and aligned for their types
+unsafe fn apply_custom<T: CustomData>(
+    lhs: *const T,
+    rhs: *const T,
+    out: *mut T,
+    op: CustomPtr,
+) {
+    let panicked = CUSTOM_PANIC.with(|p| {
+        let prev = p.take();
+        let out = prev.is_some();
+        p.set(prev);
+        out
+    });
+    if panicked {
+        return;
+    }
+    let (lhs, rhs) = (lhs.read(), rhs.read());
+    match std::panic::catch_unwind(AssertUnwindSafe(|| {
+        T::apply(&**op, lhs, rhs)
+    })) {
+        Ok(v) => out.write(v),
+        Err(e) => CUSTOM_PANIC.with(|p| p.set(Some(e))),
+    }
+}
+
+thread_local! {
+    /// Panic raised by a [`CustomOp`] while JIT code was running
+    static CUSTOM_PANIC: Cell<Option<Box<dyn Any + Send>>> =
+        const { Cell::new(None) };
+}
+
+/// Resumes unwinding from a [`CustomOp`] which panicked in JIT code
+///
+/// This must be called after every call into JIT code.
+#[inline]
+pub(crate) fn resume_custom_panic() {
+    if let Some(e) = CUSTOM_PANIC.with(|p| p.take()) {
+        std::panic::resume_unwind(e)
+    }
+}
+
+/// Entry point for JIT code, which evaluates noise
+///
+/// # Safety
+/// All pointers must be valid and aligned for `T`
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe extern "sysv64" fn noise<T: NoiseData>(
+    x: *const T,
+    y: *const T,
+    z: *const T,
+    out: *mut T,
+    seed: u16,
+) {
+    out.write(T::noise3(x.read(), y.read(), z.read(), seed))
+}
+
+/// Entry point for JIT code, which evaluates noise
+///
+/// # Safety
+/// All pointers must be valid and aligned for `T`
+#[cfg(target_arch = "aarch64")]
+pub(crate) unsafe extern "C" fn noise<T: NoiseData>(
+    x: *const T,
+    y: *const T,
+    z: *const T,
+    out: *mut T,
+    seed: u16,
+) {
+    out.write(T::noise3(x.read(), y.read(), z.read(), seed))
+}
+
+/// Data type for which noise can be evaluated
+pub(crate) trait NoiseData: Sized {
+    /// Evaluates noise, picking the function for this type
+    fn noise3(x: Self, y: Self, z: Self, seed: u16) -> Self;
+}
+
+impl NoiseData for f32 {
+    fn noise3(x: f32, y: f32, z: f32, seed: u16) -> f32 {
+        noise::noise3(x, y, z, seed)
+    }
+}
+
+impl NoiseData for Interval {
+    fn noise3(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3_interval(x, y, z, seed)
+    }
+}
+
+impl NoiseData for Grad {
+    fn noise3(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        noise::noise3_grad(x, y, z, seed)
+    }
+}
+
+impl NoiseData for [f32; SIMD_WIDTH] {
+    fn noise3(x: Self, y: Self, z: Self, seed: u16) -> Self {
+        std::array::from_fn(|i| noise::noise3(x[i], y[i], z[i], seed))
+    }
+}
+
+/// Clamp, `max(min(a, c), b)`, propagating `NaN`
+///
+/// This is only called by tracing evaluators, where the native `min` and `max`
+/// implementations would write to the choice array.
+pub(crate) enum Clamp {}
+
+impl TernaryOp<f32> for Clamp {
+    fn apply(x: f32, lo: f32, hi: f32) -> f32 {
+        if x.is_nan() || lo.is_nan() || hi.is_nan() {
+            f32::NAN
+        } else {
+            x.min(hi).max(lo)
+        }
+    }
+}
+
+impl TernaryOp<Interval> for Clamp {
+    fn apply(x: Interval, lo: Interval, hi: Interval) -> Interval {
+        x.clamp(lo, hi)
+    }
+}
+
+/// Data type which can be passed to a [`CustomOp`]
+pub(crate) trait CustomData: Sized {
+    /// Applies the operation, picking the method for this type
+    fn apply(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self;
+}
+
+impl CustomData for f32 {
+    fn apply(op: &dyn CustomOp, lhs: f32, rhs: f32) -> f32 {
+        op.eval_f32(lhs, rhs)
+    }
+}
+
+impl CustomData for Interval {
+    fn apply(op: &dyn CustomOp, lhs: Interval, rhs: Interval) -> Interval {
+        op.eval_interval(lhs, rhs)
+    }
+}
+
+impl CustomData for Grad {
+    fn apply(op: &dyn CustomOp, lhs: Grad, rhs: Grad) -> Grad {
+        op.eval_grad(lhs, rhs)
+    }
+}
+
+impl CustomData for [f32; SIMD_WIDTH] {
+    fn apply(op: &dyn CustomOp, lhs: Self, rhs: Self) -> Self {
+        std::array::from_fn(|i| op.eval_f32(lhs[i], rhs[i]))
+    }
+}
+
+/// Sine, `sin(lhs)`; `rhs` is ignored
+pub(crate) enum Sin {}
+
+impl BinaryOp<f32> for Sin {
+    fn apply(lhs: f32, _rhs: f32) -> f32 {
+        lhs.sin()
+    }
+}
+
+impl BinaryOp<Interval> for Sin {
+    fn apply(lhs: Interval, _rhs: Interval) -> Interval {
+        lhs.sin()
+    }
+}
+
+impl BinaryOp<Grad> for Sin {
+    fn apply(lhs: Grad, _rhs: Grad) -> Grad {
+        lhs.sin()
+    }
+}
+
+impl BinaryOp<[f32; SIMD_WIDTH]> for Sin {
+    fn apply(
+        lhs: [f32; SIMD_WIDTH],
+        _rhs: [f32; SIMD_WIDTH],
+    ) -> [f32; SIMD_WIDTH] {
+        std::array::from_fn(|i| lhs[i].sin())
+    }
+}
+
+/// Cosine, `cos(lhs)`; `rhs` is ignored
+pub(crate) enum Cos {}
+
+impl BinaryOp<f32> for Cos {
+    fn apply(lhs: f32, _rhs: f32) -> f32 {
+        lhs.cos()
+    }
+}
+
+impl BinaryOp<Interval> for Cos {
+    fn apply(lhs: Interval, _rhs: Interval) -> Interval {
+        lhs.cos()
+    }
+}
+
+impl BinaryOp<Grad> for Cos {
+    fn apply(lhs: Grad, _rhs: Grad) -> Grad {
+        lhs.cos()
+    }
+}
+
+impl BinaryOp<[f32; SIMD_WIDTH]> for Cos {
+    fn apply(
+        lhs: [f32; SIMD_WIDTH],
+        _rhs: [f32; SIMD_WIDTH],
+    ) -> [f32; SIMD_WIDTH] {
+        std::array::from_fn(|i| lhs[i].cos())
+    }
+}
+
+/// Four-quadrant arctangent, `atan2(lhs, rhs)`
+pub(crate) enum Atan2 {}
+
+impl BinaryOp<f32> for Atan2 {
+    fn apply(lhs: f32, rhs: f32) -> f32 {
+        lhs.atan2(rhs)
+    }
+}
+
+impl BinaryOp<Interval> for Atan2 {
+    fn apply(lhs: Interval, rhs: Interval) -> Interval {
+        lhs.atan2(rhs)
+    }
+}
+
+impl BinaryOp<Grad> for Atan2 {
+    fn apply(lhs: Grad, rhs: Grad) -> Grad {
+        lhs.atan2(rhs)
+    }
+}
+
+impl BinaryOp<[f32; SIMD_WIDTH]> for Atan2 {
+    fn apply(
+        lhs: [f32; SIMD_WIDTH],
+        rhs: [f32; SIMD_WIDTH],
+    ) -> [f32; SIMD_WIDTH] {
+        std::array::from_fn(|i| lhs[i].atan2(rhs[i]))
+    }
+}
+
+/// Hypotenuse, `sqrt(lhs² + rhs²)`
+pub(crate) enum Hypot {}
+
+impl BinaryOp<f32> for Hypot {
+    fn apply(lhs: f32, rhs: f32) -> f32 {
+        lhs.hypot(rhs)
+    }
+}
+
+impl BinaryOp<Interval> for Hypot {
+    fn apply(lhs: Interval, rhs: Interval) -> Interval {
+        lhs.hypot(rhs)
+    }
+}
+
+impl BinaryOp<Grad> for Hypot {
+    fn apply(lhs: Grad, rhs: Grad) -> Grad {
+        lhs.hypot(rhs)
+    }
+}
+
+impl BinaryOp<[f32; SIMD_WIDTH]> for Hypot {
+    fn apply(
+        lhs: [f32; SIMD_WIDTH],
+        rhs: [f32; SIMD_WIDTH],
+    ) -> [f32; SIMD_WIDTH] {
+        std::array::from_fn(|i| lhs[i].hypot(rhs[i]))
+    }
+}
+
+/// Floored modulo, `lhs - rhs * floor(lhs / rhs)`
+///
+/// This is native on the point and float slice assemblers, so it's only
+/// implemented for intervals and gradients.
+pub(crate) enum Mod {}
+
+impl BinaryOp<Interval> for Mod {
+    fn apply(lhs: Interval, rhs: Interval) -> Interval {
+        lhs.modulo(rhs)
+    }
+}
+
+impl BinaryOp<Grad> for Mod {
+    fn apply(lhs: Grad, rhs: Grad) -> Grad {
+        lhs.modulo(rhs)
+    }
+}
+
+/// Size of each argument slot, which fits a full SIMD register
+#[cfg(target_arch = "x86_64")]
+const ARG_SIZE: i32 = 32;
+
+/// General-purpose registers which hold evaluator state
+///
+/// These are `rdi`, `rsi`, `rdx`, `rcx`, `r8`, and `r9`, i.e. every argument
+/// register in the System V calling convention.
+#[cfg(target_arch = "x86_64")]
+const SAVED_GPRS: [u8; 6] = [7, 6, 2, 1, 8, 9];
+
+/// Size of each argument slot, which fits a full SIMD register
+#[cfg(target_arch = "aarch64")]
+const ARG_SIZE: u32 = 16;
+
+/// Floating-point registers which hold inputs (`v0-2`) or tape data (`v8-31`)
+#[cfg(target_arch = "aarch64")]
+const SAVED_VREGS: [u32; 27] = [
+    0, 1, 2, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+    25, 26, 27, 28, 29, 30, 31,
+];
+
+/// Number of general-purpose registers which hold evaluator state (`x0-7`)
+#[cfg(target_arch = "aarch64")]
+const SAVED_GPRS: u32 = 8;
+
+impl<T> AssemblerData<T> {
+    /// Calls `f(&lhs, &rhs, &mut out)`
+    pub(crate) fn call_fn_binary<D>(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        f: BinaryFn<D>,
+    ) {
+        self.call_fn(out_reg, &[lhs_reg, rhs_reg], f as usize as u64, None)
+    }
+
+    /// Calls `f(&lhs, &rhs, &mut out, op)`
+    ///
+    /// `op` must remain valid for as long as the compiled function is used.
+    pub(crate) fn call_fn_custom<D>(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        f: CustomFn<D>,
+        op: CustomPtr,
+    ) {
+        let (f, op) = (f as usize as u64, op as usize as u64);
+        self.call_fn(out_reg, &[lhs_reg, rhs_reg], f, Some(op))
+    }
+
+    /// Calls `f(&a, &b, &c, &mut out)`
+    pub(crate) fn call_fn_ternary<D>(
+        &mut self,
+        out_reg: u8,
+        a_reg: u8,
+        b_reg: u8,
+        c_reg: u8,
+        f: TernaryFn<D>,
+    ) {
+        self.call_fn(out_reg, &[a_reg, b_reg, c_reg], f as usize as u64, None)
+    }
+
+    /// Calls `f(&x, &y, &z, &mut out, seed)`
+    pub(crate) fn call_fn_noise<D>(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        f: NoiseFn<D>,
+        seed: u16,
+    ) {
+        let (f, seed) = (f as usize as u64, seed as u64);
+        self.call_fn(out_reg, &[x_reg, y_reg, z_reg], f, Some(seed))
+    }
+
+    /// Calls the function at `addr`, passing pointers to each argument and
+    /// `out` (then `data`, if present) as arguments
+    ///
+    /// The stack frame contains a slot for each argument and `out`, followed
+    /// by saved registers.
+    #[cfg(target_arch = "x86_64")]
+    fn call_fn(
+        &mut self,
+        out_reg: u8,
+        arg_regs: &[u8],
+        addr: u64,
+        data: Option<u64>,
+    ) {
+        // Pointer arguments are passed in rdi, rsi, rdx, rcx, and r8
+        const ARG_GPRS: [u8; 5] = [7, 6, 2, 1, 8];
+        assert!(arg_regs.len() < ARG_GPRS.len());
+
+        let out = arg_regs.len() as i32 * ARG_SIZE;
+        let regs = out + ARG_SIZE;
+        let gprs = regs + REGISTER_LIMIT as i32 * ARG_SIZE;
+        let frame = (gprs + SAVED_GPRS.len() as i32 * 8 + 15) / 16 * 16;
+
+        dynasm!(self.ops ; sub rsp, frame);
+        for i in 0..REGISTER_LIMIT {
+            let offset = regs + i as i32 * ARG_SIZE;
+            dynasm!(self.ops ; vmovups [rsp + offset], Ry(reg(i)));
+        }
+        for (i, r) in SAVED_GPRS.iter().enumerate() {
+            let offset = gprs + i as i32 * 8;
+            dynasm!(self.ops ; mov [rsp + offset], Rq(*r));
+        }
+        for (i, r) in arg_regs.iter().enumerate() {
+            let offset = i as i32 * ARG_SIZE;
+            dynasm!(self.ops
+                ; vmovups [rsp + offset], Ry(reg(*r))
+                ; lea Rq(ARG_GPRS[i]), [rsp + offset]
+            );
+        }
+        let n = arg_regs.len();
+        dynasm!(self.ops ; lea Rq(ARG_GPRS[n]), [rsp + out]);
+        if let Some(data) = data {
+            dynasm!(self.ops ; mov Rq(ARG_GPRS[n + 1]), QWORD data as i64);
+        }
+        dynasm!(self.ops
+            ; mov rax, QWORD addr as i64
+            ; vzeroupper
+            ; call rax
+        );
+        for (i, r) in SAVED_GPRS.iter().enumerate() {
+            let offset = gprs + i as i32 * 8;
+            dynasm!(self.ops ; mov Rq(*r), [rsp + offset]);
+        }
+        for i in 0..REGISTER_LIMIT {
+            let offset = regs + i as i32 * ARG_SIZE;
+            dynasm!(self.ops ; vmovups Ry(reg(i)), [rsp + offset]);
+        }
+        dynasm!(self.ops
+            ; vmovups Ry(reg(out_reg)), [rsp + out]
+            ; add rsp, frame
+        );
+    }
+
+    /// Calls the function at `addr`, passing pointers to each argument and
+    /// `out` (then `data`, if present) as arguments
+    ///
+    /// The stack frame contains a slot for each argument and `out`, followed
+    /// by saved registers.  The link register is clobbered, but it's restored
+    /// from the stack when the function returns.
+    #[cfg(target_arch = "aarch64")]
+    fn call_fn(
+        &mut self,
+        out_reg: u8,
+        arg_regs: &[u8],
+        addr: u64,
+        data: Option<u64>,
+    ) {
+        // Arguments are passed in x0-4
+        assert!(arg_regs.len() < 4);
+
+        let out = arg_regs.len() as u32 * ARG_SIZE;
+        let regs = out + ARG_SIZE;
+        let gprs = regs + SAVED_VREGS.len() as u32 * ARG_SIZE;
+        let frame = (gprs + SAVED_GPRS * 8 + 15) / 16 * 16;
+
+        dynasm!(self.ops ; sub sp, sp, #(frame));
+        for (i, r) in SAVED_VREGS.iter().enumerate() {
+            let offset = regs + i as u32 * ARG_SIZE;
+            dynasm!(self.ops ; str Q(*r), [sp, #(offset)]);
+        }
+        for i in 0..SAVED_GPRS {
+            let offset = gprs + i * 8;
+            dynasm!(self.ops ; str X(i), [sp, #(offset)]);
+        }
+        for (i, r) in arg_regs.iter().enumerate() {
+            let offset = i as u32 * ARG_SIZE;
+            dynasm!(self.ops
+                ; str Q(reg(*r)), [sp, #(offset)]
+                ; add X(i as u32), sp, #(offset)
+            );
+        }
+        let n = arg_regs.len() as u32;
+        dynasm!(self.ops ; add X(n), sp, #(out));
+        if let Some(data) = data {
+            dynasm!(self.ops
+                ; movz X(n + 1), #((data >> 48) as u32), lsl 48
+                ; movk X(n + 1), #((data >> 32) as u32 & 0xffff), lsl 32
+                ; movk X(n + 1), #((data >> 16) as u32 & 0xffff), lsl 16
+                ; movk X(n + 1), #(data as u32 & 0xffff)
+            );
+        }
+        dynasm!(self.ops
+            ; movz x9, #((addr >> 48) as u32), lsl 48
+            ; movk x9, #((addr >> 32) as u32 & 0xffff), lsl 32
+            ; movk x9, #((addr >> 16) as u32 & 0xffff), lsl 16
+            ; movk x9, #(addr as u32 & 0xffff)
+            ; blr x9
+        );
+        for i in 0..SAVED_GPRS {
+            let offset = gprs + i * 8;
+            dynasm!(self.ops ; ldr X(i), [sp, #(offset)]);
+        }
+        for (i, r) in SAVED_VREGS.iter().enumerate() {
+            let offset = regs + i as u32 * ARG_SIZE;
+            dynasm!(self.ops ; ldr Q(*r), [sp, #(offset)]);
+        }
+        dynasm!(self.ops
+            ; ldr Q(reg(out_reg)), [sp, #(out)]
+            ; add sp, sp, #(frame)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Disassembly of JIT-compiled functions, for debugging
+use capstone::prelude::*;
+
+/// Builds a disassembler for the current platform
+#[cfg(target_arch = "x86_64")]
+fn build() -> CsResult<Capstone> {
+    Capstone::new()
+        .x86()
+        .mode(arch::x86::ArchMode::Mode64)
+        .build()
+}
+
+/// Builds a disassembler for the current platform
+#[cfg(target_arch = "aarch64")]
+fn build() -> CsResult<Capstone> {
+    Capstone::new()
+        .arm64()
+        .mode(arch::arm64::ArchMode::Arm)
+        .build()
+}
+
+/// Disassembles machine code which is loaded at `addr`
+///
+/// Each instruction is written on its own line, prefixed by its address.  If
+/// disassembly fails partway through, the number of remaining bytes (or the
+/// disassembler's error) is written instead of the rest of the code.
+pub(crate) fn disassemble(code: &[u8], addr: u64) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    let cs = match build() {
+        Ok(cs) => cs,
+        Err(e) => return format!("; disassembler error: {e}\n"),
+    };
+    let insns = match cs.disasm_all(code, addr) {
+        Ok(insns) => insns,
+        Err(e) => return format!("; disassembler error: {e}\n"),
+    };
+    let mut decoded = 0;
+    for i in insns.iter() {
+        writeln!(&mut out, "{i}").unwrap();
+        decoded += i.bytes().len();
+    }
+    if decoded < code.len() {
+        writeln!(&mut out, "; {} undecoded bytes", code.len() - decoded)
+            .unwrap();
+    }
+    out
+}
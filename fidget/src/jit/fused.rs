@@ -0,0 +1,327 @@
+//! Fused evaluation of several tapes in a single JIT function
+use crate::{
+    eval::Tape,
+    jit::{
+        arch::float_slice::SIMD_WIDTH, build_tape, call,
+        float_slice::FloatSliceAssembler, mmap::Code, mmap::Mmap, AssemblerT,
+        Eval,
+    },
+    Error,
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Maximum number of tapes in a fused function
+///
+/// This is limited by the 12-bit immediate used to advance the output pointer
+/// on `aarch64`.
+pub(crate) const MAX_OUTPUTS: usize = 255;
+
+/// Assembler which can write more than one output per iteration
+pub(crate) trait FusedAssembler: AssemblerT {
+    /// Writes the given register to an output
+    ///
+    /// Outputs are interleaved, so the `index`'th output is written to the
+    /// output array at an offset of `index` SIMD values.
+    fn build_output(&mut self, out_reg: u8, index: usize);
+
+    /// Finalizes the function, advancing the output pointer past `count`
+    /// outputs before looping
+    fn finalize_fused(self, count: usize) -> Result<Mmap, Error>;
+}
+
+/// Float slice evaluator for several tapes, compiled into a single function
+///
+/// This is useful when evaluating a handful of small tapes at the same points
+/// (e.g. the channels of a colored shape).  Each chunk of X, Y, Z values is
+/// loaded once, then every tape is evaluated in turn, sharing a single stack
+/// frame; this amortizes the per-call and per-chunk overhead which dominates
+/// the cost of evaluating small tapes separately.
+///
+/// Tapes may use different variables; the fused function takes a single
+/// variable array, with indexes given by [`FusedEval::vars`].
+///
+/// ```
+/// use fidget::{context::Context, jit};
+///
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let r = ctx.add(x, 1.0)?;
+/// let g = ctx.mul(y, 2.0)?;
+/// let tapes = [ctx.get_tape(r)?, ctx.get_tape(g)?];
+///
+/// let eval = jit::FusedEval::new(&tapes)?;
+/// let out = eval.eval(&[0.0, 1.0], &[2.0, 3.0], &[0.0; 2], &[])?;
+/// assert_eq!(out, vec![vec![1.0, 2.0], vec![4.0, 6.0]]);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+pub struct FusedEval {
+    /// Compiled code, which is only read when disassembling; it's otherwise
+    /// kept alive for the sake of `fn_bulk`
+    #[cfg_attr(not(feature = "disasm"), allow(dead_code))]
+    code: Arc<Code>,
+    /// Source tapes, which own the custom operations called by the function
+    tapes: Vec<Tape<Eval>>,
+    vars: BTreeMap<String, u32>,
+    fn_bulk: FusedFn,
+}
+
+/// Signature of a fused function
+type FusedFn = jit_fn!(
+    unsafe fn(
+        *const f32, // X
+        *const f32, // Y
+        *const f32, // Z
+        *const f32, // vars
+        *mut f32,   // out
+        u64,        // size
+    ) -> f32
+);
+
+// SAFETY: there is no mutable state in a `FusedEval`, and the pointer inside of
+// it points to its own `Code`, which is owned by an `Arc` (as are the custom
+// operations which it calls, via the tapes)
+unsafe impl Send for FusedEval {}
+unsafe impl Sync for FusedEval {}
+
+impl FusedEval {
+    /// Compiles a set of tapes into a single function
+    ///
+    /// Returns an error if `tapes` is empty or has more than 255 items.
+    pub fn new(tapes: &[Tape<Eval>]) -> Result<Self, Error> {
+        if tapes.is_empty() {
+            return Err(Error::EmptyArguments);
+        } else if tapes.len() > MAX_OUTPUTS {
+            return Err(Error::TooManyOutputs(tapes.len(), MAX_OUTPUTS));
+        }
+        let mut vars = BTreeMap::new();
+        for t in tapes {
+            for name in t.vars().keys() {
+                let next = vars.len() as u32;
+                vars.entry(name.clone()).or_insert(next);
+            }
+        }
+        let code = Code::new(build_fused::<FloatSliceAssembler>(tapes, &vars));
+        let ptr = code.as_ptr();
+        Ok(Self {
+            code: Arc::new(code),
+            tapes: tapes.to_vec(),
+            vars,
+            fn_bulk: unsafe {
+                std::mem::transmute::<*mut libc::c_void, FusedFn>(ptr)
+            },
+        })
+    }
+
+    /// Returns the mapping of variable names to indexes in the variable array
+    pub fn vars(&self) -> &BTreeMap<String, u32> {
+        &self.vars
+    }
+
+    /// Returns the number of tapes in this function
+    pub fn len(&self) -> usize {
+        self.tapes.len()
+    }
+
+    /// Checks whether this function has no tapes (which is never true)
+    pub fn is_empty(&self) -> bool {
+        self.tapes.is_empty()
+    }
+
+    /// Evaluates every tape at the given points
+    ///
+    /// Returns one array of results per tape, in the order in which they were
+    /// passed to [`FusedEval::new`].
+    pub fn eval(
+        &self,
+        xs: &[f32],
+        ys: &[f32],
+        zs: &[f32],
+        vars: &[f32],
+    ) -> Result<Vec<Vec<f32>>, Error> {
+        if xs.len() != ys.len() || ys.len() != zs.len() {
+            return Err(Error::MismatchedSlices);
+        } else if vars.len() != self.vars.len() {
+            return Err(Error::BadVarSlice(vars.len(), self.vars.len()));
+        }
+        let n = xs.len();
+        let count = self.tapes.len();
+        let mut out = vec![Vec::with_capacity(n); count];
+
+        // Evaluates a group of full chunks, then de-interleaves the results
+        let mut run = |xs: &[f32], ys: &[f32], zs: &[f32], start: usize| {
+            let size = xs.len();
+            let mut tmp = vec![f32::NAN; size * count];
+            unsafe {
+                (self.fn_bulk)(
+                    xs.as_ptr(),
+                    ys.as_ptr(),
+                    zs.as_ptr(),
+                    vars.as_ptr(),
+                    tmp.as_mut_ptr(),
+                    size as u64,
+                );
+            }
+            call::resume_custom_panic();
+            for (chunk, block) in tmp.chunks(SIMD_WIDTH * count).enumerate() {
+                for (o, values) in out.iter_mut().zip(block.chunks(SIMD_WIDTH))
+                {
+                    let i = chunk * SIMD_WIDTH;
+                    let skip = start.saturating_sub(i).min(SIMD_WIDTH);
+                    o.extend_from_slice(&values[skip..]);
+                }
+            }
+        };
+
+        // As in `JitBulkEval`, the function only accepts full SIMD chunks, so
+        // short inputs are padded and any remainder is handled by evaluating
+        // the last full chunk again.
+        if n < SIMD_WIDTH {
+            let mut x = [0.0; SIMD_WIDTH];
+            let mut y = [0.0; SIMD_WIDTH];
+            let mut z = [0.0; SIMD_WIDTH];
+            x[..n].copy_from_slice(xs);
+            y[..n].copy_from_slice(ys);
+            z[..n].copy_from_slice(zs);
+            run(&x, &y, &z, 0);
+            for o in &mut out {
+                o.truncate(n);
+            }
+        } else {
+            let m = (n / SIMD_WIDTH) * SIMD_WIDTH;
+            run(&xs[..m], &ys[..m], &zs[..m], 0);
+            if n != m {
+                let r = n - SIMD_WIDTH;
+                run(&xs[r..], &ys[r..], &zs[r..], m - r);
+            }
+        }
+
+        for (o, t) in out.iter_mut().zip(&self.tapes) {
+            t.sign_slice(o);
+        }
+        Ok(out)
+    }
+
+    /// Disassembles the compiled function, one instruction per line
+    #[cfg(feature = "disasm")]
+    pub fn disassemble(&self) -> String {
+        crate::jit::disasm::disassemble(
+            self.code.as_slice(),
+            self.code.as_ptr() as u64,
+        )
+    }
+}
+
+/// Compiles a set of tapes into a single memory-mapped function
+fn build_fused<A: FusedAssembler>(
+    tapes: &[Tape<Eval>],
+    vars: &BTreeMap<String, u32>,
+) -> Mmap {
+    span!(
+        DEBUG,
+        "jit_compile_fused",
+        len = tapes.iter().map(|t| t.len()).sum::<usize>(),
+        count = tapes.len()
+    );
+
+    // This guard may be a unit value on some systems
+    #[allow(clippy::let_unit_value)]
+    let _guard = Mmap::thread_mode_write();
+
+    let s = Mmap::scratch(Mmap::default());
+    s.make_write();
+    let slots = tapes.iter().map(|t| t.slot_count()).max().unwrap_or(0);
+    let mut asm = A::init(s, slots);
+    for (i, t) in tapes.iter().enumerate() {
+        let var_map: Vec<u32> = t
+            .vars()
+            .iter()
+            .map(|(name, j)| (*j, vars[name]))
+            .collect::<BTreeMap<u32, u32>>()
+            .into_values()
+            .collect();
+        build_tape(&mut asm, t, false, |j| var_map[j as usize]);
+        asm.build_output(0, i);
+    }
+    asm.finalize_fused(tapes.len())
+        .expect("failed to build JIT function")
+    // JIT execute mode is restored here when the _guard is dropped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    #[test]
+    fn test_fused_eval() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let a = ctx.var("a").unwrap();
+        let b = ctx.var("b").unwrap();
+
+        let r = ctx.square(x).unwrap();
+        let r = ctx.add(r, a).unwrap();
+        let g = ctx.sin(y).unwrap();
+        let g = ctx.mul(g, b).unwrap();
+        let bl = ctx.min(x, z).unwrap();
+        let bl = ctx.sub(bl, a).unwrap();
+        let roots = [r, g, bl];
+
+        let tapes: Vec<Tape<Eval>> = roots
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let t = ctx.get_tape(*r).unwrap();
+                if i == 2 {
+                    t.invert()
+                } else {
+                    t
+                }
+            })
+            .collect();
+        let eval = FusedEval::new(&tapes).unwrap();
+        assert_eq!(eval.len(), 3);
+        assert_eq!(eval.vars().len(), 2);
+
+        let mut vars = [0.0; 2];
+        vars[eval.vars()["a"] as usize] = 0.5;
+        vars[eval.vars()["b"] as usize] = -2.0;
+
+        // Check sizes below, at, and above the SIMD width
+        for n in [1, 3, SIMD_WIDTH, SIMD_WIDTH + 3, SIMD_WIDTH * 3 + 1] {
+            let xs: Vec<f32> = (0..n).map(|i| i as f32 * 0.25 - 1.0).collect();
+            let ys: Vec<f32> = (0..n).map(|i| i as f32 * 0.5).collect();
+            let zs: Vec<f32> = (0..n).map(|i| 1.0 - i as f32 * 0.1).collect();
+            let out = eval.eval(&xs, &ys, &zs, &vars).unwrap();
+            assert_eq!(out.len(), 3);
+            for (t, o) in tapes.iter().zip(&out) {
+                let names = t.vars();
+                let tv: Vec<f32> = (0..names.len())
+                    .map(|i| {
+                        let name = names.iter().find(|(_, j)| **j == i as u32);
+                        vars[eval.vars()[name.unwrap().0] as usize]
+                    })
+                    .collect();
+                let expected = t
+                    .new_float_slice_evaluator()
+                    .eval(&xs, &ys, &zs, &tv)
+                    .unwrap()
+                    .to_vec();
+                assert_eq!(o, &expected, "mismatch with {n} points");
+            }
+        }
+
+        assert!(matches!(
+            eval.eval(&[0.0], &[0.0], &[], &vars),
+            Err(Error::MismatchedSlices)
+        ));
+        assert!(matches!(
+            eval.eval(&[0.0], &[0.0], &[0.0], &[]),
+            Err(Error::BadVarSlice(0, 2))
+        ));
+        assert!(matches!(FusedEval::new(&[]), Err(Error::EmptyArguments)));
+    }
+}
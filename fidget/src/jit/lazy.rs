@@ -0,0 +1,340 @@
+//! Lazy JIT compilation, falling back to the interpreter for cold tapes
+//!
+//! During rendering and meshing, many simplified tapes are only a handful of
+//! instructions long, or are only evaluated a few times; for these tapes,
+//! generating machine code can take longer than simply interpreting them.
+//!
+//! The [`Lazy`] family evaluates tapes with the interpreter until they're
+//! known to be worth compiling, i.e. they're at least `MIN_LEN` clauses long
+//! or they've been evaluated `MIN_USES` times.
+use crate::{
+    eval::{
+        bulk::{BulkEvaluator, BulkEvaluatorData},
+        interval_slice::TracingSliceEval,
+        tracing::{TracingEvaluator, TracingEvaluatorData},
+        Choice, EvaluatorStorage, Family, Tape,
+    },
+    jit::{
+        float_slice::JitFloatSliceEval, grad_slice::JitGradSliceEval,
+        interval::JitIntervalEval, mmap::Mmap, point::JitPointEval, Eval,
+        REGISTER_LIMIT, SLOT_LIMIT,
+    },
+    vm,
+};
+use once_cell::sync::OnceCell;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+/// Family of evaluators which JIT-compile tapes on demand
+///
+/// Tapes with at least `MIN_LEN` clauses are compiled immediately; shorter
+/// tapes are interpreted until they have been evaluated `MIN_USES` times (by a
+/// single evaluator), then compiled.
+///
+/// ```
+/// use fidget::{jit, rhai::eval};
+///
+/// let (sum, ctx) = eval("x + y")?;
+///
+/// // Only compile tapes with at least 32 clauses, or after 100 evaluations
+/// let tape = ctx.get_tape::<jit::Lazy<32, 100>>(sum)?;
+/// let eval = tape.new_point_evaluator();
+/// assert_eq!(eval.eval(0.1, 0.3, 0.0, &[])?.0, 0.1 + 0.3);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+#[derive(Clone)]
+pub enum Lazy<const MIN_LEN: usize = 64, const MIN_USES: usize = 16> {}
+
+impl<const MIN_LEN: usize, const MIN_USES: usize> Family
+    for Lazy<MIN_LEN, MIN_USES>
+{
+    /// Tapes are planned for the JIT, and can also be run by the interpreter
+    const REG_LIMIT: u8 = REGISTER_LIMIT;
+    const SLOT_LIMIT: usize = SLOT_LIMIT;
+    const SCHEDULE: bool = true;
+    const LANE_WIDTH: usize = Eval::LANE_WIDTH;
+
+    type IntervalEval = LazyEval<JitIntervalEval, MIN_LEN, MIN_USES>;
+    type IntervalSliceEval =
+        TracingSliceEval<LazyEval<JitIntervalEval, MIN_LEN, MIN_USES>>;
+    type PointEval = LazyEval<JitPointEval, MIN_LEN, MIN_USES>;
+    type FloatSliceEval = LazyEval<JitFloatSliceEval, MIN_LEN, MIN_USES>;
+    type GradSliceEval = LazyEval<JitGradSliceEval, MIN_LEN, MIN_USES>;
+    type HessianSliceEval = vm::Interpreted;
+
+    fn tile_sizes_3d() -> &'static [usize] {
+        Eval::tile_sizes_3d()
+    }
+
+    fn tile_sizes_2d() -> &'static [usize] {
+        Eval::tile_sizes_2d()
+    }
+
+    fn simplify_tree_during_meshing(d: usize) -> bool {
+        Eval::simplify_tree_during_meshing(d)
+    }
+}
+
+/// Evaluator which interprets its tape until it's worth compiling
+///
+/// Users are unlikely to use this directly; consider using the
+/// [`jit::Lazy`](Lazy) evaluator family instead.
+pub struct LazyEval<J, const MIN_LEN: usize, const MIN_USES: usize> {
+    /// Interpreter, used until the tape is compiled
+    vm: vm::AsmEval,
+    /// The same tape as `vm`, used to prepare interpreter data
+    vm_tape: Tape<vm::Eval>,
+    /// The same tape as `vm`, used for compilation
+    jit_tape: Tape<Eval>,
+    /// Compiled evaluator, built on demand
+    jit: OnceCell<J>,
+    /// Number of evaluations so far
+    uses: AtomicUsize,
+    /// Storage to be used when compiling the tape
+    storage: Mutex<Option<Mmap>>,
+}
+
+impl<J: Clone, const MIN_LEN: usize, const MIN_USES: usize> Clone
+    for LazyEval<J, MIN_LEN, MIN_USES>
+{
+    fn clone(&self) -> Self {
+        Self {
+            vm: self.vm.clone(),
+            vm_tape: self.vm_tape.clone(),
+            jit_tape: self.jit_tape.clone(),
+            jit: self.jit.clone(),
+            uses: AtomicUsize::new(self.uses.load(Ordering::Relaxed)),
+            storage: Mutex::new(None),
+        }
+    }
+}
+
+impl<J, const MIN_LEN: usize, const MIN_USES: usize>
+    LazyEval<J, MIN_LEN, MIN_USES>
+where
+    J: EvaluatorStorage<Eval, Storage = Mmap>,
+{
+    /// Returns the compiled evaluator, if the tape is (or is now) compiled
+    ///
+    /// Each call to this function counts as one use of the tape.
+    fn jit(&self) -> Option<&J> {
+        if let Some(j) = self.jit.get() {
+            return Some(j);
+        }
+        if self.uses.fetch_add(1, Ordering::Relaxed) + 1 < MIN_USES {
+            return None;
+        }
+        Some(self.jit.get_or_init(|| {
+            let storage = self.storage.lock().unwrap().take();
+            J::new_with_storage(&self.jit_tape, storage.unwrap_or_default())
+        }))
+    }
+
+    /// Checks whether the tape has been compiled
+    pub fn is_compiled(&self) -> bool {
+        self.jit.get().is_some()
+    }
+}
+
+impl<J, const MIN_LEN: usize, const MIN_USES: usize>
+    EvaluatorStorage<Lazy<MIN_LEN, MIN_USES>> for LazyEval<J, MIN_LEN, MIN_USES>
+where
+    J: EvaluatorStorage<Eval, Storage = Mmap>,
+{
+    type Storage = Mmap;
+    fn new_with_storage(
+        tape: &Tape<Lazy<MIN_LEN, MIN_USES>>,
+        storage: Mmap,
+    ) -> Self {
+        let vm_tape = tape.cast::<vm::Eval>();
+        let jit_tape = tape.cast::<Eval>();
+        let (jit, storage) = if tape.len() >= MIN_LEN || MIN_USES == 0 {
+            (
+                OnceCell::with_value(J::new_with_storage(&jit_tape, storage)),
+                None,
+            )
+        } else {
+            (OnceCell::new(), Some(storage))
+        };
+        Self {
+            vm: vm::AsmEval::new_with_storage(&vm_tape, ()),
+            vm_tape,
+            jit_tape,
+            jit,
+            uses: AtomicUsize::new(0),
+            storage: Mutex::new(storage),
+        }
+    }
+
+    fn take(self) -> Option<Self::Storage> {
+        match self.jit.into_inner() {
+            Some(j) => j.take(),
+            None => {
+                Some(self.storage.into_inner().unwrap().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Scratch data for a [`LazyEval`], used when interpreting the tape
+#[derive(Default)]
+pub struct LazyData<D>(D);
+
+impl<D, const MIN_LEN: usize, const MIN_USES: usize>
+    TracingEvaluatorData<Lazy<MIN_LEN, MIN_USES>> for LazyData<D>
+{
+    fn prepare(&mut self, _tape: &Tape<Lazy<MIN_LEN, MIN_USES>>) {
+        // Data is only prepared if the tape is interpreted
+    }
+}
+
+impl<D, const MIN_LEN: usize, const MIN_USES: usize>
+    BulkEvaluatorData<Lazy<MIN_LEN, MIN_USES>> for LazyData<D>
+{
+    fn prepare(&mut self, _tape: &Tape<Lazy<MIN_LEN, MIN_USES>>, _n: usize) {
+        // Data is only prepared if the tape is interpreted
+    }
+}
+
+impl<T, J, const MIN_LEN: usize, const MIN_USES: usize>
+    TracingEvaluator<T, Lazy<MIN_LEN, MIN_USES>>
+    for LazyEval<J, MIN_LEN, MIN_USES>
+where
+    J: TracingEvaluator<T, Eval> + EvaluatorStorage<Eval, Storage = Mmap>,
+    vm::AsmEval: TracingEvaluator<T, vm::Eval>,
+{
+    type Data = LazyData<<vm::AsmEval as TracingEvaluator<T, vm::Eval>>::Data>;
+
+    fn eval_with(
+        &self,
+        x: T,
+        y: T,
+        z: T,
+        vars: &[f32],
+        choices: &mut [Choice],
+        data: &mut Self::Data,
+    ) -> (T, bool) {
+        match self.jit() {
+            Some(j) => {
+                j.eval_with(x, y, z, vars, choices, &mut Default::default())
+            }
+            None => {
+                data.0.prepare(&self.vm_tape);
+                TracingEvaluator::<T, vm::Eval>::eval_with(
+                    &self.vm,
+                    x,
+                    y,
+                    z,
+                    vars,
+                    choices,
+                    &mut data.0,
+                )
+            }
+        }
+    }
+}
+
+impl<T, J, const MIN_LEN: usize, const MIN_USES: usize>
+    BulkEvaluator<T, Lazy<MIN_LEN, MIN_USES>> for LazyEval<J, MIN_LEN, MIN_USES>
+where
+    J: BulkEvaluator<T, Eval> + EvaluatorStorage<Eval, Storage = Mmap>,
+    vm::AsmEval: BulkEvaluator<T, vm::Eval>,
+{
+    type Data = LazyData<<vm::AsmEval as BulkEvaluator<T, vm::Eval>>::Data>;
+
+    fn eval_with(
+        &self,
+        x: &[f32],
+        y: &[f32],
+        z: &[f32],
+        vars: &[f32],
+        out: &mut [T],
+        data: &mut Self::Data,
+    ) {
+        match self.jit() {
+            Some(j) => j.eval_with(x, y, z, vars, out, &mut Default::default()),
+            None => {
+                data.0.prepare(&self.vm_tape, x.len());
+                BulkEvaluator::<T, vm::Eval>::eval_with(
+                    &self.vm,
+                    x,
+                    y,
+                    z,
+                    vars,
+                    out,
+                    &mut data.0,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    #[test]
+    fn test_lazy_policy() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let sum = ctx.add(x, y).unwrap();
+
+        // Short tapes are compiled after being used enough times
+        let tape = ctx.get_tape::<Lazy<16, 3>>(sum).unwrap();
+        let eval = <Lazy<16, 3> as Family>::PointEval::new_with_storage(
+            &tape,
+            Mmap::default(),
+        );
+        let mut data = Default::default();
+        for i in 0..4 {
+            let (v, _) =
+                eval.eval_with(1.0, i as f32, 0.0, &[], &mut [], &mut data);
+            assert_eq!(v, 1.0 + i as f32);
+            assert_eq!(eval.is_compiled(), i >= 2);
+        }
+
+        // Long tapes are compiled immediately
+        let tape = ctx.get_tape::<Lazy<2, 100>>(sum).unwrap();
+        let eval = <Lazy<2, 100> as Family>::FloatSliceEval::new_with_storage(
+            &tape,
+            Mmap::default(),
+        );
+        assert!(eval.is_compiled());
+        assert!(eval.take().is_some());
+
+        // Storage is returned even if the tape was never compiled
+        let tape = ctx.get_tape::<Lazy>(sum).unwrap();
+        let eval = <Lazy as Family>::IntervalEval::new_with_storage(
+            &tape,
+            Mmap::default(),
+        );
+        assert!(!eval.is_compiled());
+        assert!(eval.take().is_some());
+    }
+
+    mod interpreted {
+        crate::grad_slice_tests!(crate::jit::Lazy<{ usize::MAX }, { usize::MAX }>);
+        crate::hessian_slice_tests!(crate::jit::Lazy<{ usize::MAX }, { usize::MAX }>);
+        crate::interval_tests!(crate::jit::Lazy<{ usize::MAX }, { usize::MAX }>);
+        crate::interval_slice_tests!(crate::jit::Lazy<{ usize::MAX }, { usize::MAX }>);
+        crate::float_slice_tests!(crate::jit::Lazy<{ usize::MAX }, { usize::MAX }>);
+        crate::point_tests!(crate::jit::Lazy<{ usize::MAX }, { usize::MAX }>);
+    }
+
+    mod compiled {
+        crate::grad_slice_tests!(crate::jit::Lazy<0, 0>);
+        crate::interval_tests!(crate::jit::Lazy<0, 0>);
+        crate::interval_slice_tests!(crate::jit::Lazy<0, 0>);
+        crate::float_slice_tests!(crate::jit::Lazy<0, 0>);
+        crate::point_tests!(crate::jit::Lazy<0, 0>);
+    }
+
+    mod mixed {
+        crate::interval_tests!(crate::jit::Lazy<8, 2>);
+        crate::point_tests!(crate::jit::Lazy<8, 2>);
+    }
+}
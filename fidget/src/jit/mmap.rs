@@ -1,6 +1,20 @@
+//! Memory-mapped regions for executable code
+//!
+//! Each compiled function is assembled into its own [`Mmap`].  Small functions
+//! are then copied into a [`PooledFn`], which packs many functions into shared
+//! chunks of executable pages; when thousands of tiny simplified tapes are
+//! live, this saves a great deal of memory (and TLB entries) compared to
+//! giving each one a page of its own.
+use std::{
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
 pub struct Mmap {
     ptr: *mut libc::c_void,
     len: usize,
+    /// Number of bytes of code, set by `finalize`
+    code_len: usize,
 }
 
 // SAFETY: this is philosophically a `Vec<u8>`, so can be sent to other threads
@@ -13,10 +27,11 @@ impl Default for Mmap {
 }
 
 impl Mmap {
-    pub fn empty() -> Self {
+    pub const fn empty() -> Self {
         Self {
             ptr: std::ptr::null_mut::<libc::c_void>(),
             len: 0,
+            code_len: 0,
         }
     }
 
@@ -42,7 +57,23 @@ impl Mmap {
         if ptr == libc::MAP_FAILED {
             Err(std::io::Error::last_os_error())
         } else {
-            Ok(Self { ptr, len })
+            Ok(Self {
+                ptr,
+                len,
+                code_len: 0,
+            })
+        }
+    }
+
+    /// Returns `prev` if it has storage, or this thread's scratch `Mmap`
+    ///
+    /// The scratch `Mmap` is left behind when code is moved into the shared
+    /// pool (see [`Code::new`]), so that it can be reused for assembly.
+    pub fn scratch(prev: Self) -> Self {
+        if prev.len > 0 {
+            prev
+        } else {
+            SCRATCH.with(Cell::take)
         }
     }
 
@@ -51,8 +82,17 @@ impl Mmap {
         self.len
     }
 
+    /// Returns the number of bytes of code, as passed to `finalize`
+    #[inline(always)]
+    pub fn code_len(&self) -> usize {
+        self.code_len
+    }
+
     #[inline(always)]
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.len == 0 {
+            return &mut [];
+        }
         unsafe { std::slice::from_raw_parts_mut(self.ptr as *mut u8, self.len) }
     }
 
@@ -69,8 +109,14 @@ impl Mmap {
     }
 
     /// Treats the memory-mapped data as a slice
+    ///
+    /// An empty map has a null pointer, so it returns an empty slice without
+    /// touching the pointer.
     #[inline(always)]
     pub fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
         unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
     }
 
@@ -92,9 +138,15 @@ impl Mmap {
     ///
     /// Note that you will still need to change the global W^X mode before
     /// evaluation, but that's on a per-thread (rather than per-mmap) basis.
-    pub fn finalize(&self, size: usize) {
+    pub fn finalize(&mut self, size: usize) {
+        self.code_len = size;
+        Self::invalidate(self.ptr, size);
+    }
+
+    /// Invalidates the caches for `size` bytes starting at `ptr`
+    pub fn invalidate(ptr: *mut libc::c_void, size: usize) {
         unsafe {
-            macos::sys_icache_invalidate(self.ptr, size);
+            macos::sys_icache_invalidate(ptr, size);
         }
     }
 
@@ -136,8 +188,9 @@ impl Mmap {
     ///
     /// The former is a no-op on systems with coherent D/I-caches (i.e. x86);
     /// the latter is a no-op if the `write-xor-execute` feature is not enabled.
-    pub fn finalize(&self, size: usize) {
-        self.flush_cache(size);
+    pub fn finalize(&mut self, size: usize) {
+        self.code_len = size;
+        Self::invalidate(self.ptr, size);
 
         // This is deliberately done as a cfg! conditional (instead of #[cfg]),
         // so that the code is type-checked even if the feature is disabled.
@@ -152,8 +205,11 @@ impl Mmap {
         }
     }
 
+    /// Flushes caches for `size` bytes starting at `ptr`
+    ///
+    /// This is a no-op on systems with coherent D/I-caches (i.e. x86).
     #[cfg(target_arch = "aarch64")]
-    fn flush_cache(&self, size: usize) {
+    pub fn invalidate(ptr: *mut libc::c_void, size: usize) {
         use std::arch::asm;
         let mut cache_type: usize;
         // Loosely based on code from mono; see mono/mono#3549 for a good
@@ -167,8 +223,8 @@ impl Mmap {
         let icache_line_size = (cache_type & 0xF) << 4;
         let dcache_line_size = ((cache_type >> 16) & 0xF) << 4;
 
-        let mut addr = self.as_ptr() as usize & !(dcache_line_size - 1);
-        let end = self.as_ptr() as usize + size;
+        let mut addr = ptr as usize & !(dcache_line_size - 1);
+        let end = ptr as usize + size;
         while addr < end {
             unsafe {
                 asm!(
@@ -182,7 +238,7 @@ impl Mmap {
             asm!("dsb ish");
         }
 
-        let mut addr = self.as_ptr() as usize & !(icache_line_size - 1);
+        let mut addr = ptr as usize & !(icache_line_size - 1);
         while addr < end {
             unsafe {
                 asm!(
@@ -198,7 +254,7 @@ impl Mmap {
     }
 
     #[cfg(not(target_arch = "aarch64"))]
-    fn flush_cache(&self, _size: usize) {
+    pub fn invalidate(_ptr: *mut libc::c_void, _size: usize) {
         // Nothing to do here
     }
 
@@ -236,6 +292,303 @@ impl Drop for Mmap {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+
+thread_local! {
+    /// Spare `Mmap` for assembly, left behind when code is pooled
+    static SCRATCH: Cell<Mmap> = const { Cell::new(Mmap::empty()) };
+}
+
+/// Size of each chunk of shared executable memory
+const CHUNK_SIZE: usize = 16 * Mmap::PAGE_SIZE;
+
+/// Size (and alignment) of each block within a chunk, which is a cache line
+const BLOCK_SIZE: usize = 64;
+
+/// Number of blocks in each chunk
+const BLOCK_COUNT: usize = CHUNK_SIZE / BLOCK_SIZE;
+
+/// Functions up to this size are packed into shared chunks
+const MAX_POOLED_SIZE: usize = Mmap::PAGE_SIZE / 2;
+
+/// Whether functions may be packed into shared chunks
+///
+/// This is disabled if `write-xor-execute` is enabled on Linux, because chunks
+/// must be writable while other functions within them are running.  (macOS
+/// doesn't have this problem, because its W^X state is per-thread)
+const POOLING: bool =
+    !cfg!(all(target_os = "linux", feature = "write-xor-execute"));
+
+/// Shared chunks of executable memory
+static POOL: Mutex<Vec<Arc<Chunk>>> = Mutex::new(Vec::new());
+
+/// A chunk of executable memory, divided into fixed-size blocks
+struct Chunk {
+    mmap: Mmap,
+    /// Bitmap of blocks which are in use
+    used: Mutex<[u64; BLOCK_COUNT / 64]>,
+}
+
+// SAFETY: the `Mmap` is only written through blocks which have been allocated
+// (under the `used` lock) to a single `PooledFn`
+unsafe impl Sync for Chunk {}
+
+impl Chunk {
+    fn new() -> Option<Self> {
+        Some(Self {
+            mmap: Mmap::new(CHUNK_SIZE).ok()?,
+            used: Mutex::new([0; BLOCK_COUNT / 64]),
+        })
+    }
+
+    /// Allocates a run of `n` blocks, returning the index of the first block
+    fn alloc(&self, n: usize) -> Option<usize> {
+        let mut used = self.used.lock().unwrap();
+        let mut run = 0;
+        for i in 0..BLOCK_COUNT {
+            if used[i / 64] & (1 << (i % 64)) != 0 {
+                run = 0;
+                continue;
+            }
+            run += 1;
+            if run == n {
+                let start = i + 1 - n;
+                for j in start..=i {
+                    used[j / 64] |= 1 << (j % 64);
+                }
+                return Some(start);
+            }
+        }
+        None
+    }
+
+    /// Releases a run of `n` blocks, starting at block `start`
+    fn free(&self, start: usize, n: usize) {
+        let mut used = self.used.lock().unwrap();
+        for j in start..start + n {
+            debug_assert!(used[j / 64] & (1 << (j % 64)) != 0);
+            used[j / 64] &= !(1 << (j % 64));
+        }
+    }
+}
+
+/// A function which has been copied into a shared chunk of executable memory
+///
+/// The function's blocks are released when this is dropped.
+pub struct PooledFn {
+    chunk: Arc<Chunk>,
+    start: usize,
+    /// Length of the function, in bytes
+    len: usize,
+}
+
+impl PooledFn {
+    /// Copies finalized code into a shared chunk
+    ///
+    /// Returns `None` if the code is too large (or empty), if pooling is
+    /// disabled, or if a new chunk couldn't be mapped.
+    pub fn new(code: &[u8]) -> Option<Self> {
+        if !POOLING || code.is_empty() || code.len() > MAX_POOLED_SIZE {
+            return None;
+        }
+        let blocks = code.len().div_ceil(BLOCK_SIZE);
+
+        let mut pool = POOL.lock().unwrap();
+        // Unmap empty chunks, keeping one spare.  A chunk's only reference
+        // may be the pool's, in which case it's empty; references are only
+        // added while the pool is locked, so this check is conservative.
+        let mut spare = false;
+        pool.retain(|c| {
+            Arc::strong_count(c) > 1 || !std::mem::replace(&mut spare, true)
+        });
+        let found = pool
+            .iter()
+            .find_map(|c| c.alloc(blocks).map(|start| (c.clone(), start)));
+        let (chunk, start) = match found {
+            Some(f) => f,
+            None => {
+                let c = Arc::new(Chunk::new()?);
+                let start = c.alloc(blocks).unwrap();
+                pool.push(c.clone());
+                (c, start)
+            }
+        };
+        drop(pool);
+
+        // This guard may be a unit value on some systems
+        #[allow(clippy::let_unit_value)]
+        let _guard = Mmap::thread_mode_write();
+        let out = Self {
+            chunk,
+            start,
+            len: code.len(),
+        };
+        let ptr = out.as_ptr();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                code.as_ptr(),
+                ptr as *mut u8,
+                code.len(),
+            );
+        }
+        Mmap::invalidate(ptr, code.len());
+        Some(out)
+    }
+
+    /// Returns a pointer to the start of the function
+    pub fn as_ptr(&self) -> *mut libc::c_void {
+        unsafe { self.chunk.mmap.as_ptr().add(self.start * BLOCK_SIZE) }
+    }
+}
+
+impl Drop for PooledFn {
+    fn drop(&mut self) {
+        self.chunk.free(self.start, self.len.div_ceil(BLOCK_SIZE));
+    }
+}
+
+/// Executable code for a compiled function
+pub enum Code {
+    /// Code in its own `Mmap`, which can be reused for assembly
+    Owned(Mmap),
+    /// Code packed into a shared chunk
+    Pooled(PooledFn),
+}
+
+// SAFETY: an `Owned` map is never modified once it has been finalized, and a
+// `PooledFn`'s blocks are only written before it's returned by `new`
+unsafe impl Sync for Code {}
+
+impl Code {
+    /// Takes ownership of finalized code, moving it into a shared chunk if
+    /// it's small enough
+    ///
+    /// If the code is moved, then `mmap` is kept as the thread's scratch map
+    /// (see [`Mmap::scratch`]).
+    pub fn new(mmap: Mmap) -> Self {
+        match PooledFn::new(&mmap.as_slice()[..mmap.code_len()]) {
+            Some(f) => {
+                SCRATCH.with(|s| {
+                    let prev = s.take();
+                    s.set(if prev.len() > mmap.len() { prev } else { mmap });
+                });
+                Code::Pooled(f)
+            }
+            None => Code::Owned(mmap),
+        }
+    }
+
+    /// Returns a pointer to the start of the function
+    pub fn as_ptr(&self) -> *mut libc::c_void {
+        match self {
+            Code::Owned(m) => m.as_ptr(),
+            Code::Pooled(f) => f.as_ptr(),
+        }
+    }
+
+    /// Returns the function's machine code
+    #[cfg(feature = "disasm")]
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            Code::Owned(m) => &m.as_slice()[..m.code_len()],
+            Code::Pooled(f) => unsafe {
+                std::slice::from_raw_parts(f.as_ptr() as *const u8, f.len)
+            },
+        }
+    }
+
+    /// Returns an `Mmap` for reuse as assembler storage
+    ///
+    /// Pooled code doesn't have an `Mmap` of its own, so this returns the
+    /// thread's scratch map instead (which may be empty).
+    pub fn take(self) -> Mmap {
+        match self {
+            Code::Owned(m) => m,
+            Code::Pooled(..) => SCRATCH.with(Cell::take),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_empty_slice() {
+        let mut m = Mmap::empty();
+        assert!(m.as_slice().is_empty());
+        assert!(m.as_mut_slice().is_empty());
+    }
+
+    #[test]
+    fn test_pooled_fn() {
+        let code: Vec<u8> = (0..100).collect();
+        if !POOLING {
+            assert!(PooledFn::new(&code).is_none());
+            return;
+        }
+        let fns: Vec<_> =
+            (0..8).map(|_| PooledFn::new(&code).unwrap()).collect();
+        for f in &fns {
+            assert_eq!(f.as_ptr() as usize % BLOCK_SIZE, 0);
+            assert_eq!(f.len, 100);
+            let data = unsafe {
+                std::slice::from_raw_parts(f.as_ptr() as *const u8, 100)
+            };
+            assert_eq!(data, code.as_slice());
+        }
+
+        // Functions share chunks, without overlapping
+        let mut chunks: Vec<_> =
+            fns.iter().map(|f| Arc::as_ptr(&f.chunk)).collect();
+        chunks.dedup();
+        assert!(chunks.len() < fns.len());
+        let mut ptrs: Vec<_> =
+            fns.iter().map(|f| f.as_ptr() as usize).collect();
+        ptrs.sort();
+        assert!(ptrs.windows(2).all(|w| w[1] - w[0] >= 2 * BLOCK_SIZE));
+
+        // Large and empty functions aren't pooled
+        assert!(PooledFn::new(&[]).is_none());
+        assert!(PooledFn::new(&vec![0; MAX_POOLED_SIZE + 1]).is_none());
+    }
+
+    #[test]
+    fn test_chunk_alloc() {
+        let chunk = Chunk::new().unwrap();
+        assert_eq!(chunk.alloc(3), Some(0));
+        assert_eq!(chunk.alloc(1), Some(3));
+        assert_eq!(chunk.alloc(BLOCK_COUNT), None);
+        chunk.free(0, 3);
+        assert_eq!(chunk.alloc(2), Some(0));
+        assert_eq!(chunk.alloc(2), Some(4));
+        assert_eq!(chunk.alloc(1), Some(2));
+        assert_eq!(chunk.alloc(BLOCK_COUNT - 6), Some(6));
+        assert_eq!(chunk.alloc(1), None);
+    }
+
+    #[test]
+    fn test_code() {
+        if !POOLING {
+            return;
+        }
+        let mut small = Mmap::new(0).unwrap();
+        small.as_mut_slice()[..4].copy_from_slice(&[1, 2, 3, 4]);
+        small.finalize(4);
+        let code = Code::new(small);
+        assert!(matches!(code, Code::Pooled(..)));
+        // The map was kept for reuse
+        assert_eq!(code.take().len(), Mmap::PAGE_SIZE);
+        assert_eq!(Mmap::scratch(Mmap::empty()).len(), 0);
+
+        let mut large = Mmap::new(MAX_POOLED_SIZE + 1).unwrap();
+        large.finalize(MAX_POOLED_SIZE + 1);
+        let code = Code::new(large);
+        assert!(matches!(code, Code::Owned(..)));
+        assert_eq!(code.take().code_len(), MAX_POOLED_SIZE + 1);
+    }
+}
+
 #[cfg(target_os = "macos")]
 mod macos {
     /// Empty struct which switches the thread to execute mode when dropped
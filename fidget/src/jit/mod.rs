@@ -16,13 +16,21 @@
 //! assert_eq!(eval.eval(0.1, 0.3, 0.0, &[])?.0, 0.1 + 0.3);
 //! # Ok::<(), fidget::Error>(())
 //! ```
+//!
+//! To evaluate several small tapes at the same points, [`FusedEval`] compiles
+//! them into a single function with one output per tape.
+//!
+//! With the `disasm` feature enabled, [`JitTracingEval`], [`JitBulkEval`], and
+//! [`FusedEval`] have a `disassemble` method which prints their machine code;
+//! please include this output when reporting bugs in code generation.
 
 use crate::{
     eval::{
-        bulk::BulkEvaluator, tape::Data as TapeData, tracing::TracingEvaluator,
-        Choice, EvaluatorStorage, Family, Tape,
+        bulk::BulkEvaluator, interval_slice::TracingSliceEval,
+        tape::Data as TapeData, tracing::TracingEvaluator, Choice, CustomOp,
+        EvaluatorStorage, Family, NanPolicy, Tape,
     },
-    jit::mmap::Mmap,
+    jit::mmap::{Code, Mmap},
     vm::Op,
     Error,
 };
@@ -32,8 +40,12 @@ use dynasmrt::{
 };
 use std::sync::Arc;
 
+mod lazy;
 mod mmap;
 
+#[cfg(feature = "disasm")]
+mod disasm;
+
 // Evaluators
 mod float_slice;
 mod grad_slice;
@@ -59,6 +71,44 @@ use x86_64 as arch;
 /// Number of registers available when executing natively
 const REGISTER_LIMIT: u8 = arch::REGISTER_LIMIT;
 
+/// Maximum stack space reserved for spilled slots, in bytes
+///
+/// This is limited by the 12-bit immediate in `sub sp, sp, #imm`, so frames
+/// never span more than one page.
+#[cfg(target_arch = "aarch64")]
+const MAX_STACK_SIZE: usize = 4080;
+
+/// Maximum stack space reserved for spilled slots, in bytes
+///
+/// This keeps frames well within the default thread stack size; frames which
+/// are larger than a page are probed as they're reserved.
+#[cfg(target_arch = "x86_64")]
+const MAX_STACK_SIZE: usize = 1 << 20;
+
+/// Size of the largest stack slot, in bytes
+///
+/// This is used by the float slice evaluator (`[f32; SIMD_WIDTH]`) or the
+/// gradient slice evaluator (`[f32; 4]`), whichever is larger.
+const MAX_SLOT_SIZE: usize = 4 * if arch::float_slice::SIMD_WIDTH > 4 {
+    arch::float_slice::SIMD_WIDTH
+} else {
+    4
+};
+
+/// Maximum number of slots in a tape which can be compiled
+///
+/// Every evaluator reserves up to four extra stack slots (for X, Y, Z on
+/// `x86_64`), which are subtracted here.
+const SLOT_LIMIT: usize =
+    REGISTER_LIMIT as usize + MAX_STACK_SIZE / MAX_SLOT_SIZE - 4;
+
+/// Maximum combined size of the variable array and constant table
+///
+/// Both are read with `ldr s, [x, #imm]` on `aarch64`, where the offset is a
+/// 12-bit immediate (scaled by the size of an `f32`).  Tapes with more
+/// constants are compiled with inline immediates instead.
+const MAX_TABLE_SIZE: usize = 4096;
+
 /// Offset before the first useable register
 const OFFSET: u8 = arch::OFFSET;
 
@@ -162,16 +212,100 @@ pub trait AssemblerT {
 
     /// Maximum of two values
     ///
-    /// In a tracing evaluator, this function must also write to the `choices`
-    /// array and may set `simplify` if one branch is always taken.
+    /// In a tracing evaluator, this function must also assign to the
+    /// `choices` array (without reading its previous value) and may set
+    /// `simplify` if one branch is always taken.
     fn build_max(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
 
     /// Minimum of two values
     ///
-    /// In a tracing evaluator, this function must also write to the `choices`
-    /// array and may set `simplify` if one branch is always taken.
+    /// In a tracing evaluator, this function must also assign to the
+    /// `choices` array (without reading its previous value) and may set
+    /// `simplify` if one branch is always taken.
     fn build_min(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
 
+    /// Maximum of two values, which doesn't make a choice
+    ///
+    /// Unlike [`build_max`](Self::build_max), this must not write to the
+    /// `choices` array or set `simplify`.
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
+
+    /// Minimum of two values, which doesn't make a choice
+    ///
+    /// Unlike [`build_min`](Self::build_min), this must not write to the
+    /// `choices` array or set `simplify`.
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
+
+    /// Sine
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8);
+
+    /// Cosine
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8);
+
+    /// Four-quadrant arctangent, `atan2(lhs_reg, rhs_reg)`
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
+
+    /// Hypotenuse, `sqrt(lhs_reg² + rhs_reg²)`
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
+
+    /// Floored modulo, `lhs_reg - rhs_reg * floor(lhs_reg / rhs_reg)`
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8);
+
+    /// User-defined operation, `op(lhs_reg, rhs_reg)`
+    ///
+    /// `op` points into the tape's list of operations, which is kept alive
+    /// by the evaluator.
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    );
+
+    /// Gradient noise, `noise3(x_reg, y_reg, z_reg, seed)`
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    );
+
+    /// Clamp, `max(min(x_reg, hi_reg), lo_reg)`
+    ///
+    /// Unlike [`build_min`](Self::build_min) and
+    /// [`build_max`](Self::build_max), this must not write to the `choices`
+    /// array.
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8);
+
+    /// Multiply-add (`a_reg * b_reg + c_reg`)
+    ///
+    /// The default implementation multiplies into the immediate register,
+    /// then adds.  Assemblers which can round the result once (i.e. point and
+    /// float slice assemblers) should overload it with a fused instruction.
+    ///
+    /// `widen` is set on tapes with
+    /// [conservative intervals](crate::eval::Tape::with_conservative_intervals),
+    /// in which case an intermediate product must be widened like any other
+    /// inexact result.
+    fn build_fma(
+        &mut self,
+        out_reg: u8,
+        a_reg: u8,
+        b_reg: u8,
+        c_reg: u8,
+        widen: bool,
+    ) {
+        let tmp = IMM_REG.wrapping_sub(OFFSET);
+        self.build_mul(tmp, a_reg, b_reg);
+        if widen {
+            self.build_widen(tmp);
+        }
+        self.build_add(out_reg, tmp, c_reg);
+    }
+
     // Special-case functions for immediates.  In some cases, you can be more
     // efficient if you know that an argument is an immediate (for example, both
     // values in the interval will be the same, and it wlll have no gradients).
@@ -205,6 +339,27 @@ pub trait AssemblerT {
         self.build_mul(out_reg, lhs_reg, imm);
     }
 
+    /// Widens the value in a register to absorb rounding error
+    ///
+    /// This is called after every inexact operation on tapes with
+    /// [conservative intervals](crate::eval::Tape::with_conservative_intervals)
+    /// enabled, and must match
+    /// [`Interval::widen`](crate::eval::types::Interval::widen).
+    /// The default implementation does nothing, which is correct for
+    /// non-interval assemblers.
+    fn build_widen(&mut self, _out_reg: u8) {
+        // Nothing to do here
+    }
+
+    /// Replaces `NaN` arguments to `min` or `max` with empty space
+    ///
+    /// This is called before `build_min` and `build_max` on tapes using
+    /// [`NanPolicy::Empty`], and must match its documented semantics.  The
+    /// sanitized arguments are written to scratch registers, which are
+    /// returned (as `(lhs, rhs)`) and must not be clobbered by the subsequent
+    /// `build_min` or `build_max` before they are read.
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8);
+
     /// Loads an immediate into a register, returning that register
     fn load_imm(&mut self, imm: f32) -> u8;
 
@@ -249,9 +404,11 @@ impl<T> AssemblerData<T> {
         let stack_slots = slot_count - REGISTER_LIMIT as usize;
         let mem = (stack_slots + 1) * std::mem::size_of::<T>();
 
-        // Round up to the nearest multiple of 16 bytes, for alignment
+        // Round up to the nearest multiple of 16 bytes, for alignment.  This
+        // is less than a page (see `MAX_STACK_SIZE`), so it doesn't need to be
+        // probed.
         self.mem_offset = ((mem + 15) / 16) * 16;
-        assert!(self.mem_offset < 4096);
+        assert!(self.mem_offset <= MAX_STACK_SIZE);
         dynasm!(self.ops
             ; sub sp, sp, #(self.mem_offset as u32)
         );
@@ -269,9 +426,29 @@ impl<T> AssemblerData<T> {
 
         // Round up to the nearest multiple of 16 bytes, for alignment
         self.mem_offset = ((mem + 15) / 16) * 16;
-        dynasm!(self.ops
-            ; sub rsp, self.mem_offset as i32
-        );
+        assert!(self.mem_offset <= MAX_STACK_SIZE);
+
+        // Touch every page as the stack grows, so that a large frame can't
+        // skip over the guard page (and write to whatever is below it).  This
+        // clobbers `eax`, which isn't used for arguments.
+        const PROBE_SIZE: usize = 4096;
+        let pages = self.mem_offset / PROBE_SIZE;
+        if pages > 0 {
+            dynasm!(self.ops
+                ; mov eax, pages as i32
+                ; P:
+                ; sub rsp, PROBE_SIZE as i32
+                ; or QWORD [rsp], 0
+                ; dec eax
+                ; jnz <P
+            );
+        }
+        let rest = self.mem_offset % PROBE_SIZE;
+        if rest > 0 {
+            dynasm!(self.ops
+                ; sub rsp, rest as i32
+            );
+        }
     }
 
     fn stack_pos(&self, slot: u32) -> u32 {
@@ -580,13 +757,80 @@ impl From<Mmap> for MmapAssembler {
 
 /////////////////////////////////////////////////////////////////////////////////////////
 
-fn build_asm_fn_with_storage<A: AssemblerT>(t: &TapeData, s: Mmap) -> Mmap {
+/// Returns the constant table for a tape, or `None` if its immediates should
+/// be compiled inline
+///
+/// See [`Tape::with_constant_table`] for details.
+fn constant_table(t: &TapeData) -> Option<Arc<[f32]>> {
+    if !t.constant_table() {
+        return None;
+    }
+    let table = t.immediates();
+    (t.var_count() + table.len() <= MAX_TABLE_SIZE).then(|| table.into())
+}
+
+/// Compiles a tape into a memory-mapped function
+///
+/// If `table` is true, immediates are loaded from the variable array, in the
+/// order returned by [`TapeData::immediates`], starting after the tape's
+/// variables; otherwise, they're compiled into the function.
+fn build_asm_fn_with_storage<A: AssemblerT>(
+    t: &TapeData,
+    table: bool,
+    s: Mmap,
+) -> Mmap {
+    span!(DEBUG, "jit_compile", len = t.len());
+
     // This guard may be a unit value on some systems
     #[allow(clippy::let_unit_value)]
     let _guard = Mmap::thread_mode_write();
 
+    let s = Mmap::scratch(s);
     s.make_write();
     let mut asm = A::init(s, t.slot_count());
+    build_tape(&mut asm, t, table, |i| i);
+    asm.finalize(0).expect("failed to build JIT function")
+    // JIT execute mode is restored here when the _guard is dropped
+}
+
+/// Emits code for every operation in a tape
+///
+/// `table` is as described in [`build_asm_fn_with_storage`], and `var_map`
+/// converts from the tape's variable indexes to indexes in the variable array
+/// passed to the function.
+fn build_tape<A: AssemblerT>(
+    asm: &mut A,
+    t: &TapeData,
+    table: bool,
+    var_map: impl Fn(u32) -> u32,
+) {
+    let conservative = t.conservative_intervals();
+
+    // Applies the tape's NaN policy to the arguments of a min or max
+    let empty = t.nan_policy() == NanPolicy::Empty;
+    let nan_args = |asm: &mut A, lhs, rhs| {
+        if empty {
+            asm.build_nan_to_empty(lhs, rhs)
+        } else {
+            (lhs, rhs)
+        }
+    };
+
+    // Loads an immediate into a register, either from the constant table or
+    // by compiling it into the function.  When using a constant table, the
+    // immediate's value must not change the generated code, so specialized
+    // `build_*_imm` functions are skipped.
+    let mut next = t.var_count() as u32;
+    let mut load_imm = |asm: &mut A, imm: f32| {
+        if table {
+            let reg = IMM_REG.wrapping_sub(OFFSET);
+            asm.build_var(reg, next);
+            next += 1;
+            reg
+        } else {
+            asm.load_imm(imm)
+        }
+    };
 
     for op in t.iter_asm() {
         match op {
@@ -600,7 +844,7 @@ fn build_asm_fn_with_storage<A: AssemblerT>(t: &TapeData, s: Mmap) -> Mmap {
                 asm.build_input(out, i);
             }
             Op::Var(out, i) => {
-                asm.build_var(out, i);
+                asm.build_var(out, var_map(i));
             }
             Op::NegReg(out, arg) => {
                 asm.build_neg(out, arg);
@@ -617,6 +861,12 @@ fn build_asm_fn_with_storage<A: AssemblerT>(t: &TapeData, s: Mmap) -> Mmap {
             Op::CopyReg(out, arg) => {
                 asm.build_copy(out, arg);
             }
+            Op::SinReg(out, arg) => {
+                asm.build_sin(out, arg);
+            }
+            Op::CosReg(out, arg) => {
+                asm.build_cos(out, arg);
+            }
             Op::SquareReg(out, arg) => {
                 asm.build_square(out, arg);
             }
@@ -632,61 +882,152 @@ fn build_asm_fn_with_storage<A: AssemblerT>(t: &TapeData, s: Mmap) -> Mmap {
             Op::SubRegReg(out, lhs, rhs) => {
                 asm.build_sub(out, lhs, rhs);
             }
+            Op::FmaRegRegReg(out, a, b, c) => {
+                asm.build_fma(out, a, b, c, conservative);
+            }
+            Op::Atan2RegReg(out, lhs, rhs) => {
+                asm.build_atan2(out, lhs, rhs);
+            }
+            Op::HypotRegReg(out, lhs, rhs) => {
+                asm.build_hypot(out, lhs, rhs);
+            }
+            Op::ModRegReg(out, lhs, rhs) => {
+                asm.build_mod(out, lhs, rhs);
+            }
+            Op::CustomRegReg(out, lhs, rhs, i) => {
+                let op = &t.custom_ops()[i as usize];
+                asm.build_custom(out, lhs, rhs, op);
+            }
+            Op::NoiseRegRegReg(out, x, y, z, seed) => {
+                asm.build_noise(out, x, y, z, seed);
+            }
+            Op::ClampRegRegReg(out, x, lo, hi) => {
+                asm.build_clamp(out, x, lo, hi);
+            }
             Op::MinRegReg(out, lhs, rhs) => {
+                let (lhs, rhs) = nan_args(asm, lhs, rhs);
                 asm.build_min(out, lhs, rhs);
             }
             Op::MaxRegReg(out, lhs, rhs) => {
+                let (lhs, rhs) = nan_args(asm, lhs, rhs);
                 asm.build_max(out, lhs, rhs);
             }
+            Op::MinNcRegReg(out, lhs, rhs) => {
+                let (lhs, rhs) = nan_args(asm, lhs, rhs);
+                asm.build_min_nc(out, lhs, rhs);
+            }
+            Op::MaxNcRegReg(out, lhs, rhs) => {
+                let (lhs, rhs) = nan_args(asm, lhs, rhs);
+                asm.build_max_nc(out, lhs, rhs);
+            }
+            Op::AddRegImm(out, arg, imm) if table => {
+                let reg = load_imm(asm, imm);
+                asm.build_add(out, arg, reg);
+            }
             Op::AddRegImm(out, arg, imm) => {
                 asm.build_add_imm(out, arg, imm);
             }
+            Op::MulRegImm(out, arg, imm) if table => {
+                let reg = load_imm(asm, imm);
+                asm.build_mul(out, arg, reg);
+            }
             Op::MulRegImm(out, arg, imm) => {
                 asm.build_mul_imm(out, arg, imm);
             }
             Op::DivRegImm(out, arg, imm) => {
-                let reg = asm.load_imm(imm);
+                let reg = load_imm(asm, imm);
                 asm.build_div(out, arg, reg);
             }
             Op::DivImmReg(out, arg, imm) => {
-                let reg = asm.load_imm(imm);
+                let reg = load_imm(asm, imm);
                 asm.build_div(out, reg, arg);
             }
+            Op::Atan2RegImm(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                asm.build_atan2(out, arg, reg);
+            }
+            Op::Atan2ImmReg(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                asm.build_atan2(out, reg, arg);
+            }
+            Op::HypotRegImm(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                asm.build_hypot(out, arg, reg);
+            }
+            Op::ModRegImm(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                asm.build_mod(out, arg, reg);
+            }
+            Op::ModImmReg(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                asm.build_mod(out, reg, arg);
+            }
+            Op::SubImmReg(out, arg, imm) if table => {
+                let reg = load_imm(asm, imm);
+                asm.build_sub(out, reg, arg);
+            }
             Op::SubImmReg(out, arg, imm) => {
                 asm.build_sub_imm_reg(out, arg, imm);
             }
+            Op::SubRegImm(out, arg, imm) if table => {
+                let reg = load_imm(asm, imm);
+                asm.build_sub(out, arg, reg);
+            }
             Op::SubRegImm(out, arg, imm) => {
                 asm.build_sub_reg_imm(out, arg, imm);
             }
             Op::MinRegImm(out, arg, imm) => {
-                let reg = asm.load_imm(imm);
+                let reg = load_imm(asm, imm);
+                let (arg, reg) = nan_args(asm, arg, reg);
                 asm.build_min(out, arg, reg);
             }
             Op::MaxRegImm(out, arg, imm) => {
-                let reg = asm.load_imm(imm);
+                let reg = load_imm(asm, imm);
+                let (arg, reg) = nan_args(asm, arg, reg);
                 asm.build_max(out, arg, reg);
             }
+            Op::MinNcRegImm(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                let (arg, reg) = nan_args(asm, arg, reg);
+                asm.build_min_nc(out, arg, reg);
+            }
+            Op::MaxNcRegImm(out, arg, imm) => {
+                let reg = load_imm(asm, imm);
+                let (arg, reg) = nan_args(asm, arg, reg);
+                asm.build_max_nc(out, arg, reg);
+            }
             Op::CopyImm(out, imm) => {
-                let reg = asm.load_imm(imm);
+                let reg = load_imm(asm, imm);
                 asm.build_copy(out, reg);
             }
         }
+        if let Some(out) = op.rounded_output().filter(|_| conservative) {
+            asm.build_widen(out);
+        }
     }
-
-    asm.finalize(0).expect("failed to build JIT function")
-    // JIT execute mode is restored here when the _guard is dropped
 }
 
+pub use lazy::{Lazy, LazyData, LazyEval};
+
 /// JIT evaluator family
 #[derive(Clone)]
 pub enum Eval {}
 impl Family for Eval {
     const REG_LIMIT: u8 = REGISTER_LIMIT;
+    const SLOT_LIMIT: usize = SLOT_LIMIT;
+    const SCHEDULE: bool = true;
+    const LANE_WIDTH: usize = arch::float_slice::SIMD_WIDTH;
 
     type IntervalEval = interval::JitIntervalEval;
+    /// The JIT doesn't have a native interval slice evaluator, so this calls
+    /// the compiled interval function once per item
+    type IntervalSliceEval = TracingSliceEval<interval::JitIntervalEval>;
     type PointEval = point::JitPointEval;
     type FloatSliceEval = float_slice::JitFloatSliceEval;
     type GradSliceEval = grad_slice::JitGradSliceEval;
+    /// There's no JIT implementation of second derivatives, so this runs the
+    /// tape in the interpreter
+    type HessianSliceEval = crate::vm::Interpreted;
 
     fn tile_sizes_3d() -> &'static [usize] {
         &[64, 16, 8]
@@ -727,15 +1068,76 @@ macro_rules! jit_fn {
     };
 }
 
+// Declared after `jit_fn!`, which they use
+mod call;
+mod fused;
+pub use fused::FusedEval;
+
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Constant table of a JIT function, along with the tape it was built from
+#[derive(Clone)]
+struct ConstantTable {
+    values: Arc<[f32]>,
+    tape: Tape<Eval>,
+}
+
+impl ConstantTable {
+    fn new(tape: &Tape<Eval>) -> Option<Self> {
+        constant_table(tape).map(|values| Self {
+            values,
+            tape: tape.clone(),
+        })
+    }
+
+    /// Returns the constant table for a tape which can reuse this table's
+    /// compiled code, or `None` if the tape needs new code
+    fn rebind(&self, tape: &Tape<Eval>) -> Option<Self> {
+        if self.tape.same_code(tape) {
+            Self::new(tape)
+        } else {
+            None
+        }
+    }
+}
+
+/// Calls `f` with a pointer to the variable array for a JIT function
+///
+/// If the function was compiled with a constant table, then the table is
+/// appended to the variables (see [`build_asm_fn_with_storage`]).
+fn with_vars<R>(
+    vars: &[f32],
+    table: Option<&ConstantTable>,
+    f: impl FnOnce(*const f32) -> R,
+) -> R {
+    /// Size of the on-stack buffer for combined variables and constants
+    const STACK_SIZE: usize = 64;
+    let Some(c) = table.map(|t| &*t.values) else {
+        return f(vars.as_ptr());
+    };
+    if vars.is_empty() {
+        f(c.as_ptr())
+    } else if vars.len() + c.len() <= STACK_SIZE {
+        let mut buf = [0.0; STACK_SIZE];
+        buf[..vars.len()].copy_from_slice(vars);
+        buf[vars.len()..][..c.len()].copy_from_slice(c);
+        f(buf.as_ptr())
+    } else {
+        f([vars, c].concat().as_ptr())
+    }
+}
+
 /// Handle owning a JIT-compiled tracing function of some kind
 ///
 /// Users are unlikely to use this directly; consider using the
 /// [`jit::Eval`](Eval) evaluator family instead.
 pub struct JitTracingEval<I: AssemblerT> {
-    mmap: Arc<Mmap>,
+    code: Arc<Code>,
     var_count: usize,
+    /// Constant table, if immediates aren't compiled into the function
+    table: Option<ConstantTable>,
+    /// User-defined operations, which are called by the compiled function
+    custom: Arc<Vec<Arc<dyn CustomOp>>>,
     fn_trace: jit_fn!(
         unsafe fn(
             I::Data,    // X
@@ -751,32 +1153,57 @@ pub struct JitTracingEval<I: AssemblerT> {
 impl<I: AssemblerT> Clone for JitTracingEval<I> {
     fn clone(&self) -> Self {
         Self {
-            mmap: self.mmap.clone(),
+            code: self.code.clone(),
             var_count: self.var_count,
+            table: self.table.clone(),
+            custom: self.custom.clone(),
             fn_trace: self.fn_trace,
         }
     }
 }
 
 // SAFETY: there is no mutable state in a `JitTracingEval`, and the pointer
-// inside of it points to its own `Mmap`, which is owned by an `Arc`
+// inside of it points to its own `Code`, which is owned by an `Arc` (as are
+// the custom operations which it calls)
 unsafe impl<I: AssemblerT> Send for JitTracingEval<I> {}
 unsafe impl<I: AssemblerT> Sync for JitTracingEval<I> {}
 
+impl<I: AssemblerT> JitTracingEval<I> {
+    /// Disassembles the compiled function, one instruction per line
+    #[cfg(feature = "disasm")]
+    pub fn disassemble(&self) -> String {
+        disasm::disassemble(self.code.as_slice(), self.code.as_ptr() as u64)
+    }
+}
+
 impl<I: AssemblerT> EvaluatorStorage<Eval> for JitTracingEval<I> {
     type Storage = Mmap;
     fn new_with_storage(t: &Tape<Eval>, prev: Self::Storage) -> Self {
-        let mmap = build_asm_fn_with_storage::<I>(t, prev);
-        let ptr = mmap.as_ptr();
+        let table = ConstantTable::new(t);
+        let code =
+            Code::new(build_asm_fn_with_storage::<I>(t, table.is_some(), prev));
+        let ptr = code.as_ptr();
         Self {
-            mmap: Arc::new(mmap),
+            code: Arc::new(code),
             var_count: t.var_count(),
+            table,
+            custom: t.shared_custom_ops(),
             fn_trace: unsafe { std::mem::transmute(ptr) },
         }
     }
 
+    fn rebind(&self, t: &Tape<Eval>) -> Self {
+        match self.table.as_ref().and_then(|table| table.rebind(t)) {
+            Some(table) => Self {
+                table: Some(table),
+                ..self.clone()
+            },
+            None => Self::new_with_storage(t, Default::default()),
+        }
+    }
+
     fn take(self) -> Option<Self::Storage> {
-        Arc::try_unwrap(self.mmap).ok()
+        Arc::try_unwrap(self.code).ok().map(Code::take)
     }
 }
 
@@ -795,16 +1222,17 @@ impl<I: AssemblerT> TracingEvaluator<I::Data, Eval> for JitTracingEval<I> {
     ) -> (I::Data, bool) {
         let mut simplify = 0;
         assert_eq!(vars.len(), self.var_count);
-        let out = unsafe {
+        let out = with_vars(vars, self.table.as_ref(), |vars| unsafe {
             (self.fn_trace)(
                 x,
                 y,
                 z,
-                vars.as_ptr(),
+                vars,
                 choices.as_mut_ptr() as *mut u8,
                 &mut simplify,
             )
-        };
+        });
+        call::resume_custom_panic();
         (out, simplify != 0)
     }
 }
@@ -816,8 +1244,12 @@ impl<I: AssemblerT> TracingEvaluator<I::Data, Eval> for JitTracingEval<I> {
 /// Users are unlikely to use this directly; consider using the
 /// [`jit::Eval`](Eval) evaluator family instead.
 pub struct JitBulkEval<I: AssemblerT> {
-    mmap: Arc<Mmap>,
+    code: Arc<Code>,
     var_count: usize,
+    /// Constant table, if immediates aren't compiled into the function
+    table: Option<ConstantTable>,
+    /// User-defined operations, which are called by the compiled function
+    custom: Arc<Vec<Arc<dyn CustomOp>>>,
     fn_bulk: jit_fn!(
         unsafe fn(
             *const f32,   // X
@@ -833,32 +1265,57 @@ pub struct JitBulkEval<I: AssemblerT> {
 impl<I: AssemblerT> Clone for JitBulkEval<I> {
     fn clone(&self) -> Self {
         Self {
-            mmap: self.mmap.clone(),
+            code: self.code.clone(),
             var_count: self.var_count,
+            table: self.table.clone(),
+            custom: self.custom.clone(),
             fn_bulk: self.fn_bulk,
         }
     }
 }
 
 // SAFETY: there is no mutable state in a `JitBulkEval`, and the pointer
-// inside of it points to its own `Mmap`, which is owned by an `Arc`
+// inside of it points to its own `Code`, which is owned by an `Arc` (as are
+// the custom operations which it calls)
 unsafe impl<I: AssemblerT> Send for JitBulkEval<I> {}
 unsafe impl<I: AssemblerT> Sync for JitBulkEval<I> {}
 
+impl<I: AssemblerT> JitBulkEval<I> {
+    /// Disassembles the compiled function, one instruction per line
+    #[cfg(feature = "disasm")]
+    pub fn disassemble(&self) -> String {
+        disasm::disassemble(self.code.as_slice(), self.code.as_ptr() as u64)
+    }
+}
+
 impl<I: AssemblerT> EvaluatorStorage<Eval> for JitBulkEval<I> {
     type Storage = Mmap;
     fn new_with_storage(t: &Tape<Eval>, prev: Self::Storage) -> Self {
-        let mmap = build_asm_fn_with_storage::<I>(t, prev);
-        let ptr = mmap.as_ptr();
+        let table = ConstantTable::new(t);
+        let code =
+            Code::new(build_asm_fn_with_storage::<I>(t, table.is_some(), prev));
+        let ptr = code.as_ptr();
         Self {
-            mmap: Arc::new(mmap),
+            code: Arc::new(code),
             var_count: t.var_count(),
+            table,
+            custom: t.shared_custom_ops(),
             fn_bulk: unsafe { std::mem::transmute(ptr) },
         }
     }
 
+    fn rebind(&self, t: &Tape<Eval>) -> Self {
+        match self.table.as_ref().and_then(|table| table.rebind(t)) {
+            Some(table) => Self {
+                table: Some(table),
+                ..self.clone()
+            },
+            None => Self::new_with_storage(t, Default::default()),
+        }
+    }
+
     fn take(self) -> Option<Self::Storage> {
-        Arc::try_unwrap(self.mmap).ok()
+        Arc::try_unwrap(self.code).ok().map(Code::take)
     }
 }
 
@@ -883,7 +1340,25 @@ where
         assert_eq!(ys.len(), zs.len());
         assert_eq!(zs.len(), out.len());
         assert_eq!(vars.len(), self.var_count);
+        with_vars(vars, self.table.as_ref(), |vars| {
+            self.eval_bulk(xs, ys, zs, vars, out)
+        })
+    }
+}
 
+impl<I: AssemblerT + SimdAssembler> JitBulkEval<I>
+where
+    I::Data: Copy + From<f32>,
+{
+    /// Evaluates multiple points, given a pointer to the variable array
+    fn eval_bulk(
+        &self,
+        xs: &[f32],
+        ys: &[f32],
+        zs: &[f32],
+        vars: *const f32,
+        out: &mut [I::Data],
+    ) {
         let n = xs.len();
 
         // Special case for when we have fewer items than the native SIMD size,
@@ -911,7 +1386,7 @@ where
                     x.as_ptr(),
                     y.as_ptr(),
                     z.as_ptr(),
-                    vars.as_ptr(),
+                    vars,
                     tmp.as_mut_ptr(),
                     I::SIMD_SIZE as u64,
                 );
@@ -927,7 +1402,7 @@ where
                     xs.as_ptr(),
                     ys.as_ptr(),
                     zs.as_ptr(),
-                    vars.as_ptr(),
+                    vars,
                     out.as_mut_ptr(),
                     m as u64,
                 );
@@ -941,13 +1416,14 @@ where
                         xs.as_ptr().add(n - I::SIMD_SIZE),
                         ys.as_ptr().add(n - I::SIMD_SIZE),
                         zs.as_ptr().add(n - I::SIMD_SIZE),
-                        vars.as_ptr(),
+                        vars,
                         out.as_mut_ptr().add(n - I::SIMD_SIZE),
                         I::SIMD_SIZE as u64,
                     );
                 }
             }
         }
+        call::resume_custom_panic();
     }
 }
 
@@ -957,7 +1433,243 @@ where
 mod test {
     use super::*;
     crate::grad_slice_tests!(Eval);
+    crate::hessian_slice_tests!(Eval);
     crate::interval_tests!(Eval);
+    crate::interval_slice_tests!(Eval);
     crate::float_slice_tests!(Eval);
     crate::point_tests!(Eval);
+
+    /// Builds a shape with `n` values which are all live at the same time
+    fn wide_shape(n: usize) -> (crate::Context, crate::context::Node) {
+        let mut ctx = crate::Context::new();
+        let x = ctx.x();
+        let vs: Vec<_> = (0..n)
+            .map(|i| {
+                let v = ctx.add(x, i as f32).unwrap();
+                ctx.sin(v).unwrap()
+            })
+            .collect();
+        // Each value is used at both ends of the sum, so that they're live at
+        // the same time; the sum is a balanced tree to limit recursion depth.
+        let mut terms: Vec<_> = vs
+            .iter()
+            .zip(vs.iter().rev())
+            .map(|(a, b)| ctx.sub(*a, *b).unwrap())
+            .collect();
+        while terms.len() > 1 {
+            terms = terms
+                .chunks(2)
+                .map(|c| match c {
+                    [a, b] => ctx.add(*a, *b).unwrap(),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        (ctx, terms[0])
+    }
+
+    #[test]
+    fn test_large_frame() {
+        // This needs more than a page of stack in most evaluators
+        let (ctx, root) = wide_shape(512);
+        let tape = ctx.get_tape::<Eval>(root).unwrap();
+        assert!(tape.slot_count() > 256);
+        let vm_tape = ctx.get_tape::<crate::vm::Eval>(root).unwrap();
+
+        let xs = [0.0, 0.5, 1.0, 2.0, -3.0, 4.0, 5.0, 6.0, 7.0];
+        let zeros = [0.0; 9];
+        let eval = tape.new_float_slice_evaluator();
+        let vm_eval = vm_tape.new_float_slice_evaluator();
+        let out = eval.eval(&xs, &zeros, &zeros, &[]).unwrap().to_vec();
+        let expected = vm_eval.eval(&xs, &zeros, &zeros, &[]).unwrap();
+        assert_eq!(out, expected);
+
+        let point = tape.new_point_evaluator();
+        let vm_point = vm_tape.new_point_evaluator();
+        for x in xs {
+            let (a, _) = point.eval(x, 0.0, 0.0, &[]).unwrap();
+            let (b, _) = vm_point.eval(x, 0.0, 0.0, &[]).unwrap();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn test_slot_limit() {
+        let (ctx, root) = wide_shape(SLOT_LIMIT * 2 + 16);
+        match ctx.get_tape::<Eval>(root) {
+            Err(Error::TooManySlots(n, limit)) => {
+                assert!(n > limit);
+                assert_eq!(limit, SLOT_LIMIT);
+            }
+            Err(e) => panic!("unexpected error {e:?}"),
+            Ok(..) => panic!("tape should have too many slots"),
+        }
+        // The interpreter has no such limit
+        assert!(ctx.get_tape::<crate::vm::Eval>(root).is_ok());
+    }
+
+    /// Builds a shape which uses every kind of immediate operation
+    fn constant_shape(
+        ctx: &mut crate::Context,
+        c: [f32; 8],
+    ) -> crate::context::Node {
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.var("a").unwrap();
+        let t0 = ctx.mul(x, c[0]).unwrap();
+        let t1 = ctx.sub(c[1], y).unwrap();
+        let t2 = ctx.div(t0, c[2]).unwrap();
+        let t3 = ctx.hypot(t1, c[3]).unwrap();
+        let t4 = ctx.atan2(t2, c[4]).unwrap();
+        let t5 = ctx.max(t3, c[5]).unwrap();
+        let t6 = ctx.min(t4, c[6]).unwrap();
+        let t7 = ctx.sub(a, c[7]).unwrap();
+        let sum = ctx.add(t5, t6).unwrap();
+        ctx.add(sum, t7).unwrap()
+    }
+
+    #[test]
+    fn test_constant_table() {
+        let mut ctx = crate::Context::new();
+        let root = constant_shape(
+            &mut ctx,
+            [-2.0, 0.5, 3.0, 1.5, -1.0, 2.0, 0.3, 4.0],
+        );
+        let inline = ctx.get_tape::<Eval>(root).unwrap();
+        let tape = inline.clone().with_constant_table(true);
+        let vm_tape = ctx.get_tape::<crate::vm::Eval>(root).unwrap();
+
+        let pts = [(0.0, 0.0), (1.0, -2.0), (-0.5, 3.0), (2.0, 0.25)];
+        let vars = [1.25];
+        let check = |tape: &Tape<Eval>, vm_tape: &Tape<crate::vm::Eval>| {
+            let point = tape.new_point_evaluator();
+            let vm_point = vm_tape.new_point_evaluator();
+            for (x, y) in pts {
+                let (a, _) = point.eval(x, y, 0.0, &vars).unwrap();
+                let (b, _) = vm_point.eval(x, y, 0.0, &vars).unwrap();
+                assert_eq!(a, b);
+            }
+            let xs: Vec<f32> = pts.iter().map(|p| p.0).collect();
+            let ys: Vec<f32> = pts.iter().map(|p| p.1).collect();
+            let zs = [0.0; 4];
+            let out = tape
+                .new_float_slice_evaluator()
+                .eval(&xs, &ys, &zs, &vars)
+                .unwrap()
+                .to_vec();
+            let expected = vm_tape
+                .new_float_slice_evaluator()
+                .eval(&xs, &ys, &zs, &vars)
+                .unwrap();
+            assert_eq!(out, expected);
+            let out = tape
+                .new_grad_slice_evaluator()
+                .eval(&xs, &ys, &zs, &vars)
+                .unwrap()
+                .to_vec();
+            let expected = vm_tape
+                .new_grad_slice_evaluator()
+                .eval(&xs, &ys, &zs, &vars)
+                .unwrap();
+            assert_eq!(out, expected);
+        };
+        check(&tape, &vm_tape);
+
+        // Interval arithmetic matches the inline code
+        let a = tape.new_interval_evaluator();
+        let b = inline.new_interval_evaluator();
+        for (x, y) in [([-1.0, 2.0], [0.0, 1.0]), ([0.5, 1.0], [-3.0, -1.0])] {
+            use crate::eval::types::Interval;
+            let (x, y) = (Interval::from(x), Interval::from(y));
+            let z = Interval::from(0.0);
+            assert_eq!(
+                a.eval(x, y, z, &vars).unwrap().0,
+                b.eval(x, y, z, &vars).unwrap().0,
+            );
+        }
+
+        // Changing constants reuses the compiled code.  The new constants
+        // avoid identities (e.g. `a - 0`), which the context would fold.
+        let next = constant_shape(
+            &mut ctx,
+            [3.0, -1.0, 0.5, 2.0, 1.0, -4.0, 0.7, 0.25],
+        );
+        let next_tape = ctx.update_tape(next, &tape).unwrap();
+        assert!(next_tape.constant_table());
+        let next_vm = ctx.get_tape::<crate::vm::Eval>(next).unwrap();
+        check(&next_tape, &next_vm);
+
+        let eval =
+            point::JitPointEval::new_with_storage(&tape, Mmap::default());
+        let rebound = eval.rebind(&next_tape);
+        assert!(Arc::ptr_eq(&eval.code, &rebound.code));
+        let point = tape.new_point_evaluator().rebind(&next_tape);
+        let vm_point = next_vm.new_point_evaluator();
+        for (x, y) in pts {
+            let (a, _) = point.eval(x, y, 0.0, &vars).unwrap();
+            let (b, _) = vm_point.eval(x, y, 0.0, &vars).unwrap();
+            assert_eq!(a, b);
+        }
+
+        // Structural changes (or tapes without a constant table) need new code
+        let changed = ctx.neg(next).unwrap();
+        let changed = ctx
+            .get_tape::<Eval>(changed)
+            .unwrap()
+            .with_constant_table(true);
+        for t in [&inline, &changed] {
+            let rebound = eval.rebind(t);
+            assert!(!Arc::ptr_eq(&eval.code, &rebound.code));
+        }
+    }
+
+    #[test]
+    fn test_large_constant_table() {
+        // This needs more constants than fit on the stack when calling
+        let (mut ctx, root) = wide_shape(100);
+        let a = ctx.var("a").unwrap();
+        let root = ctx.add(root, a).unwrap();
+        let tape = ctx
+            .get_tape::<Eval>(root)
+            .unwrap()
+            .with_constant_table(true);
+        assert!(tape.immediates().len() > 64);
+        let vm_tape = ctx.get_tape::<crate::vm::Eval>(root).unwrap();
+
+        let xs = [0.0, 0.5, 1.0, 2.0, -3.0, 4.0, 5.0, 6.0, 7.0];
+        let zeros = [0.0; 9];
+        let out = tape
+            .new_float_slice_evaluator()
+            .eval(&xs, &zeros, &zeros, &[0.5])
+            .unwrap()
+            .to_vec();
+        let expected = vm_tape
+            .new_float_slice_evaluator()
+            .eval(&xs, &zeros, &zeros, &[0.5])
+            .unwrap();
+        assert_eq!(out, expected);
+    }
+
+    #[cfg(feature = "disasm")]
+    #[test]
+    fn test_disassemble() {
+        let mut ctx = crate::Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let sum = ctx.add(x, y).unwrap();
+        let tape = ctx.get_tape::<Eval>(sum).unwrap();
+
+        let eval = point::JitPointEval::new_with_storage(&tape, Mmap::empty());
+        let text = eval.disassemble();
+        assert!(!text.contains("undecoded"), "{text}");
+        assert!(text.lines().any(|line| line.contains("ret")), "{text}");
+
+        let eval = float_slice::JitFloatSliceEval::new_with_storage(
+            &tape,
+            Mmap::empty(),
+        );
+        let text = eval.disassemble();
+        assert!(text.lines().count() > 10, "{text}");
+    }
 }
@@ -1,6 +1,6 @@
 use crate::jit::{
-    float_slice::FloatSliceAssembler, mmap::Mmap, reg, AssemblerData,
-    AssemblerT, Error, IMM_REG, OFFSET, REGISTER_LIMIT,
+    call, float_slice::FloatSliceAssembler, fused::FusedAssembler, mmap::Mmap,
+    reg, AssemblerData, AssemblerT, Error, IMM_REG, OFFSET, REGISTER_LIMIT,
 };
 use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
 
@@ -145,22 +145,119 @@ impl AssemblerT for FloatSliceAssembler {
             ; vmulps Ry(reg(out_reg)), Ry(reg(lhs_reg)), Ry(reg(rhs_reg))
         );
     }
+    fn build_fma(
+        &mut self,
+        out_reg: u8,
+        a_reg: u8,
+        b_reg: u8,
+        c_reg: u8,
+        _widen: bool,
+    ) {
+        if out_reg == c_reg {
+            dynasm!(self.0.ops
+                ; vfmadd231ps Ry(reg(out_reg)), Ry(reg(a_reg)), Ry(reg(b_reg))
+            );
+        } else {
+            dynasm!(self.0.ops
+                ; vmovaps ymm1, Ry(reg(c_reg))
+                ; vfmadd231ps ymm1, Ry(reg(a_reg)), Ry(reg(b_reg))
+                ; vmovaps Ry(reg(out_reg)), ymm1
+            );
+        }
+    }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<[f32; SIMD_WIDTH], call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            ; vdivps ymm1, Ry(reg(lhs_reg)), Ry(reg(rhs_reg))
+            ; vroundps ymm1, ymm1, 1 // round toward -infinity
+            ; vmulps ymm1, ymm1, Ry(reg(rhs_reg))
+            ; vsubps Ry(reg(out_reg)), Ry(reg(lhs_reg)), ymm1
+        );
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<[f32; SIMD_WIDTH]>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<[f32; SIMD_WIDTH]>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        // Slice evaluators don't make choices, so this can reuse `min` and
+        // `max`, with the immediate register as a temporary
+        let tmp = IMM_REG.wrapping_sub(OFFSET);
+        self.build_min(tmp, x_reg, hi_reg);
+        self.build_max(out_reg, tmp, lo_reg);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; vdivps Ry(reg(out_reg)), Ry(reg(lhs_reg)), Ry(reg(rhs_reg))
         );
     }
     fn build_max(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
-        // TODO: does this handle NaN correctly?
         dynasm!(self.0.ops
+            // vmaxps returns its second operand if either is NaN, so we only
+            // need to patch up lanes where the first operand is NaN, by
+            // setting them to all 1s (which is also a NaN)
+            ; vcmpunordps ymm1, Ry(reg(lhs_reg)), Ry(reg(lhs_reg))
             ; vmaxps Ry(reg(out_reg)), Ry(reg(lhs_reg)), Ry(reg(rhs_reg))
+            ; vorps Ry(reg(out_reg)), Ry(reg(out_reg)), ymm1
         );
     }
     fn build_min(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
-        // TODO: does this handle NaN correctly?
         dynasm!(self.0.ops
+            // See build_max for NaN handling
+            ; vcmpunordps ymm1, Ry(reg(lhs_reg)), Ry(reg(lhs_reg))
             ; vminps Ry(reg(out_reg)), Ry(reg(lhs_reg)), Ry(reg(rhs_reg))
+            ; vorps Ry(reg(out_reg)), Ry(reg(out_reg)), ymm1
+        );
+    }
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        // This evaluator doesn't record choices, so `max` is already suitable
+        self.build_max(out_reg, lhs_reg, rhs_reg)
+    }
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        self.build_min(out_reg, lhs_reg, rhs_reg)
+    }
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        dynasm!(self.0.ops
+            ; mov eax, f32::INFINITY.to_bits() as i32
+            ; vmovd xmm1, eax
+            ; vbroadcastss ymm1, xmm1
+            // vminps returns its second operand if either is NaN, so this
+            // replaces NaN with +inf and leaves other values unchanged
+            ; vminps ymm2, Ry(reg(lhs_reg)), ymm1
+            ; vminps ymm3, Ry(reg(rhs_reg)), ymm1
         );
+        (2u8.wrapping_sub(OFFSET), 3u8.wrapping_sub(OFFSET))
     }
     fn load_imm(&mut self, imm: f32) -> u8 {
         dynasm!(self.0.ops
@@ -182,3 +279,21 @@ impl AssemblerT for FloatSliceAssembler {
         self.0.ops.finalize()
     }
 }
+
+impl FusedAssembler for FloatSliceAssembler {
+    fn build_output(&mut self, out_reg: u8, index: usize) {
+        dynasm!(self.0.ops
+            ; vmovups [r8 + 32 * (index as i32)], Ry(reg(out_reg))
+        );
+    }
+    fn finalize_fused(mut self, count: usize) -> Result<Mmap, Error> {
+        dynasm!(self.0.ops
+            // Skip over every output, then adjust the remaining size
+            ; add r8, 32 * (count as i32)
+            ; sub r9, 8
+            ; jmp ->L
+        );
+
+        self.0.ops.finalize()
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     eval::types::Grad,
     jit::{
-        grad_slice::GradSliceAssembler, mmap::Mmap, reg, AssemblerData,
+        call, grad_slice::GradSliceAssembler, mmap::Mmap, reg, AssemblerData,
         AssemblerT, IMM_REG, OFFSET, REGISTER_LIMIT,
     },
     Error,
@@ -229,6 +229,54 @@ impl AssemblerT for GradSliceAssembler {
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), xmm2
         );
     }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Grad, call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Grad, call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Grad, call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Grad, call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Grad, call::Mod>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<Grad>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<Grad>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        // Slice evaluators don't make choices, so this can reuse `min` and
+        // `max`, with the immediate register as a temporary
+        let tmp = IMM_REG.wrapping_sub(OFFSET);
+        self.build_min(tmp, x_reg, hi_reg);
+        self.build_max(out_reg, tmp, lo_reg);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         // d/dx f(x) * g(x) = (f'(x)*g(x) - f(x)*g'(x)) / g(x)**2
         dynasm!(self.0.ops
@@ -259,12 +307,19 @@ impl AssemblerT for GradSliceAssembler {
     fn build_max(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; vcomiss Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jp >N
             ; ja >L
 
             // Fallthrough
+            ; R:
             ; vmovups Rx(reg(out_reg)), Rx(reg(rhs_reg))
             ; jmp >E
 
+            // NaN: return whichever argument is NaN, preferring lhs
+            ; N:
+            ; vcomiss Rx(reg(lhs_reg)), Rx(reg(lhs_reg))
+            ; jnp <R
+
             ; L:
             ; vmovups Rx(reg(out_reg)), Rx(reg(lhs_reg))
             // Fallthrough
@@ -276,12 +331,19 @@ impl AssemblerT for GradSliceAssembler {
     fn build_min(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; vcomiss Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jp >N
             ; ja >R
 
             // Fallthrough
+            ; L:
             ; vmovups Rx(reg(out_reg)), Rx(reg(lhs_reg))
             ; jmp >O
 
+            // NaN: return whichever argument is NaN, preferring lhs
+            ; N:
+            ; vcomiss Rx(reg(lhs_reg)), Rx(reg(lhs_reg))
+            ; jp <L
+
             ; R:
             ; vmovups Rx(reg(out_reg)), Rx(reg(rhs_reg))
             // Fallthrough
@@ -290,6 +352,25 @@ impl AssemblerT for GradSliceAssembler {
         );
         self.0.ops.commit_local().unwrap();
     }
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        // This evaluator doesn't record choices, so `max` is already suitable
+        self.build_max(out_reg, lhs_reg, rhs_reg)
+    }
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        self.build_min(out_reg, lhs_reg, rhs_reg)
+    }
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        dynasm!(self.0.ops
+            ; mov eax, f32::INFINITY.to_bits() as i32
+            ; vmovd xmm1, eax
+            // vminss returns its second operand if either is NaN, so this
+            // replaces a NaN value with +inf, leaving other values unchanged;
+            // the partial derivatives are copied from the first operand.
+            ; vminss xmm2, Rx(reg(lhs_reg)), xmm1
+            ; vminss xmm3, Rx(reg(rhs_reg)), xmm1
+        );
+        (2u8.wrapping_sub(OFFSET), 3u8.wrapping_sub(OFFSET))
+    }
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
         dynasm!(self.0.ops
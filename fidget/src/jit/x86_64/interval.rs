@@ -1,7 +1,7 @@
 use crate::{
     eval::types::Interval,
     jit::{
-        interval::IntervalAssembler, mmap::Mmap, reg, AssemblerData,
+        call, interval::IntervalAssembler, mmap::Mmap, reg, AssemblerData,
         AssemblerT, CHOICE_BOTH, CHOICE_LEFT, CHOICE_RIGHT, IMM_REG, OFFSET,
         REGISTER_LIMIT,
     },
@@ -128,11 +128,15 @@ impl AssemblerT for IntervalAssembler {
             ; vcomiss xmm0, Rx(reg(out_reg)) // Compare abs(hi) vs abs(lo)
             ; ja >C // if abs(hi) > abs(lo), then we don't need to swap
 
-            ; vpshufd Rx(reg(out_reg)), Rx(reg(out_reg)), 0b11110011u8 as i8
+            // Copy abs(lo) into the upper slot
+            ; vpshufd Rx(reg(out_reg)), Rx(reg(out_reg)), 0b11110000u8 as i8
 
             // Clear the lowest value of the interval, leaving us with [0, ...]
+            // (we can't shuffle in a zero from the upper lanes, because they
+            // aren't guaranteed to be clear)
             ; C:
-            ; vpshufd Rx(reg(out_reg)), Rx(reg(out_reg)), 0b11110111u8 as i8
+            ; vpxor xmm0, xmm0, xmm0
+            ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), xmm0
             // fallthrough to end
 
             ; E:
@@ -261,6 +265,51 @@ impl AssemblerT for IntervalAssembler {
             ; vunpcklps Rx(reg(out_reg)), Rx(reg(out_reg)), xmm2
         );
     }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Interval, call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<Interval, call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Interval, call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Interval, call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<Interval, call::Mod>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<Interval>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<Interval>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        let f = call::ternary::<Interval, call::Clamp>;
+        self.0.call_fn_ternary(out_reg, x_reg, lo_reg, hi_reg, f);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; vpxor xmm1, xmm1, xmm1 // xmm1 = 0.0
@@ -304,7 +353,7 @@ impl AssemblerT for IntervalAssembler {
     }
     fn build_max(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
-            ; mov ax, [rsi]
+            ; xor eax, eax
 
             // xmm1 = lhs.upper
             ; vpshufd xmm1, Rx(reg(lhs_reg)), 0b11111101u8 as i8
@@ -335,20 +384,18 @@ impl AssemblerT for IntervalAssembler {
             ; L:
             ; vmovq Rx(reg(out_reg)), Rx(reg(lhs_reg))
             ; or ax, CHOICE_LEFT as i16
-            ; mov cx, 1 // TODO: why can't we write 1 to [rdx] directly?
-            ; mov [rdx], cx
+            ; mov BYTE [rdx], 1 // `simplify` is a single byte
             ; jmp >E
 
             // rhs.upper < lhs.lower
             ; R:
             ; vmovq Rx(reg(out_reg)), Rx(reg(rhs_reg))
             ; or ax, CHOICE_RIGHT as i16
-            ; mov cx, 1
-            ; mov [rdx], cx
+            ; mov BYTE [rdx], 1
             // Fallthrough
 
             ; E:
-            ; mov [rsi], ax
+            ; mov [rsi], al
             ; add rsi, 1
         );
         self.0.ops.commit_local().unwrap();
@@ -357,16 +404,16 @@ impl AssemblerT for IntervalAssembler {
         // TODO: Godbolt uses unpcklps ?
         dynasm!(self.0.ops
             //  if lhs.upper < rhs.lower
-            //      *choices++ |= CHOICE_LEFT
+            //      *choices++ = CHOICE_LEFT
             //      out = lhs
             //  elif rhs.upper < lhs.lower
-            //      *choices++ |= CHOICE_RIGHT
+            //      *choices++ = CHOICE_RIGHT
             //      out = rhs
             //  else
-            //      *choices++ |= CHOICE_BOTH
+            //      *choices++ = CHOICE_BOTH
             //      out = fmin(lhs, rhs)
 
-            ; mov ax, [rsi]
+            ; xor eax, eax
 
             // TODO: use cmpltss to do both comparisons?
 
@@ -399,24 +446,114 @@ impl AssemblerT for IntervalAssembler {
             ; L:
             ; vmovq Rx(reg(out_reg)), Rx(reg(lhs_reg))
             ; or ax, CHOICE_LEFT as i16
-            ; mov cx, 1 // TODO: why can't we write 1 to [rdx] directly?
-            ; mov [rdx], cx
+            ; mov BYTE [rdx], 1 // `simplify` is a single byte
             ; jmp >E
 
             // rhs.upper < lhs.lower
             ; R:
             ; vmovq Rx(reg(out_reg)), Rx(reg(rhs_reg))
             ; or ax, CHOICE_RIGHT as i16
-            ; mov cx, 1
-            ; mov [rdx], cx
+            ; mov BYTE [rdx], 1
             // Fallthrough
 
             ; E:
-            ; mov [rsi], ax
+            ; mov [rsi], al
             ; add rsi, 1
         );
         self.0.ops.commit_local().unwrap();
     }
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            // Check for NaN in any of the four bounds, using the same
+            // comparisons as build_max (but ignoring the results)
+            ; vpshufd xmm1, Rx(reg(lhs_reg)), 0b11111101u8 as i8
+            ; vcomiss xmm1, Rx(reg(rhs_reg))
+            ; jp >N
+            ; vpshufd xmm1, Rx(reg(rhs_reg)), 0b11111101u8 as i8
+            ; vcomiss xmm1, Rx(reg(lhs_reg))
+            ; jp >N
+
+            ; vmaxps Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jmp >E
+
+            ; N:
+            ; vpcmpeqw Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(out_reg))
+            ; vpslld Rx(reg(out_reg)), Rx(reg(out_reg)), 23
+            ; vpsrld Rx(reg(out_reg)), Rx(reg(out_reg)), 1
+
+            ; E:
+        );
+        self.0.ops.commit_local().unwrap();
+    }
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            // See build_max_nc for NaN handling
+            ; vpshufd xmm1, Rx(reg(lhs_reg)), 0b11111101u8 as i8
+            ; vcomiss xmm1, Rx(reg(rhs_reg))
+            ; jp >N
+            ; vpshufd xmm1, Rx(reg(rhs_reg)), 0b11111101u8 as i8
+            ; vcomiss xmm1, Rx(reg(lhs_reg))
+            ; jp >N
+
+            ; vminps Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jmp >E
+
+            ; N:
+            ; vpcmpeqw Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(out_reg))
+            ; vpslld Rx(reg(out_reg)), Rx(reg(out_reg)), 23
+            ; vpsrld Rx(reg(out_reg)), Rx(reg(out_reg)), 1
+
+            ; E:
+        );
+        self.0.ops.commit_local().unwrap();
+    }
+    fn build_widen(&mut self, out_reg: u8) {
+        let eps = f32::EPSILON.to_bits();
+        let tiny = f32::MIN_POSITIVE.to_bits();
+        dynasm!(self.0.ops
+            // xmm1 = abs(reg)
+            ; vpcmpeqd xmm2, xmm2, xmm2
+            ; vpsrld xmm2, xmm2, 1 // xmm2 = 0x7fffffff
+            ; vandps xmm1, Rx(reg(out_reg)), xmm2
+
+            // xmm1 = abs(reg) * EPSILON + MIN_POSITIVE
+            ; mov eax, eps as i32
+            ; vmovd xmm2, eax
+            ; vbroadcastss xmm2, xmm2
+            ; vmulps xmm1, xmm1, xmm2
+            ; mov eax, tiny as i32
+            ; vmovd xmm2, eax
+            ; vbroadcastss xmm2, xmm2
+            ; vaddps xmm1, xmm1, xmm2
+
+            // Move the lower bound down and the upper bound up.  If the new
+            // value is NaN (from inf - inf), then min / max return their
+            // second operand, leaving the original bound unchanged.
+            ; vsubps xmm2, Rx(reg(out_reg)), xmm1
+            ; vminps xmm2, xmm2, Rx(reg(out_reg))
+            ; vaddps xmm1, Rx(reg(out_reg)), xmm1
+            ; vmaxps xmm1, xmm1, Rx(reg(out_reg))
+
+            // Splice together [lower, upper], taking the low float from xmm2
+            ; vmovss Rx(reg(out_reg)), xmm1, xmm2
+        );
+    }
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        dynasm!(self.0.ops
+            // xmm1 = [-inf, +inf]
+            ; mov eax, f32::NEG_INFINITY.to_bits() as i32
+            ; vmovd xmm1, eax
+            ; mov eax, f32::INFINITY.to_bits() as i32
+            ; vpinsrd xmm1, xmm1, eax, 1
+
+            // Replace NaN bounds with the corresponding bound from xmm1
+            ; vcmpunordps xmm2, Rx(reg(lhs_reg)), Rx(reg(lhs_reg))
+            ; vblendvps xmm2, Rx(reg(lhs_reg)), xmm1, xmm2
+            ; vcmpunordps xmm3, Rx(reg(rhs_reg)), Rx(reg(rhs_reg))
+            ; vblendvps xmm3, Rx(reg(rhs_reg)), xmm1, xmm3
+        );
+        (2u8.wrapping_sub(OFFSET), 3u8.wrapping_sub(OFFSET))
+    }
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
         dynasm!(self.0.ops
@@ -4,7 +4,8 @@
 //! tape must be planned with a <= 12 register limit; any spills will live on
 //! the stack.
 //!
-//! Right now, we never call anything, so don't worry about saving stuff.
+//! Operations without a native instruction (trigonometry and `hypot`) call
+//! back into Rust; see the `call` module, which saves every live register.
 //!
 //! Within a single operation, you'll often need to make use of scratch
 //! registers.  `xmm0` is used when loading immediates, and should not be used
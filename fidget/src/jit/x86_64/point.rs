@@ -1,7 +1,7 @@
 use crate::{
     jit::{
-        mmap::Mmap, point::PointAssembler, reg, AssemblerData, AssemblerT,
-        CHOICE_BOTH, CHOICE_LEFT, CHOICE_RIGHT, IMM_REG, OFFSET,
+        call, mmap::Mmap, point::PointAssembler, reg, AssemblerData,
+        AssemblerT, CHOICE_BOTH, CHOICE_LEFT, CHOICE_RIGHT, IMM_REG, OFFSET,
         REGISTER_LIMIT,
     },
     Error,
@@ -118,6 +118,75 @@ impl AssemblerT for PointAssembler {
             ; vmulss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
         );
     }
+    fn build_fma(
+        &mut self,
+        out_reg: u8,
+        a_reg: u8,
+        b_reg: u8,
+        c_reg: u8,
+        _widen: bool,
+    ) {
+        if out_reg == c_reg {
+            dynasm!(self.0.ops
+                ; vfmadd231ss Rx(reg(out_reg)), Rx(reg(a_reg)), Rx(reg(b_reg))
+            );
+        } else {
+            dynasm!(self.0.ops
+                ; vmovaps xmm1, Rx(reg(c_reg))
+                ; vfmadd231ss xmm1, Rx(reg(a_reg)), Rx(reg(b_reg))
+                ; vmovaps Rx(reg(out_reg)), xmm1
+            );
+        }
+    }
+    fn build_sin(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<f32, call::Sin>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_cos(&mut self, out_reg: u8, lhs_reg: u8) {
+        let f = call::binary::<f32, call::Cos>;
+        self.0.call_fn_binary(out_reg, lhs_reg, lhs_reg, f);
+    }
+    fn build_atan2(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<f32, call::Atan2>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_hypot(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        let f = call::binary::<f32, call::Hypot>;
+        self.0.call_fn_binary(out_reg, lhs_reg, rhs_reg, f);
+    }
+    fn build_mod(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            ; vdivss xmm1, Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; vroundss xmm1, xmm1, xmm1, 1 // round toward -infinity
+            ; vmulss xmm1, xmm1, Rx(reg(rhs_reg))
+            ; vsubss Rx(reg(out_reg)), Rx(reg(lhs_reg)), xmm1
+        );
+    }
+    fn build_custom(
+        &mut self,
+        out_reg: u8,
+        lhs_reg: u8,
+        rhs_reg: u8,
+        op: call::CustomPtr,
+    ) {
+        let f = call::custom::<f32>;
+        self.0.call_fn_custom(out_reg, lhs_reg, rhs_reg, f, op);
+    }
+    fn build_noise(
+        &mut self,
+        out_reg: u8,
+        x_reg: u8,
+        y_reg: u8,
+        z_reg: u8,
+        seed: u16,
+    ) {
+        let f = call::noise::<f32>;
+        self.0.call_fn_noise(out_reg, x_reg, y_reg, z_reg, f, seed);
+    }
+    fn build_clamp(&mut self, out_reg: u8, x_reg: u8, lo_reg: u8, hi_reg: u8) {
+        let f = call::ternary::<f32, call::Clamp>;
+        self.0.call_fn_ternary(out_reg, x_reg, lo_reg, hi_reg, f);
+    }
     fn build_div(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
         dynasm!(self.0.ops
             ; vdivss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
@@ -131,31 +200,32 @@ impl AssemblerT for PointAssembler {
             ; jb >R
 
             // Fallthrough for equal, so just copy to the output register
-            ; or [rsi], CHOICE_BOTH as i8
+            ; mov BYTE [rsi], CHOICE_BOTH as i8
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(lhs_reg))
             ; jmp >O
 
             // Fallthrough for NaN, which are !=; do a float addition to
             // propagate it to the output register.
             ; N:
-            ; or [rsi], CHOICE_BOTH as i8
+            ; mov BYTE [rsi], CHOICE_BOTH as i8
             // TODO: this can't be the best way to make a NAN
             ; vaddss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
             ; jmp >O
 
             ; L:
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(lhs_reg))
-            ; or [rsi], CHOICE_LEFT as i8
+            ; mov BYTE [rsi], CHOICE_LEFT as i8
             ; or [rdx], 1
             ; jmp >O
 
             ; R:
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(rhs_reg))
-            ; or [rsi], CHOICE_RIGHT as i8
+            ; mov BYTE [rsi], CHOICE_RIGHT as i8
             ; or [rdx], 1
             // fallthrough to out
 
             ; O:
+            ; add rsi, 1
         );
         self.0.ops.commit_local().unwrap()
     }
@@ -167,32 +237,71 @@ impl AssemblerT for PointAssembler {
             ; jb >L
 
             // Fallthrough for equal, so just copy to the output register
-            ; or [rsi], CHOICE_BOTH as i8
+            ; mov BYTE [rsi], CHOICE_BOTH as i8
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(lhs_reg))
             ; jmp >O
 
             ; N:
-            ; or [rsi], CHOICE_BOTH as i8
+            ; mov BYTE [rsi], CHOICE_BOTH as i8
             // TODO: this can't be the best way to make a NAN
             ; vaddss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
             ; jmp >O
 
             ; L:
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(lhs_reg))
-            ; or [rsi], CHOICE_LEFT as i8
+            ; mov BYTE [rsi], CHOICE_LEFT as i8
             ; or [rdx], 1
             ; jmp >O
 
             ; R:
             ; vmovss Rx(reg(out_reg)), Rx(reg(out_reg)), Rx(reg(rhs_reg))
-            ; or [rsi], CHOICE_RIGHT as i8
+            ; mov BYTE [rsi], CHOICE_RIGHT as i8
             ; or [rdx], 1
             // fallthrough to out
 
             ; O:
+            ; add rsi, 1
+        );
+        self.0.ops.commit_local().unwrap()
+    }
+    fn build_max_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            // vmaxss returns its second operand if either is NaN, so we check
+            // for NaN separately
+            ; vcomiss Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jp >N
+            ; vmaxss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jmp >O
+            ; N:
+            ; vaddss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; O:
         );
         self.0.ops.commit_local().unwrap()
     }
+    fn build_min_nc(&mut self, out_reg: u8, lhs_reg: u8, rhs_reg: u8) {
+        dynasm!(self.0.ops
+            // See build_max_nc for NaN handling
+            ; vcomiss Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jp >N
+            ; vminss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; jmp >O
+            ; N:
+            ; vaddss Rx(reg(out_reg)), Rx(reg(lhs_reg)), Rx(reg(rhs_reg))
+            ; O:
+        );
+        self.0.ops.commit_local().unwrap()
+    }
+    fn build_nan_to_empty(&mut self, lhs_reg: u8, rhs_reg: u8) -> (u8, u8) {
+        dynasm!(self.0.ops
+            ; mov eax, f32::INFINITY.to_bits() as i32
+            ; vmovd xmm1, eax
+            // vminss returns its second operand if either is NaN, so this
+            // replaces NaN with +inf and leaves other values unchanged
+            ; vminss xmm2, Rx(reg(lhs_reg)), xmm1
+            ; vminss xmm3, Rx(reg(rhs_reg)), xmm1
+        );
+        (2u8.wrapping_sub(OFFSET), 3u8.wrapping_sub(OFFSET))
+    }
     fn load_imm(&mut self, imm: f32) -> u8 {
         let imm_u32 = imm.to_bits();
         dynasm!(self.0.ops
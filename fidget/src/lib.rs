@@ -82,7 +82,7 @@
 //! registers](crate::eval::Family::REG_LIMIT), which affects tape planning;
 //! don't worry, this won't be on the test)
 //!
-//! At the moment, Fidget implements two evaluator families:
+//! At the moment, Fidget implements four main evaluator families:
 //!
 //! - [`fidget::jit::Eval`](crate::jit::Eval) performs fast evaluation by
 //!   compiling shapes down to native code.  This is only functional on an ARM64
@@ -90,6 +90,13 @@
 //! - [`fidget::vm::Eval`](crate::vm::Eval) evaluates
 //!   using an interpreter.  This is slower, but can run in more situations (e.g.
 //!   x86 machines or in WebAssembly).
+//! - [`fidget::vm::Threaded`](crate::vm::Threaded) is also an interpreter, but
+//!   translates each tape into an array of function pointers before
+//!   evaluation.  This is usually faster than `vm::Eval` for tapes that are
+//!   evaluated many times, and is just as portable.
+//! - [`fidget::vm::Affine`](crate::vm::Affine) uses the interpreter, but
+//!   replaces interval arithmetic with affine arithmetic, which finds tighter
+//!   bounds for shapes with correlated terms at some extra cost.
 //!
 //! Looking at the [`eval::Family`](crate::eval::Family) trait, you may notice
 //! that it requires four different kinds of evaluation:
@@ -231,8 +238,40 @@
 //! ```
 //!
 //! # Feature flags
+//! Each of the larger modules (rendering, meshing, the JIT, scripting, and so
+//! on) is behind its own feature.  Only `std`, `jit`, `rhai`, `render`, and
+//! `mesh` are enabled by default; the other modules are opt-in.  With
+//! `default-features = false, features = ["std"]`, Fidget only includes its
+//! core: the [`Context`], tapes, and the interpreter evaluators in [`vm`],
+//! without pulling in `dynasmrt`, `nalgebra`, or spawning threads.  Leaving out
+//! the `std` feature as well builds that core as `no_std + alloc`, for
+//! evaluating tapes on embedded targets.
+//!
 #![doc = document_features::document_features!()]
 #![warn(missing_docs)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+extern crate alloc;
+
+/// Enters a `tracing` span for the rest of the enclosing scope
+///
+/// This expands to nothing unless the `tracing` feature is enabled.
+macro_rules! span {
+    ($level:ident, $($args:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span =
+            ::tracing::span!(::tracing::Level::$level, $($args)+).entered();
+    };
+}
+
+/// Records a `tracing` event, if the `tracing` feature is enabled
+macro_rules! event {
+    ($level:ident, $($args:tt)+) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::event!(::tracing::Level::$level, $($args)+);
+    };
+}
 
 // Re-export everything from fidget::core into the top-level namespace
 mod core;
@@ -241,9 +280,25 @@ pub use crate::core::*;
 mod error;
 pub use error::Error;
 
+pub mod gen;
+pub mod shape;
+pub mod shapes;
+
+#[cfg(feature = "contour")]
+pub mod contour;
+
 #[cfg(feature = "render")]
 pub mod render;
 
+#[cfg(feature = "viewer")]
+pub mod viewer;
+
+#[cfg(feature = "gpu")]
+pub mod gpu;
+
+#[cfg(feature = "python")]
+pub mod python;
+
 #[cfg(feature = "rhai")]
 pub mod rhai;
 
@@ -252,3 +307,21 @@ pub mod jit;
 
 #[cfg(feature = "mesh")]
 pub mod mesh;
+
+#[cfg(feature = "text")]
+pub mod text;
+
+#[cfg(feature = "voxel")]
+pub mod voxel;
+
+#[cfg(feature = "svg")]
+pub mod svg;
+
+#[cfg(feature = "csg")]
+pub mod csg;
+
+#[cfg(any(feature = "text", feature = "svg", feature = "csg"))]
+mod outline;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
@@ -3,6 +3,7 @@ use crate::eval::types::Interval;
 
 use super::{
     gen::CELL_TO_EDGE_TO_VERT,
+    morton::MortonKey,
     types::{Axis, Corner, Edge, Intersection, X, Y, Z},
 };
 
@@ -146,12 +147,14 @@ impl std::ops::Index<Axis> for CellVertex {
 /// descending the tree.
 ///
 /// `index` points to where this cell is stored in
-/// [`Octree::cells`](super::Octree::cells)
+/// [`Octree::cells`](super::Octree::cells); `key` is the cell's Morton
+/// location code, which is independent of storage.
 #[derive(Copy, Clone, Debug)]
 pub struct CellIndex {
     pub index: usize,
     pub depth: usize,
     pub bounds: CellBounds,
+    pub key: MortonKey,
 }
 
 impl Default for CellIndex {
@@ -166,6 +169,23 @@ impl CellIndex {
             index: 0,
             bounds: CellBounds::default(),
             depth: 0,
+            key: MortonKey::root(),
+        }
+    }
+
+    /// Builds a cell index from a location code, which determines its bounds
+    pub fn from_key(index: usize, key: MortonKey) -> Self {
+        let depth = key.depth();
+        let scale = 2.0 / (1u32 << depth) as f32;
+        let [x, y, z] = key.pos().map(|p| {
+            let lo = p as f32 * scale - 1.0;
+            Interval::new(lo, lo + scale)
+        });
+        CellIndex {
+            index,
+            bounds: CellBounds { x, y, z },
+            depth,
+            key,
         }
     }
 
@@ -198,6 +218,7 @@ impl CellIndex {
             index: index + i.index(),
             bounds,
             depth: self.depth + 1,
+            key: self.key.child(i),
         }
     }
 
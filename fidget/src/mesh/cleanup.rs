@@ -0,0 +1,249 @@
+//! Mesh cleanup pass
+use super::Mesh;
+use std::collections::{HashMap, VecDeque};
+
+/// Statistics returned by [`Mesh::cleanup`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CleanupStats {
+    /// Number of vertices which were merged into a nearby vertex
+    pub welded: usize,
+    /// Number of zero-area triangles which were removed
+    pub degenerate: usize,
+    /// Number of triangles whose winding was reversed
+    pub flipped: usize,
+}
+
+impl Mesh {
+    /// Welds coincident vertices, removes degenerate triangles, and makes
+    /// triangle winding consistent
+    ///
+    /// Vertices within `tolerance` of each other are merged, keeping the
+    /// position and attributes of the first.  A triangle is removed if it
+    /// uses the same vertex twice, or if its height (relative to its longest
+    /// edge) is no more than `tolerance`.  Then, within each connected patch
+    /// of triangles, the winding is made consistent across shared edges,
+    /// flipping whichever triangles disagree with the majority.
+    ///
+    /// Vertices which are no longer used by any triangle are kept, so that
+    /// indexes into [`self.vertices`](Self::vertices) remain meaningful for
+    /// callers which only care about welding.
+    ///
+    /// ```
+    /// use fidget::mesh::{CleanupStats, Mesh};
+    /// use nalgebra::Vector3;
+    ///
+    /// // A square made from two triangles which don't share vertices
+    /// let mut mesh = Mesh::new();
+    /// mesh.vertices = vec![
+    ///     Vector3::new(0.0, 0.0, 0.0),
+    ///     Vector3::new(1.0, 0.0, 0.0),
+    ///     Vector3::new(1.0, 1.0, 0.0),
+    ///     Vector3::new(0.0, 0.0, 0.0),
+    ///     Vector3::new(1.0, 1.0, 0.0),
+    ///     Vector3::new(0.0, 1.0, 0.0),
+    /// ];
+    /// mesh.triangles = vec![Vector3::new(0, 1, 2), Vector3::new(3, 4, 5)];
+    ///
+    /// let stats = mesh.cleanup(1e-6);
+    /// assert_eq!(stats.welded, 2);
+    /// assert_eq!(mesh.vertices.len(), 4);
+    /// assert_eq!(mesh.triangles[1], Vector3::new(0, 2, 3));
+    /// ```
+    pub fn cleanup(&mut self, tolerance: f32) -> CleanupStats {
+        let mut stats = CleanupStats {
+            welded: self.weld(tolerance),
+            ..Default::default()
+        };
+
+        let before = self.triangles.len();
+        let vs = &self.vertices;
+        self.triangles.retain(|t| {
+            if t.x == t.y || t.y == t.z || t.z == t.x {
+                return false;
+            }
+            let (a, b, c) = (vs[t.x], vs[t.y], vs[t.z]);
+            let longest =
+                (b - a).norm().max((c - b).norm()).max((a - c).norm());
+            let area2 = (b - a).cross(&(c - a)).norm();
+            area2 > tolerance * longest
+        });
+        stats.degenerate = before - self.triangles.len();
+
+        stats.flipped = self.fix_winding();
+        stats
+    }
+
+    /// Merges vertices within `tolerance` of each other, returning the number
+    /// of vertices removed
+    fn weld(&mut self, tolerance: f32) -> usize {
+        // Vertices are binned into a grid with `tolerance`-sized cells, so
+        // any match is in the same or a neighboring cell.
+        let scale = if tolerance > 0.0 {
+            1.0 / tolerance
+        } else {
+            1.0
+        };
+        let key =
+            |p: nalgebra::Vector3<f32>| p.map(|v| (v * scale).floor() as i64);
+        let mut grid: HashMap<nalgebra::Vector3<i64>, Vec<usize>> =
+            HashMap::new();
+
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut keep: Vec<usize> = vec![];
+        for (i, &p) in self.vertices.iter().enumerate() {
+            let k = key(p);
+            let mut found = None;
+            'outer: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let n = k + nalgebra::Vector3::new(dx, dy, dz);
+                        let Some(bin) = grid.get(&n) else {
+                            continue;
+                        };
+                        for &j in bin {
+                            if (self.vertices[keep[j]] - p).norm() <= tolerance
+                            {
+                                found = Some(j);
+                                break 'outer;
+                            }
+                        }
+                    }
+                }
+            }
+            remap.push(found.unwrap_or_else(|| {
+                grid.entry(k).or_default().push(keep.len());
+                keep.push(i);
+                keep.len() - 1
+            }));
+        }
+
+        let welded = self.vertices.len() - keep.len();
+        if welded > 0 {
+            self.vertices = keep.iter().map(|&i| self.vertices[i]).collect();
+            for a in &mut self.attributes {
+                *a = keep.iter().map(|&i| a[i]).collect();
+            }
+            for t in &mut self.triangles {
+                *t = t.map(|i| remap[i]);
+            }
+        }
+        welded
+    }
+
+    /// Makes winding consistent within each connected patch, returning the
+    /// number of triangles which were flipped
+    fn fix_winding(&mut self) -> usize {
+        // Map from undirected edge to triangles using that edge
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (i, t) in self.triangles.iter().enumerate() {
+            for (a, b) in [(t.x, t.y), (t.y, t.z), (t.z, t.x)] {
+                edges.entry((a.min(b), a.max(b))).or_default().push(i);
+            }
+        }
+        // Returns true if the triangle traverses the edge from `a` to `b`
+        let forward = |t: &nalgebra::Vector3<usize>, a, b| {
+            [(t.x, t.y), (t.y, t.z), (t.z, t.x)].contains(&(a, b))
+        };
+
+        let mut flip: Vec<Option<bool>> = vec![None; self.triangles.len()];
+        let mut flipped = 0;
+        for seed in 0..self.triangles.len() {
+            if flip[seed].is_some() {
+                continue;
+            }
+            // Walk the patch, orienting each neighbor to match
+            let mut patch = vec![seed];
+            let mut todo = VecDeque::from([seed]);
+            flip[seed] = Some(false);
+            while let Some(i) = todo.pop_front() {
+                let t = self.triangles[i];
+                let fi = flip[i].unwrap();
+                for (a, b) in [(t.x, t.y), (t.y, t.z), (t.z, t.x)] {
+                    for &j in &edges[&(a.min(b), a.max(b))] {
+                        if flip[j].is_some() {
+                            continue;
+                        }
+                        // Neighbors must traverse the shared edge in the
+                        // opposite direction (after any flips)
+                        let same = forward(&self.triangles[j], a, b);
+                        flip[j] = Some(fi != same);
+                        patch.push(j);
+                        todo.push_back(j);
+                    }
+                }
+            }
+
+            // Keep the majority orientation of the patch
+            let count = patch.iter().filter(|&&i| flip[i].unwrap()).count();
+            let invert = count * 2 > patch.len();
+            for &i in &patch {
+                if flip[i].unwrap() != invert {
+                    self.triangles[i].swap_rows(1, 2);
+                    flipped += 1;
+                }
+            }
+        }
+        flipped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::Vector3;
+
+    /// Returns a closed tetrahedron with outward-facing triangles
+    fn tetrahedron() -> Mesh {
+        let mut mesh = Mesh::new();
+        mesh.vertices = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        mesh.triangles = vec![
+            Vector3::new(0, 2, 1),
+            Vector3::new(0, 1, 3),
+            Vector3::new(0, 3, 2),
+            Vector3::new(1, 2, 3),
+        ];
+        mesh
+    }
+
+    #[test]
+    fn test_cleanup_clean() {
+        let mut mesh = tetrahedron();
+        let stats = mesh.cleanup(1e-6);
+        assert_eq!(stats, CleanupStats::default());
+        assert_eq!(mesh.triangles, tetrahedron().triangles);
+    }
+
+    #[test]
+    fn test_cleanup() {
+        let mut mesh = tetrahedron();
+
+        // Split vertex 3 into a nearly-coincident copy
+        mesh.vertices.push(Vector3::new(0.0, 0.0, 1.0 + 1e-7));
+        mesh.triangles[3] = Vector3::new(1, 2, 4);
+
+        // Flip one triangle, and add a sliver and a collapsed triangle
+        mesh.triangles[1] = Vector3::new(0, 3, 1);
+        mesh.vertices.push(Vector3::new(0.5, 0.0, 0.0));
+        mesh.triangles.push(Vector3::new(0, 5, 1));
+        mesh.triangles.push(Vector3::new(3, 4, 2));
+        mesh.attributes = vec![vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]];
+
+        let stats = mesh.cleanup(1e-5);
+        assert_eq!(
+            stats,
+            CleanupStats {
+                welded: 1,
+                degenerate: 2,
+                flipped: 1,
+            }
+        );
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.attributes, vec![vec![0.0, 1.0, 2.0, 3.0, 5.0]]);
+        assert_eq!(mesh.triangles, tetrahedron().triangles);
+    }
+}
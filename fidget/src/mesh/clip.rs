@@ -0,0 +1,88 @@
+//! Clipping to a region of interest (see [`Settings::clip`](super::Settings))
+use crate::{
+    context::BoundingBox,
+    eval::types::{Grad, Interval},
+};
+
+/// Distance field for a clipping box, which is intersected with the shape
+///
+/// The field is `max(lower - p, p - upper)` across all three axes.  It's
+/// negative inside the box and has axis-aligned unit gradients, so the cut
+/// surface is flat (with sharp edges) where the box slices through the shape.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Clip {
+    lower: [f32; 3],
+    upper: [f32; 3],
+}
+
+impl Clip {
+    pub fn new(b: BoundingBox) -> Self {
+        Self {
+            lower: b.lower.map(|v| v as f32),
+            upper: b.upper.map(|v| v as f32),
+        }
+    }
+
+    /// Evaluates the box's distance field over a region
+    pub fn interval(&self, p: [Interval; 3]) -> Interval {
+        let mut lower = f32::NEG_INFINITY;
+        let mut upper = f32::NEG_INFINITY;
+        for ((lo, hi), p) in self.lower.iter().zip(&self.upper).zip(p) {
+            // max(lo - p, p - hi) along this axis
+            lower = lower.max((lo - p.upper()).max(p.lower() - hi));
+            upper = upper.max((lo - p.lower()).max(p.upper() - hi));
+        }
+        Interval::new(lower, upper)
+    }
+
+    /// Evaluates the box's distance field and its gradient at a point
+    pub fn grad(&self, p: [f32; 3]) -> Grad {
+        let mut out = Grad::new(f32::NEG_INFINITY, 0.0, 0.0, 0.0);
+        for i in 0..3 {
+            let mut d = [0.0; 3];
+            let v = if self.lower[i] - p[i] > p[i] - self.upper[i] {
+                d[i] = -1.0;
+                self.lower[i] - p[i]
+            } else {
+                d[i] = 1.0;
+                p[i] - self.upper[i]
+            };
+            if v > out.v {
+                out = Grad::new(v, d[0], d[1], d[2]);
+            }
+        }
+        out
+    }
+
+    /// Intersects a shape's interval result with the box
+    pub fn clip_interval(&self, i: Interval, p: [Interval; 3]) -> Interval {
+        if i.has_nan() {
+            i
+        } else {
+            let c = self.interval(p);
+            Interval::new(i.lower().max(c.lower()), i.upper().max(c.upper()))
+        }
+    }
+
+    /// Intersects a shape's value with the box
+    ///
+    /// `NaN` values are preserved.
+    pub fn clip_value(&self, v: f32, p: [f32; 3]) -> f32 {
+        let c = self.grad(p).v;
+        if c > v {
+            c
+        } else {
+            v
+        }
+    }
+
+    /// Intersects a shape's value and gradient with the box
+    pub fn clip_grad(&self, g: Grad, p: [f32; 3]) -> Grad {
+        let c = self.grad(p);
+        if c.v > g.v {
+            c
+        } else {
+            g
+        }
+    }
+}
@@ -1,4 +1,5 @@
 //! Generated tables
-use super::types::{Corner, DirectedEdge, Intersection, Offset};
+use super::types::{Corner, DirectedEdge, Edge, Intersection, Offset};
 
 include!(concat!(env!("OUT_DIR"), "/mdc_tables.rs"));
+include!(concat!(env!("OUT_DIR"), "/mc_tables.rs"));
@@ -0,0 +1,363 @@
+//! Mesh self-intersection detection
+use super::Mesh;
+use nalgebra::Vector3;
+
+/// A pair of triangles which intersect each other
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SelfIntersection {
+    /// Indexes into [`Mesh::triangles`], with the lower index first
+    pub triangles: [usize; 2],
+    /// Endpoints of the segment where the triangles cross
+    pub segment: [Vector3<f32>; 2],
+}
+
+impl Mesh {
+    /// Finds every pair of triangles which intersect each other
+    ///
+    /// Triangles which share an edge are never reported, nor are triangles
+    /// which only touch at a single point (e.g. a shared vertex).  Coplanar
+    /// overlaps are not detected.
+    ///
+    /// Candidate pairs are found with a bounding volume hierarchy, so this is
+    /// fast enough to run on large meshes.  The results are sorted by
+    /// triangle index.
+    ///
+    /// ```
+    /// use fidget::mesh::Mesh;
+    /// use nalgebra::Vector3;
+    ///
+    /// // Two triangles, one poking through the other
+    /// let mut mesh = Mesh::new();
+    /// mesh.vertices = vec![
+    ///     Vector3::new(-1.0, -1.0, 0.0),
+    ///     Vector3::new(1.0, -1.0, 0.0),
+    ///     Vector3::new(0.0, 1.0, 0.0),
+    ///     Vector3::new(0.0, 0.0, -1.0),
+    ///     Vector3::new(0.0, 0.0, 1.0),
+    ///     Vector3::new(0.0, 2.0, 1.0),
+    /// ];
+    /// mesh.triangles = vec![Vector3::new(0, 1, 2), Vector3::new(3, 4, 5)];
+    ///
+    /// let hits = mesh.find_self_intersections();
+    /// assert_eq!(hits.len(), 1);
+    /// assert_eq!(hits[0].triangles, [0, 1]);
+    /// ```
+    pub fn find_self_intersections(&self) -> Vec<SelfIntersection> {
+        let tris: Vec<[Vector3<f64>; 3]> = self
+            .triangles
+            .iter()
+            .map(|t| [t.x, t.y, t.z].map(|i| self.vertices[i].cast()))
+            .collect();
+        let bvh = Bvh::new(&tris);
+
+        let mut out = vec![];
+        let mut candidates = vec![];
+        for (i, t) in tris.iter().enumerate() {
+            candidates.clear();
+            bvh.query(&Aabb::new(t), &mut candidates);
+            candidates.retain(|&j| j > i);
+            candidates.sort_unstable();
+            for &j in &candidates {
+                let (a, b) = (self.triangles[i], self.triangles[j]);
+                let shared = a.iter().filter(|v| b.iter().any(|w| w == *v));
+                if shared.count() >= 2 {
+                    continue;
+                }
+                if let Some(segment) = intersect(&tris[i], &tris[j]) {
+                    out.push(SelfIntersection {
+                        triangles: [i, j],
+                        segment: segment.map(|p| p.cast()),
+                    });
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Axis-aligned bounding box
+#[derive(Copy, Clone, Debug)]
+struct Aabb {
+    lower: Vector3<f64>,
+    upper: Vector3<f64>,
+}
+
+impl Aabb {
+    fn new(t: &[Vector3<f64>; 3]) -> Self {
+        Self {
+            lower: t[0].inf(&t[1]).inf(&t[2]),
+            upper: t[0].sup(&t[1]).sup(&t[2]),
+        }
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            lower: self.lower.inf(&other.lower),
+            upper: self.upper.sup(&other.upper),
+        }
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        (0..3).all(|i| {
+            self.lower[i] <= other.upper[i] && other.lower[i] <= self.upper[i]
+        })
+    }
+}
+
+/// Node in a [`Bvh`]
+enum BvhNode {
+    /// Range of indexes into [`Bvh::order`]
+    Leaf(usize, usize),
+    /// Indexes of child nodes
+    Branch(usize, usize),
+}
+
+/// Bounding volume hierarchy over triangles
+struct Bvh {
+    nodes: Vec<(Aabb, BvhNode)>,
+    /// Triangle indexes, arranged so that each leaf is a contiguous range
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    /// Maximum number of triangles in a leaf
+    const LEAF_SIZE: usize = 4;
+
+    fn new(tris: &[[Vector3<f64>; 3]]) -> Self {
+        let boxes: Vec<Aabb> = tris.iter().map(Aabb::new).collect();
+        let mut out = Self {
+            nodes: vec![],
+            order: (0..tris.len()).collect(),
+        };
+        if !tris.is_empty() {
+            out.build(&boxes, 0, tris.len());
+        }
+        out
+    }
+
+    /// Recursively builds nodes for `order[start..end]`, returning the index
+    /// of the new node
+    fn build(&mut self, boxes: &[Aabb], start: usize, end: usize) -> usize {
+        let bounds = self.order[start..end]
+            .iter()
+            .map(|&i| boxes[i])
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        let index = self.nodes.len();
+        if end - start <= Self::LEAF_SIZE {
+            self.nodes.push((bounds, BvhNode::Leaf(start, end)));
+            return index;
+        }
+
+        // Split at the median along the longest axis
+        let axis = (bounds.upper - bounds.lower).imax();
+        let center = |i: &usize| boxes[*i].lower[axis] + boxes[*i].upper[axis];
+        let mid = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            center(a).total_cmp(&center(b))
+        });
+
+        self.nodes.push((bounds, BvhNode::Leaf(start, end)));
+        let left = self.build(boxes, start, mid);
+        let right = self.build(boxes, mid, end);
+        self.nodes[index].1 = BvhNode::Branch(left, right);
+        index
+    }
+
+    /// Finds every triangle whose bounding box overlaps the given box
+    fn query(&self, b: &Aabb, out: &mut Vec<usize>) {
+        let mut todo = vec![];
+        if !self.nodes.is_empty() {
+            todo.push(0);
+        }
+        while let Some(n) = todo.pop() {
+            let (bounds, node) = &self.nodes[n];
+            if !bounds.overlaps(b) {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf(start, end) => {
+                    out.extend_from_slice(&self.order[start..end])
+                }
+                BvhNode::Branch(left, right) => {
+                    todo.push(left);
+                    todo.push(right);
+                }
+            }
+        }
+    }
+}
+
+/// Computes the segment where two triangles cross, if any
+fn intersect(
+    a: &[Vector3<f64>; 3],
+    b: &[Vector3<f64>; 3],
+) -> Option<[Vector3<f64>; 2]> {
+    // Distances below this threshold are treated as zero
+    let size = a
+        .iter()
+        .chain(b)
+        .flat_map(|p| a.iter().chain(b).map(move |q| (p - q).norm()))
+        .fold(0.0, f64::max);
+    let eps = size * 1e-9;
+
+    let sa = clip(a, b, eps)?;
+    let sb = clip(b, a, eps)?;
+
+    // Both segments lie along the line where the two planes meet, so we
+    // can compare them by projecting onto that line's direction.
+    let dir = (a[1] - a[0]).cross(&(a[2] - a[0]));
+    let dir = dir.cross(&(b[1] - b[0]).cross(&(b[2] - b[0])));
+    let span = |s: [Vector3<f64>; 2]| {
+        let (t0, t1) = (dir.dot(&s[0]), dir.dot(&s[1]));
+        if t0 <= t1 {
+            (t0, s[0], t1, s[1])
+        } else {
+            (t1, s[1], t0, s[0])
+        }
+    };
+    let (lo_a, pa, hi_a, qa) = span(sa);
+    let (lo_b, pb, hi_b, qb) = span(sb);
+    let start = if lo_a >= lo_b { pa } else { pb };
+    let end = if hi_a <= hi_b { qa } else { qb };
+    if lo_a.max(lo_b) > hi_a.min(hi_b) {
+        return None;
+    }
+
+    // Ignore pairs which only touch at a point (e.g. a shared vertex)
+    if (end - start).norm() <= eps {
+        return None;
+    }
+    Some([start, end])
+}
+
+/// Clips triangle `t` against the plane of triangle `p`
+///
+/// Returns the segment of `t` which lies on the plane, or `None` if `t` is
+/// entirely on one side of the plane (or in the plane).
+fn clip(
+    t: &[Vector3<f64>; 3],
+    p: &[Vector3<f64>; 3],
+    eps: f64,
+) -> Option<[Vector3<f64>; 2]> {
+    let normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+    let norm = normal.norm();
+    if norm == 0.0 {
+        return None;
+    }
+    let normal = normal / norm;
+    let d = t.map(|v| {
+        let d = normal.dot(&(v - p[0]));
+        if d.abs() <= eps {
+            0.0
+        } else {
+            d
+        }
+    });
+    if d.iter().all(|&d| d > 0.0)
+        || d.iter().all(|&d| d < 0.0)
+        || d.iter().all(|&d| d == 0.0)
+    {
+        return None;
+    }
+
+    let mut pts = arrayvec::ArrayVec::<Vector3<f64>, 3>::new();
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        if d[i] == 0.0 {
+            pts.push(t[i]);
+        } else if d[j] != 0.0 && (d[i] > 0.0) != (d[j] > 0.0) {
+            let f = d[i] / (d[i] - d[j]);
+            pts.push(t[i] + (t[j] - t[i]) * f);
+        }
+    }
+    match pts.len() {
+        1 => Some([pts[0], pts[0]]),
+        _ => Some([pts[0], pts[1]]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        context::Context,
+        mesh::{Octree, Settings},
+    };
+
+    fn triangles(tris: &[[[f32; 3]; 3]]) -> Mesh {
+        let mut mesh = Mesh::new();
+        for t in tris {
+            let i = mesh.vertices.len();
+            mesh.vertices.extend(t.iter().map(|p| Vector3::from(*p)));
+            mesh.triangles.push(Vector3::new(i, i + 1, i + 2));
+        }
+        mesh
+    }
+
+    #[test]
+    fn test_crossing_triangles() {
+        let mesh = triangles(&[
+            [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [0.0, 1.0, 0.0]],
+            [[0.0, 0.0, -1.0], [0.0, 0.0, 1.0], [0.0, 2.0, 1.0]],
+            // Floating above the first triangle
+            [[-1.0, -1.0, 0.5], [1.0, -1.0, 0.5], [0.0, 1.0, 0.5]],
+        ]);
+        let hits = mesh.find_self_intersections();
+        assert_eq!(hits.len(), 2, "{hits:?}");
+
+        // The second triangle crosses the first along the Y axis
+        assert_eq!(hits[0].triangles, [0, 1]);
+        let mut ys = hits[0].segment.map(|p| p.y);
+        ys.sort_by(f32::total_cmp);
+        assert!((ys[0] - 0.0).abs() < 1e-6 && (ys[1] - 1.0).abs() < 1e-6);
+        assert!(hits[0].segment.iter().all(|p| p.x == 0.0 && p.z == 0.0));
+
+        assert_eq!(hits[1].triangles, [1, 2]);
+    }
+
+    #[test]
+    fn test_shared_vertex() {
+        let mut mesh = triangles(&[
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.5, 0.5, -1.0]],
+        ]);
+        // The second triangle pokes through the first, starting at the
+        // shared vertex
+        mesh.triangles[1].x = 0;
+        let hits = mesh.find_self_intersections();
+        assert_eq!(hits.len(), 1);
+
+        // Touching at a single vertex isn't an intersection
+        mesh.vertices[5] = Vector3::new(-1.0, -1.0, 1.0);
+        assert!(mesh.find_self_intersections().is_empty());
+    }
+
+    #[test]
+    fn test_sphere_no_intersections() {
+        let mut ctx = Context::new();
+        let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+        let (x2, y2, z2) = (
+            ctx.square(x).unwrap(),
+            ctx.square(y).unwrap(),
+            ctx.square(z).unwrap(),
+        );
+        let r = ctx.add(x2, y2).unwrap();
+        let r = ctx.add(r, z2).unwrap();
+        let r = ctx.sqrt(r).unwrap();
+        let shape = ctx.sub(r, 0.6).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(shape).unwrap();
+        let settings = Settings {
+            min_depth: 4,
+            max_depth: 4,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let mesh = Octree::build(&tape, settings).walk_dual(settings);
+        assert!(!mesh.triangles.is_empty());
+        assert_eq!(mesh.find_self_intersections(), vec![]);
+    }
+}
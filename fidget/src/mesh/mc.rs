@@ -0,0 +1,501 @@
+//! Marching Cubes meshing
+//!
+//! This is an alternative to Manifold Dual Contouring: it's simpler and always
+//! produces a watertight, manifold mesh, but vertices are only placed on cell
+//! edges, so sharp features are rounded off.
+use super::{
+    clip::Clip, gen::CELL_TO_TRIANGLES, morton::MortonKey, Mesh, Settings,
+};
+use crate::eval::{types::Interval, Family, Tape};
+use nalgebra::Vector3;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Number of levels at the bottom of the octree which are sampled as a block
+///
+/// Below this depth, cells are evaluated in bulk instead of being subdivided
+/// with interval arithmetic.
+const BRICK_DEPTH: u8 = 3;
+
+/// Key for a cell edge: its lower corner (on the global grid) and axis
+type EdgeKey = ([u32; 3], u8);
+
+/// A cubic block of cells which may contain the surface
+struct Brick<F: Family> {
+    /// Tape, simplified for this region
+    tape: Tape<F>,
+    /// Lower corner, on the global grid
+    origin: [u32; 3],
+    /// Number of cells along each side
+    size: u32,
+}
+
+/// Converts from a position on the global grid to a coordinate in `[-1, 1]`
+fn pos(i: u32, depth: u8) -> f32 {
+    i as f32 / (1u32 << depth) as f32 * 2.0 - 1.0
+}
+
+/// Recursively subdivides the given cell, collecting bricks in `out`
+///
+/// Cells which are entirely inside or outside the shape are discarded.
+fn find_bricks<F: Family>(
+    tape: &Tape<F>,
+    origin: [u32; 3],
+    depth: u8,
+    settings: &Settings,
+    out: &mut Vec<Brick<F>>,
+) {
+    let size = 1 << (settings.min_depth - depth);
+    let [x, y, z] = [0, 1, 2].map(|i| {
+        Interval::new(
+            pos(origin[i], settings.min_depth),
+            pos(origin[i] + size, settings.min_depth),
+        )
+    });
+    let clip = settings.clip.map(Clip::new);
+    if clip.is_some_and(|c| c.interval([x, y, z]).lower() > 0.0) {
+        return;
+    }
+    let (mut i, r) = tape.new_interval_evaluator().eval(x, y, z, &[]).unwrap();
+    if let Some(c) = clip {
+        i = c.clip_interval(i, [x, y, z]);
+    }
+    if !i.has_nan() && (i.lower() > 0.0 || i.upper() < 0.0) {
+        return;
+    }
+    let tape = match r {
+        Some(r) => r.simplify().unwrap(),
+        None => tape.clone(),
+    };
+    if depth + BRICK_DEPTH >= settings.min_depth {
+        out.push(Brick { tape, origin, size });
+        return;
+    }
+    let half = size / 2;
+    for c in 0..8 {
+        let child = [0, 1, 2].map(|i| origin[i] + half * ((c >> i) & 1));
+        find_bricks(&tape, child, depth + 1, settings, out);
+    }
+}
+
+/// Mesh fragment built by a single worker
+#[derive(Default)]
+struct Fragment {
+    vertices: Vec<Vector3<f32>>,
+    keys: Vec<EdgeKey>,
+    map: HashMap<EdgeKey, usize>,
+    triangles: Vec<Vector3<usize>>,
+}
+
+impl Fragment {
+    /// Samples the corners of every cell in a brick, then adds its triangles
+    ///
+    /// If `root` is present, samples on the faces of the brick (which are
+    /// shared with neighboring bricks) are evaluated with that tape instead of
+    /// the brick's simplified tape.  If `clip` is present, the shape is
+    /// intersected with its box.
+    fn brick<F: Family>(
+        &mut self,
+        brick: &Brick<F>,
+        depth: u8,
+        root: Option<&Tape<F>>,
+        clip: Option<Clip>,
+    ) {
+        let n = brick.size as usize + 1;
+        let (mut xs, mut ys, mut zs) = (vec![], vec![], vec![]);
+        for k in 0..n as u32 {
+            for j in 0..n as u32 {
+                for i in 0..n as u32 {
+                    xs.push(pos(brick.origin[0] + i, depth));
+                    ys.push(pos(brick.origin[1] + j, depth));
+                    zs.push(pos(brick.origin[2] + k, depth));
+                }
+            }
+        }
+        let mut values = brick
+            .tape
+            .new_float_slice_evaluator()
+            .eval(&xs, &ys, &zs, &[])
+            .unwrap()
+            .to_vec();
+        if let Some(root) = root.filter(|r| r.len() > brick.tape.len()) {
+            let face = |i: usize| {
+                let p = [i % n, (i / n) % n, i / (n * n)];
+                p.iter().any(|p| *p == 0 || *p == n - 1)
+            };
+            let faces = (0..values.len()).filter(|i| face(*i));
+            let (mut fx, mut fy, mut fz) = (vec![], vec![], vec![]);
+            for i in faces.clone() {
+                fx.push(xs[i]);
+                fy.push(ys[i]);
+                fz.push(zs[i]);
+            }
+            let out = root
+                .new_float_slice_evaluator()
+                .eval(&fx, &fy, &fz, &[])
+                .unwrap();
+            for (i, v) in faces.zip(out) {
+                values[i] = v;
+            }
+        }
+        if let Some(c) = clip {
+            for (i, v) in values.iter_mut().enumerate() {
+                *v = c.clip_value(*v, [xs[i], ys[i], zs[i]]);
+            }
+        }
+        let index = |p: [usize; 3]| p[0] + n * (p[1] + n * p[2]);
+
+        for k in 0..n - 1 {
+            for j in 0..n - 1 {
+                for i in 0..n - 1 {
+                    let v = [0, 1, 2, 3, 4, 5, 6, 7].map(|c| {
+                        values[index([
+                            i + (c & 1),
+                            j + ((c >> 1) & 1),
+                            k + ((c >> 2) & 1),
+                        ])]
+                    });
+                    let origin = [0, 1, 2]
+                        .map(|a| brick.origin[a] + [i, j, k][a] as u32);
+                    self.cell(origin, depth, v, 0.0);
+                }
+            }
+        }
+    }
+
+    /// Adds the triangles for a single cell
+    ///
+    /// `origin` is the cell's lower corner on the global grid, and `values`
+    /// are the shape's values at its corners; the surface is extracted at the
+    /// given iso-level.
+    fn cell(
+        &mut self,
+        origin: [u32; 3],
+        depth: u8,
+        values: [f32; 8],
+        iso: f32,
+    ) {
+        let mask = (0..8)
+            .filter(|c| values[*c] < iso)
+            .fold(0, |acc, c| acc | (1 << c));
+        let corner =
+            |c: usize| [0, 1, 2].map(|i| origin[i] + ((c >> i) & 1) as u32);
+        for tri in CELL_TO_TRIANGLES[mask] {
+            let t = tri.map(|e| {
+                let (a, b) = e.corners();
+                let (a, b) = (a.index(), b.index());
+                let key = (corner(a), (e.index() / 4) as u8);
+                self.vertex(key, || {
+                    let frac = (values[a] - iso) / (values[a] - values[b]);
+                    let p = |c| Vector3::from(corner(c).map(|i| pos(i, depth)));
+                    p(a) + (p(b) - p(a)) * frac
+                })
+            });
+            self.triangles.push(Vector3::from(t));
+        }
+    }
+
+    /// Looks up (or builds) the vertex on the given edge
+    fn vertex<V>(&mut self, key: EdgeKey, f: V) -> usize
+    where
+        V: FnOnce() -> Vector3<f32>,
+    {
+        let vertices = &mut self.vertices;
+        let keys = &mut self.keys;
+        *self.map.entry(key).or_insert_with(|| {
+            vertices.push(f());
+            keys.push(key);
+            vertices.len() - 1
+        })
+    }
+}
+
+/// Builds a mesh from the corner samples stored in an [`Octree`]
+///
+/// Every sampled cell must be at the same depth.
+///
+/// [`Octree`]: super::Octree
+pub(crate) fn mesh_samples(
+    samples: &HashMap<MortonKey, [f32; 8]>,
+    iso: f32,
+) -> Mesh {
+    let mut keys = samples.keys().collect::<Vec<_>>();
+    keys.sort();
+    let mut out = Fragment::default();
+    for k in keys {
+        out.cell(k.pos(), k.depth() as u8, samples[k], iso);
+    }
+    Mesh {
+        vertices: out.vertices,
+        triangles: out.triangles,
+        ..Mesh::default()
+    }
+}
+
+/// Builds a mesh of the given shape using Marching Cubes
+///
+/// The shape is sampled on a uniform grid of `2^min_depth` cells along each
+/// axis, spanning `[-1, 1]`.  Regions which are entirely inside or outside the
+/// shape are pruned with interval arithmetic, as in [`Octree::build`].
+/// `max_depth` is ignored, since Marching Cubes requires a uniform grid.
+///
+/// ```
+/// use fidget::{mesh, rhai::eval, vm};
+///
+/// let (sphere, ctx) = eval("sqrt(x*x + y*y + z*z) - 0.5")?;
+/// let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+/// let settings = mesh::Settings {
+///     min_depth: 4,
+///     max_depth: 4,
+///     threads: 0,
+///     clip: None,
+///     iso_band: None,
+///     exact_boundaries: false,
+///     edge_refinement: None,
+/// };
+/// let m = mesh::marching_cubes(&tape, settings);
+/// for v in &m.vertices {
+///     assert!((v.norm() - 0.5).abs() < 0.01);
+/// }
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// [`Octree::build`]: super::Octree::build
+pub fn marching_cubes<F: Family>(tape: &Tape<F>, settings: Settings) -> Mesh {
+    span!(
+        INFO,
+        "marching_cubes",
+        depth = settings.min_depth,
+        len = tape.len()
+    );
+    let mut bricks = vec![];
+    find_bricks(tape, [0; 3], 0, &settings, &mut bricks);
+    let clip = settings.clip.map(Clip::new);
+    let root = settings.exact_boundaries.then_some(tape);
+
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut out = Fragment::default();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            match bricks.get(i) {
+                Some(b) => out.brick(b, settings.min_depth, root, clip),
+                None => break out,
+            }
+        }
+    };
+    let fragments = if settings.threads == 0 {
+        vec![work()]
+    } else {
+        std::thread::scope(|s| {
+            let handles = (0..settings.threads)
+                .map(|_| s.spawn(work))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Merge fragments, deduplicating vertices on edges between bricks
+    let mut mesh = Mesh::new();
+    let mut map = HashMap::new();
+    for f in fragments {
+        let remap = f
+            .keys
+            .iter()
+            .zip(&f.vertices)
+            .map(|(key, v)| {
+                *map.entry(*key).or_insert_with(|| {
+                    mesh.vertices.push(*v);
+                    mesh.vertices.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        mesh.triangles
+            .extend(f.triangles.iter().map(|t| t.map(|i| remap[i])));
+    }
+    event!(
+        DEBUG,
+        bricks = bricks.len(),
+        triangles = mesh.triangles.len(),
+        "built mesh"
+    );
+    mesh
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+    use std::collections::BTreeMap;
+
+    fn sphere(ctx: &mut Context, r: f64) -> crate::context::Node {
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let z2 = ctx.square(z).unwrap();
+        let sum = ctx.add(x2, y2).unwrap();
+        let sum = ctx.add(sum, z2).unwrap();
+        let sum = ctx.sqrt(sum).unwrap();
+        ctx.sub(sum, r).unwrap()
+    }
+
+    /// Checks that every edge is shared by exactly two triangles, which use
+    /// it in opposite directions
+    fn check_manifold(mesh: &Mesh) {
+        let mut edges: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+        for t in &mesh.triangles {
+            assert!(t.x != t.y && t.y != t.z && t.z != t.x);
+            for e in [(t.x, t.y), (t.y, t.z), (t.z, t.x)] {
+                *edges.entry(e).or_default() += 1;
+            }
+        }
+        for (&(a, b), &n) in &edges {
+            assert_eq!(n, 1, "duplicate edge ({a}, {b})");
+            assert!(edges.contains_key(&(b, a)), "unpaired edge ({a}, {b})");
+        }
+    }
+
+    /// Returns the mesh's signed volume (positive if triangles face outwards)
+    fn volume(mesh: &Mesh) -> f32 {
+        mesh.triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = [t.x, t.y, t.z].map(|i| mesh.vertices[i]);
+                a.dot(&b.cross(&c)) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_mc_sphere() {
+        let mut ctx = Context::new();
+        let s = sphere(&mut ctx, 0.85);
+        let tape = ctx.get_tape::<crate::vm::Eval>(s).unwrap();
+
+        let mut prev: Option<Mesh> = None;
+        for threads in [0, 8] {
+            let settings = Settings {
+                min_depth: 5,
+                max_depth: 5,
+                threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let mesh = marching_cubes(&tape, settings);
+            check_manifold(&mesh);
+            for v in &mesh.vertices {
+                assert!((v.norm() - 0.85).abs() < 0.01, "bad vertex {v}");
+            }
+            let expected = 4.0 / 3.0 * std::f32::consts::PI * 0.85f32.powi(3);
+            let v = volume(&mesh);
+            assert!((v - expected).abs() / expected < 0.02, "bad volume {v}");
+            if let Some(prev) = prev {
+                assert_eq!(prev.vertices.len(), mesh.vertices.len());
+                assert_eq!(prev.triangles.len(), mesh.triangles.len());
+            }
+            prev = Some(mesh);
+        }
+    }
+
+    #[test]
+    fn test_mc_clip() {
+        let mut ctx = Context::new();
+        let s = sphere(&mut ctx, 0.85);
+        let tape = ctx.get_tape::<crate::vm::Eval>(s).unwrap();
+
+        // Cutting the sphere at X = 0.3 removes a spherical cap
+        let settings = Settings {
+            min_depth: 5,
+            max_depth: 5,
+            threads: 0,
+            clip: Some(crate::context::BoundingBox::new(
+                [-2.0; 3],
+                [0.3, 2.0, 2.0],
+            )),
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let mesh = marching_cubes(&tape, settings);
+        check_manifold(&mesh);
+        for v in &mesh.vertices {
+            assert!(v.x <= 0.3 + 1e-3, "vertex {v} is outside the clip");
+        }
+        let h = 0.85 - 0.3;
+        let cap = std::f32::consts::PI * h * h * (3.0 * 0.85 - h) / 3.0;
+        let expected = 4.0 / 3.0 * std::f32::consts::PI * 0.85f32.powi(3) - cap;
+        let v = volume(&mesh);
+        assert!((v - expected).abs() / expected < 0.02, "bad volume {v}");
+    }
+
+    #[test]
+    fn test_mc_ambiguous() {
+        // Many small spheres produce every kind of ambiguous face
+        let mut ctx = Context::new();
+        let mut shapes = vec![];
+        for i in 0..4 {
+            let s = sphere(&mut ctx, 0.11);
+            let offset = [0.13, -0.37, 0.29, -0.53][i];
+            let x = ctx.x();
+            let y = ctx.y();
+            let z = ctx.z();
+            let dx = ctx.sub(x, offset).unwrap();
+            let dy = ctx.add(y, offset * 0.7).unwrap();
+            let dz = ctx.sub(z, offset * 1.3).unwrap();
+            shapes.push(ctx.remap_xyz(s, [dx, dy, dz]).unwrap());
+        }
+        let root = ctx.min_many(shapes).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(root).unwrap();
+        for depth in 2..6 {
+            let settings = Settings {
+                min_depth: depth,
+                max_depth: depth,
+                threads: 0,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let mesh = marching_cubes(&tape, settings);
+            check_manifold(&mesh);
+
+            // Sampling brick faces with the unsimplified tape doesn't change
+            // anything, because the VM's simplified tapes are exact
+            let settings = Settings {
+                exact_boundaries: true,
+                ..settings
+            };
+            let exact = marching_cubes(&tape, settings);
+            assert_eq!(exact.vertices, mesh.vertices);
+            assert_eq!(exact.triangles, mesh.triangles);
+        }
+    }
+
+    #[test]
+    fn test_mc_empty() {
+        let mut ctx = Context::new();
+        let s = sphere(&mut ctx, 3.0);
+        let tape = ctx.get_tape::<crate::vm::Eval>(s).unwrap();
+        let settings = Settings {
+            min_depth: 4,
+            max_depth: 4,
+            threads: 4,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let mesh = marching_cubes(&tape, settings);
+        assert!(mesh.triangles.is_empty());
+        assert!(mesh.vertices.is_empty());
+    }
+}
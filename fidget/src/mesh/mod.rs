@@ -2,20 +2,38 @@
 
 mod builder;
 mod cell;
+mod cleanup;
+mod clip;
 mod dc;
 mod fixup;
 mod frame;
 mod gen;
+mod intersect;
+mod mc;
+mod morton;
 mod mt;
 mod octree;
 mod output;
 mod qef;
+mod quality;
+mod winding;
 
 #[doc(hidden)]
 pub mod types;
 
 // Re-export the main Octree type as public
-pub use octree::Octree;
+pub use cleanup::CleanupStats;
+pub use intersect::SelfIntersection;
+pub use mc::marching_cubes;
+pub use morton::MortonKey;
+pub use octree::{Occupancy, Octree};
+pub use quality::MeshError;
+
+use crate::{
+    context::BoundingBox,
+    eval::{Choice, Family, Tape},
+    Error,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -26,6 +44,11 @@ pub struct Mesh {
     pub triangles: Vec<nalgebra::Vector3<usize>>,
     /// Vertex positions
     pub vertices: Vec<nalgebra::Vector3<f32>>,
+    /// Per-vertex attributes
+    ///
+    /// Each attribute has one value per vertex; they're populated by
+    /// [`eval_attributes`](Self::eval_attributes).
+    pub attributes: Vec<Vec<f32>>,
 }
 
 impl Mesh {
@@ -33,6 +56,129 @@ impl Mesh {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Evaluates extra fields at every vertex, storing them as attributes
+    ///
+    /// Each field is a tape (e.g. texture coordinates, density, or a material
+    /// ID) which is evaluated at the vertex positions; its values are appended
+    /// to [`self.attributes`](Self::attributes).
+    ///
+    /// ```
+    /// use fidget::{mesh::{Octree, Settings}, rhai::eval, vm};
+    ///
+    /// let (sphere, ctx) = eval("sqrt(x*x + y*y + z*z) - 0.5")?;
+    /// let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+    /// let settings = Settings {
+    ///     min_depth: 4,
+    ///     max_depth: 4,
+    ///     threads: 0,
+    ///     clip: None,
+    ///     iso_band: None,
+    ///     exact_boundaries: false,
+    ///     edge_refinement: None,
+    /// };
+    /// let mut mesh = Octree::build(&tape, settings).walk_dual(settings);
+    ///
+    /// // Use the Z coordinate as a texture coordinate
+    /// let (z, ctx) = eval("z")?;
+    /// mesh.eval_attributes(&[ctx.get_tape::<vm::Eval>(z)?])?;
+    /// for (v, t) in mesh.vertices.iter().zip(&mesh.attributes[0]) {
+    ///     assert_eq!(v.z, *t);
+    /// }
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn eval_attributes<I: Family>(
+        &mut self,
+        fields: &[Tape<I>],
+    ) -> Result<(), Error> {
+        let xs = self.vertices.iter().map(|v| v.x).collect::<Vec<_>>();
+        let ys = self.vertices.iter().map(|v| v.y).collect::<Vec<_>>();
+        let zs = self.vertices.iter().map(|v| v.z).collect::<Vec<_>>();
+        let mut data = Default::default();
+        for f in fields {
+            let mut out = vec![0.0; self.vertices.len()];
+            f.new_float_slice_evaluator().eval_into(
+                &xs,
+                &ys,
+                &zs,
+                &[],
+                &mut out,
+                &mut data,
+            )?;
+            self.attributes.push(out);
+        }
+        Ok(())
+    }
+
+    /// Finds the material ID at every vertex
+    ///
+    /// `shape` is usually the tape which was meshed.  At each vertex, it's
+    /// evaluated with a tracing point evaluator, and the resulting choices are
+    /// passed to [`Data::material`](crate::eval::tape::Data::material); this
+    /// returns the named node which "won" the shape's `min` / `max` operations,
+    /// as an index into [`Data::names`](crate::eval::tape::Data::names).
+    ///
+    /// ```
+    /// use fidget::{context::Context, mesh::{Octree, Settings}, vm};
+    ///
+    /// let mut ctx = Context::new();
+    /// let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+    /// let mut sphere = |dx: f32, name: &str| -> Result<_, fidget::Error> {
+    ///     let x = ctx.sub(x, dx)?;
+    ///     let (x2, y2, z2) = (ctx.square(x)?, ctx.square(y)?, ctx.square(z)?);
+    ///     let r = ctx.add(x2, y2)?;
+    ///     let r = ctx.add(r, z2)?;
+    ///     let r = ctx.sqrt(r)?;
+    ///     let s = ctx.sub(r, 0.4)?;
+    ///     ctx.name(s, name)?;
+    ///     Ok(s)
+    /// };
+    /// let left = sphere(-0.3, "left")?;
+    /// let right = sphere(0.3, "right")?;
+    /// let shape = ctx.min(left, right)?;
+    ///
+    /// let tape = ctx.get_tape::<vm::Eval>(shape)?;
+    /// let settings = Settings {
+    ///     min_depth: 4,
+    ///     max_depth: 4,
+    ///     threads: 0,
+    ///     clip: None,
+    ///     iso_band: None,
+    ///     exact_boundaries: false,
+    ///     edge_refinement: None,
+    /// };
+    /// let mesh = Octree::build(&tape, settings).walk_dual(settings);
+    /// let materials = mesh.eval_materials(&tape)?;
+    /// for (v, m) in mesh.vertices.iter().zip(&materials) {
+    ///     // Skip vertices near the seam between the two spheres
+    ///     if v.x.abs() > 0.1 {
+    ///         let name = &tape.names()[m.unwrap() as usize];
+    ///         assert_eq!(name, if v.x < 0.0 { "left" } else { "right" });
+    ///     }
+    /// }
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn eval_materials<I: Family>(
+        &self,
+        shape: &Tape<I>,
+    ) -> Result<Vec<Option<u32>>, Error> {
+        let eval = shape.new_point_evaluator();
+        let ties = vec![Choice::Both; shape.choice_count()];
+        let mut data = Default::default();
+        self.vertices
+            .iter()
+            .map(|v| {
+                let (_, trace) =
+                    eval.eval_with(v.x, v.y, v.z, &[], &mut data)?;
+                // Without a trace, every choice was a tie
+                let choices = match &trace {
+                    Some(t) => t.choices(),
+                    None => &ties,
+                };
+                shape.material(choices)
+            })
+            .collect()
+    }
 }
 
 /// Settings when building an octree and mesh
@@ -55,4 +201,59 @@ pub struct Settings {
     ///
     /// This is **much slower**.
     pub max_depth: u8,
+
+    /// Optional region of interest
+    ///
+    /// If present, the octree only recurses into cells which intersect this
+    /// box, and the shape is intersected with the box while meshing; where the
+    /// box cuts through the shape, the mesh is capped with flat faces.
+    pub clip: Option<BoundingBox>,
+
+    /// Range of iso-levels which can be meshed from the finished octree
+    ///
+    /// If present, [`Octree::build`] subdivides down to `min_depth` wherever
+    /// the shape could be within `±iso_band` and stores the shape's values at
+    /// the corners of those cells, so that [`Octree::mesh_at`] can extract
+    /// offset surfaces without evaluating the shape again.  This uses more
+    /// memory and disables pruning with the shape's bounding box.
+    pub iso_band: Option<f32>,
+
+    /// Evaluate samples on cell boundaries with the unsimplified tape
+    ///
+    /// Tapes are simplified as the octree is subdivided, and two simplified
+    /// tapes may return slightly different values at a corner or edge shared
+    /// by neighboring cells, which can cause cracks in the mesh.  Setting this
+    /// flag makes neighboring cells agree on their shared samples; interval
+    /// pruning still uses simplified tapes.
+    pub exact_boundaries: bool,
+
+    /// Optional Newton refinement of edge intersections
+    ///
+    /// Dual contouring places each edge intersection by searching for a sign
+    /// change between samples along the edge, which quantizes its position.
+    /// If present, the intersection is then polished with Newton's method
+    /// using the shape's gradient, which improves the mesh when the octree
+    /// is shallow.  This has no effect on [`marching_cubes`].
+    pub edge_refinement: Option<EdgeRefinement>,
+}
+
+/// Settings for Newton refinement of edge intersections
+///
+/// See [`Settings::edge_refinement`] for details.
+#[derive(Copy, Clone, Debug)]
+pub struct EdgeRefinement {
+    /// Maximum number of Newton steps to take along each edge
+    pub iterations: u8,
+
+    /// Stop refining once the shape's absolute value is below this threshold
+    pub tolerance: f32,
+}
+
+impl Default for EdgeRefinement {
+    fn default() -> Self {
+        Self {
+            iterations: 4,
+            tolerance: 1e-6,
+        }
+    }
 }
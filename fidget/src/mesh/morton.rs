@@ -0,0 +1,150 @@
+//! Morton-order location codes for octree cells
+use super::types::{Axis, Corner};
+
+/// Location code for an octree cell
+///
+/// This is a Morton (Z-order) code with a leading sentinel bit: the root is
+/// `0b1`, and each level of descent appends the 3-bit child index (which is
+/// already ordered as `x | y << 1 | z << 2`, matching [`Corner`]).  The depth
+/// is therefore implied by the position of the sentinel bit, and keys for
+/// cells at different depths never collide.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct MortonKey(u64);
+
+impl Default for MortonKey {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+impl MortonKey {
+    /// Maximum depth which can be represented by a key
+    pub const MAX_DEPTH: usize = 21;
+
+    /// Returns the key for the root cell
+    pub const fn root() -> Self {
+        Self(1)
+    }
+
+    /// Returns the raw location code
+    pub fn raw(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the depth of this cell, where the root is at depth 0
+    pub fn depth(self) -> usize {
+        (63 - self.0.leading_zeros() as usize) / 3
+    }
+
+    /// Returns the key for the given child of this cell
+    ///
+    /// # Panics
+    /// If the child would exceed [`Self::MAX_DEPTH`]
+    pub fn child(self, c: Corner) -> Self {
+        assert!(self.depth() < Self::MAX_DEPTH);
+        Self(self.0 << 3 | c.index() as u64)
+    }
+
+    /// Returns the key for this cell's parent, or `None` for the root
+    pub fn parent(self) -> Option<Self> {
+        if self.0 == 1 {
+            None
+        } else {
+            Some(Self(self.0 >> 3))
+        }
+    }
+
+    /// Builds a key from an integer position at the given depth
+    ///
+    /// Each coordinate must be less than `1 << depth`.
+    pub fn from_pos(depth: usize, pos: [u32; 3]) -> Self {
+        assert!(depth <= Self::MAX_DEPTH);
+        let mut out = 1;
+        for d in (0..depth).rev() {
+            let x = (pos[0] >> d) & 1;
+            let y = (pos[1] >> d) & 1;
+            let z = (pos[2] >> d) & 1;
+            out = out << 3 | (x | y << 1 | z << 2) as u64;
+        }
+        Self(out)
+    }
+
+    /// Returns the integer position of this cell within its level
+    ///
+    /// Positions are in the range `0..(1 << depth)` on each axis, with 0 at the
+    /// lower (negative) side of the model.
+    pub fn pos(self) -> [u32; 3] {
+        let mut out = [0; 3];
+        for d in 0..self.depth() {
+            let c = (self.0 >> (3 * d)) & 0b111;
+            for (i, o) in out.iter_mut().enumerate() {
+                *o |= (((c >> i) & 1) as u32) << d;
+            }
+        }
+        out
+    }
+
+    /// Returns the key for the same-sized neighbor across a face
+    ///
+    /// `positive` selects the face on the positive side of `axis`.  Returns
+    /// `None` if the neighbor would be outside of the root cell.
+    pub fn neighbor(self, axis: Axis, positive: bool) -> Option<Self> {
+        let depth = self.depth();
+        let mut pos = self.pos();
+        let p = &mut pos[axis.index()];
+        if positive {
+            *p += 1;
+            if *p >= 1 << depth {
+                return None;
+            }
+        } else {
+            *p = p.checked_sub(1)?;
+        }
+        Some(Self::from_pos(depth, pos))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mesh::types::{X, Y, Z};
+
+    #[test]
+    fn test_morton_pos() {
+        let k = MortonKey::root();
+        assert_eq!(k.depth(), 0);
+        assert_eq!(k.pos(), [0, 0, 0]);
+        assert_eq!(k.parent(), None);
+
+        let k = k.child(X.into()).child(Y | Z);
+        assert_eq!(k.depth(), 2);
+        assert_eq!(k.pos(), [2, 1, 1]);
+        assert_eq!(MortonKey::from_pos(2, [2, 1, 1]), k);
+        assert_eq!(k.parent(), Some(MortonKey::root().child(X.into())));
+
+        for depth in 0..5 {
+            let n = 1 << depth;
+            for x in 0..n {
+                for y in 0..n {
+                    for z in 0..n {
+                        let k = MortonKey::from_pos(depth, [x, y, z]);
+                        assert_eq!(k.depth(), depth);
+                        assert_eq!(k.pos(), [x, y, z]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_morton_neighbor() {
+        let k = MortonKey::from_pos(3, [0, 7, 3]);
+        assert_eq!(k.neighbor(X, false), None);
+        assert_eq!(k.neighbor(X, true).unwrap().pos(), [1, 7, 3]);
+        assert_eq!(k.neighbor(Y, true), None);
+        assert_eq!(k.neighbor(Y, false).unwrap().pos(), [0, 6, 3]);
+        assert_eq!(k.neighbor(Z, true).unwrap().pos(), [0, 7, 4]);
+        assert_eq!(k.neighbor(Z, false).unwrap().pos(), [0, 7, 2]);
+        assert_eq!(MortonKey::root().neighbor(Z, false), None);
+    }
+}
@@ -102,6 +102,7 @@ impl<'a> DcWorker<'a> {
                 *vert_offsets.last().unwrap()
             ],
             triangles: vec![nalgebra::Vector3::zeros(); tri_count],
+            attributes: vec![],
         };
 
         let mut slice = mesh.vertices.as_mut_slice();
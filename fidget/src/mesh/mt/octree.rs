@@ -1,12 +1,11 @@
 //! Multithreaded octree construction
 use super::pool::{QueuePool, ThreadContext, ThreadPool};
 use crate::{
-    eval::Family,
+    eval::{pool::EvalContext, Family},
     mesh::{
         cell::{Cell, CellData, CellIndex},
         octree::{
-            BranchResult, CellResult, EvalData, EvalGroup, EvalStorage,
-            OctreeBuilder,
+            BranchResult, CellResult, EvalData, EvalGroup, OctreeBuilder,
         },
         types::Corner,
         Octree, Settings,
@@ -62,11 +61,11 @@ impl<I: Family> Task<I> {
         }
     }
 
-    fn release(self, storage: &mut EvalStorage<I>) {
+    fn release(self, storage: &mut EvalContext<I>) {
         if let Ok(mut t) = Arc::try_unwrap(self.data) {
             loop {
                 if let Ok(e) = Arc::try_unwrap(t.eval) {
-                    storage.claim(e);
+                    e.release(storage);
                 }
                 if let Some(parent) =
                     t.parent.and_then(|n| Arc::try_unwrap(n).ok())
@@ -223,7 +222,7 @@ impl<I: Family> OctreeWorker<I> {
             }
 
             if let Some(task) = self.queue.pop() {
-                // Each task represents 8 cells, so evaluate them one by one
+                // Each task represents 8 cells, so evaluate them all at once
                 // here and return results.
 
                 // Prepare a set of 8x cells for storage
@@ -232,16 +231,18 @@ impl<I: Family> OctreeWorker<I> {
                     self.octree.o.cells.push(Cell::Invalid.into());
                 }
 
-                for i in Corner::iter() {
+                let children = self.octree.eval_children(
+                    &task.eval,
+                    &mut self.data,
+                    &mut storage,
+                    task.target_cell,
+                    index,
+                    settings,
+                );
+                for (i, r) in Corner::iter().zip(children) {
                     let sub_cell = task.target_cell.child(index, i);
 
-                    match self.octree.eval_cell(
-                        &task.eval,
-                        &mut self.data,
-                        &mut storage,
-                        sub_cell,
-                        settings,
-                    ) {
+                    match r {
                         // If this child is finished, then record it locally.
                         // If it's a branching cell, then we'll let a caller
                         // fill it in eventually (via the done queue).
@@ -335,8 +336,9 @@ impl<I: Family> OctreeWorker<I> {
 
         // Check to see whether this is the last cell in the cluster of 8
         let target_cell = parent_task.target_cell;
-        let Some(r) = self.octree.check_done(target_cell, index & !7)
-            else { return };
+        let Some(r) = self.octree.check_done(target_cell, index & !7) else {
+            return;
+        };
 
         // It's safe to unwrap `task` here because the only task lacking a
         // parent is at the root of the tree, which is never a set of 8
@@ -3,23 +3,27 @@
 use super::{
     builder::MeshBuilder,
     cell::{Cell, CellData, CellIndex, CellVertex, Leaf},
+    clip::Clip,
     dc::DcBuilder,
     fixup::DcFixup,
     frame::Frame,
     gen::CELL_TO_VERT_TO_EDGES,
+    mc,
+    morton::MortonKey,
     mt::{DcWorker, OctreeWorker},
     qef::QuadraticErrorSolver,
-    types::{Axis, Corner, Edge, EdgeMask, Face, FaceMask},
-    Mesh, Settings,
+    types::{Axis, Corner, Edge, EdgeMask, Face, FaceMask, X, Y, Z},
+    EdgeRefinement, Mesh, Settings,
 };
 use crate::eval::{
-    float_slice::{FloatSliceEvalData, FloatSliceEvalStorage},
-    grad_slice::{GradSliceEvalData, GradSliceEvalStorage},
-    interval::{IntervalEvalData, IntervalEvalStorage},
-    tape, Family, FloatSliceEval, GradSliceEval, IntervalEval, Tape,
+    float_slice::FloatSliceEvalData, grad_slice::GradSliceEvalData,
+    interval::IntervalEvalData, interval_slice::IntervalSliceEvalData,
+    pool::EvalContext, types::Grad, types::Interval, Family, FloatSliceEval,
+    GradSliceEval, IntervalEval, IntervalSliceEval, Tape,
 };
+use crate::Error;
 use once_cell::sync::OnceCell;
-use std::{num::NonZeroUsize, sync::Arc};
+use std::{collections::HashMap, num::NonZeroUsize, sync::Arc};
 
 /// Helper struct to contain a set of matched evaluators
 ///
@@ -31,8 +35,14 @@ pub struct EvalGroup<I: Family> {
     // indirection (since the evaluators also contain `Arc`); could we flatten
     // them out?  (same with `Tape`, which is an `Arc<Data>`)
     pub interval: OnceCell<IntervalEval<I>>,
+    pub interval_slice: OnceCell<IntervalSliceEval<I>>,
     pub float_slice: OnceCell<FloatSliceEval<I>>,
     pub grad_slice: OnceCell<GradSliceEval<I>>,
+
+    /// Unsimplified group from which this group's tape was derived
+    ///
+    /// This is `None` for the root group itself.
+    pub root: Option<Arc<EvalGroup<I>>>,
 }
 
 impl<I: Family> EvalGroup<I> {
@@ -40,39 +50,50 @@ impl<I: Family> EvalGroup<I> {
         Self {
             tape,
             interval: OnceCell::new(),
+            interval_slice: OnceCell::new(),
             float_slice: OnceCell::new(),
             grad_slice: OnceCell::new(),
+            root: None,
         }
     }
-    fn interval(
-        &self,
-        s: &mut Vec<IntervalEvalStorage<I>>,
-    ) -> &IntervalEval<I> {
-        self.interval.get_or_init(|| {
-            self.tape.new_interval_evaluator_with_storage(
-                s.pop().unwrap_or_default(),
-            )
-        })
+    /// Builds a group for a tape simplified from `parent`'s tape
+    fn simplified(tape: Tape<I>, parent: &Arc<Self>) -> Self {
+        Self {
+            root: Some(parent.root.clone().unwrap_or_else(|| parent.clone())),
+            ..Self::new(tape)
+        }
     }
-    fn float_slice(
-        &self,
-        s: &mut Vec<FloatSliceEvalStorage<I>>,
-    ) -> &FloatSliceEval<I> {
-        self.float_slice.get_or_init(|| {
-            self.tape.new_float_slice_evaluator_with_storage(
-                s.pop().unwrap_or_default(),
-            )
-        })
+    fn interval(&self, s: &mut EvalContext<I>) -> &IntervalEval<I> {
+        self.interval
+            .get_or_init(|| s.new_interval_evaluator(&self.tape))
     }
-    fn grad_slice(
-        &self,
-        s: &mut Vec<GradSliceEvalStorage<I>>,
-    ) -> &GradSliceEval<I> {
-        self.grad_slice.get_or_init(|| {
-            self.tape.new_grad_slice_evaluator_with_storage(
-                s.pop().unwrap_or_default(),
-            )
-        })
+    fn interval_slice(&self, s: &mut EvalContext<I>) -> &IntervalSliceEval<I> {
+        self.interval_slice
+            .get_or_init(|| s.new_interval_slice_evaluator(&self.tape))
+    }
+    fn float_slice(&self, s: &mut EvalContext<I>) -> &FloatSliceEval<I> {
+        self.float_slice
+            .get_or_init(|| s.new_float_slice_evaluator(&self.tape))
+    }
+    fn grad_slice(&self, s: &mut EvalContext<I>) -> &GradSliceEval<I> {
+        self.grad_slice
+            .get_or_init(|| s.new_grad_slice_evaluator(&self.tape))
+    }
+    /// Returns the group's evaluators and tape to the given pool
+    pub fn release(mut self, s: &mut EvalContext<I>) {
+        if let Some(e) = self.interval.take() {
+            s.release_interval_evaluator(e);
+        }
+        if let Some(e) = self.interval_slice.take() {
+            s.release_interval_slice_evaluator(e);
+        }
+        if let Some(e) = self.float_slice.take() {
+            s.release_float_slice_evaluator(e);
+        }
+        if let Some(e) = self.grad_slice.take() {
+            s.release_grad_slice_evaluator(e);
+        }
+        s.release_tape(self.tape);
     }
 }
 
@@ -80,6 +101,7 @@ pub struct EvalData<I: Family> {
     float_data: FloatSliceEvalData<I>,
     grad_data: GradSliceEvalData<I>,
     interval_data: IntervalEvalData<I>,
+    interval_slice_data: IntervalSliceEvalData<I>,
 }
 
 impl<I: Family> Default for EvalData<I> {
@@ -88,38 +110,7 @@ impl<I: Family> Default for EvalData<I> {
             float_data: Default::default(),
             grad_data: Default::default(),
             interval_data: Default::default(),
-        }
-    }
-}
-
-pub struct EvalStorage<I: Family> {
-    pub workspace: tape::Workspace,
-    pub tape_storage: Vec<tape::Data>,
-    pub float_storage: Vec<FloatSliceEvalStorage<I>>,
-    pub interval_storage: Vec<IntervalEvalStorage<I>>,
-    pub grad_storage: Vec<GradSliceEvalStorage<I>>,
-}
-
-impl<I: Family> EvalStorage<I> {
-    pub fn claim(&mut self, mut e: EvalGroup<I>) {
-        self.interval_storage
-            .extend(e.interval.take().and_then(|s| s.take()));
-        self.float_storage
-            .extend(e.float_slice.take().and_then(|s| s.take()));
-        self.grad_storage
-            .extend(e.grad_slice.take().and_then(|s| s.take()));
-        self.tape_storage.extend(e.tape.take());
-    }
-}
-
-impl<I: Family> Default for EvalStorage<I> {
-    fn default() -> Self {
-        Self {
-            workspace: Default::default(),
-            tape_storage: Default::default(),
-            float_storage: Default::default(),
-            grad_storage: Default::default(),
-            interval_storage: Default::default(),
+            interval_slice_data: Default::default(),
         }
     }
 }
@@ -138,6 +129,32 @@ pub struct Octree {
     /// This is indexed by cell leaf index; the exact shape depends heavily on
     /// the number of intersections and vertices within each leaf.
     pub(crate) verts: Vec<CellVertex>,
+
+    /// Map from Morton location code to index in `cells`
+    ///
+    /// This is built lazily by [`Octree::locate`], and is only valid for a
+    /// finished octree.
+    locations: OnceCell<HashMap<MortonKey, usize>>,
+
+    /// Shape values at the corners of cells at `min_depth`
+    ///
+    /// This is only populated if [`Settings::iso_band`] is set, and is used by
+    /// [`Octree::mesh_at`].
+    samples: HashMap<MortonKey, [f32; 8]>,
+
+    /// Range of iso-levels which are captured by `samples`
+    iso_band: Option<f32>,
+}
+
+/// Classification of a point within an [`Octree`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Occupancy {
+    /// The point is in a cell which is entirely inside the shape
+    Inside,
+    /// The point is in a cell which is entirely outside the shape
+    Outside,
+    /// The point is in a leaf cell which contains part of the surface
+    Surface,
 }
 
 impl Octree {
@@ -160,6 +177,9 @@ impl Octree {
         let mut out = Octree {
             cells: Vec::with_capacity(*cell_offsets.last().unwrap()),
             verts: Vec::with_capacity(*vert_offsets.last().unwrap()),
+            locations: OnceCell::new(),
+            samples: HashMap::new(),
+            iso_band: None,
         };
 
         for (t, o) in os.iter().enumerate() {
@@ -178,14 +198,24 @@ impl Octree {
                 out.cells.push(c.into());
             }
             out.verts.extend(o.verts.iter().cloned());
+            out.samples.extend(o.samples.iter().map(|(k, v)| (*k, *v)));
         }
         out
     }
 
     /// Builds an octree to the given depth
     ///
-    /// The shape is evaluated on the region `[-1, 1]` on all axes
+    /// The shape is evaluated on the region `[-1, 1]` on all axes.  The
+    /// resulting cells are stored in depth-first Morton order (see
+    /// [`Octree::neighbor`] for lookups by location).
     pub fn build<I: Family>(tape: &Tape<I>, settings: Settings) -> Self {
+        span!(
+            INFO,
+            "octree_build",
+            min_depth = settings.min_depth,
+            max_depth = settings.max_depth,
+            len = tape.len()
+        );
         let eval = Arc::new(EvalGroup::new(tape.clone()));
 
         let mut octree = if settings.threads == 0 {
@@ -193,7 +223,7 @@ impl Octree {
             out.recurse(
                 &eval,
                 &mut EvalData::default(),
-                &mut EvalStorage::default(),
+                &mut EvalContext::default(),
                 CellIndex::default(),
                 settings,
             );
@@ -201,10 +231,11 @@ impl Octree {
         } else {
             OctreeWorker::scheduler(eval.clone(), settings)
         };
+        octree.iso_band = settings.iso_band;
 
         // If we can't refine any further, then return right away
         if settings.min_depth == settings.max_depth {
-            return octree;
+            return octree.into_morton();
         }
 
         loop {
@@ -239,6 +270,9 @@ impl Octree {
                 o: Octree {
                     cells,
                     verts: octree.verts,
+                    locations: OnceCell::new(),
+                    samples: octree.samples,
+                    iso_band: octree.iso_band,
                 },
                 leafs,
                 hermite: vec![LeafHermiteData::default()],
@@ -247,24 +281,280 @@ impl Octree {
             b.refine(
                 &eval,
                 &mut EvalData::default(),
-                &mut EvalStorage::default(),
+                &mut EvalContext::default(),
                 CellIndex::default(),
                 &fixup.needs_fixing,
+                settings.clip.map(Clip::new),
+                settings.edge_refinement,
             );
             octree = b.into();
         }
-        octree
+        octree.into_morton()
+    }
+
+    /// Rebuilds the octree with cells and vertices in depth-first Morton order
+    ///
+    /// Each branch's children are stored as a contiguous block of 8 cells
+    /// (indexed by [`Corner`], which matches the Morton child order), and each
+    /// block is immediately followed by the blocks of its first child's
+    /// subtree.  Leaf vertices are stored in the same order.  This keeps
+    /// spatially nearby cells close together in memory during dual contouring,
+    /// and drops any cells and vertices orphaned by collapsing or merging.
+    fn into_morton(mut self) -> Octree {
+        let mut out = Octree {
+            cells: vec![Cell::Invalid.into(); 8],
+            verts: Vec::with_capacity(self.verts.len()),
+            locations: OnceCell::new(),
+            samples: std::mem::take(&mut self.samples),
+            iso_band: self.iso_band,
+        };
+        self.reorder_cell(0, 0, &mut out);
+        out
+    }
+
+    fn reorder_cell(&self, from: usize, to: usize, out: &mut Octree) {
+        out.cells[to] = match self.cells[from].into() {
+            c @ (Cell::Empty | Cell::Full | Cell::Invalid) => c,
+            Cell::Branch { index, .. } => {
+                let start = out.cells.len();
+                out.cells.resize(start + 8, Cell::Invalid.into());
+                for i in Corner::iter() {
+                    self.reorder_cell(
+                        index + i.index(),
+                        start + i.index(),
+                        out,
+                    );
+                }
+                Cell::Branch {
+                    index: start,
+                    thread: 0,
+                }
+            }
+            Cell::Leaf(Leaf { mask, index }) => {
+                // Each leaf owns its vertices, followed by its intersections
+                let edges = &CELL_TO_VERT_TO_EDGES[mask as usize];
+                let count =
+                    edges.len() + edges.iter().map(|e| e.len()).sum::<usize>();
+                let start = out.verts.len();
+                out.verts.extend_from_slice(&self.verts[index..][..count]);
+                Cell::Leaf(Leaf { mask, index: start })
+            }
+        }
+        .into();
+    }
+
+    /// Finds the cell with the given Morton location code
+    ///
+    /// Returns `None` if the octree does not subdivide down to that cell
+    /// (e.g. because an ancestor is a leaf).  The lookup table is built on the
+    /// first call, after which lookups are O(1).
+    pub(crate) fn locate(&self, key: MortonKey) -> Option<CellIndex> {
+        let locations = self.locations.get_or_init(|| {
+            let mut out = HashMap::with_capacity(self.cells.len());
+            let mut todo = vec![CellIndex::default()];
+            while let Some(cell) = todo.pop() {
+                out.insert(cell.key, cell.index);
+                if let Cell::Branch { index, .. } = self[cell].into() {
+                    todo.extend(Corner::iter().map(|i| cell.child(index, i)));
+                }
+            }
+            out
+        });
+        locations
+            .get(&key)
+            .map(|&index| CellIndex::from_key(index, key))
+    }
+
+    /// Checks whether the octree contains a cell with the given location code
+    ///
+    /// This is `false` if the octree does not subdivide down to that cell
+    /// (e.g. because an ancestor is a leaf).
+    pub fn contains(&self, key: MortonKey) -> bool {
+        self.locate(key).is_some()
+    }
+
+    /// Finds the neighbor of a cell across the given face
+    ///
+    /// The neighbor is the smallest cell which is at least as large as the
+    /// cell at `key` and shares the face; it may be a branch, if the neighbor
+    /// is subdivided more finely.  Returns `None` if the face is on the
+    /// boundary of the octree.
+    ///
+    /// Each step is an O(1) lookup, so this is bounded by the difference in
+    /// depth between the two cells.
+    pub fn neighbor(
+        &self,
+        key: MortonKey,
+        axis: Axis,
+        positive: bool,
+    ) -> Option<MortonKey> {
+        let mut key = key.neighbor(axis, positive)?;
+        while !self.contains(key) {
+            key = key.parent()?;
+        }
+        Some(key)
+    }
+
+    /// Finds the smallest cell containing the given point
+    ///
+    /// Points on the boundary between two cells are assigned to the cell on
+    /// the positive side.  Returns `None` if the point is outside of the
+    /// octree's `[-1, 1]` region.
+    pub fn cell_at(&self, p: nalgebra::Vector3<f32>) -> Option<MortonKey> {
+        self.cell_index_at(p).map(|c| c.key)
+    }
+
+    fn cell_index_at(&self, p: nalgebra::Vector3<f32>) -> Option<CellIndex> {
+        if p.iter().any(|v| !(-1.0..=1.0).contains(v)) {
+            return None;
+        }
+        let mut cell = CellIndex::default();
+        while let Cell::Branch { index, .. } = self[cell].into() {
+            let b = cell.bounds;
+            let mut corner = Corner::new(0);
+            for (axis, (v, i)) in
+                [X, Y, Z].into_iter().zip(p.iter().zip([b.x, b.y, b.z]))
+            {
+                if *v >= i.midpoint() {
+                    corner = corner | axis;
+                }
+            }
+            cell = cell.child(index, corner);
+        }
+        Some(cell)
+    }
+
+    /// Checks whether the given point is inside or outside of the shape
+    ///
+    /// This is answered from the octree's cells, without evaluating the shape;
+    /// points in leaf cells (which contain the surface) are reported as
+    /// [`Occupancy::Surface`].  Returns `None` if the point is outside of the
+    /// octree's `[-1, 1]` region.
+    pub fn occupancy(&self, p: nalgebra::Vector3<f32>) -> Option<Occupancy> {
+        let cell = self.cell_index_at(p)?;
+        Some(match self[cell].into() {
+            Cell::Empty => Occupancy::Outside,
+            Cell::Full => Occupancy::Inside,
+            Cell::Leaf(..) => Occupancy::Surface,
+            Cell::Branch { .. } | Cell::Invalid => unreachable!(),
+        })
+    }
+
+    /// Finds the surface vertex nearest to the given point
+    ///
+    /// Candidates are the leaf vertices and the edge intersections found while
+    /// building the octree.  Returns the leaf cell containing the nearest
+    /// candidate and its position,
+    /// or `None` if the octree doesn't contain any surface.  The point may be
+    /// outside of the octree's `[-1, 1]` region.
+    ///
+    /// This is a branch-and-bound search, which skips cells that are farther
+    /// away than the best vertex found so far.
+    pub fn nearest_surface(
+        &self,
+        p: nalgebra::Vector3<f32>,
+    ) -> Option<(MortonKey, nalgebra::Vector3<f32>)> {
+        // Distance from the point to a cell's bounding box
+        let dist = |c: &CellIndex| {
+            let b = c.bounds;
+            nalgebra::Vector3::from_iterator(
+                p.iter()
+                    .zip([b.x, b.y, b.z])
+                    .map(|(v, i)| (i.lower() - v).max(v - i.upper()).max(0.0)),
+            )
+            .norm()
+        };
+
+        let mut best: Option<(f32, MortonKey, nalgebra::Vector3<f32>)> = None;
+        let mut todo = vec![CellIndex::default()];
+        while let Some(cell) = todo.pop() {
+            if best.is_some_and(|(d, ..)| dist(&cell) >= d) {
+                continue;
+            }
+            match self[cell].into() {
+                Cell::Empty | Cell::Full => (),
+                Cell::Leaf(Leaf { mask, index }) => {
+                    // Check the leaf's vertices and edge intersections
+                    let edges = &CELL_TO_VERT_TO_EDGES[mask as usize];
+                    let count = edges.len()
+                        + edges.iter().map(|e| e.len()).sum::<usize>();
+                    for v in &self.verts[index..][..count] {
+                        let d = (v.pos - p).norm();
+                        if best.is_none_or(|(b, ..)| d < b) {
+                            best = Some((d, cell.key, v.pos));
+                        }
+                    }
+                }
+                Cell::Branch { index, .. } => {
+                    // Push the nearest children last, so they're checked first
+                    let mut children = Corner::iter()
+                        .map(|i| cell.child(index, i))
+                        .map(|c| (dist(&c), c))
+                        .collect::<arrayvec::ArrayVec<_, 8>>();
+                    children.sort_by(|a, b| b.0.total_cmp(&a.0));
+                    todo.extend(children.into_iter().map(|(_, c)| c));
+                }
+                Cell::Invalid => panic!(),
+            }
+        }
+        best.map(|(_, key, pos)| (key, pos))
     }
 
     /// Recursively walks the dual of the octree, building a mesh
     pub fn walk_dual(&self, settings: Settings) -> Mesh {
+        span!(INFO, "walk_dual");
         let mut mesh = MeshBuilder::default();
 
-        if settings.threads == 0 {
+        let out = if settings.threads == 0 {
             mesh.cell(self, CellIndex::default());
             mesh.take()
         } else {
             DcWorker::scheduler(self, settings.threads)
+        };
+        event!(DEBUG, triangles = out.triangles.len(), "built mesh");
+        out
+    }
+
+    /// Builds a mesh of the surface at the given iso-level
+    ///
+    /// This uses the corner samples stored when the octree was built (see
+    /// [`Settings::iso_band`]) rather than evaluating the shape again, so an
+    /// octree can be used to extract several offset surfaces.  The surface is
+    /// extracted with Marching Cubes on the `min_depth` grid.
+    ///
+    /// Returns an error if the octree was built without `iso_band`, or if
+    /// `iso` is outside of that band.
+    ///
+    /// ```
+    /// use fidget::{mesh::{Octree, Settings}, rhai::eval, vm};
+    ///
+    /// let (sphere, ctx) = eval("sqrt(x*x + y*y + z*z) - 0.5")?;
+    /// let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+    /// let settings = Settings {
+    ///     min_depth: 5,
+    ///     max_depth: 5,
+    ///     threads: 0,
+    ///     clip: None,
+    ///     iso_band: Some(0.25),
+    ///     exact_boundaries: false,
+    ///     edge_refinement: None,
+    /// };
+    /// let octree = Octree::build(&tape, settings);
+    ///
+    /// // Extract a larger sphere, without evaluating the shape again
+    /// let mesh = octree.mesh_at(0.2)?;
+    /// for v in &mesh.vertices {
+    ///     assert!((v.norm() - 0.7).abs() < 0.01);
+    /// }
+    /// assert!(octree.mesh_at(0.3).is_err());
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn mesh_at(&self, iso: f32) -> Result<Mesh, Error> {
+        match self.iso_band {
+            Some(band) if iso.abs() <= band => {
+                Ok(mc::mesh_samples(&self.samples, iso))
+            }
+            _ => Err(Error::BadIsoLevel(iso)),
         }
     }
 
@@ -308,10 +598,12 @@ impl Octree {
                 Some(EdgeMask::new(lo as u8 + ((hi as u8) << 1)))
             }
             Cell::Branch { index, .. } => {
-                let Some(lo) = self.edge_mask(cell.child(index, a), edge) else {
+                let Some(lo) = self.edge_mask(cell.child(index, a), edge)
+                else {
                     return None;
                 };
-                let Some(hi) = self.edge_mask(cell.child(index, b), edge) else {
+                let Some(hi) = self.edge_mask(cell.child(index, b), edge)
+                else {
                     return None;
                 };
                 let center = lo.0 & (0b10) != 0;
@@ -473,6 +765,9 @@ impl From<OctreeBuilder> for Octree {
         Self {
             cells,
             verts: o.o.verts,
+            locations: OnceCell::new(),
+            samples: o.o.samples,
+            iso_band: o.o.iso_band,
         }
     }
 }
@@ -484,6 +779,9 @@ impl OctreeBuilder {
             o: Octree {
                 cells: vec![Cell::Invalid.into(); 8],
                 verts: vec![],
+                locations: OnceCell::new(),
+                samples: HashMap::new(),
+                iso_band: None,
             },
             leafs: vec![],
             hermite: vec![LeafHermiteData::default()],
@@ -499,6 +797,9 @@ impl OctreeBuilder {
             o: Octree {
                 cells: vec![],
                 verts: vec![],
+                locations: OnceCell::new(),
+                samples: HashMap::new(),
+                iso_band: None,
             },
             leafs: vec![],
             hermite: vec![LeafHermiteData::default()],
@@ -544,12 +845,16 @@ impl OctreeBuilder {
         &mut self,
         eval: &Arc<EvalGroup<I>>,
         data: &mut EvalData<I>,
-        storage: &mut EvalStorage<I>,
+        storage: &mut EvalContext<I>,
         cell: CellIndex,
         settings: Settings,
     ) -> CellResult<I> {
+        let clip = settings.clip.map(Clip::new);
+        if Self::skip_cell(eval, cell, settings, clip) {
+            return CellResult::Done(Cell::Empty);
+        }
         let (i, r) = eval
-            .interval(&mut storage.interval_storage)
+            .interval(storage)
             .eval_with(
                 cell.bounds.x,
                 cell.bounds.y,
@@ -558,30 +863,207 @@ impl OctreeBuilder {
                 &mut data.interval_data,
             )
             .unwrap();
-        if i.upper() < 0.0 {
-            CellResult::Done(Cell::Full)
-        } else if i.lower() > 0.0 {
-            CellResult::Done(Cell::Empty)
-        } else {
-            let sub_tape = if I::simplify_tree_during_meshing(cell.depth) {
-                r.map(|r| {
-                    Arc::new(EvalGroup::new(
-                        r.simplify_with(
-                            &mut storage.workspace,
-                            storage.tape_storage.pop().unwrap_or_default(),
+        let i = Self::clip_interval(i, cell, clip);
+        if let Some(c) = Self::classify(i, settings) {
+            return CellResult::Done(c);
+        }
+        let sub_tape = r
+            .filter(|_| Self::should_simplify::<I>(cell, settings))
+            .map(|r| {
+                Arc::new(EvalGroup::simplified(
+                    storage.simplify(&r).unwrap(),
+                    eval,
+                ))
+            });
+        self.finish_cell(eval, data, storage, cell, settings, clip, sub_tape)
+    }
+
+    /// Evaluates the 8 children of a cell in the octree
+    ///
+    /// Children are classified with a single call to the interval slice
+    /// evaluator, then evaluated as leafs (or returned for recursion) one by
+    /// one.  As in [`eval_cell`](Self::eval_cell), results are **not** written
+    /// back to the `cells` array.
+    ///
+    /// `index` is the position of the first child in the `cells` array.
+    pub(crate) fn eval_children<I: Family>(
+        &mut self,
+        eval: &Arc<EvalGroup<I>>,
+        data: &mut EvalData<I>,
+        storage: &mut EvalContext<I>,
+        cell: CellIndex,
+        index: usize,
+        settings: Settings,
+    ) -> [CellResult<I>; 8] {
+        let clip = settings.clip.map(Clip::new);
+        let children: [CellIndex; 8] =
+            std::array::from_fn(|j| cell.child(index, Corner::new(j as u8)));
+
+        // Pick out the children which actually need to be evaluated
+        let mut xs = [Interval::from(0.0); 8];
+        let mut ys = [Interval::from(0.0); 8];
+        let mut zs = [Interval::from(0.0); 8];
+        let mut targets = [0; 8];
+        let mut n = 0;
+        for (j, c) in children.iter().enumerate() {
+            if !Self::skip_cell(eval, *c, settings, clip) {
+                xs[n] = c.bounds.x;
+                ys[n] = c.bounds.y;
+                zs[n] = c.bounds.z;
+                targets[n] = j;
+                n += 1;
+            }
+        }
+
+        // Classify every child at once, simplifying the tape for children
+        // that are ambiguous.  The interval results borrow from `data`, so
+        // ambiguous children are finished in a second pass.
+        let mut out: [CellResult<I>; 8] =
+            std::array::from_fn(|_| CellResult::Done(Cell::Empty));
+        let mut ambiguous: [Option<Option<Arc<EvalGroup<I>>>>; 8] =
+            Default::default();
+        if n > 0 {
+            let r = eval
+                .interval_slice(storage)
+                .eval_with(
+                    &xs[..n],
+                    &ys[..n],
+                    &zs[..n],
+                    &[],
+                    &mut data.interval_slice_data,
+                )
+                .unwrap();
+            for (k, &j) in targets[..n].iter().enumerate() {
+                let child = children[j];
+                let i = Self::clip_interval(r.values()[k], child, clip);
+                match Self::classify(i, settings) {
+                    Some(c) => out[j] = CellResult::Done(c),
+                    None => {
+                        ambiguous[j] = Some(
+                            r.trace(k)
+                                .filter(|_| {
+                                    Self::should_simplify::<I>(child, settings)
+                                })
+                                .map(|t| {
+                                    Arc::new(EvalGroup::simplified(
+                                        storage.simplify(&t).unwrap(),
+                                        eval,
+                                    ))
+                                }),
                         )
-                        .unwrap(),
-                    ))
-                })
-            } else {
-                None
+                    }
+                }
+            }
+        }
+        for (j, sub_tape) in ambiguous.into_iter().enumerate() {
+            if let Some(sub_tape) = sub_tape {
+                out[j] = self.finish_cell(
+                    eval,
+                    data,
+                    storage,
+                    children[j],
+                    settings,
+                    clip,
+                    sub_tape,
+                );
+            }
+        }
+        out
+    }
+
+    /// Checks whether a cell is known to be empty without evaluating it
+    ///
+    /// Cells outside of the shape's bounds are known to be empty.  The bounds
+    /// only guarantee that the shape is positive, so they can't be used when
+    /// sampling a band of iso-levels.
+    fn skip_cell<I: Family>(
+        eval: &EvalGroup<I>,
+        cell: CellIndex,
+        settings: Settings,
+        clip: Option<Clip>,
+    ) -> bool {
+        let b = cell.bounds;
+        let band = settings.iso_band.unwrap_or(0.0);
+        (settings.iso_band.is_none()
+            && !eval.tape.bounds().intersects(b.x, b.y, b.z))
+            || clip.is_some_and(|c| c.interval([b.x, b.y, b.z]).lower() > band)
+    }
+
+    /// Intersects a cell's interval result with the clipping box, if present
+    fn clip_interval(
+        i: Interval,
+        cell: CellIndex,
+        clip: Option<Clip>,
+    ) -> Interval {
+        match clip {
+            Some(c) => {
+                let b = cell.bounds;
+                c.clip_interval(i, [b.x, b.y, b.z])
+            }
+            None => i,
+        }
+    }
+
+    /// Classifies a cell as full or empty based on its interval result
+    ///
+    /// Returns `None` if the cell is ambiguous.
+    fn classify(i: Interval, settings: Settings) -> Option<Cell> {
+        let band = settings.iso_band.unwrap_or(0.0);
+        if i.upper() < -band {
+            Some(Cell::Full)
+        } else if i.lower() > band {
+            Some(Cell::Empty)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether an ambiguous cell should simplify its tape
+    ///
+    /// Every sample taken by a leaf is on a corner or edge which it shares
+    /// with its neighbors, so leafs are evaluated with the root tape if we need
+    /// them to agree.
+    fn should_simplify<I: Family>(cell: CellIndex, settings: Settings) -> bool {
+        let leaf = cell.depth == settings.min_depth as usize;
+        I::simplify_tree_during_meshing(cell.depth)
+            && !(leaf && settings.exact_boundaries)
+    }
+
+    /// Finishes evaluating an ambiguous cell
+    ///
+    /// Leaf cells are evaluated and returned; other cells are returned for
+    /// recursion, using `sub_tape` if present.
+    #[allow(clippy::too_many_arguments)]
+    fn finish_cell<I: Family>(
+        &mut self,
+        eval: &Arc<EvalGroup<I>>,
+        data: &mut EvalData<I>,
+        storage: &mut EvalContext<I>,
+        cell: CellIndex,
+        settings: Settings,
+        clip: Option<Clip>,
+        sub_tape: Option<Arc<EvalGroup<I>>>,
+    ) -> CellResult<I> {
+        if cell.depth == settings.min_depth as usize {
+            let eval = match &eval.root {
+                Some(root) if settings.exact_boundaries => root.clone(),
+                _ => sub_tape.unwrap_or_else(|| eval.clone()),
             };
-            if cell.depth == settings.min_depth as usize {
-                let eval = sub_tape.unwrap_or_else(|| eval.clone());
-                CellResult::Done(self.leaf(&eval, data, storage, cell))
-            } else {
-                CellResult::Recurse(sub_tape.unwrap_or_else(|| eval.clone()))
+            let corners = Self::corners(&eval, data, storage, cell, clip);
+            if settings.iso_band.is_some() {
+                self.o.samples.insert(cell.key, corners);
             }
+            CellResult::Done(self.leaf(
+                &eval,
+                data,
+                storage,
+                cell,
+                corners,
+                clip,
+                settings.edge_refinement,
+            ))
+        } else {
+            CellResult::Recurse(sub_tape.unwrap_or_else(|| eval.clone()))
         }
     }
 
@@ -625,20 +1107,41 @@ impl OctreeBuilder {
         &mut self,
         eval: &Arc<EvalGroup<I>>,
         data: &mut EvalData<I>,
-        storage: &mut EvalStorage<I>,
+        storage: &mut EvalContext<I>,
+        cell: CellIndex,
+        settings: Settings,
+    ) {
+        let r = self.eval_cell(eval, data, storage, cell, settings);
+        self.record_child(data, storage, cell, r, settings);
+    }
+
+    /// Records an evaluated cell, recursing into it if necessary
+    fn record_child<I: Family>(
+        &mut self,
+        data: &mut EvalData<I>,
+        storage: &mut EvalContext<I>,
         cell: CellIndex,
+        r: CellResult<I>,
         settings: Settings,
     ) {
-        match self.eval_cell(eval, data, storage, cell, settings) {
+        match r {
             CellResult::Done(c) => self.o[cell] = c.into(),
             CellResult::Recurse(sub_eval) => {
                 let index = self.o.cells.len();
                 for _ in Corner::iter() {
                     self.o.cells.push(Cell::Invalid.into());
                 }
-                for i in Corner::iter() {
-                    let cell = cell.child(index, i);
-                    self.recurse(&sub_eval, data, storage, cell, settings);
+                let children = self.eval_children(
+                    &sub_eval, data, storage, cell, index, settings,
+                );
+                for (i, r) in Corner::iter().zip(children) {
+                    self.record_child(
+                        data,
+                        storage,
+                        cell.child(index, i),
+                        r,
+                        settings,
+                    );
                 }
 
                 let r = self.check_done(cell, index).unwrap();
@@ -657,26 +1160,22 @@ impl OctreeBuilder {
 
                 // Try to recycle tape storage
                 if let Ok(e) = Arc::try_unwrap(sub_eval) {
-                    storage.claim(e);
+                    e.release(storage);
                 }
             }
         }
     }
 
-    /// Evaluates the given leaf
+    /// Evaluates the shape at the corners of a cell
     ///
-    /// Writes the leaf vertex to `self.o.verts`, hermite data to
-    /// `self.hermite`, and the leaf data to `self.leafs`.  Does **not** write
-    /// anything to `self.o.cells`; the cell is returned instead.
-    fn leaf<I: Family>(
-        &mut self,
+    /// If `clip` is present, the shape is intersected with its box.
+    fn corners<I: Family>(
         eval: &EvalGroup<I>,
         data: &mut EvalData<I>,
-        storage: &mut EvalStorage<I>,
+        storage: &mut EvalContext<I>,
         cell: CellIndex,
-    ) -> Cell {
-        let float_eval = eval.float_slice(&mut storage.float_storage);
-
+        clip: Option<Clip>,
+    ) -> [f32; 8] {
         let mut xs = [0.0; 8];
         let mut ys = [0.0; 8];
         let mut zs = [0.0; 8];
@@ -687,14 +1186,46 @@ impl OctreeBuilder {
             zs[i.index()] = z;
         }
 
-        let out = float_eval
+        let mut out: [f32; 8] = eval
+            .float_slice(storage)
             .eval_with(&xs, &ys, &zs, &[], &mut data.float_data)
+            .unwrap()
+            .try_into()
             .unwrap();
-        debug_assert_eq!(out.len(), 8);
+        if let Some(c) = clip {
+            for (i, v) in out.iter_mut().enumerate() {
+                *v = c.clip_value(*v, [xs[i], ys[i], zs[i]]);
+            }
+        }
+        out
+    }
+
+    /// Evaluates the given leaf
+    ///
+    /// Writes the leaf vertex to `self.o.verts`, hermite data to
+    /// `self.hermite`, and the leaf data to `self.leafs`.  Does **not** write
+    /// anything to `self.o.cells`; the cell is returned instead.
+    ///
+    /// `corners` are the shape's values at the cell's corners (from
+    /// [`corners`](Self::corners)).  If `clip` is present, the shape is
+    /// intersected with its box; if `refinement` is present, edge
+    /// intersections are polished with Newton's method.
+    #[allow(clippy::too_many_arguments)]
+    fn leaf<I: Family>(
+        &mut self,
+        eval: &EvalGroup<I>,
+        data: &mut EvalData<I>,
+        storage: &mut EvalContext<I>,
+        cell: CellIndex,
+        corners: [f32; 8],
+        clip: Option<Clip>,
+        refinement: Option<EdgeRefinement>,
+    ) -> Cell {
+        let float_eval = eval.float_slice(storage);
 
         // Build a mask of active corners, which determines cell
         // topology / vertex count / active edges / etc.
-        let mask = out
+        let mask = corners
             .iter()
             .enumerate()
             .filter(|(_i, &v)| v < 0.0)
@@ -787,9 +1318,18 @@ impl OctreeBuilder {
             debug_assert_eq!(i, EDGE_SEARCH_SIZE * edge_count);
 
             // Do the actual evaluation
-            let out = float_eval
-                .eval_with(xs, ys, zs, &[], &mut data.float_data)
-                .unwrap();
+            let mut out = [0f32; 12 * EDGE_SEARCH_SIZE];
+            let out = &mut out[..edge_count * EDGE_SEARCH_SIZE];
+            out.copy_from_slice(
+                float_eval
+                    .eval_with(xs, ys, zs, &[], &mut data.float_data)
+                    .unwrap(),
+            );
+            if let Some(c) = clip {
+                for (i, v) in out.iter_mut().enumerate() {
+                    *v = c.clip_value(*v, [xs[i], ys[i], zs[i]]);
+                }
+            }
 
             // Update start and end positions based on evaluation
             for ((start, end), search) in start
@@ -827,28 +1367,100 @@ impl OctreeBuilder {
         }
 
         // Populate intersections to the average of start and end
-        let intersections: arrayvec::ArrayVec<nalgebra::Vector3<u16>, 12> =
+        let mut intersections: arrayvec::ArrayVec<nalgebra::Vector3<f32>, 12> =
             start
                 .iter()
                 .zip(end.iter())
                 .map(|(a, b)| {
-                    ((a.map(|v| v as u32) + b.map(|v| v as u32)) / 2)
-                        .map(|v| v as u16)
+                    cell.pos(
+                        ((a.map(|v| v as u32) + b.map(|v| v as u32)) / 2)
+                            .map(|v| v as u16),
+                    )
                 })
                 .collect();
 
-        for (i, xyz) in intersections.iter().enumerate() {
-            let pos = cell.pos(*xyz);
+        let grad_eval = eval.grad_slice(storage);
+        if let Some(r) = refinement {
+            // Each intersection stays within the bracket found by the search,
+            // which shrinks as we learn which side of the surface we're on.
+            let mut brackets: arrayvec::ArrayVec<_, 12> = start
+                .iter()
+                .zip(end.iter())
+                .map(|(a, b)| (cell.pos(*a), cell.pos(*b)))
+                .collect();
+            let mut done = [false; 12];
+            for _ in 0..r.iterations {
+                for (i, pos) in intersections.iter().enumerate() {
+                    xs[i] = pos.x;
+                    ys[i] = pos.y;
+                    zs[i] = pos.z;
+                }
+                let grads = grad_eval
+                    .eval_with(xs, ys, zs, &[], &mut data.grad_data)
+                    .unwrap();
+                for (i, (pos, (inside, outside))) in intersections
+                    .iter_mut()
+                    .zip(brackets.iter_mut())
+                    .enumerate()
+                {
+                    if done[i] {
+                        continue;
+                    }
+                    let mut g = grads[i];
+                    if let Some(c) = clip {
+                        g = c.clip_grad(g, [pos.x, pos.y, pos.z]);
+                    }
+                    if g.v.abs() <= r.tolerance {
+                        done[i] = true;
+                        continue;
+                    }
+                    if g.v < 0.0 {
+                        *inside = *pos;
+                    } else {
+                        *outside = *pos;
+                    }
+
+                    // Take a Newton step along the edge, falling back to
+                    // bisection if it would leave the bracket.
+                    let axis = (*outside - *inside).iamax();
+                    let d = [g.dx, g.dy, g.dz][axis];
+                    let step = pos[axis] - g.v / d;
+                    let (lo, hi) = if inside[axis] < outside[axis] {
+                        (inside[axis], outside[axis])
+                    } else {
+                        (outside[axis], inside[axis])
+                    };
+                    pos[axis] = if step > lo && step < hi {
+                        step
+                    } else {
+                        (lo + hi) / 2.0
+                    };
+                }
+                if done[..intersections.len()].iter().all(|d| *d) {
+                    break;
+                }
+            }
+        }
+
+        for (i, pos) in intersections.iter().enumerate() {
             xs[i] = pos.x;
             ys[i] = pos.y;
             zs[i] = pos.z;
         }
 
         // TODO: special case for cells with multiple gradients ("features")
-        let grad_eval = eval.grad_slice(&mut storage.grad_storage);
-        let grads = grad_eval
-            .eval_with(xs, ys, zs, &[], &mut data.grad_data)
-            .unwrap();
+        let mut grads = [Grad::default(); 12];
+        let grads = &mut grads[..intersections.len()];
+        grads.copy_from_slice(
+            &grad_eval
+                .eval_with(xs, ys, zs, &[], &mut data.grad_data)
+                .unwrap()[..intersections.len()],
+        );
+        if let Some(c) = clip {
+            for (i, g) in grads.iter_mut().enumerate() {
+                *g = c.clip_grad(*g, [xs[i], ys[i], zs[i]]);
+            }
+        }
 
         let mut verts: arrayvec::ArrayVec<_, 4> = arrayvec::ArrayVec::new();
         let mut i = 0;
@@ -883,11 +1495,9 @@ impl OctreeBuilder {
         // TODO: use self.record_leaf here?
         let vert_index = self.o.verts.len();
         self.o.verts.extend(verts.into_iter());
-        self.o.verts.extend(
-            intersections
-                .into_iter()
-                .map(|pos| CellVertex { pos: cell.pos(pos) }),
-        );
+        self.o
+            .verts
+            .extend(intersections.into_iter().map(|pos| CellVertex { pos }));
 
         let hermite_index = self.push_hermite(hermite_cell);
         debug_assert!(hermite_index > 0);
@@ -1095,13 +1705,16 @@ impl OctreeBuilder {
     }
 
     /// Recurse down the octree, splitting the given leaf cells
+    #[allow(clippy::too_many_arguments)]
     fn refine<I: Family>(
         &mut self,
         eval: &Arc<EvalGroup<I>>,
         data: &mut EvalData<I>,
-        storage: &mut EvalStorage<I>,
+        storage: &mut EvalContext<I>,
         cell: CellIndex,
         needs_fixing: &[bool],
+        clip: Option<Clip>,
+        refinement: Option<EdgeRefinement>,
     ) {
         match self.o[cell].into() {
             Cell::Empty | Cell::Full | Cell::Leaf(..)
@@ -1114,7 +1727,11 @@ impl OctreeBuilder {
                 // Evaluate all 8 leafs
                 for i in Corner::iter() {
                     let subcell = cell.child(index, i);
-                    let leaf = self.leaf(eval, data, storage, subcell);
+                    let corners =
+                        Self::corners(eval, data, storage, subcell, clip);
+                    let leaf = self.leaf(
+                        eval, data, storage, subcell, corners, clip, refinement,
+                    );
                     match leaf {
                         Cell::Leaf(Leaf { index, .. }) => {
                             // Discard hermite data immediately, because we
@@ -1143,6 +1760,8 @@ impl OctreeBuilder {
                         storage,
                         cell.child(index, i),
                         needs_fixing,
+                        clip,
+                        refinement,
                     )
                 }
             }
@@ -1249,7 +1868,6 @@ impl LeafHermiteData {
     /// Merges an octree subdivision of leaf hermite data
     fn merge(leafs: [LeafHermiteData; 8]) -> Self {
         let mut out = Self::default();
-        use super::types::{X, Y, Z};
 
         // Accumulate intersections along edges
         for t in [X, Y, Z] {
@@ -1378,11 +1996,19 @@ mod test {
         min_depth: 0,
         max_depth: 0,
         threads: 0,
+        clip: None,
+        iso_band: None,
+        exact_boundaries: false,
+        edge_refinement: None,
     };
     const DEPTH1_SINGLE_THREAD: Settings = Settings {
         min_depth: 1,
         max_depth: 1,
         threads: 0,
+        clip: None,
+        iso_band: None,
+        exact_boundaries: false,
+        edge_refinement: None,
     };
 
     fn sphere(
@@ -1498,8 +2124,9 @@ mod test {
 
         // Each cell is a leaf with 4 vertices (3 edges, 1 center)
         for o in &octree.cells[8..] {
-            let Cell::Leaf ( Leaf { index, mask}) = (*o).into()
-                else { panic!() };
+            let Cell::Leaf(Leaf { index, mask }) = (*o).into() else {
+                panic!()
+            };
             assert_eq!(mask.count_ones(), 1);
             assert_eq!(index % 4, 0);
         }
@@ -1509,6 +2136,31 @@ mod test {
         assert!(!sphere_mesh.triangles.is_empty());
     }
 
+    #[test]
+    fn test_mesh_bounds() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.5; 3], 0.2);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+        assert!(tape.bounds().is_finite());
+
+        // Skipping cells outside the bounds shouldn't change the mesh
+        let settings = Settings {
+            min_depth: 4,
+            max_depth: 4,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let bounded = Octree::build(&tape, settings).walk_dual(settings);
+        let unbounded = tape.with_bounds(crate::context::BoundingBox::INFINITE);
+        let unbounded = Octree::build(&unbounded, settings).walk_dual(settings);
+        assert!(!bounded.triangles.is_empty());
+        assert_eq!(bounded.vertices, unbounded.vertices);
+        assert_eq!(bounded.triangles, unbounded.triangles);
+    }
+
     #[test]
     fn test_sphere_verts() {
         let ctx = BoundContext::new();
@@ -1547,6 +2199,56 @@ mod test {
         assert_eq!(edge_count, 6);
     }
 
+    #[test]
+    fn test_sphere_edge_refinement() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.0; 3], 0.2);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+
+        // Returns the worst radius error among edge vertices, which lie on
+        // the axes at this sampling depth
+        let edge_err = |settings| {
+            let mesh = Octree::build(&tape, settings).walk_dual(settings);
+            let edges = mesh
+                .vertices
+                .iter()
+                .filter(|v| v.iter().filter(|p| **p != 0.0).count() == 1)
+                .map(|v| (v.norm() - 0.2).abs())
+                .collect::<Vec<_>>();
+            assert_eq!(edges.len(), 6);
+            edges.into_iter().fold(0.0, f32::max)
+        };
+        let coarse = edge_err(DEPTH1_SINGLE_THREAD);
+        let refined = edge_err(Settings {
+            edge_refinement: Some(EdgeRefinement::default()),
+            ..DEPTH1_SINGLE_THREAD
+        });
+        assert!(refined < 1e-6, "refined error {refined} is too large");
+        assert!(refined < coarse, "{refined} is not better than {coarse}");
+    }
+
+    #[test]
+    fn test_sphere_positive_inside() {
+        let ctx = BoundContext::new();
+        let r = sphere(&ctx, [0.1, 0.0, -0.2], 0.0);
+        let tape = (r.clone() - 0.6).get_tape::<crate::vm::Eval>().unwrap();
+        let inverted = (0.6 - r)
+            .get_tape::<crate::vm::Eval>()
+            .unwrap()
+            .with_sign_convention(crate::eval::SignConvention::PositiveInside);
+
+        let settings = Settings {
+            min_depth: 4,
+            max_depth: 4,
+            ..DEPTH0_SINGLE_THREAD
+        };
+        let expected = Octree::build(&tape, settings).walk_dual(settings);
+        let mesh = Octree::build(&inverted, settings).walk_dual(settings);
+        assert!(!mesh.triangles.is_empty());
+        assert_eq!(mesh.vertices, expected.vertices);
+        assert_eq!(mesh.triangles, expected.triangles);
+    }
+
     #[test]
     fn test_sphere_manifold() {
         let ctx = BoundContext::new();
@@ -1558,6 +2260,10 @@ mod test {
                 min_depth: 5,
                 max_depth: 5,
                 threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
             };
             let octree = Octree::build(&tape, settings);
             let sphere_mesh = octree.walk_dual(settings);
@@ -1577,6 +2283,61 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_sphere_clip() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.0; 3], 0.85);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+
+        // Cut the sphere with a plane at X = 0.3
+        let clip = crate::context::BoundingBox::new([-2.0; 3], [0.3, 2.0, 2.0]);
+        for threads in [0, 8] {
+            let settings = Settings {
+                min_depth: 5,
+                max_depth: 5,
+                threads,
+                clip: Some(clip),
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let mesh = Octree::build(&tape, settings).walk_dual(settings);
+            assert!(!mesh.triangles.is_empty());
+            if let Err(e) = check_for_vertex_dupes(&mesh) {
+                panic!("{e} (with {threads} threads)");
+            }
+            if let Err(e) = check_for_edge_matching(&mesh) {
+                panic!("{e} (with {threads} threads)");
+            }
+
+            let mut cap = 0;
+            for v in &mesh.vertices {
+                assert!(v.x <= 0.3 + 1e-3, "vertex {v} is outside the clip");
+                if (v.x - 0.3).abs() < 1e-3 {
+                    if v.yz().norm() < 0.7 {
+                        cap += 1;
+                    }
+                } else {
+                    assert!((v.norm() - 0.85).abs() < 0.01, "bad vertex {v}");
+                }
+            }
+            assert!(cap > 0, "missing cap (with {threads} threads)");
+        }
+
+        // A clip region which doesn't touch the shape produces nothing
+        let settings = Settings {
+            min_depth: 5,
+            max_depth: 5,
+            threads: 0,
+            clip: Some(crate::context::BoundingBox::new([0.9; 3], [1.0; 3])),
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let mesh = Octree::build(&tape, settings).walk_dual(settings);
+        assert!(mesh.triangles.is_empty());
+    }
+
     #[test]
     fn test_cube_verts() {
         let ctx = BoundContext::new();
@@ -1717,6 +2478,10 @@ mod test {
                     min_depth: 2,
                     max_depth: 2,
                     threads,
+                    clip: None,
+                    iso_band: None,
+                    exact_boundaries: false,
+                    edge_refinement: None,
                 };
                 let octree = Octree::build(&tape, settings);
 
@@ -1747,7 +2512,7 @@ mod test {
             out.recurse(
                 &eval,
                 &mut EvalData::default(),
-                &mut EvalStorage::default(),
+                &mut EvalContext::default(),
                 CellIndex::default(),
                 settings,
             );
@@ -1784,6 +2549,10 @@ mod test {
                 min_depth: 1,
                 max_depth: 1,
                 threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
             };
             let octree = Octree::build(&tape, settings);
             assert_eq!(
@@ -1805,6 +2574,10 @@ mod test {
                 min_depth: 5,
                 max_depth: 5,
                 threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
             };
             let octree = Octree::build(&tape, settings);
             let mesh = octree.walk_dual(settings);
@@ -1815,6 +2588,323 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_morton_order() {
+        const COLONNADE: &str = include_str!("../../../models/colonnade.vm");
+        let (ctx, root) =
+            crate::Context::from_text(COLONNADE.as_bytes()).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(root).unwrap();
+
+        // Child blocks should be allocated in depth-first order, without any
+        // orphaned cells left over from collapsing or merging.
+        fn check(octree: &Octree, cell: CellIndex, next: &mut usize) {
+            if let Cell::Branch { index, thread } = octree[cell].into() {
+                assert_eq!(index, *next);
+                assert_eq!(thread, 0);
+                *next += 8;
+                for i in Corner::iter() {
+                    check(octree, cell.child(index, i), next);
+                }
+            }
+        }
+        for threads in [0, 8] {
+            let settings = Settings {
+                min_depth: 5,
+                max_depth: 5,
+                threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let octree = Octree::build(&tape, settings);
+            let mut next = 8;
+            check(&octree, CellIndex::default(), &mut next);
+            assert_eq!(next, octree.cells.len());
+        }
+
+        // Reordering shouldn't change the resulting mesh
+        let settings = Settings {
+            min_depth: 5,
+            max_depth: 5,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let eval = Arc::new(EvalGroup::new(tape));
+        let mut b = OctreeBuilder::new();
+        b.recurse(
+            &eval,
+            &mut EvalData::default(),
+            &mut EvalContext::default(),
+            CellIndex::default(),
+            settings,
+        );
+        let raw: Octree = b.into();
+        let expected = raw.walk_dual(settings);
+        let actual = raw.into_morton().walk_dual(settings);
+        assert_eq!(expected.vertices, actual.vertices);
+        assert_eq!(expected.triangles, actual.triangles);
+    }
+
+    #[test]
+    fn test_octree_neighbor() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.2, 0.1, 0.0], 0.4);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+        let settings = Settings {
+            min_depth: 3,
+            max_depth: 3,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let octree = Octree::build(&tape, settings);
+        assert!(octree.contains(MortonKey::root()));
+        assert!(!octree.contains(MortonKey::from_pos(4, [0, 0, 0])));
+
+        let mut todo = vec![CellIndex::default()];
+        let mut count = 0;
+        while let Some(cell) = todo.pop() {
+            if let Cell::Branch { index, .. } = octree[cell].into() {
+                todo.extend(Corner::iter().map(|i| cell.child(index, i)));
+                continue;
+            }
+            for axis in [X, Y, Z] {
+                for positive in [false, true] {
+                    let Some(n) = octree.neighbor(cell.key, axis, positive)
+                    else {
+                        let b = cell.bounds[axis];
+                        let edge = if positive { b.upper() } else { b.lower() };
+                        assert_eq!(edge.abs(), 1.0);
+                        continue;
+                    };
+                    count += 1;
+                    assert!(n.depth() <= cell.depth);
+                    let nb = CellIndex::from_key(0, n).bounds;
+                    let (a, b) = (cell.bounds[axis], nb[axis]);
+                    if positive {
+                        assert_eq!(a.upper(), b.lower());
+                    } else {
+                        assert_eq!(a.lower(), b.upper());
+                    }
+                    for other in [X, Y, Z].into_iter().filter(|&a| a != axis) {
+                        let (a, b) = (cell.bounds[other], nb[other]);
+                        assert!(b.lower() <= a.lower());
+                        assert!(b.upper() >= a.upper());
+                    }
+                }
+            }
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_octree_queries() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.0; 3], 0.5);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+        let settings = Settings {
+            min_depth: 4,
+            max_depth: 4,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let octree = Octree::build(&tape, settings);
+        let v = nalgebra::Vector3::new;
+
+        assert_eq!(octree.occupancy(v(0.0, 0.0, 0.0)), Some(Occupancy::Inside));
+        assert_eq!(
+            octree.occupancy(v(0.9, -0.9, 0.9)),
+            Some(Occupancy::Outside)
+        );
+        assert_eq!(
+            octree.occupancy(v(0.49, 0.01, 0.01)),
+            Some(Occupancy::Surface)
+        );
+        assert_eq!(octree.occupancy(v(1.5, 0.0, 0.0)), None);
+
+        // Points are found in the smallest cell which contains them
+        let key = octree.cell_at(v(0.49, 0.01, 0.01)).unwrap();
+        assert_eq!(key.depth(), 4);
+        assert_eq!(key.pos(), [11, 8, 8]);
+        assert!(octree.contains(key));
+        assert_eq!(octree.cell_at(v(-1.0, 1.0, 0.0)).unwrap().pos()[0], 0);
+        assert_eq!(octree.cell_at(v(0.0, 0.0, -1.01)), None);
+
+        for p in [v(0.9, 0.0, 0.0), v(0.1, 0.0, 0.0), v(3.0, 0.0, 0.0)] {
+            let (key, pos) = octree.nearest_surface(p).unwrap();
+            assert!((pos.norm() - 0.5).abs() < 0.01, "bad vertex {pos}");
+            assert!((pos.normalize() - v(1.0, 0.0, 0.0)).norm() < 0.2);
+            assert!(octree.contains(key));
+
+            // Compare against a brute-force search through mesh vertices
+            let mesh = octree.walk_dual(settings);
+            let best = mesh
+                .vertices
+                .iter()
+                .map(|m| (m - p).norm())
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap();
+            assert!((pos - p).norm() <= best + 1e-6);
+        }
+
+        let empty = sphere(&ctx, [0.0; 3], 3.0);
+        let tape = empty.get_tape::<crate::vm::Eval>().unwrap();
+        let octree = Octree::build(&tape, settings);
+        assert_eq!(octree.occupancy(v(0.0, 0.0, 0.0)), Some(Occupancy::Inside));
+        assert_eq!(octree.nearest_surface(v(0.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_mesh_attributes() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.0; 3], 0.5);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+        let (x, y, _z) = ctx.axes();
+        let fields = [x.clone(), x * 2.0 + y]
+            .map(|f| f.get_tape::<crate::vm::Eval>().unwrap());
+
+        for threads in [0, 8] {
+            let settings = Settings {
+                min_depth: 4,
+                max_depth: 4,
+                threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let mut mesh = Octree::build(&tape, settings).walk_dual(settings);
+            assert!(mesh.attributes.is_empty());
+            mesh.eval_attributes(&fields).unwrap();
+            assert_eq!(mesh.attributes.len(), 2);
+            for (i, v) in mesh.vertices.iter().enumerate() {
+                assert_eq!(mesh.attributes[0][i], v.x);
+                let expected = v.x * 2.0 + v.y;
+                assert!((mesh.attributes[1][i] - expected).abs() < 1e-6);
+            }
+
+            // Attributes are appended
+            mesh.eval_attributes(&fields[..1]).unwrap();
+            assert_eq!(mesh.attributes.len(), 3);
+            assert_eq!(mesh.attributes[2], mesh.attributes[0]);
+        }
+    }
+
+    #[test]
+    fn test_mesh_at() {
+        let ctx = BoundContext::new();
+        let shape = sphere(&ctx, [0.0; 3], 0.5);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+
+        let mut prev = None;
+        for threads in [0, 8] {
+            let settings = Settings {
+                min_depth: 5,
+                max_depth: 5,
+                threads,
+                clip: None,
+                iso_band: Some(0.2),
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let octree = Octree::build(&tape, settings);
+
+            // Building with an iso band doesn't change the primary mesh
+            let mesh = octree.walk_dual(settings);
+            let plain = Octree::build(
+                &tape,
+                Settings {
+                    iso_band: None,
+                    ..settings
+                },
+            )
+            .walk_dual(settings);
+            assert_eq!(mesh.triangles.len(), plain.triangles.len());
+
+            for iso in [-0.2, 0.0, 0.15, 0.2] {
+                let mesh = octree.mesh_at(iso).unwrap();
+                assert!(!mesh.triangles.is_empty());
+                if let Err(e) = check_for_edge_matching(&mesh) {
+                    panic!("{e} (iso {iso}, {threads} threads)");
+                }
+                for v in &mesh.vertices {
+                    let err = (v.norm() - (0.5 + iso)).abs();
+                    assert!(err < 0.01, "bad vertex {v} at iso {iso}");
+                }
+            }
+            let mesh = octree.mesh_at(0.1).unwrap();
+            if let Some((verts, tris)) = prev {
+                assert_eq!(verts, mesh.vertices);
+                assert_eq!(tris, mesh.triangles);
+            }
+            prev = Some((mesh.vertices, mesh.triangles));
+
+            assert!(matches!(
+                octree.mesh_at(0.25),
+                Err(crate::Error::BadIsoLevel(..))
+            ));
+            assert!(octree.mesh_at(-0.3).is_err());
+        }
+
+        let settings = Settings {
+            min_depth: 5,
+            max_depth: 5,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        assert!(Octree::build(&tape, settings).mesh_at(0.0).is_err());
+    }
+
+    #[test]
+    fn test_exact_boundaries() {
+        let ctx = BoundContext::new();
+        let a = sphere(&ctx, [0.3, 0.0, 0.0], 0.5);
+        let b = sphere(&ctx, [-0.3, 0.1, 0.0], 0.4);
+        let shape = a.min(b);
+        let tape = shape.get_tape::<crate::vm::Eval>().unwrap();
+
+        for threads in [0, 8] {
+            let settings = Settings {
+                min_depth: 5,
+                max_depth: 5,
+                threads,
+                clip: None,
+                iso_band: None,
+                exact_boundaries: false,
+                edge_refinement: None,
+            };
+            let expected = Octree::build(&tape, settings).walk_dual(settings);
+            let settings = Settings {
+                exact_boundaries: true,
+                ..settings
+            };
+            let mesh = Octree::build(&tape, settings).walk_dual(settings);
+            if let Err(e) = check_for_edge_matching(&mesh) {
+                panic!("{e} (with {threads} threads)");
+            }
+            // The VM evaluator's simplified tapes are exact, so the mesh is
+            // unchanged (though vertex order depends on thread scheduling)
+            assert_eq!(mesh.vertices.len(), expected.vertices.len());
+            assert_eq!(mesh.triangles.len(), expected.triangles.len());
+            if threads == 0 {
+                assert_eq!(mesh.vertices, expected.vertices);
+                assert_eq!(mesh.triangles, expected.triangles);
+            }
+        }
+    }
+
     fn check_for_vertex_dupes(mesh: &Mesh) -> Result<(), String> {
         let mut verts = mesh.vertices.clone();
         verts.sort_by_key(|k| (k.x.to_bits(), k.y.to_bits(), k.z.to_bits()));
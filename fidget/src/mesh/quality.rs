@@ -0,0 +1,166 @@
+//! Measuring how closely a mesh matches its source shape
+use super::Mesh;
+use crate::{
+    eval::{Family, Tape},
+    Error,
+};
+
+/// Distance-field residuals on the surface of a mesh
+///
+/// Returned by [`Mesh::measure_error`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshError {
+    /// Number of samples with a finite value
+    pub samples: usize,
+    /// Number of samples where the shape's value isn't finite
+    pub non_finite: usize,
+    /// Largest `|f|` among samples
+    ///
+    /// For a distance field, this is a one-sided Hausdorff distance from the
+    /// (sampled) mesh to the true surface.
+    pub max: f32,
+    /// Mean `|f|` among samples
+    pub mean: f32,
+    /// Root-mean-square `f` among samples
+    pub rms: f32,
+    /// Position of the sample with the largest `|f|`
+    pub worst: [f32; 3],
+}
+
+impl Mesh {
+    /// Measures the shape's value on the surface of the mesh
+    ///
+    /// Every vertex is sampled; in addition, `subdivisions` rows of samples
+    /// are placed inside each triangle on a regular barycentric grid (so `1`
+    /// samples the centroid, `2` samples three points, `3` samples six, and so
+    /// on).  Samples
+    /// inside triangles catch errors which vertices alone can't, such as flat
+    /// triangles cutting across curved parts of the surface.
+    ///
+    /// If the shape is a distance field, the residuals are distances from the
+    /// mesh to the true surface; this makes the report a useful metric when
+    /// tuning octree depth.
+    ///
+    /// ```
+    /// use fidget::{mesh::{Octree, Settings}, rhai::eval, vm};
+    ///
+    /// let (sphere, ctx) = eval("sqrt(x*x + y*y + z*z) - 0.6")?;
+    /// let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+    /// let mut prev = f32::INFINITY;
+    /// for depth in [3, 5] {
+    ///     let settings = Settings {
+    ///         min_depth: depth,
+    ///         max_depth: depth,
+    ///         threads: 0,
+    ///         clip: None,
+    ///         iso_band: None,
+    ///         exact_boundaries: false,
+    ///         edge_refinement: None,
+    ///     };
+    ///     let mesh = Octree::build(&tape, settings).walk_dual(settings);
+    ///     let err = mesh.measure_error(&tape, 2)?;
+    ///     assert!(err.max < prev);
+    ///     prev = err.max;
+    /// }
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn measure_error<I: Family>(
+        &self,
+        shape: &Tape<I>,
+        subdivisions: usize,
+    ) -> Result<MeshError, Error> {
+        let mut xs = vec![];
+        let mut ys = vec![];
+        let mut zs = vec![];
+        for v in &self.vertices {
+            xs.push(v.x);
+            ys.push(v.y);
+            zs.push(v.z);
+        }
+        // Interior points of a barycentric grid with `n` steps per edge
+        let n = subdivisions + 2;
+        for t in &self.triangles {
+            let [a, b, c] = [t.x, t.y, t.z].map(|i| self.vertices[i]);
+            for i in 1..n {
+                for j in 1..n - i {
+                    let k = n - i - j;
+                    let p =
+                        (a * i as f32 + b * j as f32 + c * k as f32) / n as f32;
+                    xs.push(p.x);
+                    ys.push(p.y);
+                    zs.push(p.z);
+                }
+            }
+        }
+
+        let values =
+            shape.new_float_slice_evaluator().eval(&xs, &ys, &zs, &[])?;
+        let mut out = MeshError {
+            samples: 0,
+            non_finite: 0,
+            max: 0.0,
+            mean: 0.0,
+            rms: 0.0,
+            worst: [f32::NAN; 3],
+        };
+        let mut sum = 0.0f64;
+        let mut sum_sq = 0.0f64;
+        for (i, v) in values.iter().enumerate() {
+            if !v.is_finite() {
+                out.non_finite += 1;
+                continue;
+            }
+            let e = v.abs();
+            if out.samples == 0 || e > out.max {
+                out.max = e;
+                out.worst = [xs[i], ys[i], zs[i]];
+            }
+            out.samples += 1;
+            sum += e as f64;
+            sum_sq += (e as f64).powi(2);
+        }
+        if out.samples > 0 {
+            out.mean = (sum / out.samples as f64) as f32;
+            out.rms = (sum_sq / out.samples as f64).sqrt() as f32;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_measure_error() {
+        let mut ctx = Context::new();
+        let z = ctx.z();
+        let tape = ctx.get_tape::<crate::vm::Eval>(z).unwrap();
+
+        // A single triangle, tilted so that its centroid is at z = 1/3
+        let mut mesh = Mesh::new();
+        mesh.vertices = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 1.0),
+        ];
+        mesh.triangles = vec![Vector3::new(0, 1, 2)];
+
+        let err = mesh.measure_error(&tape, 0).unwrap();
+        assert_eq!(err.samples, 3);
+        assert_eq!(err.max, 1.0);
+        assert_eq!(err.worst, [0.0, 1.0, 1.0]);
+        assert!((err.mean - 1.0 / 3.0).abs() < 1e-6);
+        assert!((err.rms - (1.0f32 / 3.0).sqrt()).abs() < 1e-6);
+
+        let err = mesh.measure_error(&tape, 1).unwrap();
+        assert_eq!(err.samples, 4);
+        assert_eq!(err.non_finite, 0);
+        assert!((err.mean - (1.0 + 1.0 / 3.0) / 4.0).abs() < 1e-6);
+
+        // Subdivision level 2 samples three points per triangle
+        assert_eq!(mesh.measure_error(&tape, 2).unwrap().samples, 6);
+    }
+}
@@ -0,0 +1,157 @@
+//! Inside / outside queries using generalized winding numbers
+use super::Mesh;
+use nalgebra::Vector3;
+
+impl Mesh {
+    /// Computes the generalized winding number of the mesh around a point
+    ///
+    /// This is the sum of the solid angles subtended by each triangle,
+    /// divided by 4π.  It's 1 inside a closed, outward-facing mesh and 0
+    /// outside; for meshes with holes or other defects, it varies smoothly
+    /// between the two.
+    pub fn winding_number(&self, p: Vector3<f32>) -> f32 {
+        let p = p.cast::<f64>();
+        let total: f64 = self
+            .triangles
+            .iter()
+            .map(|t| {
+                // Solid angle, from Van Oosterom and Strackee (1983)
+                let [a, b, c] =
+                    [t.x, t.y, t.z].map(|i| self.vertices[i].cast::<f64>() - p);
+                let (la, lb, lc) = (a.norm(), b.norm(), c.norm());
+                let num = a.dot(&b.cross(&c));
+                let den = la * lb * lc
+                    + a.dot(&b) * lc
+                    + b.dot(&c) * la
+                    + c.dot(&a) * lb;
+                2.0 * num.atan2(den)
+            })
+            .sum();
+        (total / (4.0 * std::f64::consts::PI)) as f32
+    }
+
+    /// Checks whether a point is inside the mesh
+    ///
+    /// A point is inside if its [winding number](Self::winding_number) is
+    /// greater than ½, which is robust to small holes and cracks in the mesh.
+    ///
+    /// This can be used to check a mesh against its source shape, e.g. by
+    /// comparing it with the sign of the shape's value at sample points:
+    ///
+    /// ```
+    /// use fidget::{mesh::{Octree, Settings}, rhai::eval, vm};
+    ///
+    /// let (sphere, ctx) = eval("sqrt(x*x + y*y + z*z) - 0.6")?;
+    /// let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+    /// let settings = Settings {
+    ///     min_depth: 4,
+    ///     max_depth: 4,
+    ///     threads: 0,
+    ///     clip: None,
+    ///     iso_band: None,
+    ///     exact_boundaries: false,
+    ///     edge_refinement: None,
+    /// };
+    /// let mesh = Octree::build(&tape, settings).walk_dual(settings);
+    ///
+    /// let eval = tape.new_point_evaluator();
+    /// for p in [[0.0, 0.0, 0.0], [0.5, 0.1, 0.0], [0.7, 0.0, 0.0]] {
+    ///     let (v, _) = eval.eval(p[0], p[1], p[2], &[])?;
+    ///     assert_eq!(mesh.contains(p.into()), v < 0.0);
+    /// }
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn contains(&self, p: Vector3<f32>) -> bool {
+        self.winding_number(p) > 0.5
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        context::Context,
+        mesh::{Octree, Settings},
+    };
+
+    #[test]
+    fn test_winding_number() {
+        let mut mesh = Mesh::new();
+        mesh.vertices = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        ];
+        mesh.triangles = vec![
+            Vector3::new(0, 2, 1),
+            Vector3::new(0, 1, 3),
+            Vector3::new(0, 3, 2),
+            Vector3::new(1, 2, 3),
+        ];
+        let inside = Vector3::new(0.1, 0.1, 0.1);
+        let outside = Vector3::new(1.0, 1.0, 1.0);
+        assert!((mesh.winding_number(inside) - 1.0).abs() < 1e-6);
+        assert!(mesh.winding_number(outside).abs() < 1e-6);
+        assert!(mesh.contains(inside));
+        assert!(!mesh.contains(outside));
+
+        // Removing a face leaves a partial winding number
+        mesh.triangles.pop();
+        let w = mesh.winding_number(inside);
+        assert!(w > 0.0 && w < 1.0, "bad winding number {w}");
+    }
+
+    #[test]
+    fn test_contains_matches_shape() {
+        let mut ctx = Context::new();
+        let (x, y, z) = (ctx.x(), ctx.y(), ctx.z());
+        let a = ctx.sub(x, 0.2).unwrap();
+        let (a2, y2, z2) = (
+            ctx.square(a).unwrap(),
+            ctx.square(y).unwrap(),
+            ctx.square(z).unwrap(),
+        );
+        let r = ctx.add(a2, y2).unwrap();
+        let r = ctx.add(r, z2).unwrap();
+        let r = ctx.sqrt(r).unwrap();
+        let sphere = ctx.sub(r, 0.5).unwrap();
+        let cube = ctx.abs(x).unwrap();
+        let cube = ctx.sub(cube, 0.4).unwrap();
+        let shape = ctx.max(sphere, cube).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(shape).unwrap();
+
+        let settings = Settings {
+            min_depth: 5,
+            max_depth: 5,
+            threads: 0,
+            clip: None,
+            iso_band: None,
+            exact_boundaries: false,
+            edge_refinement: None,
+        };
+        let mesh = Octree::build(&tape, settings).walk_dual(settings);
+
+        // Sample a grid, skipping points which are close to the surface
+        let eval = tape.new_point_evaluator();
+        let mut checked = 0;
+        for i in 0..16 {
+            for j in 0..16 {
+                for k in 0..16 {
+                    let p =
+                        Vector3::new(i, j, k).map(|v| v as f32 / 8.0 - 0.97);
+                    let (v, _) = eval.eval(p.x, p.y, p.z, &[]).unwrap();
+                    if v.abs() > 0.05 {
+                        assert_eq!(
+                            mesh.contains(p),
+                            v < 0.0,
+                            "mismatch at {p}"
+                        );
+                        checked += 1;
+                    }
+                }
+            }
+        }
+        assert!(checked > 1000);
+    }
+}
@@ -0,0 +1,310 @@
+//! Conversion from 2D outlines into distance field expressions
+//!
+//! An outline is a set of closed contours, each made of line segments and
+//! Bézier curves.  Curves are flattened into line segments, then the outline
+//! is converted into an expression of the form `d(x, y) * s(x, y)`, where
+//! - `d` is the exact distance to the nearest segment
+//! - `s` is `-1` inside the outline and `+1` outside, based on a [`FillRule`]
+//!
+//! The sign is found by counting crossings of a ray cast in the +X direction.
+//! Each crossing test is a product of steep clamped ramps, which act as step
+//! functions except within a tiny distance of their edges.
+use crate::{
+    context::{Context, Node},
+    Error,
+};
+
+/// Number of line segments used to approximate a quadratic Bézier curve
+///
+/// With `n` chords at evenly spaced parameter values, the flattened curve is
+/// within `|B''| / (8n²)` of the true curve, where `B'' = 2(P₀ - 2P₁ + P₂)`.
+#[cfg(any(feature = "text", feature = "svg"))]
+const QUAD_STEPS: usize = 8;
+
+/// Number of line segments used to approximate a cubic Bézier curve
+///
+/// The same bound applies as for [`QUAD_STEPS`], where `|B''|` is at most
+/// `6 max(|P₀ - 2P₁ + P₂|, |P₁ - 2P₂ + P₃|)`.
+#[cfg(any(feature = "text", feature = "svg"))]
+const CUBIC_STEPS: usize = 12;
+
+/// Width of the crossing test ramps, relative to the outline's size
+const RAMP_WIDTH: f64 = 1e-5;
+
+/// Rule used to decide which regions are inside an outline
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(not(any(feature = "svg", feature = "csg")), allow(dead_code))]
+pub enum FillRule {
+    /// A point is inside if the outline winds around it a non-zero number of
+    /// times (used by TrueType fonts and as the SVG default)
+    #[default]
+    NonZero,
+    /// A point is inside if a ray from it crosses the outline an odd number of
+    /// times
+    EvenOdd,
+}
+
+/// Builder for a set of closed contours
+///
+/// Points are transformed by `p * scale + offset` as they are added.
+#[derive(Debug)]
+pub(crate) struct Outline {
+    contours: Vec<Vec<[f64; 2]>>,
+    current: Vec<[f64; 2]>,
+    /// Marks points which are endpoints of path commands (rather than points
+    /// added when flattening curves), parallel to `contours`
+    knots: Vec<Vec<bool>>,
+    current_knots: Vec<bool>,
+    scale: [f64; 2],
+    offset: [f64; 2],
+}
+
+impl Outline {
+    /// Builds a new outline with the given transform
+    pub fn new(scale: [f64; 2], offset: [f64; 2]) -> Self {
+        Self {
+            contours: vec![],
+            current: vec![],
+            knots: vec![],
+            current_knots: vec![],
+            scale,
+            offset,
+        }
+    }
+
+    /// Checks whether the outline contains any closed contours
+    #[cfg(feature = "text")]
+    pub fn is_empty(&self) -> bool {
+        self.contours.is_empty() && self.current.len() < 3
+    }
+
+    /// Returns the most recent point, in untransformed coordinates
+    #[cfg(any(feature = "text", feature = "svg"))]
+    pub fn last(&self) -> Option<[f64; 2]> {
+        self.current.last().map(|p| {
+            [
+                (p[0] - self.offset[0]) / self.scale[0],
+                (p[1] - self.offset[1]) / self.scale[1],
+            ]
+        })
+    }
+
+    fn push(&mut self, x: f64, y: f64, knot: bool) {
+        let p = [
+            x * self.scale[0] + self.offset[0],
+            y * self.scale[1] + self.offset[1],
+        ];
+        if self.current.last() != Some(&p) {
+            self.current.push(p);
+            self.current_knots.push(knot);
+        } else if let Some(k) = self.current_knots.last_mut() {
+            *k |= knot;
+        }
+    }
+
+    /// Starts a new contour, closing the current one (if present)
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.close();
+        self.push(x, y, true);
+    }
+
+    /// Adds a line segment to the current contour
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.push(x, y, true);
+    }
+
+    /// Adds a quadratic Bézier curve to the current contour
+    #[cfg(any(feature = "text", feature = "svg"))]
+    pub fn quad_to(&mut self, x1: f64, y1: f64, x: f64, y: f64) {
+        let Some([x0, y0]) = self.last() else {
+            return self.move_to(x, y);
+        };
+        for i in 1..=QUAD_STEPS {
+            let t = i as f64 / QUAD_STEPS as f64;
+            let s = 1.0 - t;
+            let (a, b, c) = (s * s, 2.0 * s * t, t * t);
+            self.push(
+                a * x0 + b * x1 + c * x,
+                a * y0 + b * y1 + c * y,
+                i == QUAD_STEPS,
+            );
+        }
+    }
+
+    /// Adds a cubic Bézier curve to the current contour
+    #[cfg(any(feature = "text", feature = "svg"))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn cubic_to(
+        &mut self,
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        x: f64,
+        y: f64,
+    ) {
+        let Some([x0, y0]) = self.last() else {
+            return self.move_to(x, y);
+        };
+        for i in 1..=CUBIC_STEPS {
+            let t = i as f64 / CUBIC_STEPS as f64;
+            let s = 1.0 - t;
+            let (a, b, c, d) =
+                (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+            self.push(
+                a * x0 + b * x1 + c * x2 + d * x,
+                a * y0 + b * y1 + c * y2 + d * y,
+                i == CUBIC_STEPS,
+            );
+        }
+    }
+
+    /// Closes the current contour
+    ///
+    /// Degenerate contours (with fewer than three points) are discarded.
+    pub fn close(&mut self) {
+        let mut c = std::mem::take(&mut self.current);
+        let mut k = std::mem::take(&mut self.current_knots);
+        if c.len() > 1 && c.first() == c.last() {
+            c.pop();
+            let last = k.pop().unwrap();
+            k[0] |= last;
+        }
+        if c.len() >= 3 {
+            self.contours.push(c);
+            self.knots.push(k);
+        }
+    }
+
+    /// Closes the outline, returning its contours
+    ///
+    /// Each contour is paired with flags which mark endpoints of path commands
+    /// (rather than points added when flattening curves).
+    #[cfg(feature = "text")]
+    pub fn into_contours(mut self) -> Vec<(Vec<[f64; 2]>, Vec<bool>)> {
+        self.close();
+        self.contours.into_iter().zip(self.knots).collect()
+    }
+
+    /// Converts the outline into a distance field expression in `ctx`
+    ///
+    /// The result is positive outside the outline and negative inside it.  If
+    /// the outline is empty, returns a constant (positive) infinity.
+    pub fn build(
+        mut self,
+        ctx: &mut Context,
+        rule: FillRule,
+    ) -> Result<Node, Error> {
+        self.close();
+
+        let mut lo = [f64::INFINITY; 2];
+        let mut hi = [f64::NEG_INFINITY; 2];
+        for p in self.contours.iter().flatten() {
+            for i in 0..2 {
+                lo[i] = lo[i].min(p[i]);
+                hi[i] = hi[i].max(p[i]);
+            }
+        }
+        if self.contours.is_empty() {
+            return Ok(ctx.constant(f64::INFINITY));
+        }
+        let size = (hi[0] - lo[0]).max(hi[1] - lo[1]).max(f64::EPSILON);
+        let k = 1.0 / (RAMP_WIDTH * size);
+
+        let x = ctx.x();
+        let y = ctx.y();
+
+        let mut dist = None;
+        let mut sign: Option<Node> = None;
+        for c in &self.contours {
+            for (i, &a) in c.iter().enumerate() {
+                let b = c[(i + 1) % c.len()];
+                let ba = [b[0] - a[0], b[1] - a[1]];
+                let l2 = ba[0] * ba[0] + ba[1] * ba[1];
+                if l2 == 0.0 {
+                    continue;
+                }
+
+                // Distance to the line segment, squared
+                let pax = ctx.sub(x, a[0])?;
+                let pay = ctx.sub(y, a[1])?;
+                let hx = ctx.mul(pax, ba[0] / l2)?;
+                let hy = ctx.mul(pay, ba[1] / l2)?;
+                let h = ctx.add(hx, hy)?;
+                let h = clamp(ctx, h, 0.0, 1.0)?;
+                let dx = ctx.mul(h, ba[0])?;
+                let dx = ctx.sub(pax, dx)?;
+                let dy = ctx.mul(h, ba[1])?;
+                let dy = ctx.sub(pay, dy)?;
+                let dx2 = ctx.square(dx)?;
+                let dy2 = ctx.square(dy)?;
+                let d2 = ctx.add(dx2, dy2)?;
+                dist = Some(match dist {
+                    Some(prev) => ctx.min(prev, d2)?,
+                    None => d2,
+                });
+
+                // Horizontal segments never cross the +X ray
+                if ba[1] == 0.0 {
+                    continue;
+                }
+                // The band is half-open (including its lower edge), so a ray
+                // passing exactly through a vertex is counted once; the ramp
+                // is shifted down so that it's complementary to the upper
+                // ramp of an adjacent segment ending at the same height.
+                let (ylo, yhi) = (a[1].min(b[1]), a[1].max(b[1]));
+                let below = ctx.sub(y, ylo)?;
+                let below = ctx.mul(below, k)?;
+                let below = ctx.add(below, 1.0)?;
+                let below = clamp(ctx, below, 0.0, 1.0)?;
+                let above = ctx.sub(yhi, y)?;
+                let above = ctx.mul(above, k)?;
+                let above = clamp(ctx, above, 0.0, 1.0)?;
+                let in_band = ctx.mul(below, above)?;
+
+                // Horizontal distance from the point to the segment
+                let g = ctx.mul(pay, ba[0] / ba[1])?;
+                let g = ctx.sub(g, pax)?;
+                let g = ctx.mul(g, k)?;
+                let left = clamp(ctx, g, 0.0, 1.0)?;
+
+                let crossing = ctx.mul(in_band, left)?;
+                let term = match rule {
+                    // Each crossing flips the sign
+                    FillRule::EvenOdd => {
+                        let c = ctx.mul(crossing, -2.0)?;
+                        ctx.add(c, 1.0)?
+                    }
+                    // Crossings are added with their direction
+                    FillRule::NonZero if ba[1] > 0.0 => crossing,
+                    FillRule::NonZero => ctx.neg(crossing)?,
+                };
+                sign = Some(match (sign, rule) {
+                    (Some(prev), FillRule::EvenOdd) => ctx.mul(prev, term)?,
+                    (Some(prev), FillRule::NonZero) => ctx.add(prev, term)?,
+                    (None, _) => term,
+                });
+            }
+        }
+
+        let dist = ctx.sqrt(dist.unwrap())?;
+        let sign = match (sign, rule) {
+            (Some(s), FillRule::EvenOdd) => s,
+            (Some(w), FillRule::NonZero) => {
+                // Convert from winding number to sign
+                let w = ctx.abs(w)?;
+                let w = ctx.min(w, 1.0)?;
+                let w = ctx.mul(w, -2.0)?;
+                ctx.add(w, 1.0)?
+            }
+            (None, _) => return Ok(dist),
+        };
+        ctx.mul(dist, sign)
+    }
+}
+
+/// Clamps `v` to the range `[lo, hi]`
+fn clamp(ctx: &mut Context, v: Node, lo: f64, hi: f64) -> Result<Node, Error> {
+    let v = ctx.min(v, hi)?;
+    ctx.max(v, lo)
+}
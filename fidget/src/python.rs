@@ -0,0 +1,351 @@
+//! Python bindings, using [PyO3](https://pyo3.rs)
+//!
+//! The [`fidget`] function builds a Python module exposing [`Context`],
+//! shape builders, rendering, and meshing; images and meshes are returned as
+//! `numpy` arrays.  To build an importable extension, add a `cdylib` crate
+//! which depends on `fidget` (with the `python` feature) and re-exports the
+//! module:
+//!
+//! ```ignore
+//! #[pyo3::pymodule]
+//! fn fidget(m: &pyo3::Bound<'_, pyo3::types::PyModule>) -> pyo3::PyResult<()> {
+//!     fidget::python::fidget(m)
+//! }
+//! ```
+//!
+//! From Python, usage looks like this:
+//!
+//! ```python
+//! import fidget
+//! ctx = fidget.Context()
+//! x, y = ctx.x(), ctx.y()
+//! r = ctx.sqrt(ctx.add(ctx.square(x), ctx.square(y)))
+//! circle = ctx.sub(r, 0.5)
+//! image = fidget.render(ctx, circle, 256)        # (256, 256) bool array
+//! verts, tris = fidget.mesh(ctx, fidget.extrude(ctx, circle, 0.5), 6)
+//! ```
+use crate::{context, eval::Family as _, shapes, Error};
+use numpy::{PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray1};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+#[cfg(feature = "jit")]
+type Family = crate::jit::Eval;
+#[cfg(not(feature = "jit"))]
+type Family = crate::vm::Eval;
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        PyValueError::new_err(e.to_string())
+    }
+}
+
+/// Handle to a node in a [`Context`]
+#[pyclass(frozen)]
+#[derive(Copy, Clone)]
+pub struct Node(context::Node);
+
+/// Argument which may be either a node or a constant
+#[derive(FromPyObject)]
+enum Arg {
+    Node(Node),
+    Const(f64),
+}
+
+impl Arg {
+    fn node(self, ctx: &mut context::Context) -> context::Node {
+        match self {
+            Arg::Node(n) => n.0,
+            Arg::Const(v) => ctx.constant(v),
+        }
+    }
+}
+
+/// Python wrapper for a [`Context`](context::Context)
+#[pyclass]
+#[derive(Default)]
+pub struct Context(context::Context);
+
+#[pymethods]
+impl Context {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the X coordinate
+    fn x(&mut self) -> Node {
+        Node(self.0.x())
+    }
+
+    /// Returns the Y coordinate
+    fn y(&mut self) -> Node {
+        Node(self.0.y())
+    }
+
+    /// Returns the Z coordinate
+    fn z(&mut self) -> Node {
+        Node(self.0.z())
+    }
+
+    /// Returns a named variable
+    fn var(&mut self, name: &str) -> PyResult<Node> {
+        Ok(Node(self.0.var(name)?))
+    }
+
+    /// Returns a constant
+    fn constant(&mut self, v: f64) -> Node {
+        Node(self.0.constant(v))
+    }
+
+    /// Builds a `neg` node
+    fn neg(&mut self, a: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        Ok(Node(self.0.neg(a)?))
+    }
+
+    /// Builds a `abs` node
+    fn abs(&mut self, a: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        Ok(Node(self.0.abs(a)?))
+    }
+
+    /// Builds a `recip` node
+    fn recip(&mut self, a: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        Ok(Node(self.0.recip(a)?))
+    }
+
+    /// Builds a `sqrt` node
+    fn sqrt(&mut self, a: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        Ok(Node(self.0.sqrt(a)?))
+    }
+
+    /// Builds a `square` node
+    fn square(&mut self, a: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        Ok(Node(self.0.square(a)?))
+    }
+
+    /// Builds a `add` node
+    fn add(&mut self, a: Arg, b: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        let b = b.node(&mut self.0);
+        Ok(Node(self.0.add(a, b)?))
+    }
+
+    /// Builds a `sub` node
+    fn sub(&mut self, a: Arg, b: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        let b = b.node(&mut self.0);
+        Ok(Node(self.0.sub(a, b)?))
+    }
+
+    /// Builds a `mul` node
+    fn mul(&mut self, a: Arg, b: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        let b = b.node(&mut self.0);
+        Ok(Node(self.0.mul(a, b)?))
+    }
+
+    /// Builds a `div` node
+    fn div(&mut self, a: Arg, b: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        let b = b.node(&mut self.0);
+        Ok(Node(self.0.div(a, b)?))
+    }
+
+    /// Builds a `min` node
+    fn min(&mut self, a: Arg, b: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        let b = b.node(&mut self.0);
+        Ok(Node(self.0.min(a, b)?))
+    }
+
+    /// Builds a `max` node
+    fn max(&mut self, a: Arg, b: Arg) -> PyResult<Node> {
+        let a = a.node(&mut self.0);
+        let b = b.node(&mut self.0);
+        Ok(Node(self.0.max(a, b)?))
+    }
+
+    /// Evaluates a node at a single point
+    fn eval_xyz(&self, n: Node, x: f64, y: f64, z: f64) -> PyResult<f64> {
+        Ok(self.0.eval_xyz(n.0, x, y, z)?)
+    }
+
+    /// Evaluates a node at many points, given as 1D `float32` arrays
+    fn eval_array<'py>(
+        &self,
+        py: Python<'py>,
+        n: Node,
+        x: PyReadonlyArray1<'py, f32>,
+        y: PyReadonlyArray1<'py, f32>,
+        z: PyReadonlyArray1<'py, f32>,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let tape = self.0.get_tape::<Family>(n.0)?;
+        let eval = tape.new_float_slice_evaluator();
+        let out =
+            eval.eval(x.as_slice()?, y.as_slice()?, z.as_slice()?, &[])?;
+        Ok(PyArray1::from_vec(py, out))
+    }
+}
+
+/// Extrudes a 2D shape along the Z axis; see [`shapes::extrude`]
+#[pyfunction]
+fn extrude(ctx: &mut Context, shape: Node, height: f64) -> PyResult<Node> {
+    Ok(Node(shapes::extrude(&mut ctx.0, shape.0, height)?))
+}
+
+/// Revolves a 2D shape around the `"x"` or `"y"` axis; see
+/// [`shapes::revolve`]
+#[pyfunction]
+fn revolve(ctx: &mut Context, shape: Node, axis: &str) -> PyResult<Node> {
+    let axis = match axis {
+        "x" | "X" => shapes::Axis::X,
+        "y" | "Y" => shapes::Axis::Y,
+        _ => return Err(PyValueError::new_err("axis must be 'x' or 'y'")),
+    };
+    Ok(Node(shapes::revolve(&mut ctx.0, shape.0, axis)?))
+}
+
+/// Renders a 2D image at Z = 0, spanning ±1 on the X and Y axes
+///
+/// With `mode = "bit"` (the default), this returns an `(N, N)` array of
+/// `bool`; with `mode = "sdf"`, it returns an `(N, N, 3)` array of RGB
+/// `uint8` values.
+#[pyfunction]
+#[pyo3(signature = (ctx, shape, image_size, mode = "bit"))]
+fn render<'py>(
+    py: Python<'py>,
+    ctx: &Context,
+    shape: Node,
+    image_size: usize,
+    mode: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let tape = ctx.0.get_tape::<Family>(shape.0)?;
+    let config = crate::render::RenderConfig {
+        image_size,
+        tile_sizes: Family::tile_sizes_2d().to_vec(),
+        ..Default::default()
+    };
+    let n = image_size;
+    let out = match mode {
+        "bit" => {
+            let image = crate::render::render2d(
+                tape,
+                &config,
+                &crate::render::BitRenderMode,
+            );
+            PyArray1::from_vec(py, image).reshape([n, n])?.into_any()
+        }
+        "sdf" => {
+            let image = crate::render::render2d(
+                tape,
+                &config,
+                &crate::render::SdfRenderMode,
+            );
+            let image = image.into_iter().flatten().collect();
+            PyArray1::from_vec(py, image).reshape([n, n, 3])?.into_any()
+        }
+        _ => return Err(PyValueError::new_err("mode must be 'bit' or 'sdf'")),
+    };
+    Ok(out)
+}
+
+/// Meshes a shape within the ±1 cube at the given octree depth
+///
+/// Returns a tuple of `(vertices, triangles)`, which are `(N, 3)` arrays of
+/// `float32` positions and `uint32` vertex indices respectively.
+#[pyfunction]
+#[pyo3(signature = (ctx, shape, depth, threads = 0))]
+#[allow(clippy::type_complexity)]
+fn mesh<'py>(
+    py: Python<'py>,
+    ctx: &Context,
+    shape: Node,
+    depth: u8,
+    threads: u8,
+) -> PyResult<(Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<u32>>)> {
+    let tape = ctx.0.get_tape::<Family>(shape.0)?;
+    let settings = crate::mesh::Settings {
+        threads,
+        min_depth: depth,
+        max_depth: depth,
+        clip: None,
+        iso_band: None,
+        exact_boundaries: false,
+        edge_refinement: None,
+    };
+    let mesh = crate::mesh::Octree::build(&tape, settings).walk_dual(settings);
+
+    let verts: Vec<f32> =
+        mesh.vertices.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+    let tris: Vec<u32> = mesh
+        .triangles
+        .iter()
+        .flat_map(|t| [t.x as u32, t.y as u32, t.z as u32])
+        .collect();
+    let verts = PyArray1::from_vec(py, verts);
+    let tris = PyArray1::from_vec(py, tris);
+    Ok((
+        verts.reshape([mesh.vertices.len(), 3])?,
+        tris.reshape([mesh.triangles.len(), 3])?,
+    ))
+}
+
+/// Populates a Python module with Fidget's classes and functions
+pub fn fidget(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Context>()?;
+    m.add_class::<Node>()?;
+    m.add_function(wrap_pyfunction!(extrude, m)?)?;
+    m.add_function(wrap_pyfunction!(revolve, m)?)?;
+    m.add_function(wrap_pyfunction!(render, m)?)?;
+    m.add_function(wrap_pyfunction!(mesh, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_python_context() {
+        Python::initialize();
+        Python::attach(|py| {
+            let m = PyModule::new(py, "fidget").unwrap();
+            fidget(&m).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("fidget", m).unwrap();
+            py.run(
+                c"
+ctx = fidget.Context()
+x, y = ctx.x(), ctx.y()
+r = ctx.sqrt(ctx.add(ctx.square(x), ctx.square(y)))
+circle = ctx.sub(r, 0.5)
+assert ctx.eval_xyz(circle, 1.0, 0.0, 0.0) == 0.5
+assert ctx.eval_xyz(ctx.max(circle, 2), 0.0, 0.0, 0.0) == 2.0
+
+cylinder = fidget.extrude(ctx, circle, 1.0)
+assert ctx.eval_xyz(cylinder, 0.0, 0.0, 0.5) == -0.5
+
+try:
+    ctx.var('X')
+    assert False
+except ValueError as e:
+    assert 'reserved' in str(e)
+
+try:
+    fidget.revolve(ctx, circle, 'q')
+    assert False
+except ValueError:
+    pass
+",
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+        });
+    }
+}
@@ -0,0 +1,290 @@
+//! Persistent cache of interval results for 2D rendering
+//!
+//! Re-rendering a model which hasn't changed repeats the same interval
+//! evaluations on the same tiles.  A [`RenderCache`] records the result of each
+//! tile's interval evaluation, keyed by the tile's region and the
+//! [content hash](crate::eval::tape::Data::content_hash) of the tape which was
+//! evaluated, along with the choices made during evaluation and the content
+//! hash of the resulting simplified tape.
+//!
+//! When a tile is found in the cache, its interval evaluation is skipped; if
+//! the tile must be subdivided, the recorded choices are used to rebuild its
+//! simplified tape, and the recorded hash is used to look up its children.
+//! Because content hashes are stable across runs, the cache can be saved to
+//! disk (with [`RenderCache::open`] and [`RenderCache::save`]), so that
+//! re-rendering an unchanged model after a restart skips most of the work.
+//!
+//! ```
+//! use fidget::{
+//!     context::Context,
+//!     render::{
+//!         cache::RenderCache, render2d_cached, BitRenderMode, RenderConfig,
+//!     },
+//!     vm,
+//! };
+//!
+//! let mut ctx = Context::new();
+//! let (x, y) = (ctx.x(), ctx.y());
+//! let r = ctx.hypot(x, y)?;
+//! let circle = ctx.sub(r, 0.5)?;
+//! let tape = ctx.get_tape::<vm::Eval>(circle)?;
+//!
+//! let cfg = RenderConfig::<2> {
+//!     image_size: 64,
+//!     tile_sizes: vec![32, 8],
+//!     ..Default::default()
+//! };
+//! let mut cache = RenderCache::new();
+//! let a = render2d_cached(tape.clone(), &cfg, &BitRenderMode, &mut cache);
+//! assert_eq!(cache.stats().hits, 0);
+//!
+//! // The second render finds every tile in the cache
+//! let b = render2d_cached(tape, &cfg, &BitRenderMode, &mut cache);
+//! assert_eq!(a, b);
+//! assert_eq!(cache.stats().hits, cache.stats().misses);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    eval::{cache::CacheStats, tape::TapeHash, types::Interval, Choice},
+    Error,
+};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Header at the start of serialized cache data, including a format version
+const MAGIC: &[u8; 8] = b"FIDGRC01";
+
+/// Key for a cached tile: the evaluated tape's hash and the region's bounds
+pub(crate) type Key = (TapeHash, [u32; 6]);
+
+/// Builds a cache key from a tape hash and an interval region
+pub(crate) fn key(
+    hash: TapeHash,
+    x: Interval,
+    y: Interval,
+    z: Interval,
+) -> Key {
+    let region = [
+        x.lower(),
+        x.upper(),
+        y.lower(),
+        y.upper(),
+        z.lower(),
+        z.upper(),
+    ];
+    (hash, region.map(f32::to_bits))
+}
+
+/// Result of evaluating a single tile
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    /// Interval result
+    pub interval: Interval,
+    /// Choices made during evaluation, or `None` if the tape can't be
+    /// simplified in this region
+    pub choices: Option<Box<[Choice]>>,
+    /// Content hash of the simplified tape, if it was built
+    pub tape: Option<TapeHash>,
+}
+
+/// Cache entries and statistics from a single tile, to be merged into a
+/// [`RenderCache`] once rendering is done
+#[derive(Default)]
+pub(crate) struct CacheUpdate {
+    pub entries: Vec<(Key, Entry)>,
+    pub hits: usize,
+}
+
+/// Cache of interval results for rendering, with optional disk backing
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Default)]
+pub struct RenderCache {
+    entries: HashMap<Key, Entry>,
+    stats: CacheStats,
+    path: Option<PathBuf>,
+}
+
+impl RenderCache {
+    /// Builds a new in-memory cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a cache which is backed by the given file
+    ///
+    /// If the file exists, then the cache is loaded from it; otherwise, the
+    /// cache starts out empty.  The file is only written by
+    /// [`save`](Self::save).
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_owned();
+        let mut out = match std::fs::File::open(&path) {
+            Ok(f) => Self::read_from(&mut std::io::BufReader::new(f))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::new(),
+            Err(e) => return Err(e.into()),
+        };
+        out.path = Some(path);
+        Ok(out)
+    }
+
+    /// Writes the cache to its backing file
+    ///
+    /// This does nothing if the cache wasn't built with
+    /// [`open`](Self::open).
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            let f = std::fs::File::create(path)?;
+            let mut w = std::io::BufWriter::new(f);
+            self.write_to(&mut w)?;
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the cache's entries in a compact binary format
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for ((hash, region), e) in &self.entries {
+            out.write_all(&hash.0.to_le_bytes())?;
+            for r in region {
+                out.write_all(&r.to_le_bytes())?;
+            }
+            out.write_all(&e.interval.lower().to_le_bytes())?;
+            out.write_all(&e.interval.upper().to_le_bytes())?;
+            let flags =
+                e.choices.is_some() as u8 | ((e.tape.is_some() as u8) << 1);
+            out.write_all(&[flags])?;
+            if let Some(c) = &e.choices {
+                out.write_all(&(c.len() as u32).to_le_bytes())?;
+                let bytes: Vec<u8> = c.iter().map(|c| *c as u8).collect();
+                out.write_all(&bytes)?;
+            }
+            if let Some(t) = e.tape {
+                out.write_all(&t.0.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a cache which was written by [`write_to`](Self::write_to)
+    ///
+    /// The resulting cache is not backed by a file.  Returns
+    /// [`Error::BadRenderCache`] if the data is invalid.
+    pub fn read_from<R: Read>(input: &mut R) -> Result<Self, Error> {
+        fn read<const N: usize, R: Read>(r: &mut R) -> Result<[u8; N], Error> {
+            let mut buf = [0u8; N];
+            r.read_exact(&mut buf).map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => Error::BadRenderCache,
+                _ => e.into(),
+            })?;
+            Ok(buf)
+        }
+        fn read_u32<R: Read>(r: &mut R) -> Result<u32, Error> {
+            read::<4, R>(r).map(u32::from_le_bytes)
+        }
+        fn read_hash<R: Read>(r: &mut R) -> Result<TapeHash, Error> {
+            read::<16, R>(r).map(|b| TapeHash(u128::from_le_bytes(b)))
+        }
+
+        if &read::<8, R>(input)? != MAGIC {
+            return Err(Error::BadRenderCache);
+        }
+        let count = u64::from_le_bytes(read::<8, R>(input)?);
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let hash = read_hash(input)?;
+            let mut region = [0; 6];
+            for r in &mut region {
+                *r = read_u32(input)?;
+            }
+            let lower = f32::from_bits(read_u32(input)?);
+            let upper = f32::from_bits(read_u32(input)?);
+            if !(upper >= lower || (lower.is_nan() && upper.is_nan())) {
+                return Err(Error::BadRenderCache);
+            }
+            let [flags] = read::<1, R>(input)?;
+            if flags & !0b11 != 0 {
+                return Err(Error::BadRenderCache);
+            }
+            let choices = if flags & 1 != 0 {
+                let n = read_u32(input)? as usize;
+                let mut c = Vec::with_capacity(n.min(1 << 16));
+                for _ in 0..n {
+                    c.push(match read::<1, R>(input)? {
+                        [0] => Choice::Unknown,
+                        [1] => Choice::Left,
+                        [2] => Choice::Right,
+                        [3] => Choice::Both,
+                        _ => return Err(Error::BadRenderCache),
+                    });
+                }
+                Some(c.into())
+            } else {
+                None
+            };
+            let tape = if flags & 2 != 0 {
+                Some(read_hash(input)?)
+            } else {
+                None
+            };
+            let interval = Interval::new(lower, upper);
+            entries.insert(
+                (hash, region),
+                Entry {
+                    interval,
+                    choices,
+                    tape,
+                },
+            );
+        }
+        Ok(Self {
+            entries,
+            stats: CacheStats::default(),
+            path: None,
+        })
+    }
+
+    /// Looks up a tile in the cache
+    pub(crate) fn get(&self, key: &Key) -> Option<&Entry> {
+        self.entries.get(key)
+    }
+
+    /// Merges entries and statistics from a render into the cache
+    pub(crate) fn apply(&mut self, update: CacheUpdate) {
+        self.stats.hits += update.hits;
+        self.stats.misses += update.entries.len();
+        self.entries.extend(update.entries);
+    }
+
+    /// Returns hit / miss statistics for this cache
+    ///
+    /// Each tile which is interval-evaluated counts as a miss.  The cache is
+    /// never trimmed, so there are no evictions.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets hit / miss statistics, without clearing the cache
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Returns the number of tiles in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every tile from the cache
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,252 @@
+//! Camera model for 3D rendering
+use nalgebra::{
+    Isometry3, Matrix4, Orthographic3, Perspective3, Point3, Transform3,
+    Vector3,
+};
+
+/// Projection used by a [`Camera`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Parallel projection, showing `±half_height` around the view axis
+    Orthographic {
+        /// Half of the visible height (and width), in model units
+        half_height: f32,
+    },
+    /// Perspective projection with the given vertical field of view
+    Perspective {
+        /// Vertical field of view, in radians
+        fov_y: f32,
+    },
+}
+
+/// Camera for 3D rendering
+///
+/// A camera is positioned with [`look_at`](Camera::look_at), then converted
+/// into a transform (with [`transform`](Camera::transform)) which maps the
+/// renderer's `±1` view cube into model space; this can be used as the `mat`
+/// in a [`RenderConfig`](crate::render::RenderConfig) or as the transform in a
+/// [`FrameRequest`](crate::viewer::FrameRequest).
+///
+/// Only geometry between the `near` and `far` clipping planes is rendered.
+///
+/// ```
+/// use fidget::render::Camera;
+/// use nalgebra::{Point3, Vector3};
+///
+/// let camera = Camera::look_at(
+///     Point3::new(0.0, 0.0, 3.0),
+///     Point3::origin(),
+///     Vector3::y(),
+/// )
+/// .perspective(60f32.to_radians())
+/// .clip(1.0, 5.0);
+///
+/// // The center of the near plane is in front of the camera
+/// let p = camera.transform().transform_point(&Point3::new(0.0, 0.0, 1.0));
+/// assert!((p - Point3::new(0.0, 0.0, 2.0)).norm() < 1e-6);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// Position of the camera
+    pub eye: Point3<f32>,
+    /// Point at the center of the image
+    pub target: Point3<f32>,
+    /// Direction which is up in the image
+    pub up: Vector3<f32>,
+    /// Projection type
+    pub projection: Projection,
+    /// Distance from the camera to the near clipping plane
+    pub near: f32,
+    /// Distance from the camera to the far clipping plane
+    pub far: f32,
+}
+
+impl Default for Camera {
+    /// Returns an orthographic camera whose transform is the identity
+    fn default() -> Self {
+        Self {
+            eye: Point3::new(0.0, 0.0, 2.0),
+            target: Point3::origin(),
+            up: Vector3::y(),
+            projection: Projection::Orthographic { half_height: 1.0 },
+            near: 1.0,
+            far: 3.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Builds a camera at `eye`, looking towards `target`
+    ///
+    /// The camera uses an orthographic projection which shows `±1` around the
+    /// target, with clipping planes on either side of the target; these can be
+    /// changed with [`perspective`](Self::perspective),
+    /// [`orthographic`](Self::orthographic), and [`clip`](Self::clip).
+    pub fn look_at(
+        eye: Point3<f32>,
+        target: Point3<f32>,
+        up: Vector3<f32>,
+    ) -> Self {
+        let d = (target - eye).norm();
+        Self {
+            eye,
+            target,
+            up,
+            near: (d - 1.0).max(d / 100.0),
+            far: d + 1.0,
+            ..Self::default()
+        }
+    }
+
+    /// Switches to a perspective projection with the given vertical field of
+    /// view (in radians)
+    pub fn perspective(self, fov_y: f32) -> Self {
+        Self {
+            projection: Projection::Perspective { fov_y },
+            ..self
+        }
+    }
+
+    /// Switches to an orthographic projection showing `±half_height`
+    pub fn orthographic(self, half_height: f32) -> Self {
+        Self {
+            projection: Projection::Orthographic { half_height },
+            ..self
+        }
+    }
+
+    /// Sets the near and far clipping planes
+    pub fn clip(self, near: f32, far: f32) -> Self {
+        Self { near, far, ..self }
+    }
+
+    /// Returns a transform from the `±1` view cube into model space
+    ///
+    /// In the view cube, `Z = +1` is the near plane and `Z = -1` is the far
+    /// plane, matching the renderer (which treats larger `Z` values as closer
+    /// to the viewer).
+    pub fn transform(&self) -> Transform3<f32> {
+        let proj = match self.projection {
+            Projection::Orthographic { half_height: h } => {
+                Orthographic3::new(-h, h, -h, h, self.near, self.far).inverse()
+            }
+            Projection::Perspective { fov_y } => {
+                Perspective3::new(1.0, fov_y, self.near, self.far).inverse()
+            }
+        };
+        let view = Isometry3::look_at_rh(&self.eye, &self.target, &self.up)
+            .inverse()
+            .to_homogeneous();
+
+        // Normalized device coordinates put the near plane at Z = -1
+        let flip =
+            Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 1.0, -1.0));
+        Transform3::from_matrix_unchecked(view * proj * flip)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, render::RenderConfig, vm};
+
+    fn assert_near(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).norm() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_default_camera() {
+        let t = Camera::default().transform();
+        for p in [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, -1.0, 0.5),
+            Point3::new(-0.25, 0.75, -1.0),
+        ] {
+            assert_near(t.transform_point(&p), p);
+        }
+    }
+
+    #[test]
+    fn test_camera_projections() {
+        let camera = Camera::look_at(
+            Point3::new(0.0, 0.0, 3.0),
+            Point3::origin(),
+            Vector3::y(),
+        )
+        .clip(1.0, 5.0);
+
+        let t = camera.orthographic(2.0).transform();
+        let f = |x, y, z| t.transform_point(&Point3::new(x, y, z));
+        assert_near(f(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, 2.0));
+        assert_near(f(0.0, 0.0, -1.0), Point3::new(0.0, 0.0, -2.0));
+        assert_near(f(1.0, -1.0, 1.0), Point3::new(2.0, -2.0, 2.0));
+        assert_near(f(1.0, -1.0, -1.0), Point3::new(2.0, -2.0, -2.0));
+
+        // With a 90° field of view, the view's half-width is its depth
+        let t = camera.perspective(90f32.to_radians()).transform();
+        let f = |x, y, z| t.transform_point(&Point3::new(x, y, z));
+        assert_near(f(1.0, 0.0, 1.0), Point3::new(1.0, 0.0, 2.0));
+        assert_near(f(1.0, 0.0, -1.0), Point3::new(5.0, 0.0, -2.0));
+        assert_near(f(0.0, -1.0, -1.0), Point3::new(0.0, -5.0, -2.0));
+
+        // Looking along the X axis
+        let t = Camera::look_at(
+            Point3::new(4.0, 0.0, 0.0),
+            Point3::origin(),
+            Vector3::z(),
+        )
+        .clip(2.0, 6.0)
+        .transform();
+        let f = |x, y, z| t.transform_point(&Point3::new(x, y, z));
+        assert_near(f(0.0, 0.0, 1.0), Point3::new(2.0, 0.0, 0.0));
+        assert_near(f(0.0, 0.0, -1.0), Point3::new(-2.0, 0.0, 0.0));
+        assert_near(f(0.0, 1.0, 0.0), Point3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_camera_render() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let xy = ctx.hypot(x, y).unwrap();
+        let r = ctx.hypot(xy, z).unwrap();
+        let sphere = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(sphere).unwrap();
+
+        let camera = Camera::look_at(
+            Point3::new(0.0, 0.0, 3.0),
+            Point3::origin(),
+            Vector3::y(),
+        );
+        let size = 32;
+        let render = |camera: Camera| {
+            let config = RenderConfig {
+                image_size: size,
+                tile_sizes: vec![16, 8],
+                threads: 1,
+                mat: camera.transform(),
+                exact_boundaries: false,
+            };
+            crate::render::render3d(tape.clone(), &config).0
+        };
+        let center = size / 2 * size + size / 2;
+
+        // The sphere appears smaller with a wide perspective projection
+        let ortho = render(camera);
+        let persp = render(camera.perspective(90f32.to_radians()));
+        let count = |d: &[u32]| d.iter().filter(|d| **d > 0).count();
+        assert!(ortho[center] > 0);
+        assert!(persp[center] > 0);
+        assert!(count(&persp) < count(&ortho));
+
+        // Clipping in front of the sphere hides it
+        let clipped = render(camera.perspective(1.0).clip(1.0, 2.0));
+        assert_eq!(count(&clipped), 0);
+
+        // Clipping through the middle of the sphere shows its cross-section
+        // at the near plane
+        let cut = render(camera.clip(3.0, 4.0));
+        assert_eq!(cut[center], size as u32);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     context::{Context, Node},
-    eval::Family,
+    eval::{dynamic::Backend, Family},
     render::RenderMode,
     Error,
 };
@@ -8,7 +8,10 @@ use nalgebra::{
     allocator::Allocator, geometry::Transform, Const, DefaultAllocator,
     DimNameAdd, DimNameSub, DimNameSum, U1,
 };
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
 
 /// Container to store render configuration (resolution, etc)
 pub struct RenderConfig<const N: usize>
@@ -35,6 +38,15 @@ where
     /// By default, we render a cube spanning ±1 on all axes; `mat` allows for
     /// rotation, scaling, transformation, and even perspective.
     pub mat: Transform<f32, nalgebra::TGeneral, N>,
+
+    /// Evaluate pixels on tile boundaries with the unsimplified tape
+    ///
+    /// Tapes are simplified per-tile, and two simplified tapes may return
+    /// slightly different values for the same point, which can show up as
+    /// seams between tiles (e.g. in the normals of a 3D render).  Setting this
+    /// flag makes neighboring tiles agree along their shared boundaries, at the
+    /// cost of extra evaluation.
+    pub exact_boundaries: bool,
 }
 
 impl<const N: usize> Default for RenderConfig<N>
@@ -52,6 +64,7 @@ where
             },
             threads: 8,
             mat: Transform::identity(),
+            exact_boundaries: false,
         }
     }
 }
@@ -117,6 +130,8 @@ where
             tile_sizes,
             threads: self.threads,
             mat,
+            exact_boundaries: self.exact_boundaries,
+            deadline: None,
         }
     }
 }
@@ -137,6 +152,10 @@ where
     pub threads: usize,
 
     pub mat: NPlusOneMatrix<N>,
+    pub exact_boundaries: bool,
+
+    /// Time after which tiles are no longer subdivided (2D only)
+    pub deadline: Option<Instant>,
 }
 
 /// Type for a static `f32` matrix of size `N + 1`
@@ -213,6 +232,46 @@ impl RenderConfig<2> {
         let tape = context.get_tape(root)?;
         Ok(crate::render::render2d::<I, M>(tape, self, mode))
     }
+
+    /// Renders a shape in 2D, with the evaluator family picked at runtime
+    ///
+    /// This is equivalent to [`run`](Self::run) with the family given by
+    /// `backend`.
+    pub fn run_with_backend<M: RenderMode + Sync>(
+        &self,
+        backend: Backend,
+        root: Node,
+        context: Context,
+        mode: &M,
+    ) -> Result<Vec<<M as RenderMode>::Output>, Error> {
+        match backend {
+            Backend::Vm => self.run::<crate::vm::Eval, M>(root, context, mode),
+            #[cfg(feature = "jit")]
+            Backend::Jit => {
+                self.run::<crate::jit::Eval, M>(root, context, mode)
+            }
+        }
+    }
+
+    /// High-level API for rendering a shape with color channels in 2D
+    ///
+    /// Under the hood, this delegates to
+    /// [`fidget::render::render2d_color`](crate::render::render2d_color())
+    ///
+    /// Returns an RGBA image.
+    pub fn run_color<I: Family>(
+        &self,
+        root: Node,
+        colors: &[Node],
+        context: Context,
+    ) -> Result<Vec<[u8; 4]>, Error> {
+        let tape = context.get_tape(root)?;
+        let colors = colors
+            .iter()
+            .map(|c| context.get_tape(*c))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(crate::render::render2d_color::<I>(tape, &colors, self))
+    }
 }
 
 impl RenderConfig<3> {
@@ -230,6 +289,23 @@ impl RenderConfig<3> {
         let tape = context.get_tape(root)?;
         Ok(crate::render::render3d::<I>(tape, self))
     }
+
+    /// Renders a shape in 3D, with the evaluator family picked at runtime
+    ///
+    /// This is equivalent to [`run`](Self::run) with the family given by
+    /// `backend`.
+    pub fn run_with_backend(
+        &self,
+        backend: Backend,
+        root: Node,
+        context: Context,
+    ) -> Result<(Vec<u32>, Vec<[u8; 3]>), Error> {
+        match backend {
+            Backend::Vm => self.run::<crate::vm::Eval>(root, context),
+            #[cfg(feature = "jit")]
+            Backend::Jit => self.run::<crate::jit::Eval>(root, context),
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -247,6 +323,7 @@ mod test {
             tile_sizes: vec![64, 32],
             threads: 8,
             mat: Transform::identity(),
+            exact_boundaries: false,
         };
         let aligned = config.align();
         assert_eq!(aligned.image_size, config.image_size);
@@ -270,6 +347,7 @@ mod test {
             tile_sizes: vec![64, 32],
             threads: 8,
             mat: Transform::identity(),
+            exact_boundaries: false,
         };
         let aligned = config.align();
         assert_eq!(aligned.orig_image_size, 575);
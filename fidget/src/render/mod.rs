@@ -4,12 +4,51 @@
 //! [`RenderConfig::run`](RenderConfig::run); you can also use the lower-level
 //! functions ([`render2d`](render2d()) and [`render3d`](render3d())) for manual
 //! control over the input tape.
-mod config;
+//!
+//! For interactive use, [`render2d_progressive`] renders a low-resolution
+//! preview, then refines it tile-by-tile, reporting intermediate images to a
+//! callback.  [`render2d_bounded`] stops subdividing tiles after a time
+//! budget, returning a coarser image (and the regions which are unresolved)
+//! so that a UI can stay responsive.  [`render2d_color`] renders a shape along
+//! with color channels from separate tapes, and [`render2d_composite`] draws
+//! several shapes with their own colors and stacking order;
+//! [`render2d_materials`] finds which named primitive produced each pixel.
+//! [`render2d_into`] writes into a caller-provided RGBA framebuffer, reporting
+//! each region as it's finished.  [`render2d_cached`] keeps a persistent
+//! [`RenderCache`](cache::RenderCache) of interval results, so that
+//! re-rendering an unchanged model is cheap.  With the `rayon` feature, 2D
+//! tiles are rendered with [rayon](https://docs.rs/rayon), and
+//! `render2d_in_pool`, `render2d_cached_in_pool`, and `render3d_in_pool`
+//! render on a caller-provided thread pool.
+//!
+//! For 3D rendering, a [`Camera`] builds the transform matrix from a viewpoint
+//! and an orthographic or perspective projection.
+pub mod cache;
+mod camera;
+pub(crate) mod config;
 mod render2d;
 mod render3d;
 
+pub use camera::{Camera, Projection};
 pub use config::RenderConfig;
 pub use render2d::render as render2d;
+pub use render2d::render_bounded as render2d_bounded;
+pub use render2d::render_cached as render2d_cached;
+#[cfg(feature = "rayon")]
+pub use render2d::render_cached_in_pool as render2d_cached_in_pool;
+pub use render2d::render_color as render2d_color;
+pub use render2d::render_composite as render2d_composite;
+#[cfg(feature = "rayon")]
+pub use render2d::render_in_pool as render2d_in_pool;
+pub use render2d::render_into as render2d_into;
+pub use render2d::render_materials as render2d_materials;
+pub use render2d::render_progressive as render2d_progressive;
 pub use render3d::render as render3d;
+#[cfg(feature = "rayon")]
+pub use render3d::render_in_pool as render3d_in_pool;
 
-pub use render2d::{BitRenderMode, DebugRenderMode, RenderMode, SdfRenderMode};
+pub use render2d::{
+    BitRenderMode, BoundedImage, CoverageRenderMode, DebugRenderMode,
+    FrameRegion, Layer, RefineOrder, RenderMode, RenderProgress, RgbaPixel,
+    SdfRenderMode, UpdateGranularity,
+};
@@ -1,17 +1,26 @@
 //! 2D bitmap rendering / rasterization
 use crate::{
+    context::BoundingBox,
     eval::{
-        float_slice::{
-            FloatSliceEval, FloatSliceEvalData, FloatSliceEvalStorage,
-        },
-        interval::{IntervalEval, IntervalEvalData, IntervalEvalStorage},
-        tape::{Data as TapeData, Tape, Workspace},
+        float_slice::{FloatSliceEval, FloatSliceEvalData},
+        interval::{IntervalEval, IntervalEvalData},
+        pool::EvalContext,
+        tape::{Tape, TapeHash},
+        tracing::{ChoiceHint, TracingEvalResult},
         types::Interval,
-        Family,
+        Choice, Family,
+    },
+    render::{
+        cache::{self, CacheUpdate, Entry, RenderCache},
+        config::{AlignedRenderConfig, RenderConfig, Tile},
     },
-    render::config::{AlignedRenderConfig, Queue, RenderConfig, Tile},
+    Error,
 };
 use nalgebra::{Point2, Vector2};
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "rayon"))]
+use {crate::render::config::Queue, std::sync::mpsc};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -60,7 +69,7 @@ impl RenderMode for DebugRenderMode {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum DebugPixel {
     EmptyTile,
     FilledTile,
@@ -154,6 +163,44 @@ impl RenderMode for SdfRenderMode {
     }
 }
 
+/// Renderer that emits per-pixel coverage, for antialiased output
+///
+/// The shape is assumed to be a distance field: coverage ramps linearly from
+/// 255 (filled) to 0 (empty) as the distance goes from `-width / 2` to
+/// `width / 2`.  Use [`CoverageRenderMode::new`] to pick a width of one pixel.
+#[derive(Copy, Clone, Debug)]
+pub struct CoverageRenderMode {
+    /// Width of the antialiasing band, in model units
+    pub width: f32,
+}
+
+impl CoverageRenderMode {
+    /// Builds a coverage mode whose band is one pixel wide
+    pub fn new(config: &RenderConfig<2>) -> Self {
+        let mat = config.align().mat;
+        let p = |x, y| mat.transform_point(&Point2::new(x, y));
+        let o = p(0.0, 0.0);
+        let width = ((p(1.0, 0.0) - o).norm() + (p(0.0, 1.0) - o).norm()) / 2.0;
+        Self { width }
+    }
+}
+
+impl RenderMode for CoverageRenderMode {
+    type Output = u8;
+    fn interval(&self, i: Interval, _depth: usize) -> Option<u8> {
+        if i.upper() < -self.width / 2.0 {
+            Some(u8::MAX)
+        } else if i.lower() > self.width / 2.0 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+    fn pixel(&self, f: f32) -> u8 {
+        ((0.5 - f / self.width).clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 struct Scratch {
@@ -178,13 +225,13 @@ struct Worker<'a, I: Family, M: RenderMode> {
     config: &'a AlignedRenderConfig<2>,
     scratch: Scratch,
 
-    image: Vec<M::Output>,
+    /// Bounds of the shape, outside of which it is known to be empty
+    bounds: BoundingBox,
 
-    /// Storage for float slice evaluators
-    float_storage: [FloatSliceEvalStorage<I>; 2],
+    image: Vec<M::Output>,
 
-    /// Storage for interval evaluators, based on recursion depth
-    interval_storage: Vec<IntervalEvalStorage<I>>,
+    /// Pool of tapes and evaluator storage, to avoid allocation churn
+    pool: EvalContext<I>,
 
     /// Workspace for interval evaluators, based on recursion depth
     interval_data: Vec<IntervalEvalData<I>>,
@@ -192,14 +239,104 @@ struct Worker<'a, I: Family, M: RenderMode> {
     /// Workspace for pixel evaluators
     float_data: FloatSliceEvalData<I>,
 
-    spare_tapes: Vec<TapeData>,
-    workspace: Workspace,
+    /// Unsimplified tape, used for pixels on tile boundaries if
+    /// `config.exact_boundaries` is set
+    root: Tape<I>,
+
+    /// Evaluator for `root`, built when first needed
+    root_float: Option<FloatSliceEval<I>>,
+
+    /// Simplified tape and evaluators from the previous tile at each depth
+    siblings: Vec<Sibling<I>>,
+
+    /// Persistent cache of interval results, if enabled
+    cache: Option<&'a RenderCache>,
+
+    /// New cache entries and statistics for the current tile
+    cache_update: CacheUpdate,
+
+    /// Regions of the current tile which were estimated after the deadline
+    unresolved: Vec<FrameRegion>,
+}
+
+/// Simplified tape and evaluators from the most recent tile at a given depth
+///
+/// Neighboring tiles usually make the same choices, so the next tile at the
+/// same depth can reuse them instead of simplifying the tape and building new
+/// evaluators (which is expensive for the JIT).
+struct Sibling<I: Family> {
+    hint: ChoiceHint<I>,
+    /// Content hash of the hint's tape, if it has been computed
+    hash: Option<TapeHash>,
+    interval: Option<IntervalEval<I>>,
+    float: Option<FloatSliceEval<I>>,
+}
+
+impl<I: Family> Default for Sibling<I> {
+    fn default() -> Self {
+        Self {
+            hint: ChoiceHint::new(),
+            hash: None,
+            interval: None,
+            float: None,
+        }
+    }
 }
 
 impl<I: Family, M: RenderMode> Worker<'_, I, M> {
+    /// Simplifies the tape for a tile at the given depth, reusing the
+    /// previous sibling's tape if it made the same choices
+    fn simplify<B: core::borrow::Borrow<[crate::eval::Choice]>>(
+        &mut self,
+        depth: usize,
+        trace: &TracingEvalResult<Interval, I, B>,
+    ) -> Tape<I> {
+        let sibling = &mut self.siblings[depth];
+        if !sibling.hint.matches(trace) {
+            sibling.hash = None;
+            // Release stale evaluators first, so that the hint's tape is no
+            // longer shared and can be returned to the pool when replaced.
+            if let Some(e) = sibling.interval.take() {
+                self.pool.release_interval_evaluator(e);
+            }
+            if let Some(e) = sibling.float.take() {
+                self.pool.release_float_slice_evaluator(e);
+            }
+        }
+        self.pool.simplify_hinted(trace, &mut sibling.hint).unwrap()
+    }
+
+    /// Returns the content hash of the simplified tape at the given depth,
+    /// which must have been returned by [`Worker::simplify`]
+    fn tape_hash(&mut self, depth: usize, tape: &Tape<I>) -> TapeHash {
+        *self.siblings[depth]
+            .hash
+            .get_or_insert_with(|| tape.content_hash())
+    }
+
+    /// Releases the sibling tape and evaluators at the given depth
+    ///
+    /// This must be called before releasing their parent tape.
+    fn release_sibling(&mut self, depth: usize) {
+        let Some(sibling) = self.siblings.get_mut(depth) else {
+            return;
+        };
+        sibling.hash = None;
+        if let Some(e) = sibling.interval.take() {
+            self.pool.release_interval_evaluator(e);
+        }
+        if let Some(e) = sibling.float.take() {
+            self.pool.release_float_slice_evaluator(e);
+        }
+        if let Some(t) = sibling.hint.take() {
+            self.pool.release_tape(t);
+        }
+    }
+
     fn render_tile_recurse(
         &mut self,
         i_handle: &mut IntervalEval<I>,
+        hash: Option<TapeHash>,
         depth: usize,
         tile: Tile<2>,
         float_handle: &mut Option<FloatSliceEval<I>>,
@@ -229,38 +366,92 @@ impl<I: Family, M: RenderMode> Worker<'_, I, M> {
         let y = Interval::new(y_min, y_max);
         let z = Interval::new(0.0, 0.0);
 
+        // If the tile is outside the shape's bounds, then it's strictly
+        // positive, which may let us fill it without evaluation.
+        if !self.bounds.intersects(x, y, z) {
+            let outside = Interval::new(f32::MIN_POSITIVE, f32::INFINITY);
+            if let Some(fill) = mode.interval(outside, depth) {
+                for y in 0..tile_size {
+                    let start = self.config.tile_to_offset(tile, 0, y);
+                    self.image[start..][..tile_size].fill(fill);
+                }
+                return;
+            }
+        }
+
+        // Look up the tile in the persistent cache, if present (ignoring
+        // entries whose choices don't fit the tape, which are corrupt)
+        let key = hash.map(|h| cache::key(h, x, y, z));
+        let cached = match (self.cache, key) {
+            (Some(cache), Some(key)) => {
+                let n = i_handle.tape().choice_count();
+                cache.get(&key).filter(|e| match &e.choices {
+                    Some(c) => c.len() == n,
+                    None => true,
+                })
+            }
+            _ => None,
+        };
+
         let mut data = std::mem::take(&mut self.interval_data[depth]);
-        let (i, simplify) =
-            i_handle.eval_with(x, y, z, &[], &mut data).unwrap();
+        let (i, simplify) = if let Some(e) = cached {
+            self.cache_update.hits += 1;
+            let trace = e
+                .choices
+                .as_deref()
+                .map(|c| TracingEvalResult::new(c, i_handle.tape()));
+            (e.interval, trace)
+        } else {
+            i_handle.eval_with(x, y, z, &[], &mut data).unwrap()
+        };
 
         let fill = mode.interval(i, depth);
 
+        let mut sub_hash = None;
         if let Some(fill) = fill {
             for y in 0..tile_size {
                 let start = self.config.tile_to_offset(tile, 0, y);
                 self.image[start..][..tile_size].fill(fill);
             }
+        } else if depth + 1 < self.config.tile_sizes.len()
+            && self.config.deadline.is_some_and(|d| Instant::now() >= d)
+        {
+            self.estimate_tile(depth, tile, mode);
         } else if let Some(next_tile_size) =
             self.config.tile_sizes.get(depth + 1)
         {
             let sub_tape = if let Some(data) = simplify.as_ref() {
-                data.simplify_with(
-                    &mut self.workspace,
-                    std::mem::take(&mut self.spare_tapes[depth]),
-                )
-                .unwrap()
+                self.simplify(depth, data)
             } else {
                 i_handle.tape()
             };
-            let storage = std::mem::take(&mut self.interval_storage[depth]);
-            let mut sub_jit =
-                sub_tape.new_interval_evaluator_with_storage(storage);
+            if hash.is_some() {
+                sub_hash = if simplify.is_none() {
+                    hash
+                } else if let Some(h) = cached.and_then(|e| e.tape) {
+                    self.siblings[depth].hash = Some(h);
+                    Some(h)
+                } else {
+                    Some(self.tape_hash(depth, &sub_tape))
+                };
+            }
+            // Reuse the previous sibling's evaluator if it has the same tape
+            let mut sub_jit = match self.siblings[depth].interval.take() {
+                Some(e) if e.tape().id() == sub_tape.id() => e,
+                prev => {
+                    if let Some(e) = prev {
+                        self.pool.release_interval_evaluator(e);
+                    }
+                    self.pool.new_interval_evaluator(&sub_tape)
+                }
+            };
             let n = tile_size / next_tile_size;
             let mut float_handle = None;
             for j in 0..n {
                 for i in 0..n {
                     self.render_tile_recurse(
                         &mut sub_jit,
+                        sub_hash,
                         depth + 1,
                         self.config.new_tile([
                             tile.corner[0] + i * next_tile_size,
@@ -271,52 +462,95 @@ impl<I: Family, M: RenderMode> Worker<'_, I, M> {
                     );
                 }
             }
-            self.interval_storage[depth] = sub_jit.take().unwrap();
+            // Release evaluators before the tape, since they hold references
+            // to it (releasing an unsimplified tape does nothing, because it's
+            // still shared with our parent).  Our children's siblings are
+            // derived from `sub_tape`, so they're released too; our own
+            // evaluator is kept for our next sibling.
+            self.release_sibling(depth + 1);
+            self.siblings[depth].interval = Some(sub_jit);
             if let Some(f) = float_handle {
-                self.float_storage[0] = f.take().unwrap();
-            }
-            if simplify.is_some() {
-                self.spare_tapes[depth] = sub_tape.take().unwrap();
+                self.pool.release_float_slice_evaluator(f);
             }
+            self.pool.release_tape(sub_tape);
         } else {
-            // TODO this is not a place of honor
             let sub_tape = if let Some(simplify) = simplify.as_ref() {
-                simplify
-                    .simplify_with(
-                        &mut self.workspace,
-                        std::mem::take(self.spare_tapes.last_mut().unwrap()),
-                    )
-                    .unwrap()
+                self.simplify(depth, simplify)
             } else {
                 i_handle.tape()
             };
             self.render_tile_pixels(
                 i_handle.tape(),
                 &sub_tape,
-                tile_size,
+                depth,
                 tile,
                 float_handle,
                 mode,
             );
-            if simplify.is_some() {
-                *self.spare_tapes.last_mut().unwrap() =
-                    sub_tape.take().unwrap();
-            }
+            self.pool.release_tape(sub_tape);
+        }
+
+        if let (Some(key), None) = (key, cached) {
+            let entry = Entry {
+                interval: i,
+                choices: simplify.as_ref().map(|t| t.choices().into()),
+                tape: sub_hash.filter(|_| simplify.is_some()),
+            };
+            self.cache_update.entries.push((key, entry));
         }
 
         // Return the data
         self.interval_data[depth] = data;
     }
 
+    /// Fills a tile with the shape's value at its center, instead of
+    /// subdividing it, and records it as unresolved
+    fn estimate_tile(&mut self, depth: usize, tile: Tile<2>, mode: &M) {
+        let tile_size = self.config.tile_sizes[depth];
+        let half = (tile_size - 1) as f32 / 2.0;
+        let p = self.config.mat.transform_point(&Point2::new(
+            tile.corner[0] as f32 + half,
+            tile.corner[1] as f32 + half,
+        ));
+
+        // Use the unsimplified tape, which is shared by every tile, so that
+        // we don't build a new evaluator for each one
+        let func = self
+            .root_float
+            .get_or_insert_with(|| self.root.new_float_slice_evaluator());
+        let out = func
+            .eval_with(&[p.x], &[p.y], &[0.0], &[], &mut self.float_data)
+            .unwrap();
+        let fill = mode.pixel(out[0]);
+        for y in 0..tile_size {
+            let start = self.config.tile_to_offset(tile, 0, y);
+            self.image[start..][..tile_size].fill(fill);
+        }
+
+        // Convert to image coordinates, clipping to the original image size
+        let size = self.config.orig_image_size;
+        let right = (tile.corner[0] + tile_size).min(size);
+        let top = (tile.corner[1] + tile_size).min(size);
+        if tile.corner[0] < right && tile.corner[1] < top {
+            self.unresolved.push(FrameRegion {
+                x: tile.corner[0],
+                y: size - top,
+                width: right - tile.corner[0],
+                height: top - tile.corner[1],
+            });
+        }
+    }
+
     fn render_tile_pixels(
         &mut self,
         prev_tape: Tape<I>,
         sub_tape: &Tape<I>,
-        tile_size: usize,
+        depth: usize,
         tile: Tile<2>,
         float_handle: &mut Option<FloatSliceEval<I>>,
         mode: &M,
     ) {
+        let tile_size = self.config.tile_sizes[depth];
         let mut index = 0;
         for j in 0..tile_size {
             for i in 0..tile_size {
@@ -329,6 +563,7 @@ impl<I: Family, M: RenderMode> Worker<'_, I, M> {
                 index += 1;
             }
         }
+        let size = index.next_multiple_of(I::LANE_WIDTH);
 
         // In some cases, the shortened tape isn't actually any shorter, so
         // it's a waste of time to rebuild it.  Instead, we want to use a
@@ -338,44 +573,51 @@ impl<I: Family, M: RenderMode> Worker<'_, I, M> {
         // use it.
         //
         // (this matters most for the JIT compiler, which is _expensive_)
-        let out = if sub_tape.len() < prev_tape.len() {
-            let storage = std::mem::take(&mut self.float_storage[1]);
-            let func = sub_tape.new_float_slice_evaluator_with_storage(storage);
+        let simplified = sub_tape.len() < prev_tape.len();
+        let out = if simplified {
+            // Reuse the previous sibling's evaluator if it has the same tape
+            let func = match self.siblings[depth].float.take() {
+                Some(f) if f.tape().id() == sub_tape.id() => f,
+                prev => {
+                    if let Some(f) = prev {
+                        self.pool.release_float_slice_evaluator(f);
+                    }
+                    self.pool.new_float_slice_evaluator(sub_tape)
+                }
+            };
 
             let out = func
                 .eval_with(
-                    &self.scratch.x,
-                    &self.scratch.y,
-                    &self.scratch.z,
+                    &self.scratch.x[..size],
+                    &self.scratch.y[..size],
+                    &self.scratch.z[..size],
                     &[],
                     &mut self.float_data,
                 )
                 .unwrap();
 
-            // We consume the evaluator, so any reuse of memory between the
-            // FloatSliceFunc and FloatSliceEval should be cleared up and we
-            // should be able to reuse the working memory.
-            self.float_storage[1] = func.take().unwrap();
+            // Keep the evaluator around for our next sibling, which will
+            // probably have the same simplified tape
+            self.siblings[depth].float = Some(func);
             out
         } else {
             // Reuse the FloatSliceFunc handle passed in, or build one if it
             // wasn't already available (which makes it available to siblings)
             let func = float_handle.get_or_insert_with(|| {
-                let storage = std::mem::take(&mut self.float_storage[0]);
-                prev_tape.new_float_slice_evaluator_with_storage(storage)
+                self.pool.new_float_slice_evaluator(&prev_tape)
             });
 
             func.eval_with(
-                &self.scratch.x,
-                &self.scratch.y,
-                &self.scratch.z,
+                &self.scratch.x[..size],
+                &self.scratch.y[..size],
+                &self.scratch.z[..size],
                 &[],
                 &mut self.float_data,
             )
             .unwrap()
 
-            // Don't release func to self.float_storage[0] here; it's done by
-            // the parent caller at the end of subtile iteration.
+            // Don't release func to the pool here; it's done by the parent
+            // caller at the end of subtile iteration.
         };
 
         let mut index = 0;
@@ -386,44 +628,147 @@ impl<I: Family, M: RenderMode> Worker<'_, I, M> {
                 index += 1;
             }
         }
+
+        let len = if simplified {
+            sub_tape.len()
+        } else {
+            prev_tape.len()
+        };
+        if self.config.exact_boundaries && len < self.root.len() {
+            self.render_boundary_pixels(tile_size, tile, mode);
+        }
+    }
+
+    /// Re-renders pixels on the edges of a tile with the unsimplified tape
+    ///
+    /// This means that neighboring tiles agree along their shared boundaries,
+    /// regardless of how their tapes were simplified.
+    fn render_boundary_pixels(
+        &mut self,
+        tile_size: usize,
+        tile: Tile<2>,
+        mode: &M,
+    ) {
+        let edge = |i: usize, j: usize| {
+            i == 0 || j == 0 || i + 1 == tile_size || j + 1 == tile_size
+        };
+        let mut index = 0;
+        for j in 0..tile_size {
+            for i in (0..tile_size).filter(|i| edge(*i, j)) {
+                let p = self.config.mat.transform_point(&Point2::new(
+                    (tile.corner[0] + i) as f32,
+                    (tile.corner[1] + j) as f32,
+                ));
+                self.scratch.x[index] = p.x;
+                self.scratch.y[index] = p.y;
+                index += 1;
+            }
+        }
+
+        // Round up to a multiple of the evaluator's lane width, so that it
+        // doesn't fall back to a scalar tail; the extra points are leftovers
+        // from a previous tile, and their results are ignored.
+        let size = index.next_multiple_of(I::LANE_WIDTH);
+        let func = self
+            .root_float
+            .get_or_insert_with(|| self.root.new_float_slice_evaluator());
+        let out = func
+            .eval_with(
+                &self.scratch.x[..size],
+                &self.scratch.y[..size],
+                &self.scratch.z[..size],
+                &[],
+                &mut self.float_data,
+            )
+            .unwrap();
+
+        let mut index = 0;
+        for j in 0..tile_size {
+            let o = self.config.tile_to_offset(tile, 0, j);
+            for i in (0..tile_size).filter(|i| edge(*i, j)) {
+                self.image[o + i] = mode.pixel(out[index]);
+                index += 1;
+            }
+        }
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
+impl<'a, I: Family, M: RenderMode> Worker<'a, I, M> {
+    fn new(
+        root: Tape<I>,
+        cache: Option<&'a RenderCache>,
+        config: &'a AlignedRenderConfig<2>,
+    ) -> Self {
+        let tile_size = config.tile_sizes.last().unwrap_or(&0);
+        let scratch =
+            Scratch::new(tile_size.pow(2).next_multiple_of(I::LANE_WIDTH));
+        Worker {
+            scratch,
+            image: vec![],
+            config,
+            bounds: root.bounds(),
+            pool: EvalContext::new(),
+            interval_data: (0..config.tile_sizes.len())
+                .map(|_| Default::default())
+                .collect(),
+            float_data: Default::default(),
+            root,
+            root_float: None,
+            siblings: (0..config.tile_sizes.len())
+                .map(|_| Default::default())
+                .collect(),
+            cache,
+            cache_update: CacheUpdate::default(),
+            unresolved: vec![],
+        }
+    }
+
+    /// Renders a single top-level tile
+    fn render_tile(
+        &mut self,
+        i_handle: &mut IntervalEval<I>,
+        hash: Option<TapeHash>,
+        tile: Tile<2>,
+        mode: &M,
+    ) -> TileOutput<M::Output> {
+        self.image =
+            vec![M::Output::default(); self.config.tile_sizes[0].pow(2)];
+        self.render_tile_recurse(i_handle, hash, 0, tile, &mut None, mode);
+        TileOutput {
+            pixels: std::mem::take(&mut self.image),
+            update: std::mem::take(&mut self.cache_update),
+            unresolved: std::mem::take(&mut self.unresolved),
+        }
+    }
+}
+
+/// Result of rendering a single top-level tile
+struct TileOutput<T> {
+    pixels: Vec<T>,
+    update: CacheUpdate,
+    /// Regions which were estimated after the deadline
+    unresolved: Vec<FrameRegion>,
+}
+
+#[cfg(not(feature = "rayon"))]
 fn worker<I: Family, M: RenderMode>(
     mut i_handle: IntervalEval<I>,
+    hash: Option<TapeHash>,
+    cache: Option<&RenderCache>,
     queue: &Queue<2>,
     config: &AlignedRenderConfig<2>,
     mode: &M,
-) -> Vec<(Tile<2>, Vec<M::Output>)> {
-    let mut out = vec![];
-    let scratch = Scratch::new(config.tile_sizes.last().unwrap_or(&0).pow(2));
-
-    let mut w: Worker<I, M> = Worker {
-        scratch,
-        image: vec![],
-        config,
-        float_storage: Default::default(),
-        interval_storage: (0..config.tile_sizes.len())
-            .map(|_| Default::default())
-            .collect(),
-        interval_data: (0..config.tile_sizes.len())
-            .map(|_| Default::default())
-            .collect(),
-        spare_tapes: (0..config.tile_sizes.len())
-            .map(|_| Default::default())
-            .collect(),
-        float_data: Default::default(),
-        workspace: Default::default(),
-    };
+    out: mpsc::Sender<(Tile<2>, TileOutput<M::Output>)>,
+) {
+    let mut w: Worker<I, M> = Worker::new(i_handle.tape(), cache, config);
     while let Some(tile) = queue.next() {
-        w.image = vec![M::Output::default(); config.tile_sizes[0].pow(2)];
-        w.render_tile_recurse(&mut i_handle, 0, tile, &mut None, mode);
-        let pixels = std::mem::take(&mut w.image);
-        out.push((tile, pixels))
+        let data = w.render_tile(&mut i_handle, hash, tile, mode);
+        if out.send((tile, data)).is_err() {
+            break;
+        }
     }
-    out
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -442,13 +787,615 @@ pub fn render<I: Family, M: RenderMode + Sync>(
     config: &RenderConfig<2>,
     mode: &M,
 ) -> Vec<M::Output> {
+    render_with_cache(tape, config, mode, None)
+}
+
+/// Renders the given tape like [`render`](render()), using and updating a
+/// persistent cache of interval results
+///
+/// Tiles which are found in the cache skip interval evaluation, so
+/// re-rendering an unchanged tape is much cheaper; see the
+/// [`cache`](crate::render::cache) module for details.
+pub fn render_cached<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    cache: &mut RenderCache,
+) -> Vec<M::Output> {
+    render_with_cache(tape, config, mode, Some(cache))
+}
+
+fn render_with_cache<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    cache: Option<&mut RenderCache>,
+) -> Vec<M::Output> {
+    let config = config.align();
+    let tiles = all_tiles(&config);
+
+    let mut image = vec![M::Output::default(); config.orig_image_size.pow(2)];
+    render_tiles(tape, &config, mode, tiles, cache, |tile, data, _| {
+        write_tile(&config, &mut image, tile, data)
+    });
+    image
+}
+
+/// Order in which [`render_progressive`] refines tiles
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum RefineOrder {
+    /// Refine tiles closest to the center of the image first
+    #[default]
+    Center,
+    /// Refine tiles with the most edges in the preview first
+    ///
+    /// These are the tiles where the preview is most likely to be wrong;
+    /// ties are broken by distance to the center of the image.
+    Edges,
+}
+
+/// Progress of a [`render_progressive`] call, passed to its callback
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RenderProgress {
+    /// Number of tiles which have been rendered at full resolution
+    pub refined: usize,
+    /// Total number of tiles
+    pub total: usize,
+}
+
+impl RenderProgress {
+    /// Checks whether every tile has been rendered at full resolution
+    pub fn is_done(&self) -> bool {
+        self.refined == self.total
+    }
+}
+
+/// Scale factor between the preview and the full-resolution image
+const PREVIEW_SCALE: usize = 8;
+
+/// Renders a 2D image progressively, for a responsive preview
+///
+/// The image is first rendered at 1/8 resolution (then upscaled); after that,
+/// top-level tiles are rendered at full resolution in the given `order`.  The
+/// callback is invoked with the full-size image after the preview and after
+/// each refined tile, so an interactive editor can display intermediate
+/// results.
+///
+/// Returns the final image, which is identical to the output of
+/// [`render`](render()).
+pub fn render_progressive<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    order: RefineOrder,
+    mut callback: impl FnMut(&[M::Output], RenderProgress),
+) -> Vec<M::Output>
+where
+    M::Output: PartialEq,
+{
+    let size = config.image_size;
+    let preview_size = size.div_ceil(PREVIEW_SCALE);
+    let preview_config = RenderConfig {
+        image_size: preview_size,
+        tile_sizes: config.tile_sizes.clone(),
+        threads: config.threads,
+        mat: config.mat,
+        exact_boundaries: config.exact_boundaries,
+    };
+    let preview = render(tape.clone(), &preview_config, mode);
+
+    // Upscales the preview, picking the nearest pixel for full-resolution
+    // coordinates (with Y pointing up, as in `AlignedRenderConfig`)
+    let preview_at = |x: usize, y: usize| {
+        let px = x * preview_size / size;
+        let py = y * preview_size / size;
+        preview[(preview_size - py - 1) * preview_size + px]
+    };
+    let mut image = Vec::with_capacity(size * size);
+    for y in (0..size).rev() {
+        image.extend((0..size).map(|x| preview_at(x, y)));
+    }
+
+    let config = config.align();
+    let mut tiles = all_tiles(&config);
+    let total = tiles.len();
+    callback(&image, RenderProgress { refined: 0, total });
+
+    // Sort tiles by priority, using (negative) edge count then distance to
+    // the image's center as the key
+    let center = size as f32 / 2.0;
+    let tile_size = config.tile_sizes[0];
+    tiles.sort_by_cached_key(|tile| {
+        let edges = match order {
+            RefineOrder::Center => 0,
+            RefineOrder::Edges => {
+                let mut edges = 0usize;
+                for y in tile.corner[1]..(tile.corner[1] + tile_size).min(size)
+                {
+                    for x in
+                        tile.corner[0]..(tile.corner[0] + tile_size).min(size)
+                    {
+                        let p = preview_at(x, y);
+                        if (x + 1 < size && preview_at(x + 1, y) != p)
+                            || (y + 1 < size && preview_at(x, y + 1) != p)
+                        {
+                            edges += 1;
+                        }
+                    }
+                }
+                edges
+            }
+        };
+        let dx = tile.corner[0] as f32 + tile_size as f32 / 2.0 - center;
+        let dy = tile.corner[1] as f32 + tile_size as f32 / 2.0 - center;
+        (std::cmp::Reverse(edges), (dx * dx + dy * dy) as u64)
+    });
+
+    let mut refined = 0;
+    render_tiles(tape, &config, mode, tiles, None, |tile, data, _| {
+        write_tile(&config, &mut image, tile, data);
+        refined += 1;
+        callback(&image, RenderProgress { refined, total });
+    });
+    image
+}
+
+/// Image returned by [`render_bounded`], which may be partially resolved
+#[derive(Clone, Debug)]
+pub struct BoundedImage<T> {
+    /// Image pixels, in the same layout as [`render`](render())
+    pub image: Vec<T>,
+    /// Regions which were filled with a single sample at their center,
+    /// because the deadline passed before they could be subdivided
+    pub unresolved: Vec<FrameRegion>,
+}
+
+impl<T> BoundedImage<T> {
+    /// Checks whether every region was fully resolved before the deadline
+    pub fn is_complete(&self) -> bool {
+        self.unresolved.is_empty()
+    }
+}
+
+/// Renders a 2D image, giving up on subdivision after a time budget
+///
+/// Rendering proceeds as in [`render`](render()) until `budget` has elapsed.
+/// After that, any tile whose interval result is ambiguous is filled with the
+/// shape's value at the tile's center rather than being subdivided, and its
+/// region is recorded as unresolved.  Every remaining tile still costs one
+/// interval evaluation, so the overrun past the budget is small.
+///
+/// This keeps an interactive UI responsive while dragging parameters: render
+/// with a short budget on every change, then call [`render`](render()) (or
+/// this function with a longer budget) when the user stops, if the result
+/// [wasn't complete](BoundedImage::is_complete).
+pub fn render_bounded<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    budget: Duration,
+) -> BoundedImage<M::Output> {
+    let mut config = config.align();
+    config.deadline = Some(Instant::now() + budget);
+    let tiles = all_tiles(&config);
+
+    let mut image = vec![M::Output::default(); config.orig_image_size.pow(2)];
+    let mut unresolved = vec![];
+    render_tiles(tape, &config, mode, tiles, None, |tile, data, u| {
+        write_tile(&config, &mut image, tile, data);
+        unresolved.extend_from_slice(u);
+    });
+    BoundedImage { image, unresolved }
+}
+
+/// Pixel type which can be written into an RGBA framebuffer
+pub trait RgbaPixel {
+    /// Converts this pixel into an `[r, g, b, a]` color
+    fn to_rgba(&self) -> [u8; 4];
+}
+
+impl RgbaPixel for bool {
+    /// Filled pixels are white, empty pixels are black
+    fn to_rgba(&self) -> [u8; 4] {
+        if *self {
+            [u8::MAX; 4]
+        } else {
+            [0, 0, 0, u8::MAX]
+        }
+    }
+}
+
+impl RgbaPixel for [u8; 3] {
+    fn to_rgba(&self) -> [u8; 4] {
+        let [r, g, b] = *self;
+        [r, g, b, u8::MAX]
+    }
+}
+
+impl RgbaPixel for u8 {
+    /// Coverage is used as the alpha channel of a white pixel
+    fn to_rgba(&self) -> [u8; 4] {
+        [u8::MAX, u8::MAX, u8::MAX, *self]
+    }
+}
+
+impl RgbaPixel for DebugPixel {
+    fn to_rgba(&self) -> [u8; 4] {
+        self.as_debug_color()
+    }
+}
+
+/// How often [`render_into`] invokes its callback
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum UpdateGranularity {
+    /// Invoke the callback as each top-level tile is written
+    #[default]
+    Tile,
+    /// Invoke the callback once every tile in a band of rows is written
+    ///
+    /// Bands are reported from the top of the image to the bottom, and each
+    /// one spans the full width of the image.
+    Row,
+}
+
+/// Region of a framebuffer which has been written by [`render_into`], or of an
+/// image which was left unresolved by [`render_bounded`]
+///
+/// Coordinates are in pixels, with the origin at the top-left corner of the
+/// image (i.e. row 0 is the first row of the framebuffer).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FrameRegion {
+    /// Leftmost column
+    pub x: usize,
+    /// Topmost row
+    pub y: usize,
+    /// Width in pixels
+    pub width: usize,
+    /// Height in pixels
+    pub height: usize,
+}
+
+/// Renders a 2D image directly into a caller-provided RGBA framebuffer
+///
+/// The framebuffer stores `[r, g, b, a]` bytes for each pixel, with rows
+/// starting every `stride` bytes from the top of the image; bytes between the
+/// end of a row and the start of the next one are left unchanged.  This lets
+/// the image be written straight into (for example) a mapped GUI texture or a
+/// video encoder's frame, without an intermediate copy.
+///
+/// Pixels are written as tiles finish rendering, and `callback` is invoked
+/// with the framebuffer and the region that was just written, at the given
+/// `granularity`.  Every pixel in the image is covered by exactly one region.
+///
+/// Returns [`Error::BadFramebuffer`] if the stride is less than four bytes per
+/// pixel or the framebuffer is too short for the image.
+pub fn render_into<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    framebuffer: &mut [u8],
+    stride: usize,
+    granularity: UpdateGranularity,
+    mut callback: impl FnMut(&[u8], FrameRegion),
+) -> Result<(), Error>
+where
+    M::Output: RgbaPixel,
+{
+    let size = config.image_size;
+    let needed = match size {
+        0 => 0,
+        _ => stride * (size - 1) + size * 4,
+    };
+    if stride < size * 4 || framebuffer.len() < needed {
+        return Err(Error::BadFramebuffer(stride, framebuffer.len()));
+    }
+
     let config = config.align();
+    let mut tiles = all_tiles(&config);
+
+    // Render from the top of the image down, so that bands of rows are
+    // finished as early as possible
+    tiles.sort_by_key(|t| (std::cmp::Reverse(t.corner[1]), t.corner[0]));
+    let tile_size = config.tile_sizes[0];
+    let per_row = config.image_size / tile_size;
+    let mut remaining = vec![per_row; per_row];
+
+    // Returns the image-space rows covered by a tile's Y position
+    let rows = |corner: usize| {
+        let bottom = size.saturating_sub(corner);
+        let top = size.saturating_sub(corner + tile_size);
+        (top, bottom - top)
+    };
+
+    render_tiles(tape, &config, mode, tiles, None, |tile, data, _| {
+        let mut index = 0;
+        for j in 0..tile_size {
+            let y = j + tile.corner[1];
+            for i in 0..tile_size {
+                let x = i + tile.corner[0];
+                if y < size && x < size {
+                    let o = (size - y - 1) * stride + x * 4;
+                    framebuffer[o..o + 4]
+                        .copy_from_slice(&data[index].to_rgba());
+                }
+                index += 1;
+            }
+        }
+
+        let (y, height) = rows(tile.corner[1]);
+        let region = match granularity {
+            UpdateGranularity::Tile => {
+                let x = tile.corner[0].min(size);
+                let width = (x + tile_size).min(size) - x;
+                FrameRegion {
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            }
+            UpdateGranularity::Row => {
+                let r = &mut remaining[tile.corner[1] / tile_size];
+                *r -= 1;
+                if *r > 0 {
+                    return;
+                }
+                FrameRegion {
+                    x: 0,
+                    y,
+                    width: size,
+                    height,
+                }
+            }
+        };
+        if region.width > 0 && region.height > 0 {
+            callback(framebuffer, region);
+        }
+    });
+    Ok(())
+}
+
+/// Shape and color to be drawn by [`render_composite`]
+#[derive(Clone)]
+pub struct Layer<I: Family> {
+    /// Shape to draw, which should be a distance field
+    pub tape: Tape<I>,
+    /// Color as `[r, g, b, a]`; the alpha channel is multiplied by coverage
+    pub color: [u8; 4],
+    /// Stacking order; layers with higher values are drawn on top
+    pub z: i32,
+}
+
+/// Renders multiple shapes into a single RGBA image at Z = 0
+///
+/// Each layer is rendered separately with [`CoverageRenderMode`], then
+/// composited onto a transparent background in order of increasing
+/// [`z`](Layer::z) (layers with the same `z` are drawn in the order given),
+/// using the standard "over" operator.  This draws simple scenes without
+/// merging every shape into a single expression, and with antialiased edges.
+///
+/// The output is an RGBA image with straight (non-premultiplied) alpha.
+pub fn render_composite<I: Family>(
+    layers: &[Layer<I>],
+    config: &RenderConfig<2>,
+) -> Vec<[u8; 4]> {
+    let mode = CoverageRenderMode::new(config);
+    let mut order: Vec<&Layer<I>> = layers.iter().collect();
+    order.sort_by_key(|layer| layer.z);
+
+    let mut image = vec![[0.0f32; 4]; config.image_size.pow(2)];
+    for layer in order {
+        let coverage = render(layer.tape.clone(), config, &mode);
+        let [r, g, b, a] = layer.color.map(|c| c as f32 / 255.0);
+        for (p, c) in image.iter_mut().zip(coverage) {
+            let src = a * c as f32 / 255.0;
+            let dst = p[3] * (1.0 - src);
+            let out = src + dst;
+            if out > 0.0 {
+                for (v, s) in p.iter_mut().zip([r, g, b]) {
+                    *v = (s * src + *v * dst) / out;
+                }
+            }
+            p[3] = out;
+        }
+    }
+    image
+        .into_iter()
+        .map(|p| p.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8))
+        .collect()
+}
+
+/// Renders a shape and its color channels into an RGBA image at Z = 0
+///
+/// `shape` is a distance field, which is rendered like [`BitRenderMode`] to
+/// determine coverage (stored in the alpha channel).  Each tape in `colors`
+/// provides one color channel (red, then green, then blue; missing channels
+/// are zero), with values in `[0, 1]` mapped to `[0, 255]`.
+///
+/// Color tapes are only evaluated at pixels inside the shape, in bulk and
+/// in parallel, so the shape's geometry is only rendered once and empty
+/// regions are never colored.  Pixels outside the shape are `[0, 0, 0, 0]`.
+///
+/// # Panics
+/// If more than three color tapes are provided.
+pub fn render_color<I: Family>(
+    shape: Tape<I>,
+    colors: &[Tape<I>],
+    config: &RenderConfig<2>,
+) -> Vec<[u8; 4]> {
+    assert!(colors.len() <= 3, "too many color channels");
+    let config = config.align();
+
+    let size = config.orig_image_size;
+    let mut image = vec![[0u8; 4]; size.pow(2)];
+    let filled = filled_pixels(shape, &config);
+
+    // Evaluate every color channel at the filled pixels
+    let chunk_size = (filled.len() / config.threads.max(1)).max(1);
+    let channels: Vec<Vec<u8>> = std::thread::scope(|s| {
+        let handles: Vec<_> = filled
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let config = &config;
+                s.spawn(move || {
+                    let mut xs = Vec::with_capacity(chunk.len());
+                    let mut ys = Vec::with_capacity(chunk.len());
+                    for &(x, y) in chunk {
+                        let p = config
+                            .mat
+                            .transform_point(&Point2::new(x as f32, y as f32));
+                        xs.push(p.x);
+                        ys.push(p.y);
+                    }
+                    let zs = vec![0.0; chunk.len()];
+                    let mut values = vec![0.0; chunk.len()];
+                    let mut data = Default::default();
+                    let mut out = vec![];
+                    for tape in colors {
+                        let eval = tape.new_float_slice_evaluator();
+                        eval.eval_into(
+                            &xs,
+                            &ys,
+                            &zs,
+                            &[],
+                            &mut values,
+                            &mut data,
+                        )
+                        .unwrap();
+                        out.push(
+                            values
+                                .iter()
+                                .map(|v| (v.clamp(0.0, 1.0) * 255.0) as u8)
+                                .collect::<Vec<u8>>(),
+                        );
+                    }
+                    out
+                })
+            })
+            .collect();
+        let mut channels = vec![vec![]; colors.len()];
+        for h in handles {
+            for (c, v) in channels.iter_mut().zip(h.join().unwrap()) {
+                c.extend(v);
+            }
+        }
+        channels
+    });
+
+    for (i, &(x, y)) in filled.iter().enumerate() {
+        let pixel = &mut image[(size - y - 1) * size + x];
+        for (c, channel) in channels.iter().enumerate() {
+            pixel[c] = channel[i];
+        }
+        pixel[3] = u8::MAX;
+    }
+    image
+}
+
+/// Renders a per-pixel material ID channel at Z = 0
+///
+/// `shape` is rendered like [`BitRenderMode`]; then, at each pixel inside the
+/// shape, it's evaluated with a tracing point evaluator and the resulting
+/// choices are passed to
+/// [`Data::material`](crate::eval::tape::Data::material).  This finds the
+/// named node which "won" the `min` / `max` operations at that pixel, so each
+/// primitive in a union can be given its own color (by looking up its name in
+/// [`Data::names`](crate::eval::tape::Data::names)) without evaluating any
+/// extra fields.
+///
+/// Pixels outside the shape (or whose winning node is unnamed) are `None`.
+pub fn render_materials<I: Family>(
+    shape: Tape<I>,
+    config: &RenderConfig<2>,
+) -> Vec<Option<u32>> {
+    let config = config.align();
+    let size = config.orig_image_size;
+    let filled = filled_pixels(shape.clone(), &config);
+
+    let chunk_size = (filled.len() / config.threads.max(1)).max(1);
+    let materials: Vec<Option<u32>> = std::thread::scope(|s| {
+        let handles: Vec<_> = filled
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let config = &config;
+                let shape = &shape;
+                s.spawn(move || {
+                    let eval = shape.new_point_evaluator();
+                    let ties = vec![Choice::Both; shape.choice_count()];
+                    let mut data = Default::default();
+                    chunk
+                        .iter()
+                        .map(|&(x, y)| {
+                            let p = config.mat.transform_point(&Point2::new(
+                                x as f32, y as f32,
+                            ));
+                            let (_, trace) = eval
+                                .eval_with(p.x, p.y, 0.0, &[], &mut data)
+                                .unwrap();
+                            // Without a trace, every choice was a tie
+                            let choices = match &trace {
+                                Some(t) => t.choices(),
+                                None => &ties,
+                            };
+                            shape.material(choices).unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    });
+
+    let mut image = vec![None; size.pow(2)];
+    for (&(x, y), m) in filled.iter().zip(materials) {
+        image[(size - y - 1) * size + x] = m;
+    }
+    image
+}
+
+/// Renders a shape, returning the position of each filled pixel
+///
+/// Positions are in the renderer's coordinates (with Y pointing up).
+fn filled_pixels<I: Family>(
+    shape: Tape<I>,
+    config: &AlignedRenderConfig<2>,
+) -> Vec<(usize, usize)> {
+    let size = config.orig_image_size;
+    let tiles = all_tiles(config);
+    let mut filled = vec![];
+    render_tiles(
+        shape,
+        config,
+        &BitRenderMode,
+        tiles,
+        None,
+        |tile, data, _| {
+            let mut index = 0;
+            for j in 0..config.tile_sizes[0] {
+                let y = j + tile.corner[1];
+                for i in 0..config.tile_sizes[0] {
+                    let x = i + tile.corner[0];
+                    if data[index] && x < size && y < size {
+                        filled.push((x, y));
+                    }
+                    index += 1;
+                }
+            }
+        },
+    );
+    filled
+}
+
+/// Returns every top-level tile in the image
+fn all_tiles(config: &AlignedRenderConfig<2>) -> Vec<Tile<2>> {
     assert!(config.image_size % config.tile_sizes[0] == 0);
     for i in 0..config.tile_sizes.len() - 1 {
         assert!(config.tile_sizes[i] % config.tile_sizes[i + 1] == 0);
     }
 
-    let i_handle = tape.new_interval_evaluator();
     let mut tiles = vec![];
     for i in 0..config.image_size / config.tile_sizes[0] {
         for j in 0..config.image_size / config.tile_sizes[0] {
@@ -458,37 +1405,756 @@ pub fn render<I: Family, M: RenderMode + Sync>(
             ]));
         }
     }
+    tiles
+}
 
+/// Renders the given top-level tiles (in order) with a pool of worker threads
+///
+/// `f` is called on the calling thread as each tile is finished.  If a cache
+/// is provided, then workers read from it during rendering, and new entries
+/// are added once every tile is done.
+#[cfg(not(feature = "rayon"))]
+fn render_tiles<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &AlignedRenderConfig<2>,
+    mode: &M,
+    tiles: Vec<Tile<2>>,
+    mut cache: Option<&mut RenderCache>,
+    mut f: impl FnMut(Tile<2>, &[M::Output], &[FrameRegion]),
+) {
+    span!(
+        INFO,
+        "render2d",
+        size = config.image_size,
+        tiles = tiles.len(),
+        len = tape.len()
+    );
+    let hash = cache.as_ref().map(|_| tape.content_hash());
+    let i_handle = tape.new_interval_evaluator();
     let queue = Queue::new(tiles);
-    let out = std::thread::scope(|s| {
-        let mut handles = vec![];
+    let shared = cache.as_deref();
+    let mut updates = vec![];
+    std::thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
         for _ in 0..config.threads {
             let i = i_handle.clone();
-            handles.push(s.spawn(|| worker::<I, M>(i, &queue, &config, mode)));
+            let tx = tx.clone();
+            let queue = &queue;
+            s.spawn(move || {
+                worker::<I, M>(i, hash, shared, queue, config, mode, tx)
+            });
         }
-        let mut out = vec![];
-        for h in handles {
-            out.extend(h.join().unwrap().into_iter());
+        drop(tx);
+        for (tile, data) in rx {
+            f(tile, &data.pixels, &data.unresolved);
+            updates.push(data.update);
         }
-        out
     });
+    if let Some(cache) = cache.as_mut() {
+        for u in updates {
+            cache.apply(u);
+        }
+    }
+}
+
+/// Renders the given top-level tiles on a new pool of `config.threads`
+/// threads
+///
+/// See [`render_tiles_in_pool`] for details.
+#[cfg(feature = "rayon")]
+fn render_tiles<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &AlignedRenderConfig<2>,
+    mode: &M,
+    tiles: Vec<Tile<2>>,
+    cache: Option<&mut RenderCache>,
+    f: impl FnMut(Tile<2>, &[M::Output], &[FrameRegion]),
+) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .build()
+        .expect("could not build thread pool");
+    render_tiles_in_pool(&pool, tape, config, mode, tiles, cache, f)
+}
+
+/// Renders the given top-level tiles (in order) on a rayon thread pool
+///
+/// Unlike the work queue, this doesn't stream results back: `f` is called on
+/// the calling thread once every tile is finished, in the same order as
+/// `tiles`, so the sequence of callbacks and cache updates is deterministic.
+#[cfg(feature = "rayon")]
+fn render_tiles_in_pool<I: Family, M: RenderMode + Sync>(
+    pool: &rayon::ThreadPool,
+    tape: Tape<I>,
+    config: &AlignedRenderConfig<2>,
+    mode: &M,
+    tiles: Vec<Tile<2>>,
+    mut cache: Option<&mut RenderCache>,
+    mut f: impl FnMut(Tile<2>, &[M::Output], &[FrameRegion]),
+) {
+    use rayon::prelude::*;
+    span!(
+        INFO,
+        "render2d",
+        size = config.image_size,
+        tiles = tiles.len(),
+        len = tape.len()
+    );
+    let hash = cache.as_ref().map(|_| tape.content_hash());
+    let i_handle = tape.new_interval_evaluator();
+    let shared = cache.as_deref();
+    let out: Vec<_> = pool.install(|| {
+        tiles
+            .par_iter()
+            .map_init(
+                || {
+                    let w: Worker<I, M> =
+                        Worker::new(i_handle.tape(), shared, config);
+                    (i_handle.clone(), w)
+                },
+                |(i, w), tile| w.render_tile(i, hash, *tile, mode),
+            )
+            .collect()
+    });
+    let mut updates = vec![];
+    for (tile, data) in tiles.into_iter().zip(out) {
+        f(tile, &data.pixels, &data.unresolved);
+        updates.push(data.update);
+    }
+    if let Some(cache) = cache.as_mut() {
+        for u in updates {
+            cache.apply(u);
+        }
+    }
+}
+
+/// Renders the given tape into a 2D image on a caller-provided thread pool
+///
+/// This is equivalent to [`render`](render()), but ignores
+/// [`RenderConfig::threads`] in favor of the pool, so that applications which
+/// already use rayon can share (or limit) threads.  The output is identical
+/// regardless of the pool's size.
+#[cfg(feature = "rayon")]
+pub fn render_in_pool<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    pool: &rayon::ThreadPool,
+) -> Vec<M::Output> {
+    render_with_pool(tape, config, mode, None, pool)
+}
+
+/// Renders the given tape into a 2D image on a caller-provided thread pool,
+/// using and updating a persistent cache of interval results
+///
+/// This combines [`render_cached`] and [`render_in_pool`], which is useful for
+/// interactive viewers: neither threads nor per-tile simplifications are
+/// rebuilt from scratch on every frame.
+#[cfg(feature = "rayon")]
+pub fn render_cached_in_pool<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    cache: &mut RenderCache,
+    pool: &rayon::ThreadPool,
+) -> Vec<M::Output> {
+    render_with_pool(tape, config, mode, Some(cache), pool)
+}
+
+#[cfg(feature = "rayon")]
+fn render_with_pool<I: Family, M: RenderMode + Sync>(
+    tape: Tape<I>,
+    config: &RenderConfig<2>,
+    mode: &M,
+    cache: Option<&mut RenderCache>,
+    pool: &rayon::ThreadPool,
+) -> Vec<M::Output> {
+    let config = config.align();
+    let tiles = all_tiles(&config);
 
     let mut image = vec![M::Output::default(); config.orig_image_size.pow(2)];
-    for (tile, data) in out.iter() {
-        let mut index = 0;
-        for j in 0..config.tile_sizes[0] {
-            let y = j + tile.corner[1];
-            for i in 0..config.tile_sizes[0] {
-                let x = i + tile.corner[0];
-                if y < config.orig_image_size && x < config.orig_image_size {
-                    let o = (config.orig_image_size - y - 1)
-                        * config.orig_image_size
-                        + x;
-                    image[o] = data[index];
+    render_tiles_in_pool(
+        pool,
+        tape,
+        &config,
+        mode,
+        tiles,
+        cache,
+        |tile, data, _| write_tile(&config, &mut image, tile, data),
+    );
+    image
+}
+
+/// Copies a rendered tile into the output image, clipping to its size
+fn write_tile<T: Copy>(
+    config: &AlignedRenderConfig<2>,
+    image: &mut [T],
+    tile: Tile<2>,
+    data: &[T],
+) {
+    let mut index = 0;
+    for j in 0..config.tile_sizes[0] {
+        let y = j + tile.corner[1];
+        for i in 0..config.tile_sizes[0] {
+            let x = i + tile.corner[0];
+            if y < config.orig_image_size && x < config.orig_image_size {
+                let o = (config.orig_image_size - y - 1)
+                    * config.orig_image_size
+                    + x;
+                image[o] = data[index];
+            }
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, vm};
+
+    #[test]
+    fn test_render_bounded() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x = ctx.sub(x, 0.3).unwrap();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 200,
+            tile_sizes: vec![64, 16, 8],
+            threads: 4,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config, &BitRenderMode);
+
+        // With plenty of time, the image is identical to a normal render
+        let out = render_bounded(
+            tape.clone(),
+            &config,
+            &BitRenderMode,
+            Duration::from_secs(3600),
+        );
+        assert!(out.is_complete());
+        assert_eq!(out.image, expected);
+
+        // With no time, every tile on the circle's edge is unresolved, and
+        // every wrong pixel is within an unresolved region
+        let out = render_bounded(
+            tape.clone(),
+            &config,
+            &BitRenderMode,
+            Duration::ZERO,
+        );
+        assert!(!out.is_complete());
+        let unresolved = |x: usize, y: usize| {
+            out.unresolved.iter().any(|r| {
+                (r.x..r.x + r.width).contains(&x)
+                    && (r.y..r.y + r.height).contains(&y)
+            })
+        };
+        let mut wrong = 0;
+        for y in 0..200 {
+            for x in 0..200 {
+                let i = y * 200 + x;
+                if out.image[i] != expected[i] {
+                    assert!(unresolved(x, y), "bad pixel at {x}, {y}");
+                    wrong += 1;
                 }
-                index += 1;
             }
         }
+        assert!(wrong > 0);
+        for r in &out.unresolved {
+            assert!(r.x + r.width <= 200 && r.y + r.height <= 200);
+            assert_eq!(r.width.max(r.height), 64);
+        }
+
+        // Solid regions are still filled from their intervals
+        assert!(out.image[100 * 200 + 130]);
+        assert!(!out.image[0]);
+    }
+
+    #[test]
+    fn test_render_progressive() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x = ctx.sub(x, 0.3).unwrap();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 200,
+            tile_sizes: vec![64, 16, 8],
+            threads: 4,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config, &BitRenderMode);
+
+        for order in [RefineOrder::Center, RefineOrder::Edges] {
+            let mut progress = vec![];
+            let mut first_diff = None;
+            let out = render_progressive(
+                tape.clone(),
+                &config,
+                &BitRenderMode,
+                order,
+                |image, p| {
+                    assert_eq!(image.len(), 200 * 200);
+                    if p.refined == 0 {
+                        // The preview is blocky, but close to the real image
+                        let diff = image
+                            .iter()
+                            .zip(&expected)
+                            .filter(|(a, b)| a != b)
+                            .count();
+                        assert!(diff > 0 && diff < 200 * 200 / 10, "{diff}");
+                        first_diff = Some(diff);
+                    }
+                    progress.push(p);
+                },
+            );
+            assert_eq!(out, expected);
+            assert!(first_diff.is_some());
+            assert_eq!(progress.len(), 17);
+            assert_eq!(progress[0].refined, 0);
+            assert!(progress.last().unwrap().is_done());
+            assert!(progress[..16].iter().all(|p| !p.is_done()));
+        }
+    }
+
+    #[test]
+    fn test_render_color() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        // Red varies with X, green is constant, blue is out of range
+        let red = ctx.add(x, 0.5).unwrap();
+        let green = ctx.constant(0.5);
+        let blue = ctx.constant(2.0);
+
+        let shape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+        let colors =
+            [red, green, blue].map(|c| ctx.get_tape::<vm::Eval>(c).unwrap());
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![32, 8],
+            threads: 3,
+            ..RenderConfig::default()
+        };
+        let image = render_color(shape.clone(), &colors, &config);
+        let mask = render(shape.clone(), &config, &BitRenderMode);
+        assert_eq!(image.len(), mask.len());
+        for (i, (p, m)) in image.iter().zip(&mask).enumerate() {
+            if *m {
+                let x = (i % 100) as f32 / 50.0 - 1.0;
+                let r = ((x + 0.5).clamp(0.0, 1.0) * 255.0) as u8;
+                assert_eq!(*p, [r, 127, 255, 255], "{i}");
+            } else {
+                assert_eq!(*p, [0; 4]);
+            }
+        }
+
+        // A single color channel
+        let image = render_color(shape, &colors[1..2], &config);
+        assert_eq!(image[50 * 100 + 50], [127, 0, 0, 255]);
+        assert_eq!(image[0], [0; 4]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_render_in_pool() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![32, 8],
+            threads: 3,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config, &SdfRenderMode);
+        for threads in [1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let out =
+                render_in_pool(tape.clone(), &config, &SdfRenderMode, &pool);
+            assert!(out == expected, "mismatch with {threads} threads");
+        }
+    }
+
+    #[test]
+    fn test_render_into() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let y = ctx.add(y, 0.2).unwrap();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![32, 8],
+            threads: 3,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config, &DebugRenderMode);
+
+        // Rows are padded with bytes that must be left alone
+        let stride = 100 * 4 + 12;
+        for granularity in [UpdateGranularity::Tile, UpdateGranularity::Row] {
+            let mut fb = vec![7u8; stride * 100];
+            let mut covered = vec![0; 100 * 100];
+            let mut last_row = None;
+            render_into(
+                tape.clone(),
+                &config,
+                &DebugRenderMode,
+                &mut fb,
+                stride,
+                granularity,
+                |fb, r| {
+                    assert_eq!(fb.len(), stride * 100);
+                    if granularity == UpdateGranularity::Row {
+                        assert_eq!((r.x, r.width), (0, 100));
+                        assert!(last_row.map(|y| y < r.y).unwrap_or(true));
+                        last_row = Some(r.y);
+                    }
+                    for y in r.y..r.y + r.height {
+                        for x in r.x..r.x + r.width {
+                            covered[y * 100 + x] += 1;
+                            let o = y * stride + x * 4;
+                            assert_eq!(
+                                fb[o..o + 4],
+                                expected[y * 100 + x].as_debug_color()
+                            );
+                        }
+                    }
+                },
+            )
+            .unwrap();
+            assert!(covered.iter().all(|c| *c == 1));
+            for row in fb.chunks(stride) {
+                assert!(row[400..].iter().all(|b| *b == 7));
+            }
+        }
+
+        let mut fb = vec![0u8; stride * 99];
+        let r = render_into(
+            tape.clone(),
+            &config,
+            &BitRenderMode,
+            &mut fb,
+            stride,
+            UpdateGranularity::Tile,
+            |_, _| (),
+        );
+        assert!(matches!(r, Err(Error::BadFramebuffer(..))));
+        let r = render_into(
+            tape,
+            &config,
+            &BitRenderMode,
+            &mut fb,
+            100 * 4 - 1,
+            UpdateGranularity::Tile,
+            |_, _| (),
+        );
+        assert!(matches!(r, Err(Error::BadFramebuffer(..))));
+    }
+
+    #[test]
+    fn test_render_coverage() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![32, 8],
+            ..RenderConfig::default()
+        };
+        let mode = CoverageRenderMode::new(&config);
+        assert!((mode.width - 0.02).abs() < 1e-6, "{}", mode.width);
+
+        let image = render(tape.clone(), &config, &mode);
+        let bits = render(tape, &config, &BitRenderMode);
+        let partial = image.iter().filter(|c| **c != 0 && **c != 255).count();
+        assert!(partial > 0);
+        for (c, b) in image.iter().zip(&bits) {
+            // Pixels may only change by half of the ramp from the bitmap
+            if *b {
+                assert!(*c >= 127, "{c}");
+            } else {
+                assert!(*c <= 128, "{c}");
+            }
+        }
+
+        // The total coverage matches the circle's area (in pixels)
+        let area: f32 = image.iter().map(|c| *c as f32 / 255.0).sum();
+        let expected = std::f32::consts::PI * 25.0f32.powi(2);
+        assert!((area - expected).abs() < expected * 0.01, "{area}");
+    }
+
+    #[test]
+    fn test_render_composite() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let mut circle = |dx: f32| {
+            let x = ctx.sub(x, dx).unwrap();
+            let r = ctx.hypot(x, y).unwrap();
+            let c = ctx.sub(r, 0.4).unwrap();
+            ctx.get_tape::<vm::Eval>(c).unwrap()
+        };
+        let left = circle(-0.25);
+        let right = circle(0.25);
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![32, 8],
+            ..RenderConfig::default()
+        };
+        // The red circle is listed second, but drawn underneath
+        let layers = [
+            Layer {
+                tape: left,
+                color: [0, 0, 255, 255],
+                z: 1,
+            },
+            Layer {
+                tape: right,
+                color: [255, 0, 0, 128],
+                z: 0,
+            },
+        ];
+        let image = render_composite(&layers, &config);
+        let pixel = |x: usize, y: usize| image[(99 - y) * 100 + x];
+
+        assert_eq!(pixel(50, 50), [0, 0, 255, 255]); // overlap
+        assert_eq!(pixel(30, 50), [0, 0, 255, 255]); // only blue
+        assert_eq!(pixel(70, 50), [255, 0, 0, 128]); // only red
+        assert_eq!(pixel(2, 2), [0; 4]);
+
+        // Swapping the order puts the translucent red circle on top
+        let mut layers = layers;
+        layers[1].z = 2;
+        let image = render_composite(&layers, &config);
+        let [r, g, b, a] = image[49 * 100 + 50];
+        assert_eq!((g, a), (0, 255));
+        assert!(r.abs_diff(128) <= 1 && b.abs_diff(127) <= 1, "{r} {b}");
+    }
+
+    #[test]
+    fn test_render_materials() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let mut circle = |dx: f32, dy: f32| {
+            let x = ctx.sub(x, dx).unwrap();
+            let y = ctx.sub(y, dy).unwrap();
+            let r = ctx.hypot(x, y).unwrap();
+            ctx.sub(r, 0.3).unwrap()
+        };
+        let a = circle(-0.5, 0.5);
+        let b = circle(0.5, 0.5);
+        let c = circle(0.0, -0.5);
+        ctx.name(a, "a").unwrap();
+        ctx.name(b, "b").unwrap();
+        let ab = ctx.min(a, b).unwrap();
+        let root = ctx.min(ab, c).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(root).unwrap();
+
+        let config = RenderConfig {
+            image_size: 100,
+            tile_sizes: vec![32, 8],
+            threads: 3,
+            ..RenderConfig::default()
+        };
+        let image = render_materials(tape.clone(), &config);
+        let mask = render(tape.clone(), &config, &BitRenderMode);
+        let name = |x: usize, y: usize| {
+            image[(99 - y) * 100 + x].map(|i| tape.names()[i as usize].as_str())
+        };
+        assert_eq!(name(25, 75), Some("a"));
+        assert_eq!(name(75, 75), Some("b"));
+        assert_eq!(name(50, 25), None); // unnamed
+        assert_eq!(name(50, 50), None); // empty
+        for (m, b) in image.iter().zip(&mask) {
+            assert!(m.is_none() || *b);
+        }
+    }
+
+    #[test]
+    fn test_render_affine() {
+        // Correlated terms, which interval arithmetic over-estimates
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let xy = ctx.mul(x, y).unwrap();
+        let a = ctx.sub(xy, x).unwrap();
+        let a = ctx.add(a, y).unwrap();
+        let r = ctx.hypot(x, y).unwrap();
+        let r = ctx.sub(r, 0.7).unwrap();
+        let shape = ctx.max(a, r).unwrap();
+
+        let config = RenderConfig {
+            image_size: 128,
+            tile_sizes: vec![32, 8],
+            threads: 2,
+            ..RenderConfig::default()
+        };
+        let tape = ctx.get_tape::<vm::Eval>(shape).unwrap();
+        let expected = render(tape, &config, &BitRenderMode);
+        assert!(expected.iter().any(|b| *b));
+        let tape = ctx.get_tape::<vm::Affine>(shape).unwrap();
+        let out = render(tape, &config, &BitRenderMode);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_exact_boundaries() {
+        // Two circles, so that tapes are simplified in most tiles
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let a = ctx.sub(x, 0.3).unwrap();
+        let a = ctx.hypot(a, y).unwrap();
+        let a = ctx.sub(a, 0.5).unwrap();
+        let b = ctx.add(x, 0.3).unwrap();
+        let b = ctx.hypot(b, y).unwrap();
+        let b = ctx.sub(b, 0.4).unwrap();
+        let shape = ctx.min(a, b).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(shape).unwrap();
+
+        let config = RenderConfig {
+            image_size: 200,
+            tile_sizes: vec![64, 16, 8],
+            threads: 2,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config, &SdfRenderMode);
+        let config = RenderConfig {
+            exact_boundaries: true,
+            ..config
+        };
+        // The VM evaluator's simplified tapes are exact, so the image is
+        // unchanged
+        let out = render(tape, &config, &SdfRenderMode);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_render_cached() {
+        // Two circles, so that tapes are simplified in most tiles
+        let build = || {
+            let mut ctx = Context::new();
+            let x = ctx.x();
+            let y = ctx.y();
+            let a = ctx.sub(x, 0.3).unwrap();
+            let a = ctx.hypot(a, y).unwrap();
+            let a = ctx.sub(a, 0.5).unwrap();
+            let b = ctx.add(x, 0.3).unwrap();
+            let b = ctx.hypot(b, y).unwrap();
+            let b = ctx.sub(b, 0.4).unwrap();
+            let shape = ctx.min(a, b).unwrap();
+            ctx.get_tape::<vm::Eval>(shape).unwrap()
+        };
+        let tape = build();
+
+        let config = RenderConfig {
+            image_size: 200,
+            tile_sizes: vec![64, 16, 8],
+            threads: 2,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config, &SdfRenderMode);
+
+        let mut cache = RenderCache::new();
+        let out =
+            render_cached(tape.clone(), &config, &SdfRenderMode, &mut cache);
+        assert_eq!(out, expected);
+        let misses = cache.stats().misses;
+        assert_eq!(cache.stats().hits, 0);
+        assert_eq!(cache.len(), misses);
+
+        let out = render_cached(tape, &config, &SdfRenderMode, &mut cache);
+        assert_eq!(out, expected);
+        assert_eq!(cache.stats().hits, misses);
+        assert_eq!(cache.stats().misses, misses);
+
+        // Round-trip through serialization, then render a tape which was
+        // built from scratch (as if the program had been restarted)
+        let mut bytes = vec![];
+        cache.write_to(&mut bytes).unwrap();
+        let mut cache = RenderCache::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(cache.len(), misses);
+        let out = render_cached(build(), &config, &SdfRenderMode, &mut cache);
+        assert_eq!(out, expected);
+        assert_eq!(cache.stats().hits, misses);
+        assert_eq!(cache.stats().misses, 0);
+
+        // A different shape misses every tile
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let tape = ctx.get_tape::<vm::Eval>(x).unwrap();
+        render_cached(tape, &config, &BitRenderMode, &mut cache);
+        assert_eq!(cache.stats().hits, misses);
+        assert!(cache.stats().misses > 0);
+
+        // Invalid data is rejected
+        for bad in [&b"nope"[..], &bytes[..bytes.len() - 1]] {
+            let r = RenderCache::read_from(&mut &bad[..]);
+            assert!(matches!(r, Err(Error::BadRenderCache)));
+        }
+    }
+
+    #[test]
+    fn test_refine_order() {
+        // A shape which only touches the top-right tile
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let x = ctx.sub(x, 0.75).unwrap();
+        let y = ctx.sub(y, 0.75).unwrap();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.2).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(circle).unwrap();
+
+        let config = RenderConfig {
+            image_size: 256,
+            tile_sizes: vec![64, 16, 8],
+            threads: 1,
+            ..RenderConfig::default()
+        };
+        for order in [RefineOrder::Center, RefineOrder::Edges] {
+            let mut images = vec![];
+            render_progressive(
+                tape.clone(),
+                &config,
+                &BitRenderMode,
+                order,
+                |image, _p| images.push(image.to_vec()),
+            );
+            // Refining the center tile doesn't change the (empty) preview,
+            // while refining the tile with edges does.
+            assert_eq!(images[0] != images[1], order == RefineOrder::Edges);
+        }
     }
-    image
 }
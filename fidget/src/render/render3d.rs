@@ -1,14 +1,13 @@
 //! 3D bitmap rendering / rasterization
 use crate::{
     eval::{
-        float_slice::{
-            FloatSliceEval, FloatSliceEvalData, FloatSliceEvalStorage,
-        },
-        grad_slice::{GradSliceEval, GradSliceEvalData, GradSliceEvalStorage},
+        float_slice::{FloatSliceEval, FloatSliceEvalData},
+        grad_slice::{GradSliceEval, GradSliceEvalData},
         interval::{IntervalEval, IntervalEvalData},
-        tape::{Data as TapeData, Tape, Workspace},
+        pool::EvalContext,
+        tape::Tape,
         types::{Grad, Interval},
-        Choice, EvaluatorStorage, Family,
+        Choice, Family,
     },
     render::config::{AlignedRenderConfig, Queue, RenderConfig, Tile},
 };
@@ -118,56 +117,30 @@ struct Worker<'a, I: Family> {
     depth: Vec<u32>,
     color: Vec<[u8; 3]>,
 
-    /// Storage for float slice evaluators
-    ///
-    /// This has `n + 1` slots, where `n` is the number of render levels; one
-    /// level for each round of interval evaluation, and one for the voxel
-    /// stage.  Don't be mislead by the use of `Option` here; slots should
-    /// always be populated except when someone is borrowing them, and borrowers
-    /// must give them back (using the `.give()` extension method)
-    ///
-    /// This means that we'll often use `.take().unwrap()`, to ensure that we
-    /// didn't mess up our invariants.
-    float_storage: Vec<Option<FloatSliceEvalStorage<I>>>,
-
-    /// Equivalent to `float_storage`, for gradient evaluators
-    grad_storage: Vec<Option<GradSliceEvalStorage<I>>>,
-
-    /// Storage for interval evaluators.
-    ///
-    /// This has `n` slots, where `n` is the number of render levels.  The first
-    /// slot is always `None`, because philosophically, it represents the
-    /// storage of `i_handle` (but `i_handle` is shared between multiple
-    /// threads, so we never actually reclaim its storage).
-    interval_storage: Vec<
-        Option<<<I as Family>::IntervalEval as EvaluatorStorage<I>>::Storage>,
-    >,
-
-    /// Spare tapes to avoid allocation churn
-    ///
-    /// This has `n + 1` slots: one for each render level, and one for per-voxel
-    /// evaluation (after the final tape simplification).
-    spare_tapes: Vec<Option<TapeData>>,
+    /// Pool of tapes, choice arrays, and evaluator storage, to avoid
+    /// allocation churn
+    pool: EvalContext<I>,
 
-    /// Reusable workspace for tape simplification, to minimize allocation
-    workspace: Workspace,
+    /// Evaluators for the unsimplified tape, used for pixels on tile
+    /// boundaries if `config.exact_boundaries` is set
+    root: Option<Evaluators<I>>,
 }
 
 impl<I: Family> Worker<'_, I> {
-    fn reclaim_storage(&mut self, eval: Evaluators<I>) -> TapeData {
+    /// Returns a set of evaluators (and their tape) to the pool
+    ///
+    /// Evaluators are released first, since they hold references to the tape.
+    fn release(&mut self, eval: Evaluators<I>) {
         if let Some(float) = eval.float_slice {
-            self.float_storage[eval.level].give(float.take().unwrap());
+            self.pool.release_float_slice_evaluator(float);
         }
         if let Some(interval) = eval.interval {
-            self.interval_storage[eval.level].give(interval.take().unwrap());
+            self.pool.release_interval_evaluator(interval);
         }
         if let Some(grad) = eval.grad {
-            self.grad_storage[eval.level].give(grad.take().unwrap());
+            self.pool.release_grad_slice_evaluator(grad);
         }
-
-        // Use Err to indicate that we have to shorten the tape
-        // (basically a bootleg Either)
-        eval.tape.take().unwrap()
+        self.pool.release_tape(eval.tape);
     }
 
     fn render_tile_recurse(
@@ -215,13 +188,15 @@ impl<I: Family> Worker<'_, I> {
         let y = Interval::new(y_min, y_max);
         let z = Interval::new(z_min, z_max);
 
+        // Skip tiles which are outside the shape's bounds (and therefore empty)
+        if !eval.tape.bounds().intersects(x, y, z) {
+            return sibling;
+        }
+
         let mut data_interval = std::mem::take(&mut self.scratch.data_interval);
         let (i, simplify) = eval
             .interval
-            .get_or_insert_with(|| {
-                let storage = self.interval_storage[eval.level].take().unwrap();
-                eval.tape.new_interval_evaluator_with_storage(storage)
-            })
+            .get_or_insert_with(|| self.pool.new_interval_evaluator(&eval.tape))
             .eval_with(x, y, z, &[], &mut data_interval)
             .unwrap();
 
@@ -244,72 +219,64 @@ impl<I: Family> Worker<'_, I> {
 
         // Calculate a simplified tape, reverting to the parent tape if the
         // simplified tape isn't any shorter.
-        let (mut sub_eval, mut prev_sibling) = if let Some(simplify) =
-            simplify.as_ref()
-        {
-            // See if our previous tape shortening used the exact same set of
-            // choices; in that case, then we can reuse it.
-            //
-            // This is likely because of spatial locality!
-            let res = if let Some((choices, sibling_eval)) = sibling {
-                if choices == simplify.choices() {
-                    Ok((choices, sibling_eval))
-                } else {
-                    // The sibling didn't make the same choices, so we'll tear
-                    // it down for parts and build our own tape here.
-                    //
-                    // Release all of the sibling evaluators, which should free
-                    // up the tape for reuse.
-                    let tape = self.reclaim_storage(sibling_eval);
-
-                    // Use Err to indicate that we have to shorten the tape
-                    // (basically a bootleg Either)
-                    Err((tape, choices))
-                }
-            } else {
-                Err((self.spare_tapes[eval.level].take().unwrap(), vec![]))
-            };
-
-            let out = match res {
-                Ok(out) => Some(out),
-                Err((tape, mut choices)) => {
-                    let sub_tape = simplify
-                        .simplify_with(&mut self.workspace, tape)
-                        .unwrap();
-
-                    if sub_tape.len() < eval.tape.len() {
-                        choices
-                            .resize(simplify.choices().len(), Choice::Unknown);
-                        choices.copy_from_slice(simplify.choices());
-                        Some((
-                            choices,
-                            Evaluators {
-                                level: eval.level + 1,
-                                tape: sub_tape,
-                                interval: None,
-                                float_slice: None,
-                                grad: None,
-                            },
-                        ))
+        let (mut sub_eval, mut prev_sibling) =
+            if let Some(simplify) = simplify.as_ref() {
+                // See if our previous tape shortening used the exact same set of
+                // choices; in that case, then we can reuse it.
+                //
+                // This is likely because of spatial locality!
+                let res = if let Some((choices, sibling_eval)) = sibling {
+                    if choices == simplify.choices() {
+                        Ok((choices, sibling_eval))
                     } else {
-                        // Immediately return the spare tape
+                        // The sibling didn't make the same choices, so we'll tear
+                        // it down for parts and build our own tape here.
                         //
-                        // TODO: reuse choices
-                        self.spare_tapes[eval.level]
-                            .give(sub_tape.take().unwrap());
-
-                        // Alas, the sibling has been consumed, so we can't
-                        // reuse it at all.
-                        None
+                        // Release all of the sibling evaluators, which should free
+                        // up the tape for reuse.
+                        self.release(sibling_eval);
+                        self.pool.release_choices(choices);
+
+                        // Use Err to indicate that we have to shorten the tape
+                        // (basically a bootleg Either)
+                        Err(())
                     }
-                }
+                } else {
+                    Err(())
+                };
+
+                let out = match res {
+                    Ok(out) => Some(out),
+                    Err(()) => {
+                        let sub_tape = self.pool.simplify(simplify).unwrap();
+
+                        if sub_tape.len() < eval.tape.len() {
+                            Some((
+                                self.pool.new_choices(simplify.choices()),
+                                Evaluators {
+                                    level: eval.level + 1,
+                                    tape: sub_tape,
+                                    interval: None,
+                                    float_slice: None,
+                                    grad: None,
+                                },
+                            ))
+                        } else {
+                            // Immediately return the spare tape
+                            self.pool.release_tape(sub_tape);
+
+                            // Alas, the sibling has been consumed, so we can't
+                            // reuse it at all.
+                            None
+                        }
+                    }
+                };
+                (out, None) // prev_sibling is always consumed
+            } else {
+                // If we're not simplifying this tape, then keep the sibling around,
+                // since it's still useful.
+                (None, sibling)
             };
-            (out, None) // prev_sibling is always consumed
-        } else {
-            // If we're not simplifying this tape, then keep the sibling around,
-            // since it's still useful.
-            (None, sibling)
-        };
 
         // Return `data_interval` to our scratch buffer
         self.scratch.data_interval = data_interval;
@@ -369,11 +336,10 @@ impl<I: Family> Worker<'_, I> {
         // we have to recycle it here.
         if let Some((choices, sub)) = sub_eval {
             assert!(prev_sibling.is_none());
-            if let Some((_choices, sibling)) = new_sibling {
+            if let Some((choices, sibling)) = new_sibling {
                 assert!(sibling.level == sub.level + 1);
-                let tape = self.reclaim_storage(sibling);
-                self.spare_tapes[sub.level].give(tape);
-                // TODO: reuse _choices
+                self.release(sibling);
+                self.pool.release_choices(choices);
             }
             // We return our own subtape, which can be a sibling subtape in
             // future calls.
@@ -395,6 +361,31 @@ impl<I: Family> Worker<'_, I> {
         eval: &mut Evaluators<I>,
         tile_size: usize,
         tile: Tile<3>,
+    ) {
+        // Any evaluator below the root level uses a simplified tape, so render
+        // the pixels on the edges of the tile with the unsimplified tape
+        // instead; this means that neighboring tiles agree on depth and
+        // normals along their shared boundaries.
+        if self.config.exact_boundaries && eval.level > 0 {
+            let edge = |i: usize, j: usize| {
+                i == 0 || j == 0 || i + 1 == tile_size || j + 1 == tile_size
+            };
+            let mut root = self.root.take().unwrap();
+            self.render_pixels(&mut root, tile_size, tile, edge);
+            self.root.give(root);
+            self.render_pixels(eval, tile_size, tile, |i, j| !edge(i, j));
+        } else {
+            self.render_pixels(eval, tile_size, tile, |_, _| true);
+        }
+    }
+
+    /// Renders the columns of a tile which are selected by `filter`
+    fn render_pixels<F: Fn(usize, usize) -> bool>(
+        &mut self,
+        eval: &mut Evaluators<I>,
+        tile_size: usize,
+        tile: Tile<3>,
+        filter: F,
     ) {
         // Prepare for pixel-by-pixel evaluation
         let mut index = 0;
@@ -405,6 +396,9 @@ impl<I: Family> Worker<'_, I> {
         for xy in 0..tile_size.pow(2) {
             let i = xy % tile_size;
             let j = xy / tile_size;
+            if !filter(i, j) {
+                continue;
+            }
             let o = self.config.tile_to_offset(tile, i, j);
 
             // Skip pixels which are behind the image
@@ -442,13 +436,15 @@ impl<I: Family> Worker<'_, I> {
             self.scratch.columns.push(xy);
         }
         let size = index;
-        assert!(size > 0);
+        if size == 0 {
+            // Every selected column is already filled
+            return;
+        }
 
         // Reuse the FloatSliceFunc handle passed in, or build one if it
         // wasn't already available (which makes it available to siblings)
         let func = eval.float_slice.get_or_insert_with(|| {
-            let storage = self.float_storage[eval.level].take().unwrap();
-            eval.tape.new_float_slice_evaluator_with_storage(storage)
+            self.pool.new_float_slice_evaluator(&eval.tape)
         });
 
         // Borrow the scratch data, returning it at the end of the function
@@ -508,8 +504,7 @@ impl<I: Family> Worker<'_, I> {
             // Reuse the FloatSliceFunc handle passed in, or build one if it
             // wasn't already available (which makes it available to siblings)
             let func = eval.grad.get_or_insert_with(|| {
-                let storage = self.grad_storage[eval.level].take().unwrap();
-                eval.tape.new_grad_slice_evaluator_with_storage(storage)
+                self.pool.new_grad_slice_evaluator(&eval.tape)
             });
             let mut data_grad = std::mem::take(&mut self.scratch.data_grad);
             let out_grad = self.scratch.eval_g(func, grad, &mut data_grad);
@@ -562,22 +557,20 @@ fn worker<I: Family>(
         color: vec![],
         config,
 
-        // Notice that these are all populated with Some(...)!
-        float_storage: (0..=config.tile_sizes.len())
-            .map(|_| Some(Default::default()))
-            .collect(),
-        grad_storage: (0..=config.tile_sizes.len())
-            .map(|_| Some(Default::default()))
-            .collect(),
-
-        interval_storage: (0..config.tile_sizes.len())
-            .map(|_| Some(Default::default()))
-            .collect(),
-
-        workspace: Workspace::default(),
-        spare_tapes: (0..=config.tile_sizes.len())
-            .map(|_| Some(Default::default()))
-            .collect(),
+        pool: EvalContext::new(),
+
+        // Root evaluators are built up front, since they're used for the
+        // lifetime of the worker
+        root: config.exact_boundaries.then(|| {
+            let tape = i_handle.tape();
+            Evaluators {
+                level: 0,
+                interval: None,
+                float_slice: Some(tape.new_float_slice_evaluator()),
+                grad: Some(tape.new_grad_slice_evaluator()),
+                tape,
+            }
+        }),
     };
 
     // Every thread has a set of tiles assigned to it, which are in Z-sorted
@@ -601,27 +594,23 @@ fn worker<I: Family>(
                 float_slice: None,
                 grad: None,
             };
-            if let Some((_, e)) =
+            if let Some((choices, e)) =
                 w.render_tile_recurse(&mut eval, None, 0, tile)
             {
-                if let Some(i) = e.interval {
-                    w.interval_storage[1].give(i.take().unwrap());
-                }
-                if let Some(f) = e.float_slice {
-                    w.float_storage[1].give(f.take().unwrap());
-                }
-                if let Some(g) = e.grad {
-                    w.grad_storage[1].give(g.take().unwrap());
-                }
-                w.spare_tapes[0].give(e.tape.take().unwrap());
+                w.release(e);
+                w.pool.release_choices(choices);
             }
 
-            // Check our invariants, to make sure that everyone gave back their
-            // storage data when complete.
-            assert!(w.interval_storage.iter().all(Option::is_some));
-            assert!(w.float_storage.iter().all(Option::is_some));
-            assert!(w.grad_storage.iter().all(Option::is_some));
-            assert!(w.spare_tapes.iter().all(Option::is_some));
+            // If the tile was never simplified, then the top-level evaluators
+            // may have borrowed storage for pixel rendering; return it.  The
+            // interval evaluator is shared with other threads, so it stays
+            // where it is.
+            if let Some(f) = eval.float_slice {
+                w.pool.release_float_slice_evaluator(f);
+            }
+            if let Some(g) = eval.grad {
+                w.pool.release_grad_slice_evaluator(g);
+            }
 
             // Steal the tile, replacing it with an empty vec
             let depth = std::mem::take(&mut w.depth);
@@ -655,6 +644,75 @@ pub fn render<I: Family>(
     tape: Tape<I>,
     config: &RenderConfig<3>,
 ) -> (Vec<u32>, Vec<[u8; 3]>) {
+    render_with(tape, config, config.threads, |i_handle, queues, config| {
+        // Special-case for single-threaded operation, to give simpler
+        // backtraces
+        if config.threads == 1 {
+            return worker::<I>(i_handle.clone(), queues, 0, config)
+                .into_iter()
+                .collect();
+        }
+        std::thread::scope(|s| {
+            let mut handles = vec![];
+            // If there are fewer queues than threads (e.g. for tiny images),
+            // then there's no point in spawning the extra threads.
+            for i in 0..config.threads.min(queues.len()) {
+                let handle = i_handle.clone();
+                handles.push(
+                    s.spawn(move || worker::<I>(handle, queues, i, config)),
+                );
+            }
+            let mut out = vec![];
+            for h in handles {
+                out.extend(h.join().unwrap().into_iter());
+            }
+            out
+        })
+    })
+}
+
+/// Renders the given tape into a 3D image on a caller-provided thread pool
+///
+/// This is equivalent to [`render`](render()), but ignores
+/// [`RenderConfig::threads`] in favor of the pool, so that repeated renders
+/// (e.g. in an interactive viewer) don't start new threads every time.
+#[cfg(feature = "rayon")]
+pub fn render_in_pool<I: Family>(
+    tape: Tape<I>,
+    config: &RenderConfig<3>,
+    pool: &rayon::ThreadPool,
+) -> (Vec<u32>, Vec<[u8; 3]>) {
+    use rayon::prelude::*;
+    let threads = pool.current_num_threads();
+    render_with(tape, config, threads, |i_handle, queues, config| {
+        pool.install(|| {
+            (0..threads.min(queues.len()))
+                .into_par_iter()
+                .flat_map_iter(|i| {
+                    worker::<I>(i_handle.clone(), queues, i, config)
+                })
+                .collect()
+        })
+    })
+}
+
+/// Splits the image into per-thread queues of tiles, renders them with `run`,
+/// then assembles the results into depth and color images
+///
+/// `run` is called with (roughly) one queue per worker thread, and should call
+/// [`worker`] on each thread with a different starting queue; workers steal
+/// from other queues once their own is empty.
+fn render_with<I: Family>(
+    tape: Tape<I>,
+    config: &RenderConfig<3>,
+    threads: usize,
+    run: impl FnOnce(
+        &IntervalEval<I>,
+        &[Queue<3>],
+        &AlignedRenderConfig<3>,
+    ) -> Vec<([usize; 2], Image)>,
+) -> (Vec<u32>, Vec<[u8; 3]>) {
+    span!(INFO, "render3d", size = config.image_size, len = tape.len());
     let config = config.align();
     assert!(config.image_size % config.tile_sizes[0] == 0);
     for i in 0..config.tile_sizes.len() - 1 {
@@ -674,35 +732,12 @@ pub fn render<I: Family>(
             }
         }
     }
-    let tiles_per_thread = (tiles.len() / config.threads).max(1);
+    let tiles_per_thread = (tiles.len() / threads.max(1)).max(1);
     let mut tile_queues = vec![];
     for ts in tiles.chunks(tiles_per_thread) {
         tile_queues.push(Queue::new(ts.to_vec()));
     }
-
-    // Special-case for single-threaded operation, to give simpler backtraces
-    let out = if config.threads == 1 {
-        worker::<I>(i_handle, tile_queues.as_slice(), 0, &config)
-            .into_iter()
-            .collect()
-    } else {
-        let config_ref = &config;
-        std::thread::scope(|s| {
-            let mut handles = vec![];
-            let queues = tile_queues.as_slice();
-            for i in 0..config.threads {
-                let handle = i_handle.clone();
-                handles.push(
-                    s.spawn(move || worker::<I>(handle, queues, i, config_ref)),
-                );
-            }
-            let mut out = vec![];
-            for h in handles {
-                out.extend(h.join().unwrap().into_iter());
-            }
-            out
-        })
-    };
+    let out = run(&i_handle, &tile_queues, &config);
 
     let mut image_depth = vec![0; config.orig_image_size.pow(2)];
     let mut image_color = vec![[0; 3]; config.orig_image_size.pow(2)];
@@ -727,3 +762,74 @@ pub fn render<I: Family>(
     }
     (image_depth, image_color)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{context::Context, vm};
+
+    #[test]
+    fn test_exact_boundaries() {
+        // Two spheres, so that tapes are simplified in most tiles
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let mut sphere = |dx: f64, r: f64| {
+            let x = ctx.sub(x, dx).unwrap();
+            let xy = ctx.hypot(x, y).unwrap();
+            let d = ctx.hypot(xy, z).unwrap();
+            ctx.sub(d, r).unwrap()
+        };
+        let a = sphere(0.3, 0.5);
+        let b = sphere(-0.3, 0.4);
+        let shape = ctx.min(a, b).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(shape).unwrap();
+
+        let config = RenderConfig {
+            image_size: 64,
+            tile_sizes: vec![32, 16, 8],
+            threads: 2,
+            ..RenderConfig::default()
+        };
+        let expected = render(tape.clone(), &config);
+        assert!(expected.0.iter().any(|d| *d > 0));
+        let config = RenderConfig {
+            exact_boundaries: true,
+            ..config
+        };
+        // The VM evaluator's simplified tapes are exact, so the image is
+        // unchanged
+        assert_eq!(render(tape, &config), expected);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_render_in_pool() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let xy = ctx.hypot(x, y).unwrap();
+        let d = ctx.hypot(xy, z).unwrap();
+        let sphere = ctx.sub(d, 0.5).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(sphere).unwrap();
+
+        let config = RenderConfig {
+            image_size: 64,
+            tile_sizes: vec![32, 16, 8],
+            threads: 2,
+            ..RenderConfig::default()
+        };
+        let (expected, _) = render(tape.clone(), &config);
+        assert!(expected.iter().any(|d| *d > 0));
+        for threads in [1, 3] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let (depth, _) = render_in_pool(tape.clone(), &config, &pool);
+            assert!(depth == expected, "mismatch with {threads} threads");
+        }
+    }
+}
@@ -77,6 +77,8 @@ impl Engine {
         engine.register_fn("__var_y", var_y);
         engine.register_fn("__draw", draw);
         engine.register_fn("__draw_rgb", draw_rgb);
+        engine.register_fn("noise3", noise3);
+        engine.register_fn("clamp", clamp);
 
         macro_rules! register_binary_fns {
             ($op:literal, $name:ident, $engine:ident) => {
@@ -99,8 +101,15 @@ impl Engine {
         register_binary_fns!("/", div, engine);
         register_binary_fns!("min", min, engine);
         register_binary_fns!("max", max, engine);
+        register_binary_fns!("min_nc", min_nc, engine);
+        register_binary_fns!("max_nc", max_nc, engine);
+        register_binary_fns!("atan2", atan2, engine);
+        register_binary_fns!("hypot", hypot, engine);
+        register_binary_fns!("mod", modulo, engine);
         register_unary_fns!("sqrt", sqrt, engine);
         register_unary_fns!("square", square, engine);
+        register_unary_fns!("sin", sin, engine);
+        register_unary_fns!("cos", cos, engine);
         register_unary_fns!("-", neg, engine);
 
         engine.set_fast_operators(false);
@@ -256,6 +265,53 @@ fn draw_rgb(ctx: rhai::NativeCallContext, node: Node, r: f64, g: f64, b: f64) {
     });
 }
 
+/// Builds gradient noise, where each coordinate is a node or a number
+fn noise3(
+    ctx: rhai::NativeCallContext,
+    x: rhai::Dynamic,
+    y: rhai::Dynamic,
+    z: rhai::Dynamic,
+    seed: i64,
+) -> Result<Node, Box<rhai::EvalAltResult>> {
+    let seed = u16::try_from(seed).map_err(|_| {
+        format!("noise seed must be in the range 0-{}", u16::MAX)
+    })?;
+    let x = to_node(&ctx, x, "noise")?;
+    let y = to_node(&ctx, y, "noise")?;
+    let z = to_node(&ctx, z, "noise")?;
+    Ok(ctx.with_fidget_context(|c| c.noise3(x, y, z, seed).unwrap()))
+}
+
+/// Clamps a value to a range, where each argument is a node or a number
+fn clamp(
+    ctx: rhai::NativeCallContext,
+    x: rhai::Dynamic,
+    lo: rhai::Dynamic,
+    hi: rhai::Dynamic,
+) -> Result<Node, Box<rhai::EvalAltResult>> {
+    let x = to_node(&ctx, x, "clamp")?;
+    let lo = to_node(&ctx, lo, "clamp")?;
+    let hi = to_node(&ctx, hi, "clamp")?;
+    Ok(ctx.with_fidget_context(|c| c.clamp(x, lo, hi).unwrap()))
+}
+
+/// Converts a node or number into a node, for use as an argument to `op`
+fn to_node(
+    ctx: &rhai::NativeCallContext,
+    v: rhai::Dynamic,
+    op: &str,
+) -> Result<Node, String> {
+    if let Some(n) = v.clone().try_cast::<Node>() {
+        Ok(n)
+    } else if let Ok(f) = v.as_float() {
+        Ok(ctx.with_fidget_context(|c| c.constant(f)))
+    } else if let Ok(i) = v.as_int() {
+        Ok(ctx.with_fidget_context(|c| c.constant(i as f64)))
+    } else {
+        Err(format!("invalid {op} argument of type {}", v.type_name()))
+    }
+}
+
 macro_rules! define_binary_fns {
     ($name:ident) => {
         mod $name {
@@ -310,8 +366,15 @@ define_binary_fns!(mul);
 define_binary_fns!(div);
 define_binary_fns!(min);
 define_binary_fns!(max);
+define_binary_fns!(min_nc);
+define_binary_fns!(max_nc);
+define_binary_fns!(atan2);
+define_binary_fns!(hypot);
+define_binary_fns!(modulo);
 define_unary_fns!(sqrt);
 define_unary_fns!(square);
+define_unary_fns!(sin);
+define_unary_fns!(cos);
 define_unary_fns!(neg);
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -341,6 +404,30 @@ mod test {
         let (sum, ctx) = engine.eval("x + y").unwrap();
         assert_eq!(ctx.eval_xyz(sum, 1.0, 2.0, 0.0).unwrap(), 3.0);
     }
+
+    #[test]
+    fn test_noise() {
+        let mut engine = Engine::new();
+        let (n, ctx) = engine.eval("noise3(x, y, 0.5, 3)").unwrap();
+        assert_eq!(
+            ctx.eval_xyz(n, 1.25, 2.5, 0.0).unwrap(),
+            crate::eval::noise::noise3(1.25, 2.5, 0.5, 3) as f64
+        );
+
+        assert!(engine.eval("noise3(x, y, z, -1)").is_err());
+        assert!(engine.eval("noise3(x, \"y\", z, 1)").is_err());
+    }
+
+    #[test]
+    fn test_clamp() {
+        let mut engine = Engine::new();
+        let (n, ctx) = engine.eval("clamp(x, -1, y)").unwrap();
+        assert_eq!(ctx.eval_xyz(n, 0.5, 2.0, 0.0).unwrap(), 0.5);
+        assert_eq!(ctx.eval_xyz(n, 3.0, 2.0, 0.0).unwrap(), 2.0);
+        assert_eq!(ctx.eval_xyz(n, -3.0, 2.0, 0.0).unwrap(), -1.0);
+
+        assert!(engine.eval("clamp(x, \"y\", z)").is_err());
+    }
 }
 
 pub mod core;
@@ -0,0 +1,257 @@
+//! Self-contained shape handles
+//!
+//! A [`Shape`] bundles a [`Context`] with a root [`Node`], so that it can be
+//! passed around, combined, and rendered without keeping track of which
+//! context its nodes belong to:
+//!
+//! ```
+//! use fidget::{context::Context, shape::Shape};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let r = ctx.hypot(x, y)?;
+//! let circle = ctx.sub(r, 1.0)?;
+//! let circle = Shape::new(ctx, circle)?;
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let half = ctx.sub(x, 0.5)?;
+//! let half = Shape::new(ctx, half)?;
+//!
+//! // Shapes from different contexts can be combined with CSG operators
+//! let bite = &circle - &half;
+//! assert_eq!(bite.eval(0.0, 0.0, 0.0)?, 0.5);
+//! assert_eq!(bite.eval(0.75, 0.0, 0.0)?, -0.25);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+//!
+//! Shapes are immutable; CSG operators build a new shape, reusing the
+//! left-hand context if it isn't shared.  The lower-level [`Context`] API
+//! remains available through [`Shape::context`] and [`Shape::node`].
+use crate::{
+    context::{Context, IntoNode, Node},
+    eval::{Family, Tape},
+    Error,
+};
+use alloc::sync::Arc;
+
+/// A shape, represented as a root node and the context that owns it
+///
+/// See the [module-level documentation](crate::shape) for details.
+#[derive(Clone, Debug)]
+pub struct Shape {
+    ctx: Arc<Context>,
+    root: Node,
+}
+
+impl Shape {
+    /// Builds a new shape from a context and root node
+    ///
+    /// Returns [`Error::BadNode`] if the node is not present in the context.
+    pub fn new(ctx: Context, root: Node) -> Result<Self, Error> {
+        ctx.get_op(root).ok_or(Error::BadNode)?;
+        Ok(Self {
+            ctx: Arc::new(ctx),
+            root,
+        })
+    }
+
+    /// Builds a new shape from an expression [`Tree`](crate::context::Tree)
+    ///
+    /// ```
+    /// use fidget::{context::Tree, shape::Shape};
+    ///
+    /// let (x, y, _) = Tree::axes();
+    /// let circle = Shape::from_tree(&(x.hypot(y) - 1.0))?;
+    /// assert_eq!(circle.eval(2.0, 0.0, 0.0)?, 1.0);
+    /// # Ok::<(), fidget::Error>(())
+    /// ```
+    pub fn from_tree(tree: &crate::context::Tree) -> Result<Self, Error> {
+        let mut ctx = Context::new();
+        let root = tree.into_node(&mut ctx)?;
+        Self::new(ctx, root)
+    }
+
+    /// Returns the context which owns this shape's nodes
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// Returns this shape's root node, which is valid in
+    /// [`self.context()`](Self::context)
+    pub fn node(&self) -> Node {
+        self.root
+    }
+
+    /// Builds a tape for the given evaluator family
+    pub fn tape<F: Family>(&self) -> Result<Tape<F>, Error> {
+        self.ctx.get_tape(self.root)
+    }
+
+    /// Evaluates the shape at a single point, using the interpreter
+    ///
+    /// This builds a new tape on every call; to evaluate many points, build a
+    /// [`Tape`] with [`Shape::tape`] and use one of its evaluators instead.
+    ///
+    /// Returns [`Error::BadVarSlice`] if the shape uses variables other than
+    /// X, Y, and Z.
+    pub fn eval(&self, x: f32, y: f32, z: f32) -> Result<f32, Error> {
+        let tape = self.tape::<crate::vm::Eval>()?;
+        let (out, _) = tape.new_point_evaluator().eval(x, y, z, &[])?;
+        Ok(out)
+    }
+
+    /// Renders the shape in 2D, using the fastest available backend
+    ///
+    /// See [`render2d`](crate::render::render2d()) for details.
+    #[cfg(feature = "render")]
+    pub fn render<M: crate::render::RenderMode + Sync>(
+        &self,
+        config: &crate::render::RenderConfig<2>,
+        mode: &M,
+    ) -> Result<alloc::vec::Vec<M::Output>, Error> {
+        use crate::{eval::dynamic::Backend, render::render2d};
+        Ok(match Backend::auto() {
+            Backend::Vm => {
+                render2d(self.tape::<crate::vm::Eval>()?, config, mode)
+            }
+            #[cfg(feature = "jit")]
+            Backend::Jit => {
+                render2d(self.tape::<crate::jit::Eval>()?, config, mode)
+            }
+        })
+    }
+
+    /// Builds a mesh of the shape, using the fastest available backend
+    ///
+    /// This builds an [`Octree`](crate::mesh::Octree) with the given settings,
+    /// then meshes it with
+    /// [`Octree::walk_dual`](crate::mesh::Octree::walk_dual).
+    #[cfg(feature = "mesh")]
+    pub fn mesh(
+        &self,
+        settings: crate::mesh::Settings,
+    ) -> Result<crate::mesh::Mesh, Error> {
+        use crate::{eval::dynamic::Backend, mesh::Octree};
+        Ok(match Backend::auto() {
+            Backend::Vm => {
+                Octree::build(&self.tape::<crate::vm::Eval>()?, settings)
+                    .walk_dual(settings)
+            }
+            #[cfg(feature = "jit")]
+            Backend::Jit => {
+                Octree::build(&self.tape::<crate::jit::Eval>()?, settings)
+                    .walk_dual(settings)
+            }
+        })
+    }
+
+    /// Builds a new shape by applying `f` to this shape and `rhs`
+    ///
+    /// # Panics
+    /// If `f` fails, or if the shapes can't be merged into a single context
+    /// (e.g. because they use too many custom operations)
+    fn combine(
+        self,
+        rhs: &Shape,
+        f: fn(&mut Context, Node, Node) -> Result<Node, Error>,
+    ) -> Shape {
+        let (mut ctx, a) = match Arc::try_unwrap(self.ctx) {
+            Ok(ctx) => (ctx, self.root),
+            Err(shared) => {
+                let mut ctx = Context::new();
+                let a = ctx.import(&shared, self.root).unwrap();
+                (ctx, a)
+            }
+        };
+        let b = ctx.import(&rhs.ctx, rhs.root).unwrap();
+        let root = f(&mut ctx, a, b).unwrap();
+        Shape {
+            ctx: Arc::new(ctx),
+            root,
+        }
+    }
+}
+
+fn difference(ctx: &mut Context, a: Node, b: Node) -> Result<Node, Error> {
+    let b = ctx.neg(b)?;
+    ctx.max(a, b)
+}
+
+macro_rules! impl_csg {
+    ($op:ident, $base_fn:ident, $f:expr) => {
+        impl core::ops::$op<Shape> for Shape {
+            type Output = Shape;
+
+            fn $base_fn(self, rhs: Shape) -> Shape {
+                self.combine(&rhs, $f)
+            }
+        }
+        impl core::ops::$op<&Shape> for Shape {
+            type Output = Shape;
+
+            fn $base_fn(self, rhs: &Shape) -> Shape {
+                self.combine(rhs, $f)
+            }
+        }
+        impl core::ops::$op<&Shape> for &Shape {
+            type Output = Shape;
+
+            fn $base_fn(self, rhs: &Shape) -> Shape {
+                self.clone().combine(rhs, $f)
+            }
+        }
+    };
+}
+
+// Union
+impl_csg!(Add, add, Context::min);
+// Difference
+impl_csg!(Sub, sub, difference);
+// Intersection
+impl_csg!(BitAnd, bitand, Context::max);
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn axis(f: fn(&mut Context) -> Node) -> Shape {
+        let mut ctx = Context::new();
+        let n = f(&mut ctx);
+        Shape::new(ctx, n).unwrap()
+    }
+
+    #[test]
+    fn test_shape_csg() {
+        let x = axis(Context::x);
+        let y = axis(Context::y);
+
+        let u = &x + &y;
+        assert_eq!(u.eval(1.0, 2.0, 0.0).unwrap(), 1.0);
+        let i = &x & &y;
+        assert_eq!(i.eval(1.0, 2.0, 0.0).unwrap(), 2.0);
+        let d = &x - &y;
+        assert_eq!(d.eval(1.0, 2.0, 0.0).unwrap(), 1.0);
+        assert_eq!(d.eval(1.0, -2.0, 0.0).unwrap(), 2.0);
+
+        // The operands are untouched
+        assert_eq!(x.eval(1.0, 2.0, 0.0).unwrap(), 1.0);
+        assert_eq!(y.eval(1.0, 2.0, 0.0).unwrap(), 2.0);
+
+        // Combining a shape with itself deduplicates its nodes
+        let n = x.context().len();
+        let xx = x.clone() + &x;
+        assert_eq!(xx.context().len(), n);
+        assert_eq!(xx.eval(3.0, 0.0, 0.0).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_shape_bad_node() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        assert!(matches!(Shape::new(Context::new(), x), Err(Error::BadNode)));
+    }
+}
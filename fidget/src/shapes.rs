@@ -0,0 +1,1721 @@
+//! Builders which turn 2D shapes into 3D shapes
+//!
+//! A 2D shape is a distance field in the XY plane, e.g. from
+//! [`fidget::svg`](crate::svg) or [`fidget::text`](crate::text).  Any
+//! dependence on Z is removed by evaluating the shape in the `Z = 0` plane.
+//!
+//! If the input is an exact distance field, then so is the output.
+//!
+//! In addition, [`displace`] roughens a (2D or 3D) shape's surface with
+//! gradient noise, and [`offset`] grows or shrinks a shape.
+//!
+//! [`smooth_union`], [`smooth_intersection`], and [`smooth_difference`]
+//! combine shapes with a fillet where they meet, and [`morph`] interpolates
+//! between two shapes.
+//!
+//! [`mirror`], [`linear_array`], and [`radial_array`] repeat a (2D or 3D)
+//! shape by remapping coordinates, so that evaluating many copies costs the
+//! same as evaluating one.
+//!
+//! [`rounded_box`], [`capsule`], [`capped_cone`], and [`torus_segment`] build
+//! exact distance fields for common primitives, positioned by their
+//! parameters.
+//!
+//! For 3D printing, [`shell`] hollows out a shape, and [`infill`] fills the
+//! hollow with a lattice, e.g. [`gyroid`], [`grid`], or [`honeycomb`].
+//! Lattices built from [triply periodic minimal surfaces](tpms) may vary in
+//! wall thickness (see [`ramp`]) and can be cut to shape with [`trim`].
+//!
+//! ```
+//! use fidget::{context::Context, shapes};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let x2 = ctx.square(x)?;
+//! let y2 = ctx.square(y)?;
+//! let r = ctx.add(x2, y2)?;
+//! let r = ctx.sqrt(r)?;
+//! let circle = ctx.sub(r, 1.0)?;
+//!
+//! // A cylinder with radius 1, from Z = 0 to Z = 2
+//! let cylinder = shapes::extrude(&mut ctx, circle, 2.0)?;
+//! assert_eq!(ctx.eval_xyz(cylinder, 0.0, 0.0, 1.5)?, -0.5);
+//! assert_eq!(ctx.eval_xyz(cylinder, 0.0, 0.0, 3.0)?, 1.0);
+//! assert_eq!(ctx.eval_xyz(cylinder, 4.0, 0.0, 1.0)?, 3.0);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{BoundingBox, Context, IntoNode, Node},
+    validate::{check_distance_field, Settings},
+    vm, Error,
+};
+use alloc::vec;
+#[cfg(not(any(test, feature = "std")))]
+use num_traits::Float;
+
+/// Coordinate axis, used by [`revolve`] and the symmetry builders
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Axis {
+    /// The X axis; [`revolve`] sweeps the `Y ≥ 0` half-plane around it
+    X,
+    /// The Y axis; [`revolve`] sweeps the `X ≥ 0` half-plane around it
+    Y,
+    /// The Z axis; [`revolve`] sweeps the `X ≥ 0` half-plane around it, with
+    /// the 2D shape's Y axis becoming the Z axis
+    Z,
+}
+
+/// Extrudes a 2D shape along the Z axis, from `Z = 0` to `Z = height`
+///
+/// Returns [`Error::BadNode`] if `shape` is not valid in this context.
+pub fn extrude(
+    ctx: &mut Context,
+    shape: Node,
+    height: f64,
+) -> Result<Node, Error> {
+    let flat = flatten(ctx, shape)?;
+
+    // Signed distance to the Z slab
+    let half = height / 2.0;
+    let z = ctx.z();
+    let dz = ctx.sub(z, half)?;
+    let dz = ctx.abs(dz)?;
+    let dz = ctx.sub(dz, half)?;
+
+    // Exterior distance, if either axis is outside
+    let ox = ctx.max(flat, 0.0)?;
+    let oz = ctx.max(dz, 0.0)?;
+    let ox = ctx.square(ox)?;
+    let oz = ctx.square(oz)?;
+    let outside = ctx.add(ox, oz)?;
+    let outside = ctx.sqrt(outside)?;
+
+    // Interior distance, if both axes are inside
+    let inside = ctx.max(flat, dz)?;
+    let inside = ctx.min(inside, 0.0)?;
+
+    ctx.add(outside, inside)
+}
+
+/// Revolves a 2D shape around an axis in the XY plane
+///
+/// Only the half-plane on the positive side of the axis is swept, so the
+/// result is symmetric about the axis (e.g. a circle centered at `(2, 0)`
+/// revolved around [`Axis::Y`] produces a torus).
+///
+/// Returns [`Error::BadNode`] if `shape` is not valid in this context.
+pub fn revolve(
+    ctx: &mut Context,
+    shape: Node,
+    axis: Axis,
+) -> Result<Node, Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+    let zero = ctx.constant(0.0);
+
+    // Radial distance from the axis
+    let (u, v) = match axis {
+        Axis::X => (y, z),
+        Axis::Y => (x, z),
+        Axis::Z => (x, y),
+    };
+    let u2 = ctx.square(u)?;
+    let v2 = ctx.square(v)?;
+    let r = ctx.add(u2, v2)?;
+    let r = ctx.sqrt(r)?;
+
+    let xyz = match axis {
+        Axis::X => [x, r, zero],
+        Axis::Y => [r, y, zero],
+        Axis::Z => [r, z, zero],
+    };
+    ctx.remap_xyz(shape, xyz)
+}
+
+/// Mirrors a shape across the plane through the origin which is perpendicular
+/// to the given axis
+///
+/// The part of the shape on the positive side of the plane is kept, and the
+/// part on the negative side is replaced by its reflection.  If the input is
+/// an exact distance field which doesn't cross the plane, then so is the
+/// output.
+///
+/// Returns [`Error::BadNode`] if `shape` is not valid in this context.
+pub fn mirror(
+    ctx: &mut Context,
+    shape: Node,
+    axis: Axis,
+) -> Result<Node, Error> {
+    let mut xyz = [ctx.x(), ctx.y(), ctx.z()];
+    let i = axis as usize;
+    xyz[i] = ctx.abs(xyz[i])?;
+    ctx.remap_xyz(shape, xyz)
+}
+
+/// Repeats a shape `count` times along an axis, `spacing` units apart
+///
+/// Copies are placed at `k * spacing` for `k` in `0..count`, so the first
+/// copy is the original shape.  Rather than building a union of every copy,
+/// each point is mapped into the nearest copy's frame, so the output is as
+/// cheap to evaluate as a single copy.
+///
+/// If the input is an exact distance field which lies within `spacing / 2`
+/// of the origin along the axis, then the output is also exact.
+///
+/// Returns [`Error::EmptyArray`] if `count` is zero, or [`Error::BadNode`] if
+/// `shape` is not valid in this context.
+pub fn linear_array(
+    ctx: &mut Context,
+    shape: Node,
+    axis: Axis,
+    count: usize,
+    spacing: f64,
+) -> Result<Node, Error> {
+    if count == 0 {
+        return Err(Error::EmptyArray);
+    }
+    let mut xyz = [ctx.x(), ctx.y(), ctx.z()];
+    let i = axis as usize;
+    let c = xyz[i];
+
+    // Position of the nearest copy, clamped to the ends of the array
+    let end = spacing * (count - 1) as f64;
+    let wrapped = wrap(ctx, c, spacing)?;
+    let nearest = ctx.sub(c, wrapped)?;
+    let nearest = ctx.clamp(nearest, end.min(0.0), end.max(0.0))?;
+
+    xyz[i] = ctx.sub(c, nearest)?;
+    ctx.remap_xyz(shape, xyz)
+}
+
+/// Repeats a shape `count` times around an axis, at evenly spaced angles
+///
+/// Copies are rotated by multiples of `2π / count` (counter-clockwise when
+/// looking down the axis), so the first copy is the original shape.  As with
+/// [`linear_array`], each point is mapped into the nearest copy's frame, so
+/// the output is as cheap to evaluate as a single copy.
+///
+/// If the input is an exact distance field which lies within the wedge of
+/// angles `±π / count` around the positive X (for [`Axis::Z`]), Y (for
+/// [`Axis::X`]), or Z (for [`Axis::Y`]) axis, then the output is also exact.
+///
+/// ```
+/// # use fidget::{context::Context, shapes::{self, Axis}};
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let dx = ctx.sub(x, 2.0)?;
+/// let r = ctx.hypot(dx, y)?;
+/// let circle = ctx.sub(r, 0.5)?;
+///
+/// // Six circles, evenly spaced on a ring of radius 2
+/// let ring = shapes::radial_array(&mut ctx, circle, Axis::Z, 6)?;
+/// let v = ctx.eval_xyz(ring, -2.0, 0.0, 0.0)?;
+/// assert!((v - -0.5).abs() < 1e-6);
+/// let v = ctx.eval_xyz(ring, 0.0, 2.0, 0.0)?;
+/// assert!(v > 0.0); // between two copies
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::EmptyArray`] if `count` is zero, or [`Error::BadNode`] if
+/// `shape` is not valid in this context.
+pub fn radial_array(
+    ctx: &mut Context,
+    shape: Node,
+    axis: Axis,
+    count: usize,
+) -> Result<Node, Error> {
+    if count == 0 {
+        return Err(Error::EmptyArray);
+    }
+    let mut xyz = [ctx.x(), ctx.y(), ctx.z()];
+
+    // Indices of the right-handed coordinate plane around the axis
+    let (i, j) = match axis {
+        Axis::X => (1, 2),
+        Axis::Y => (2, 0),
+        Axis::Z => (0, 1),
+    };
+    let (u, v) = (xyz[i], xyz[j]);
+    let r = ctx.hypot(u, v)?;
+    let angle = ctx.atan2(v, u)?;
+    let step = core::f64::consts::TAU / count as f64;
+    let angle = wrap(ctx, angle, step)?;
+
+    let cos = ctx.cos(angle)?;
+    let sin = ctx.sin(angle)?;
+    xyz[i] = ctx.mul(r, cos)?;
+    xyz[j] = ctx.mul(r, sin)?;
+    ctx.remap_xyz(shape, xyz)
+}
+
+/// Builds a box with rounded edges and corners
+///
+/// The box is centered at `center`, and extends by `half_size` from the center
+/// along each axis; its edges and corners are rounded with the given `radius`,
+/// so a radius of zero builds a sharp box.  The output is an exact distance
+/// field.
+///
+/// ```
+/// # use fidget::{context::Context, shapes};
+/// let mut ctx = Context::new();
+/// let b = shapes::rounded_box(&mut ctx, [0.0; 3], [2.0, 1.0, 1.0], 0.5)?;
+/// assert_eq!(ctx.eval_xyz(b, 3.0, 0.0, 0.0)?, 1.0);
+/// assert_eq!(ctx.eval_xyz(b, 0.0, 0.0, 0.0)?, -1.0);
+///
+/// // The corner is rounded, so it's further away than for a sharp box
+/// let v = ctx.eval_xyz(b, 2.0, 1.0, 0.0)?;
+/// assert!((v - (0.5 * 2f64.sqrt() - 0.5)).abs() < 1e-12);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadDimensions`] if `radius` is negative or larger than the
+/// smallest half-size.
+pub fn rounded_box(
+    ctx: &mut Context,
+    center: [f64; 3],
+    half_size: [f64; 3],
+    radius: f64,
+) -> Result<Node, Error> {
+    if !(radius >= 0.0 && half_size.iter().all(|&h| h >= radius)) {
+        return Err(Error::BadDimensions);
+    }
+    let p = relative(ctx, center)?;
+
+    // Per-axis signed distance to the inner (unrounded) box
+    let mut q = vec![];
+    for (p, h) in p.into_iter().zip(half_size) {
+        let d = ctx.abs(p)?;
+        q.push(ctx.sub(d, h - radius)?);
+    }
+
+    // Exterior distance, if any axis is outside
+    let mut outside = ctx.constant(0.0);
+    for &d in &q {
+        let d = ctx.max(d, 0.0)?;
+        let d = ctx.square(d)?;
+        outside = ctx.add(outside, d)?;
+    }
+    let outside = ctx.sqrt(outside)?;
+
+    // Interior distance, if every axis is inside
+    let inside = ctx.max_many(q)?;
+    let inside = ctx.min(inside, 0.0)?;
+
+    let d = ctx.add(outside, inside)?;
+    ctx.sub(d, radius)
+}
+
+/// Builds a capsule, i.e. a cylinder with hemispherical ends
+///
+/// The capsule's axis runs from `a` to `b`, and every point within `radius` of
+/// that line segment is inside.  If `a` and `b` are equal, the result is a
+/// sphere.  The output is an exact distance field.
+///
+/// Returns [`Error::BadDimensions`] if `radius` is negative.
+pub fn capsule(
+    ctx: &mut Context,
+    a: [f64; 3],
+    b: [f64; 3],
+    radius: f64,
+) -> Result<Node, Error> {
+    if radius.is_nan() || radius < 0.0 {
+        return Err(Error::BadDimensions);
+    }
+    let pa = relative(ctx, a)?;
+    let ba = [0, 1, 2].map(|i| b[i] - a[i]);
+    let l2 = ba.iter().map(|v| v * v).sum::<f64>();
+
+    // Position of the nearest point along the segment, from 0 to 1
+    let h = if l2 > 0.0 {
+        let mut h = ctx.constant(0.0);
+        for (p, v) in pa.into_iter().zip(ba) {
+            let t = ctx.mul(p, v / l2)?;
+            h = ctx.add(h, t)?;
+        }
+        ctx.clamp(h, 0.0, 1.0)?
+    } else {
+        ctx.constant(0.0)
+    };
+
+    let mut d = vec![];
+    for (p, v) in pa.into_iter().zip(ba) {
+        let t = ctx.mul(h, v)?;
+        d.push(ctx.sub(p, t)?);
+    }
+    let r = ctx.hypot(d[0], d[1])?;
+    let r = ctx.hypot(r, d[2])?;
+    ctx.sub(r, radius)
+}
+
+/// Builds a capped cone, i.e. a cone truncated by two flat ends
+///
+/// The cone's axis runs from `a` to `b`; its radius is `ra` at `a` and `rb`
+/// at `b`.  Either radius may be zero, for a pointed cone, and equal radii
+/// build a cylinder.  The output is an exact distance field.
+///
+/// ```
+/// # use fidget::{context::Context, shapes};
+/// let mut ctx = Context::new();
+/// let cone =
+///     shapes::capped_cone(&mut ctx, [0.0; 3], [0.0, 0.0, 2.0], 1.0, 0.0)?;
+/// assert_eq!(ctx.eval_xyz(cone, 0.0, 0.0, -1.0)?, 1.0);
+/// assert_eq!(ctx.eval_xyz(cone, 0.0, 0.0, 3.0)?, 1.0);
+///
+/// // Past the rim, the nearest point is on its edge
+/// assert_eq!(ctx.eval_xyz(cone, 4.0, 0.0, -4.0)?, 5.0);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadDimensions`] if `a` and `b` are equal or either radius
+/// is negative.
+pub fn capped_cone(
+    ctx: &mut Context,
+    a: [f64; 3],
+    b: [f64; 3],
+    ra: f64,
+    rb: f64,
+) -> Result<Node, Error> {
+    let ba = [0, 1, 2].map(|i| b[i] - a[i]);
+    let len = ba.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if !(len > 0.0 && ra >= 0.0 && rb >= 0.0) {
+        return Err(Error::BadDimensions);
+    }
+    let pa = relative(ctx, a)?;
+
+    // Position along the axis (y) and distance from it (x), which reduces
+    // this to a 2D problem: a trapezoid from (0, 0) to (ra, 0) to (rb, len)
+    // to (0, len), revolved around the y axis.
+    let mut y = ctx.constant(0.0);
+    let mut pa2 = ctx.constant(0.0);
+    for (p, v) in pa.into_iter().zip(ba) {
+        let t = ctx.mul(p, v / len)?;
+        y = ctx.add(y, t)?;
+        let p2 = ctx.square(p)?;
+        pa2 = ctx.add(pa2, p2)?;
+    }
+    let y2 = ctx.square(y)?;
+    let x2 = ctx.sub(pa2, y2)?;
+    let x2 = ctx.max_nc(x2, 0.0)?; // guard against rounding below zero
+    let x = ctx.sqrt(x2)?;
+
+    // Squared distances to the end caps
+    let mut caps = vec![];
+    for (r, h) in [(ra, 0.0), (rb, len)] {
+        let dx = ctx.sub(x, r)?;
+        let dx = ctx.max(dx, 0.0)?;
+        let dx = ctx.square(dx)?;
+        let dy = ctx.sub(y, h)?;
+        let dy = ctx.square(dy)?;
+        caps.push(ctx.add(dx, dy)?);
+    }
+
+    // Squared distance to the side, from (ra, 0) to (rb, len)
+    let (sx, sy) = (rb - ra, len);
+    let s2 = sx * sx + sy * sy;
+    let xa = ctx.sub(x, ra)?;
+    let hx = ctx.mul(xa, sx / s2)?;
+    let hy = ctx.mul(y, sy / s2)?;
+    let h = ctx.add(hx, hy)?;
+    let h = ctx.clamp(h, 0.0, 1.0)?;
+    let dx = ctx.mul(h, sx)?;
+    let dx = ctx.sub(xa, dx)?;
+    let dy = ctx.mul(h, sy)?;
+    let dy = ctx.sub(y, dy)?;
+    let dx = ctx.square(dx)?;
+    let dy = ctx.square(dy)?;
+    let side = ctx.add(dx, dy)?;
+
+    // Unsigned distance to the surface
+    let d = ctx.min(caps[0], caps[1])?;
+    let d = ctx.min(d, side)?;
+    let d = ctx.sqrt(d)?;
+
+    // Inside a convex shape, the distance to the surface is the distance to
+    // the nearest face plane (i.e. the largest signed plane distance, which
+    // is negative).  Adding twice that value flips the sign inside, and
+    // changes nothing outside.
+    let bottom = ctx.neg(y)?;
+    let top = ctx.sub(y, len)?;
+    let px = ctx.mul(xa, sy / s2.sqrt())?;
+    let py = ctx.mul(y, sx / s2.sqrt())?;
+    let plane = ctx.sub(px, py)?;
+    let inside = ctx.max_many([bottom, top, plane])?;
+    let inside = ctx.min(inside, 0.0)?;
+    let inside = ctx.mul(inside, 2.0)?;
+    ctx.add(d, inside)
+}
+
+/// Builds a segment of a torus, i.e. a tube swept along a circular arc
+///
+/// The arc has radius `major` and is centered at `center`, in the plane
+/// perpendicular to `axis`.  It starts on the positive X (for [`Axis::Z`]), Y
+/// (for [`Axis::X`]), or Z (for [`Axis::Y`]) axis, then sweeps counter-clockwise
+/// (looking down the axis) by `angle` radians; an angle of `2π` or more builds
+/// a complete torus.  The tube has radius `minor`, and its ends are rounded.
+/// The output is an exact distance field.
+///
+/// ```
+/// # use fidget::{context::Context, shapes::{self, Axis}};
+/// let mut ctx = Context::new();
+/// let angle = std::f64::consts::FRAC_PI_2;
+/// let t = shapes::torus_segment(&mut ctx, [0.0; 3], Axis::Z, 2.0, 0.5, angle)?;
+/// assert_eq!(ctx.eval_xyz(t, 2.0, 0.0, 0.0)?, -0.5);
+/// assert_eq!(ctx.eval_xyz(t, 0.0, 2.0, 0.0)?, -0.5);
+///
+/// // Beyond the end of the arc, the nearest point is on its rounded end
+/// let v = ctx.eval_xyz(t, 2.0, -1.0, 0.0)?;
+/// assert!((v - 0.5).abs() < 1e-12);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadDimensions`] if either radius is negative or `angle`
+/// isn't positive.
+pub fn torus_segment(
+    ctx: &mut Context,
+    center: [f64; 3],
+    axis: Axis,
+    major: f64,
+    minor: f64,
+    angle: f64,
+) -> Result<Node, Error> {
+    if !(major >= 0.0 && minor >= 0.0 && angle > 0.0) {
+        return Err(Error::BadDimensions);
+    }
+    let [x, y, z] = relative(ctx, center)?;
+    let (u, v, w) = match axis {
+        Axis::X => (y, z, x),
+        Axis::Y => (z, x, y),
+        Axis::Z => (x, y, z),
+    };
+    let r = ctx.hypot(u, v)?;
+
+    // Projection of the point onto the direction of the nearest point on the
+    // arc, found by clamping the angle from the arc's midpoint
+    let k = if angle >= core::f64::consts::TAU {
+        r
+    } else {
+        let half = angle / 2.0;
+        let (s, c) = half.sin_cos();
+        let uc = ctx.mul(u, c)?;
+        let vs = ctx.mul(v, s)?;
+        let mu = ctx.add(uc, vs)?;
+        let vc = ctx.mul(v, c)?;
+        let us = ctx.mul(u, s)?;
+        let mv = ctx.sub(vc, us)?;
+        let phi = ctx.atan2(mv, mu)?;
+        let phi = ctx.abs(phi)?;
+        let phi = ctx.sub(phi, half)?;
+        let phi = ctx.max_nc(phi, 0.0)?;
+        let k = ctx.cos(phi)?;
+        ctx.mul(k, r)?
+    };
+
+    // Distance to the nearest point on the arc, by the law of cosines
+    let r2 = ctx.square(r)?;
+    let w2 = ctx.square(w)?;
+    let d = ctx.add(r2, w2)?;
+    let d = ctx.add(d, major * major)?;
+    let k = ctx.mul(k, 2.0 * major)?;
+    let d = ctx.sub(d, k)?;
+    let d = ctx.max_nc(d, 0.0)?;
+    let d = ctx.sqrt(d)?;
+    ctx.sub(d, minor)
+}
+
+/// Displaces a shape's surface with gradient noise
+///
+/// The noise is sampled at `scale` times each point's position, so `scale`
+/// controls the size of surface features, and offsets the distance field by
+/// at most `amplitude`.  Different values of `seed` produce independent
+/// patterns.
+///
+/// The output is no longer an exact distance field, even if `shape` is.
+///
+/// Returns [`Error::BadNode`] if `shape` is not valid in this context.
+pub fn displace(
+    ctx: &mut Context,
+    shape: Node,
+    amplitude: f64,
+    scale: f64,
+    seed: u16,
+) -> Result<Node, Error> {
+    let [x, y, z] = [ctx.x(), ctx.y(), ctx.z()];
+    let x = ctx.mul(x, scale)?;
+    let y = ctx.mul(y, scale)?;
+    let z = ctx.mul(z, scale)?;
+    let n = ctx.noise3(x, y, z, seed)?;
+    let n = ctx.mul(n, amplitude)?;
+    ctx.add(shape, n)
+}
+
+/// Gradient magnitude correction used by [`offset`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Correction {
+    /// The shape is a metric distance field (`|∇f| = 1`), so the distance is
+    /// subtracted directly
+    None,
+    /// The shape's gradient magnitude near its surface is known, e.g. `0.5`
+    /// for a distance field which was scaled by half
+    Scale(f64),
+    /// Estimates the gradient magnitude by sampling the shape within the given
+    /// region (with [`check_distance_field`]), using the mean magnitude of
+    /// samples between the original and offset surfaces
+    Sampled(BoundingBox),
+}
+
+/// Offsets a shape's surface by `distance`
+///
+/// Positive distances grow (dilate) the shape, and negative distances shrink
+/// (erode) it.  Naively subtracting `distance` from the shape only moves the
+/// surface by the right amount if the shape is a metric distance field; the
+/// shape is first divided by its gradient magnitude (as selected by
+/// `correction`), so that offsets of scaled or squashed shapes stay accurate.
+///
+/// ```
+/// # use fidget::{context::{BoundingBox, Context}, shapes::{offset, Correction}};
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let r = ctx.hypot(x, y)?;
+/// let circle = ctx.sub(r, 1.0)?;
+///
+/// // A circle whose value is in the wrong units, i.e. 10× too steep
+/// let scaled = ctx.mul(circle, 10.0)?;
+/// let region = BoundingBox::new([-2.0, -2.0, 0.0], [2.0, 2.0, 0.0]);
+/// let grown = offset(&mut ctx, scaled, 0.5, Correction::Sampled(region))?;
+/// assert!(ctx.eval_xyz(grown, 1.5, 0.0, 0.0)?.abs() < 1e-3);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadNode`] if `shape` is not valid in this context,
+/// [`Error::InfiniteRegion`] if a sampled region isn't finite, or
+/// [`Error::BadGradientScale`] if the gradient magnitude isn't positive and
+/// finite.
+pub fn offset(
+    ctx: &mut Context,
+    shape: Node,
+    distance: f64,
+    correction: Correction,
+) -> Result<Node, Error> {
+    let scale = match correction {
+        Correction::None => 1.0,
+        Correction::Scale(s) => s,
+        Correction::Sampled(region) => {
+            // Find the steepest gradient, which bounds how far from the
+            // surface the offset band can be, then sample within that band.
+            let mut settings = Settings::default();
+            let r = check_distance_field::<vm::Eval>(
+                ctx, shape, region, &settings,
+            )?;
+            settings.band = Some(distance.abs() as f32 * r.max_norm);
+            let near = check_distance_field::<vm::Eval>(
+                ctx, shape, region, &settings,
+            )?;
+            if near.samples > near.non_finite {
+                near.mean_norm as f64
+            } else {
+                r.mean_norm as f64
+            }
+        }
+    };
+    if !(scale > 0.0 && scale.is_finite()) {
+        return Err(Error::BadGradientScale(scale));
+    }
+    let shape = if scale == 1.0 {
+        shape
+    } else {
+        ctx.div(shape, scale)?
+    };
+    ctx.sub(shape, distance)
+}
+
+/// Builds a union of two shapes, with a fillet of the given radius where
+/// they meet
+///
+/// This uses a quadratic smooth minimum, which only differs from the sharp
+/// union where the two inputs are within `radius` of each other.  If both
+/// inputs are distance fields, the output's gradient magnitude is at most 1,
+/// so it never overestimates the distance to the blended surface.  A radius
+/// of zero (or less) builds a sharp union.
+///
+/// Interval bounds are tight away from the fillet (where the inputs'
+/// intervals are more than `radius` apart), and at most `radius / 4` wider
+/// than the sharp union's bounds elsewhere.
+///
+/// ```
+/// # use fidget::{context::Context, shapes};
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let a = ctx.sub(x, 1.0)?; // half-space X < 1
+/// let b = ctx.sub(y, 1.0)?; // half-space Y < 1
+/// let s = shapes::smooth_union(&mut ctx, a, b, 0.5)?;
+///
+/// // The sharp corner at (1, 1) is filled in...
+/// assert_eq!(ctx.eval_xyz(s, 1.0, 1.0, 0.0)?, -0.125);
+/// // ...but the shape is unchanged away from the fillet
+/// assert_eq!(ctx.eval_xyz(s, 1.0, 3.0, 0.0)?, 0.0);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadNode`] if either node is not valid in this context.
+pub fn smooth_union(
+    ctx: &mut Context,
+    a: Node,
+    b: Node,
+    radius: f64,
+) -> Result<Node, Error> {
+    let sharp = ctx.min(a, b)?;
+    if radius.is_nan() || radius <= 0.0 {
+        return Ok(sharp);
+    }
+    let blend = smooth_blend(ctx, a, b, radius)?;
+    ctx.sub(sharp, blend)
+}
+
+/// Builds an intersection of two shapes, with a fillet of the given radius
+/// where they meet
+///
+/// See [`smooth_union`] for details on the blend.
+///
+/// Returns [`Error::BadNode`] if either node is not valid in this context.
+pub fn smooth_intersection(
+    ctx: &mut Context,
+    a: Node,
+    b: Node,
+    radius: f64,
+) -> Result<Node, Error> {
+    let sharp = ctx.max(a, b)?;
+    if radius.is_nan() || radius <= 0.0 {
+        return Ok(sharp);
+    }
+    let blend = smooth_blend(ctx, a, b, radius)?;
+    ctx.add(sharp, blend)
+}
+
+/// Subtracts shape `b` from shape `a`, with a fillet of the given radius
+/// where they meet
+///
+/// See [`smooth_union`] for details on the blend.
+///
+/// Returns [`Error::BadNode`] if either node is not valid in this context.
+pub fn smooth_difference(
+    ctx: &mut Context,
+    a: Node,
+    b: Node,
+    radius: f64,
+) -> Result<Node, Error> {
+    let b = ctx.neg(b)?;
+    smooth_intersection(ctx, a, b, radius)
+}
+
+/// Interpolates between two shapes, returning `a` at `t = 0` and `b` at
+/// `t = 1`
+///
+/// `t` may be a constant or a node; a variable (from [`Context::var`]) lets a
+/// single tape render every frame of an animation, by binding a new value of
+/// `t` for each frame rather than rebuilding the shape.
+///
+/// The output is a linear blend of the two fields, `a + t·(b - a)`.  For `t`
+/// in `[0, 1]`, it never overestimates the distance to its surface if `a` and
+/// `b` don't; outside of that range, it extrapolates.
+///
+/// ```
+/// # use fidget::{context::Context, shapes, vm};
+/// # use fidget::eval::Vars;
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let circle = ctx.hypot(x, y)?;
+/// let circle = ctx.sub(circle, 1.0)?;
+/// let ax = ctx.abs(x)?;
+/// let ay = ctx.abs(y)?;
+/// let square = ctx.max(ax, ay)?;
+/// let square = ctx.sub(square, 1.0)?;
+///
+/// let t = ctx.var("t")?;
+/// let shape = shapes::morph(&mut ctx, circle, square, t)?;
+/// let tape = ctx.get_tape::<vm::Eval>(shape)?;
+/// let eval = tape.new_point_evaluator();
+/// let mut vars = Vars::new(&tape);
+/// for t in [0.0, 0.5, 1.0] {
+///     // At the corner of the square, only the circle's distance is non-zero
+///     let (v, _) = eval.eval(1.0, 1.0, 0.0, vars.bind([("t", t)].into_iter()))?;
+///     assert!((v - (1.0 - t) * (2f32.sqrt() - 1.0)).abs() < 1e-6);
+/// }
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadNode`] if any node is not valid in this context.
+pub fn morph<T: IntoNode>(
+    ctx: &mut Context,
+    a: Node,
+    b: Node,
+    t: T,
+) -> Result<Node, Error> {
+    let t = t.into_node(ctx)?;
+    let d = ctx.sub(b, a)?;
+    let d = ctx.mul(d, t)?;
+    ctx.add(a, d)
+}
+
+/// Returns the offset between a sharp and smooth minimum (or maximum),
+/// `max(r - |a - b|, 0)² / 4r`
+///
+/// This is in the range `[0, r / 4]`, and is zero when `|a - b| >= r`.
+fn smooth_blend(
+    ctx: &mut Context,
+    a: Node,
+    b: Node,
+    radius: f64,
+) -> Result<Node, Error> {
+    let d = ctx.sub(a, b)?;
+    let d = ctx.abs(d)?;
+    let h = ctx.sub(radius, d)?;
+    let h = ctx.max(h, 0.0)?;
+    let h = ctx.square(h)?;
+    ctx.div(h, 4.0 * radius)
+}
+
+/// Hollows out a shape, leaving a wall of the given thickness
+///
+/// The wall is inside the original surface, so the shape's outer dimensions
+/// are unchanged.  If the input is an exact distance field, then so is the
+/// output.
+///
+/// Returns [`Error::BadNode`] if `shape` is not valid in this context.
+pub fn shell(
+    ctx: &mut Context,
+    shape: Node,
+    thickness: f64,
+) -> Result<Node, Error> {
+    let inner = ctx.add(shape, thickness)?;
+    let inner = ctx.neg(inner)?;
+    ctx.max(shape, inner)
+}
+
+/// Hollows out a shape with [`shell`], then fills the hollow with a pattern
+///
+/// The pattern is a distance field which is negative inside the lattice, e.g.
+/// from [`gyroid`], [`grid`], or [`honeycomb`].
+///
+/// ```
+/// # use fidget::{context::Context, shapes};
+/// let mut ctx = Context::new();
+/// let x = ctx.x();
+/// let y = ctx.y();
+/// let z = ctx.z();
+/// let r = ctx.hypot(x, y)?;
+/// let r = ctx.hypot(r, z)?;
+/// let sphere = ctx.sub(r, 10.0)?;
+///
+/// let lattice = shapes::gyroid(&mut ctx, 4.0, 0.5)?;
+/// let part = shapes::infill(&mut ctx, sphere, 1.0, lattice)?;
+/// assert!(ctx.eval_xyz(part, 9.5, 0.0, 0.0)? < 0.0); // in the wall
+/// assert!(ctx.eval_xyz(part, 0.0, 0.0, 0.0)? < 0.0); // on the lattice
+/// assert!(ctx.eval_xyz(part, 1.0, 0.0, 0.0)? > 0.0); // between walls
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadNode`] if either node is not valid in this context.
+pub fn infill(
+    ctx: &mut Context,
+    shape: Node,
+    wall: f64,
+    pattern: Node,
+) -> Result<Node, Error> {
+    let outer = shell(ctx, shape, wall)?;
+    let inner = ctx.max(shape, pattern)?;
+    ctx.min(outer, inner)
+}
+
+/// Triply periodic minimal surface, for use with [`tpms`]
+///
+/// Each surface divides space into two interleaved channels and is
+/// self-supporting, which makes them popular as 3D printing infill.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Tpms {
+    /// Schoen's gyroid, `sin x cos y + sin y cos z + sin z cos x = 0`
+    Gyroid,
+    /// Schwarz's primitive surface, `cos x + cos y + cos z = 0`
+    SchwarzP,
+    /// Schwarz's diamond surface,
+    /// `sin x sin y sin z + sin x cos y cos z + cos x sin y cos z +
+    /// cos x cos y sin z = 0`
+    Diamond,
+}
+
+/// Builds a lattice from a triply periodic minimal surface
+///
+/// The surface repeats every `period` units along each axis, and is thickened
+/// into walls of (at least) the given thickness.  `thickness` may be a
+/// constant or a node, e.g. from [`ramp`], for walls which vary in thickness
+/// across the part.
+///
+/// The output is an approximate distance field, which is scaled so that it
+/// never overestimates the distance to the surface; walls are up to 1.23
+/// times `thickness` for the gyroid and diamond surfaces, and up to 1.73
+/// times `thickness` for the Schwarz P surface.  If `thickness` varies, then
+/// the field's gradient is skewed by half of the thickness gradient.
+///
+/// Use [`trim`] to cut the (infinite) lattice down to a finite shape.
+///
+/// ```
+/// # use fidget::{context::Context, shapes::{self, Tpms}};
+/// let mut ctx = Context::new();
+/// let z = ctx.z();
+///
+/// // Walls which thicken from 0.2 at Z = 0 to 0.8 at Z = 8
+/// let t = shapes::ramp(&mut ctx, z, (0.0, 0.2), (8.0, 0.8))?;
+/// let lattice = shapes::tpms(&mut ctx, Tpms::SchwarzP, 4.0, t)?;
+///
+/// // Walls are centered on the surface, which passes through (2, 1, 0)
+/// let v = ctx.eval_xyz(lattice, 2.0, 1.0, 0.0)?;
+/// assert!((v - -0.1).abs() < 1e-6);
+/// let v = ctx.eval_xyz(lattice, 2.0, 1.0, 8.0)?;
+/// assert!((v - -0.4).abs() < 1e-6);
+/// # Ok::<(), fidget::Error>(())
+/// ```
+///
+/// Returns [`Error::BadNode`] if `thickness` is not valid in this context.
+pub fn tpms<T: IntoNode>(
+    ctx: &mut Context,
+    surface: Tpms,
+    period: f64,
+    thickness: T,
+) -> Result<Node, Error> {
+    let thickness = thickness.into_node(ctx)?;
+    let k = core::f64::consts::TAU / period;
+    let mut sin = vec![];
+    let mut cos = vec![];
+    for a in [ctx.x(), ctx.y(), ctx.z()] {
+        let a = ctx.mul(a, k)?;
+        sin.push(ctx.sin(a)?);
+        cos.push(ctx.cos(a)?);
+    }
+
+    let (sx, sy, sz) = (sin[0], sin[1], sin[2]);
+    let (cx, cy, cz) = (cos[0], cos[1], cos[2]);
+    let g = match surface {
+        Tpms::Gyroid => {
+            sum_of_products(ctx, &[&[sx, cy], &[sy, cz], &[sz, cx]])?
+        }
+        Tpms::SchwarzP => sum_of_products(ctx, &[&[cx], &[cy], &[cz]])?,
+        Tpms::Diamond => sum_of_products(
+            ctx,
+            &[&[sx, sy, sz], &[sx, cy, cz], &[cx, sy, cz], &[cx, cy, sz]],
+        )?,
+    };
+
+    // For each surface, |∇g| is at most √3·k everywhere (and at least √2·k
+    // on the gyroid and diamond surfaces, or k on the Schwarz P surface)
+    let g = ctx.abs(g)?;
+    let g = ctx.div(g, 3f64.sqrt() * k)?;
+    let half = ctx.mul(thickness, 0.5)?;
+    ctx.sub(g, half)
+}
+
+/// Builds a gyroid lattice, with walls of (at least) the given thickness
+///
+/// The gyroid is a triply periodic minimal surface, which repeats every
+/// `period` units along each axis; it's self-supporting and divides space
+/// into two connected channels, which makes it a popular 3D printing infill.
+///
+/// This is shorthand for [`tpms`] with [`Tpms::Gyroid`].
+pub fn gyroid(
+    ctx: &mut Context,
+    period: f64,
+    thickness: f64,
+) -> Result<Node, Error> {
+    tpms(ctx, Tpms::Gyroid, period, thickness)
+}
+
+/// Builds a Schwarz P lattice, with walls of (at least) the given thickness
+///
+/// This is shorthand for [`tpms`] with [`Tpms::SchwarzP`].
+pub fn schwarz_p(
+    ctx: &mut Context,
+    period: f64,
+    thickness: f64,
+) -> Result<Node, Error> {
+    tpms(ctx, Tpms::SchwarzP, period, thickness)
+}
+
+/// Builds a diamond lattice, with walls of (at least) the given thickness
+///
+/// This is shorthand for [`tpms`] with [`Tpms::Diamond`].
+pub fn diamond(
+    ctx: &mut Context,
+    period: f64,
+    thickness: f64,
+) -> Result<Node, Error> {
+    tpms(ctx, Tpms::Diamond, period, thickness)
+}
+
+/// Trims a lattice (or any other shape) to lie within a boundary shape
+///
+/// This is the intersection of the two shapes; unlike [`infill`], it doesn't
+/// add a wall around the boundary.
+///
+/// Returns [`Error::BadNode`] if either node is not valid in this context.
+pub fn trim(
+    ctx: &mut Context,
+    lattice: Node,
+    boundary: Node,
+) -> Result<Node, Error> {
+    ctx.max(lattice, boundary)
+}
+
+/// Builds a value which varies linearly with a field, e.g. a wall thickness
+///
+/// The output is `start.1` where `field <= start.0`, `end.1` where
+/// `field >= end.0`, and linearly interpolated in between.  Using a
+/// coordinate as the field grades the value along an axis; using a shape's
+/// distance field grades it by depth below the surface.
+///
+/// Returns [`Error::BadNode`] if `field` is not valid in this context.
+pub fn ramp(
+    ctx: &mut Context,
+    field: Node,
+    start: (f64, f64),
+    end: (f64, f64),
+) -> Result<Node, Error> {
+    let t = ctx.sub(field, start.0)?;
+    let t = ctx.div(t, end.0 - start.0)?;
+    let t = ctx.clamp(t, 0.0, 1.0)?;
+    let t = ctx.mul(t, end.1 - start.1)?;
+    ctx.add(t, start.1)
+}
+
+/// Builds a rectilinear grid of walls, parallel to the XZ and YZ planes
+///
+/// Walls are centered on multiples of `period` along X and Y.  If the walls
+/// don't overlap (i.e. `thickness < period`), the output is an exact distance
+/// field.
+pub fn grid(
+    ctx: &mut Context,
+    period: f64,
+    thickness: f64,
+) -> Result<Node, Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let dx = wrap(ctx, x, period)?;
+    let dx = ctx.abs(dx)?;
+    let dy = wrap(ctx, y, period)?;
+    let dy = ctx.abs(dy)?;
+    let d = ctx.min(dx, dy)?;
+    ctx.sub(d, thickness / 2.0)
+}
+
+/// Builds a honeycomb of hexagonal cells, extending along the Z axis
+///
+/// Opposite walls of each cell are `period` units apart (measured between the
+/// centers of the walls), and the cell at the origin has vertices on the Y
+/// axis.  If the walls don't overlap, the output is an exact distance field.
+pub fn honeycomb(
+    ctx: &mut Context,
+    period: f64,
+    thickness: f64,
+) -> Result<Node, Error> {
+    let apothem = period / 2.0;
+    let sqrt3 = 3f64.sqrt();
+
+    // Cell centers form two rectangular lattices, with spacing `period` along
+    // X and `period·√3` along Y, offset from each other by half a cell.  For
+    // each lattice, we find the hexagonal norm of the offset from the nearest
+    // center; the smaller norm picks the cell which contains the point.
+    let x = ctx.x();
+    let y = ctx.y();
+    let mut norms = vec![];
+    for (ox, oy) in [(0.0, 0.0), (apothem, apothem * sqrt3)] {
+        let x = ctx.sub(x, ox)?;
+        let y = ctx.sub(y, oy)?;
+        let dx = wrap(ctx, x, period)?;
+        let dx = ctx.abs(dx)?;
+        let dy = wrap(ctx, y, period * sqrt3)?;
+        let dy = ctx.abs(dy)?;
+        let hx = ctx.mul(dx, 0.5)?;
+        let hy = ctx.mul(dy, sqrt3 / 2.0)?;
+        let h = ctx.add(hx, hy)?;
+        norms.push(ctx.max(dx, h)?);
+    }
+    let h = ctx.min(norms[0], norms[1])?;
+
+    // Distance to the nearest wall, from within the cell
+    let d = ctx.sub(apothem, h)?;
+    ctx.sub(d, thickness / 2.0)
+}
+
+/// Wraps `v` into `[-p/2, p/2)`
+///
+/// This is a sawtooth wave, i.e. the offset from the nearest multiple of `p`.
+fn wrap(ctx: &mut Context, v: Node, p: f64) -> Result<Node, Error> {
+    let v = ctx.add(v, p / 2.0)?;
+    let v = ctx.modulo(v, p)?;
+    ctx.sub(v, p / 2.0)
+}
+
+/// Builds a sum of products, e.g. `a·b + c·d` from `[[a, b], [c, d]]`
+fn sum_of_products(
+    ctx: &mut Context,
+    terms: &[&[Node]],
+) -> Result<Node, Error> {
+    let mut out = ctx.constant(0.0);
+    for term in terms {
+        let mut t = term[0];
+        for &f in &term[1..] {
+            t = ctx.mul(t, f)?;
+        }
+        out = ctx.add(out, t)?;
+    }
+    Ok(out)
+}
+
+/// Returns the X, Y, Z coordinates relative to the given point
+fn relative(ctx: &mut Context, p: [f64; 3]) -> Result<[Node; 3], Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let z = ctx.z();
+    Ok([ctx.sub(x, p[0])?, ctx.sub(y, p[1])?, ctx.sub(z, p[2])?])
+}
+
+/// Evaluates a shape in the `Z = 0` plane, removing any dependence on Z
+fn flatten(ctx: &mut Context, shape: Node) -> Result<Node, Error> {
+    let x = ctx.x();
+    let y = ctx.y();
+    let zero = ctx.constant(0.0);
+    ctx.remap_xyz(shape, [x, y, zero])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds a circle with the given center and radius
+    fn circle(ctx: &mut Context, cx: f64, cy: f64, r: f64) -> Node {
+        let x = ctx.x();
+        let y = ctx.y();
+        let dx = ctx.sub(x, cx).unwrap();
+        let dy = ctx.sub(y, cy).unwrap();
+        let dx2 = ctx.square(dx).unwrap();
+        let dy2 = ctx.square(dy).unwrap();
+        let d = ctx.add(dx2, dy2).unwrap();
+        let d = ctx.sqrt(d).unwrap();
+        ctx.sub(d, r).unwrap()
+    }
+
+    #[test]
+    fn test_extrude() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 0.0, 0.0, 1.0);
+
+        // Make the input depend on Z, which should be ignored
+        let z = ctx.z();
+        let c = ctx.add(c, z).unwrap();
+
+        let s = extrude(&mut ctx, c, 2.0).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(s, x, y, z).unwrap();
+        assert_eq!(f(0.0, 0.0, 1.0), -1.0);
+        assert_eq!(f(0.0, 0.0, 0.5), -0.5);
+        assert_eq!(f(0.5, 0.0, 1.0), -0.5);
+        assert_eq!(f(0.0, 0.0, -1.0), 1.0);
+        assert_eq!(f(0.0, 3.0, 1.0), 2.0);
+        assert_eq!(f(4.0, 0.0, 6.0), 5.0); // corner distance
+    }
+
+    #[test]
+    fn test_revolve() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 2.0, 0.0, 0.5);
+
+        // A torus around the Y axis
+        let s = revolve(&mut ctx, c, Axis::Y).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(s, x, y, z).unwrap();
+        assert_eq!(f(2.0, 0.0, 0.0), -0.5);
+        assert_eq!(f(0.0, 0.0, 2.0), -0.5);
+        assert_eq!(f(-2.0, 0.0, 0.0), -0.5);
+        assert_eq!(f(0.0, 0.0, 0.0), 1.5);
+        assert_eq!(f(0.0, 1.0, -2.0), 0.5);
+
+        // Revolving around the X axis sweeps a sphere
+        let s = revolve(&mut ctx, c, Axis::X).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(s, x, y, z).unwrap();
+        assert_eq!(f(2.0, 0.0, 0.0), -0.5);
+        assert_eq!(f(2.0, 0.0, 0.25), -0.25);
+        assert_eq!(f(2.0, 0.0, 1.0), 0.5);
+    }
+
+    #[test]
+    fn test_revolve_z() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 2.0, 1.0, 0.5);
+        let s = revolve(&mut ctx, c, Axis::Z).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(s, x, y, z).unwrap();
+        assert_eq!(f(2.0, 0.0, 1.0), -0.5);
+        assert_eq!(f(0.0, -2.0, 1.0), -0.5);
+        assert_eq!(f(0.0, 0.0, 1.0), 1.5);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 2.0, 1.0, 0.5);
+        let s = mirror(&mut ctx, c, Axis::X).unwrap();
+        let f = |x, y| ctx.eval_xyz(s, x, y, 0.0).unwrap();
+        assert_eq!(f(2.0, 1.0), -0.5);
+        assert_eq!(f(-2.0, 1.0), -0.5);
+        assert_eq!(f(-2.0, -1.0), 1.5);
+        assert_eq!(f(0.0, 1.0), 1.5);
+
+        let s = mirror(&mut ctx, c, Axis::Y).unwrap();
+        let f = |x, y| ctx.eval_xyz(s, x, y, 0.0).unwrap();
+        assert_eq!(f(2.0, -1.0), -0.5);
+        assert_eq!(f(-2.0, 1.0), 3.5);
+    }
+
+    #[test]
+    fn test_linear_array() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 0.0, 0.0, 0.5);
+        let s = linear_array(&mut ctx, c, Axis::X, 4, 2.0).unwrap();
+        let f = |x, y| ctx.eval_xyz(s, x, y, 0.0).unwrap();
+        for k in 0..4 {
+            let x = k as f64 * 2.0;
+            assert_eq!(f(x, 0.0), -0.5, "{k}");
+            assert_eq!(f(x, 1.5), 1.0, "{k}");
+        }
+        assert_eq!(f(1.0, 0.0), 0.5);
+        assert_eq!(f(-3.0, 0.0), 2.5);
+        assert_eq!(f(10.0, 0.0), 3.5);
+
+        // Negative spacing builds the array in the other direction
+        let s = linear_array(&mut ctx, c, Axis::Y, 3, -1.5).unwrap();
+        let f = |y| ctx.eval_xyz(s, 0.0, y, 0.0).unwrap();
+        assert_eq!(f(-3.0), -0.5);
+        assert_eq!(f(-5.0), 1.5);
+        assert_eq!(f(2.0), 1.5);
+
+        assert!(matches!(
+            linear_array(&mut ctx, c, Axis::X, 0, 1.0),
+            Err(Error::EmptyArray)
+        ));
+    }
+
+    #[test]
+    fn test_radial_array() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 2.0, 0.0, 0.5);
+        let s = radial_array(&mut ctx, c, Axis::Z, 4).unwrap();
+        let f = |x, y| ctx.eval_xyz(s, x, y, 0.0).unwrap();
+        for (x, y) in [(2.0, 0.0), (0.0, 2.0), (-2.0, 0.0), (0.0, -2.0)] {
+            assert!((f(x, y) + 0.5).abs() < 1e-6, "{x} {y}");
+        }
+        assert!((f(0.0, 0.0) - 1.5).abs() < 1e-6);
+        // Halfway between copies, both are at the same distance
+        assert!((f(1.0, 1.0) - (2f64.sqrt() - 0.5)).abs() < 1e-6);
+
+        // Around the Y axis, the copy at angle 0 is on the +Z axis
+        let c = ctx.z();
+        let c = ctx.sub(c, 2.0).unwrap();
+        let s = radial_array(&mut ctx, c, Axis::Y, 2).unwrap();
+        let f = |x, z| ctx.eval_xyz(s, x, 1.0, z).unwrap();
+        assert!((f(0.0, 3.0) - 1.0).abs() < 1e-6);
+        assert!((f(0.0, -3.0) - 1.0).abs() < 1e-6);
+
+        assert!(matches!(
+            radial_array(&mut ctx, c, Axis::X, 0),
+            Err(Error::EmptyArray)
+        ));
+    }
+
+    #[test]
+    fn test_array_intervals() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 1.5, 0.25, 0.5);
+        let s = radial_array(&mut ctx, c, Axis::Z, 5).unwrap();
+        let s = linear_array(&mut ctx, s, Axis::Y, 3, 4.0).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(s).unwrap();
+        let ieval = tape.new_interval_evaluator();
+        let peval = tape.new_point_evaluator();
+        for (x, y, w) in [(-2.0, -1.0, 1.0), (0.5, 3.0, 0.5), (-4.0, 6.0, 3.0)]
+        {
+            let i = ieval.eval_xy([x, x + w], [y, y + w]);
+            for j in 0..=10 {
+                for k in 0..=10 {
+                    let px = x + w * j as f32 / 10.0;
+                    let py = y + w * k as f32 / 10.0;
+                    let (v, _) = peval.eval(px, py, 0.0, &[]).unwrap();
+                    assert!(
+                        v >= i.lower() - 1e-5 && v <= i.upper() + 1e-5,
+                        "{v} at ({px}, {py}) is outside {i:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_smooth() {
+        let mut ctx = Context::new();
+        let a = circle(&mut ctx, -1.0, 0.0, 1.0);
+        let b = circle(&mut ctx, 1.0, 0.0, 1.0);
+        let u = smooth_union(&mut ctx, a, b, 0.5).unwrap();
+        let i = smooth_intersection(&mut ctx, a, b, 0.5).unwrap();
+        let d = smooth_difference(&mut ctx, a, b, 0.5).unwrap();
+        let sharp = smooth_union(&mut ctx, a, b, 0.0).unwrap();
+        let f = |n, x, y| ctx.eval_xyz(n, x, y, 0.0).unwrap();
+
+        // Where the circles touch, both inputs are zero
+        assert_eq!(f(u, 0.0, 0.0), -0.125);
+        assert_eq!(f(i, 0.0, 0.0), 0.125);
+        assert_eq!(f(d, 0.0, 0.0), 0.125);
+        assert_eq!(f(sharp, 0.0, 0.0), 0.0);
+
+        // Away from the fillet, blends match sharp operations
+        assert_eq!(f(u, -2.5, 0.0), 0.5);
+        assert_eq!(f(i, -2.5, 0.0), 2.5);
+        assert_eq!(f(d, -1.5, 0.0), -0.5);
+        assert_eq!(f(d, 3.0, 0.0), 3.0);
+    }
+
+    #[test]
+    fn test_morph() {
+        let mut ctx = Context::new();
+        let a = circle(&mut ctx, 0.0, 0.0, 1.0);
+        let b = circle(&mut ctx, 2.0, 0.0, 2.0);
+        let m = morph(&mut ctx, a, b, 0.25).unwrap();
+        let f = |n, x| ctx.eval_xyz(n, x, 0.0, 0.0).unwrap();
+        assert_eq!(f(m, 0.0), -0.75);
+        assert_eq!(f(m, 1.0), -0.25);
+
+        // A variable can be bound to a constant later
+        let var = ctx.var("t").unwrap();
+        let m = morph(&mut ctx, a, b, var).unwrap();
+        for (t, v) in [(0.0, -1.0), (0.5, -0.5), (1.0, 0.0), (2.0, 1.0)] {
+            let bound = ctx.bind_constant(m, var, t).unwrap();
+            assert_eq!(ctx.eval_xyz(bound, 0.0, 0.0, 0.0).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn test_smooth_intervals() {
+        let mut ctx = Context::new();
+        let a = circle(&mut ctx, -1.0, 0.0, 1.0);
+        let b = circle(&mut ctx, 1.5, 0.5, 1.0);
+        let u = smooth_union(&mut ctx, a, b, 0.5).unwrap();
+        let sharp = ctx.min(a, b).unwrap();
+        let tape = ctx.get_tape::<vm::Eval>(u).unwrap();
+        let ieval = tape.new_interval_evaluator();
+        let peval = tape.new_point_evaluator();
+        let sharp_tape = ctx.get_tape::<vm::Eval>(sharp).unwrap();
+        let sharp_eval = sharp_tape.new_interval_evaluator();
+
+        for (x, y, w) in [(-0.5, -0.5, 1.0), (-3.0, 2.0, 0.5), (0.2, 0.1, 0.2)]
+        {
+            let xs = [x, x + w];
+            let ys = [y, y + w];
+            let i = ieval.eval_xy(xs, ys);
+            let s = sharp_eval.eval_xy(xs, ys);
+            assert!(i.upper() <= s.upper());
+            assert!(i.lower() >= s.lower() - 0.125);
+            for j in 0..=10 {
+                for k in 0..=10 {
+                    let px = x + w * j as f32 / 10.0;
+                    let py = y + w * k as f32 / 10.0;
+                    let (v, _) = peval.eval(px, py, 0.0, &[]).unwrap();
+                    assert!(
+                        v >= i.lower() && v <= i.upper(),
+                        "{v} at ({px}, {py}) is outside {i:?}"
+                    );
+                }
+            }
+        }
+
+        // Far from the fillet, the bounds are the same as a sharp union
+        let xs = [-2.5, -2.0];
+        let ys = [-0.25, 0.25];
+        assert_eq!(ieval.eval_xy(xs, ys), sharp_eval.eval_xy(xs, ys));
+    }
+
+    #[test]
+    fn test_displace() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 0.0, 0.0, 1.0);
+        let s = displace(&mut ctx, c, 0.25, 4.0, 1).unwrap();
+
+        // The surface is only moved by up to the amplitude
+        let mut moved = false;
+        for i in 0..100 {
+            let t = i as f64 * 0.173;
+            let (x, y, z) = (t.cos() * 0.5, t.sin() * 3.0, t * 0.1);
+            let a = ctx.eval_xyz(c, x, y, z).unwrap();
+            let b = ctx.eval_xyz(s, x, y, z).unwrap();
+            assert!((a - b).abs() <= 0.25);
+            moved |= a != b;
+        }
+        assert!(moved);
+
+        // Noise is zero on lattice points
+        assert_eq!(ctx.eval_xyz(s, 0.5, 0.0, 0.0).unwrap(), -0.5);
+    }
+
+    #[test]
+    fn test_offset() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 0.0, 0.0, 1.0);
+        let s = offset(&mut ctx, c, 0.25, Correction::None).unwrap();
+        assert_eq!(ctx.eval_xyz(s, 1.25, 0.0, 0.0).unwrap(), 0.0);
+        let s = offset(&mut ctx, c, -0.5, Correction::None).unwrap();
+        assert_eq!(ctx.eval_xyz(s, 0.0, 0.5, 0.0).unwrap(), 0.0);
+
+        // A shallow shape (half the magnitude of a distance field)
+        let half = ctx.mul(c, 0.5).unwrap();
+        let naive = offset(&mut ctx, half, 0.5, Correction::None).unwrap();
+        assert_eq!(ctx.eval_xyz(naive, 2.0, 0.0, 0.0).unwrap(), 0.0);
+        let s = offset(&mut ctx, half, 0.5, Correction::Scale(0.5)).unwrap();
+        assert_eq!(ctx.eval_xyz(s, 1.5, 0.0, 0.0).unwrap(), 0.0);
+
+        // A squashed circle (an ellipse), where the gradient varies
+        let [x, y, z] = [ctx.x(), ctx.y(), ctx.z()];
+        let x2 = ctx.mul(x, 2.0).unwrap();
+        let ellipse = ctx.remap_xyz(c, [x2, y, z]).unwrap();
+        let region = BoundingBox::new([-2.0, -2.0, 0.0], [2.0, 2.0, 0.0]);
+        let s = offset(&mut ctx, ellipse, 0.25, Correction::Sampled(region))
+            .unwrap();
+        for (x, y) in [(0.75, 0.0), (0.0, 1.25)] {
+            let v = ctx.eval_xyz(s, x, y, 0.0).unwrap();
+            assert!(v.abs() < 0.15, "{v}");
+        }
+
+        for bad in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                offset(&mut ctx, c, 0.5, Correction::Scale(bad)),
+                Err(Error::BadGradientScale(..))
+            ));
+        }
+        assert!(matches!(
+            offset(
+                &mut ctx,
+                c,
+                0.5,
+                Correction::Sampled(BoundingBox::INFINITE)
+            ),
+            Err(Error::InfiniteRegion)
+        ));
+    }
+
+    #[test]
+    fn test_shell() {
+        let mut ctx = Context::new();
+        let c = circle(&mut ctx, 0.0, 0.0, 1.0);
+        let s = shell(&mut ctx, c, 0.25).unwrap();
+        let f = |x| ctx.eval_xyz(s, x, 0.0, 0.0).unwrap();
+        assert_eq!(f(0.875), -0.125);
+        assert_eq!(f(1.5), 0.5);
+        assert_eq!(f(0.5), 0.25);
+        assert_eq!(f(0.0), 0.75);
+    }
+
+    #[test]
+    fn test_rounded_box() {
+        let mut ctx = Context::new();
+        let b = rounded_box(&mut ctx, [1.0, 2.0, 3.0], [1.0, 2.0, 3.0], 0.5)
+            .unwrap();
+        let f = |x, y, z| ctx.eval_xyz(b, x, y, z).unwrap();
+        assert_eq!(f(1.0, 2.0, 3.0), -1.0);
+        assert_eq!(f(3.0, 2.0, 3.0), 1.0);
+        assert_eq!(f(1.0, -1.0, 3.0), 1.0);
+        assert_eq!(f(1.0, 2.0, 6.5), 0.5);
+        // The rounded corner is centered at (1.5, 3.5, 5.5)
+        let v = f(2.5, 4.5, 6.5);
+        assert!((v - (3f64.sqrt() - 0.5)).abs() < 1e-12);
+
+        let region = BoundingBox::new([-2.0; 3], [4.0, 6.0, 8.0]);
+        let r = check_distance_field::<vm::Eval>(
+            &ctx,
+            b,
+            region,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert!((r.max_norm - 1.0).abs() < 1e-5);
+        assert!((r.min_norm - 1.0).abs() < 1e-5);
+
+        for (size, radius) in [([1.0; 3], -0.1), ([1.0, 0.5, 1.0], 0.6)] {
+            assert!(matches!(
+                rounded_box(&mut ctx, [0.0; 3], size, radius),
+                Err(Error::BadDimensions)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_capsule() {
+        let mut ctx = Context::new();
+        let c = capsule(&mut ctx, [0.0; 3], [0.0, 0.0, 2.0], 0.5).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(c, x, y, z).unwrap();
+        assert_eq!(f(0.0, 0.0, 1.0), -0.5);
+        assert_eq!(f(2.0, 0.0, 1.0), 1.5);
+        assert_eq!(f(0.0, 0.0, -1.0), 0.5);
+        assert_eq!(f(0.0, 3.0, 6.0), 4.5);
+
+        // Degenerate capsules are spheres
+        let s = capsule(&mut ctx, [1.0; 3], [1.0; 3], 0.5).unwrap();
+        assert_eq!(ctx.eval_xyz(s, 1.0, 1.0, 3.0).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn test_capped_cone() {
+        /// Reference distance, from the 2D profile in the (radius, height)
+        /// half-plane
+        fn reference(
+            p: [f64; 3],
+            a: [f64; 3],
+            b: [f64; 3],
+            ra: f64,
+            rb: f64,
+        ) -> f64 {
+            let ba = [0, 1, 2].map(|i| b[i] - a[i]);
+            let pa = [0, 1, 2].map(|i| p[i] - a[i]);
+            let len = ba.iter().map(|v| v * v).sum::<f64>().sqrt();
+            let y = (0..3).map(|i| pa[i] * ba[i]).sum::<f64>() / len;
+            let x = (pa.iter().map(|v| v * v).sum::<f64>() - y * y)
+                .max(0.0)
+                .sqrt();
+            let segment = |a: [f64; 2], b: [f64; 2]| {
+                let ba = [b[0] - a[0], b[1] - a[1]];
+                let pa = [x - a[0], y - a[1]];
+                let h = ((pa[0] * ba[0] + pa[1] * ba[1])
+                    / (ba[0] * ba[0] + ba[1] * ba[1]))
+                    .clamp(0.0, 1.0);
+                (pa[0] - h * ba[0]).hypot(pa[1] - h * ba[1])
+            };
+            let d = segment([0.0, 0.0], [ra, 0.0])
+                .min(segment([ra, 0.0], [rb, len]))
+                .min(segment([rb, len], [0.0, len]));
+            let inside = y > 0.0 && y < len && x < ra + (rb - ra) * y / len;
+            if inside {
+                -d
+            } else {
+                d
+            }
+        }
+
+        let mut ctx = Context::new();
+        for (a, b, ra, rb) in [
+            ([0.0; 3], [0.0, 0.0, 2.0], 1.0, 1.0),
+            ([0.0; 3], [0.0, 0.0, 2.0], 1.0, 0.0),
+            ([1.0, -1.0, 0.5], [-0.5, 0.5, 1.5], 0.25, 1.0),
+            ([0.2, 0.3, -1.0], [0.1, -0.4, 1.2], 1.5, 0.5),
+        ] {
+            let c = capped_cone(&mut ctx, a, b, ra, rb).unwrap();
+            for i in 0..8 {
+                for j in 0..8 {
+                    for k in 0..8 {
+                        let p = [i, j, k].map(|v| v as f64 * 0.5 - 1.8);
+                        let v = ctx.eval_xyz(c, p[0], p[1], p[2]).unwrap();
+                        let e = reference(p, a, b, ra, rb);
+                        assert!((v - e).abs() < 1e-9, "{p:?}: {v} != {e}");
+                    }
+                }
+            }
+        }
+        assert!(matches!(
+            capped_cone(&mut ctx, [1.0; 3], [1.0; 3], 1.0, 1.0),
+            Err(Error::BadDimensions)
+        ));
+    }
+
+    #[test]
+    fn test_torus_segment() {
+        let mut ctx = Context::new();
+        let angle = 4.0;
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let t = torus_segment(
+                &mut ctx,
+                [0.5, 0.0, -0.5],
+                axis,
+                2.0,
+                0.25,
+                angle,
+            )
+            .unwrap();
+            // Compare against the distance to densely sampled points on the
+            // arc, in the (u, v, w) frame of the axis
+            let arc: Vec<[f64; 3]> = (0..=4000)
+                .map(|i| {
+                    let a = angle * i as f64 / 4000.0;
+                    [2.0 * a.cos(), 2.0 * a.sin(), 0.0]
+                })
+                .collect();
+            for i in 0..6 {
+                for j in 0..6 {
+                    for k in 0..6 {
+                        let uvw = [i, j, k].map(|v| v as f64 * 0.8 - 2.1);
+                        let xyz = match axis {
+                            Axis::X => [uvw[2], uvw[0], uvw[1]],
+                            Axis::Y => [uvw[1], uvw[2], uvw[0]],
+                            Axis::Z => uvw,
+                        };
+                        let p = [xyz[0] + 0.5, xyz[1], xyz[2] - 0.5];
+                        let v = ctx.eval_xyz(t, p[0], p[1], p[2]).unwrap();
+                        let e = arc
+                            .iter()
+                            .map(|q| {
+                                (0..3)
+                                    .map(|i| (uvw[i] - q[i]).powi(2))
+                                    .sum::<f64>()
+                                    .sqrt()
+                            })
+                            .fold(f64::INFINITY, f64::min)
+                            - 0.25;
+                        assert!((v - e).abs() < 1e-5, "{p:?}: {v} != {e}");
+                    }
+                }
+            }
+        }
+
+        // A full turn is a complete torus
+        let t =
+            torus_segment(&mut ctx, [0.0; 3], Axis::Y, 2.0, 0.5, 7.0).unwrap();
+        let v = ctx.eval_xyz(t, 0.0, 0.0, -2.0).unwrap();
+        assert!((v + 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut ctx = Context::new();
+        let g = grid(&mut ctx, 2.0, 0.5).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(g, x, y, z).unwrap();
+        assert_eq!(f(0.0, 0.5, 3.0), -0.25);
+        assert_eq!(f(4.0, 1.0, 0.0), -0.25);
+        assert_eq!(f(1.0, 1.0, 0.0), 0.75);
+        assert_eq!(f(-3.0, 2.75, 0.0), 0.5);
+    }
+
+    #[test]
+    fn test_honeycomb() {
+        let mut ctx = Context::new();
+        let h = honeycomb(&mut ctx, 2.0, 0.2).unwrap();
+        let f = |x, y| ctx.eval_xyz(h, x, y, 5.0).unwrap();
+        let s3 = 3f64.sqrt();
+
+        // Cell centers are the furthest points from the walls
+        for (x, y) in [(0.0, 0.0), (2.0, 0.0), (1.0, s3), (-1.0, -s3)] {
+            assert!((f(x, y) - 0.9).abs() < 1e-5, "{x} {y}");
+        }
+        // Walls between neighboring cells
+        for (x, y) in [(1.0, 0.0), (0.5, s3 / 2.0), (-0.5, s3 / 2.0)] {
+            assert!((f(x, y) + 0.1).abs() < 1e-5, "{x} {y}");
+        }
+        // Partway to a wall
+        assert!((f(0.0, 0.5) - (0.9 - 0.25 * s3)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_gyroid() {
+        let mut ctx = Context::new();
+        let g = gyroid(&mut ctx, 4.0, 0.5).unwrap();
+        let f = |x, y, z| ctx.eval_xyz(g, x, y, z).unwrap();
+
+        // The surface passes through the origin
+        assert_eq!(f(0.0, 0.0, 0.0), -0.25);
+        // ...and is periodic
+        assert!((f(4.0, -8.0, 12.0) - -0.25).abs() < 1e-5);
+        assert!(f(1.0, 0.0, 0.0) > 0.0);
+
+        // Walls are at least as thick as requested
+        for t in [-0.24, 0.0, 0.24] {
+            assert!(f(t, 0.0, 0.0) <= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_tpms() {
+        let mut ctx = Context::new();
+        let p = schwarz_p(&mut ctx, 4.0, 0.5).unwrap();
+        let d = diamond(&mut ctx, 4.0, 0.5).unwrap();
+        let f = |n, x, y, z| ctx.eval_xyz(n, x, y, z).unwrap();
+
+        // Points on each surface, and their periodic images
+        for (x, y, z) in [(2.0, 1.0, 0.0), (1.0, 1.0, 1.0), (-2.0, 5.0, 4.0)] {
+            assert!((f(p, x, y, z) - -0.25).abs() < 1e-9, "{x} {y} {z}");
+        }
+        for (x, y, z) in [(0.0, 0.0, 0.0), (2.0, 0.0, 0.0), (4.0, -4.0, 8.0)] {
+            assert!((f(d, x, y, z) - -0.25).abs() < 1e-9, "{x} {y} {z}");
+        }
+        // Channels between the walls
+        assert!(f(p, 0.0, 0.0, 0.0) > 0.5);
+        assert!(f(d, 0.5, 0.5, 0.5) > 0.0);
+
+        // None of the fields overestimate the distance to the surface
+        let region = BoundingBox::new([-4.0; 3], [4.0; 3]);
+        let settings = Settings {
+            resolution: 16,
+            ..Settings::default()
+        };
+        for kind in [Tpms::Gyroid, Tpms::SchwarzP, Tpms::Diamond] {
+            let t = tpms(&mut ctx, kind, 4.0, 0.5).unwrap();
+            let r =
+                check_distance_field::<vm::Eval>(&ctx, t, region, &settings)
+                    .unwrap();
+            assert!(r.max_norm <= 1.0 + 1e-5, "{kind:?}: {}", r.max_norm);
+        }
+    }
+
+    #[test]
+    fn test_ramp_and_trim() {
+        let mut ctx = Context::new();
+        let z = ctx.z();
+        let t = ramp(&mut ctx, z, (0.0, 1.0), (4.0, 3.0)).unwrap();
+        let g = tpms(&mut ctx, Tpms::Gyroid, 4.0, t).unwrap();
+        let c = circle(&mut ctx, 0.0, 0.0, 3.0);
+        let s = trim(&mut ctx, g, c).unwrap();
+
+        let f = |n, x, z| ctx.eval_xyz(n, x, 0.0, z).unwrap();
+        assert_eq!(f(t, 0.0, -1.0), 1.0);
+        assert_eq!(f(t, 0.0, 0.0), 1.0);
+        assert_eq!(f(t, 0.0, 1.0), 1.5);
+        assert_eq!(f(t, 0.0, 4.0), 3.0);
+        assert_eq!(f(t, 0.0, 10.0), 3.0);
+
+        // Graded walls are centered on the surface
+        assert_eq!(f(g, 0.0, 0.0), -0.5);
+        assert_eq!(f(g, 0.0, 4.0), -1.5);
+
+        // Trimming removes everything outside of the boundary
+        assert_eq!(f(s, 0.0, 0.0), -0.5);
+        assert_eq!(f(s, 8.0, 0.0), 5.0);
+    }
+
+    #[test]
+    fn test_lattice_intervals() {
+        let mut ctx = Context::new();
+        let g = grid(&mut ctx, 2.0, 0.5).unwrap();
+        let h = honeycomb(&mut ctx, 0.7, 0.1).unwrap();
+        for s in [g, h] {
+            let tape = ctx.get_tape::<vm::Eval>(s).unwrap();
+            let ieval = tape.new_interval_evaluator();
+            let peval = tape.new_point_evaluator();
+            for (x, y, w) in
+                [(-3.0, 0.2, 0.1), (0.9, 1.4, 0.5), (1.4, -7.0, 0.3)]
+            {
+                let i = ieval.eval_xy([x, x + w], [y, y + w]);
+                for j in 0..=10 {
+                    for k in 0..=10 {
+                        let px = x + w * j as f32 / 10.0;
+                        let py = y + w * k as f32 / 10.0;
+                        let (v, _) = peval.eval(px, py, 0.0, &[]).unwrap();
+                        assert!(
+                            v >= i.lower() - 1e-5 && v <= i.upper() + 1e-5,
+                            "{v} at ({px}, {py}) is outside {i:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Import of 2D shapes from SVG path data
+//!
+//! The [`path`] function parses the contents of an SVG path's `d` attribute
+//! (e.g. `"M 0 0 L 1 0 L 0 1 Z"`) and converts it into a distance field: the
+//! exact distance to the (flattened) path, negative inside and positive
+//! outside.  All path commands are supported, in both absolute and relative
+//! forms; curves and elliptical arcs are approximated by line segments.
+//!
+//! SVG uses a coordinate system where `+Y` points down, so the `Y` axis is
+//! flipped during import; this means that artwork appears upright when
+//! rendered by Fidget.
+//!
+//! ```
+//! use fidget::{context::Context, svg};
+//!
+//! let mut ctx = Context::new();
+//! let shape = svg::path(
+//!     &mut ctx,
+//!     "M -1 -1 H 1 V 1 H -1 Z",
+//!     svg::FillRule::NonZero,
+//! )?;
+//!
+//! let tape = ctx.get_tape::<fidget::vm::Eval>(shape)?;
+//! let eval = tape.new_point_evaluator();
+//! let (v, _) = eval.eval(0.0, 0.5, 0.0, &[])?;
+//! assert!((v + 0.5).abs() < 1e-6);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{Context, Node},
+    outline::Outline,
+    Error,
+};
+
+pub use crate::outline::FillRule;
+
+/// Number of line segments used to approximate a full turn of an elliptical arc
+const ARC_STEPS: usize = 32;
+
+/// Builds a distance field from SVG path data
+///
+/// Every subpath is treated as closed, as when filling a path.  If the path
+/// contains no filled regions, then the result is a constant (positive)
+/// infinity.
+///
+/// Returns [`Error::BadPathData`] if the path data is malformed.
+pub fn path(
+    ctx: &mut Context,
+    data: &str,
+    rule: FillRule,
+) -> Result<Node, Error> {
+    let mut out = Outline::new([1.0, -1.0], [0.0; 2]);
+    Parser::new(data).run(&mut out)?;
+    out.build(ctx, rule)
+}
+
+/// Tokenizer for SVG path data
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            data: data.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::BadPathData(self.pos, msg.to_owned())
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    /// Skips whitespace and (at most one) comma
+    fn skip_separators(&mut self) {
+        let mut comma = false;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || (c == b',' && !comma) {
+                comma |= c == b',';
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Returns the next command letter, if present
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let c = self.peek().filter(|c| c.is_ascii_alphabetic())?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn number(&mut self) -> Result<f64, Error> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let s = p.pos;
+            while p.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+                p.pos += 1;
+            }
+            p.pos - s
+        };
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut n = digits(self);
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            n += digits(self);
+        }
+        if n == 0 {
+            self.pos = start;
+            return Err(self.error("expected number"));
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if digits(self) == 0 {
+                return Err(self.error("expected exponent"));
+            }
+        }
+        // The slice is ASCII, so this can't fail
+        let s = std::str::from_utf8(&self.data[start..self.pos]).unwrap();
+        s.parse().map_err(|_| {
+            self.pos = start;
+            self.error("invalid number")
+        })
+    }
+
+    fn point(&mut self) -> Result<[f64; 2], Error> {
+        Ok([self.number()?, self.number()?])
+    }
+
+    /// Parses an arc flag, which may be packed without separators
+    fn flag(&mut self) -> Result<bool, Error> {
+        self.skip_separators();
+        let out = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error("expected flag")),
+        };
+        self.pos += 1;
+        Ok(out)
+    }
+
+    /// Parses the entire path, sending commands to the given outline
+    fn run(&mut self, out: &mut Outline) -> Result<(), Error> {
+        let mut cmd = None;
+        let mut pos = [0.0; 2];
+        let mut start = [0.0; 2];
+        let mut open = false;
+
+        // Previous control point, for smooth curve commands
+        let mut prev_cubic: Option<[f64; 2]> = None;
+        let mut prev_quad: Option<[f64; 2]> = None;
+
+        loop {
+            let c = match self.command() {
+                Some(c) => c,
+                None if self.peek().is_none() => break,
+                None => cmd.ok_or_else(|| self.error("expected command"))?,
+            };
+            let relative = c.is_ascii_lowercase();
+            let base = if relative { pos } else { [0.0; 2] };
+            let abs = |p: [f64; 2]| [p[0] + base[0], p[1] + base[1]];
+
+            // Drawing after a closepath starts a new subpath at the same point
+            if !open && !matches!(c, b'M' | b'm' | b'Z' | b'z') {
+                out.move_to(pos[0], pos[1]);
+                open = true;
+            }
+
+            let (mut cubic, mut quad) = (None, None);
+            match c.to_ascii_uppercase() {
+                b'M' => {
+                    pos = abs(self.point()?);
+                    start = pos;
+                    out.move_to(pos[0], pos[1]);
+                    open = true;
+                    // Subsequent coordinate pairs are implicit line commands
+                    cmd = Some(if relative { b'l' } else { b'L' });
+                }
+                b'L' => {
+                    pos = abs(self.point()?);
+                    out.line_to(pos[0], pos[1]);
+                }
+                b'H' => {
+                    pos[0] = self.number()? + base[0];
+                    out.line_to(pos[0], pos[1]);
+                }
+                b'V' => {
+                    pos[1] = self.number()? + base[1];
+                    out.line_to(pos[0], pos[1]);
+                }
+                u @ (b'C' | b'S') => {
+                    let c1 = if u == b'C' {
+                        abs(self.point()?)
+                    } else {
+                        reflect(prev_cubic, pos)
+                    };
+                    let c2 = abs(self.point()?);
+                    let end = abs(self.point()?);
+                    out.cubic_to(c1[0], c1[1], c2[0], c2[1], end[0], end[1]);
+                    cubic = Some(c2);
+                    pos = end;
+                }
+                u @ (b'Q' | b'T') => {
+                    let c1 = if u == b'Q' {
+                        abs(self.point()?)
+                    } else {
+                        reflect(prev_quad, pos)
+                    };
+                    let end = abs(self.point()?);
+                    out.quad_to(c1[0], c1[1], end[0], end[1]);
+                    quad = Some(c1);
+                    pos = end;
+                }
+                b'A' => {
+                    let rx = self.number()?;
+                    let ry = self.number()?;
+                    let angle = self.number()?;
+                    let large_arc = self.flag()?;
+                    let sweep = self.flag()?;
+                    let end = abs(self.point()?);
+                    arc(out, pos, [rx, ry], angle, large_arc, sweep, end);
+                    pos = end;
+                }
+                b'Z' => {
+                    out.close();
+                    open = false;
+                    pos = start;
+                    // Coordinates can't follow a closepath command
+                    cmd = None;
+                }
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("unknown command"));
+                }
+            }
+            if !matches!(c, b'M' | b'm' | b'Z' | b'z') {
+                cmd = Some(c);
+            }
+            prev_cubic = cubic;
+            prev_quad = quad;
+        }
+        Ok(())
+    }
+}
+
+/// Reflects a control point about the current position
+///
+/// If there is no previous control point, returns the current position.
+fn reflect(ctrl: Option<[f64; 2]>, pos: [f64; 2]) -> [f64; 2] {
+    match ctrl {
+        Some(c) => [2.0 * pos[0] - c[0], 2.0 * pos[1] - c[1]],
+        None => pos,
+    }
+}
+
+/// Adds an elliptical arc to the outline, as line segments
+///
+/// The arc is converted from endpoint to center parameterization, following
+/// the SVG specification's implementation notes (section F.6.5 and F.6.6).
+fn arc(
+    out: &mut Outline,
+    p0: [f64; 2],
+    radii: [f64; 2],
+    angle: f64,
+    large_arc: bool,
+    sweep: bool,
+    p1: [f64; 2],
+) {
+    if p0 == p1 {
+        return;
+    }
+    let (mut rx, mut ry) = (radii[0].abs(), radii[1].abs());
+    if rx == 0.0 || ry == 0.0 {
+        return out.line_to(p1[0], p1[1]);
+    }
+    let (sin, cos) = angle.to_radians().sin_cos();
+
+    // Midpoint between the endpoints, in the ellipse's frame
+    let dx = (p0[0] - p1[0]) / 2.0;
+    let dy = (p0[1] - p1[1]) / 2.0;
+    let x1 = cos * dx + sin * dy;
+    let y1 = -sin * dx + cos * dy;
+
+    // Scale up radii which are too small to reach between the endpoints
+    let lambda = (x1 / rx).powi(2) + (y1 / ry).powi(2);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = (rx * ry).powi(2) - (rx * y1).powi(2) - (ry * x1).powi(2);
+    let den = (rx * y1).powi(2) + (ry * x1).powi(2);
+    let mut k = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+        k = -k;
+    }
+    let cx1 = k * rx * y1 / ry;
+    let cy1 = -k * ry * x1 / rx;
+    let cx = cos * cx1 - sin * cy1 + (p0[0] + p1[0]) / 2.0;
+    let cy = sin * cx1 + cos * cy1 + (p0[1] + p1[1]) / 2.0;
+
+    let theta = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+    let end = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx);
+    let mut delta = (end - theta) % std::f64::consts::TAU;
+    if sweep && delta < 0.0 {
+        delta += std::f64::consts::TAU;
+    } else if !sweep && delta > 0.0 {
+        delta -= std::f64::consts::TAU;
+    }
+
+    let n = ((delta.abs() / std::f64::consts::TAU * ARC_STEPS as f64).ceil()
+        as usize)
+        .max(1);
+    for i in 1..n {
+        let t = theta + delta * i as f64 / n as f64;
+        let (ts, tc) = t.sin_cos();
+        out.line_to(
+            cx + rx * tc * cos - ry * ts * sin,
+            cy + rx * tc * sin + ry * ts * cos,
+        );
+    }
+    out.line_to(p1[0], p1[1]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(d: &str, rule: FillRule, pts: &[[f32; 2]]) -> Vec<f32> {
+        let mut ctx = Context::new();
+        let shape = path(&mut ctx, d, rule).unwrap();
+        let tape = ctx.get_tape::<crate::vm::Eval>(shape).unwrap();
+        let eval = tape.new_point_evaluator();
+        pts.iter()
+            .map(|&[x, y]| eval.eval(x, y, 0.0, &[]).unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn test_fill_rules() {
+        // Outer square with an inner square wound in the same direction
+        let d = "M -2 -2 L 2 -2 L 2 2 L -2 2 Z M -1 -1 L 1 -1 L 1 1 L -1 1 Z";
+        let pts = [[0.0, 0.0], [1.5, 0.0], [3.0, 0.0]];
+
+        let v = eval(d, FillRule::EvenOdd, &pts);
+        assert!((v[0] - 1.0).abs() < 1e-6);
+        assert!((v[1] + 0.5).abs() < 1e-6);
+        assert!((v[2] - 1.0).abs() < 1e-6);
+
+        let v = eval(d, FillRule::NonZero, &pts);
+        assert!((v[0] + 1.0).abs() < 1e-6);
+        assert!((v[1] + 0.5).abs() < 1e-6);
+        assert!((v[2] - 1.0).abs() < 1e-6);
+
+        // Reversing the inner square cuts a hole with either rule
+        let d = "M -2 -2 L 2 -2 L 2 2 L -2 2 Z M -1 -1 L -1 1 L 1 1 L 1 -1 Z";
+        let v = eval(d, FillRule::NonZero, &pts);
+        assert!((v[0] - 1.0).abs() < 1e-6);
+        assert!((v[1] + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_relative() {
+        // Implicit lineto after moveto, packed numbers, and H / V commands
+        let d = "m1,1 2-0 v2h-2z m5 0 h1v1h-1z";
+        let pts = [[2.0, -2.0], [2.0, -0.5], [6.5, -1.5], [4.0, -2.0]];
+        let v = eval(d, FillRule::NonZero, &pts);
+        assert!((v[0] + 1.0).abs() < 1e-6);
+        assert!((v[1] - 0.5).abs() < 1e-6);
+        assert!((v[2] + 0.5).abs() < 1e-6);
+        assert!((v[3] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_curves() {
+        // A circle of radius 1, drawn with two arcs (with packed flags)
+        let d = "M 1 0 A 1 1 0 00-1 0 a 1 1 0 0 0 2 0 z";
+        let pts = [[0.0, 0.0], [0.0, 2.0], [0.0, -0.5]];
+        let v = eval(d, FillRule::NonZero, &pts);
+        assert!((v[0] + 1.0).abs() < 0.01);
+        assert!((v[1] - 1.0).abs() < 0.01);
+        assert!((v[2] + 0.5).abs() < 0.01);
+
+        // A lens made of smooth cubic curves
+        let d = "M 0 0 C 0 -1 2 -1 2 0 S 0 1 0 0 Z";
+        let v = eval(d, FillRule::NonZero, &[[1.0, 0.0], [1.0, 1.0]]);
+        assert!(v[0] < 0.0);
+        assert!(v[1] > 0.0);
+
+        // A block with a wavy top, made of smooth quadratic curves
+        let d = "M 3 0 Q 4 -1 5 0 T 7 0 L 7 1 L 3 1 Z";
+        let pts = [[4.0, -0.5], [6.0, -0.9], [6.0, -0.2], [4.0, 0.8]];
+        let v = eval(d, FillRule::NonZero, &pts);
+        assert!(v[0] < 0.0);
+        assert!(v[1] < 0.0);
+        assert!(v[2] > 0.0);
+        assert!(v[3] > 0.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut ctx = Context::new();
+        for (d, pos) in [
+            ("M 0 0 L 1", 9),
+            ("M 0 0 X 1 1", 6),
+            ("1 2", 0),
+            ("M 0 0 L 1 1 Z 2 2", 14),
+            ("M 0 0 A 1 1 0 2 0 1 1", 14),
+            ("M 0 0 L 1e 2", 10),
+        ] {
+            match path(&mut ctx, d, FillRule::NonZero) {
+                Err(Error::BadPathData(p, _)) => assert_eq!(p, pos, "{d}"),
+                r => panic!("unexpected result {r:?} for {d}"),
+            }
+        }
+        let empty = path(&mut ctx, "", FillRule::NonZero).unwrap();
+        assert_eq!(ctx.const_value(empty).unwrap(), Some(f64::INFINITY));
+    }
+}
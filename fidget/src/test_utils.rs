@@ -0,0 +1,248 @@
+//! Differential testing of evaluator families
+//!
+//! This module generates random expressions and checks that two evaluator
+//! families (e.g. the [`vm`](crate::vm) interpreter and the
+//! [`jit`](crate::jit) compiler) agree when evaluating them, for point,
+//! interval, float slice, and gradient slice evaluation.  It also checks that
+//! each family's interval results enclose its point results.
+//!
+//! ```
+//! use fidget::{test_utils, vm};
+//!
+//! // Check 16 random expressions, using seeds 0 through 15
+//! test_utils::compare_families::<vm::Eval, vm::Eval>(0, 16);
+//! ```
+//!
+//! Failures panic with the seed of the offending expression, which can be
+//! rebuilt for debugging by passing `Rng::new(seed)` and [`EXPR_DEPTH`] to
+//! [`random_expr`].
+use crate::{
+    context::{Context, Node},
+    eval::{types::Interval, Family, Tape},
+    gen,
+};
+
+pub use crate::gen::Rng;
+
+/// Number of sample points used for each expression
+const SAMPLES: usize = 32;
+
+/// Maximum depth of expressions built by [`compare_families`]
+pub const EXPR_DEPTH: usize = 6;
+
+/// Relative tolerance when comparing results from two families
+///
+/// Families are free to reorder floating-point operations (e.g. in gradient
+/// calculations), so results may differ by a few ulps.
+const TOLERANCE: f32 = 1e-5;
+
+/// Builds a random expression with at most `depth` levels of operations
+///
+/// This uses the default [`gen::Settings`], so leaves are `x`, `y`, `z`, or a
+/// constant in the range `-2..2`.
+pub fn random_expr(ctx: &mut Context, rng: &mut Rng, depth: usize) -> Node {
+    let settings = gen::Settings {
+        depth,
+        ..Default::default()
+    };
+    gen::random_expr(ctx, rng, &settings).unwrap()
+}
+
+/// Checks whether two values are equal within a relative tolerance
+///
+/// `NaN` is considered equal to itself, and infinities must match exactly.
+pub fn approx_eq(a: f32, b: f32, tol: f32) -> bool {
+    if a.is_nan() || b.is_nan() {
+        a.is_nan() && b.is_nan()
+    } else if a.is_infinite() || b.is_infinite() {
+        a == b
+    } else {
+        (a - b).abs() <= tol * a.abs().max(b.abs()).max(1.0)
+    }
+}
+
+/// Returns a random interval within `-2..2`
+fn random_interval(rng: &mut Rng) -> Interval {
+    let a = rng.range(-2.0, 2.0);
+    let b = rng.range(-2.0, 2.0);
+    Interval::new(a.min(b), a.max(b))
+}
+
+/// Checks that families `A` and `B` agree when evaluating `node`
+///
+/// Evaluation uses random sample points (and intervals) drawn from `rng`.
+///
+/// # Panics
+/// If the families disagree, or if either family's interval results fail to
+/// enclose its point results.
+pub fn check_agreement<A: Family, B: Family>(
+    ctx: &Context,
+    node: Node,
+    rng: &mut Rng,
+) {
+    let ta = ctx.get_tape::<A>(node).unwrap();
+    let tb = ctx.get_tape::<B>(node).unwrap();
+
+    let mut xs = [0.0; SAMPLES];
+    let mut ys = [0.0; SAMPLES];
+    let mut zs = [0.0; SAMPLES];
+    for i in 0..SAMPLES {
+        xs[i] = rng.range(-2.0, 2.0);
+        ys[i] = rng.range(-2.0, 2.0);
+        zs[i] = rng.range(-2.0, 2.0);
+    }
+
+    // Point and float slice evaluation
+    let pa = ta.new_point_evaluator();
+    let pb = tb.new_point_evaluator();
+    let fa = ta
+        .new_float_slice_evaluator()
+        .eval(&xs, &ys, &zs, &[])
+        .unwrap();
+    let fb = tb
+        .new_float_slice_evaluator()
+        .eval(&xs, &ys, &zs, &[])
+        .unwrap();
+    for i in 0..SAMPLES {
+        let (x, y, z) = (xs[i], ys[i], zs[i]);
+        let a = pa.eval(x, y, z, &[]).unwrap().0;
+        let b = pb.eval(x, y, z, &[]).unwrap().0;
+        let check = |name: &str, va: f32, vb: f32| {
+            assert!(
+                approx_eq(va, vb, TOLERANCE),
+                "{name} mismatch at ({x}, {y}, {z}): {va} != {vb}"
+            );
+        };
+        check("point", a, b);
+        check("float slice (A)", a, fa[i]);
+        check("float slice (B)", b, fb[i]);
+    }
+
+    // Gradient slice evaluation
+    let ga = ta
+        .new_grad_slice_evaluator()
+        .eval(&xs, &ys, &zs, &[])
+        .unwrap();
+    let gb = tb
+        .new_grad_slice_evaluator()
+        .eval(&xs, &ys, &zs, &[])
+        .unwrap();
+    for (i, (a, b)) in ga.iter().zip(&gb).enumerate() {
+        // Derivatives are meaningless if the value is NaN
+        let ok = (a.v.is_nan() && b.v.is_nan())
+            || (approx_eq(a.v, b.v, TOLERANCE)
+                && approx_eq(a.dx, b.dx, TOLERANCE)
+                && approx_eq(a.dy, b.dy, TOLERANCE)
+                && approx_eq(a.dz, b.dz, TOLERANCE));
+        assert!(
+            ok,
+            "gradient mismatch at ({}, {}, {}): {a:?} != {b:?}",
+            xs[i], ys[i], zs[i]
+        );
+    }
+
+    // Interval evaluation
+    let ia = ta.new_interval_evaluator();
+    let ib = tb.new_interval_evaluator();
+    for _ in 0..SAMPLES {
+        let (x, y, z) = (
+            random_interval(rng),
+            random_interval(rng),
+            random_interval(rng),
+        );
+        let a = ia.eval(x, y, z, &[]).unwrap().0;
+        let b = ib.eval(x, y, z, &[]).unwrap().0;
+        assert!(
+            approx_eq(a.lower(), b.lower(), TOLERANCE)
+                && approx_eq(a.upper(), b.upper(), TOLERANCE),
+            "interval mismatch at ({x}, {y}, {z}): {a} != {b}"
+        );
+    }
+
+    check_enclosure(&ta, rng);
+    check_enclosure(&tb, rng);
+}
+
+/// Checks that interval results enclose point results within the interval
+///
+/// The check uses [conservative intervals](Tape::with_conservative_intervals),
+/// so rounding error can't produce false positives.  Points which evaluate to
+/// `NaN` and intervals containing `NaN` are skipped.
+///
+/// # Panics
+/// If a point result is outside of the corresponding interval result
+pub fn check_enclosure<F: Family>(tape: &Tape<F>, rng: &mut Rng) {
+    let tape = tape.clone().with_conservative_intervals(true);
+    let ieval = tape.new_interval_evaluator();
+    let peval = tape.new_point_evaluator();
+    for _ in 0..SAMPLES {
+        let (x, y, z) = (
+            random_interval(rng),
+            random_interval(rng),
+            random_interval(rng),
+        );
+        let i = ieval.eval(x, y, z, &[]).unwrap().0;
+        if i.has_nan() {
+            continue;
+        }
+        for _ in 0..4 {
+            let px = rng.range(x.lower(), x.upper());
+            let py = rng.range(y.lower(), y.upper());
+            let pz = rng.range(z.lower(), z.upper());
+            let p = peval.eval(px, py, pz, &[]).unwrap().0;
+            assert!(
+                p.is_nan() || (p >= i.lower() && p <= i.upper()),
+                "{p} at ({px}, {py}, {pz}) is outside of {i}, \
+                 evaluated on ({x}, {y}, {z})"
+            );
+        }
+    }
+}
+
+/// Generates `count` random expressions and checks each one with
+/// [`check_agreement`]
+///
+/// Each expression uses its own seed, counting up from `seed`.
+///
+/// # Panics
+/// If any check fails; the panic message includes the seed of the failing
+/// expression.
+pub fn compare_families<A: Family, B: Family>(seed: u64, count: usize) {
+    for seed in (seed..).take(count) {
+        let mut rng = Rng::new(seed);
+        let mut ctx = Context::new();
+        let node = random_expr(&mut ctx, &mut rng, EXPR_DEPTH);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            check_agreement::<A, B>(&ctx, node, &mut rng)
+        }));
+        if let Err(e) = r {
+            let msg = e
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| e.downcast_ref::<&str>().copied())
+                .unwrap_or("unknown error");
+            panic!("expression with seed {seed} failed: {msg}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vm_vs_vm() {
+        compare_families::<crate::vm::Eval, crate::vm::Eval>(0, 256);
+    }
+
+    #[test]
+    fn test_vm_vs_threaded() {
+        compare_families::<crate::vm::Eval, crate::vm::Threaded>(0, 1024);
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_vm_vs_jit() {
+        compare_families::<crate::vm::Eval, crate::jit::Eval>(0, 1024);
+    }
+}
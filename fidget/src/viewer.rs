@@ -0,0 +1,485 @@
+//! GUI-agnostic helpers for interactive viewers
+//!
+//! A GUI front-end (e.g. using `egui` or `winit`) should only have to worry
+//! about windows and input handling.  This module implements the other half of
+//! an interactive render loop: the front-end builds a [`FrameRequest`] whenever
+//! the camera or render mode changes, then passes it to a [`Viewer`] and gets
+//! back a [`FrameResult`] with RGBA pixels ready for upload to a texture.
+//!
+//! The viewer keeps its state between frames: tapes are built once per shape,
+//! rendering runs on a persistent thread pool, and 2D frames share a
+//! [`RenderCache`] of per-tile interval results and simplified tapes (keyed by
+//! each tile's region), so that revisiting a view or switching between 2D
+//! modes skips most of the work.  3D frames are rendered from scratch (on the
+//! same thread pool) whenever the camera changes.
+//!
+//! ```
+//! use fidget::{
+//!     context::Context,
+//!     viewer::{FrameMode, FrameRequest, Viewer},
+//!     vm,
+//! };
+//!
+//! let mut ctx = Context::new();
+//! let (x, y) = (ctx.x(), ctx.y());
+//! let r = ctx.hypot(x, y)?;
+//! let shape = ctx.sub(r, 0.5)?;
+//! let mut viewer = Viewer::<vm::Eval>::new();
+//! viewer.set_shapes(&ctx, &[(shape, [255, 0, 0])])?;
+//!
+//! let req = FrameRequest {
+//!     size: 32,
+//!     mode: FrameMode::Bitmap,
+//!     ..FrameRequest::default()
+//! };
+//! let frame = viewer.render(&req);
+//! assert_eq!(frame.pixels.len(), 32 * 32);
+//! assert_eq!(frame.pixels[16 * 32 + 16], [255, 0, 0, 255]); // center
+//! assert_eq!(frame.pixels[0], [0, 0, 0, 255]); // corner
+//!
+//! // Rendering the same frame again is free
+//! let frame = viewer.render(&req);
+//! assert!(frame.stats.cached);
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::{Context, Node},
+    eval::{cache::CacheStats, Family, Tape},
+    render::{
+        cache::RenderCache, render2d_cached_in_pool, render3d_in_pool,
+        BitRenderMode, Camera, DebugRenderMode, RenderConfig, SdfRenderMode,
+    },
+    Error,
+};
+use nalgebra::{Matrix3, Transform2, Transform3};
+
+/// Rendering mode for a single frame
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum FrameMode {
+    /// 2D rendering, filling each shape with its color
+    #[default]
+    Bitmap,
+    /// 2D rendering of the first shape's distance field
+    Sdf,
+    /// 2D rendering of the first shape, showing the interval subdivision
+    Debug,
+    /// 3D rendering, coloring each shape by its surface normals
+    Shaded,
+    /// 3D rendering, showing depth as brightness
+    Heightmap,
+}
+
+impl FrameMode {
+    /// Checks whether this is a 3D rendering mode
+    pub fn is_3d(&self) -> bool {
+        matches!(self, FrameMode::Shaded | FrameMode::Heightmap)
+    }
+}
+
+/// Parameters for a single frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameRequest {
+    /// Transform from the `±1` view cube into model space
+    ///
+    /// 2D modes render the `Z = 0` plane of this transform; the `Z` row and
+    /// column are ignored.  For 3D modes, this is typically built from a
+    /// [`Camera`] (see [`FrameRequest::with_camera`]).
+    pub transform: Transform3<f32>,
+
+    /// Image size, in pixels (for a square output image)
+    pub size: usize,
+
+    /// Rendering mode
+    pub mode: FrameMode,
+}
+
+impl Default for FrameRequest {
+    fn default() -> Self {
+        Self {
+            transform: Transform3::identity(),
+            size: 512,
+            mode: FrameMode::default(),
+        }
+    }
+}
+
+impl FrameRequest {
+    /// Sets the transform from a camera
+    pub fn with_camera(self, camera: &Camera) -> Self {
+        Self {
+            transform: camera.transform(),
+            ..self
+        }
+    }
+}
+
+/// Statistics about a rendered frame
+#[derive(Copy, Clone, Debug)]
+pub struct FrameStats {
+    /// Time spent rendering (excluding tape construction)
+    pub render_time: std::time::Duration,
+
+    /// Total length of all tapes which were rendered
+    pub tape_len: usize,
+
+    /// Set if this frame was returned from the cache without rendering
+    pub cached: bool,
+}
+
+/// Output from a single frame
+#[derive(Clone, Debug)]
+pub struct FrameResult {
+    /// RGBA pixels, in row-major order starting from the top-left corner
+    pub pixels: Vec<[u8; 4]>,
+
+    /// Statistics about the rendering process
+    pub stats: FrameStats,
+}
+
+/// Stateful renderer for interactive viewers
+///
+/// The viewer stores a tape for each shape, so they're only built once, and
+/// caches the most recent frame, so redundant requests (which are common in
+/// GUI event loops) return immediately.  Rendering itself is parallelized
+/// across a persistent pool of [`threads`](Self::set_threads) worker threads.
+///
+/// 2D frames use a [`RenderCache`], so tiles which were evaluated in an
+/// earlier frame (with the same region and tape) skip interval evaluation and
+/// reuse their simplified tapes.  The cache is shared between shapes and
+/// kept when shapes are replaced, because it's keyed by content hash; it's
+/// cleared once it grows past [`Viewer::CACHE_LIMIT`] entries.
+pub struct Viewer<I: Family> {
+    shapes: Vec<(Tape<I>, [u8; 3])>,
+    threads: usize,
+    pool: rayon::ThreadPool,
+    cache: RenderCache,
+    last: Option<(FrameRequest, FrameResult)>,
+}
+
+impl<I: Family> Default for Viewer<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Family> Viewer<I> {
+    /// Maximum number of tiles in the render cache
+    pub const CACHE_LIMIT: usize = 1 << 20;
+
+    /// Builds a new viewer with no shapes
+    pub fn new() -> Self {
+        Self {
+            shapes: vec![],
+            threads: 8,
+            pool: build_pool(8),
+            cache: RenderCache::new(),
+            last: None,
+        }
+    }
+
+    /// Sets the number of worker threads used during rendering (8 by default)
+    ///
+    /// The thread pool is rebuilt if the number changes.
+    pub fn set_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        if threads != self.threads {
+            self.threads = threads;
+            self.pool = build_pool(threads);
+            self.last = None;
+        }
+    }
+
+    /// Returns hit and miss counts for the render cache
+    ///
+    /// These accumulate across frames; a hit is a tile whose interval
+    /// evaluation (and simplification) was skipped.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Replaces the set of shapes, each of which is drawn in a given color
+    ///
+    /// Shapes are drawn in order, so later shapes are drawn on top of earlier
+    /// ones in 2D modes.
+    pub fn set_shapes(
+        &mut self,
+        ctx: &Context,
+        shapes: &[(Node, [u8; 3])],
+    ) -> Result<(), Error> {
+        self.shapes = shapes
+            .iter()
+            .map(|(node, color)| Ok((ctx.get_tape(*node)?, *color)))
+            .collect::<Result<_, Error>>()?;
+        self.last = None;
+        Ok(())
+    }
+
+    /// Removes all shapes from the viewer, and clears the render cache
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+        self.cache.clear();
+        self.last = None;
+    }
+
+    /// Renders a frame, returning a cached result if possible
+    pub fn render(&mut self, req: &FrameRequest) -> FrameResult {
+        if let Some((prev, out)) = &self.last {
+            if prev == req {
+                let mut out = out.clone();
+                out.stats.cached = true;
+                return out;
+            }
+        }
+        let out = self.render_uncached(req);
+        self.last = Some((req.clone(), out.clone()));
+        out
+    }
+
+    fn render_uncached(&mut self, req: &FrameRequest) -> FrameResult {
+        let start = std::time::Instant::now();
+        let mut pixels = vec![[0, 0, 0, 255]; req.size.pow(2)];
+        if req.mode.is_3d() {
+            self.render_3d(req, &mut pixels);
+        } else {
+            self.render_2d(req, &mut pixels);
+        }
+        FrameResult {
+            pixels,
+            stats: FrameStats {
+                render_time: start.elapsed(),
+                tape_len: self.shapes.iter().map(|(t, _)| t.len()).sum(),
+                cached: false,
+            },
+        }
+    }
+
+    fn render_2d(&mut self, req: &FrameRequest, pixels: &mut [[u8; 4]]) {
+        if self.cache.len() > Self::CACHE_LIMIT {
+            self.cache.clear();
+        }
+
+        // Drop the Z row and column from the 3D transform
+        let m = req.transform.matrix();
+        let mat = Matrix3::from_fn(|i, j| {
+            let i = if i == 2 { 3 } else { i };
+            let j = if j == 2 { 3 } else { j };
+            m[(i, j)]
+        });
+        let config = RenderConfig {
+            image_size: req.size,
+            tile_sizes: I::tile_sizes_2d().to_vec(),
+            threads: self.threads,
+            mat: Transform2::from_matrix_unchecked(mat),
+            exact_boundaries: false,
+        };
+        match req.mode {
+            FrameMode::Bitmap => {
+                for (tape, [r, g, b]) in &self.shapes {
+                    let image = render2d_cached_in_pool(
+                        tape.clone(),
+                        &config,
+                        &BitRenderMode,
+                        &mut self.cache,
+                        &self.pool,
+                    );
+                    for (p, &i) in pixels.iter_mut().zip(&image) {
+                        if i {
+                            *p = [*r, *g, *b, 255];
+                        }
+                    }
+                }
+            }
+            FrameMode::Sdf => {
+                if let Some((tape, _)) = self.shapes.first() {
+                    let image = render2d_cached_in_pool(
+                        tape.clone(),
+                        &config,
+                        &SdfRenderMode,
+                        &mut self.cache,
+                        &self.pool,
+                    );
+                    for (p, [r, g, b]) in pixels.iter_mut().zip(image) {
+                        *p = [r, g, b, 255];
+                    }
+                }
+            }
+            FrameMode::Debug => {
+                if let Some((tape, _)) = self.shapes.first() {
+                    let image = render2d_cached_in_pool(
+                        tape.clone(),
+                        &config,
+                        &DebugRenderMode,
+                        &mut self.cache,
+                        &self.pool,
+                    );
+                    for (p, i) in pixels.iter_mut().zip(&image) {
+                        *p = i.as_debug_color();
+                    }
+                }
+            }
+            FrameMode::Shaded | FrameMode::Heightmap => unreachable!(),
+        }
+    }
+
+    fn render_3d(&self, req: &FrameRequest, pixels: &mut [[u8; 4]]) {
+        let config = RenderConfig {
+            image_size: req.size,
+            tile_sizes: I::tile_sizes_3d().to_vec(),
+            threads: self.threads,
+            mat: req.transform,
+            exact_boundaries: false,
+        };
+
+        // Composite shapes by depth, keeping the nearest surface
+        let mut depth = vec![0; req.size.pow(2)];
+        let mut color = vec![[0u8; 3]; req.size.pow(2)];
+        for (tape, _) in &self.shapes {
+            let (d, c) = render3d_in_pool(tape.clone(), &config, &self.pool);
+            for i in 0..depth.len() {
+                if d[i] > depth[i] {
+                    depth[i] = d[i];
+                    color[i] = c[i];
+                }
+            }
+        }
+
+        match req.mode {
+            FrameMode::Shaded => {
+                for (p, (&d, &[r, g, b])) in
+                    pixels.iter_mut().zip(depth.iter().zip(&color))
+                {
+                    if d != 0 {
+                        *p = [r, g, b, 255];
+                    }
+                }
+            }
+            FrameMode::Heightmap => {
+                let max_depth = depth.iter().max().cloned().unwrap_or(1).max(1);
+                for (p, &d) in pixels.iter_mut().zip(&depth) {
+                    if d != 0 {
+                        let b = (d as u64 * 255 / max_depth as u64) as u8;
+                        *p = [b, b, b, 255];
+                    }
+                }
+            }
+            FrameMode::Bitmap | FrameMode::Sdf | FrameMode::Debug => {
+                unreachable!()
+            }
+        }
+    }
+}
+
+/// Builds a thread pool for rendering
+fn build_pool(threads: usize) -> rayon::ThreadPool {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("could not build thread pool")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_viewer_2d_cache() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let r = ctx.hypot(x, y).unwrap();
+        let circle = ctx.sub(r, 0.5).unwrap();
+
+        let mut viewer = Viewer::<crate::vm::Eval>::new();
+        viewer.set_threads(2);
+        viewer.set_shapes(&ctx, &[(circle, [255; 3])]).unwrap();
+
+        let mut req = FrameRequest {
+            size: 64,
+            mode: FrameMode::Bitmap,
+            ..FrameRequest::default()
+        };
+        let first = viewer.render(&req);
+        assert_eq!(viewer.cache_stats().hits, 0);
+        let misses = viewer.cache_stats().misses;
+        assert!(misses > 0);
+
+        // Switching modes reuses every tile from the previous frame
+        req.mode = FrameMode::Sdf;
+        let out = viewer.render(&req);
+        assert!(!out.stats.cached);
+        assert_eq!(viewer.cache_stats().hits, misses);
+
+        // Moving the view evaluates new tiles, but moving back doesn't
+        let moved = FrameRequest {
+            transform: Transform3::from_matrix_unchecked(
+                nalgebra::Matrix4::new_translation(&nalgebra::Vector3::new(
+                    0.5, 0.0, 0.0,
+                )),
+            ),
+            ..req.clone()
+        };
+        viewer.render(&moved);
+        let misses = viewer.cache_stats().misses;
+        req.mode = FrameMode::Bitmap;
+        let out = viewer.render(&req);
+        assert!(!out.stats.cached);
+        assert_eq!(viewer.cache_stats().misses, misses);
+        assert_eq!(out.pixels, first.pixels);
+
+        viewer.clear();
+        assert!(viewer.cache.is_empty());
+    }
+
+    #[test]
+    fn test_viewer_3d() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let z2 = ctx.square(z).unwrap();
+        let r = ctx.add(x2, y2).unwrap();
+        let r = ctx.add(r, z2).unwrap();
+        let sphere = ctx.sub(r, 0.25).unwrap();
+
+        let mut viewer = Viewer::<crate::vm::Eval>::new();
+        viewer.set_threads(2);
+        viewer.set_shapes(&ctx, &[(sphere, [255; 3])]).unwrap();
+
+        let mut req = FrameRequest {
+            size: 32,
+            mode: FrameMode::Heightmap,
+            ..FrameRequest::default()
+        };
+        let out = viewer.render(&req);
+        assert!(!out.stats.cached);
+        assert_eq!(out.pixels[0], [0, 0, 0, 255]);
+        assert_eq!(out.pixels[16 * 32 + 16], [255, 255, 255, 255]);
+
+        req.mode = FrameMode::Shaded;
+        let out = viewer.render(&req);
+        assert!(!out.stats.cached);
+        assert_eq!(out.pixels[0], [0, 0, 0, 255]);
+        assert_ne!(out.pixels[16 * 32 + 16], [0, 0, 0, 255]);
+        assert!(viewer.render(&req).stats.cached);
+
+        // Changing the camera invalidates the cache
+        let camera = Camera::look_at(
+            nalgebra::Point3::new(2.0, 0.0, 2.0),
+            nalgebra::Point3::origin(),
+            nalgebra::Vector3::y(),
+        )
+        .perspective(1.0);
+        let req = req.with_camera(&camera);
+        let out = viewer.render(&req);
+        assert!(!out.stats.cached);
+        assert_eq!(out.pixels[0], [0, 0, 0, 255]);
+        assert_ne!(out.pixels[16 * 32 + 16], [0, 0, 0, 255]);
+
+        viewer.clear();
+        let out = viewer.render(&req);
+        assert!(!out.stats.cached);
+        assert!(out.pixels.iter().all(|p| *p == [0, 0, 0, 255]));
+    }
+}
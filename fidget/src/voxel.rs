@@ -0,0 +1,474 @@
+//! Sampling shapes into dense and sparse voxel grids
+//!
+//! The grid is split into cubic bricks.  Each brick is checked with interval
+//! arithmetic first; bricks which are entirely inside or outside the shape are
+//! skipped, and the rest are sampled (with a tape simplified for the brick)
+//! at the center of every voxel.
+//!
+//! Results are stored as flat arrays (in X-major order, i.e. X varies
+//! fastest), so they can be handed to other tools without conversion.
+//!
+//! ```
+//! use fidget::{context::Context, voxel, vm};
+//!
+//! let mut ctx = Context::new();
+//! let x = ctx.x();
+//! let y = ctx.y();
+//! let z = ctx.z();
+//! let x2 = ctx.square(x)?;
+//! let y2 = ctx.square(y)?;
+//! let z2 = ctx.square(z)?;
+//! let r = ctx.add(x2, y2)?;
+//! let r = ctx.add(r, z2)?;
+//! let r = ctx.sqrt(r)?;
+//! let sphere = ctx.sub(r, 0.5)?;
+//! let tape = ctx.get_tape::<vm::Eval>(sphere)?;
+//!
+//! let settings = voxel::Settings {
+//!     size: [32; 3],
+//!     ..Default::default()
+//! };
+//! let dense = voxel::dense(&tape, &settings)?;
+//! assert!(dense.is_inside(16, 16, 16));
+//! assert!(!dense.is_inside(0, 0, 0));
+//!
+//! let sparse = voxel::sparse(&tape, &settings)?;
+//! assert!(sparse.values.len() < dense.values.len());
+//! assert!(sparse.is_inside(16, 16, 16));
+//! # Ok::<(), fidget::Error>(())
+//! ```
+use crate::{
+    context::BoundingBox,
+    eval::{types::Interval, Family, IntervalEval, Tape},
+    Error,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Settings when sampling a voxel grid
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// Number of voxels along each axis
+    pub size: [usize; 3],
+
+    /// Region of space covered by the grid; ±1 on every axis by default
+    pub region: BoundingBox,
+
+    /// Number of voxels along each side of a brick; 8 by default
+    ///
+    /// Bricks are the unit of interval pruning (and of storage in a
+    /// [`SparseGrid`]).
+    pub brick_size: usize,
+
+    /// Number of threads to use; 8 by default
+    pub threads: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            size: [128; 3],
+            region: BoundingBox::new([-1.0; 3], [1.0; 3]),
+            brick_size: 8,
+            threads: 8,
+        }
+    }
+}
+
+impl Settings {
+    /// Returns the number of bricks along each axis
+    fn brick_counts(&self) -> [usize; 3] {
+        self.size.map(|s| s.div_ceil(self.brick_size))
+    }
+
+    /// Returns the position of the center of the given voxel on one axis
+    fn center(&self, axis: usize, i: usize) -> f32 {
+        let lo = self.region.lower[axis];
+        let hi = self.region.upper[axis];
+        let t = (i as f64 + 0.5) / self.size[axis] as f64;
+        (lo + (hi - lo) * t) as f32
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if self.brick_size == 0 || !self.region.is_finite() {
+            Err(Error::BadVoxelSettings)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Result of sampling a single brick
+enum Brick {
+    /// The brick is entirely outside the shape; the value is a lower bound
+    Empty(f32),
+    /// The brick is entirely inside the shape; the value is an upper bound
+    Filled(f32),
+    /// Samples at every voxel in the brick (including voxels past the edge
+    /// of the grid, if this is a partial brick)
+    Sampled(Vec<f32>),
+}
+
+/// Per-thread evaluation state
+struct Worker<'a, F: Family> {
+    settings: &'a Settings,
+    tape: &'a Tape<F>,
+    interval: IntervalEval<F>,
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    zs: Vec<f32>,
+}
+
+impl<'a, F: Family> Worker<'a, F> {
+    fn new(tape: &'a Tape<F>, settings: &'a Settings) -> Self {
+        Self {
+            settings,
+            tape,
+            interval: tape.new_interval_evaluator(),
+            xs: vec![],
+            ys: vec![],
+            zs: vec![],
+        }
+    }
+
+    /// Evaluates the brick at the given position (in units of bricks)
+    fn run(&mut self, pos: [usize; 3]) -> Result<Brick, Error> {
+        let b = self.settings.brick_size;
+        let [x, y, z] = [0, 1, 2].map(|axis| {
+            Interval::new(
+                self.settings.center(axis, pos[axis] * b),
+                self.settings.center(axis, pos[axis] * b + b - 1),
+            )
+        });
+        let (i, r) = self.interval.eval(x, y, z, &[])?;
+        if !i.has_nan() {
+            if i.lower() > 0.0 {
+                return Ok(Brick::Empty(i.lower()));
+            } else if i.upper() < 0.0 {
+                return Ok(Brick::Filled(i.upper()));
+            }
+        }
+
+        self.xs.clear();
+        self.ys.clear();
+        self.zs.clear();
+        for k in 0..b {
+            for j in 0..b {
+                for i in 0..b {
+                    let s = self.settings;
+                    self.xs.push(s.center(0, pos[0] * b + i));
+                    self.ys.push(s.center(1, pos[1] * b + j));
+                    self.zs.push(s.center(2, pos[2] * b + k));
+                }
+            }
+        }
+        let tape = match r {
+            Some(r) => r.simplify()?,
+            None => self.tape.clone(),
+        };
+        let out = tape.new_float_slice_evaluator().eval(
+            &self.xs,
+            &self.ys,
+            &self.zs,
+            &[],
+        )?;
+        Ok(Brick::Sampled(out))
+    }
+}
+
+/// Evaluates every brick in the grid, returning them in X-major order
+fn eval_bricks<F: Family>(
+    tape: &Tape<F>,
+    settings: &Settings,
+) -> Result<Vec<Brick>, Error> {
+    settings.check()?;
+    let [nx, ny, nz] = settings.brick_counts();
+    let count = nx * ny * nz;
+    let pos = |i: usize| [i % nx, (i / nx) % ny, i / (nx * ny)];
+
+    let threads = settings.threads.min(count).max(1);
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut worker = Worker::new(tape, settings);
+        let mut out = vec![];
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            if i >= count {
+                break Ok(out);
+            }
+            out.push((i, worker.run(pos(i))?));
+        }
+    };
+    let results: Vec<Result<Vec<(usize, Brick)>, Error>> = if threads == 1 {
+        vec![work()]
+    } else {
+        std::thread::scope(|s| {
+            let handles =
+                (0..threads).map(|_| s.spawn(work)).collect::<Vec<_>>();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    };
+
+    let mut bricks = vec![];
+    for r in results {
+        bricks.extend(r?);
+    }
+    bricks.sort_unstable_by_key(|(i, _)| *i);
+    Ok(bricks.into_iter().map(|(_, b)| b).collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Dense grid of samples, storing one value per voxel
+#[derive(Clone, Debug)]
+pub struct DenseGrid {
+    /// Number of voxels along each axis
+    pub size: [usize; 3],
+
+    /// Sample values, in X-major order
+    ///
+    /// Voxels in bricks which were skipped by interval pruning don't contain
+    /// exact samples; instead, they store the interval bound closest to zero
+    /// (which has the correct sign, and is a conservative estimate of the
+    /// distance for distance fields).
+    pub values: Vec<f32>,
+}
+
+impl DenseGrid {
+    /// Returns the value at the given voxel
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[x + self.size[0] * (y + self.size[1] * z)]
+    }
+
+    /// Checks whether the given voxel is inside the shape
+    pub fn is_inside(&self, x: usize, y: usize, z: usize) -> bool {
+        self.get(x, y, z) < 0.0
+    }
+
+    /// Returns a flat occupancy grid, in X-major order
+    pub fn occupancy(&self) -> Vec<bool> {
+        self.values.iter().map(|v| *v < 0.0).collect()
+    }
+}
+
+/// Samples a tape into a [`DenseGrid`]
+///
+/// Returns [`Error::BadVoxelSettings`] if the brick size is zero or the region
+/// isn't finite.
+pub fn dense<F: Family>(
+    tape: &Tape<F>,
+    settings: &Settings,
+) -> Result<DenseGrid, Error> {
+    let bricks = eval_bricks(tape, settings)?;
+    let [nx, ny, _] = settings.brick_counts();
+    let b = settings.brick_size;
+    let size = settings.size;
+    let mut values = vec![0.0; size.iter().product()];
+    for (index, brick) in bricks.iter().enumerate() {
+        let pos = [index % nx, (index / nx) % ny, index / (nx * ny)];
+        for k in 0..b {
+            let z = pos[2] * b + k;
+            for j in 0..b {
+                let y = pos[1] * b + j;
+                for i in 0..b {
+                    let x = pos[0] * b + i;
+                    if x >= size[0] || y >= size[1] || z >= size[2] {
+                        continue;
+                    }
+                    values[x + size[0] * (y + size[1] * z)] = match brick {
+                        Brick::Empty(v) | Brick::Filled(v) => *v,
+                        Brick::Sampled(s) => s[i + b * (j + b * k)],
+                    };
+                }
+            }
+        }
+    }
+    Ok(DenseGrid { size, values })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sparse grid of samples, only storing bricks which contain the surface
+///
+/// Like an OpenVDB tree (with a single level of tiles), the grid is a table of
+/// bricks; each entry is either [`EMPTY`](Self::EMPTY),
+/// [`FILLED`](Self::FILLED), or the index of a brick of samples in
+/// [`values`](Self::values).
+#[derive(Clone, Debug)]
+pub struct SparseGrid {
+    /// Number of voxels along each axis
+    pub size: [usize; 3],
+
+    /// Number of voxels along each side of a brick
+    pub brick_size: usize,
+
+    /// Number of bricks along each axis
+    pub brick_counts: [usize; 3],
+
+    /// Table of bricks, in X-major order
+    pub table: Vec<u32>,
+
+    /// Samples for each sampled brick, in X-major order within the brick
+    ///
+    /// Each brick stores `brick_size³` values; bricks at the edge of the grid
+    /// include samples past the grid's boundary.
+    pub values: Vec<f32>,
+}
+
+impl SparseGrid {
+    /// Table entry for a brick which is entirely outside the shape
+    pub const EMPTY: u32 = u32::MAX;
+
+    /// Table entry for a brick which is entirely inside the shape
+    pub const FILLED: u32 = u32::MAX - 1;
+
+    /// Returns the table entry for the brick containing the given voxel
+    fn entry(&self, x: usize, y: usize, z: usize) -> (u32, usize) {
+        let b = self.brick_size;
+        let [nx, ny, _] = self.brick_counts;
+        let t = self.table[x / b + nx * (y / b + ny * (z / b))];
+        (t, x % b + b * (y % b + b * (z % b)))
+    }
+
+    /// Returns the sampled value at the given voxel
+    ///
+    /// Returns `None` if the voxel is in an empty or filled brick.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<f32> {
+        match self.entry(x, y, z) {
+            (Self::EMPTY | Self::FILLED, _) => None,
+            (t, i) => {
+                let n = self.brick_size.pow(3);
+                Some(self.values[t as usize * n + i])
+            }
+        }
+    }
+
+    /// Checks whether the given voxel is inside the shape
+    pub fn is_inside(&self, x: usize, y: usize, z: usize) -> bool {
+        match self.entry(x, y, z).0 {
+            Self::EMPTY => false,
+            Self::FILLED => true,
+            _ => self.get(x, y, z).unwrap() < 0.0,
+        }
+    }
+
+    /// Returns the number of sampled bricks
+    pub fn sampled_bricks(&self) -> usize {
+        self.values.len() / self.brick_size.pow(3)
+    }
+}
+
+/// Samples a tape into a [`SparseGrid`]
+///
+/// Returns [`Error::BadVoxelSettings`] if the brick size is zero or the region
+/// isn't finite.
+pub fn sparse<F: Family>(
+    tape: &Tape<F>,
+    settings: &Settings,
+) -> Result<SparseGrid, Error> {
+    let bricks = eval_bricks(tape, settings)?;
+    let mut table = Vec::with_capacity(bricks.len());
+    let mut values = vec![];
+    let n = settings.brick_size.pow(3);
+    for brick in bricks {
+        table.push(match brick {
+            Brick::Empty(..) => SparseGrid::EMPTY,
+            Brick::Filled(..) => SparseGrid::FILLED,
+            Brick::Sampled(s) => {
+                values.extend(s);
+                (values.len() / n - 1) as u32
+            }
+        });
+    }
+    Ok(SparseGrid {
+        size: settings.size,
+        brick_size: settings.brick_size,
+        brick_counts: settings.brick_counts(),
+        table,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::Context;
+
+    fn test_voxels<F: Family>() {
+        let mut ctx = Context::new();
+        let x = ctx.x();
+        let y = ctx.y();
+        let z = ctx.z();
+        let x2 = ctx.square(x).unwrap();
+        let y2 = ctx.square(y).unwrap();
+        let z2 = ctx.square(z).unwrap();
+        let r = ctx.add(x2, y2).unwrap();
+        let r = ctx.add(r, z2).unwrap();
+        let r = ctx.sqrt(r).unwrap();
+        let sphere = ctx.sub(r, 0.6).unwrap();
+        let plane = ctx.sub(0.1, x).unwrap();
+        let shape = ctx.max(sphere, plane).unwrap();
+        let tape = ctx.get_tape::<F>(shape).unwrap();
+        let eval = tape.new_point_evaluator();
+
+        for threads in [1, 4] {
+            let settings = Settings {
+                size: [20, 17, 24],
+                brick_size: 4,
+                threads,
+                ..Default::default()
+            };
+            let dense = dense(&tape, &settings).unwrap();
+            let sparse = sparse(&tape, &settings).unwrap();
+            assert_eq!(dense.values.len(), 20 * 17 * 24);
+            assert_eq!(sparse.table.len(), 5 * 5 * 6);
+            assert!(sparse.sampled_bricks() < sparse.table.len());
+            assert!(sparse.table.contains(&SparseGrid::EMPTY));
+
+            for k in 0..24 {
+                for j in 0..17 {
+                    for i in 0..20 {
+                        let p =
+                            [0, 1, 2].map(|a| settings.center(a, [i, j, k][a]));
+                        let v = eval.eval(p[0], p[1], p[2], &[]).unwrap().0;
+                        let d = dense.get(i, j, k);
+                        assert_eq!(v < 0.0, dense.is_inside(i, j, k));
+                        assert_eq!(v < 0.0, sparse.is_inside(i, j, k));
+                        match sparse.get(i, j, k) {
+                            Some(s) => {
+                                assert_eq!(s, v);
+                                assert_eq!(d, v);
+                            }
+                            // Skipped values are bounds on the true value
+                            None if v < 0.0 => assert!(d >= v),
+                            None => assert!(d <= v),
+                        }
+                    }
+                }
+            }
+            assert_eq!(
+                dense.occupancy().iter().filter(|b| **b).count(),
+                dense.values.iter().filter(|v| **v < 0.0).count()
+            );
+        }
+
+        let settings = Settings {
+            brick_size: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            dense(&tape, &settings),
+            Err(Error::BadVoxelSettings)
+        ));
+    }
+
+    #[test]
+    fn test_voxels_vm() {
+        test_voxels::<crate::vm::Eval>();
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn test_voxels_jit() {
+        test_voxels::<crate::jit::Eval>();
+    }
+}
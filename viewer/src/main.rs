@@ -8,8 +8,7 @@ use log::{debug, error, info};
 use nalgebra::{Transform2, Transform3, Vector2, Vector3};
 use notify::Watcher;
 
-use std::{path::Path, error::Error};
-
+use std::{error::Error, path::Path};
 
 #[cfg(feature = "jit")]
 type Eval = fidget::jit::Eval;
@@ -170,6 +169,7 @@ fn render(
                 threads: 8,
 
                 mat,
+                exact_boundaries: false,
             };
             match mode {
                 TwoDMode::Color => {
@@ -233,6 +233,7 @@ fn render(
                 threads: 8,
 
                 mat,
+                exact_boundaries: false,
             };
             let (depth, color) = fidget::render::render3d(tape, &config);
             match mode {